@@ -125,11 +125,14 @@ import platform
 import os
 import onnxruntime
 import glob
+import logging
+
+logger = logging.getLogger(__name__)
 
 path = os.path.dirname(onnxruntime.__file__) + "/capi/"
 
 if path is None:
-    print("onnxruntime is not installed. Install it using `pip install onnxruntime-gpu`")
+    logger.warning("onnxruntime is not installed. Install it using `pip install onnxruntime-gpu`")
 
 else:
     if platform.system() == "Windows":
@@ -144,7 +147,7 @@ else:
     if dylib_path:
         os.environ["ORT_DYLIB_PATH"] = dylib_path[0]
     else:
-        print("onnxruntime dynamic library not found.")
+        logger.warning("onnxruntime dynamic library not found.")
 
 __doc__ = _embed_anything.__doc__
 if hasattr(_embed_anything, "__all__"):
@@ -1,55 +1,19 @@
 from enum import Enum
-from typing import List, Dict, Optional
-from abc import ABC, abstractmethod
+from typing import Any, Callable, List, Dict, Literal, Optional, Protocol, overload
 
-class Adapter(ABC):
-    def __init__(self, api_key: str):
-        """
-        Initializes the Adapter object.
-
-        Args:
-            api_key: The API key for accessing the adapter.
-        """
-
-    @abstractmethod
-    def create_index(self, dimension: int, metric: str, index_name: str, **kwargs): ...
+class Adapter(Protocol):
     """
-    Creates an index for storing the embeddings.
-
-    Args:
-        dimension: The dimension of the embeddings.
-        metric: The metric for measuring the distance between embeddings.
-        index_name: The name of the index.
-        kwargs: Additional keyword arguments.
+    What the embed functions need from an adapter. Instead of returning the embeddings, they call
+    `upsert` with a list of EmbedData every time a buffer of embeddings is ready. Every adapter in
+    `embed_anything.vectordb` satisfies it, and so does any object with a matching `upsert`.
     """
-    @abstractmethod
-    def delete_index(self, index_name: str):
-        """
-        Deletes an index.
-
-        Args:
-            index_name: The name of the index to delete.
-        """
 
-    @abstractmethod
-    def convert(self, embeddings: List[List[EmbedData]]) -> List[Dict]:
+    def upsert(self, data: list[EmbedData]) -> Any:
         """
-        Converts the embeddings to a list of dictionaries.
+        Writes the data to the vector database.
 
         Args:
-            embeddings: The list of embeddings.
-
-        Returns:
-            A list of dictionaries.
-        """
-
-    @abstractmethod
-    def upsert(self, data: List[Dict]):
-        """
-        Upserts the data into the index.
-
-        Args:
-            data: The list of data to upsert.
+            data: The embeddings of the buffer.
         """
 
 def embed_query(
@@ -78,23 +42,53 @@ def embed_query(
     ```
     """
 
+@overload
+def embed_text(
+    text: str, embedder: EmbeddingModel, config: TextEmbedConfig | None = None
+) -> EmbedData: ...
+@overload
+def embed_text(
+    text: list[str], embedder: EmbeddingModel, config: TextEmbedConfig | None = None
+) -> list[EmbedData]: ...
+def embed_text(
+    text: str | list[str], embedder: EmbeddingModel, config: TextEmbedConfig | None = None
+) -> EmbedData | list[EmbedData]:
+    """
+    Embeds a single text or a list of texts. Like `embed_query`, but also accepts a plain string.
+
+    Args:
+        text: A text, or a list of texts embedded in batches.
+        embedder: The embedding model to use.
+        config: The configuration for the embedding model.
+
+    Returns:
+        An EmbedData object for a single text, or a list of EmbedData objects in the order of the
+        texts.
+
+    Example:
+    ```python
+    data = embed_anything.embed_text("What is Rust?", embedder=model)
+    batch = embed_anything.embed_text(["What is Rust?", "What is Python?"], embedder=model)
+    ```
+    """
+
 def embed_file(
-    file_path: str,
+    file_name: str,
     embedder: EmbeddingModel,
     config: TextEmbedConfig | None = None,
     adapter: Adapter | None = None,
-) -> list[EmbedData]:
+) -> list[EmbedData] | None:
     """
     Embeds the given file and returns a list of EmbedData objects.
 
     Args:
-        file_path: The path to the file to embed.
+        file_name: The path to the file to embed.
         embedder: The embedding model to use.
         config: The configuration for the embedding model.
         adapter: The adapter to use for storing the embeddings in a vector database.
 
     Returns:
-        A list of EmbedData objects.
+        A list of EmbedData objects, or None if an adapter is given.
 
     Example:
     ```python
@@ -109,17 +103,17 @@ def embed_file(
     """
 
 def embed_directory(
-    file_path: str,
+    directory: str,
     embedder: EmbeddingModel,
     extensions: list[str],
     config: TextEmbedConfig | None = None,
     adapter: Adapter | None = None,
-) -> list[EmbedData]:
+) -> list[EmbedData] | None:
     """
     Embeds the files in the given directory and returns a list of EmbedData objects.
 
     Args:
-        file_path: The path to the directory containing the files to embed.
+        directory: The path to the directory containing the files to embed.
         embedder: The embedding model to use.
         extensions: The list of file extensions to consider for embedding.
         config: The configuration for the embedding model.
@@ -141,16 +135,16 @@ def embed_directory(
     """
 
 def embed_image_directory(
-    file_path: str,
+    directory: str,
     embedder: EmbeddingModel,
     config: ImageEmbedConfig | None = None,
     adapter: Adapter | None = None,
-) -> list[EmbedData]:
+) -> list[EmbedData] | None:
     """
     Embeds the images in the given directory and returns a list of EmbedData objects.
 
     Args:
-        file_path: The path to the directory containing the images to embed.
+        directory: The path to the directory containing the images to embed.
         embedder: The embedding model to use.
         config: The configuration for the embedding model.
         adapter: The adapter to use for storing the embeddings in a vector database.
@@ -159,11 +153,189 @@ def embed_image_directory(
         A list of EmbedData objects.
     """
 
+def runtime_info() -> dict:
+    """
+    Reports what the library runs on, for health and readiness checks.
+
+    Returns:
+        A dict with "version", "device" ("cpu", "cuda:N" or "metal:N"), "ort_cuda_available",
+        "ort_rocm_available" and the compiled-in "features".
+    """
+
+def set_log_level(level: str | None = None) -> None:
+    """
+    Sets the level of the library's log messages, which are written to stderr. The library is
+    quiet until this is called.
+
+    Args:
+        level: "error", "warn", "info", "debug" or "trace". None or "off" silences the library
+            again.
+
+    Example:
+    ```python
+    import embed_anything
+
+    embed_anything.set_log_level("info")
+    ```
+    """
+
+def memory_usage() -> dict:
+    """
+    Reports current memory usage, e.g. to check that closing a model released its memory.
+
+    Returns:
+        A dict with "process_rss_bytes" (resident memory of the process, Linux only),
+        "device_used_bytes" and "device_total_bytes" (CUDA device memory, CUDA builds only).
+        Values that can't be measured are None.
+    """
+
+def cluster_embeddings(
+    data: list[EmbedData],
+    k: int,
+    distance: str = "cosine",
+    batch_size: int | None = None,
+    max_iterations: int | None = None,
+    seed: int | None = None,
+) -> list[int]:
+    """
+    Clusters embeddings with mini-batch k-means, e.g. to deduplicate, group by topic or sample a
+    corpus after embedding it.
+
+    Args:
+        data: The embeddings to cluster. Only dense embeddings are supported.
+        k: The number of clusters.
+        distance: "cosine" (default) or "euclidean".
+        batch_size: The number of embeddings sampled per iteration. Defaults to 1024.
+        max_iterations: The maximum number of iterations. Defaults to 100.
+        seed: The random seed, for reproducible clusters. Defaults to 42.
+
+    Returns:
+        The cluster label of every item in data, in the same order.
+
+    Example:
+    ```python
+    data = embed_anything.embed_directory("test_files", embedder=model)
+    labels = embed_anything.cluster_embeddings(data, k=5)
+    ```
+    """
+
+def project_embeddings(
+    data: list[EmbedData], n_components: int = 2, path: str | None = None
+) -> list[list[float]]:
+    """
+    Projects embeddings to a few dimensions with PCA, for plotting a corpus.
+
+    Args:
+        data: The embeddings to project. Only dense embeddings are supported.
+        n_components: The number of dimensions to project to. Defaults to 2.
+        path: If given, the coordinates are written there together with the text and metadata of
+            every item, as JSON if the path ends with ".json" and as CSV otherwise.
+
+    Returns:
+        The coordinates of every item in data, in the same order.
+
+    Example:
+    ```python
+    data = embed_anything.embed_directory("test_files", embedder=model)
+    embed_anything.project_embeddings(data, path="corpus.csv")
+    ```
+    """
+
+def flag_low_quality(
+    data: list[EmbedData],
+    duplicate_threshold: float | None = None,
+    max_duplicates: int | None = None,
+    outlier_z_score: float | None = None,
+) -> tuple[int, int]:
+    """
+    Flags items that are near-duplicates of many others (boilerplate such as headers and footers)
+    or statistical outliers (often OCR garbage), so they can be kept out of an index. Sets the
+    "quality_flag" metadata field of every item to "ok", "near_duplicate" or "outlier".
+
+    Args:
+        data: The embeddings to check. Only dense embeddings are flagged.
+        duplicate_threshold: The cosine similarity above which two items count as near-duplicates.
+            Defaults to 0.98.
+        max_duplicates: An item is flagged once it is a near-duplicate of more than this many other
+            items. Defaults to 3.
+        outlier_z_score: An item is an outlier when its similarity to the rest of the corpus is
+            this many standard deviations below the mean. Defaults to 3.0.
+
+    Returns:
+        The number of near-duplicates and outliers found.
+
+    Example:
+    ```python
+    data = embed_anything.embed_file("test_files/scanned.pdf", embedder=model)
+    embed_anything.flag_low_quality(data)
+    data = [d for d in data if d.metadata["quality_flag"] == "ok"]
+    ```
+    """
+
+def chunk_text(text: str, config: TextEmbedConfig | None = None) -> list[str]:
+    """
+    Splits text into chunks the same way the embedding functions do, without embedding them.
+
+    Args:
+        text: The text to split.
+        config: The chunk size, overlap and splitting strategy to use.
+
+    Returns:
+        A list of chunks.
+
+    Raises:
+        EmbedAnythingError: If the chunk size and overlap of the config cannot be used together.
+    """
+
+def chunk_file(file_name: str, config: TextEmbedConfig | None = None) -> list[Chunk]:
+    """
+    Extracts the text of a file and splits it into chunks without embedding them.
+
+    Args:
+        file_name: The path to the file.
+        config: The chunk size, overlap, splitting strategy and OCR setting to use.
+
+    Returns:
+        A list of Chunk objects carrying the metadata of the file.
+
+    Example:
+    ```python
+    import embed_anything
+    chunks = embed_anything.chunk_file("test_files/test.pdf")
+    ```
+    """
+
+def embed_image_url(
+    url: str,
+    embedder: EmbeddingModel,
+    config: ImageEmbedConfig | None = None,
+) -> EmbedData:
+    """
+    Embeds a single image given as an http(s) URL, a file:// URL or a local path.
+
+    Args:
+        url: The URL or path of the image.
+        embedder: The vision embedding model to use.
+        config: Limits for the download, see ImageEmbedConfig.
+
+    Returns:
+        An EmbedData object with the source in the "image_url" metadata field.
+
+    Example:
+    ```python
+    import embed_anything
+    model = embed_anything.EmbeddingModel.from_pretrained_hf(
+        embed_anything.WhichModel.Clip, model_id="openai/clip-vit-base-patch16"
+    )
+    data = embed_anything.embed_image_url("https://example.com/cat.jpg", embedder=model)
+    ```
+    """
+
 def embed_webpage(
     url: str,
     embedder: EmbeddingModel,
-    config: TextEmbedConfig | None,
-    adapter: Adapter | None,
+    config: TextEmbedConfig | None = None,
+    adapter: Adapter | None = None,
 ) -> list[EmbedData] | None:
     """Embeds the webpage at the given URL and returns a list of EmbedData
     objects.
@@ -190,19 +362,113 @@ def embed_webpage(
     ```
     """
 
+def embed_webpages(
+    urls: list[str],
+    embedder: EmbeddingModel,
+    config: TextEmbedConfig | None = None,
+    concurrency: int | None = None,
+    timeout: float | None = None,
+    user_agent: str | None = None,
+    respect_robots_txt: bool | None = None,
+    domain_delay: float | None = None,
+    cache_path: str | None = None,
+    adapter: Adapter | None = None,
+) -> list[WebpageResult]:
+    """Embeds many webpages, fetching several of them at the same time.
+
+    A page that cannot be fetched or embedded does not stop the others. Its error is reported in
+    the `WebpageResult` of its URL. With an adapter, the embeddings of each page are sent to it as
+    soon as the page is done. Pages disallowed by robots.txt are skipped and reported as errors.
+
+    Args:
+        urls: The URLs of the webpages to embed.
+        embedder: The embedding model to use.
+        config: The configuration for the embedding model.
+        concurrency: The number of pages fetched at the same time. Default is 8.
+        timeout: The timeout in seconds for fetching one page. Default is 30.
+        user_agent: The User-Agent header, also used to pick the robots.txt rules. Default is "embed_anything/<version>".
+        respect_robots_txt: Whether to skip pages disallowed by robots.txt and honour its Crawl-delay. Default is True.
+        domain_delay: The minimum time in seconds between two requests to the same host. Default is 0.
+        cache_path: A JSON file remembering the ETag, Last-Modified and content hash of embedded pages. Pages that did not change since they were last embedded are skipped and reported with `unchanged=True`. Default is None.
+        adapter: The adapter to use for storing the embeddings.
+
+    Returns:
+        A list of WebpageResult objects in the order of `urls`.
+
+    Example:
+    ```python
+    import embed_anything
+
+    model = embed_anything.EmbeddingModel.from_pretrained_hf(
+        embed_anything.WhichModel.Bert, model_id="sentence-transformers/all-MiniLM-L6-v2"
+    )
+    results = embed_anything.embed_webpages(
+        ["https://www.akshaymakes.com/", "https://example.com/"], model, concurrency=4
+    )
+    for page in results:
+        if page.error is not None:
+            print(page.url, page.error)
+    ```
+    """
+
+def embed_sitemap(
+    url: str,
+    embedder: EmbeddingModel,
+    config: TextEmbedConfig | None = None,
+    concurrency: int | None = None,
+    timeout: float | None = None,
+    user_agent: str | None = None,
+    respect_robots_txt: bool | None = None,
+    domain_delay: float | None = None,
+    cache_path: str | None = None,
+    adapter: Adapter | None = None,
+) -> list[WebpageResult]:
+    """Embeds every page listed in a sitemap.xml, following sitemap indexes.
+
+    The pages are fetched and embedded like `embed_webpages` does. On top of the `url` of their
+    page, the embeddings carry in their metadata the `sitemap` that listed the page and its
+    `lastmod` when the sitemap gives one. Only the sitemap itself failing raises an error.
+
+    Args:
+        url: The URL of the sitemap.
+        embedder: The embedding model to use.
+        config: The configuration for the embedding model.
+        concurrency: The number of pages fetched at the same time. Default is 8.
+        timeout: The timeout in seconds for fetching one page. Default is 30.
+        user_agent: The User-Agent header, also used to pick the robots.txt rules. Default is "embed_anything/<version>".
+        respect_robots_txt: Whether to skip pages disallowed by robots.txt and honour its Crawl-delay. Default is True.
+        domain_delay: The minimum time in seconds between two requests to the same host. Default is 0.
+        cache_path: A JSON file remembering the ETag, Last-Modified and content hash of embedded pages. Pages that did not change since they were last embedded are skipped and reported with `unchanged=True`. Default is None.
+        adapter: The adapter to use for storing the embeddings.
+
+    Returns:
+        A list of WebpageResult objects in the order of the sitemap.
+
+    Example:
+    ```python
+    import embed_anything
+
+    model = embed_anything.EmbeddingModel.from_pretrained_hf(
+        embed_anything.WhichModel.Bert, model_id="sentence-transformers/all-MiniLM-L6-v2"
+    )
+    results = embed_anything.embed_sitemap(
+        "https://example.com/sitemap.xml", model, concurrency=4, cache_path="web_cache.json"
+    )
+    print(sum(page.error is None for page in results), "pages embedded")
+    ```
+    """
+
 def embed_audio_file(
-    file_path: str,
+    audio_file: str,
     audio_decoder: AudioDecoderModel,
     embedder: EmbeddingModel,
-    text_embed_config: TextEmbedConfig | None = TextEmbedConfig(
-        chunk_size=200, batch_size=32
-    ),
+    text_embed_config: TextEmbedConfig | None = None,
 ) -> list[EmbedData]:
     """
     Embeds the given audio file and returns a list of EmbedData objects.
 
     Args:
-        file_path: The path to the audio file to embed.
+        audio_file: The path to the audio file to embed.
         audio_decoder: The audio decoder model to use.
         embedder: The embedding model to use.
         text_embed_config: The configuration for the embedding model.
@@ -235,6 +501,122 @@ def embed_audio_file(
 
     """
 
+def embed_audio_directory(
+    directory: str,
+    audio_decoder: AudioDecoderModel,
+    embedder: EmbeddingModel,
+    text_embed_config: TextEmbedConfig | None = None,
+    adapter: Adapter | None = None,
+) -> list[EmbedData] | None:
+    """
+    Transcribes and embeds the audio files in a directory, decoding several files in parallel.
+
+    Args:
+        directory: The path to the directory containing the audio files.
+        audio_decoder: The audio decoder model to use. Its `num_workers` sets how many files are
+            decoded at the same time.
+        embedder: The embedding model to use.
+        text_embed_config: The configuration for the embedding model.
+        adapter: The adapter the embeddings of each file are sent to as soon as they are ready.
+
+    Returns:
+        A list of EmbedData objects, or None if an adapter is given.
+
+    Example:
+    ```python
+
+    data = embed_anything.embed_audio_directory(
+        "test_files/audio",
+        audio_decoder=audio_decoder,
+        embedder=embedder,
+    )
+    ```
+    """
+
+def embed_mixed_directory(
+    directory: str,
+    text_embedder: EmbeddingModel | None = None,
+    image_embedder: EmbeddingModel | None = None,
+    audio_decoder: AudioDecoderModel | None = None,
+    audio_embedder: EmbeddingModel | None = None,
+    config: TextEmbedConfig | None = None,
+    image_config: ImageEmbedConfig | None = None,
+    adapter: Adapter | None = None,
+) -> list[EmbedData] | None:
+    """
+    Embeds a directory of mixed files in one call. Documents, images and audio each go to the
+    matching embedder. Kinds without an embedder are skipped. The `modality` metadata of each
+    embedding is `text`, `image` or `audio`.
+
+    Args:
+        directory: The path to the directory containing the files.
+        text_embedder: The embedding model for pdf, md, txt, docx, pptx, epub, json, jsonl, csv and xlsx files.
+        image_embedder: The embedding model for images.
+        audio_decoder: The audio decoder model that transcribes audio files.
+        audio_embedder: The embedding model for the transcripts. Defaults to `text_embedder`.
+        config: The configuration for the text and audio embeddings.
+        image_config: The configuration for the image embeddings.
+        adapter: The adapter the embeddings are sent to.
+
+    Returns:
+        A list of EmbedData objects, or None if an adapter is given.
+
+    Example:
+    ```python
+
+    data = embed_anything.embed_mixed_directory(
+        "test_files",
+        text_embedder=bert_model,
+        image_embedder=clip_model,
+    )
+    ```
+    """
+
+def set_hub_config(
+    endpoint: str | None = None,
+    proxy: str | None = None,
+    connect_timeout: float | None = None,
+    cache_dir: str | None = None,
+    token: str | None = None,
+    progress: Callable[[str, int, int | None], None] | None = None,
+    allowed_models: list[str] | None = None,
+) -> None:
+    """
+    Configures how model files are downloaded from the Hugging Face Hub. Applies to every model
+    loaded after the call. Unset values fall back to `HF_ENDPOINT`, `HF_HOME`, the saved
+    Hugging Face token and the `HTTP(S)_PROXY` environment variables.
+
+    Args:
+        endpoint: The Hub endpoint or mirror, e.g. "https://hf-mirror.com".
+        proxy: The proxy used for all Hub requests, e.g. "http://proxy.corp:3128".
+        connect_timeout: The connect timeout in seconds.
+        cache_dir: The directory models are cached in.
+        token: The Hugging Face token used for gated or private models.
+        progress: Called with the file name, the bytes downloaded so far and the total size.
+        allowed_models: If set, only these model ids can be loaded. Others raise UnsupportedModelError. The repositories ColPali and CLIP take their tokenizer from, "vidore/colpali" and "openai/clip-vit-base-patch32", are not checked.
+
+    Example:
+    ```python
+    import embed_anything
+    embed_anything.set_hub_config(
+        endpoint="https://hf-mirror.com",
+        proxy="http://proxy.corp:3128",
+        progress=lambda file, done, total: print(file, done, total),
+    )
+    ```
+    """
+
+class Chunk:
+    """Represents a chunk of a document produced by `chunk_file`.
+
+    Attributes:
+        text: The text of the chunk.
+        metadata: The metadata of the file the chunk came from.
+    """
+
+    text: str
+    metadata: dict[str, str] | None
+
 class EmbedData:
     """Represents the data of an embedded file.
 
@@ -242,6 +624,9 @@ class EmbedData:
         embedding: The embedding of the file.
         text: The text for which the embedding is generated for.
         metadata: Additional metadata associated with the embedding.
+        id: A UUID derived from the source ("file_name" or "url" metadata) and the text with
+            whitespace collapsed. It stays the same across runs while the source and text do, so
+            adapters can upsert with it without creating duplicates.
     """
 
     def __init__(self, embedding: list[float], text: str, metadata: dict[str, str]):
@@ -251,35 +636,64 @@ class EmbedData:
     embedding: list[float]
     text: str
     metadata: dict[str, str]
+    @property
+    def id(self) -> str: ...
+
+    def to_json(self) -> str:
+        """
+        Serializes the embedding, text and metadata to JSON. The format carries a
+        `schema_version` field and is described by docs/schema/embed_data.v1.schema.json.
+        """
+
+    @staticmethod
+    def from_json(json: str) -> EmbedData:
+        """
+        Reads an EmbedData from the JSON written by `to_json`.
+
+        Raises:
+            EmbedAnythingError: If the JSON is malformed or has a newer schema version.
+        """
 
 class ColpaliModel:
     """
     Represents the Colpali model.
     """
 
-    def __init__(self, model_id: str, revision: str | None = None):
+    def __init__(
+        self, model_id: str, revision: str | None = None, max_vectors: int | None = None
+    ):
         """
         Initializes the ColpaliModel object.
 
         Args:
             model_id: The ID of the model from Hugging Face.
             revision: The revision of the model.
+            max_vectors: Pool the embedding of every image or page to at most this many vectors, by
+                merging the most similar neighbouring vectors. Queries are not pooled. Default is
+                None, which keeps all 1030 vectors of a page.
         """
 
-    def from_pretrained(model_id: str, revision: str | None = None) -> ColpaliModel:
+    @staticmethod
+    def from_pretrained(
+        model_id: str, revision: str | None = None, max_vectors: int | None = None
+    ) -> ColpaliModel:
         """
         Loads a pre-trained Colpali model from the Hugging Face model hub.
 
         Args:
             model_id: The ID of the model from Hugging Face.
             revision: The revision of the model.
+            max_vectors: Pool the embedding of every image or page to at most this many vectors, by
+                merging the most similar neighbouring vectors. Queries are not pooled. Default is
+                None, which keeps all 1030 vectors of a page.
 
         Returns:
             A ColpaliModel object.
         """
 
+    @staticmethod
     def from_pretrained_onnx(
-        model_id: str, revision: str | None = None
+        model_id: str, revision: str | None = None, max_vectors: int | None = None
     ) -> ColpaliModel:
         """
         Loads a pre-trained Colpali model from the Hugging Face model hub.
@@ -287,18 +701,27 @@ class ColpaliModel:
         Args:
             model_id: The ID of the model from Hugging Face.
             revision: The revision of the model.
+            max_vectors: Pool the embedding of every image or page to at most this many vectors, by
+                merging the most similar neighbouring vectors. Queries are not pooled. Default is
+                None, which keeps all 1030 vectors of a page.
 
         Returns:
             A ColpaliModel object.
         """
 
-    def embed_file(self, file_path: str, batch_size: int | None = 1) -> list[EmbedData]:
+    def embed_file(
+        self,
+        file_path: str,
+        batch_size: int | None = 1,
+        config: ImageEmbedConfig | None = None,
+    ) -> list[EmbedData]:
         """
         Embeds the given pdf file and returns a list of EmbedData objects for each page in the file This first convert the pdf file into images and then embed each image.
 
         Args:
             file_path: The path to the pdf file to embed.
             batch_size: The batch size for processing the embeddings. Default is 1.
+            config: The resolution, colour and page range the pages are rendered with.
 
         Returns:
             A list of EmbedData objects for each page in the file.
@@ -326,16 +749,30 @@ class ColbertModel:
         hf_model_id: str | None = None,
         revision: str | None = None,
         path_in_repo: str | None = None,
+        skip_punctuation: bool = False,
+        skip_special_tokens: bool = False,
+        top_k: int | None = None,
     ):
         """
         Initializes the ColbertModel object.
+
+        Attributes:
+            hf_model_id: The ID of the model from Hugging Face.
+            revision: The revision of the model.
+            path_in_repo: The path to the model in the repository.
+            skip_punctuation: Drop punctuation token vectors from documents.
+            skip_special_tokens: Drop special tokens, markers and padding. Query [MASK] padding is kept.
+            top_k: Keep at most this many token vectors per text, the ones with the largest norm.
         """
 
+    @staticmethod
     def from_pretrained_onnx(
-        self,
         hf_model_id: str | None = None,
         revision: str | None = None,
         path_in_repo: str | None = None,
+        skip_punctuation: bool = False,
+        skip_special_tokens: bool = False,
+        top_k: int | None = None,
     ) -> ColbertModel:
         """
         Loads a pre-trained Colbert model from the Hugging Face model hub.
@@ -344,6 +781,9 @@ class ColbertModel:
             hf_model_id: The ID of the model from Hugging Face.
             revision: The revision of the model.
             path_in_repo: The path to the model in the repository.
+            skip_punctuation: Drop punctuation token vectors from documents.
+            skip_special_tokens: Drop special tokens, markers and padding. Query [MASK] padding is kept.
+            top_k: Keep at most this many token vectors per text, the ones with the largest norm.
 
         Returns:
             A ColbertModel object.
@@ -368,20 +808,123 @@ class Reranker:
         Initializes the Reranker object.
         """
 
+    @staticmethod
     def from_pretrained(
-        model_id: str, revision: str | None = None, dtype: Dtype | None = None
+        model_id: str,
+        revision: str | None = None,
+        dtype: Dtype | None = None,
+        normalization: str | None = None,
+        calibration_offset: float | None = None,
+        device: str | None = None,
     ) -> Reranker:
         """
         Loads a pre-trained Reranker model from the Hugging Face model hub.
+
+        Args:
+            model_id: The ID of the model.
+            revision: The revision of the model.
+            dtype: The dtype of the ONNX weights to load.
+            normalization: How logits become scores: "sigmoid" (default), "minmax" to rescale the
+                scores of each query to [0, 1], or "none" for raw logits.
+            calibration_offset: Added to every logit before normalization, so one threshold works
+                across rerankers. Defaults to 0.
+            device: "auto" (default), "cpu", "cuda:N" or "metal:N", e.g. "cpu" to keep the
+                reranker off the GPU the embedding model runs on.
         """
 
     def rerank(
-        self, query: list[str], documents: list[str], top_k: int
-    ) -> RerankerResult:
+        self, query: list[str], documents: list[str], batch_size: int
+    ) -> list[RerankerResult]:
         """
-        Reranks the given documents for the query and returns a list of RerankerResult objects.
+        Reranks the documents for every query and returns one RerankerResult per query.
+
+        Args:
+            query: The queries.
+            documents: The documents to rank against every query.
+            batch_size: The number of query-document pairs per forward pass.
         """
 
+class ScoredChunk:
+    """
+    Represents a chunk retrieved by a Pipeline.
+
+    Attributes:
+        text: The text of the chunk.
+        metadata: The metadata stored with the chunk.
+        score: The relevance score, from the reranker if one is used, otherwise from the adapter.
+    """
+
+    text: str
+    metadata: dict[str, str] | None
+    score: float
+
+class Pipeline:
+    """
+    Retrieves chunks for a query in one call: embeds the query, searches the adapter and optionally
+    reranks the candidates with a cross-encoder.
+
+    The adapter must implement `query(embedding, top_k)` returning a list of dicts with "text",
+    "score" and optionally "metadata".
+    """
+
+    def __init__(
+        self,
+        embedder: EmbeddingModel,
+        adapter: Adapter,
+        reranker: Reranker | None = None,
+        top_k: int = 5,
+        candidates: int = 20,
+    ):
+        """
+        Initializes the Pipeline object.
+
+        Attributes:
+            embedder: The model used to embed the query.
+            adapter: The vector database adapter to search.
+            reranker: An optional reranker applied to the candidates.
+            top_k: The number of chunks to return.
+            candidates: The number of candidates fetched for the reranker.
+        """
+
+    def retrieve(self, query: str) -> list[ScoredChunk]:
+        """
+        Returns the top_k chunks for the query.
+
+        Example:
+        ```python
+        pipeline = embed_anything.Pipeline(model, adapter, reranker=reranker, top_k=3)
+        for chunk in pipeline.retrieve("What is attention?"):
+            print(chunk.score, chunk.text)
+        ```
+        """
+
+class EmbedAnythingError(ValueError):
+    """Base class for errors raised by embed_anything."""
+
+class ModelLoadError(EmbedAnythingError):
+    """The model weights, config or tokenizer could not be loaded."""
+
+class UnsupportedModelError(EmbedAnythingError):
+    """The requested model or architecture is not supported."""
+
+class TokenizationError(EmbedAnythingError):
+    """The tokenizer failed to encode the input."""
+
+class InferenceError(EmbedAnythingError):
+    """The forward pass of the model failed."""
+
+class NetworkError(EmbedAnythingError):
+    """A request to a cloud embedding API failed."""
+
+class RateLimitedError(NetworkError):
+    """A cloud embedding API rejected the request because of rate limits."""
+
+class UnsupportedFileError(EmbedAnythingError):
+    """The file type cannot be embedded."""
+
+class InvalidConfigError(EmbedAnythingError):
+    """A configuration value is out of range or inconsistent with another one."""
+
 class Dtype(Enum):
     """
     Represents the data type of the model.
@@ -393,6 +936,8 @@ class Dtype(Enum):
     UINT8 = "UINT8"
     BNB4 = "BNB4"
     Q4F16 = "Q4F16"
+    F32 = "F32"
+    BF16 = "BF16"
 
 class RerankerResult:
     """
@@ -427,35 +972,136 @@ class TextEmbedConfig:
     Attributes:
         chunk_size: The chunk size for the Text Embedding model.
         batch_size: The batch size for processing the embeddings. Default is 32. Based on the memory, you can increase or decrease the batch size.
-        splitting_strategy: The strategy to use for splitting the text into chunks. Default is "sentence".
+        buffer_size: The number of embeddings handed to the adapter at once. Default is 100.
+        overlap_ratio: The fraction of each chunk repeated at the start of the next one. Default is 0.0.
+        overlap_tokens: The number of tokens repeated at the start of the next chunk. Takes precedence over overlap_ratio. Default is None.
+        splitting_strategy: The strategy to use for splitting the text into chunks. "content_defined" places boundaries by a rolling hash of the text, so editing a document only changes the chunks around the edit. Default is "sentence".
         semantic_encoder: The semantic encoder for the Text Embedding model. Default is None.
         use_ocr: A flag indicating whether to use OCR for the Text Embedding model. Default is False.
+        metadata_fields: The metadata keys to keep, e.g. ["file_name"]. Default is None, which keeps all keys.
+        max_metadata_length: The longest metadata value in characters. Longer values are cut and end with "...[truncated]". Default is None.
+        document_pooling: Also emit one document embedding per file, pooled from its chunk embeddings with "mean" or "weighted" (by chunk length). The metadata field "embedding_level" is "chunk" or "document". Default is None.
+        length_sorted_batching: Embed the buffered chunks of embed_directory_stream grouped by length, so batches need less padding. The embeddings keep the order of the chunks. Default is False.
+        invalid_embeddings: What happens to chunks whose embedding has NaN values or is all zeros: "keep" them, "drop" them or "retry" them on their own and drop them if still invalid. They are reported by take_warnings either way. Default is "keep".
+        pdf_first_page: The first page of PDFs to embed, counting from 1. Default is the first page.
+        pdf_last_page: The last page of PDFs to embed. Default is the last page.
+        split_by_outline: Chunk PDFs section by section, one per top-level outline entry (bookmark). Chunks get the title and first page of their section as the metadata fields "section_title" and "section_page". Default is False.
+        table_rows_per_chunk: The rows of CSV and Excel files embedded together, each as "column: value" pairs. Chunks get the column names, their rows (e.g. "11-20") and the Excel sheet as the metadata fields "columns", "rows" and "sheet". Default is 10.
+        headers_footers: Whether to embed page headers and footers. False drops the lines repeated at the top and bottom of most pages of PDFs, such as running titles and page numbers. True embeds the headers and footers of DOCX files along with their body. Default is None, which keeps the PDF text as extracted and leaves DOCX headers and footers out.
+        footnotes: Embed the footnotes and endnotes of DOCX files after their body, under the headings "Footnotes" and "Endnotes". Default is False.
+        json_text_fields: The fields of the records of JSON and JSONL files to embed, e.g. ["title", "body"]. Nested fields are named by their path, e.g. "author.name". Default is None, which embeds every field as "field: value" lines.
+        json_metadata_fields: The fields of the records of JSON and JSONL files to add to the metadata of their chunks, e.g. ["id"]. Chunks also get the number of their record as the metadata field "record". Default is None.
+        streaming_threshold: The size in bytes above which txt files are read and chunked block by block instead of whole, so memory use does not grow with the file. With an adapter, their embeddings are handed over every buffer_size chunks while the file is read. Default is 64 MiB.
+        follow_symlinks: Whether `embed_directory` and `embed_audio_directory` follow symbolic links. Links back to a directory being walked are skipped, and a file reached through several links is embedded once. Default is False, which skips links.
+        max_depth: The deepest level of the directory walked, 1 for the files directly in it, 2 to also walk its subdirectories, and so on. Must be at least 1. Default is None, which looks for documents only directly in the directory and for recordings at any depth.
+        shard_index: The shard of the files of directories to embed, from 0 to num_shards - 1, so that several workers running the same directory each embed a disjoint part of it without coordinating. Files are assigned to shards by the hash of their path relative to the directory. Given together with num_shards. Default is None, which embeds every file.
+        num_shards: The number of shards the files of directories are split into. Default is None.
+        chunk_unit: What chunk_size and the overlap are counted in. "tokens" counts the tokens of the embedding model, special tokens included, so no chunk is longer than the model reads; cloud models count cl100k tokens instead. "characters" counts characters. Default is None, which counts cl100k tokens for every model.
+        min_batch_size: The smallest batch size to retry with when the model runs out of memory. Batches that run out of memory are halved and retried, and later batches embedded with this config keep the smaller size. Default is 1.
+        namespace: The tenant the embeddings belong to, added to their metadata as the field "namespace". Default is None.
     """
 
     def __init__(
         self,
-        chunk_size: int | None = 256,
-        overlap_ratio: float | None = 0.0,
-        batch_size: int | None = 32,
-        buffer_size: int | None = 100,
-        splitting_strategy: str | None = "sentence",
+        chunk_size: int | None = None,
+        batch_size: int | None = None,
+        buffer_size: int | None = None,
+        overlap_ratio: float | None = None,
+        overlap_tokens: int | None = None,
+        splitting_strategy: Literal["sentence", "semantic", "content_defined"] | None = None,
         semantic_encoder: EmbeddingModel | None = None,
-        use_ocr: bool | None = False,
-    ):
-        self.chunk_size = chunk_size
-        self.overlap_ratio = overlap_ratio
-        self.batch_size = batch_size
-        self.buffer_size = buffer_size
-        self.splitting_strategy = splitting_strategy
-        self.semantic_encoder = semantic_encoder
-        self.use_ocr = use_ocr
-    chunk_size: int | None
-    overlap_ratio: float | None
-    batch_size: int | None
-    buffer_size: int | None
-    splitting_strategy: str | None
-    semantic_encoder: EmbeddingModel | None
-    use_ocr: bool | None
+        use_ocr: bool | None = None,
+        metadata_fields: list[str] | None = None,
+        max_metadata_length: int | None = None,
+        document_pooling: Literal["mean", "weighted"] | None = None,
+        length_sorted_batching: bool | None = None,
+        invalid_embeddings: Literal["keep", "drop", "retry"] | None = None,
+        pdf_first_page: int | None = None,
+        pdf_last_page: int | None = None,
+        split_by_outline: bool | None = None,
+        table_rows_per_chunk: int | None = None,
+        headers_footers: bool | None = None,
+        footnotes: bool | None = None,
+        json_text_fields: list[str] | None = None,
+        json_metadata_fields: list[str] | None = None,
+        streaming_threshold: int | None = None,
+        follow_symlinks: bool | None = None,
+        max_depth: int | None = None,
+        shard_index: int | None = None,
+        num_shards: int | None = None,
+        chunk_unit: Literal["tokens", "characters"] | None = None,
+        min_batch_size: int | None = None,
+        namespace: str | None = None,
+    ) -> None: ...
+    @property
+    def chunk_size(self) -> int | None: ...
+    @property
+    def batch_size(self) -> int | None: ...
+    @property
+    def buffer_size(self) -> int | None: ...
+    @property
+    def overlap_ratio(self) -> float | None: ...
+    @property
+    def overlap_tokens(self) -> int | None: ...
+    @property
+    def splitting_strategy(self) -> Literal["sentence", "semantic", "content_defined"] | None: ...
+    @property
+    def chunk_unit(self) -> Literal["tokens", "characters"] | None: ...
+    @property
+    def semantic_encoder(self) -> EmbeddingModel | None: ...
+    @property
+    def use_ocr(self) -> bool | None: ...
+    @property
+    def metadata_fields(self) -> list[str] | None: ...
+    @property
+    def max_metadata_length(self) -> int | None: ...
+    @property
+    def namespace(self) -> str | None: ...
+    @property
+    def document_pooling(self) -> Literal["mean", "weighted"] | None: ...
+    @property
+    def length_sorted_batching(self) -> bool | None: ...
+    @property
+    def min_batch_size(self) -> int: ...
+    @property
+    def invalid_embeddings(self) -> Literal["keep", "drop", "retry"]: ...
+    @property
+    def pdf_first_page(self) -> int | None: ...
+    @property
+    def pdf_last_page(self) -> int | None: ...
+    @property
+    def split_by_outline(self) -> bool: ...
+    @property
+    def table_rows_per_chunk(self) -> int | None: ...
+    @property
+    def headers_footers(self) -> bool | None: ...
+    @property
+    def footnotes(self) -> bool: ...
+    @property
+    def json_text_fields(self) -> list[str]: ...
+    @property
+    def json_metadata_fields(self) -> list[str]: ...
+    @property
+    def streaming_threshold(self) -> int | None: ...
+    @property
+    def follow_symlinks(self) -> bool: ...
+    @property
+    def max_depth(self) -> int | None: ...
+    @property
+    def shard_index(self) -> int | None: ...
+    @property
+    def num_shards(self) -> int | None: ...
+    def take_warnings(self) -> list[dict[str, str | None]]:
+        """
+        Returns the warnings of the runs that used this config since the last call and clears
+        them: files without text, files that were skipped, chunks that were truncated, invalid
+        embeddings and batch sizes reduced after running out of memory.
+
+        Returns:
+            A list of dicts with the "kind" of warning ("empty_file", "skipped_file",
+            "truncated_chunks" or "batch_size_reduced"), the "source" file or URL, if any, and
+            a readable "message".
+        """
 
 class ImageEmbedConfig:
     """
@@ -463,19 +1109,129 @@ class ImageEmbedConfig:
 
     Attributes:
         buffer_size: The buffer size for the Image Embedding model. Default is 100.
+        max_download_bytes: The largest image `embed_image_url` downloads. Default is 20 MiB.
+        download_timeout: The timeout in seconds for downloading an image. Default is 30.
+        pdf_dpi: The resolution PDF pages are rendered at for ColPali. Default is 150.
+        pdf_grayscale: Whether PDF pages are rendered in grayscale. Default is False.
+        pdf_first_page: The first page of a PDF to embed, counting from 1. Default is the first page.
+        pdf_last_page: The last page of a PDF to embed. Default is the last page.
+        follow_symlinks: Whether `embed_image_directory` follows symbolic links. Links back to a directory being walked are skipped, and an image reached through several links is embedded once. Default is False, which skips links.
+        max_depth: The deepest level of the directory walked, 1 for the images directly in it. Must be at least 1. Default is None, which walks every subdirectory.
+        shard_index: The shard of the images to embed, from 0 to num_shards - 1, see `TextEmbedConfig`. Given together with num_shards. Default is None, which embeds every image.
+        num_shards: The number of shards the images are split into. Default is None.
+    """
+
+    def __init__(
+        self,
+        buffer_size: int | None = None,
+        max_download_bytes: int | None = None,
+        download_timeout: float | None = None,
+        pdf_dpi: int | None = None,
+        pdf_grayscale: bool | None = None,
+        pdf_first_page: int | None = None,
+        pdf_last_page: int | None = None,
+        follow_symlinks: bool | None = None,
+        max_depth: int | None = None,
+        shard_index: int | None = None,
+        num_shards: int | None = None,
+    ) -> None: ...
+    @property
+    def buffer_size(self) -> int | None: ...
+    @property
+    def max_download_bytes(self) -> int | None: ...
+    @property
+    def download_timeout(self) -> float | None: ...
+    @property
+    def pdf_dpi(self) -> int | None: ...
+    @property
+    def pdf_grayscale(self) -> bool | None: ...
+    @property
+    def pdf_first_page(self) -> int | None: ...
+    @property
+    def pdf_last_page(self) -> int | None: ...
+    @property
+    def follow_symlinks(self) -> bool: ...
+    @property
+    def max_depth(self) -> int | None: ...
+    @property
+    def shard_index(self) -> int | None: ...
+    @property
+    def num_shards(self) -> int | None: ...
+
+class PrefetchReport:
+    """
+    Represents the files downloaded by `EmbeddingModel.prefetch`.
+
+    Attributes:
+        files: The local paths of the prefetched files.
+        total_bytes: The total size of the files.
+        downloaded_bytes: The size of the files that were not cached yet.
+    """
+
+    files: list[str]
+    total_bytes: int
+    downloaded_bytes: int
+
+class WebpageResult:
+    """
+    Represents the outcome of one URL passed to `embed_webpages`, or listed by the sitemap passed
+    to `embed_sitemap`.
+
+    Attributes:
+        url: The URL of the webpage.
+        embeddings: The embeddings of the page, or None when they were sent to the adapter or the page failed.
+        error: The reason the page could not be fetched or embedded, or None.
+        unchanged: Whether the page was skipped because it did not change since it was last embedded.
     """
 
-    def __init__(self, buffer_size: int | None = None):
-        self.buffer_size = buffer_size
-    buffer_size: int | None
+    url: str
+    embeddings: list[EmbedData] | None
+    error: str | None
+    unchanged: bool
 
 class EmbeddingModel:
     """
     Represents an embedding model.
     """
 
+    @staticmethod
+    def prefetch(
+        model_id: str,
+        revision: str | None = None,
+        files: list[str] | None = None,
+    ) -> PrefetchReport:
+        """
+        Downloads the files of a Hugging Face model into the local cache without loading it, so
+        caches can be warmed during deploys. Interrupted downloads are resumed.
+
+        Attributes:
+            model_id: The ID of the model.
+            revision: The revision of the model.
+            files: The files to download. Defaults to the configs, tokenizer and weights used by
+                `from_pretrained_hf`.
+
+        Returns:
+            A PrefetchReport with the local paths and sizes of the files.
+
+        Example:
+        ```python
+        report = EmbeddingModel.prefetch("sentence-transformers/all-MiniLM-L6-v2")
+        print(report.total_bytes, report.downloaded_bytes)
+        ```
+        """
+
+    @staticmethod
     def from_pretrained_hf(
-        model: WhichModel, model_id: str, revision: str | None = None
+        model: WhichModel,
+        model_id: str,
+        revision: str | None = None,
+        dtype: Dtype | None = None,
+        query_prompt: str | None = None,
+        document_prompt: str | None = None,
+        device: str | None = None,
+        timing: bool = False,
+        max_vectors: int | None = None,
+        flash_attn: bool | None = None,
     ) -> EmbeddingModel:
         """
         Loads an embedding model from the Hugging Face model hub.
@@ -483,6 +1239,21 @@ class EmbeddingModel:
         Attributes:
             model_id: The ID of the model.
             revision: The revision of the model.
+            dtype: The dtype to run the model in. F16 and BF16 are used on supported GPUs, otherwise F32.
+            query_prompt: The instruction prepended to queries in embed_query. Known instruction
+                models (BGE, GTE-Qwen, E5, Qwen3-Embedding, NV-Embed, Nomic) get theirs automatically;
+                this overrides it. Only supported for Bert models.
+            document_prompt: The instruction prepended to documents. Overrides the automatic one
+                like query_prompt. Pass "" for both to embed texts as they are.
+            device: The device to load the model on: "auto" (default), "cpu", "cuda:N" or "metal:N".
+            timing: Record the time spent per stage, read with `timing_report`. Stages wait for the
+                GPU to finish, which costs a little throughput. Only supported for Bert and Jina models.
+            max_vectors: Pool every image or page embedding to at most this many vectors, see
+                ColpaliModel. Only supported for Colpali models.
+            flash_attn: Run the attention with the fused flash-attn kernel. Needs a build with the
+                `flash-attn` feature, a CUDA device and F16/BF16, and falls back to the regular
+                attention otherwise. Default is on for F16/BF16 in such builds. Only supported for
+                Jina models.
 
         Returns:
             An EmbeddingModel object.
@@ -497,8 +1268,15 @@ class EmbeddingModel:
 
         """
 
+    @staticmethod
     def from_pretrained_cloud(
-        model: WhichModel, model_id: str, api_key: str | None = None
+        model: WhichModel,
+        model_id: str,
+        api_key: str | None = None,
+        proxy: str | None = None,
+        ca_cert: str | None = None,
+        timeout: float | None = None,
+        truncation: str | None = None,
     ) -> EmbeddingModel:
         """
         Loads an embedding model from a cloud-based service.
@@ -511,6 +1289,10 @@ class EmbeddingModel:
             api_key (str | None, optional): The API key for accessing the model. If not provided, it is taken from the environment variable:
                 - For OpenAI: OPENAI_API_KEY
                 - For Cohere: CO_API_KEY
+            proxy (str | None, optional): Proxy URL for all requests, e.g. "http://proxy.corp:3128". If not provided, the HTTPS_PROXY and HTTP_PROXY environment variables are honored.
+            ca_cert (str | None, optional): Path to a PEM file with an extra root certificate to trust, e.g. the CA of a corporate gateway.
+            timeout (float | None, optional): Timeout in seconds for each request.
+            truncation (str | None, optional): What to do with inputs longer than the model accepts: "error", "truncate_head" (keep the end), "truncate_tail" (keep the beginning) or "split_and_average". Shortened chunks get a "truncated" metadata field. If not provided, inputs are sent as they are.
 
         Returns:
             EmbeddingModel: An initialized EmbeddingModel object.
@@ -534,6 +1316,7 @@ class EmbeddingModel:
         ```
         """
 
+    @staticmethod
     def from_pretrained_onnx(
         model: WhichModel,
         model_name: Optional[ONNXModel] | None = None,
@@ -541,6 +1324,12 @@ class EmbeddingModel:
         revision: Optional[str] | None = None,
         dtype: Optional[Dtype] | None = None,
         path_in_repo: Optional[str] | None = None,
+        task: Optional[str] | None = None,
+        query_prompt: Optional[str] | None = None,
+        document_prompt: Optional[str] | None = None,
+        device: Optional[str] | None = None,
+        timing: bool = False,
+        profile_path: Optional[str] | None = None,
     ) -> EmbeddingModel:
         """
         Loads an ONNX embedding model.
@@ -552,6 +1341,20 @@ class EmbeddingModel:
             revision (str | None, optional): The revision of the model. Defaults to None.
             dtype (Dtype | None, optional): The dtype of the model. Defaults to None.
             path_in_repo (str | None, optional): The path to the model in the repository. Defaults to None.
+            task (str | None, optional): The jina-embeddings-v3 task adapter: "retrieval.query",
+                "retrieval.passage", "separation", "classification" or "text-matching". Only
+                supported for Jina models. Defaults to "text-matching".
+            query_prompt (str | None, optional): Overrides the query instruction picked from the
+                model id. Only supported for Bert models. Defaults to None.
+            document_prompt (str | None, optional): Overrides the document instruction picked from
+                the model id. Only supported for Bert models. Defaults to None.
+            device (str | None, optional): The device to run the model on: "auto", "cpu", "cuda:N"
+                or "metal:N". Defaults to "auto".
+            timing (bool, optional): Record the time spent per stage, read with `timing_report`.
+                Only supported for Bert models. Defaults to False.
+            profile_path (str | None, optional): Turns on ONNX Runtime profiling. A per-operator
+                trace is written to a JSON file starting with this prefix when the model is closed.
+                Only supported for Bert models. Defaults to None.
         Returns:
             EmbeddingModel: An initialized EmbeddingModel object.
 
@@ -562,6 +1365,10 @@ class EmbeddingModel:
         If hf_model_id is provided, dtype is ignored and the path_in_repo has to be provided pointing to the model file in the repository.
         If model_name is provided, dtype is used to determine the model file to load.
 
+        hf_model_id can also be a local directory holding the tokenizer files (tokenizer.json,
+        tokenizer_config.json, config.json). path_in_repo is then relative to that directory or an
+        absolute path to the .onnx file.
+
         Example:
         ```python
         model = EmbeddingModel.from_pretrained_onnx(
@@ -575,6 +1382,12 @@ class EmbeddingModel:
             hf_model_id="jinaai/jina-embeddings-v3",
             path_in_repo="onnx/model_fp16.onnx"
         )
+
+        model = EmbeddingModel.from_pretrained_onnx(
+            model=WhichModel.Bert,
+            hf_model_id="/models/my-exported-bert",
+            path_in_repo="/artifacts/my-exported-bert/model.onnx"
+        )
         ```
 
         Note:
@@ -583,6 +1396,149 @@ class EmbeddingModel:
         scenarios where performance is critical.
         """
 
+    def timing_report(self) -> dict[str, float | int] | None:
+        """
+        Returns the time spent per stage since the last call and resets it, or None if the model
+        was not loaded with `timing=True`.
+
+        Returns:
+            A dict with the seconds spent in "extraction", "chunking", "tokenize", "inference"
+            (the forward pass), "pooling", "normalize" and their "total", and the number of
+            "batches" and "texts" embedded. Extraction and chunking are recorded when embedding
+            files.
+        """
+
+    def warmup(self, batch_size: int = 32, seq_len: int = 256) -> float:
+        """
+        Embeds a dummy batch so kernel selection and memory allocation happen now rather than on
+        the first real request. Warm up with the largest batch you expect. Does nothing for cloud
+        models.
+
+        Args:
+            batch_size: The number of texts in the dummy batch.
+            seq_len: The approximate number of tokens per text. Longer texts are truncated to the
+                model's maximum length.
+
+        Returns:
+            The time the warmup took, in seconds.
+        """
+
+    closed: bool
+    """Whether close() has been called."""
+
+    def close(self) -> None:
+        """
+        Releases the model's weights, GPU memory and ONNX session. Pipelines, audio streams and
+        TextEmbedConfig semantic encoders built from the model keep their own reference, so the
+        memory is freed once those are gone too. Using the model after closing it raises an error.
+        """
+
+    def __enter__(self) -> EmbeddingModel: ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool:
+        """
+        Closes the model when the with block ends.
+
+        Example:
+        ```python
+        with EmbeddingModel.from_pretrained_hf(WhichModel.Bert, "BAAI/bge-small-en-v1.5") as model:
+            data = embed_anything.embed_file("test_files/test.pdf", embedder=model)
+        print(embed_anything.memory_usage())
+        ```
+        """
+
+    def embed_directory_iter(
+        self,
+        directory: str,
+        extensions: list[str] | None = None,
+        config: TextEmbedConfig | None = None,
+        max_pending: int = 4,
+    ) -> EmbedDataIterator:
+        """
+        Embeds the files of a directory in the background and yields the embeddings in batches as
+        they are produced, instead of passing them to an adapter.
+
+        Args:
+            directory: The directory containing the files to embed.
+            extensions: The file extensions to embed, e.g. [".pdf"]. Defaults to all supported files.
+            config: The configuration for the embedding model. Batches hold up to
+                `config.buffer_size` embeddings.
+            max_pending: The number of batches embedded ahead of the consumer. Embedding pauses
+                while this many are waiting.
+
+        Example:
+        ```python
+        for batch in model.embed_directory_iter("test_files", extensions=[".pdf"]):
+            index.add([d.embedding for d in batch])
+        ```
+        """
+
+    def embed_queries(
+        self, queries: list[str], batch_size: int | None = None
+    ) -> list[EmbedData]:
+        """
+        Embeds search queries the way the model expects them: instruction-tuned models such as BGE
+        or E5 get their query prefix, jina-embeddings-v3 uses its retrieval.query adapter and Cohere
+        the search_query input type. Symmetric models embed queries like any text.
+
+        Attributes:
+            queries: The queries to embed.
+            batch_size: The number of texts per forward pass.
+
+        Example:
+        ```python
+        model = EmbeddingModel.from_pretrained_hf(WhichModel.Bert, model_id="BAAI/bge-small-en-v1.5")
+        queries = model.embed_queries(["What is the capital of France?"])
+        documents = model.embed_documents(["Paris is the capital of France."])
+        ```
+        """
+
+    def embed_documents(
+        self,
+        documents: list[str] | list[list[dict[str, str]]],
+        batch_size: int | None = None,
+    ) -> list[EmbedData]:
+        """
+        Embeds documents to search over, the counterpart of `embed_queries`.
+
+        Documents can also be made of interleaved text and image parts, one vector per document.
+        Those are only supported by multimodal Cohere models such as embed-v4.0.
+
+        Attributes:
+            documents: The texts to embed, or documents that are each a list of parts; a part is
+                either {"text": ...} or {"image": path}.
+            batch_size: The number of texts per forward pass. Ignored for documents with parts.
+
+        Example:
+        ```python
+        model = EmbeddingModel.from_pretrained_cloud(WhichModel.Cohere, model_id="embed-v4.0")
+        data = model.embed_documents([
+            [{"text": "Quarterly revenue"}, {"image": "chart.png"}],
+        ])
+        ```
+        """
+
+    def embed_all(
+        self, texts: list[str], batch_size: int | None = None
+    ) -> list[dict[str, Any]]:
+        """
+        Returns the dense, sparse and ColBERT representations of every text from a single forward
+        pass. Only supported by BGE-M3 models.
+
+        Attributes:
+            texts: The texts to embed.
+            batch_size: The number of texts per forward pass. Defaults to 32.
+
+        Returns:
+            One dict per text with "dense" (list of floats), "sparse" (token id to weight) and
+            "colbert" (one vector per token).
+
+        Example:
+        ```python
+        model = EmbeddingModel.from_pretrained_onnx(WhichModel.BgeM3, model_name=ONNXModel.BGEM3)
+        outputs = model.embed_all(["What is BGE M3?"])
+        ```
+        """
+
 class AudioDecoderModel:
     """
     Represents an audio decoder model.
@@ -592,6 +1548,14 @@ class AudioDecoderModel:
         revision: The revision of the audio decoder model.
         model_type: The type of the audio decoder model.
         quantized: A flag indicating whether the audio decoder model is quantized or not.
+        window_duration: Merge transcribed segments into windows of about this many seconds
+            before embedding. Segments are embedded as decoded when None.
+        window_overlap: Seconds shared by consecutive windows. Defaults to no overlap.
+        vad: Skip silence with voice activity detection before transcribing. Defaults to False.
+        vad_aggressiveness: How much audio the voice activity detection skips, from 0 to 3.
+            Defaults to 1.
+        num_workers: Files `embed_audio_directory` decodes at the same time. Defaults to half
+            the CPU cores on CPU and 1 on GPU.
 
     Example:
     ```python
@@ -610,12 +1574,72 @@ class AudioDecoderModel:
     model_type: str
     quantized: bool
 
+    @staticmethod
     def from_pretrained_hf(
         model_id: str | None = None,
         revision: str | None = None,
         model_type: str | None = None,
         quantized: bool | None = None,
+        window_duration: float | None = None,
+        window_overlap: float | None = None,
+        vad: bool | None = None,
+        vad_aggressiveness: int | None = None,
+        num_workers: int | None = None,
+    ) -> AudioDecoderModel: ...
+
+class EmbedDataIterator:
+    """
+    Iterator over the batches of embeddings produced by `EmbeddingModel.embed_directory_iter`.
+    Raises the error of the embedding run, if any, once all batches before it are consumed.
+    """
+
+    def __iter__(self) -> EmbedDataIterator: ...
+    def __next__(self) -> list[EmbedData]: ...
+
+class AudioStream:
+    """
+    Embeds audio pushed in chunks, such as from a microphone, while it is transcribed.
+
+    Audio is transcribed and embedded every 30 seconds. Segment start and end times count from
+    the first sample pushed.
+
+    Attributes:
+        audio_decoder: The audio decoder model to use.
+        embedder: The embedding model to use.
+        source: Stored in the `file_name` metadata of the embeddings.
+        config: The configuration for the embedding model.
+
+    Example:
+    ```python
+
+    stream = embed_anything.AudioStream(audio_decoder, embedder, source="meeting")
+    for chunk in microphone_chunks():
+        index(stream.push_bytes(chunk))
+    index(stream.finish())
+    ```
+    """
+
+    def __init__(
+        self,
+        audio_decoder: AudioDecoderModel,
+        embedder: EmbeddingModel,
+        source: str = "stream",
+        config: TextEmbedConfig | None = None,
     ): ...
+    def push(self, samples: list[float]) -> list[EmbedData]:
+        """
+        Adds mono float samples at 16 kHz and returns the embeddings of every full 30 second
+        window.
+        """
+    def push_bytes(self, data: bytes) -> list[EmbedData]:
+        """
+        Adds 16-bit little-endian mono PCM at 16 kHz and returns the embeddings of every full
+        30 second window.
+        """
+    def finish(self) -> list[EmbedData]:
+        """
+        Embeds the audio still buffered, once the input has ended.
+        """
 
 class WhichModel(Enum):
     OpenAI = ("OpenAI",)
@@ -626,6 +1650,8 @@ class WhichModel(Enum):
     Colpali = ("Colpali",)
     ColBert = ("ColBert",)
     SparseBert = ("SparseBert",)
+    BgeM3 = ("BgeM3",)
+    ResNet = ("ResNet",)
 
 class ONNXModel(Enum):
     """
@@ -667,6 +1693,9 @@ class ONNXModel(Enum):
     | `JINAV3`                         | jinaai/jina-embeddings-v3                        |
     | `SPLADEPPENV1`                   | prithivida/Splade_PP_en_v1                      |
     | `SPLADEPPENV2`                   | prithivida/Splade_PP_en_v2                      |
+    | `BGEM3`                          | BAAI/bge-m3, dense output                        |
+    | `BGEM3Sparse`                    | BAAI/bge-m3, sparse lexical weights output       |
+    | `BGEM3ColBERT`                   | BAAI/bge-m3, ColBERT multi-vector output         |
     ```
     """
 
@@ -735,3 +1764,9 @@ class ONNXModel(Enum):
     SPLADEPPENV1 = "SPLADEPPENV1"
 
     SPLADEPPENV2 = "SPLADEPPENV2"
+
+    BGEM3 = "BGEM3"
+
+    BGEM3Sparse = "BGEM3Sparse"
+
+    BGEM3ColBERT = "BGEM3ColBERT"
@@ -1,14 +1,25 @@
 import os
 import re
-import uuid
-from typing import List, Dict
+from typing import Any, Dict, Iterator, List, Optional
 from abc import ABC, abstractmethod
 from ._embed_anything import EmbedData
 
 
 class Adapter(ABC):
-    def __init__(self, api_key: str):
+    """
+    Base class for vector database adapters.
+
+    The embed functions (`embed_file`, `embed_directory`, `embed_image_directory`, ...) call
+    `upsert` with a list of `EmbedData` every time a buffer of embeddings is ready, and return
+    `None` instead of the embeddings. `upsert` converts them with `convert` and writes them to the
+    database in chunks of `batch_size`.
+    """
+
+    def __init__(self, api_key: str, batch_size: int = 100):
+        if batch_size < 1:
+            raise ValueError("batch_size must be at least 1")
         self.api_key = api_key
+        self.batch_size = batch_size
 
     @abstractmethod
     def create_index(self, dimension: int, metric: str, index_name: str, **kwargs):
@@ -26,3 +37,309 @@ class Adapter(ABC):
     def upsert(self, data: List[Dict]):
         data = self.convert(data)
         pass
+
+    def query(self, embedding: List[float], top_k: int) -> List[Dict]:
+        """
+        Searches the index for the chunks closest to the query embedding. Adapters implement this
+        to be used with `Pipeline`.
+
+        Returns:
+            A list of dicts with "text", "score" (higher is better) and optionally "metadata".
+        """
+        raise NotImplementedError(f"{type(self).__name__} does not support query")
+
+    def batches(self, items: List[Any]) -> Iterator[List[Any]]:
+        """
+        Splits `items` into lists of at most `batch_size` items.
+        """
+        for start in range(0, len(items), self.batch_size):
+            yield items[start : start + self.batch_size]
+
+
+def _check_embeddings(embeddings: List[EmbedData]) -> None:
+    # The built-in adapters store one dense vector per chunk.
+    for embedding in embeddings:
+        if embedding.embedding and isinstance(embedding.embedding[0], list):
+            raise ValueError(
+                "Multi-vector embeddings (e.g. ColBERT, ColPali) are not supported by this adapter"
+            )
+
+
+def _file_name(embedding: EmbedData) -> str:
+    metadata = embedding.metadata or {}
+    return re.split(r"/|\\", metadata.get("file_name", ""))[-1]
+
+
+class PineconeAdapter(Adapter):
+    """
+    Adapter for Pinecone. Requires the `pinecone` package.
+    """
+
+    def __init__(self, api_key: Optional[str] = None, batch_size: int = 100):
+        from pinecone import Pinecone
+
+        super().__init__(api_key or os.environ.get("PINECONE_API_KEY"), batch_size)
+        self.pc = Pinecone(api_key=self.api_key)
+        self.index_name = None
+
+    def create_index(
+        self,
+        dimension: int,
+        metric: str = "cosine",
+        index_name: str = "anything",
+        spec=None,
+    ):
+        """
+        Creates a new index and uses it for `upsert` and `query`.
+
+        Args:
+            dimension: The dimensionality of the embeddings.
+            metric: The distance metric to use for similarity search.
+            index_name: The name of the index.
+            spec: The index spec. Defaults to a serverless index on AWS in us-east-1.
+        """
+        if spec is None:
+            from pinecone import ServerlessSpec
+
+            spec = ServerlessSpec(cloud="aws", region="us-east-1")
+        self.index_name = index_name
+        self.pc.create_index(
+            name=index_name, dimension=dimension, metric=metric, spec=spec
+        )
+
+    def use_index(self, index_name: str):
+        """
+        Uses an existing index for `upsert` and `query`.
+        """
+        self.index_name = index_name
+
+    def delete_index(self, index_name: str):
+        self.pc.delete_index(name=index_name)
+
+    def convert(self, embeddings: List[EmbedData]) -> List[Dict]:
+        _check_embeddings(embeddings)
+        return [
+            {
+                "id": embedding.id,
+                "values": embedding.embedding,
+                "metadata": {
+                    **(embedding.metadata or {}),
+                    "text": embedding.text or "",
+                    "file": _file_name(embedding),
+                },
+            }
+            for embedding in embeddings
+        ]
+
+    def upsert(self, data: List[EmbedData]):
+        if not self.index_name:
+            raise ValueError("Index must be created before upserting data")
+        index = self.pc.Index(name=self.index_name)
+        for batch in self.batches(self.convert(data)):
+            index.upsert(vectors=batch)
+
+    def query(self, embedding: List[float], top_k: int) -> List[Dict]:
+        if not self.index_name:
+            raise ValueError("Index must be created before querying")
+        response = self.pc.Index(name=self.index_name).query(
+            vector=embedding, top_k=top_k, include_metadata=True
+        )
+        hits = []
+        for match in response.matches:
+            metadata = {k: str(v) for k, v in (match.metadata or {}).items()}
+            hits.append(
+                {
+                    "text": metadata.pop("text", ""),
+                    "score": match.score,
+                    "metadata": metadata,
+                }
+            )
+        return hits
+
+
+class QdrantAdapter(Adapter):
+    """
+    Adapter for Qdrant. Requires the `qdrant-client` package.
+
+    Args:
+        url: The Qdrant server URL. Use `location=":memory:"` for a local in-memory instance.
+        api_key: The API key for Qdrant Cloud.
+    """
+
+    _DISTANCES = {"cosine": "Cosine", "dot": "Dot", "euclidean": "Euclid"}
+
+    def __init__(
+        self,
+        url: Optional[str] = None,
+        api_key: Optional[str] = None,
+        batch_size: int = 100,
+        **client_kwargs,
+    ):
+        from qdrant_client import QdrantClient
+
+        super().__init__(api_key, batch_size)
+        self.client = QdrantClient(url=url, api_key=api_key, **client_kwargs)
+        self.index_name = None
+
+    def create_index(
+        self, dimension: int, metric: str = "cosine", index_name: str = "anything"
+    ):
+        from qdrant_client import models
+
+        if metric not in self._DISTANCES:
+            raise ValueError(
+                f"Unsupported metric {metric!r}, expected one of {list(self._DISTANCES)}"
+            )
+        self.index_name = index_name
+        self.client.create_collection(
+            collection_name=index_name,
+            vectors_config=models.VectorParams(
+                size=dimension, distance=models.Distance(self._DISTANCES[metric])
+            ),
+        )
+
+    def use_index(self, index_name: str):
+        """
+        Uses an existing collection for `upsert` and `query`.
+        """
+        self.index_name = index_name
+
+    def delete_index(self, index_name: str):
+        self.client.delete_collection(collection_name=index_name)
+
+    def convert(self, embeddings: List[EmbedData]) -> List[Any]:
+        from qdrant_client import models
+
+        _check_embeddings(embeddings)
+        return [
+            models.PointStruct(
+                id=embedding.id,
+                vector=embedding.embedding,
+                payload={
+                    "text": embedding.text or "",
+                    "metadata": embedding.metadata or {},
+                },
+            )
+            for embedding in embeddings
+        ]
+
+    def upsert(self, data: List[EmbedData]):
+        if not self.index_name:
+            raise ValueError("Index must be created before upserting data")
+        for batch in self.batches(self.convert(data)):
+            self.client.upsert(collection_name=self.index_name, points=batch)
+
+    def query(self, embedding: List[float], top_k: int) -> List[Dict]:
+        if not self.index_name:
+            raise ValueError("Index must be created before querying")
+        response = self.client.query_points(
+            collection_name=self.index_name, query=embedding, limit=top_k
+        )
+        return [
+            {
+                "text": point.payload.get("text", ""),
+                "score": point.score,
+                "metadata": point.payload.get("metadata"),
+            }
+            for point in response.points
+        ]
+
+
+class WeaviateAdapter(Adapter):
+    """
+    Adapter for Weaviate Cloud. Requires the `weaviate-client` package (v4).
+    """
+
+    def __init__(self, api_key: str, url: str, batch_size: int = 100):
+        import weaviate
+        import weaviate.classes as wvc
+
+        super().__init__(api_key, batch_size)
+        self.client = weaviate.connect_to_weaviate_cloud(
+            cluster_url=url, auth_credentials=wvc.init.Auth.api_key(api_key)
+        )
+        self.index_name = None
+
+    def create_index(
+        self, dimension: int = None, metric: str = "cosine", index_name: str = "Anything"
+    ):
+        """
+        Creates a collection without a vectorizer. Weaviate infers the dimension from the first
+        upserted vector, so `dimension` is only accepted for compatibility with other adapters.
+        """
+        import weaviate.classes as wvc
+
+        distances = {
+            "cosine": wvc.config.VectorDistances.COSINE,
+            "dot": wvc.config.VectorDistances.DOT,
+            "euclidean": wvc.config.VectorDistances.L2_SQUARED,
+        }
+        if metric not in distances:
+            raise ValueError(
+                f"Unsupported metric {metric!r}, expected one of {list(distances)}"
+            )
+        self.index_name = index_name
+        return self.client.collections.create(
+            index_name,
+            vectorizer_config=wvc.config.Configure.Vectorizer.none(),
+            vector_index_config=wvc.config.Configure.VectorIndex.hnsw(
+                distance_metric=distances[metric]
+            ),
+        )
+
+    def use_index(self, index_name: str):
+        """
+        Uses an existing collection for `upsert` and `query`.
+        """
+        self.index_name = index_name
+
+    def delete_index(self, index_name: str):
+        self.client.collections.delete(index_name)
+
+    def convert(self, embeddings: List[EmbedData]) -> List[Any]:
+        import weaviate.classes as wvc
+
+        _check_embeddings(embeddings)
+        return [
+            wvc.data.DataObject(
+                properties={
+                    **(embedding.metadata or {}),
+                    "text": embedding.text or "",
+                },
+                vector=embedding.embedding,
+            )
+            for embedding in embeddings
+        ]
+
+    def upsert(self, data: List[EmbedData]):
+        if not self.index_name:
+            raise ValueError("Index must be created before upserting data")
+        collection = self.client.collections.get(self.index_name)
+        for batch in self.batches(self.convert(data)):
+            collection.data.insert_many(batch)
+
+    def query(self, embedding: List[float], top_k: int) -> List[Dict]:
+        import weaviate.classes as wvc
+
+        if not self.index_name:
+            raise ValueError("Index must be created before querying")
+        response = self.client.collections.get(self.index_name).query.near_vector(
+            near_vector=embedding,
+            limit=top_k,
+            return_metadata=wvc.query.MetadataQuery(distance=True),
+        )
+        hits = []
+        for obj in response.objects:
+            properties = {k: str(v) for k, v in obj.properties.items()}
+            hits.append(
+                {
+                    "text": properties.pop("text", ""),
+                    # Weaviate reports a distance, lower is better.
+                    "score": -obj.metadata.distance,
+                    "metadata": properties,
+                }
+            )
+        return hits
+
+    def close(self):
+        self.client.close()
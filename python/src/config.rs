@@ -1,8 +1,16 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use embed_anything::text_loader::SplittingStrategy;
+use embed_anything::config::DocumentPooling;
+use embed_anything::embeddings::validation::InvalidEmbeddingPolicy;
+use embed_anything::error::EmbedError;
+use embed_anything::text_loader::{ChunkUnit, SplittingStrategy};
+use embed_anything::warning::Warnings;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
 
+use crate::errors::to_py_err;
 use crate::EmbeddingModel;
 
 #[pyclass]
@@ -14,36 +22,162 @@ pub struct TextEmbedConfig {
 #[pymethods]
 impl TextEmbedConfig {
     #[new]
-    #[pyo3(signature = (chunk_size=None, batch_size=None, buffer_size=None, overlap_ratio=None, splitting_strategy=None, semantic_encoder=None, use_ocr=None))]
+    #[pyo3(signature = (chunk_size=None, batch_size=None, buffer_size=None, overlap_ratio=None, overlap_tokens=None, splitting_strategy=None, semantic_encoder=None, use_ocr=None, metadata_fields=None, max_metadata_length=None, document_pooling=None, length_sorted_batching=None, invalid_embeddings=None, pdf_first_page=None, pdf_last_page=None, split_by_outline=None, table_rows_per_chunk=None, headers_footers=None, footnotes=None, json_text_fields=None, json_metadata_fields=None, streaming_threshold=None, follow_symlinks=None, max_depth=None, shard_index=None, num_shards=None, chunk_unit=None, min_batch_size=None, namespace=None))]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         chunk_size: Option<usize>,
         batch_size: Option<usize>,
         buffer_size: Option<usize>,
         overlap_ratio: Option<f32>,
+        overlap_tokens: Option<usize>,
         splitting_strategy: Option<&str>,
         semantic_encoder: Option<&EmbeddingModel>,
         use_ocr: Option<bool>,
-    ) -> Self {
+        metadata_fields: Option<Vec<String>>,
+        max_metadata_length: Option<usize>,
+        document_pooling: Option<&str>,
+        length_sorted_batching: Option<bool>,
+        invalid_embeddings: Option<&str>,
+        pdf_first_page: Option<usize>,
+        pdf_last_page: Option<usize>,
+        split_by_outline: Option<bool>,
+        table_rows_per_chunk: Option<usize>,
+        headers_footers: Option<bool>,
+        footnotes: Option<bool>,
+        json_text_fields: Option<Vec<String>>,
+        json_metadata_fields: Option<Vec<String>>,
+        streaming_threshold: Option<u64>,
+        follow_symlinks: Option<bool>,
+        max_depth: Option<usize>,
+        shard_index: Option<usize>,
+        num_shards: Option<usize>,
+        chunk_unit: Option<&str>,
+        min_batch_size: Option<usize>,
+        namespace: Option<String>,
+    ) -> PyResult<Self> {
         let strategy = match splitting_strategy {
-            Some(strategy) => match strategy {
-                "sentence" => Some(SplittingStrategy::Sentence),
-                "semantic" => Some(SplittingStrategy::Semantic),
-                _ => None,
-            },
+            Some("sentence") => Some(SplittingStrategy::Sentence),
+            Some("semantic") => Some(SplittingStrategy::Semantic),
+            Some("content_defined") => Some(SplittingStrategy::ContentDefined),
+            Some(strategy) => {
+                return Err(PyValueError::new_err(format!(
+                    "splitting_strategy must be sentence, semantic or content_defined, got '{}'",
+                    strategy
+                )))
+            }
             None => None,
         };
-        let semantic_encoder = semantic_encoder.map(|model| Arc::clone(&model.inner));
-        Self {
-            inner: embed_anything::config::TextEmbedConfig::new(
-                chunk_size,
-                batch_size,
-                buffer_size,
-                overlap_ratio,
-                strategy,
-                semantic_encoder,
-                use_ocr,
-            ),
+        let mut builder = embed_anything::config::TextEmbedConfig::builder();
+        if let Some(chunk_size) = chunk_size {
+            builder = builder.with_chunk_size(chunk_size);
         }
+        if let Some(batch_size) = batch_size {
+            builder = builder.with_batch_size(batch_size);
+        }
+        if let Some(buffer_size) = buffer_size {
+            builder = builder.with_buffer_size(buffer_size);
+        }
+        if let Some(overlap_ratio) = overlap_ratio {
+            builder = builder.with_overlap_ratio(overlap_ratio);
+        }
+        if let Some(overlap_tokens) = overlap_tokens {
+            builder = builder.with_overlap_tokens(overlap_tokens);
+        }
+        if let Some(strategy) = strategy {
+            builder = builder.with_splitting_strategy(strategy);
+        }
+        if let Some(unit) = chunk_unit {
+            builder = builder.with_chunk_unit(match unit {
+                "tokens" => ChunkUnit::Tokens,
+                "characters" => ChunkUnit::Characters,
+                unit => {
+                    return Err(PyValueError::new_err(format!(
+                        "chunk_unit must be tokens or characters, got '{}'",
+                        unit
+                    )))
+                }
+            });
+        }
+        if let Some(model) = semantic_encoder {
+            builder = builder.with_semantic_encoder(Arc::clone(model.embedder()?));
+        }
+        if let Some(use_ocr) = use_ocr {
+            builder = builder.with_ocr(use_ocr);
+        }
+        if metadata_fields.is_some() || max_metadata_length.is_some() || namespace.is_some() {
+            builder = builder.with_metadata_config(embed_anything::config::MetadataConfig {
+                fields: metadata_fields,
+                max_value_length: max_metadata_length,
+                namespace,
+            });
+        }
+        if let Some(pooling) = document_pooling {
+            builder = builder.with_document_pooling(match pooling {
+                "mean" => DocumentPooling::Mean,
+                "weighted" => DocumentPooling::Weighted,
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "document_pooling must be 'mean' or 'weighted', got '{}'",
+                        pooling
+                    )))
+                }
+            });
+        }
+        if let Some(length_sorted_batching) = length_sorted_batching {
+            builder = builder.with_length_sorted_batching(length_sorted_batching);
+        }
+        if let Some(min_batch_size) = min_batch_size {
+            builder = builder.with_min_batch_size(min_batch_size);
+        }
+        if let Some(policy) = invalid_embeddings {
+            builder = builder.with_invalid_embeddings(match policy {
+                "keep" => InvalidEmbeddingPolicy::Keep,
+                "drop" => InvalidEmbeddingPolicy::Drop,
+                "retry" => InvalidEmbeddingPolicy::Retry,
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "invalid_embeddings must be 'keep', 'drop' or 'retry', got '{}'",
+                        policy
+                    )))
+                }
+            });
+        }
+        if pdf_first_page.is_some() || pdf_last_page.is_some() {
+            builder = builder.with_pdf_page_range(pdf_first_page.unwrap_or(1), pdf_last_page);
+        }
+        if let Some(split_by_outline) = split_by_outline {
+            builder = builder.with_split_by_outline(split_by_outline);
+        }
+        if let Some(rows) = table_rows_per_chunk {
+            builder = builder.with_table_rows_per_chunk(rows);
+        }
+        if let Some(include) = headers_footers {
+            builder = builder.with_headers_footers(include);
+        }
+        if let Some(include) = footnotes {
+            builder = builder.with_footnotes(include);
+        }
+        if let Some(fields) = json_text_fields {
+            builder = builder.with_json_text_fields(fields);
+        }
+        if let Some(fields) = json_metadata_fields {
+            builder = builder.with_json_metadata_fields(fields);
+        }
+        if let Some(bytes) = streaming_threshold {
+            builder = builder.with_streaming_threshold(bytes);
+        }
+        if let Some(follow_symlinks) = follow_symlinks {
+            builder = builder.with_follow_symlinks(follow_symlinks);
+        }
+        if let Some(max_depth) = max_depth {
+            builder = builder.with_max_depth(max_depth);
+        }
+        if let Some((index, count)) = shard(shard_index, num_shards)? {
+            builder = builder.with_shard(index, count);
+        }
+        builder = builder.with_warnings(Warnings::new());
+        let inner = builder.build().map_err(|e| to_py_err(e.into()))?;
+        Ok(Self { inner })
     }
 
     #[getter]
@@ -55,6 +189,230 @@ impl TextEmbedConfig {
     pub fn batch_size(&self) -> Option<usize> {
         self.inner.batch_size
     }
+
+    #[getter]
+    pub fn buffer_size(&self) -> Option<usize> {
+        self.inner.buffer_size
+    }
+
+    #[getter]
+    pub fn overlap_ratio(&self) -> Option<f32> {
+        self.inner.overlap_ratio
+    }
+
+    #[getter]
+    pub fn overlap_tokens(&self) -> Option<usize> {
+        self.inner.overlap_tokens
+    }
+
+    #[getter]
+    pub fn splitting_strategy(&self) -> Option<&'static str> {
+        self.inner
+            .splitting_strategy
+            .map(|strategy| match strategy {
+                SplittingStrategy::Sentence => "sentence",
+                SplittingStrategy::Semantic => "semantic",
+                SplittingStrategy::ContentDefined => "content_defined",
+            })
+    }
+
+    #[getter]
+    pub fn chunk_unit(&self) -> Option<&'static str> {
+        self.inner.chunk_unit.map(|unit| match unit {
+            ChunkUnit::Tokens => "tokens",
+            ChunkUnit::Characters => "characters",
+        })
+    }
+
+    #[getter]
+    pub fn semantic_encoder(&self) -> Option<EmbeddingModel> {
+        self.inner
+            .semantic_encoder
+            .as_ref()
+            .map(|encoder| EmbeddingModel {
+                inner: Some(Arc::clone(encoder)),
+            })
+    }
+
+    #[getter]
+    pub fn use_ocr(&self) -> Option<bool> {
+        self.inner.use_ocr
+    }
+
+    #[getter]
+    pub fn metadata_fields(&self) -> Option<Vec<String>> {
+        self.inner
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.fields.clone())
+    }
+
+    #[getter]
+    pub fn max_metadata_length(&self) -> Option<usize> {
+        self.inner
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.max_value_length)
+    }
+
+    #[getter]
+    pub fn namespace(&self) -> Option<String> {
+        self.inner
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.namespace.clone())
+    }
+
+    #[getter]
+    pub fn document_pooling(&self) -> Option<&'static str> {
+        self.inner.document_pooling.map(|pooling| match pooling {
+            DocumentPooling::Mean => "mean",
+            DocumentPooling::Weighted => "weighted",
+        })
+    }
+
+    #[getter]
+    pub fn length_sorted_batching(&self) -> Option<bool> {
+        self.inner.length_sorted_batching
+    }
+
+    #[getter]
+    pub fn min_batch_size(&self) -> usize {
+        self.inner.backoff.min_batch_size()
+    }
+
+    #[getter]
+    pub fn invalid_embeddings(&self) -> &'static str {
+        match self.inner.invalid_embeddings {
+            InvalidEmbeddingPolicy::Keep => "keep",
+            InvalidEmbeddingPolicy::Drop => "drop",
+            InvalidEmbeddingPolicy::Retry => "retry",
+        }
+    }
+
+    #[getter]
+    pub fn pdf_first_page(&self) -> Option<usize> {
+        self.inner.pdf.first_page
+    }
+
+    #[getter]
+    pub fn pdf_last_page(&self) -> Option<usize> {
+        self.inner.pdf.last_page
+    }
+
+    #[getter]
+    pub fn split_by_outline(&self) -> bool {
+        self.inner.pdf.split_by_outline
+    }
+
+    #[getter]
+    pub fn table_rows_per_chunk(&self) -> Option<usize> {
+        self.inner.table_rows_per_chunk
+    }
+
+    #[getter]
+    pub fn headers_footers(&self) -> Option<bool> {
+        if self.inner.docx.headers_footers {
+            Some(true)
+        } else if self.inner.pdf.strip_headers_footers {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    #[getter]
+    pub fn footnotes(&self) -> bool {
+        self.inner.docx.footnotes
+    }
+
+    #[getter]
+    pub fn json_text_fields(&self) -> Vec<String> {
+        self.inner.json.text_fields.clone()
+    }
+
+    #[getter]
+    pub fn json_metadata_fields(&self) -> Vec<String> {
+        self.inner.json.metadata_fields.clone()
+    }
+
+    #[getter]
+    pub fn streaming_threshold(&self) -> Option<u64> {
+        self.inner.streaming_threshold
+    }
+
+    #[getter]
+    pub fn follow_symlinks(&self) -> bool {
+        self.inner.walk.follow_symlinks
+    }
+
+    #[getter]
+    pub fn max_depth(&self) -> Option<usize> {
+        self.inner.walk.max_depth
+    }
+
+    #[getter]
+    pub fn shard_index(&self) -> Option<usize> {
+        self.inner.walk.shard.map(|shard| shard.index)
+    }
+
+    #[getter]
+    pub fn num_shards(&self) -> Option<usize> {
+        self.inner.walk.shard.map(|shard| shard.count)
+    }
+
+    /// Returns the warnings of the runs that used this config since the last call, e.g. files
+    /// without text, as dicts with "kind", "source" and "message".
+    pub fn take_warnings<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let Some(warnings) = &self.inner.warnings else {
+            return Ok(Vec::new());
+        };
+        warnings
+            .take()
+            .into_iter()
+            .map(|warning| {
+                let dict = PyDict::new(py);
+                dict.set_item("kind", warning.kind())?;
+                dict.set_item("source", warning.source())?;
+                dict.set_item("message", warning.to_string())?;
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let fields = PyDict::new(py);
+        fields.set_item("chunk_size", self.chunk_size())?;
+        fields.set_item("batch_size", self.batch_size())?;
+        fields.set_item("buffer_size", self.buffer_size())?;
+        fields.set_item("overlap_ratio", self.overlap_ratio())?;
+        fields.set_item("overlap_tokens", self.overlap_tokens())?;
+        fields.set_item("splitting_strategy", self.splitting_strategy())?;
+        fields.set_item("chunk_unit", self.chunk_unit())?;
+        fields.set_item("semantic_encoder", self.semantic_encoder())?;
+        fields.set_item("use_ocr", self.use_ocr())?;
+        fields.set_item("metadata_fields", self.metadata_fields())?;
+        fields.set_item("max_metadata_length", self.max_metadata_length())?;
+        fields.set_item("namespace", self.namespace())?;
+        fields.set_item("document_pooling", self.document_pooling())?;
+        fields.set_item("length_sorted_batching", self.length_sorted_batching())?;
+        fields.set_item("min_batch_size", self.min_batch_size())?;
+        fields.set_item("invalid_embeddings", self.invalid_embeddings())?;
+        fields.set_item("pdf_first_page", self.pdf_first_page())?;
+        fields.set_item("pdf_last_page", self.pdf_last_page())?;
+        fields.set_item("split_by_outline", self.split_by_outline())?;
+        fields.set_item("table_rows_per_chunk", self.table_rows_per_chunk())?;
+        fields.set_item("headers_footers", self.headers_footers())?;
+        fields.set_item("footnotes", self.footnotes())?;
+        fields.set_item("json_text_fields", self.json_text_fields())?;
+        fields.set_item("json_metadata_fields", self.json_metadata_fields())?;
+        fields.set_item("streaming_threshold", self.streaming_threshold())?;
+        fields.set_item("follow_symlinks", self.follow_symlinks())?;
+        fields.set_item("max_depth", self.max_depth())?;
+        fields.set_item("shard_index", self.shard_index())?;
+        fields.set_item("num_shards", self.num_shards())?;
+        repr_fields("TextEmbedConfig", &fields)
+    }
 }
 
 #[pyclass]
@@ -66,15 +424,174 @@ pub struct ImageEmbedConfig {
 #[pymethods]
 impl ImageEmbedConfig {
     #[new]
-    #[pyo3(signature = (buffer_size=None))]
-    pub fn new(buffer_size: Option<usize>) -> Self {
-        Self {
-            inner: embed_anything::config::ImageEmbedConfig::new(buffer_size),
+    #[pyo3(signature = (
+        buffer_size=None,
+        max_download_bytes=None,
+        download_timeout=None,
+        pdf_dpi=None,
+        pdf_grayscale=None,
+        pdf_first_page=None,
+        pdf_last_page=None,
+        follow_symlinks=None,
+        max_depth=None,
+        shard_index=None,
+        num_shards=None,
+    ))]
+    pub fn new(
+        buffer_size: Option<usize>,
+        max_download_bytes: Option<u64>,
+        download_timeout: Option<f64>,
+        pdf_dpi: Option<u32>,
+        pdf_grayscale: Option<bool>,
+        pdf_first_page: Option<usize>,
+        pdf_last_page: Option<usize>,
+        follow_symlinks: Option<bool>,
+        max_depth: Option<usize>,
+        shard_index: Option<usize>,
+        num_shards: Option<usize>,
+    ) -> PyResult<Self> {
+        let mut inner = embed_anything::config::ImageEmbedConfig::new(buffer_size);
+        if let Some(max_download_bytes) = max_download_bytes {
+            inner = inner.with_max_download_bytes(max_download_bytes);
+        }
+        if let Some(download_timeout) = download_timeout {
+            inner = inner.with_download_timeout(Duration::from_secs_f64(download_timeout));
+        }
+        if let Some(pdf_dpi) = pdf_dpi {
+            inner = inner.with_pdf_dpi(pdf_dpi);
+        }
+        if let Some(pdf_grayscale) = pdf_grayscale {
+            inner = inner.with_pdf_grayscale(pdf_grayscale);
+        }
+        if pdf_first_page.is_some() || pdf_last_page.is_some() {
+            inner = inner.with_pdf_page_range(pdf_first_page.unwrap_or(1), pdf_last_page);
         }
+        if let Some(follow_symlinks) = follow_symlinks {
+            inner = inner.with_follow_symlinks(follow_symlinks);
+        }
+        if let Some(max_depth) = max_depth {
+            if max_depth == 0 {
+                return Err(to_py_err(
+                    EmbedError::InvalidConfig(
+                        "max_depth must be at least 1 to find the files of the directory"
+                            .to_string(),
+                    )
+                    .into(),
+                ));
+            }
+            inner = inner.with_max_depth(max_depth);
+        }
+        if let Some((index, count)) = shard(shard_index, num_shards)? {
+            if index >= count {
+                return Err(to_py_err(
+                    EmbedError::InvalidConfig(format!(
+                        "shard_index ({}) must be smaller than num_shards ({})",
+                        index, count
+                    ))
+                    .into(),
+                ));
+            }
+            inner = inner.with_shard(index, count);
+        }
+        Ok(Self { inner })
     }
 
     #[getter]
     pub fn buffer_size(&self) -> Option<usize> {
         self.inner.buffer_size
     }
+
+    #[getter]
+    pub fn max_download_bytes(&self) -> Option<u64> {
+        self.inner.max_download_bytes
+    }
+
+    #[getter]
+    pub fn download_timeout(&self) -> Option<f64> {
+        self.inner
+            .download_timeout
+            .map(|timeout| timeout.as_secs_f64())
+    }
+
+    #[getter]
+    pub fn pdf_dpi(&self) -> Option<u32> {
+        self.inner.pdf_dpi
+    }
+
+    #[getter]
+    pub fn pdf_grayscale(&self) -> Option<bool> {
+        self.inner.pdf_grayscale
+    }
+
+    #[getter]
+    pub fn pdf_first_page(&self) -> Option<usize> {
+        self.inner.pdf_first_page
+    }
+
+    #[getter]
+    pub fn pdf_last_page(&self) -> Option<usize> {
+        self.inner.pdf_last_page
+    }
+
+    #[getter]
+    pub fn follow_symlinks(&self) -> bool {
+        self.inner.walk.follow_symlinks
+    }
+
+    #[getter]
+    pub fn max_depth(&self) -> Option<usize> {
+        self.inner.walk.max_depth
+    }
+
+    #[getter]
+    pub fn shard_index(&self) -> Option<usize> {
+        self.inner.walk.shard.map(|shard| shard.index)
+    }
+
+    #[getter]
+    pub fn num_shards(&self) -> Option<usize> {
+        self.inner.walk.shard.map(|shard| shard.count)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let fields = PyDict::new(py);
+        fields.set_item("buffer_size", self.buffer_size())?;
+        fields.set_item("max_download_bytes", self.max_download_bytes())?;
+        fields.set_item("download_timeout", self.download_timeout())?;
+        fields.set_item("pdf_dpi", self.pdf_dpi())?;
+        fields.set_item("pdf_grayscale", self.pdf_grayscale())?;
+        fields.set_item("pdf_first_page", self.pdf_first_page())?;
+        fields.set_item("pdf_last_page", self.pdf_last_page())?;
+        fields.set_item("follow_symlinks", self.follow_symlinks())?;
+        fields.set_item("max_depth", self.max_depth())?;
+        fields.set_item("shard_index", self.shard_index())?;
+        fields.set_item("num_shards", self.num_shards())?;
+        repr_fields("ImageEmbedConfig", &fields)
+    }
+}
+
+/// The shard set by `shard_index` and `num_shards`, which are given together or not at all.
+fn shard(
+    shard_index: Option<usize>,
+    num_shards: Option<usize>,
+) -> PyResult<Option<(usize, usize)>> {
+    match (shard_index, num_shards) {
+        (Some(index), Some(count)) => Ok(Some((index, count))),
+        (None, None) => Ok(None),
+        _ => Err(to_py_err(
+            EmbedError::InvalidConfig(
+                "shard_index and num_shards must be given together".to_string(),
+            )
+            .into(),
+        )),
+    }
+}
+
+/// Formats a config like a dataclass, e.g. `ImageEmbedConfig(buffer_size=100, ...)`.
+fn repr_fields(name: &str, fields: &Bound<'_, PyDict>) -> PyResult<String> {
+    let fields = fields
+        .iter()
+        .map(|(key, value)| Ok(format!("{}={}", key, value.repr()?)))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(format!("{}({})", name, fields.join(", ")))
 }
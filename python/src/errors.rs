@@ -0,0 +1,55 @@
+use embed_anything::error::EmbedError;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyFileNotFoundError, PyIOError, PyValueError};
+use pyo3::prelude::*;
+
+// The base class derives from ValueError, which is what the bindings raised before.
+create_exception!(embed_anything, EmbedAnythingError, PyValueError);
+create_exception!(embed_anything, ModelLoadError, EmbedAnythingError);
+create_exception!(embed_anything, UnsupportedModelError, EmbedAnythingError);
+create_exception!(embed_anything, TokenizationError, EmbedAnythingError);
+create_exception!(embed_anything, InferenceError, EmbedAnythingError);
+create_exception!(embed_anything, NetworkError, EmbedAnythingError);
+create_exception!(embed_anything, RateLimitedError, NetworkError);
+create_exception!(embed_anything, UnsupportedFileError, EmbedAnythingError);
+create_exception!(embed_anything, InvalidConfigError, EmbedAnythingError);
+
+/// Maps an error from the core crate to the matching Python exception class.
+pub fn to_py_err(error: anyhow::Error) -> PyErr {
+    let message = error.to_string();
+    match EmbedError::from_anyhow(&error) {
+        Some(EmbedError::ModelLoad(_)) => ModelLoadError::new_err(message),
+        Some(EmbedError::UnsupportedModel(_)) => UnsupportedModelError::new_err(message),
+        Some(EmbedError::Tokenization(_)) => TokenizationError::new_err(message),
+        Some(EmbedError::Inference(_)) => InferenceError::new_err(message),
+        Some(EmbedError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            PyFileNotFoundError::new_err(message)
+        }
+        Some(EmbedError::Io(_)) => PyIOError::new_err(message),
+        Some(EmbedError::Network(_)) => NetworkError::new_err(message),
+        Some(EmbedError::RateLimited(_)) => RateLimitedError::new_err(message),
+        Some(EmbedError::UnsupportedFile(_)) => UnsupportedFileError::new_err(message),
+        Some(EmbedError::InvalidConfig(_)) => InvalidConfigError::new_err(message),
+        None => EmbedAnythingError::new_err(message),
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("EmbedAnythingError", py.get_type::<EmbedAnythingError>())?;
+    m.add("ModelLoadError", py.get_type::<ModelLoadError>())?;
+    m.add(
+        "UnsupportedModelError",
+        py.get_type::<UnsupportedModelError>(),
+    )?;
+    m.add("TokenizationError", py.get_type::<TokenizationError>())?;
+    m.add("InferenceError", py.get_type::<InferenceError>())?;
+    m.add("NetworkError", py.get_type::<NetworkError>())?;
+    m.add("RateLimitedError", py.get_type::<RateLimitedError>())?;
+    m.add(
+        "UnsupportedFileError",
+        py.get_type::<UnsupportedFileError>(),
+    )?;
+    m.add("InvalidConfigError", py.get_type::<InvalidConfigError>())?;
+    Ok(())
+}
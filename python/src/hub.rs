@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use embed_anything::hub::HubConfig;
+use pyo3::prelude::*;
+
+/// Configures how model files are downloaded from the Hugging Face Hub. Applies to every model
+/// loaded after the call.
+#[pyfunction]
+#[pyo3(signature = (endpoint=None, proxy=None, connect_timeout=None, cache_dir=None, token=None, progress=None, allowed_models=None))]
+pub fn set_hub_config(
+    endpoint: Option<&str>,
+    proxy: Option<&str>,
+    connect_timeout: Option<f64>,
+    cache_dir: Option<PathBuf>,
+    token: Option<&str>,
+    progress: Option<PyObject>,
+    allowed_models: Option<Vec<String>>,
+) {
+    let mut config = HubConfig::new();
+    if let Some(endpoint) = endpoint {
+        config = config.with_endpoint(endpoint);
+    }
+    if let Some(proxy) = proxy {
+        config = config.with_proxy(proxy);
+    }
+    if let Some(connect_timeout) = connect_timeout {
+        config = config.with_connect_timeout(Duration::from_secs_f64(connect_timeout));
+    }
+    if let Some(cache_dir) = cache_dir {
+        config = config.with_cache_dir(cache_dir);
+    }
+    if let Some(token) = token {
+        config = config.with_token(token);
+    }
+    if let Some(progress) = progress {
+        config = config.with_progress(Arc::new(move |file, downloaded, total| {
+            Python::with_gil(|py| {
+                if let Err(e) = progress.call1(py, (file, downloaded, total)) {
+                    e.print(py);
+                }
+            });
+        }));
+    }
+    if let Some(allowed_models) = allowed_models {
+        config = config.with_allowed_models(allowed_models);
+    }
+    embed_anything::hub::set_hub_config(config);
+}
+
+#[pyclass]
+pub struct PrefetchReport {
+    pub inner: embed_anything::hub::PrefetchReport,
+}
+
+#[pymethods]
+impl PrefetchReport {
+    #[getter]
+    pub fn files(&self) -> Vec<String> {
+        self.inner
+            .files
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[getter]
+    pub fn total_bytes(&self) -> u64 {
+        self.inner.total_bytes
+    }
+
+    #[getter]
+    pub fn downloaded_bytes(&self) -> u64 {
+        self.inner.downloaded_bytes
+    }
+}
@@ -1,28 +1,44 @@
 pub mod config;
+pub mod errors;
+pub mod hub;
+pub mod logging;
 pub mod models;
+pub mod pipeline;
+use embed_anything::embeddings::cloud::cohere::{CohereEmbedder, ContentPart};
+use embed_anything::embeddings::cloud::http::HttpClientConfig;
+use embed_anything::embeddings::cloud::openai::OpenAIEmbedder;
+use embed_anything::embeddings::cloud::truncation::TruncationPolicy;
+use embed_anything::embeddings::clustering::{Distance, KMeansConfig};
 use embed_anything::embeddings::embed::{TextEmbedder, VisionEmbedder};
+use embed_anything::embeddings::local::token_pooling::TokenPooledColPali;
+use embed_anything::embeddings::projection;
+use embed_anything::embeddings::prompts::PromptTemplate;
+use embed_anything::embeddings::quality::{self, QualityConfig};
+use embed_anything::embeddings::ComputeDevice;
 use embed_anything::{
     self,
     config::TextEmbedConfig,
     emb_audio,
     embeddings::embed::{Embedder, EmbeddingResult},
     file_processor::audio::audio_processor,
-    text_loader::FileLoadingError,
 };
+use errors::to_py_err;
 use models::colbert::ColbertModel;
 use models::colpali::ColpaliModel;
 use models::reranker::{DocumentRank, Dtype, Reranker, RerankerResult};
 use pyo3::{
-    exceptions::{PyFileNotFoundError, PyValueError},
+    exceptions::{PyFileNotFoundError, PyRuntimeError, PyValueError},
     prelude::*,
-    types::PyList,
+    types::{PyDict, PyList},
 };
 use std::fmt;
 use std::str::FromStr;
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{mpsc, Arc, Mutex},
+    thread::JoinHandle,
+    time::Duration,
 };
 use strum::EnumString;
 use tokio::runtime::Builder;
@@ -32,6 +48,16 @@ pub struct EmbedData {
     pub inner: embed_anything::embeddings::embed::EmbedData,
 }
 
+fn to_embed_data(encodings: Vec<EmbeddingResult>, texts: Vec<String>) -> Vec<EmbedData> {
+    encodings
+        .into_iter()
+        .zip(texts)
+        .map(|(embedding, text)| EmbedData {
+            inner: embed_anything::embeddings::embed::EmbedData::new(embedding, Some(text), None),
+        })
+        .collect()
+}
+
 #[pymethods]
 impl EmbedData {
     #[getter(embedding)]
@@ -59,6 +85,12 @@ impl EmbedData {
         self.inner.metadata.clone()
     }
 
+    /// The stable ID of the chunk, derived from its source and text.
+    #[getter(id)]
+    fn id(&self) -> String {
+        self.inner.id()
+    }
+
     #[setter(text)]
     fn set_text(&mut self, text: Option<String>) {
         self.inner.text = text;
@@ -69,6 +101,19 @@ impl EmbedData {
         self.inner.metadata = metadata;
     }
 
+    /// Serializes to the versioned JSON form of the core crate.
+    fn to_json(&self) -> PyResult<String> {
+        self.inner.to_json().map_err(to_py_err)
+    }
+
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        Ok(EmbedData {
+            inner: embed_anything::embeddings::embed::EmbedData::from_json(json)
+                .map_err(to_py_err)?,
+        })
+    }
+
     fn __str__(&self) -> String {
         format!(
             "EmbedData(embedding: {:?}, text: {:?}, metadata: {:?})",
@@ -83,6 +128,31 @@ impl EmbedData {
     }
 }
 
+#[pyclass]
+pub struct Chunk {
+    pub inner: embed_anything::text_loader::Chunk,
+}
+
+#[pymethods]
+impl Chunk {
+    #[getter(text)]
+    fn text(&self) -> String {
+        self.inner.text.clone()
+    }
+
+    #[getter(metadata)]
+    fn metadata(&self) -> Option<HashMap<String, String>> {
+        self.inner.metadata.clone()
+    }
+
+    fn __str__(&self) -> String {
+        format!(
+            "Chunk(text: {:?}, metadata: {:?})",
+            self.inner.text, self.inner.metadata
+        )
+    }
+}
+
 #[pyclass(eq, eq_int)]
 #[derive(PartialEq)]
 pub enum WhichModel {
@@ -94,6 +164,8 @@ pub enum WhichModel {
     Clip,
     Jina,
     Colpali,
+    BgeM3,
+    ResNet,
 }
 
 #[pyclass(eq, eq_int)]
@@ -132,6 +204,9 @@ pub enum ONNXModel {
     JINAV3,
     SPLADEPPENV1,
     SPLADEPPENV2,
+    BGEM3,
+    BGEM3Sparse,
+    BGEM3ColBERT,
 }
 impl fmt::Display for ONNXModel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -150,6 +225,8 @@ impl From<&str> for WhichModel {
             "clip" | "Clip" => WhichModel::Clip,
             "jina" | "Jina" => WhichModel::Jina,
             "colpali" | "Colpali" => WhichModel::Colpali,
+            "bge-m3" | "BgeM3" => WhichModel::BgeM3,
+            "resnet" | "ResNet" => WhichModel::ResNet,
             _ => panic!("Invalid model"),
         }
     }
@@ -165,87 +242,219 @@ impl From<String> for WhichModel {
             "clip" | "Clip" => WhichModel::Clip,
             "jina" | "Jina" => WhichModel::Jina,
             "colpali" | "Colpali" => WhichModel::Colpali,
+            "bge-m3" | "BgeM3" => WhichModel::BgeM3,
+            "resnet" | "ResNet" => WhichModel::ResNet,
             "colbert" | "Colbert" => WhichModel::ColBert,
             _ => panic!("Invalid model"),
         }
     }
 }
 
+/// Parses the `device` argument of the model constructors: "auto", "cpu", "cuda:N" or "metal:N".
+pub(crate) fn parse_device(device: Option<&str>) -> PyResult<ComputeDevice> {
+    device
+        .map_or(Ok(ComputeDevice::Auto), ComputeDevice::from_str)
+        .map_err(|e| to_py_err(e.into()))
+}
+
+/// Builds the prompt template that replaces the preset picked from the model id. Passing only
+/// one of the prompts leaves the other empty.
+fn prompt_override(
+    model: &WhichModel,
+    query_prompt: Option<&str>,
+    document_prompt: Option<&str>,
+) -> PyResult<Option<PromptTemplate>> {
+    if query_prompt.is_none() && document_prompt.is_none() {
+        return Ok(None);
+    }
+    if *model != WhichModel::Bert {
+        return Err(PyValueError::new_err(
+            "query_prompt and document_prompt are only supported for Bert models",
+        ));
+    }
+    Ok(Some(PromptTemplate::new(
+        query_prompt.unwrap_or_default(),
+        document_prompt.unwrap_or_default(),
+    )))
+}
+
 #[pyclass]
 pub struct EmbeddingModel {
-    pub inner: Arc<Embedder>,
+    /// `None` once the model has been closed.
+    pub inner: Option<Arc<Embedder>>,
+}
+
+impl EmbeddingModel {
+    /// Returns the loaded embedder, or an error if the model has been closed.
+    pub fn embedder(&self) -> PyResult<&Arc<Embedder>> {
+        self.inner
+            .as_ref()
+            .ok_or_else(|| PyValueError::new_err("The model has been closed"))
+    }
 }
 
 #[pymethods]
 impl EmbeddingModel {
     #[staticmethod]
-    #[pyo3(signature = (model, model_id, revision=None))]
+    #[pyo3(signature = (model_id, revision=None, files=None))]
+    fn prefetch(
+        model_id: &str,
+        revision: Option<&str>,
+        files: Option<Vec<String>>,
+    ) -> PyResult<hub::PrefetchReport> {
+        let files = files
+            .as_ref()
+            .map(|files| files.iter().map(String::as_str).collect::<Vec<_>>());
+        let report = Embedder::prefetch(model_id, revision, files.as_deref()).map_err(to_py_err)?;
+        Ok(hub::PrefetchReport { inner: report })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (model, model_id, revision=None, dtype=None, query_prompt=None, document_prompt=None, device=None, timing=false, max_vectors=None, flash_attn=None))]
+    #[allow(clippy::too_many_arguments)]
     fn from_pretrained_hf(
         model: &WhichModel,
         model_id: Option<&str>,
         revision: Option<&str>,
+        dtype: Option<&Dtype>,
+        query_prompt: Option<&str>,
+        document_prompt: Option<&str>,
+        device: Option<&str>,
+        timing: bool,
+        max_vectors: Option<usize>,
+        flash_attn: Option<bool>,
     ) -> PyResult<Self> {
+        let prompts = prompt_override(model, query_prompt, document_prompt)?;
+        let device = parse_device(device)?;
+        if timing && !matches!(model, WhichModel::Bert | WhichModel::Jina) {
+            return Err(PyValueError::new_err(
+                "timing is only supported for Bert and Jina models",
+            ));
+        }
+        if flash_attn == Some(true) && !matches!(model, WhichModel::Jina) {
+            return Err(PyValueError::new_err(
+                "flash_attn is only supported for Jina models",
+            ));
+        }
+        if max_vectors.is_some() && !matches!(model, WhichModel::Colpali) {
+            return Err(PyValueError::new_err(
+                "max_vectors is only supported for Colpali models",
+            ));
+        }
+        let dtype = match dtype {
+            Some(Dtype::F16) => Some(embed_anything::Dtype::F16),
+            Some(Dtype::BF16) => Some(embed_anything::Dtype::BF16),
+            Some(Dtype::F32) => Some(embed_anything::Dtype::F32),
+            Some(_) => {
+                return Err(PyValueError::new_err(
+                    "Only F16, BF16 and F32 are supported for Candle models",
+                ))
+            }
+            None => None,
+        };
         // let model = WhichModel::from(model);
         match model {
             WhichModel::Bert => {
                 let model_id = model_id.unwrap_or("sentence-transformers/all-MiniLM-L12-v2");
-                let model = Embedder::Text(TextEmbedder::Bert(Box::new(
-                    embed_anything::embeddings::local::bert::BertEmbedder::new(
+                let mut embedder =
+                    embed_anything::embeddings::local::bert::BertEmbedder::new_on_device(
                         model_id.to_string(),
                         revision.map(|s| s.to_string()),
+                        dtype,
+                        device,
                     )
-                    .unwrap(),
-                )));
+                    .map_err(to_py_err)?;
+                if let Some(prompts) = prompts {
+                    embedder = embedder.with_prompts(Some(prompts));
+                }
+                if timing {
+                    embedder = embedder.with_timing();
+                }
+                let model = Embedder::Text(TextEmbedder::Bert(Box::new(embedder)));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             WhichModel::SparseBert => {
                 let model_id = model_id.unwrap_or("prithivida/Splade_PP_en_v1");
                 let model = Embedder::Text(TextEmbedder::Bert(Box::new(
-                    embed_anything::embeddings::local::bert::SparseBertEmbedder::new(
+                    embed_anything::embeddings::local::bert::SparseBertEmbedder::new_on_device(
                         model_id.to_string(),
                         revision.map(|s| s.to_string()),
+                        dtype,
+                        device,
                     )
-                    .unwrap(),
+                    .map_err(to_py_err)?,
                 )));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             WhichModel::Clip => {
                 let model_id = model_id.unwrap_or("openai/clip-vit-base-patch32");
                 let model = Embedder::Vision(VisionEmbedder::Clip(
-                    embed_anything::embeddings::local::clip::ClipEmbedder::new(
+                    embed_anything::embeddings::local::clip::ClipEmbedder::new_on_device(
                         model_id.to_string(),
                         revision,
+                        dtype,
+                        device,
                     )
-                    .map_err(|e| PyValueError::new_err(e.to_string()))?,
+                    .map_err(to_py_err)?,
                 ));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             WhichModel::Jina => {
                 let model_id = model_id.unwrap_or("jinaai/jina-embeddings-v2-small-en");
-                let model = Embedder::Text(TextEmbedder::Jina(Box::new(
-                    embed_anything::embeddings::local::jina::JinaEmbedder::new(model_id, revision)
-                        .unwrap(),
-                )));
+                let mut embedder =
+                    embed_anything::embeddings::local::jina::JinaEmbedder::new_on_device(
+                        model_id,
+                        revision,
+                        dtype,
+                        flash_attn.unwrap_or(
+                            cfg!(feature = "flash-attn")
+                                && matches!(
+                                    dtype,
+                                    Some(embed_anything::Dtype::F16 | embed_anything::Dtype::BF16)
+                                ),
+                        ),
+                        device,
+                    )
+                    .map_err(to_py_err)?;
+                if timing {
+                    embedder = embedder.with_timing();
+                }
+                let model = Embedder::Text(TextEmbedder::Jina(Box::new(embedder)));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             WhichModel::Colpali => {
                 let model_id = model_id.unwrap_or("vidore/colpali-v1.2-merged");
-                let model = Embedder::Vision(VisionEmbedder::ColPali(Box::new(
-                    embed_anything::embeddings::local::colpali::ColPaliEmbedder::new(
-                        model_id, revision,
+                let embedder =
+                    embed_anything::embeddings::local::colpali::ColPaliEmbedder::new_on_device(
+                        model_id, revision, device,
                     )
-                    .unwrap(),
-                )));
+                    .map_err(to_py_err)?;
+                let model = Embedder::Vision(VisionEmbedder::ColPali(match max_vectors {
+                    Some(max_vectors) => Box::new(TokenPooledColPali::new(embedder, max_vectors)),
+                    None => Box::new(embedder),
+                }));
+                Ok(EmbeddingModel {
+                    inner: Some(Arc::new(model)),
+                })
+            }
+            WhichModel::ResNet => {
+                let model_id = model_id.unwrap_or("microsoft/resnet-50");
+                let model = Embedder::Vision(VisionEmbedder::ResNet(
+                    embed_anything::embeddings::local::resnet::ResNetEmbedder::new_on_device(
+                        model_id, revision, dtype, device,
+                    )
+                    .map_err(to_py_err)?,
+                ));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
 
@@ -254,35 +463,54 @@ impl EmbeddingModel {
     }
 
     #[staticmethod]
-    #[pyo3(signature = (model, model_id,  api_key=None))]
+    #[pyo3(signature = (model, model_id,  api_key=None, proxy=None, ca_cert=None, timeout=None, truncation=None))]
     fn from_pretrained_cloud(
         model: &WhichModel,
         model_id: Option<&str>,
         api_key: Option<String>,
+        proxy: Option<&str>,
+        ca_cert: Option<PathBuf>,
+        timeout: Option<f64>,
+        truncation: Option<&str>,
     ) -> PyResult<Self> {
+        let truncation = truncation
+            .map(TruncationPolicy::from_str)
+            .transpose()
+            .map_err(|e| to_py_err(e.into()))?;
+        let mut http_config = HttpClientConfig::default();
+        if let Some(proxy) = proxy {
+            http_config = http_config.with_proxy(proxy);
+        }
+        if let Some(ca_cert) = ca_cert {
+            http_config = http_config.with_ca_certificate(ca_cert);
+        }
+        if let Some(timeout) = timeout {
+            http_config = http_config.with_timeout(Duration::from_secs_f64(timeout));
+        }
+        let client = http_config.build().map_err(|e| to_py_err(e.into()))?;
         match model {
             WhichModel::OpenAI => {
                 let model_id = model_id.unwrap_or("text-embedding-3-small");
-                let model = Embedder::Text(TextEmbedder::OpenAI(
-                    embed_anything::embeddings::cloud::openai::OpenAIEmbedder::new(
-                        model_id.to_string(),
-                        api_key,
-                    ),
-                ));
+                let mut embedder =
+                    OpenAIEmbedder::new(model_id.to_string(), api_key).with_client(client);
+                if let Some(truncation) = truncation {
+                    embedder = embedder.with_truncation(truncation);
+                }
+                let model = Embedder::Text(TextEmbedder::OpenAI(embedder));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             WhichModel::Cohere => {
                 let model_id = model_id.unwrap_or("embed-english-v3.0");
-                let model = Embedder::Text(TextEmbedder::Cohere(
-                    embed_anything::embeddings::cloud::cohere::CohereEmbedder::new(
-                        model_id.to_string(),
-                        api_key,
-                    ),
-                ));
+                let mut embedder =
+                    CohereEmbedder::new(model_id.to_string(), api_key).with_client(client);
+                if let Some(truncation) = truncation {
+                    embedder = embedder.with_truncation(truncation);
+                }
+                let model = Embedder::Text(TextEmbedder::Cohere(embedder));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             _ => panic!("Invalid model"),
@@ -290,7 +518,8 @@ impl EmbeddingModel {
     }
 
     #[staticmethod]
-    #[pyo3(signature = (model, model_name=None, hf_model_id=None, revision=None, dtype=None, path_in_repo=None))]
+    #[pyo3(signature = (model, model_name=None, hf_model_id=None, revision=None, dtype=None, path_in_repo=None, task=None, query_prompt=None, document_prompt=None, device=None, timing=false, profile_path=None))]
+    #[allow(clippy::too_many_arguments)]
     fn from_pretrained_onnx(
         model: &WhichModel,
         model_name: Option<&ONNXModel>,
@@ -298,7 +527,31 @@ impl EmbeddingModel {
         revision: Option<&str>,
         dtype: Option<&Dtype>,
         path_in_repo: Option<&str>,
+        task: Option<&str>,
+        query_prompt: Option<&str>,
+        document_prompt: Option<&str>,
+        device: Option<&str>,
+        timing: bool,
+        profile_path: Option<PathBuf>,
     ) -> PyResult<Self> {
+        let prompts = prompt_override(model, query_prompt, document_prompt)?;
+        let device = parse_device(device)?;
+        if (timing || profile_path.is_some()) && *model != WhichModel::Bert {
+            return Err(PyValueError::new_err(
+                "timing and profile_path are only supported for Bert models",
+            ));
+        }
+        let task = task
+            .map(|task| {
+                embed_anything::embeddings::local::jina::JinaTask::from_str(task)
+                    .map_err(|_| PyValueError::new_err(format!("Unknown task: {}", task)))
+            })
+            .transpose()?;
+        if task.is_some() && *model != WhichModel::Jina {
+            return Err(PyValueError::new_err(
+                "task is only supported for Jina models",
+            ));
+        }
         let dtype = match dtype {
             Some(Dtype::Q4F16) => Some(embed_anything::Dtype::Q4F16),
             Some(Dtype::F16) => Some(embed_anything::Dtype::F16),
@@ -307,73 +560,296 @@ impl EmbeddingModel {
             Some(Dtype::UINT8) => Some(embed_anything::Dtype::UINT8),
             Some(Dtype::BNB4) => Some(embed_anything::Dtype::BNB4),
             Some(Dtype::F32) => Some(embed_anything::Dtype::F32),
+            Some(Dtype::BF16) => Some(embed_anything::Dtype::BF16),
             None => None,
         };
-        let model_name = model_name.map(|model_name| embed_anything::embeddings::local::text_embedding::ONNXModel::from_str(
-                    &model_name.to_string(),
-                )
-                .unwrap());
+        let model_name = model_name.map(|model_name| {
+            embed_anything::embeddings::local::text_embedding::ONNXModel::from_str(
+                &model_name.to_string(),
+            )
+            .unwrap()
+        });
         match model {
             WhichModel::Bert => {
-                let model = Embedder::Text(TextEmbedder::Bert(Box::new(
-                    embed_anything::embeddings::local::bert::OrtBertEmbedder::new(
+                let mut embedder =
+                    embed_anything::embeddings::local::bert::OrtBertEmbedder::new_on_device(
                         model_name,
                         hf_model_id,
                         revision,
                         dtype,
                         path_in_repo,
+                        device,
                     )
-                    .map_err(|e| PyValueError::new_err(e.to_string()))?,
-                )));
+                    .map_err(to_py_err)?;
+                if let Some(prompts) = prompts {
+                    embedder = embedder.with_prompts(Some(prompts));
+                }
+                if timing {
+                    embedder = embedder.with_timing();
+                }
+                if let Some(prefix) = profile_path {
+                    embedder = embedder.with_profiling(prefix).map_err(to_py_err)?;
+                }
+                let model = Embedder::Text(TextEmbedder::Bert(Box::new(embedder)));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             WhichModel::SparseBert => {
                 let model = Embedder::Text(TextEmbedder::Bert(Box::new(
-                    embed_anything::embeddings::local::bert::OrtSparseBertEmbedder::new(
+                    embed_anything::embeddings::local::bert::OrtSparseBertEmbedder::new_on_device(
                         model_name,
                         hf_model_id,
                         revision,
                         path_in_repo,
+                        device,
                     )
-                    .map_err(|e| PyValueError::new_err(e.to_string()))?,
+                    .map_err(to_py_err)?,
                 )));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             WhichModel::Jina => {
-                let model = Embedder::Text(TextEmbedder::Jina(Box::new(
-                    embed_anything::embeddings::local::jina::OrtJinaEmbedder::new(
+                let mut embedder =
+                    embed_anything::embeddings::local::jina::OrtJinaEmbedder::new_on_device(
                         model_name,
                         hf_model_id,
                         revision,
                         dtype,
                         path_in_repo,
+                        device,
+                    )
+                    .map_err(to_py_err)?;
+                if let Some(task) = task {
+                    embedder = embedder.with_task(task).map_err(to_py_err)?;
+                }
+                let model = Embedder::Text(TextEmbedder::Jina(Box::new(embedder)));
+                Ok(EmbeddingModel {
+                    inner: Some(Arc::new(model)),
+                })
+            }
+            WhichModel::BgeM3 => {
+                let model = Embedder::Text(TextEmbedder::BgeM3(Box::new(
+                    embed_anything::embeddings::local::bge_m3::OrtBgeM3Embedder::new_on_device(
+                        model_name,
+                        hf_model_id,
+                        revision,
+                        path_in_repo,
+                        device,
                     )
-                    .map_err(|e| PyValueError::new_err(e.to_string()))?,
+                    .map_err(to_py_err)?,
                 )));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             WhichModel::ColBert => {
                 let model = Embedder::Text(TextEmbedder::Bert(Box::new(
-                    embed_anything::embeddings::local::colbert::OrtColbertEmbedder::new(
+                    embed_anything::embeddings::local::colbert::OrtColbertEmbedder::new_on_device(
                         hf_model_id,
                         revision,
                         path_in_repo,
+                        device,
                     )
-                    .map_err(|e| PyValueError::new_err(e.to_string()))?,
+                    .map_err(to_py_err)?,
                 )));
                 Ok(EmbeddingModel {
-                    inner: Arc::new(model),
+                    inner: Some(Arc::new(model)),
                 })
             }
             _ => panic!("Invalid model"),
         }
     }
+
+    /// Embeds a dummy batch so the first real request doesn't pay for kernel selection and memory
+    /// allocation. Returns the time it took in seconds.
+    #[pyo3(signature = (batch_size=32, seq_len=256))]
+    fn warmup(&self, py: Python<'_>, batch_size: usize, seq_len: usize) -> PyResult<f64> {
+        let embedder = self.embedder()?;
+        py.allow_threads(|| {
+            let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+            rt.block_on(embedder.warmup(batch_size, seq_len))
+                .map(|elapsed| elapsed.as_secs_f64())
+                .map_err(to_py_err)
+        })
+    }
+
+    /// Returns the seconds spent per stage since the last call, or None if the model was not
+    /// loaded with `timing=True`. Embedding files also records their extraction and chunking.
+    fn timing_report<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(report) = self.embedder()?.timing_report() else {
+            return Ok(None);
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("extraction", report.extraction.as_secs_f64())?;
+        dict.set_item("chunking", report.chunking.as_secs_f64())?;
+        dict.set_item("tokenize", report.tokenize.as_secs_f64())?;
+        dict.set_item("inference", report.inference.as_secs_f64())?;
+        dict.set_item("pooling", report.pooling.as_secs_f64())?;
+        dict.set_item("normalize", report.normalize.as_secs_f64())?;
+        dict.set_item("total", report.total().as_secs_f64())?;
+        dict.set_item("batches", report.batches)?;
+        dict.set_item("texts", report.texts)?;
+        Ok(Some(dict))
+    }
+
+    /// Drops the underlying embedder, releasing its weights, GPU memory and ONNX session once no
+    /// pipeline, stream or config holds it anymore. Using the model afterwards raises an error.
+    fn close(&mut self) {
+        self.inner = None;
+    }
+
+    #[getter]
+    fn closed(&self) -> bool {
+        self.inner.is_none()
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyResult<PyRef<'_, Self>> {
+        slf.embedder()?;
+        Ok(slf)
+    }
+
+    #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+    fn __exit__(
+        &mut self,
+        _exc_type: Option<PyObject>,
+        _exc_value: Option<PyObject>,
+        _traceback: Option<PyObject>,
+    ) -> bool {
+        self.close();
+        false
+    }
+
+    /// Embeds the files of a directory on a background thread and returns an iterator over the
+    /// embeddings, one batch of up to `buffer_size` per step, as they are produced.
+    #[pyo3(signature = (directory, extensions=None, config=None, max_pending=4))]
+    fn embed_directory_iter(
+        &self,
+        directory: PathBuf,
+        extensions: Option<Vec<String>>,
+        config: Option<&config::TextEmbedConfig>,
+        max_pending: usize,
+    ) -> PyResult<EmbedDataIterator> {
+        let embedder = self.embedder()?.clone();
+        let config = config.map(|c| c.inner.clone()).unwrap_or_default();
+        // Bounded, so embedding pauses while Python is slow to consume the batches.
+        let (sender, receiver) = mpsc::sync_channel(max_pending.max(1));
+        let worker = std::thread::spawn(move || -> anyhow::Result<()> {
+            // Sending fails only once the iterator has been dropped and the batch isn't wanted.
+            let adapter = move |batch: Vec<embed_anything::embeddings::embed::EmbedData>| {
+                let _ = sender.send(batch);
+            };
+            let rt = Builder::new_multi_thread().enable_all().build()?;
+            rt.block_on(embed_anything::embed_directory_stream(
+                directory,
+                &embedder,
+                extensions,
+                Some(&config),
+                Some(adapter),
+            ))?;
+            Ok(())
+        });
+        Ok(EmbedDataIterator {
+            receiver: Mutex::new(receiver),
+            worker: Mutex::new(Some(worker)),
+        })
+    }
+
+    /// Embeds search queries with the prefix, instruction or task adapter the model expects for
+    /// queries.
+    #[pyo3(signature = (queries, batch_size=None))]
+    fn embed_queries(
+        &self,
+        queries: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> PyResult<Vec<EmbedData>> {
+        let embedder = self.embedder()?;
+        let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let encodings = rt
+            .block_on(embedder.embed_queries(&queries, batch_size))
+            .map_err(to_py_err)?;
+        Ok(to_embed_data(encodings, queries))
+    }
+
+    /// Embeds documents to search over, the counterpart of `embed_queries`. Documents are either
+    /// strings, or lists of text and image parts: each part is a dict with either a "text" or an
+    /// "image" (path) key. Parts are only supported by multimodal Cohere models such as
+    /// embed-v4.0.
+    #[pyo3(signature = (documents, batch_size=None))]
+    fn embed_documents(
+        &self,
+        documents: &Bound<'_, PyAny>,
+        batch_size: Option<usize>,
+    ) -> PyResult<Vec<EmbedData>> {
+        if let Ok(texts) = documents.extract::<Vec<String>>() {
+            let embedder = self.embedder()?;
+            let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+            let encodings = rt
+                .block_on(embedder.embed_documents(&texts, batch_size))
+                .map_err(to_py_err)?;
+            return Ok(to_embed_data(encodings, texts));
+        }
+        let documents = documents.extract::<Vec<Vec<HashMap<String, String>>>>()?;
+        let Embedder::Text(TextEmbedder::Cohere(embedder)) = self.embedder()?.as_ref() else {
+            return Err(PyValueError::new_err(
+                "embed_documents is only supported for Cohere models",
+            ));
+        };
+        let documents = documents
+            .into_iter()
+            .map(|parts| {
+                parts
+                    .into_iter()
+                    .map(|part| match (part.get("text"), part.get("image")) {
+                        (Some(text), None) => Ok(ContentPart::Text(text.clone())),
+                        (None, Some(image)) => Ok(ContentPart::Image(PathBuf::from(image))),
+                        _ => Err(PyValueError::new_err(
+                            "Each part must have exactly one of the keys 'text' or 'image'",
+                        )),
+                    })
+                    .collect::<PyResult<Vec<_>>>()
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let encodings = rt
+            .block_on(embedder.embed_documents(&documents))
+            .map_err(to_py_err)?;
+        Ok(encodings
+            .into_iter()
+            .map(|embedding| EmbedData {
+                inner: embed_anything::embeddings::embed::EmbedData::new(embedding, None, None),
+            })
+            .collect())
+    }
+
+    /// Returns the dense, sparse and ColBERT representations of every text in one pass. Each item
+    /// is a dict with "dense" (list of floats), "sparse" (token id to weight) and "colbert" (list of
+    /// token vectors). Only supported by BGE-M3 models.
+    #[pyo3(signature = (texts, batch_size=None))]
+    fn embed_all<'py>(
+        &self,
+        py: Python<'py>,
+        texts: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let Embedder::Text(TextEmbedder::BgeM3(embedder)) = self.embedder()?.as_ref() else {
+            return Err(PyValueError::new_err(
+                "embed_all is only supported for BGE-M3 models",
+            ));
+        };
+        let embeddings = embedder.embed_all(&texts, batch_size).map_err(to_py_err)?;
+        embeddings
+            .into_iter()
+            .map(|embedding| {
+                let dict = PyDict::new(py);
+                dict.set_item("dense", embedding.dense)?;
+                dict.set_item("sparse", embedding.sparse)?;
+                dict.set_item("colbert", embedding.colbert)?;
+                Ok(dict)
+            })
+            .collect()
+    }
 }
 
 #[pyclass]
@@ -384,13 +860,27 @@ pub struct AudioDecoderModel {
 #[pymethods]
 impl AudioDecoderModel {
     #[staticmethod]
-    #[pyo3(signature = (model_id, revision=None, model_type=None, quantized=None))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (model_id, revision=None, model_type=None, quantized=None, window_duration=None, window_overlap=None, vad=None, vad_aggressiveness=None, num_workers=None))]
     fn from_pretrained_hf(
         model_id: Option<&str>,
         revision: Option<&str>,
         model_type: Option<&str>,
         quantized: Option<bool>,
+        window_duration: Option<f64>,
+        window_overlap: Option<f64>,
+        vad: Option<bool>,
+        vad_aggressiveness: Option<u8>,
+        num_workers: Option<usize>,
     ) -> PyResult<Self> {
+        let decoder_config = embed_anything::config::AudioDecoderConfig {
+            window_duration,
+            window_overlap,
+            vad: vad.unwrap_or(false),
+            vad_aggressiveness,
+            num_workers,
+        };
+        decoder_config.validate().map_err(|e| to_py_err(e.into()))?;
         let model_id = model_id.unwrap_or("openai/whisper-tiny.en");
         let model_type = model_type.unwrap_or("tiny-en");
         let revision = revision.unwrap_or("main");
@@ -400,7 +890,8 @@ impl AudioDecoderModel {
             model_type,
             quantized.unwrap_or(false),
         )
-        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        .map_err(to_py_err)?
+        .with_decoder_config(decoder_config);
         Ok(AudioDecoderModel { inner: model })
     }
 }
@@ -413,21 +904,56 @@ pub fn embed_query(
     config: Option<&config::TextEmbedConfig>,
 ) -> PyResult<Vec<EmbedData>> {
     let config = config.map(|c| &c.inner);
-    let embedding_model = &embedder.inner;
+    let embedding_model = embedder.embedder()?;
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
-    Ok(rt.block_on(async {
-        embed_anything::embed_query(
-            query,
-            embedding_model,
-            Some(config.unwrap_or(&TextEmbedConfig::default())),
-        )
-        .await
-        .map_err(|e| PyValueError::new_err(e.to_string()))
-        .unwrap()
+    let embeddings = rt
+        .block_on(async {
+            embed_anything::embed_query(
+                query,
+                embedding_model,
+                Some(config.unwrap_or(&TextEmbedConfig::default())),
+            )
+            .await
+        })
+        .map_err(to_py_err)?;
+    Ok(embeddings
         .into_iter()
         .map(|data| EmbedData { inner: data })
-        .collect()
-    }))
+        .collect())
+}
+
+/// A single text or a list of texts.
+#[derive(FromPyObject)]
+pub enum TextInput {
+    #[pyo3(transparent)]
+    One(String),
+    #[pyo3(transparent)]
+    Many(Vec<String>),
+}
+
+/// Embeds one text or a list of texts. A single text returns a single `EmbedData`, a list returns
+/// one per text, in the same order.
+#[pyfunction]
+#[pyo3(signature = (text, embedder, config=None))]
+pub fn embed_text(
+    py: Python<'_>,
+    text: TextInput,
+    embedder: &EmbeddingModel,
+    config: Option<&config::TextEmbedConfig>,
+) -> PyResult<PyObject> {
+    match text {
+        TextInput::One(text) => {
+            let data = embed_query(vec![text], embedder, config)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| PyValueError::new_err("The model returned no embedding"))?;
+            Ok(Py::new(py, data)?.into_any())
+        }
+        TextInput::Many(texts) => {
+            let data = embed_query(texts, embedder, config)?;
+            Ok(PyList::new(py, data)?.into_any().unbind())
+        }
+    }
 }
 
 #[pyfunction]
@@ -439,7 +965,7 @@ pub fn embed_file(
     adapter: Option<PyObject>,
 ) -> PyResult<Option<Vec<EmbedData>>> {
     let config = config.map(|c| &c.inner);
-    let embedding_model = &embedder.inner;
+    let embedding_model = embedder.embedder()?;
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
     if !Path::new(file_name).exists() {
         // check if the file exists other wise return a "File not found" error with PyValueError
@@ -472,15 +998,7 @@ pub fn embed_file(
         .block_on(async {
             embed_anything::embed_file(file_name, embedding_model, config, adapter).await
         })
-        .map_err(|e| match e.downcast_ref::<FileLoadingError>() {
-            Some(FileLoadingError::FileNotFound(file)) => {
-                PyFileNotFoundError::new_err(file.clone())
-            }
-            Some(FileLoadingError::UnsupportedFileType(file)) => {
-                PyValueError::new_err(file.clone())
-            }
-            None => PyValueError::new_err(e.to_string()),
-        })?;
+        .map_err(to_py_err)?;
 
     Ok(embeddings.map(|embs| {
         embs.into_iter()
@@ -498,21 +1016,254 @@ pub fn embed_audio_file(
     text_embed_config: Option<&config::TextEmbedConfig>,
 ) -> PyResult<Option<Vec<EmbedData>>> {
     let config = text_embed_config.map(|c| &c.inner);
-    let embedding_model = &embedder.inner;
+    let embedding_model = embedder.embedder()?;
     let audio_decoder = &mut audio_decoder.inner;
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
-    let data = rt.block_on(async {
-        emb_audio(audio_file, audio_decoder, embedding_model, config)
+    let data = rt
+        .block_on(async { emb_audio(audio_file, audio_decoder, embedding_model, config).await })
+        .map_err(to_py_err)?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect::<Vec<_>>()
+    }))
+}
+
+#[pyfunction]
+#[pyo3(signature = (directory, audio_decoder, embedder, text_embed_config=None, adapter=None))]
+pub fn embed_audio_directory(
+    directory: PathBuf,
+    audio_decoder: &AudioDecoderModel,
+    embedder: &EmbeddingModel,
+    text_embed_config: Option<&config::TextEmbedConfig>,
+    adapter: Option<PyObject>,
+) -> PyResult<Option<Vec<EmbedData>>> {
+    let config = text_embed_config.map(|c| &c.inner);
+    let embedding_model = embedder.embedder()?;
+    let audio_decoder = &audio_decoder.inner;
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+
+    let adapter = adapter.map(|adapter| {
+        move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
+            Python::with_gil(|py| {
+                let upsert_fn = adapter.getattr(py, "upsert").unwrap();
+                let converted_data = data
+                    .into_iter()
+                    .map(|data| EmbedData { inner: data })
+                    .collect::<Vec<EmbedData>>();
+                upsert_fn
+                    .call1(py, (converted_data,))
+                    .map_err(|e| PyValueError::new_err(e.to_string()))
+                    .unwrap();
+            });
+        }
+    });
+
+    let data = rt
+        .block_on(async {
+            embed_anything::embed_audio_directory(
+                directory,
+                audio_decoder,
+                embedding_model,
+                config,
+                adapter,
+            )
             .await
-            .map_err(|e| PyValueError::new_err(e.to_string()))
-            .unwrap()
-            .map(|data| {
-                data.into_iter()
+        })
+        .map_err(to_py_err)?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect::<Vec<_>>()
+    }))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+#[pyo3(signature = (directory, text_embedder=None, image_embedder=None, audio_decoder=None, audio_embedder=None, config=None, image_config=None, adapter=None))]
+pub fn embed_mixed_directory(
+    directory: PathBuf,
+    text_embedder: Option<&EmbeddingModel>,
+    image_embedder: Option<&EmbeddingModel>,
+    audio_decoder: Option<&AudioDecoderModel>,
+    audio_embedder: Option<&EmbeddingModel>,
+    config: Option<&config::TextEmbedConfig>,
+    image_config: Option<&config::ImageEmbedConfig>,
+    adapter: Option<PyObject>,
+) -> PyResult<Option<Vec<EmbedData>>> {
+    let config = config.map(|c| &c.inner);
+    let image_config = image_config.map(|c| &c.inner);
+    let audio = match (audio_decoder, audio_embedder.or(text_embedder)) {
+        (Some(decoder), Some(embedder)) => {
+            Some((decoder.inner.clone(), embedder.embedder()?.clone()))
+        }
+        (Some(_), None) => {
+            return Err(PyValueError::new_err(
+                "audio_decoder needs audio_embedder or text_embedder",
+            ))
+        }
+        (None, _) => None,
+    };
+    let embedders = embed_anything::ModalityEmbedders {
+        text: text_embedder.map(|e| e.embedder().cloned()).transpose()?,
+        image: image_embedder.map(|e| e.embedder().cloned()).transpose()?,
+        audio,
+    };
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+
+    let adapter = adapter.map(|adapter| {
+        move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
+            Python::with_gil(|py| {
+                let upsert_fn = adapter.getattr(py, "upsert").unwrap();
+                let converted_data = data
+                    .into_iter()
                     .map(|data| EmbedData { inner: data })
-                    .collect::<Vec<_>>()
-            })
+                    .collect::<Vec<EmbedData>>();
+                upsert_fn
+                    .call1(py, (converted_data,))
+                    .map_err(|e| PyValueError::new_err(e.to_string()))
+                    .unwrap();
+            });
+        }
     });
-    Ok(data)
+
+    let data = rt
+        .block_on(async {
+            embed_anything::embed_mixed_directory(
+                directory,
+                &embedders,
+                config,
+                image_config,
+                adapter,
+            )
+            .await
+        })
+        .map_err(to_py_err)?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect::<Vec<_>>()
+    }))
+}
+
+/// Batches of embeddings produced by `EmbeddingModel.embed_directory_iter` while the directory is
+/// embedded in the background.
+#[pyclass]
+pub struct EmbedDataIterator {
+    receiver: Mutex<mpsc::Receiver<Vec<embed_anything::embeddings::embed::EmbedData>>>,
+    worker: Mutex<Option<JoinHandle<anyhow::Result<()>>>>,
+}
+
+#[pymethods]
+impl EmbedDataIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<Vec<EmbedData>>> {
+        let batch = py.allow_threads(|| self.receiver.lock().unwrap().recv());
+        if let Ok(batch) = batch {
+            return Ok(Some(
+                batch
+                    .into_iter()
+                    .map(|data| EmbedData { inner: data })
+                    .collect(),
+            ));
+        }
+        // The channel closes once the worker is done; raise its error, if any, the first time.
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            worker
+                .join()
+                .map_err(|_| PyRuntimeError::new_err("The embedding thread panicked"))?
+                .map_err(to_py_err)?;
+        }
+        Ok(None)
+    }
+}
+
+/// Embeds audio pushed in chunks, such as from a microphone, while it is transcribed.
+#[pyclass]
+pub struct AudioStream {
+    inner: audio_processor::AudioStream,
+    audio_decoder: Py<AudioDecoderModel>,
+    embedder: Arc<Embedder>,
+    config: TextEmbedConfig,
+    source: String,
+}
+
+impl AudioStream {
+    fn embed(&self, segments: Vec<audio_processor::Segment>) -> PyResult<Vec<EmbedData>> {
+        if segments.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let mut embeddings = rt
+            .block_on(async {
+                embed_anything::embeddings::embed_audio(
+                    &self.embedder,
+                    segments,
+                    &self.source,
+                    self.config.batch_size,
+                )
+                .await
+            })
+            .map_err(to_py_err)?;
+        self.config.apply_metadata_config(&mut embeddings);
+        Ok(embeddings
+            .into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect())
+    }
+}
+
+#[pymethods]
+impl AudioStream {
+    #[new]
+    #[pyo3(signature = (audio_decoder, embedder, source="stream".to_string(), config=None))]
+    fn new(
+        audio_decoder: Py<AudioDecoderModel>,
+        embedder: &EmbeddingModel,
+        source: String,
+        config: Option<&config::TextEmbedConfig>,
+    ) -> PyResult<Self> {
+        Ok(AudioStream {
+            inner: audio_processor::AudioStream::new(),
+            audio_decoder,
+            embedder: embedder.embedder()?.clone(),
+            config: config.map(|c| c.inner.clone()).unwrap_or_default(),
+            source,
+        })
+    }
+
+    /// Adds mono float samples at 16 kHz and returns the embeddings of every full 30 second window.
+    fn push(&mut self, py: Python<'_>, samples: Vec<f32>) -> PyResult<Vec<EmbedData>> {
+        let mut audio_decoder = self.audio_decoder.borrow_mut(py);
+        let segments = self
+            .inner
+            .push(&mut audio_decoder.inner, &samples)
+            .map_err(to_py_err)?;
+        self.embed(segments)
+    }
+
+    /// Adds 16-bit little-endian mono PCM at 16 kHz, like `push`.
+    fn push_bytes(&mut self, py: Python<'_>, data: &[u8]) -> PyResult<Vec<EmbedData>> {
+        let mut audio_decoder = self.audio_decoder.borrow_mut(py);
+        let segments = self
+            .inner
+            .push_bytes(&mut audio_decoder.inner, data)
+            .map_err(to_py_err)?;
+        self.embed(segments)
+    }
+
+    /// Embeds the audio still buffered, once the input has ended.
+    fn finish(&mut self, py: Python<'_>) -> PyResult<Vec<EmbedData>> {
+        let mut audio_decoder = self.audio_decoder.borrow_mut(py);
+        let segments = self
+            .inner
+            .finish(&mut audio_decoder.inner)
+            .map_err(to_py_err)?;
+        self.embed(segments)
+    }
 }
 
 #[pyfunction]
@@ -525,10 +1276,9 @@ pub fn embed_directory(
     adapter: Option<PyObject>,
 ) -> PyResult<Option<Vec<EmbedData>>> {
     let config = config.map(|c| &c.inner);
-    let embedding_model = &embedder.inner;
+    let embedding_model = embedder.embedder()?;
 
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
-    println!("Runtime created");
     let adapter = match adapter {
         Some(adapter) => {
             let callback = move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
@@ -549,24 +1299,23 @@ pub fn embed_directory(
         None => None,
     };
 
-    let data = rt.block_on(async {
-        embed_anything::embed_directory_stream(
-            directory,
-            embedding_model,
-            extensions,
-            config,
-            adapter,
-        )
-        .await
-        .map_err(|e| PyValueError::new_err(e.to_string()))
-        .unwrap()
-        .map(|data| {
-            data.into_iter()
-                .map(|data| EmbedData { inner: data })
-                .collect::<Vec<_>>()
+    let data = rt
+        .block_on(async {
+            embed_anything::embed_directory_stream(
+                directory,
+                embedding_model,
+                extensions,
+                config,
+                adapter,
+            )
+            .await
         })
-    });
-    Ok(data)
+        .map_err(to_py_err)?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect::<Vec<_>>()
+    }))
 }
 
 #[pyfunction]
@@ -577,10 +1326,9 @@ pub fn embed_image_directory(
     config: Option<&config::ImageEmbedConfig>,
     adapter: Option<PyObject>,
 ) -> PyResult<Option<Vec<EmbedData>>> {
-    let embedding_model = &embedder.inner;
+    let embedding_model = embedder.embedder()?;
     let config = config.map(|c| &c.inner);
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
-    println!("Runtime created");
 
     let adapter = match adapter {
         Some(adapter) => {
@@ -602,19 +1350,170 @@ pub fn embed_image_directory(
         None => None,
     };
 
-    let data = rt.block_on(async {
-        embed_anything::embed_image_directory(directory, embedding_model, config, adapter)
-            .await
-            .map_err(|e| PyValueError::new_err(e.to_string()))
-            .unwrap()
-            .map(|data| {
-                data.into_iter()
-                    .map(|data| EmbedData { inner: data })
-                    .collect::<Vec<_>>()
-            })
-    });
-    Ok(data)
+    let data = rt
+        .block_on(async {
+            embed_anything::embed_image_directory(directory, embedding_model, config, adapter).await
+        })
+        .map_err(to_py_err)?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect::<Vec<_>>()
+    }))
 }
+/// Reports the device and inference backends in use, for readiness checks.
+#[pyfunction]
+pub fn runtime_info(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let info = embed_anything::embeddings::runtime_info();
+    let dict = PyDict::new(py);
+    dict.set_item("version", info.version)?;
+    dict.set_item("device", info.device)?;
+    dict.set_item("ort_cuda_available", info.ort_cuda_available)?;
+    dict.set_item("ort_rocm_available", info.ort_rocm_available)?;
+    dict.set_item("features", info.features)?;
+    Ok(dict)
+}
+
+#[pyfunction]
+pub fn memory_usage(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let usage = embed_anything::embeddings::memory_usage();
+    let dict = PyDict::new(py);
+    dict.set_item("process_rss_bytes", usage.process_rss_bytes)?;
+    dict.set_item("device_used_bytes", usage.device_used_bytes)?;
+    dict.set_item("device_total_bytes", usage.device_total_bytes)?;
+    Ok(dict)
+}
+
+/// Clusters the embeddings with mini-batch k-means and returns one cluster label per item.
+#[pyfunction]
+#[pyo3(signature = (data, k, distance="cosine", batch_size=None, max_iterations=None, seed=None))]
+pub fn cluster_embeddings(
+    py: Python<'_>,
+    data: Vec<PyRef<EmbedData>>,
+    k: usize,
+    distance: &str,
+    batch_size: Option<usize>,
+    max_iterations: Option<usize>,
+    seed: Option<u64>,
+) -> PyResult<Vec<usize>> {
+    let distance = Distance::from_str(distance)
+        .map_err(|_| PyValueError::new_err(format!("Unknown distance: {}", distance)))?;
+    let defaults = KMeansConfig::default();
+    let config = KMeansConfig {
+        k,
+        distance,
+        batch_size: batch_size.unwrap_or(defaults.batch_size),
+        max_iterations: max_iterations.unwrap_or(defaults.max_iterations),
+        seed: seed.unwrap_or(defaults.seed),
+        ..defaults
+    };
+    let data = data
+        .iter()
+        .map(|data| data.inner.clone())
+        .collect::<Vec<_>>();
+    py.allow_threads(|| {
+        embed_anything::embeddings::clustering::cluster_embeddings(&data, &config)
+            .map(|(_, labels)| labels)
+            .map_err(to_py_err)
+    })
+}
+
+/// Projects the embeddings with PCA and returns the coordinates of every item. With `path` the
+/// coordinates, texts and metadata are also written as JSON (`.json`) or CSV (anything else).
+#[pyfunction]
+#[pyo3(signature = (data, n_components=2, path=None))]
+pub fn project_embeddings(
+    py: Python<'_>,
+    data: Vec<PyRef<EmbedData>>,
+    n_components: usize,
+    path: Option<PathBuf>,
+) -> PyResult<Vec<Vec<f32>>> {
+    let data = data
+        .iter()
+        .map(|data| data.inner.clone())
+        .collect::<Vec<_>>();
+    py.allow_threads(|| {
+        let points = projection::project_embeddings(&data, n_components).map_err(to_py_err)?;
+        if let Some(path) = path {
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("json") => projection::export_json(&points, &path),
+                _ => projection::export_csv(&points, &path),
+            }
+            .map_err(to_py_err)?;
+        }
+        Ok(points.into_iter().map(|point| point.coordinates).collect())
+    })
+}
+
+/// Flags near-duplicate and outlier items by setting the `quality_flag` metadata field of every
+/// item, and returns how many were flagged as `(near_duplicates, outliers)`.
+#[pyfunction]
+#[pyo3(signature = (data, duplicate_threshold=None, max_duplicates=None, outlier_z_score=None))]
+pub fn flag_low_quality(
+    py: Python<'_>,
+    mut data: Vec<PyRefMut<EmbedData>>,
+    duplicate_threshold: Option<f32>,
+    max_duplicates: Option<usize>,
+    outlier_z_score: Option<f32>,
+) -> PyResult<(usize, usize)> {
+    let defaults = QualityConfig::default();
+    let config = QualityConfig {
+        duplicate_threshold: duplicate_threshold.unwrap_or(defaults.duplicate_threshold),
+        max_duplicates: max_duplicates.unwrap_or(defaults.max_duplicates),
+        outlier_z_score: outlier_z_score.unwrap_or(defaults.outlier_z_score),
+    };
+    let mut inner = data
+        .iter()
+        .map(|data| data.inner.clone())
+        .collect::<Vec<_>>();
+    let report = py
+        .allow_threads(|| quality::flag_low_quality(&mut inner, &config))
+        .map_err(to_py_err)?;
+    for (data, inner) in data.iter_mut().zip(inner) {
+        data.inner.metadata = inner.metadata;
+    }
+    Ok((report.near_duplicates, report.outliers))
+}
+
+#[pyfunction]
+#[pyo3(signature = (text, config=None))]
+pub fn chunk_text(text: &str, config: Option<&config::TextEmbedConfig>) -> PyResult<Vec<String>> {
+    embed_anything::chunk_text(text, config.map(|c| &c.inner)).map_err(to_py_err)
+}
+
+#[pyfunction]
+#[pyo3(signature = (file_name, config=None))]
+pub fn chunk_file(
+    file_name: &str,
+    config: Option<&config::TextEmbedConfig>,
+) -> PyResult<Vec<Chunk>> {
+    if !Path::new(file_name).exists() {
+        return Err(PyFileNotFoundError::new_err(format!(
+            "File not found: {:?}",
+            file_name
+        )));
+    }
+    let chunks =
+        embed_anything::chunk_file(file_name, config.map(|c| &c.inner)).map_err(to_py_err)?;
+    Ok(chunks.into_iter().map(|inner| Chunk { inner }).collect())
+}
+
+#[pyfunction]
+#[pyo3(signature = (url, embedder, config=None))]
+pub fn embed_image_url(
+    url: &str,
+    embedder: &EmbeddingModel,
+    config: Option<&config::ImageEmbedConfig>,
+) -> PyResult<EmbedData> {
+    let embedding_model = embedder.embedder()?;
+    let config = config.map(|c| &c.inner);
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+    let data = rt
+        .block_on(async { embed_anything::embed_image_url(url, embedding_model, config).await })
+        .map_err(to_py_err)?;
+    Ok(EmbedData { inner: data })
+}
+
 #[pyfunction]
 #[pyo3(signature = (url, embedder, config=None, adapter = None))]
 pub fn embed_webpage(
@@ -623,7 +1522,7 @@ pub fn embed_webpage(
     config: Option<&config::TextEmbedConfig>,
     adapter: Option<PyObject>,
 ) -> PyResult<Option<Vec<EmbedData>>> {
-    let embedding_model = &embedder.inner;
+    let embedding_model = embedder.embedder()?;
     let config = config.map(|c| &c.inner);
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
     let adapter = match adapter {
@@ -646,18 +1545,218 @@ pub fn embed_webpage(
         None => None,
     };
 
-    let data = rt.block_on(async {
-        embed_anything::embed_webpage(url, embedding_model, config, adapter)
+    let data = rt
+        .block_on(async {
+            embed_anything::embed_webpage(url, embedding_model, config, adapter).await
+        })
+        .map_err(to_py_err)?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect::<Vec<_>>()
+    }))
+}
+
+#[pyclass]
+pub struct WebpageResult {
+    url: String,
+    embeddings: Option<Vec<embed_anything::embeddings::embed::EmbedData>>,
+    error: Option<String>,
+    unchanged: bool,
+}
+
+#[pymethods]
+impl WebpageResult {
+    #[getter]
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// The embeddings of the page, `None` when they were sent to the adapter or the page failed.
+    #[getter]
+    fn embeddings(&self) -> Option<Vec<EmbedData>> {
+        self.embeddings.as_ref().map(|embeddings| {
+            embeddings
+                .iter()
+                .map(|data| EmbedData {
+                    inner: data.clone(),
+                })
+                .collect()
+        })
+    }
+
+    #[getter]
+    fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    /// Whether the page was skipped because it did not change since it was last embedded.
+    #[getter]
+    fn unchanged(&self) -> bool {
+        self.unchanged
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.error {
+            Some(error) => format!("WebpageResult(url: {:?}, error: {:?})", self.url, error),
+            None => format!("WebpageResult(url: {:?})", self.url),
+        }
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (urls, embedder, config=None, concurrency=None, timeout=None, user_agent=None, respect_robots_txt=None, domain_delay=None, cache_path=None, adapter=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn embed_webpages(
+    urls: Vec<String>,
+    embedder: &EmbeddingModel,
+    config: Option<&config::TextEmbedConfig>,
+    concurrency: Option<usize>,
+    timeout: Option<f64>,
+    user_agent: Option<String>,
+    respect_robots_txt: Option<bool>,
+    domain_delay: Option<f64>,
+    cache_path: Option<String>,
+    adapter: Option<PyObject>,
+) -> PyResult<Vec<WebpageResult>> {
+    let embedding_model = embedder.embedder()?;
+    let config = config.map(|c| &c.inner);
+    let fetch_config = web_fetch_config(
+        concurrency,
+        timeout,
+        user_agent,
+        respect_robots_txt,
+        domain_delay,
+        cache_path,
+    );
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+    let adapter = adapter.map(webpage_adapter);
+
+    let results = rt
+        .block_on(async {
+            embed_anything::embed_webpages(
+                &urls,
+                embedding_model,
+                config,
+                Some(&fetch_config),
+                adapter,
+            )
             .await
-            .map_err(|e| PyValueError::new_err(e.to_string()))
-            .unwrap()
-            .map(|data| {
-                data.into_iter()
-                    .map(|data| EmbedData { inner: data })
-                    .collect::<Vec<_>>()
-            })
-    });
-    Ok(data)
+        })
+        .map_err(to_py_err)?;
+    Ok(webpage_results(results))
+}
+
+#[pyfunction]
+#[pyo3(signature = (url, embedder, config=None, concurrency=None, timeout=None, user_agent=None, respect_robots_txt=None, domain_delay=None, cache_path=None, adapter=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn embed_sitemap(
+    url: String,
+    embedder: &EmbeddingModel,
+    config: Option<&config::TextEmbedConfig>,
+    concurrency: Option<usize>,
+    timeout: Option<f64>,
+    user_agent: Option<String>,
+    respect_robots_txt: Option<bool>,
+    domain_delay: Option<f64>,
+    cache_path: Option<String>,
+    adapter: Option<PyObject>,
+) -> PyResult<Vec<WebpageResult>> {
+    let embedding_model = embedder.embedder()?;
+    let config = config.map(|c| &c.inner);
+    let fetch_config = web_fetch_config(
+        concurrency,
+        timeout,
+        user_agent,
+        respect_robots_txt,
+        domain_delay,
+        cache_path,
+    );
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+    let adapter = adapter.map(webpage_adapter);
+
+    let results = rt
+        .block_on(async {
+            embed_anything::embed_sitemap(
+                &url,
+                embedding_model,
+                config,
+                Some(&fetch_config),
+                adapter,
+            )
+            .await
+        })
+        .map_err(to_py_err)?;
+    Ok(webpage_results(results))
+}
+
+fn web_fetch_config(
+    concurrency: Option<usize>,
+    timeout: Option<f64>,
+    user_agent: Option<String>,
+    respect_robots_txt: Option<bool>,
+    domain_delay: Option<f64>,
+    cache_path: Option<String>,
+) -> embed_anything::config::WebFetchConfig {
+    let mut fetch_config = embed_anything::config::WebFetchConfig::default();
+    if let Some(concurrency) = concurrency {
+        fetch_config = fetch_config.with_concurrency(concurrency);
+    }
+    if let Some(timeout) = timeout {
+        fetch_config = fetch_config.with_timeout(std::time::Duration::from_secs_f64(timeout));
+    }
+    if let Some(user_agent) = user_agent {
+        fetch_config = fetch_config.with_user_agent(user_agent);
+    }
+    if let Some(respect_robots_txt) = respect_robots_txt {
+        fetch_config = fetch_config.with_robots_txt(respect_robots_txt);
+    }
+    if let Some(domain_delay) = domain_delay {
+        fetch_config =
+            fetch_config.with_domain_delay(std::time::Duration::from_secs_f64(domain_delay));
+    }
+    if let Some(cache_path) = cache_path {
+        fetch_config = fetch_config.with_cache_path(cache_path);
+    }
+    fetch_config
+}
+
+fn webpage_adapter(
+    adapter: PyObject,
+) -> impl Fn(Vec<embed_anything::embeddings::embed::EmbedData>) + Send {
+    move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
+        Python::with_gil(|py| {
+            let upsert_fn = adapter.getattr(py, "upsert").unwrap();
+            let converted_data = data
+                .into_iter()
+                .map(|data| EmbedData { inner: data })
+                .collect::<Vec<EmbedData>>();
+            upsert_fn
+                .call1(py, (converted_data,))
+                .map_err(|e| PyValueError::new_err(e.to_string()))
+                .unwrap();
+        });
+    }
+}
+
+fn webpage_results(results: Vec<embed_anything::WebpageResult>) -> Vec<WebpageResult> {
+    results
+        .into_iter()
+        .map(|page| match page.result {
+            Ok(embeddings) => WebpageResult {
+                url: page.url,
+                embeddings,
+                error: None,
+                unchanged: page.unchanged,
+            },
+            Err(e) => WebpageResult {
+                url: page.url,
+                embeddings: None,
+                error: Some(e.to_string()),
+                unchanged: false,
+            },
+        })
+        .collect()
 }
 
 #[pymodule]
@@ -665,20 +1764,44 @@ fn _embed_anything(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(embed_file, m)?)?;
     m.add_function(wrap_pyfunction!(embed_directory, m)?)?;
     m.add_function(wrap_pyfunction!(embed_image_directory, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_image_url, m)?)?;
+    m.add_function(wrap_pyfunction!(chunk_text, m)?)?;
+    m.add_function(wrap_pyfunction!(runtime_info, m)?)?;
+    m.add_function(wrap_pyfunction!(memory_usage, m)?)?;
+    m.add_function(wrap_pyfunction!(chunk_file, m)?)?;
     m.add_function(wrap_pyfunction!(embed_query, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_text, m)?)?;
     m.add_function(wrap_pyfunction!(embed_webpage, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_webpages, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_sitemap, m)?)?;
     m.add_function(wrap_pyfunction!(embed_audio_file, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_audio_directory, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_mixed_directory, m)?)?;
+    m.add_function(wrap_pyfunction!(cluster_embeddings, m)?)?;
+    m.add_function(wrap_pyfunction!(project_embeddings, m)?)?;
+    m.add_function(wrap_pyfunction!(flag_low_quality, m)?)?;
+    m.add_function(wrap_pyfunction!(hub::set_hub_config, m)?)?;
+    m.add_function(wrap_pyfunction!(logging::set_log_level, m)?)?;
     m.add_class::<ColpaliModel>()?;
     m.add_class::<ColbertModel>()?;
     m.add_class::<EmbeddingModel>()?;
     m.add_class::<AudioDecoderModel>()?;
+    m.add_class::<AudioStream>()?;
+    m.add_class::<EmbedDataIterator>()?;
     m.add_class::<WhichModel>()?;
     m.add_class::<EmbedData>()?;
+    m.add_class::<Chunk>()?;
     m.add_class::<config::TextEmbedConfig>()?;
+    m.add_class::<config::ImageEmbedConfig>()?;
     m.add_class::<ONNXModel>()?;
     m.add_class::<Reranker>()?;
     m.add_class::<Dtype>()?;
     m.add_class::<RerankerResult>()?;
     m.add_class::<DocumentRank>()?;
+    m.add_class::<hub::PrefetchReport>()?;
+    m.add_class::<WebpageResult>()?;
+    m.add_class::<pipeline::Pipeline>()?;
+    m.add_class::<pipeline::ScoredChunk>()?;
+    errors::register(m)?;
     Ok(())
 }
@@ -0,0 +1,40 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+static LEVEL: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+/// Sets the level of the library's log messages, which are written to stderr. The library is
+/// quiet until this is called. Pass None or "off" to silence it again.
+#[pyfunction]
+#[pyo3(signature = (level=None))]
+pub fn set_log_level(level: Option<&str>) -> PyResult<()> {
+    let filter = match level {
+        None => LevelFilter::OFF,
+        Some(level) => LevelFilter::from_str(level).map_err(|_| {
+            PyValueError::new_err(format!(
+                "Unknown log level '{}', use off, error, warn, info, debug or trace",
+                level
+            ))
+        })?,
+    };
+    let handle = LEVEL.get_or_init(|| {
+        let (filter, handle) = reload::Layer::new(LevelFilter::OFF);
+        // Fails if the application already installed a subscriber, which then decides what is
+        // logged.
+        let _ = tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer().with_writer(std::io::stderr))
+            .try_init();
+        handle
+    });
+    handle
+        .reload(filter)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
@@ -1,8 +1,6 @@
-use std::rc::Rc;
-
+use crate::errors::to_py_err;
 use embed_anything::embeddings::get_text_metadata;
-use embed_anything::embeddings::local::colbert::{ColbertEmbed, OrtColbertEmbedder};
-use pyo3::exceptions::PyValueError;
+use embed_anything::embeddings::local::colbert::{ColbertEmbed, OrtColbertEmbedder, TokenFilter};
 use pyo3::prelude::*;
 use pyo3::PyResult;
 
@@ -16,28 +14,48 @@ pub struct ColbertModel {
 #[pymethods]
 impl ColbertModel {
     #[new]
-    #[pyo3(signature = (hf_model_id=None, revision=None, path_in_repo=None))]
+    #[pyo3(signature = (hf_model_id=None, revision=None, path_in_repo=None, skip_punctuation=false, skip_special_tokens=false, top_k=None))]
     pub fn new(
         hf_model_id: Option<&str>,
         revision: Option<&str>,
         path_in_repo: Option<&str>,
+        skip_punctuation: bool,
+        skip_special_tokens: bool,
+        top_k: Option<usize>,
     ) -> PyResult<Self> {
+        let mut token_filter = TokenFilter::default()
+            .with_skip_punctuation(skip_punctuation)
+            .with_skip_special_tokens(skip_special_tokens);
+        if let Some(top_k) = top_k {
+            token_filter = token_filter.with_top_k(top_k);
+        }
         let model = OrtColbertEmbedder::new(hf_model_id, revision, path_in_repo)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+            .map_err(to_py_err)?
+            .with_token_filter(token_filter);
         Ok(Self {
             model: Box::new(model),
         })
     }
 
     #[staticmethod]
-    #[pyo3(signature = (hf_model_id=None, revision=None, path_in_repo=None))]
+    #[pyo3(signature = (hf_model_id=None, revision=None, path_in_repo=None, skip_punctuation=false, skip_special_tokens=false, top_k=None))]
     fn from_pretrained_onnx(
         hf_model_id: Option<&str>,
         revision: Option<&str>,
         path_in_repo: Option<&str>,
+        skip_punctuation: bool,
+        skip_special_tokens: bool,
+        top_k: Option<usize>,
     ) -> PyResult<Self> {
+        let mut token_filter = TokenFilter::default()
+            .with_skip_punctuation(skip_punctuation)
+            .with_skip_special_tokens(skip_special_tokens);
+        if let Some(top_k) = top_k {
+            token_filter = token_filter.with_top_k(top_k);
+        }
         let model = OrtColbertEmbedder::new(hf_model_id, revision, path_in_repo)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+            .map_err(to_py_err)?
+            .with_token_filter(token_filter);
         Ok(Self {
             model: Box::new(model),
         })
@@ -57,9 +75,8 @@ impl ColbertModel {
         let embed_data = self
             .model
             .embed(&text_batch, batch_size, is_doc)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
-        let embeddings = get_text_metadata(&Rc::new(embed_data), &text_batch, &None)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+            .map_err(to_py_err)?;
+        let embeddings = get_text_metadata(&embed_data, &text_batch, &None).map_err(to_py_err)?;
         Ok(embeddings
             .into_iter()
             .map(|data| EmbedData { inner: data })
@@ -1,53 +1,81 @@
+use crate::errors::to_py_err;
 use embed_anything::embeddings::local::colpali::ColPaliEmbed;
 use embed_anything::embeddings::local::colpali::ColPaliEmbedder;
 use embed_anything::embeddings::local::colpali_ort::OrtColPaliEmbedder;
-use pyo3::exceptions::PyValueError;
+use embed_anything::embeddings::local::token_pooling::TokenPooledColPali;
 use pyo3::prelude::*;
 use pyo3::PyResult;
 
+use crate::config::ImageEmbedConfig;
 use crate::EmbedData;
 #[pyclass]
 pub struct ColpaliModel {
     pub model: Box<dyn ColPaliEmbed + Send + Sync>,
 }
 
+impl ColpaliModel {
+    fn pooled<E: ColPaliEmbed + Send + Sync + 'static>(
+        model: E,
+        max_vectors: Option<usize>,
+    ) -> Self {
+        let model: Box<dyn ColPaliEmbed + Send + Sync> = match max_vectors {
+            Some(max_vectors) => Box::new(TokenPooledColPali::new(model, max_vectors)),
+            None => Box::new(model),
+        };
+        Self { model }
+    }
+}
+
 #[pymethods]
 impl ColpaliModel {
     #[new]
-    #[pyo3(signature = (model_id, revision=None))]
-    pub fn new(model_id: &str, revision: Option<&str>) -> PyResult<Self> {
-        let model = ColPaliEmbedder::new(model_id, revision)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(Self {
-            model: Box::new(model),
-        })
+    #[pyo3(signature = (model_id, revision=None, max_vectors=None))]
+    pub fn new(
+        model_id: &str,
+        revision: Option<&str>,
+        max_vectors: Option<usize>,
+    ) -> PyResult<Self> {
+        let model = ColPaliEmbedder::new(model_id, revision).map_err(to_py_err)?;
+        Ok(Self::pooled(model, max_vectors))
     }
 
     #[staticmethod]
-    #[pyo3(signature = (model_id, revision=None))]
-    pub fn from_pretrained(model_id: &str, revision: Option<&str>) -> PyResult<Self> {
-        let model = ColPaliEmbedder::new(model_id, revision)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(Self {
-            model: Box::new(model),
-        })
+    #[pyo3(signature = (model_id, revision=None, max_vectors=None))]
+    pub fn from_pretrained(
+        model_id: &str,
+        revision: Option<&str>,
+        max_vectors: Option<usize>,
+    ) -> PyResult<Self> {
+        let model = ColPaliEmbedder::new(model_id, revision).map_err(to_py_err)?;
+        Ok(Self::pooled(model, max_vectors))
     }
 
     #[staticmethod]
-    #[pyo3(signature = (model_id, revision=None))]
-    pub fn from_pretrained_onnx(model_id: &str, revision: Option<&str>) -> PyResult<Self> {
-        let model = OrtColPaliEmbedder::new(model_id, revision)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(Self {
-            model: Box::new(model),
-        })
+    #[pyo3(signature = (model_id, revision=None, max_vectors=None))]
+    pub fn from_pretrained_onnx(
+        model_id: &str,
+        revision: Option<&str>,
+        max_vectors: Option<usize>,
+    ) -> PyResult<Self> {
+        let model = OrtColPaliEmbedder::new(model_id, revision).map_err(to_py_err)?;
+        Ok(Self::pooled(model, max_vectors))
     }
 
-    pub fn embed_file(&self, file_path: &str, batch_size: usize) -> PyResult<Vec<EmbedData>> {
+    #[pyo3(signature = (file_path, batch_size=1, config=None))]
+    pub fn embed_file(
+        &self,
+        file_path: &str,
+        batch_size: usize,
+        config: Option<&ImageEmbedConfig>,
+    ) -> PyResult<Vec<EmbedData>> {
+        let default_config = embed_anything::config::ImageEmbedConfig::default();
+        let config = config
+            .map(|config| &config.inner)
+            .unwrap_or(&default_config);
         let embed_data = self
             .model
-            .embed_file(file_path.into(), batch_size)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+            .embed_file_with_config(file_path.into(), batch_size, config)
+            .map_err(to_py_err)?;
         Ok(embed_data
             .into_iter()
             .map(|data| EmbedData { inner: data })
@@ -55,10 +83,7 @@ impl ColpaliModel {
     }
 
     pub fn embed_query(&self, query: &str) -> PyResult<Vec<EmbedData>> {
-        let embed_data = self
-            .model
-            .embed_query(query)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let embed_data = self.model.embed_query(query).map_err(to_py_err)?;
         Ok(embed_data
             .into_iter()
             .map(|data| EmbedData { inner: data })
@@ -1,10 +1,16 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::errors::to_py_err;
+use crate::parse_device;
+use embed_anything::reranker::model::ScoreNormalization;
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::PyResult;
 
 #[pyclass]
 pub struct Reranker {
-    pub model: embed_anything::reranker::model::Reranker,
+    pub model: Arc<embed_anything::reranker::model::Reranker>,
 }
 
 #[pyclass(eq, eq_int)]
@@ -18,6 +24,7 @@ pub enum Dtype {
     BNB4,
     Q4F16,
     F32,
+    BF16,
 }
 
 #[pyclass]
@@ -104,12 +111,23 @@ impl RerankerResult {
 #[pymethods]
 impl Reranker {
     #[staticmethod]
-    #[pyo3(signature = (model_id, revision=None, dtype=None))]
+    #[pyo3(signature = (model_id, revision=None, dtype=None, normalization=None, calibration_offset=None, device=None))]
     pub fn from_pretrained(
         model_id: &str,
         revision: Option<&str>,
         dtype: Option<&Dtype>,
+        normalization: Option<&str>,
+        calibration_offset: Option<f32>,
+        device: Option<&str>,
     ) -> PyResult<Self> {
+        let normalization = normalization
+            .map(|normalization| {
+                ScoreNormalization::from_str(normalization).map_err(|_| {
+                    PyValueError::new_err(format!("Unknown normalization: {}", normalization))
+                })
+            })
+            .transpose()?
+            .unwrap_or_default();
         let dtype = match dtype {
             Some(Dtype::F16) => embed_anything::Dtype::F16,
             Some(Dtype::INT8) => embed_anything::Dtype::INT8,
@@ -117,11 +135,19 @@ impl Reranker {
             Some(Dtype::UINT8) => embed_anything::Dtype::UINT8,
             Some(Dtype::BNB4) => embed_anything::Dtype::BNB4,
             Some(Dtype::F32) => embed_anything::Dtype::F32,
+            Some(Dtype::BF16) => embed_anything::Dtype::BF16,
             _ => embed_anything::Dtype::F32,
         };
-        let model = embed_anything::reranker::model::Reranker::new(model_id, revision, dtype)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
-        Ok(Self { model })
+        let device = parse_device(device)?;
+        let model = embed_anything::reranker::model::Reranker::new_on_device(
+            model_id, revision, dtype, device,
+        )
+        .map_err(to_py_err)?
+        .with_normalization(normalization)
+        .with_calibration_offset(calibration_offset.unwrap_or_default());
+        Ok(Self {
+            model: Arc::new(model),
+        })
     }
 
     #[pyo3(signature = (query, documents, batch_size))]
@@ -135,7 +161,7 @@ impl Reranker {
         let document_refs: Vec<&str> = documents.iter().map(|s| s.as_str()).collect();
         let results = self
             .model
-            .rerank(query_refs, document_refs, batch_size)
+            .rank(query_refs, document_refs, batch_size)
             .unwrap();
         Ok(results
             .into_iter()
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use embed_anything::embeddings::embed::EmbeddingResult;
+use embed_anything::pipeline::{SearchAdapter, DEFAULT_CANDIDATES, DEFAULT_TOP_K};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use tokio::runtime::Builder;
+
+use crate::errors::to_py_err;
+use crate::models::reranker::Reranker;
+use crate::EmbeddingModel;
+
+/// Calls `adapter.query(embedding, top_k)` on a Python adapter. The adapter returns a list of dicts
+/// with `text`, `score` and optionally `metadata`.
+struct PyAdapter {
+    adapter: PyObject,
+}
+
+impl SearchAdapter for PyAdapter {
+    fn query(
+        &self,
+        embedding: &EmbeddingResult,
+        top_k: usize,
+    ) -> anyhow::Result<Vec<embed_anything::pipeline::ScoredChunk>> {
+        Python::with_gil(|py| {
+            let hits = match embedding {
+                EmbeddingResult::DenseVector(x) => {
+                    self.adapter.call_method1(py, "query", (x.clone(), top_k))?
+                }
+                EmbeddingResult::MultiVector(x) => {
+                    self.adapter.call_method1(py, "query", (x.clone(), top_k))?
+                }
+            };
+            let hits: Vec<Bound<'_, PyDict>> = hits.extract(py)?;
+            hits.iter()
+                .map(|hit| {
+                    let text = hit
+                        .get_item("text")?
+                        .ok_or_else(|| anyhow::anyhow!("adapter result is missing `text`"))?
+                        .extract::<String>()?;
+                    let score = hit
+                        .get_item("score")?
+                        .ok_or_else(|| anyhow::anyhow!("adapter result is missing `score`"))?
+                        .extract::<f32>()?;
+                    let metadata = match hit.get_item("metadata")? {
+                        Some(metadata) => metadata.extract::<Option<HashMap<String, String>>>()?,
+                        None => None,
+                    };
+                    Ok(embed_anything::pipeline::ScoredChunk {
+                        text,
+                        metadata,
+                        score,
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+#[pyclass]
+pub struct ScoredChunk {
+    pub inner: embed_anything::pipeline::ScoredChunk,
+}
+
+#[pymethods]
+impl ScoredChunk {
+    #[getter]
+    fn text(&self) -> String {
+        self.inner.text.clone()
+    }
+
+    #[getter]
+    fn metadata(&self) -> Option<HashMap<String, String>> {
+        self.inner.metadata.clone()
+    }
+
+    #[getter]
+    fn score(&self) -> f32 {
+        self.inner.score
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ScoredChunk(text={:?}, score={}, metadata={:?})",
+            self.inner.text, self.inner.score, self.inner.metadata
+        )
+    }
+}
+
+#[pyclass]
+pub struct Pipeline {
+    inner: embed_anything::pipeline::Pipeline<PyAdapter>,
+}
+
+#[pymethods]
+impl Pipeline {
+    #[new]
+    #[pyo3(signature = (embedder, adapter, reranker=None, top_k=DEFAULT_TOP_K, candidates=DEFAULT_CANDIDATES))]
+    fn new(
+        embedder: &EmbeddingModel,
+        adapter: PyObject,
+        reranker: Option<&Reranker>,
+        top_k: usize,
+        candidates: usize,
+    ) -> PyResult<Self> {
+        let mut inner = embed_anything::pipeline::Pipeline::new(
+            Arc::clone(embedder.embedder()?),
+            PyAdapter { adapter },
+        )
+        .with_top_k(top_k)
+        .with_candidates(candidates);
+        if let Some(reranker) = reranker {
+            inner = inner.with_reranker(Arc::clone(&reranker.model));
+        }
+        Ok(Self { inner })
+    }
+
+    fn retrieve(&self, query: &str) -> PyResult<Vec<ScoredChunk>> {
+        let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+        let chunks = rt.block_on(self.inner.retrieve(query)).map_err(to_py_err)?;
+        Ok(chunks
+            .into_iter()
+            .map(|chunk| ScoredChunk { inner: chunk })
+            .collect())
+    }
+}
@@ -1,6 +1,6 @@
-use embed_anything::Dtype;
+use embed_anything::{Dtype, Reranker};
 fn main() {
-    let reranker = embed_anything::reranker::model::Reranker::new(
+    let reranker = Reranker::new(
         "jinaai/jina-reranker-v2-base-multilingual",
         None,
         Dtype::F16,
@@ -18,9 +18,14 @@ fn main() {
         "Do you like pizza?",
     ];
 
-    let query = vec!["There is a cat outside"];
+    let query = "There is a cat outside";
 
-    let reranker_results = reranker.rerank(query, sentences, 32).unwrap();
+    for hit in reranker.rerank(query, &sentences, 3).unwrap() {
+        println!("{:.4} {}", hit.score, sentences[hit.index]);
+    }
+
+    let queries = ["There is a cat outside", "What should I watch tonight?"];
+    let reranker_results = reranker.rerank_batch(&queries, &sentences, 2).unwrap();
     let pretty_results = serde_json::to_string_pretty(&reranker_results).unwrap();
     println!("{}", pretty_results);
 }
@@ -0,0 +1,372 @@
+//! Stores embeddings in vector databases. A [`VectorAdapter`] creates the index and upserts the
+//! batches of embeddings, and an [`AdapterSink`] hands it the batches produced by `embed_file`,
+//! `embed_directory_stream` and the other functions that take an adapter callback.
+//!
+//! [`EmbeddingSink`] is the async counterpart of the callbacks, for stores whose clients are
+//! async. The directory, webpage and sitemap pipelines await it, so a slow sink slows the
+//! pipeline down instead of batches piling up in memory.
+//!
+//! A [`TenantAdapter`] keeps the embeddings of every namespace in a collection of their own, for
+//! one pipeline shared by several tenants.
+
+#[cfg(feature = "lancedb")]
+pub mod lancedb;
+pub mod qdrant;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use strum::EnumString;
+
+use crate::config::NAMESPACE_METADATA_KEY;
+use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+use crate::error::EmbedError;
+
+/// The similarity an index ranks vectors by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Metric {
+    #[default]
+    Cosine,
+    Dot,
+    Euclidean,
+}
+
+/// A vector database embeddings can be written to.
+pub trait VectorAdapter: Send + Sync {
+    /// Creates the index for vectors of `dimension`, unless it exists. With `multi_vector`, every
+    /// embedding is a set of vectors of that dimension, as produced by ColBERT and ColPali.
+    fn create_index(&self, dimension: usize, multi_vector: bool) -> anyhow::Result<()>;
+
+    /// Stores `embeddings`, replacing the ones with the same [`EmbedData::id`].
+    fn upsert(&self, embeddings: &[EmbedData]) -> anyhow::Result<()>;
+
+    fn delete_index(&self) -> anyhow::Result<()>;
+}
+
+/// Receives the batches of embeddings of a pipeline. The pipeline waits for `upsert` to finish
+/// before handing over the next batch, and stops at the first error.
+///
+/// Callbacks taking a `Vec<EmbedData>` are sinks that never fail, so the functions accepting a
+/// sink still accept the closures they took before. Sinks and their futures are `Send`, so a
+/// pipeline can drive them from a spawned task.
+///
+/// ```ignore
+/// struct Remote { client: MyAsyncClient }
+///
+/// impl EmbeddingSink for Remote {
+///     async fn upsert(&mut self, batch: Vec<EmbedData>) -> anyhow::Result<()> {
+///         self.client.upsert(batch).await
+///     }
+/// }
+///
+/// embed_directory_stream(directory, &embedder, None, None, Some(Remote { client })).await?;
+/// ```
+pub trait EmbeddingSink: Send {
+    fn upsert(&mut self, batch: Vec<EmbedData>) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+impl<F: Fn(Vec<EmbedData>) + Send> EmbeddingSink for F {
+    async fn upsert(&mut self, batch: Vec<EmbedData>) -> anyhow::Result<()> {
+        self(batch);
+        Ok(())
+    }
+}
+
+/// Writes the batches of a pipeline to a [`VectorAdapter`], creating the index from the first
+/// embedding. The adapter callbacks cannot fail, so the first error is kept, later batches are
+/// dropped and [`finish`](Self::finish) returns it.
+///
+/// ```no_run
+/// use embed_anything::adapters::qdrant::QdrantAdapter;
+/// use embed_anything::adapters::AdapterSink;
+/// use embed_anything::embed_file;
+/// use embed_anything::embeddings::embed::Embedder;
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let embedder = Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)?;
+/// let qdrant = QdrantAdapter::new("http://localhost:6333", "documents");
+/// let sink = AdapterSink::new(&qdrant);
+/// embed_file("report.pdf", &embedder, None, Some(|batch| sink.write(batch))).await?;
+/// sink.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AdapterSink<'a, A: VectorAdapter + ?Sized> {
+    adapter: &'a A,
+    index_created: AtomicBool,
+    error: Mutex<Option<anyhow::Error>>,
+}
+
+impl<'a, A: VectorAdapter + ?Sized> AdapterSink<'a, A> {
+    pub fn new(adapter: &'a A) -> Self {
+        Self {
+            adapter,
+            index_created: AtomicBool::new(false),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Upserts `embeddings`, unless a previous batch failed.
+    pub fn write(&self, embeddings: Vec<EmbedData>) {
+        let mut error = self.error.lock().unwrap();
+        if error.is_some() || embeddings.is_empty() {
+            return;
+        }
+        if let Err(e) = self.upsert(&embeddings) {
+            tracing::error!("Error writing embeddings to the vector database: {:?}", e);
+            *error = Some(e);
+        }
+    }
+
+    fn upsert(&self, embeddings: &[EmbedData]) -> anyhow::Result<()> {
+        if !self.index_created.load(Ordering::Acquire) {
+            let (dimension, multi_vector) = index_shape(&embeddings[0]);
+            self.adapter.create_index(dimension, multi_vector)?;
+            self.index_created.store(true, Ordering::Release);
+        }
+        self.adapter.upsert(embeddings)
+    }
+
+    /// Returns the first error of the batches written so far.
+    pub fn finish(self) -> anyhow::Result<()> {
+        match self.error.into_inner().unwrap() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The dimension and kind of the vectors of `embedding`, as [`VectorAdapter::create_index`] takes
+/// them.
+pub(crate) fn index_shape(embedding: &EmbedData) -> (usize, bool) {
+    match &embedding.embedding {
+        EmbeddingResult::DenseVector(vector) => (vector.len(), false),
+        EmbeddingResult::MultiVector(vectors) => (vectors.first().map_or(0, Vec::len), true),
+    }
+}
+
+/// Routes embeddings to one adapter per tenant, picked by the namespace that
+/// [`MetadataConfig::namespace`](crate::config::MetadataConfig::namespace) adds to their metadata.
+/// The adapter of a namespace is made by `make_adapter` on its first batch, and its index is
+/// created with the dimension given to [`create_index`](VectorAdapter::create_index).
+///
+/// Embeddings without a namespace are rejected, so no tenant writes to a shared collection.
+///
+/// ```no_run
+/// use embed_anything::adapters::qdrant::QdrantAdapter;
+/// use embed_anything::adapters::TenantAdapter;
+///
+/// let adapter = TenantAdapter::new(|namespace: &str| {
+///     QdrantAdapter::new("http://localhost:6333", &format!("documents_{}", namespace))
+/// });
+/// ```
+pub struct TenantAdapter<A, F> {
+    make_adapter: F,
+    /// The dimension and kind of the vectors, once [`create_index`](VectorAdapter::create_index)
+    /// was called.
+    index: Mutex<Option<(usize, bool)>>,
+    tenants: Mutex<HashMap<String, Arc<A>>>,
+}
+
+impl<A, F> TenantAdapter<A, F>
+where
+    A: VectorAdapter,
+    F: Fn(&str) -> A + Send + Sync,
+{
+    pub fn new(make_adapter: F) -> Self {
+        Self {
+            make_adapter,
+            index: Mutex::new(None),
+            tenants: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The adapter of `namespace`, made and given an index the first time it is asked for.
+    fn tenant(&self, namespace: &str) -> anyhow::Result<Arc<A>> {
+        let mut tenants = self.tenants.lock().unwrap();
+        if let Some(adapter) = tenants.get(namespace) {
+            return Ok(adapter.clone());
+        }
+        let adapter = (self.make_adapter)(namespace);
+        if let Some((dimension, multi_vector)) = *self.index.lock().unwrap() {
+            adapter.create_index(dimension, multi_vector)?;
+        }
+        let adapter = Arc::new(adapter);
+        tenants.insert(namespace.to_string(), adapter.clone());
+        Ok(adapter)
+    }
+}
+
+impl<A, F> VectorAdapter for TenantAdapter<A, F>
+where
+    A: VectorAdapter,
+    F: Fn(&str) -> A + Send + Sync,
+{
+    fn create_index(&self, dimension: usize, multi_vector: bool) -> anyhow::Result<()> {
+        *self.index.lock().unwrap() = Some((dimension, multi_vector));
+        for adapter in self.tenants.lock().unwrap().values() {
+            adapter.create_index(dimension, multi_vector)?;
+        }
+        Ok(())
+    }
+
+    /// Upserts every run of embeddings sharing a namespace to the adapter of that namespace.
+    fn upsert(&self, embeddings: &[EmbedData]) -> anyhow::Result<()> {
+        let namespace = |embedding: &EmbedData| {
+            embedding
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get(NAMESPACE_METADATA_KEY))
+                .cloned()
+        };
+        for run in embeddings.chunk_by(|a, b| namespace(a) == namespace(b)) {
+            let namespace = namespace(&run[0]).ok_or_else(|| {
+                EmbedError::InvalidConfig(format!(
+                    "Embedding has no {} in its metadata",
+                    NAMESPACE_METADATA_KEY
+                ))
+            })?;
+            self.tenant(&namespace)?.upsert(run)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the indexes of the namespaces written to so far.
+    fn delete_index(&self) -> anyhow::Result<()> {
+        for adapter in self.tenants.lock().unwrap().values() {
+            adapter.delete_index()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingAdapter {
+        indexes: Mutex<Vec<(usize, bool)>>,
+        upserted: Mutex<usize>,
+    }
+
+    impl VectorAdapter for RecordingAdapter {
+        fn create_index(&self, dimension: usize, multi_vector: bool) -> anyhow::Result<()> {
+            self.indexes.lock().unwrap().push((dimension, multi_vector));
+            Ok(())
+        }
+
+        fn upsert(&self, embeddings: &[EmbedData]) -> anyhow::Result<()> {
+            if embeddings.iter().any(|embedding| embedding.text.is_none()) {
+                anyhow::bail!("missing text");
+            }
+            *self.upserted.lock().unwrap() += embeddings.len();
+            Ok(())
+        }
+
+        fn delete_index(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn embedding(text: Option<&str>) -> EmbedData {
+        EmbedData::new(
+            EmbeddingResult::MultiVector(vec![vec![0.0; 4]; 3]),
+            text.map(str::to_string),
+            None,
+        )
+    }
+
+    /// Records the calls made to the adapter of one namespace.
+    struct TenantLog {
+        namespace: String,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl VectorAdapter for TenantLog {
+        fn create_index(&self, dimension: usize, _multi_vector: bool) -> anyhow::Result<()> {
+            let call = format!("{}: index {}", self.namespace, dimension);
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+
+        fn upsert(&self, embeddings: &[EmbedData]) -> anyhow::Result<()> {
+            let call = format!("{}: upsert {}", self.namespace, embeddings.len());
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+
+        fn delete_index(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_tenant_adapter_routes_by_namespace() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let adapter = TenantAdapter::new(|namespace: &str| TenantLog {
+            namespace: namespace.to_string(),
+            calls: calls.clone(),
+        });
+        let tagged = |namespace: &str| {
+            let mut embedding = embedding(Some("a"));
+            embedding.metadata = Some(HashMap::from([(
+                NAMESPACE_METADATA_KEY.to_string(),
+                namespace.to_string(),
+            )]));
+            embedding
+        };
+        let sink = AdapterSink::new(&adapter);
+        sink.write(vec![tagged("a"), tagged("a"), tagged("b")]);
+        sink.write(vec![tagged("b")]);
+        assert!(sink.finish().is_ok());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "a: index 4",
+                "a: upsert 2",
+                "b: index 4",
+                "b: upsert 1",
+                "b: upsert 1"
+            ]
+        );
+
+        assert!(adapter.upsert(&[embedding(Some("c"))]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_callbacks_are_embedding_sinks() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut callback = {
+            let received = received.clone();
+            move |batch: Vec<EmbedData>| received.lock().unwrap().extend(batch)
+        };
+        callback.upsert(vec![embedding(Some("a"))]).await.unwrap();
+        // Sinks can be driven from a spawned task.
+        tokio::spawn(async move { callback.upsert(vec![embedding(Some("b"))]).await })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_sink_creates_index_once_and_keeps_first_error() {
+        let adapter = RecordingAdapter::default();
+        let sink = AdapterSink::new(&adapter);
+        sink.write(vec![embedding(Some("a")), embedding(Some("b"))]);
+        sink.write(vec![embedding(Some("c"))]);
+        assert!(sink.finish().is_ok());
+        assert_eq!(*adapter.indexes.lock().unwrap(), vec![(4, true)]);
+        assert_eq!(*adapter.upserted.lock().unwrap(), 3);
+
+        let sink = AdapterSink::new(&adapter);
+        sink.write(vec![embedding(None)]);
+        sink.write(vec![embedding(Some("d"))]);
+        assert!(sink.finish().is_err());
+        assert_eq!(*adapter.upserted.lock().unwrap(), 3);
+    }
+}
@@ -0,0 +1,332 @@
+//! Stores embeddings in a [LanceDB](https://lancedb.com) table. Rows have an `id` column with
+//! the [`EmbedData::id`] of the chunk, the `text`, the `vector`, one `Utf8` column per metadata
+//! field chosen with [`LanceDbAdapter::with_metadata_columns`] and the whole metadata as JSON in
+//! `metadata`.
+//!
+//! LanceDB's API is async while adapters are called synchronously from the pipelines, so the
+//! adapter runs the LanceDB calls on a runtime of its own.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use ::lancedb::connection::{Connection, CreateTableMode};
+use ::lancedb::Table;
+use arrow_array::{
+    Array, ArrayRef, FixedSizeListArray, Float32Array, ListArray, RecordBatch, RecordBatchIterator,
+    StringArray,
+};
+use arrow_buffer::{OffsetBuffer, ScalarBuffer};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use tokio::runtime::Runtime;
+
+use crate::adapters::VectorAdapter;
+use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+use crate::error::EmbedError;
+
+/// How [`LanceDbAdapter::create_index`](VectorAdapter::create_index) treats an existing table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Keep the rows of an existing table and upsert into it by `id`.
+    #[default]
+    Append,
+    /// Replace an existing table with an empty one.
+    Overwrite,
+}
+
+pub struct LanceDbAdapter {
+    runtime: Runtime,
+    connection: Connection,
+    table_name: String,
+    mode: WriteMode,
+    metadata_columns: Vec<String>,
+    table: Mutex<Option<(Table, SchemaRef)>>,
+}
+
+impl LanceDbAdapter {
+    /// Connects to the database at `uri`, a local directory or an object store URI such as
+    /// `s3://bucket/path`, and writes to `table`.
+    pub fn new(uri: &str, table: &str) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let connection = block_on(&runtime, ::lancedb::connect(uri).execute())?;
+        Ok(Self {
+            runtime,
+            connection,
+            table_name: table.to_string(),
+            mode: WriteMode::default(),
+            metadata_columns: Vec::new(),
+            table: Mutex::new(None),
+        })
+    }
+
+    pub fn with_mode(mut self, mode: WriteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Metadata fields stored in columns of their own, e.g. `file_name` or `page_number`, so they
+    /// can be filtered on. Chunks without the field get a null. Only used when the table is
+    /// created.
+    pub fn with_metadata_columns<S: Into<String>>(
+        mut self,
+        columns: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.metadata_columns = columns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn block_on<F: Future + Send>(&self, future: F) -> F::Output
+    where
+        F::Output: Send,
+    {
+        block_on(&self.runtime, future)
+    }
+
+    fn schema(&self, dimension: usize, multi_vector: bool) -> SchemaRef {
+        let vector = vector_type(dimension);
+        let vector = if multi_vector {
+            DataType::List(Arc::new(Field::new("item", vector, false)))
+        } else {
+            vector
+        };
+        let mut fields = vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("text", DataType::Utf8, true),
+            Field::new("vector", vector, false),
+        ];
+        fields.extend(
+            self.metadata_columns
+                .iter()
+                .map(|column| Field::new(column, DataType::Utf8, true)),
+        );
+        fields.push(Field::new("metadata", DataType::Utf8, true));
+        Arc::new(Schema::new(fields))
+    }
+}
+
+impl VectorAdapter for LanceDbAdapter {
+    fn create_index(&self, dimension: usize, multi_vector: bool) -> anyhow::Result<()> {
+        let schema = self.schema(dimension, multi_vector);
+        let builder = self
+            .connection
+            .create_empty_table(&self.table_name, schema.clone());
+        let builder = match self.mode {
+            WriteMode::Append => builder.mode(CreateTableMode::exist_ok(|request| request)),
+            WriteMode::Overwrite => builder.mode(CreateTableMode::Overwrite),
+        };
+        let table = self.block_on(builder.execute())?;
+        // An existing table keeps its own schema, which the batches have to match.
+        let schema = self.block_on(table.schema())?;
+        *self.table.lock().unwrap() = Some((table, schema));
+        Ok(())
+    }
+
+    fn upsert(&self, embeddings: &[EmbedData]) -> anyhow::Result<()> {
+        let Some((table, schema)) = self.table.lock().unwrap().clone() else {
+            return Err(EmbedError::InvalidConfig(format!(
+                "Table {} was not created before upserting",
+                self.table_name
+            ))
+            .into());
+        };
+        let batch = record_batch(embeddings, schema.clone())?;
+        let reader = RecordBatchIterator::new(vec![Ok(batch)], schema);
+        match self.mode {
+            WriteMode::Append => {
+                let mut merge = table.merge_insert(&["id"]);
+                merge
+                    .when_matched_update_all(None)
+                    .when_not_matched_insert_all();
+                self.block_on(merge.execute(Box::new(reader)))?;
+            }
+            // The table was emptied by `create_index`, so the ids only repeat when the same chunk
+            // is embedded twice in one run.
+            WriteMode::Overwrite => {
+                self.block_on(table.add(reader).execute())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_index(&self) -> anyhow::Result<()> {
+        self.block_on(self.connection.drop_table(&self.table_name))?;
+        *self.table.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+/// Runs `future` to completion on `runtime` from a thread of its own, since the pipelines call
+/// adapters from within their own tokio runtime, where blocking on another one panics.
+fn block_on<F: Future + Send>(runtime: &Runtime, future: F) -> F::Output
+where
+    F::Output: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| runtime.block_on(future))
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+fn vector_type(dimension: usize) -> DataType {
+    DataType::FixedSizeList(
+        Arc::new(Field::new("item", DataType::Float32, true)),
+        dimension as i32,
+    )
+}
+
+/// The rows of `embeddings` as a batch of `schema`, as made by [`LanceDbAdapter::schema`].
+fn record_batch(embeddings: &[EmbedData], schema: SchemaRef) -> anyhow::Result<RecordBatch> {
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+    for field in schema.fields() {
+        let column: ArrayRef = match field.name().as_str() {
+            "id" => Arc::new(StringArray::from_iter_values(
+                embeddings.iter().map(EmbedData::id),
+            )),
+            "text" => Arc::new(StringArray::from_iter(
+                embeddings.iter().map(|embedding| embedding.text.as_deref()),
+            )),
+            "vector" => vector_column(embeddings, field.data_type())?,
+            "metadata" => Arc::new(StringArray::from_iter(embeddings.iter().map(|embedding| {
+                embedding
+                    .metadata
+                    .as_ref()
+                    .map(|metadata| serde_json::to_string(metadata).unwrap_or_default())
+            }))),
+            key => Arc::new(StringArray::from_iter(embeddings.iter().map(|embedding| {
+                embedding
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.get(key))
+                    .map(String::as_str)
+            }))),
+        };
+        columns.push(column);
+    }
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn vector_column(embeddings: &[EmbedData], data_type: &DataType) -> anyhow::Result<ArrayRef> {
+    let (item, dimension, multi_vector) = match data_type {
+        DataType::FixedSizeList(item, dimension) => (item.clone(), *dimension as usize, false),
+        DataType::List(list_item) => match list_item.data_type() {
+            DataType::FixedSizeList(item, dimension) => (item.clone(), *dimension as usize, true),
+            other => return Err(unexpected_vector_type(other)),
+        },
+        other => return Err(unexpected_vector_type(other)),
+    };
+
+    let mut values = Vec::new();
+    let mut lengths = Vec::with_capacity(embeddings.len());
+    for embedding in embeddings {
+        let vectors = match (&embedding.embedding, multi_vector) {
+            (EmbeddingResult::DenseVector(vector), false) => std::slice::from_ref(vector),
+            (EmbeddingResult::MultiVector(vectors), true) => vectors.as_slice(),
+            _ => {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "The vector column holds {} embeddings",
+                    if multi_vector {
+                        "multi-vector"
+                    } else {
+                        "dense"
+                    }
+                ))
+                .into())
+            }
+        };
+        for vector in vectors {
+            if vector.len() != dimension {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "Embedding of dimension {} does not fit the vector column of dimension {}",
+                    vector.len(),
+                    dimension
+                ))
+                .into());
+            }
+            values.extend_from_slice(vector);
+        }
+        lengths.push(vectors.len());
+    }
+
+    let vectors = FixedSizeListArray::try_new(
+        item,
+        dimension as i32,
+        Arc::new(Float32Array::new(ScalarBuffer::from(values), None)),
+        None,
+    )?;
+    if !multi_vector {
+        return Ok(Arc::new(vectors));
+    }
+    let field = Arc::new(Field::new("item", vectors.data_type().clone(), false));
+    Ok(Arc::new(ListArray::try_new(
+        field,
+        OffsetBuffer::from_lengths(lengths),
+        Arc::new(vectors),
+        None,
+    )?))
+}
+
+fn unexpected_vector_type(data_type: &DataType) -> anyhow::Error {
+    EmbedError::InvalidConfig(format!(
+        "Unexpected type of the vector column: {}",
+        data_type
+    ))
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use arrow_array::cast::AsArray;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn embedding(embedding: EmbeddingResult, file_name: &str) -> EmbedData {
+        let metadata = HashMap::from([("file_name".to_string(), file_name.to_string())]);
+        EmbedData::new(embedding, Some("text".to_string()), Some(metadata))
+    }
+
+    #[test]
+    fn test_record_batch_maps_metadata_to_columns() {
+        let dir = TempDir::new("lancedb").unwrap();
+        let adapter = LanceDbAdapter::new(dir.path().to_str().unwrap(), "chunks")
+            .unwrap()
+            .with_metadata_columns(["file_name", "page_number"]);
+
+        let schema = adapter.schema(2, true);
+        let embeddings = [
+            embedding(vec![vec![1.0, 0.0], vec![0.0, 1.0]].into(), "a.pdf"),
+            embedding(vec![vec![0.5, 0.5]].into(), "b.pdf"),
+        ];
+        let batch = record_batch(&embeddings, schema.clone()).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.column(3).as_string::<i32>().value(1), "b.pdf");
+        assert!(batch.column(4).is_null(0));
+        assert_eq!(batch.column(2).as_list::<i32>().value(0).len(), 2);
+
+        let dense = [embedding(vec![1.0, 0.0].into(), "a.pdf")];
+        assert!(record_batch(&dense, schema).is_err());
+    }
+
+    #[test]
+    fn test_append_upserts_by_id() {
+        let dir = TempDir::new("lancedb").unwrap();
+        let adapter = LanceDbAdapter::new(dir.path().to_str().unwrap(), "chunks").unwrap();
+        let embeddings = [
+            embedding(vec![1.0, 0.0].into(), "a.txt"),
+            embedding(vec![0.0, 1.0].into(), "b.txt"),
+        ];
+        adapter.create_index(2, false).unwrap();
+        adapter.upsert(&embeddings).unwrap();
+        adapter.upsert(&embeddings).unwrap();
+
+        let (table, _) = adapter.table.lock().unwrap().clone().unwrap();
+        assert_eq!(adapter.block_on(table.count_rows(None)).unwrap(), 2);
+        adapter.delete_index().unwrap();
+    }
+}
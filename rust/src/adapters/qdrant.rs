@@ -0,0 +1,224 @@
+//! Stores embeddings in a [Qdrant](https://qdrant.tech) collection through its REST API. Points
+//! get the [`EmbedData::id`] of the chunk as id and carry its text and metadata as payload, so
+//! embedding the same files again replaces their points.
+
+use std::collections::HashMap;
+
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde_json::{json, Value};
+
+use crate::adapters::{Metric, VectorAdapter};
+use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+use crate::error::EmbedError;
+use crate::pipeline::{ScoredChunk, SearchAdapter};
+
+/// Number of points sent to Qdrant per request unless set with [`QdrantAdapter::with_batch_size`].
+pub const DEFAULT_QDRANT_BATCH_SIZE: usize = 100;
+
+pub struct QdrantAdapter {
+    client: Client,
+    url: String,
+    collection: String,
+    api_key: Option<String>,
+    metric: Metric,
+    batch_size: usize,
+}
+
+impl QdrantAdapter {
+    /// Writes to `collection` of the Qdrant server at `url`, e.g. `http://localhost:6333`.
+    pub fn new(url: &str, collection: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            collection: collection.to_string(),
+            api_key: None,
+            metric: Metric::default(),
+            batch_size: DEFAULT_QDRANT_BATCH_SIZE,
+        }
+    }
+
+    /// Sends `api_key` with every request, as Qdrant Cloud requires.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// The distance the collection is created with. Has no effect on existing collections.
+    pub fn with_metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    fn collection_url(&self, path: &str) -> String {
+        format!("{}/collections/{}{}", self.url, self.collection, path)
+    }
+
+    fn send(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        let request = match &self.api_key {
+            Some(api_key) => request.header("api-key", api_key),
+            None => request,
+        };
+        let response = request
+            .send()
+            .map_err(|e| EmbedError::Network(e.to_string()))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().unwrap_or_default();
+            return Err(EmbedError::Network(format!("{}: {}", status, body)).into());
+        }
+        Ok(response)
+    }
+}
+
+impl VectorAdapter for QdrantAdapter {
+    fn create_index(&self, dimension: usize, multi_vector: bool) -> anyhow::Result<()> {
+        let exists: Value = self
+            .send(self.client.get(self.collection_url("/exists")))?
+            .json()?;
+        if exists["result"]["exists"].as_bool() == Some(true) {
+            return Ok(());
+        }
+        self.send(
+            self.client
+                .put(self.collection_url(""))
+                .json(&collection_config(dimension, multi_vector, self.metric)),
+        )?;
+        Ok(())
+    }
+
+    fn upsert(&self, embeddings: &[EmbedData]) -> anyhow::Result<()> {
+        for batch in embeddings.chunks(self.batch_size) {
+            let points = batch.iter().map(point).collect::<Vec<_>>();
+            self.send(
+                self.client
+                    .put(self.collection_url("/points?wait=true"))
+                    .json(&json!({ "points": points })),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn delete_index(&self) -> anyhow::Result<()> {
+        self.send(self.client.delete(self.collection_url("")))?;
+        Ok(())
+    }
+}
+
+impl SearchAdapter for QdrantAdapter {
+    fn query(&self, embedding: &EmbeddingResult, top_k: usize) -> anyhow::Result<Vec<ScoredChunk>> {
+        let response: Value = self
+            .send(
+                self.client
+                    .post(self.collection_url("/points/query"))
+                    .json(&json!({
+                        "query": vector(embedding),
+                        "limit": top_k,
+                        "with_payload": true,
+                    })),
+            )?
+            .json()?;
+        let points = response["result"]["points"].as_array().ok_or_else(|| {
+            EmbedError::Network(format!("Unexpected response from Qdrant: {}", response))
+        })?;
+        Ok(points
+            .iter()
+            .map(|point| scored_chunk(point, self.metric))
+            .collect())
+    }
+}
+
+fn collection_config(dimension: usize, multi_vector: bool, metric: Metric) -> Value {
+    let distance = match metric {
+        Metric::Cosine => "Cosine",
+        Metric::Dot => "Dot",
+        Metric::Euclidean => "Euclid",
+    };
+    let mut vectors = json!({ "size": dimension, "distance": distance });
+    if multi_vector {
+        vectors["multivector_config"] = json!({ "comparator": "max_sim" });
+    }
+    json!({ "vectors": vectors })
+}
+
+fn vector(embedding: &EmbeddingResult) -> Value {
+    match embedding {
+        EmbeddingResult::DenseVector(vector) => json!(vector),
+        EmbeddingResult::MultiVector(vectors) => json!(vectors),
+    }
+}
+
+fn point(embedding: &EmbedData) -> Value {
+    json!({
+        "id": embedding.id(),
+        "vector": vector(&embedding.embedding),
+        "payload": {
+            "text": embedding.text,
+            "metadata": embedding.metadata,
+        },
+    })
+}
+
+fn scored_chunk(point: &Value, metric: Metric) -> ScoredChunk {
+    let payload = &point["payload"];
+    let metadata = payload["metadata"].as_object().map(|metadata| {
+        metadata
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+            .collect::<HashMap<_, _>>()
+    });
+    let score = point["score"].as_f64().unwrap_or_default() as f32;
+    ScoredChunk {
+        text: payload["text"].as_str().unwrap_or_default().to_string(),
+        metadata,
+        // Qdrant scores Euclidean matches by distance, lower is better.
+        score: if metric == Metric::Euclidean {
+            -score
+        } else {
+            score
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collection_config() {
+        let dense = collection_config(384, false, Metric::Euclidean);
+        assert_eq!(
+            dense,
+            json!({ "vectors": { "size": 384, "distance": "Euclid" } })
+        );
+
+        let multi = collection_config(128, true, Metric::Cosine);
+        assert_eq!(
+            multi["vectors"]["multivector_config"]["comparator"],
+            "max_sim"
+        );
+    }
+
+    #[test]
+    fn test_point_and_scored_chunk() {
+        let metadata = HashMap::from([("file_name".to_string(), "a.txt".to_string())]);
+        let embedding = EmbedData::new(
+            EmbeddingResult::MultiVector(vec![vec![1.0, 0.0], vec![0.0, 1.0]]),
+            Some("hello".to_string()),
+            Some(metadata.clone()),
+        );
+        let mut point = point(&embedding);
+        assert_eq!(point["id"], embedding.id());
+        assert_eq!(point["vector"], json!([[1.0, 0.0], [0.0, 1.0]]));
+
+        point["score"] = json!(0.5);
+        let chunk = scored_chunk(&point, Metric::Euclidean);
+        assert_eq!(chunk.text, "hello");
+        assert_eq!(chunk.metadata, Some(metadata));
+        assert_eq!(chunk.score, -0.5);
+    }
+}
@@ -0,0 +1,204 @@
+//! Content-defined chunking. Boundaries are placed where a rolling hash of the last 64 bytes
+//! matches a pattern, rather than every N tokens, so inserting a paragraph only changes the chunks
+//! around it: the rest of the document produces the same chunks as before and can be skipped by
+//! deduplication or incremental indexing.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Rough number of UTF-8 bytes per token of the cl100k tokenizer, used to turn a chunk size in
+/// tokens into bytes.
+pub const BYTES_PER_TOKEN: usize = 4;
+/// Average length of a word and the space after it in bytes. Only whitespace and the punctuation
+/// in [`BREAKS`] are candidate boundaries, so the hash is checked about once per word.
+const BYTES_PER_WORD: usize = 6;
+/// Punctuation that ends a clause in scripts written without spaces, such as Chinese and Japanese.
+const BREAKS: &[&str] = &["。", "，", "、", "！", "？", "；", "：", "」", "』", "।"];
+
+/// Splits text at whitespace or clause punctuation chosen by a gear hash (as in FastCDC). Chunks
+/// are at least `min_size` bytes, at most `max_size` bytes and `avg_size` bytes on average. A
+/// chunk is only cut inside a word when the word is longer than `max_size`, and then between
+/// grapheme clusters, so emoji sequences and combining marks stay whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentDefinedChunker {
+    pub min_size: usize,
+    pub avg_size: usize,
+    pub max_size: usize,
+}
+
+impl ContentDefinedChunker {
+    pub fn new(min_size: usize, avg_size: usize, max_size: usize) -> Self {
+        Self {
+            min_size,
+            avg_size: avg_size.max(min_size),
+            max_size: max_size.max(avg_size).max(1),
+        }
+    }
+
+    /// Chunks of about `chunk_size` tokens, between a quarter and twice that.
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        let avg_size = chunk_size * BYTES_PER_TOKEN;
+        Self::new(avg_size / 4, avg_size, avg_size * 2)
+    }
+
+    /// The number of high bits of the hash that must be zero at a boundary.
+    fn mask_bits(&self) -> u32 {
+        let candidates = ((self.avg_size - self.min_size) / BYTES_PER_WORD).max(1);
+        candidates.ilog2()
+    }
+
+    /// Splits `text` into trimmed, non-empty chunks, in document order.
+    pub fn chunk<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let bits = self.mask_bits();
+        let is_boundary = |hash: u64| bits == 0 || hash >> (64 - bits) == 0;
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let mut last_space = None;
+        let mut hash = 0u64;
+        for (i, grapheme) in text.grapheme_indices(true) {
+            if i > start && i + grapheme.len() - start > self.max_size {
+                // Cut at the last whitespace so words stay intact, unless there is none.
+                let end = last_space.unwrap_or(i);
+                chunks.push(&text[start..end]);
+                start = end;
+                last_space = None;
+            }
+            // The hash is never reset, so whether a position is a boundary only depends on the
+            // 64 bytes before it and not on where the previous chunk ended.
+            for &byte in grapheme.as_bytes() {
+                hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+            }
+            if grapheme.chars().all(char::is_whitespace) || BREAKS.contains(&grapheme) {
+                let end = i + grapheme.len();
+                if end - start >= self.min_size && is_boundary(hash) {
+                    chunks.push(&text[start..end]);
+                    start = end;
+                    last_space = None;
+                } else {
+                    last_space = Some(end);
+                }
+            }
+        }
+        chunks.push(&text[start..]);
+
+        chunks
+            .into_iter()
+            .map(str::trim)
+            .filter(|chunk| !chunk.is_empty())
+            .collect()
+    }
+}
+
+/// 256 pseudo-random values, one per byte, generated with splitmix64 so the boundaries are the same
+/// on every platform and release.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(paragraphs: usize) -> Vec<String> {
+        (0..paragraphs)
+            .map(|p| {
+                (0..40)
+                    .map(|w| format!("word{}x{}", p, w))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_sizes() {
+        let text = document(50).join("\n\n");
+        let chunker = ContentDefinedChunker::with_chunk_size(64);
+        let chunks = chunker.chunk(&text);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= chunker.max_size));
+        assert_eq!(
+            chunks.concat().split_whitespace().collect::<String>(),
+            text.split_whitespace().collect::<String>()
+        );
+    }
+
+    #[test]
+    fn test_insertion_keeps_other_chunks() {
+        let mut paragraphs = document(50);
+        let chunker = ContentDefinedChunker::with_chunk_size(64);
+        let original = paragraphs.join("\n\n");
+        let before = chunker.chunk(&original);
+        paragraphs.insert(
+            25,
+            "An inserted paragraph about something else.".to_string(),
+        );
+        let text = paragraphs.join("\n\n");
+        let after = chunker.chunk(&text);
+
+        let changed = after.iter().filter(|chunk| !before.contains(chunk)).count();
+        assert!(changed > 0);
+        assert!(
+            changed <= 3,
+            "{} of {} chunks changed",
+            changed,
+            after.len()
+        );
+    }
+
+    #[test]
+    fn test_long_word_is_split_on_char_boundaries() {
+        let text = "é".repeat(1000);
+        let chunks = ContentDefinedChunker::new(0, 10, 101).chunk(&text);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn test_long_word_is_split_on_grapheme_boundaries() {
+        // A family emoji joined with ZWJs, and an "e" followed by a combining acute accent.
+        for grapheme in ["\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}", "e\u{301}"] {
+            let text = grapheme.repeat(200);
+            let chunks = ContentDefinedChunker::new(0, 10, 101).chunk(&text);
+            assert!(chunks.len() > 1);
+            assert_eq!(chunks.concat(), text);
+            for chunk in chunks {
+                assert!(chunk.graphemes(true).all(|g| g == grapheme), "{:?}", chunk);
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_without_spaces_is_split_at_punctuation() {
+        let sentence = |i: usize| format!("第{}句话没有空格，但是有标点符号。", i);
+        let mut sentences = (0..200).map(sentence).collect::<Vec<_>>();
+        let chunker = ContentDefinedChunker::with_chunk_size(32);
+        let original = sentences.concat();
+        let before = chunker.chunk(&original);
+        assert!(before
+            .iter()
+            .all(|chunk| chunk.ends_with('。') || chunk.ends_with('，')));
+
+        sentences.insert(100, "插入的句子。".to_string());
+        let text = sentences.concat();
+        let after = chunker.chunk(&text);
+        let changed = after.iter().filter(|chunk| !before.contains(chunk)).count();
+        assert!(
+            changed <= 3,
+            "{} of {} chunks changed",
+            changed,
+            after.len()
+        );
+    }
+}
@@ -107,7 +107,7 @@ impl<Sizer: ChunkSizer> CumulativeChunker<Sizer> {
         }
 
         for chunk in chunks {
-            println!("----------CHUNK ------\n{}", chunk);
+            tracing::debug!("----------CHUNK ------\n{}", chunk);
         }
     }
 
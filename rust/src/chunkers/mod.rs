@@ -1,2 +1,4 @@
+pub mod content_defined;
 pub mod cumulative;
 pub mod statistical;
+pub mod token;
@@ -79,7 +79,8 @@ impl StatisticalChunker {
         if text.is_empty() {
             return None;
         }
-        if text.len() < chunk_size {
+        // `chunk_size` counts words, not bytes, so multi-byte scripts are not split early.
+        if text.split_whitespace().count() < chunk_size {
             chunks.push(text.to_owned());
             return Some(chunks);
         }
@@ -117,7 +118,7 @@ impl StatisticalChunker {
         // let splits = self.split_into_sentences(text, 50).unwrap();
         if self.verbose {
             for split in splits.iter() {
-                println!("-----Split---\n{}", split);
+                tracing::debug!("-----Split---\n{}", split);
             }
         }
         let mut chunks: Vec<String> = Vec::new();
@@ -164,7 +165,7 @@ impl StatisticalChunker {
 
         if self.verbose {
             for chunk in chunks.iter() {
-                println!("-----Chunk---\n{}", chunk);
+                tracing::debug!("-----Chunk---\n{}", chunk);
             }
         }
         chunks
@@ -259,7 +260,7 @@ impl StatisticalChunker {
         let mut calculated_threshold = 0.0;
 
         while low <= high {
-            println!("Iteration: {}", iteration);
+            tracing::trace!("Iteration: {}", iteration);
             calculated_threshold = (low + high) / 2.0;
             let split_indices = self._find_split_indices(similarities, calculated_threshold);
             let split_token_counts: Vec<usize> = [0]
@@ -0,0 +1,69 @@
+//! Chunking in the tokens of the embedding model. Chunks sized with another tokenizer can still be
+//! longer than the model reads, so their end is cut off; counting with the model's own tokenizer
+//! keeps every chunk within its limit.
+
+use anyhow::Error;
+use text_splitter::{ChunkConfig, TextSplitter};
+use tokenizers::Tokenizer;
+
+/// Splits text at sentence and word boundaries into chunks of at most `max_tokens` tokens of a
+/// model, counting the special tokens the model adds around every input.
+#[derive(Debug)]
+pub struct TokenChunker {
+    splitter: TextSplitter<Tokenizer>,
+}
+
+impl TokenChunker {
+    /// Chunks of at most `max_tokens` tokens of `tokenizer`, consecutive chunks sharing
+    /// `overlap_tokens` tokens. The overlap is capped below the chunk size.
+    pub fn new(
+        tokenizer: &Tokenizer,
+        max_tokens: usize,
+        overlap_tokens: usize,
+    ) -> Result<Self, Error> {
+        // The model's tokenizer truncates and pads its inputs, which would hide how long a chunk
+        // really is.
+        let mut tokenizer = tokenizer.clone();
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| Error::msg(e.to_string()))?
+            .with_padding(None);
+        let special_tokens = tokenizer
+            .encode("", true)
+            .map_err(|e| Error::msg(e.to_string()))?
+            .len();
+        let capacity = max_tokens.saturating_sub(special_tokens).max(1);
+        let config = ChunkConfig::new(capacity)
+            .with_overlap(overlap_tokens.min(capacity - 1))?
+            .with_sizer(tokenizer);
+        Ok(Self {
+            splitter: TextSplitter::new(config),
+        })
+    }
+
+    /// Splits `text` into trimmed, non-empty chunks, in document order.
+    pub fn chunk<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        self.splitter.chunks(text).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::local::bert::{BertEmbed, BertEmbedder};
+
+    #[test]
+    fn test_chunks_fit_the_model() {
+        let embedder = BertEmbedder::default();
+        let tokenizer = embedder.tokenizer().unwrap();
+        let chunker = TokenChunker::new(tokenizer, 16, 4).unwrap();
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+
+        let chunks = chunker.chunk(&text);
+        assert!(chunks.len() > 1);
+        for chunk in chunks {
+            let tokens = tokenizer.encode(chunk, true).unwrap().len();
+            assert!(tokens <= 16, "{} tokens in {:?}", tokens, chunk);
+        }
+    }
+}
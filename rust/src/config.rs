@@ -1,33 +1,207 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::{embeddings::embed::Embedder, text_loader::SplittingStrategy};
+use crate::embeddings::embed::{EmbedData, Embedder};
+use crate::embeddings::validation::InvalidEmbeddingPolicy;
+use crate::embeddings::BatchBackoff;
+use crate::file_loader::{Shard, WalkOptions};
+use crate::file_processor::image_url::{DEFAULT_IMAGE_TIMEOUT, DEFAULT_MAX_IMAGE_BYTES};
+use crate::file_processor::{
+    docx_processor::DocxTextOptions, json_processor::JsonTextOptions, pdf_processor::PdfTextOptions,
+};
+use crate::warning::Warnings;
+use crate::{
+    error::EmbedError,
+    text_loader::{ChunkUnit, SplittingStrategy},
+};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 256;
+pub const DEFAULT_OVERLAP_RATIO: f32 = 0.0;
+pub const DEFAULT_BATCH_SIZE: usize = 32;
+pub const DEFAULT_BUFFER_SIZE: usize = 100;
+/// Rows of a CSV or Excel file embedded together unless set with `with_table_rows_per_chunk`.
+pub const DEFAULT_TABLE_ROWS_PER_CHUNK: usize = 10;
+/// Size in bytes above which text files are streamed unless set with `with_streaming_threshold`.
+pub const DEFAULT_STREAMING_THRESHOLD: u64 = 64 * 1024 * 1024;
+/// Appended to metadata values cut by [`MetadataConfig::max_value_length`].
+pub const METADATA_TRUNCATION_MARKER: &str = "...[truncated]";
+/// The metadata key [`MetadataConfig::namespace`] is stored under.
+pub const NAMESPACE_METADATA_KEY: &str = "namespace";
+
+/// Selects the metadata fields stored with each [`EmbedData`] and caps their length, for vector
+/// databases that reject large documents. A namespace tags the embeddings of one tenant, so a
+/// [`TenantAdapter`](crate::adapters::TenantAdapter) can keep them in a collection of their own.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataConfig {
+    /// Keys to keep. All keys are kept when `None`.
+    pub fields: Option<Vec<String>>,
+    /// Longest value in characters. Longer values are cut and end with
+    /// [`METADATA_TRUNCATION_MARKER`]. Values are not capped when `None`.
+    pub max_value_length: Option<usize>,
+    /// Added to the metadata of every embedding as [`NAMESPACE_METADATA_KEY`], after the fields
+    /// are selected and cut. Not added when `None`.
+    pub namespace: Option<String>,
+}
+
+impl MetadataConfig {
+    pub fn with_fields<S: Into<String>>(mut self, fields: impl IntoIterator<Item = S>) -> Self {
+        self.fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_max_value_length(mut self, max_value_length: usize) -> Self {
+        self.max_value_length = Some(max_value_length);
+        self
+    }
+
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// Drops the fields that are not selected, truncates the values that are too long and adds
+    /// the namespace.
+    pub fn apply(&self, metadata: &mut HashMap<String, String>) {
+        if let Some(fields) = &self.fields {
+            metadata.retain(|key, _| fields.contains(key));
+        }
+        if let Some(max_value_length) = self.max_value_length {
+            for value in metadata.values_mut() {
+                truncate_value(value, max_value_length);
+            }
+        }
+        if let Some(namespace) = &self.namespace {
+            metadata.insert(NAMESPACE_METADATA_KEY.to_string(), namespace.clone());
+        }
+    }
+
+    /// Applies [`MetadataConfig::apply`] to the metadata of every embedding. With a namespace,
+    /// embeddings without metadata get metadata holding only the namespace.
+    pub fn apply_to(&self, embeddings: &mut [EmbedData]) {
+        for embedding in embeddings {
+            match &mut embedding.metadata {
+                Some(metadata) => self.apply(metadata),
+                None if self.namespace.is_some() => {
+                    let mut metadata = HashMap::new();
+                    self.apply(&mut metadata);
+                    embedding.metadata = Some(metadata);
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+fn truncate_value(value: &mut String, max_length: usize) {
+    if value.chars().count() <= max_length {
+        return;
+    }
+    let keep = max_length.saturating_sub(METADATA_TRUNCATION_MARKER.chars().count());
+    let end = value
+        .char_indices()
+        .nth(keep)
+        .map_or(value.len(), |(i, _)| i);
+    value.truncate(end);
+    value.push_str(METADATA_TRUNCATION_MARKER);
+}
+
+/// How [`TextEmbedConfig::document_pooling`] combines the chunk vectors of a file into one
+/// document vector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentPooling {
+    /// Average of the chunk vectors.
+    Mean,
+    /// Average weighted by the length of each chunk, so short chunks count less.
+    Weighted,
+}
 
 #[derive(Clone)]
 pub struct TextEmbedConfig {
     pub chunk_size: Option<usize>,
     pub overlap_ratio: Option<f32>,
+    /// Tokens shared by consecutive chunks, counted with the tokenizer that sizes the chunks, so
+    /// the overlap is the same for every script. Takes precedence over `overlap_ratio`.
+    pub overlap_tokens: Option<usize>,
     pub batch_size: Option<usize>,
     pub buffer_size: Option<usize>, // Required for adapter. Default is 100.
     pub splitting_strategy: Option<SplittingStrategy>,
+    /// What `chunk_size` and the overlap are counted in. Default is tokens of the cl100k
+    /// tokenizer, whatever the model.
+    pub chunk_unit: Option<ChunkUnit>,
     pub semantic_encoder: Option<Arc<Embedder>>,
     pub use_ocr: Option<bool>,
+    pub metadata: Option<MetadataConfig>,
+    /// Also emit one document embedding per file, pooled from its chunk embeddings. Default is
+    /// chunk embeddings only.
+    pub document_pooling: Option<DocumentPooling>,
+    /// Embed each buffer of chunks shortest first, so that every batch holds chunks of similar
+    /// length and less of it is padding. The embeddings keep the order of the chunks. Default is
+    /// false.
+    pub length_sorted_batching: Option<bool>,
+    /// Shrinks the batches that run out of memory. Clones of the config keep the reduced batch
+    /// size, so share one per model.
+    pub backoff: BatchBackoff,
+    /// Collects the [`EmbedWarning`](crate::warning::EmbedWarning)s of the runs using this
+    /// config, e.g. files without text. Warnings are only logged when `None`.
+    pub warnings: Option<Warnings>,
+    /// What happens to chunks whose embedding has NaN or infinite values or is all zeros. They
+    /// are reported as warnings either way. Default is to keep them.
+    pub invalid_embeddings: InvalidEmbeddingPolicy,
+    /// The pages of PDFs to embed and whether to split them at their outline. Chunks of a split
+    /// PDF get the title and first page of their section as `section_title` and `section_page`.
+    pub pdf: PdfTextOptions,
+    /// The parts of DOCX files besides their body to embed, such as headers and footnotes.
+    pub docx: DocxTextOptions,
+    /// The fields of JSON and JSONL records to embed and to attach as metadata.
+    pub json: JsonTextOptions,
+    /// Rows of a CSV or Excel file written into one chunk, 1 for a chunk per row. Default is
+    /// [`DEFAULT_TABLE_ROWS_PER_CHUNK`].
+    pub table_rows_per_chunk: Option<usize>,
+    /// Size in bytes above which text files are read and chunked block by block instead of
+    /// whole. Default is [`DEFAULT_STREAMING_THRESHOLD`].
+    pub streaming_threshold: Option<u64>,
+    /// How directories of documents and recordings are walked: whether symbolic links are
+    /// followed and how deep.
+    pub walk: WalkOptions,
 }
 
 impl Default for TextEmbedConfig {
     fn default() -> Self {
         Self {
-            chunk_size: Some(256),
-            overlap_ratio: Some(0.0),
-            batch_size: Some(32),
-            buffer_size: Some(100),
+            chunk_size: Some(DEFAULT_CHUNK_SIZE),
+            overlap_ratio: Some(DEFAULT_OVERLAP_RATIO),
+            overlap_tokens: None,
+            batch_size: Some(DEFAULT_BATCH_SIZE),
+            buffer_size: Some(DEFAULT_BUFFER_SIZE),
             splitting_strategy: None,
+            chunk_unit: None,
             semantic_encoder: None,
             use_ocr: None,
+            metadata: None,
+            document_pooling: None,
+            length_sorted_batching: None,
+            backoff: BatchBackoff::default(),
+            warnings: None,
+            invalid_embeddings: InvalidEmbeddingPolicy::default(),
+            pdf: PdfTextOptions::default(),
+            docx: DocxTextOptions::default(),
+            json: JsonTextOptions::default(),
+            table_rows_per_chunk: None,
+            streaming_threshold: None,
+            walk: WalkOptions::default(),
         }
     }
 }
 
 impl TextEmbedConfig {
+    /// Returns a builder that validates the configuration in [`TextEmbedConfigBuilder::build`].
+    pub fn builder() -> TextEmbedConfigBuilder {
+        TextEmbedConfigBuilder::default()
+    }
+
     pub fn new(
         chunk_size: Option<usize>,
         batch_size: Option<usize>,
@@ -37,10 +211,11 @@ impl TextEmbedConfig {
         semantic_encoder: Option<Arc<Embedder>>,
         use_ocr: Option<bool>,
     ) -> Self {
+        let batch_size = batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
         let config = Self::default()
-            .with_chunk_size(chunk_size.unwrap_or(256), overlap_ratio)
-            .with_batch_size(batch_size.unwrap_or(32))
-            .with_buffer_size(buffer_size.unwrap_or(100))
+            .with_chunk_size(chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE), overlap_ratio)
+            .with_batch_size(batch_size)
+            .with_buffer_size(buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE.max(batch_size)))
             .with_ocr(use_ocr.unwrap_or(false));
 
         match splitting_strategy {
@@ -63,6 +238,23 @@ impl TextEmbedConfig {
         self
     }
 
+    /// Overlaps consecutive chunks by `overlap_tokens` tokens instead of a ratio of the chunk size.
+    pub fn with_overlap_tokens(mut self, overlap_tokens: usize) -> Self {
+        self.overlap_tokens = Some(overlap_tokens);
+        self
+    }
+
+    /// The overlap as a ratio of the chunk size, derived from `overlap_tokens` when it is set. The
+    /// chunkers turn the ratio back into the same number of tokens.
+    pub fn effective_overlap_ratio(&self) -> f32 {
+        match self.overlap_tokens {
+            Some(overlap_tokens) => {
+                overlap_tokens as f32 / self.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1) as f32
+            }
+            None => self.overlap_ratio.unwrap_or(DEFAULT_OVERLAP_RATIO),
+        }
+    }
+
     pub fn with_batch_size(mut self, size: usize) -> Self {
         self.batch_size = Some(size);
         self
@@ -78,6 +270,13 @@ impl TextEmbedConfig {
         self
     }
 
+    /// Counts the chunk size and overlap in `unit`, e.g. [`ChunkUnit::Tokens`] so no chunk is
+    /// longer than the model reads.
+    pub fn with_chunk_unit(mut self, unit: ChunkUnit) -> Self {
+        self.chunk_unit = Some(unit);
+        self
+    }
+
     pub fn with_semantic_encoder(mut self, encoder: Arc<Embedder>) -> Self {
         self.semantic_encoder = Some(encoder);
         self
@@ -87,23 +286,853 @@ impl TextEmbedConfig {
         self.use_ocr = Some(use_ocr);
         self
     }
+
+    pub fn with_metadata_config(mut self, metadata: MetadataConfig) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn with_document_pooling(mut self, pooling: DocumentPooling) -> Self {
+        self.document_pooling = Some(pooling);
+        self
+    }
+
+    pub fn with_length_sorted_batching(mut self, length_sorted_batching: bool) -> Self {
+        self.length_sorted_batching = Some(length_sorted_batching);
+        self
+    }
+
+    /// Stops shrinking the batches that run out of memory at `min_batch_size` and fails instead.
+    /// Default is [`MIN_BACKOFF_BATCH_SIZE`](crate::embeddings::MIN_BACKOFF_BATCH_SIZE).
+    pub fn with_min_batch_size(mut self, min_batch_size: usize) -> Self {
+        self.backoff = BatchBackoff::new(min_batch_size);
+        self
+    }
+
+    /// Records the warnings of the runs using this config in `warnings`. Keep a clone to read
+    /// them with [`Warnings::take`].
+    pub fn with_warnings(mut self, warnings: Warnings) -> Self {
+        self.warnings = Some(warnings);
+        self
+    }
+
+    pub fn with_invalid_embeddings(mut self, policy: InvalidEmbeddingPolicy) -> Self {
+        self.invalid_embeddings = policy;
+        self
+    }
+
+    /// Embeds only the pages of PDFs from `first` to `last`, both included and counting from 1.
+    /// Without `last`, the pages up to the end are embedded.
+    pub fn with_pdf_page_range(mut self, first: usize, last: Option<usize>) -> Self {
+        self.pdf.first_page = Some(first);
+        self.pdf.last_page = last;
+        self
+    }
+
+    /// Chunks PDFs section by section, one per top-level outline entry, see
+    /// [`PdfTextOptions::split_by_outline`].
+    pub fn with_split_by_outline(mut self, split_by_outline: bool) -> Self {
+        self.pdf.split_by_outline = split_by_outline;
+        self
+    }
+
+    /// Whether to embed page headers and footers. With `false`, the running headers and footers
+    /// of PDFs are dropped, see [`PdfTextOptions::strip_headers_footers`]. With `true`, the
+    /// headers and footers of DOCX files are embedded along with their body. Unset, PDFs keep
+    /// them and DOCX files leave them out.
+    pub fn with_headers_footers(mut self, include: bool) -> Self {
+        self.pdf.strip_headers_footers = !include;
+        self.docx.headers_footers = include;
+        self
+    }
+
+    /// Embeds the footnotes and endnotes of DOCX files after their body, see
+    /// [`DocxTextOptions::footnotes`]. The footnotes of PDFs are part of their page text either
+    /// way.
+    pub fn with_footnotes(mut self, include: bool) -> Self {
+        self.docx.footnotes = include;
+        self
+    }
+
+    /// Embeds only the given fields of the records of JSON and JSONL files, e.g. `title` and
+    /// `body`, instead of every field. Nested fields are named by their path, e.g. `author.name`.
+    pub fn with_json_text_fields(mut self, fields: Vec<String>) -> Self {
+        self.json.text_fields = fields;
+        self
+    }
+
+    /// Adds the given fields of the records of JSON and JSONL files, e.g. `id`, to the metadata of
+    /// their chunks under the field name. Chunks also get the number of their record as `record`.
+    pub fn with_json_metadata_fields(mut self, fields: Vec<String>) -> Self {
+        self.json.metadata_fields = fields;
+        self
+    }
+
+    /// Embeds the rows of CSV and Excel files `rows` at a time. Their chunks get the column names
+    /// as `columns` metadata and the row numbers as `rows`, e.g. `11-20`.
+    pub fn with_table_rows_per_chunk(mut self, rows: usize) -> Self {
+        self.table_rows_per_chunk = Some(rows);
+        self
+    }
+
+    /// Reads text files larger than `bytes` block by block, so the whole file is never in memory.
+    /// With an adapter, `embed_file` then hands over the embeddings of such files every
+    /// `buffer_size` chunks while it reads on. 0 streams every text file.
+    pub fn with_streaming_threshold(mut self, bytes: u64) -> Self {
+        self.streaming_threshold = Some(bytes);
+        self
+    }
+
+    /// Follows symbolic links when walking directories, see [`WalkOptions::follow_symlinks`].
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.walk.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Walks directories down to `max_depth` levels, 1 for the files directly in them, see
+    /// [`WalkOptions::max_depth`]. Documents are then also looked for in subdirectories.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.walk.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Embeds only shard `index` of `count` of the files of directories, numbered from 0, see
+    /// [`Shard`]. Workers running the same directory with the same `count` and each their own
+    /// `index` embed every file once between them.
+    pub fn with_shard(mut self, index: usize, count: usize) -> Self {
+        self.walk.shard = Some(Shard { index, count });
+        self
+    }
+
+    /// Applies the [`MetadataConfig`], if any, to the metadata of `embeddings`.
+    pub fn apply_metadata_config(&self, embeddings: &mut [EmbedData]) {
+        if let Some(metadata) = &self.metadata {
+            metadata.apply_to(embeddings);
+        }
+    }
+
+    /// Checks that the configured values are consistent with each other, filling in defaults for
+    /// the values that are not set.
+    pub fn validate(&self) -> Result<(), EmbedError> {
+        let chunk_size = self.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+        let overlap_ratio = self.overlap_ratio.unwrap_or(DEFAULT_OVERLAP_RATIO);
+        let batch_size = self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+        let buffer_size = self
+            .buffer_size
+            .unwrap_or(DEFAULT_BUFFER_SIZE.max(batch_size));
+
+        if chunk_size == 0 {
+            return Err(EmbedError::InvalidConfig(
+                "chunk_size must be greater than 0".to_string(),
+            ));
+        }
+        if !(0.0..1.0).contains(&overlap_ratio) {
+            return Err(EmbedError::InvalidConfig(format!(
+                "overlap_ratio must be in [0, 1) so the overlap stays smaller than chunk_size, got {}",
+                overlap_ratio
+            )));
+        }
+        if let Some(overlap_tokens) = self.overlap_tokens {
+            if overlap_tokens >= chunk_size {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "overlap_tokens ({}) must be smaller than chunk_size ({})",
+                    overlap_tokens, chunk_size
+                )));
+            }
+        }
+        if batch_size == 0 {
+            return Err(EmbedError::InvalidConfig(
+                "batch_size must be greater than 0".to_string(),
+            ));
+        }
+        if buffer_size < batch_size {
+            return Err(EmbedError::InvalidConfig(format!(
+                "buffer_size ({}) must be at least batch_size ({})",
+                buffer_size, batch_size
+            )));
+        }
+        if self.table_rows_per_chunk == Some(0) {
+            return Err(EmbedError::InvalidConfig(
+                "table_rows_per_chunk must be greater than 0".to_string(),
+            ));
+        }
+        if self.walk.max_depth == Some(0) {
+            return Err(EmbedError::InvalidConfig(
+                "max_depth must be at least 1 to find the files of the directory".to_string(),
+            ));
+        }
+        if let Some(shard) = self.walk.shard {
+            if shard.index >= shard.count {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "shard_index ({}) must be smaller than num_shards ({})",
+                    shard.index, shard.count
+                )));
+            }
+        }
+        if let (Some(first), Some(last)) = (self.pdf.first_page, self.pdf.last_page) {
+            if first > last {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "The first PDF page ({}) must not come after the last one ({})",
+                    first, last
+                )));
+            }
+        }
+        if matches!(self.splitting_strategy, Some(SplittingStrategy::Semantic))
+            && self.semantic_encoder.is_none()
+        {
+            return Err(EmbedError::InvalidConfig(
+                "semantic_encoder is required when using the Semantic splitting strategy"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Builds a [`TextEmbedConfig`], validating it on [`build`](TextEmbedConfigBuilder::build).
+/// Unset values fall back to the `DEFAULT_*` constants in this module.
+#[derive(Default)]
+pub struct TextEmbedConfigBuilder {
+    chunk_size: Option<usize>,
+    overlap_ratio: Option<f32>,
+    overlap_tokens: Option<usize>,
+    batch_size: Option<usize>,
+    buffer_size: Option<usize>,
+    splitting_strategy: Option<SplittingStrategy>,
+    chunk_unit: Option<ChunkUnit>,
+    semantic_encoder: Option<Arc<Embedder>>,
+    use_ocr: Option<bool>,
+    metadata: Option<MetadataConfig>,
+    document_pooling: Option<DocumentPooling>,
+    length_sorted_batching: Option<bool>,
+    backoff: BatchBackoff,
+    warnings: Option<Warnings>,
+    invalid_embeddings: InvalidEmbeddingPolicy,
+    pdf: PdfTextOptions,
+    docx: DocxTextOptions,
+    json: JsonTextOptions,
+    table_rows_per_chunk: Option<usize>,
+    streaming_threshold: Option<u64>,
+    walk: WalkOptions,
+}
+
+impl TextEmbedConfigBuilder {
+    pub fn with_chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = Some(size);
+        self
+    }
+
+    pub fn with_overlap_ratio(mut self, overlap_ratio: f32) -> Self {
+        self.overlap_ratio = Some(overlap_ratio);
+        self
+    }
+
+    pub fn with_overlap_tokens(mut self, overlap_tokens: usize) -> Self {
+        self.overlap_tokens = Some(overlap_tokens);
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = Some(size);
+        self
+    }
+
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = Some(size);
+        self
+    }
+
+    pub fn with_splitting_strategy(mut self, strategy: SplittingStrategy) -> Self {
+        self.splitting_strategy = Some(strategy);
+        self
+    }
+
+    /// Counts the chunk size and overlap in `unit`, e.g. [`ChunkUnit::Tokens`] so no chunk is
+    /// longer than the model reads.
+    pub fn with_chunk_unit(mut self, unit: ChunkUnit) -> Self {
+        self.chunk_unit = Some(unit);
+        self
+    }
+
+    pub fn with_semantic_encoder(mut self, encoder: Arc<Embedder>) -> Self {
+        self.semantic_encoder = Some(encoder);
+        self
+    }
+
+    pub fn with_ocr(mut self, use_ocr: bool) -> Self {
+        self.use_ocr = Some(use_ocr);
+        self
+    }
+
+    pub fn with_metadata_config(mut self, metadata: MetadataConfig) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn with_document_pooling(mut self, pooling: DocumentPooling) -> Self {
+        self.document_pooling = Some(pooling);
+        self
+    }
+
+    pub fn with_length_sorted_batching(mut self, length_sorted_batching: bool) -> Self {
+        self.length_sorted_batching = Some(length_sorted_batching);
+        self
+    }
+
+    /// Stops shrinking the batches that run out of memory at `min_batch_size` and fails instead.
+    /// Default is [`MIN_BACKOFF_BATCH_SIZE`](crate::embeddings::MIN_BACKOFF_BATCH_SIZE).
+    pub fn with_min_batch_size(mut self, min_batch_size: usize) -> Self {
+        self.backoff = BatchBackoff::new(min_batch_size);
+        self
+    }
+
+    /// Records the warnings of the runs using this config in `warnings`. Keep a clone to read
+    /// them with [`Warnings::take`].
+    pub fn with_warnings(mut self, warnings: Warnings) -> Self {
+        self.warnings = Some(warnings);
+        self
+    }
+
+    pub fn with_invalid_embeddings(mut self, policy: InvalidEmbeddingPolicy) -> Self {
+        self.invalid_embeddings = policy;
+        self
+    }
+
+    /// Embeds only the pages of PDFs from `first` to `last`, both included and counting from 1.
+    /// Without `last`, the pages up to the end are embedded.
+    pub fn with_pdf_page_range(mut self, first: usize, last: Option<usize>) -> Self {
+        self.pdf.first_page = Some(first);
+        self.pdf.last_page = last;
+        self
+    }
+
+    /// Chunks PDFs section by section, one per top-level outline entry, see
+    /// [`PdfTextOptions::split_by_outline`].
+    pub fn with_split_by_outline(mut self, split_by_outline: bool) -> Self {
+        self.pdf.split_by_outline = split_by_outline;
+        self
+    }
+
+    /// Whether to embed page headers and footers. With `false`, the running headers and footers
+    /// of PDFs are dropped, see [`PdfTextOptions::strip_headers_footers`]. With `true`, the
+    /// headers and footers of DOCX files are embedded along with their body. Unset, PDFs keep
+    /// them and DOCX files leave them out.
+    pub fn with_headers_footers(mut self, include: bool) -> Self {
+        self.pdf.strip_headers_footers = !include;
+        self.docx.headers_footers = include;
+        self
+    }
+
+    /// Embeds the footnotes and endnotes of DOCX files after their body, see
+    /// [`DocxTextOptions::footnotes`]. The footnotes of PDFs are part of their page text either
+    /// way.
+    pub fn with_footnotes(mut self, include: bool) -> Self {
+        self.docx.footnotes = include;
+        self
+    }
+
+    /// Embeds only the given fields of the records of JSON and JSONL files, e.g. `title` and
+    /// `body`, instead of every field. Nested fields are named by their path, e.g. `author.name`.
+    pub fn with_json_text_fields(mut self, fields: Vec<String>) -> Self {
+        self.json.text_fields = fields;
+        self
+    }
+
+    /// Adds the given fields of the records of JSON and JSONL files, e.g. `id`, to the metadata of
+    /// their chunks under the field name. Chunks also get the number of their record as `record`.
+    pub fn with_json_metadata_fields(mut self, fields: Vec<String>) -> Self {
+        self.json.metadata_fields = fields;
+        self
+    }
+
+    /// Embeds the rows of CSV and Excel files `rows` at a time. Their chunks get the column names
+    /// as `columns` metadata and the row numbers as `rows`, e.g. `11-20`.
+    pub fn with_table_rows_per_chunk(mut self, rows: usize) -> Self {
+        self.table_rows_per_chunk = Some(rows);
+        self
+    }
+
+    /// Reads text files larger than `bytes` block by block, so the whole file is never in memory.
+    /// With an adapter, `embed_file` then hands over the embeddings of such files every
+    /// `buffer_size` chunks while it reads on. 0 streams every text file.
+    pub fn with_streaming_threshold(mut self, bytes: u64) -> Self {
+        self.streaming_threshold = Some(bytes);
+        self
+    }
+
+    /// Follows symbolic links when walking directories, see [`WalkOptions::follow_symlinks`].
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.walk.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Walks directories down to `max_depth` levels, 1 for the files directly in them, see
+    /// [`WalkOptions::max_depth`]. Documents are then also looked for in subdirectories.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.walk.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Embeds only shard `index` of `count` of the files of directories, numbered from 0, see
+    /// [`Shard`]. Workers running the same directory with the same `count` and each their own
+    /// `index` embed every file once between them.
+    pub fn with_shard(mut self, index: usize, count: usize) -> Self {
+        self.walk.shard = Some(Shard { index, count });
+        self
+    }
+
+    pub fn build(self) -> Result<TextEmbedConfig, EmbedError> {
+        let batch_size = self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+        let config = TextEmbedConfig {
+            chunk_size: Some(self.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE)),
+            overlap_ratio: Some(self.overlap_ratio.unwrap_or(DEFAULT_OVERLAP_RATIO)),
+            overlap_tokens: self.overlap_tokens,
+            batch_size: Some(batch_size),
+            // Only a buffer size that was set can be too small for the batch size.
+            buffer_size: Some(
+                self.buffer_size
+                    .unwrap_or(DEFAULT_BUFFER_SIZE.max(batch_size)),
+            ),
+            splitting_strategy: self.splitting_strategy,
+            chunk_unit: self.chunk_unit,
+            semantic_encoder: self.semantic_encoder,
+            use_ocr: Some(self.use_ocr.unwrap_or(false)),
+            metadata: self.metadata,
+            document_pooling: self.document_pooling,
+            length_sorted_batching: Some(self.length_sorted_batching.unwrap_or(false)),
+            backoff: self.backoff,
+            warnings: self.warnings,
+            invalid_embeddings: self.invalid_embeddings,
+            pdf: self.pdf,
+            docx: self.docx,
+            json: self.json,
+            table_rows_per_chunk: self.table_rows_per_chunk,
+            streaming_threshold: self.streaming_threshold,
+            walk: self.walk,
+        };
+        config.validate()?;
+        Ok(config)
+    }
 }
 
 #[derive(Clone)]
 pub struct ImageEmbedConfig {
     pub buffer_size: Option<usize>, // Required for adapter. Default is 100.
+    /// Largest image `embed_image_url` downloads. Default is 20 MiB.
+    pub max_download_bytes: Option<u64>,
+    /// Timeout for downloading an image in `embed_image_url`. Default is 30 seconds.
+    pub download_timeout: Option<Duration>,
+    /// Resolution PDF pages are rendered at for vision models such as ColPali. Lower is faster;
+    /// the model resizes the pages to its input size anyway. Default is 150 DPI.
+    pub pdf_dpi: Option<u32>,
+    /// Render PDF pages in grayscale. Default is false.
+    pub pdf_grayscale: Option<bool>,
+    /// First page of a PDF to embed, counting from 1. Default is the first page.
+    pub pdf_first_page: Option<usize>,
+    /// Last page of a PDF to embed. Default is the last page.
+    pub pdf_last_page: Option<usize>,
+    /// How `embed_image_directory` walks the directory: whether symbolic links are followed and
+    /// how deep.
+    pub walk: WalkOptions,
 }
 
 impl Default for ImageEmbedConfig {
     fn default() -> Self {
         Self {
-            buffer_size: Some(100),
+            buffer_size: Some(DEFAULT_BUFFER_SIZE),
+            max_download_bytes: Some(DEFAULT_MAX_IMAGE_BYTES),
+            download_timeout: Some(DEFAULT_IMAGE_TIMEOUT),
+            pdf_dpi: None,
+            pdf_grayscale: None,
+            pdf_first_page: None,
+            pdf_last_page: None,
+            walk: WalkOptions::default(),
         }
     }
 }
 
 impl ImageEmbedConfig {
     pub fn new(buffer_size: Option<usize>) -> Self {
-        Self { buffer_size }
+        Self {
+            buffer_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_download_bytes(mut self, max_download_bytes: u64) -> Self {
+        self.max_download_bytes = Some(max_download_bytes);
+        self
+    }
+
+    pub fn with_download_timeout(mut self, download_timeout: Duration) -> Self {
+        self.download_timeout = Some(download_timeout);
+        self
+    }
+
+    pub fn with_pdf_dpi(mut self, dpi: u32) -> Self {
+        self.pdf_dpi = Some(dpi);
+        self
+    }
+
+    pub fn with_pdf_grayscale(mut self, grayscale: bool) -> Self {
+        self.pdf_grayscale = Some(grayscale);
+        self
+    }
+
+    /// Embeds only the pages from `first` to `last`, both included and counting from 1, e.g. to
+    /// skip a cover page or an appendix. Without `last`, the pages up to the end are embedded.
+    pub fn with_pdf_page_range(mut self, first: usize, last: Option<usize>) -> Self {
+        self.pdf_first_page = Some(first);
+        self.pdf_last_page = last;
+        self
+    }
+
+    /// Follows symbolic links when walking directories, see [`WalkOptions::follow_symlinks`].
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.walk.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Walks directories down to `max_depth` levels, 1 for the images directly in them, see
+    /// [`WalkOptions::max_depth`].
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.walk.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Embeds only shard `index` of `count` of the files of directories, numbered from 0, see
+    /// [`Shard`]. Workers running the same directory with the same `count` and each their own
+    /// `index` embed every file once between them.
+    pub fn with_shard(mut self, index: usize, count: usize) -> Self {
+        self.walk.shard = Some(Shard { index, count });
+        self
+    }
+
+    /// The pages of a PDF of `page_count` pages to embed, clamped to the document.
+    pub fn pdf_page_range(&self, page_count: usize) -> RangeInclusive<usize> {
+        let first = self.pdf_first_page.unwrap_or(1).max(1);
+        let last = self.pdf_last_page.unwrap_or(page_count).min(page_count);
+        first..=last
+    }
+}
+
+/// How an [`AudioDecoderModel`](crate::file_processor::audio::audio_processor::AudioDecoderModel)
+/// turns a recording into the segments that are embedded.
+#[derive(Clone, Debug, Default)]
+pub struct AudioDecoderConfig {
+    /// Merge consecutive transcribed segments into windows of about this many seconds, so short
+    /// segments do not give noisy embeddings. Segments are embedded as decoded when `None`.
+    pub window_duration: Option<f64>,
+    /// Seconds of audio shared by consecutive windows. Windows overlap by whole segments, so the
+    /// actual overlap is at least this long. Default is no overlap.
+    pub window_overlap: Option<f64>,
+    /// Skip silence with voice activity detection before transcribing. Long recordings with
+    /// pauses decode much faster. Default is off.
+    pub vad: bool,
+    /// How much audio the voice activity detection skips, from 0 (least) to 3 (most). Default is
+    /// [`DEFAULT_VAD_AGGRESSIVENESS`].
+    pub vad_aggressiveness: Option<u8>,
+    /// Files [`crate::embed_audio_directory`] decodes at the same time, each with its own copy of
+    /// the model. Default is half the CPU cores on CPU and 1 on GPU.
+    pub num_workers: Option<usize>,
+}
+
+/// Voice activity detection aggressiveness used unless configured otherwise.
+pub const DEFAULT_VAD_AGGRESSIVENESS: u8 = 1;
+
+impl AudioDecoderConfig {
+    pub fn with_window(mut self, duration: f64, overlap: f64) -> Self {
+        self.window_duration = Some(duration);
+        self.window_overlap = Some(overlap);
+        self
+    }
+
+    pub fn with_num_workers(mut self, num_workers: usize) -> Self {
+        self.num_workers = Some(num_workers);
+        self
+    }
+
+    pub fn with_vad(mut self, aggressiveness: u8) -> Self {
+        self.vad = true;
+        self.vad_aggressiveness = Some(aggressiveness);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), EmbedError> {
+        let overlap = self.window_overlap.unwrap_or(0.0);
+        if let Some(duration) = self.window_duration {
+            if duration.is_nan() || duration <= 0.0 {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "window_duration must be greater than 0, got {}",
+                    duration
+                )));
+            }
+            if !(0.0..duration).contains(&overlap) {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "window_overlap must be in [0, window_duration), got {}",
+                    overlap
+                )));
+            }
+        }
+        if self.num_workers == Some(0) {
+            return Err(EmbedError::InvalidConfig(
+                "num_workers must be greater than 0".to_string(),
+            ));
+        }
+        if let Some(aggressiveness) = self.vad_aggressiveness.filter(|&a| a > 3) {
+            return Err(EmbedError::InvalidConfig(format!(
+                "vad_aggressiveness must be between 0 and 3, got {}",
+                aggressiveness
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Pages [`crate::embed_webpages`] fetches at the same time unless configured otherwise.
+pub const DEFAULT_WEB_CONCURRENCY: usize = 8;
+/// Time allowed for fetching one page unless a different timeout is configured.
+pub const DEFAULT_WEB_TIMEOUT: Duration = Duration::from_secs(30);
+/// The User-Agent sent with web requests unless a different one is configured.
+pub const DEFAULT_USER_AGENT: &str = concat!("embed_anything/", env!("CARGO_PKG_VERSION"));
+
+/// How [`crate::embed_webpages`] fetches pages.
+#[derive(Clone, Debug)]
+pub struct WebFetchConfig {
+    /// Number of pages fetched and embedded at the same time. Default is 8.
+    pub concurrency: Option<usize>,
+    /// Timeout for fetching one page. Default is 30 seconds.
+    pub timeout: Option<Duration>,
+    /// The User-Agent header, also used to pick the `robots.txt` rules. Default is
+    /// [`DEFAULT_USER_AGENT`].
+    pub user_agent: Option<String>,
+    /// Skip pages that `robots.txt` disallows and honour its `Crawl-delay`. Default is true.
+    pub respect_robots_txt: Option<bool>,
+    /// Minimum time between two requests to the same host. Default is no delay.
+    pub domain_delay: Option<Duration>,
+    /// JSON file remembering the ETag, Last-Modified and content hash of embedded pages. When set,
+    /// pages that did not change since they were last embedded are skipped. Default is no cache.
+    pub cache_path: Option<PathBuf>,
+}
+
+impl Default for WebFetchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: Some(DEFAULT_WEB_CONCURRENCY),
+            timeout: Some(DEFAULT_WEB_TIMEOUT),
+            user_agent: None,
+            respect_robots_txt: Some(true),
+            domain_delay: None,
+            cache_path: None,
+        }
+    }
+}
+
+impl WebFetchConfig {
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn with_robots_txt(mut self, respect_robots_txt: bool) -> Self {
+        self.respect_robots_txt = Some(respect_robots_txt);
+        self
+    }
+
+    pub fn with_domain_delay(mut self, domain_delay: Duration) -> Self {
+        self.domain_delay = Some(domain_delay);
+        self
+    }
+
+    pub fn with_cache_path(mut self, cache_path: impl Into<PathBuf>) -> Self {
+        self.cache_path = Some(cache_path.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults() {
+        let config = TextEmbedConfig::builder().build().unwrap();
+        assert_eq!(config.chunk_size, Some(DEFAULT_CHUNK_SIZE));
+        assert_eq!(config.batch_size, Some(DEFAULT_BATCH_SIZE));
+        assert_eq!(config.buffer_size, Some(DEFAULT_BUFFER_SIZE));
+        assert_eq!(config.overlap_ratio, Some(DEFAULT_OVERLAP_RATIO));
+        assert_eq!(config.length_sorted_batching, Some(false));
+
+        // The default buffer grows with the batch size rather than rejecting it.
+        let config = TextEmbedConfig::builder()
+            .with_batch_size(128)
+            .build()
+            .unwrap();
+        assert_eq!(config.buffer_size, Some(128));
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_config() {
+        assert!(TextEmbedConfig::builder()
+            .with_chunk_size(0)
+            .build()
+            .is_err());
+        assert!(TextEmbedConfig::builder()
+            .with_overlap_ratio(1.0)
+            .build()
+            .is_err());
+        assert!(TextEmbedConfig::builder()
+            .with_chunk_size(64)
+            .with_overlap_tokens(64)
+            .build()
+            .is_err());
+        assert!(TextEmbedConfig::builder()
+            .with_batch_size(64)
+            .with_buffer_size(32)
+            .build()
+            .is_err());
+        assert!(TextEmbedConfig::builder()
+            .with_splitting_strategy(SplittingStrategy::Semantic)
+            .build()
+            .is_err());
+        assert!(TextEmbedConfig::builder()
+            .with_pdf_page_range(10, Some(5))
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_overlap_tokens_take_precedence() {
+        let config = TextEmbedConfig::builder()
+            .with_chunk_size(200)
+            .with_overlap_ratio(0.5)
+            .with_overlap_tokens(30)
+            .build()
+            .unwrap();
+        let overlap = config.effective_overlap_ratio();
+        assert_eq!((200.0 * overlap).round() as usize, 30);
+
+        let config = TextEmbedConfig::default().with_chunk_size(200, Some(0.25));
+        assert_eq!(config.effective_overlap_ratio(), 0.25);
+    }
+
+    #[test]
+    fn test_metadata_config() {
+        let mut metadata = HashMap::from([
+            ("file_name".to_string(), "report.pdf".to_string()),
+            ("full_text".to_string(), "a".repeat(100)),
+            ("created".to_string(), "2024-01-01".to_string()),
+        ]);
+        MetadataConfig::default()
+            .with_fields(["file_name", "full_text"])
+            .with_max_value_length(20)
+            .apply(&mut metadata);
+
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata["file_name"], "report.pdf");
+        assert_eq!(metadata["full_text"].chars().count(), 20);
+        assert!(metadata["full_text"].ends_with(METADATA_TRUNCATION_MARKER));
+    }
+
+    #[test]
+    fn test_metadata_namespace() {
+        let config = MetadataConfig::default()
+            .with_fields(["file_name"])
+            .with_namespace("tenant-a");
+        let mut embeddings = vec![
+            EmbedData::new(
+                vec![0.0].into(),
+                None,
+                Some(HashMap::from([
+                    ("file_name".to_string(), "a.txt".to_string()),
+                    ("created".to_string(), "2024-01-01".to_string()),
+                ])),
+            ),
+            EmbedData::new(vec![0.0].into(), None, None),
+        ];
+        config.apply_to(&mut embeddings);
+
+        let metadata = embeddings[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata[NAMESPACE_METADATA_KEY], "tenant-a");
+        assert_eq!(
+            embeddings[1].metadata,
+            Some(HashMap::from([(
+                NAMESPACE_METADATA_KEY.to_string(),
+                "tenant-a".to_string()
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_metadata_truncation_is_char_safe() {
+        let mut value = "日本語のテキスト".repeat(10);
+        truncate_value(&mut value, 16);
+        assert_eq!(value, format!("日本{METADATA_TRUNCATION_MARKER}"));
+    }
+
+    #[test]
+    fn test_headers_footers() {
+        let config = TextEmbedConfig::default();
+        assert!(!config.pdf.strip_headers_footers && !config.docx.headers_footers);
+
+        let config = config.with_headers_footers(false);
+        assert!(config.pdf.strip_headers_footers && !config.docx.headers_footers);
+
+        let config = config.with_headers_footers(true).with_footnotes(true);
+        assert!(!config.pdf.strip_headers_footers);
+        assert!(config.docx.headers_footers && config.docx.footnotes);
+    }
+
+    #[test]
+    fn test_walk_options() {
+        let config = TextEmbedConfig::builder()
+            .with_follow_symlinks(true)
+            .with_max_depth(3)
+            .build()
+            .unwrap();
+        assert!(config.walk.follow_symlinks);
+        assert_eq!(config.walk.max_depth, Some(3));
+        assert!(TextEmbedConfig::builder()
+            .with_max_depth(0)
+            .build()
+            .is_err());
+        assert!(TextEmbedConfig::builder().with_shard(2, 2).build().is_err());
+        let config = TextEmbedConfig::builder().with_shard(1, 2).build().unwrap();
+        assert_eq!(config.walk.shard, Some(Shard { index: 1, count: 2 }));
+    }
+
+    #[test]
+    fn test_chunk_unit() {
+        assert_eq!(TextEmbedConfig::default().chunk_unit, None);
+        let config = TextEmbedConfig::builder()
+            .with_chunk_unit(ChunkUnit::Tokens)
+            .build()
+            .unwrap();
+        assert_eq!(config.chunk_unit, Some(ChunkUnit::Tokens));
+    }
+
+    #[test]
+    fn test_pdf_page_range() {
+        let config = ImageEmbedConfig::default();
+        assert_eq!(config.pdf_page_range(10), 1..=10);
+
+        let config = config.with_pdf_page_range(2, Some(8));
+        assert_eq!(config.pdf_page_range(10), 2..=8);
+        assert_eq!(config.pdf_page_range(5), 2..=5);
+        assert!(config.pdf_page_range(1).is_empty());
+
+        let config = ImageEmbedConfig::default().with_pdf_page_range(0, None);
+        assert_eq!(config.pdf_page_range(3), 1..=3);
     }
 }
@@ -0,0 +1,252 @@
+//! Embeddings as Arrow arrays, for consumers that hand them to Polars, DataFusion or Parquet
+//! without converting every chunk to an [`EmbedData`] first.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::builder::{MapBuilder, StringBuilder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::Float32Type;
+use arrow_array::{
+    Array, ArrayRef, FixedSizeListArray, Float32Array, MapArray, RecordBatch, StringArray,
+};
+use arrow_buffer::ScalarBuffer;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+
+use super::embed::{EmbedData, EmbeddingResult};
+use crate::error::EmbedError;
+
+/// A batch of dense embeddings stored column-wise: the vectors as a `FixedSizeList<Float32>`, the
+/// text as `Utf8` and the metadata as `Map<Utf8, Utf8>`.
+#[derive(Debug, Clone)]
+pub struct EmbedDataBatch {
+    embeddings: FixedSizeListArray,
+    text: StringArray,
+    metadata: MapArray,
+}
+
+impl EmbedDataBatch {
+    /// Builds a batch from `values` holding one vector of `dimension` floats after the other.
+    /// `values` becomes the Arrow buffer without being copied.
+    pub fn from_vectors(
+        values: Vec<f32>,
+        dimension: usize,
+        text: Vec<Option<String>>,
+        metadata: Vec<Option<HashMap<String, String>>>,
+    ) -> anyhow::Result<Self> {
+        let rows = text.len();
+        if values.len() != rows * dimension
+            || metadata.len() != rows
+            || (dimension == 0 && rows > 0)
+        {
+            return Err(EmbedError::InvalidConfig(format!(
+                "{} values, {} texts and {} metadata do not make rows of dimension {}",
+                values.len(),
+                rows,
+                metadata.len(),
+                dimension
+            ))
+            .into());
+        }
+        let values = Float32Array::new(ScalarBuffer::from(values), None);
+        let embeddings = FixedSizeListArray::try_new(
+            Arc::new(Field::new("item", DataType::Float32, false)),
+            dimension as i32,
+            Arc::new(values),
+            None,
+        )?;
+
+        let mut builder = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+        for row in metadata {
+            let is_valid = row.is_some();
+            for (key, value) in row.into_iter().flatten() {
+                builder.keys().append_value(key);
+                builder.values().append_value(value);
+            }
+            builder.append(is_valid)?;
+        }
+
+        Ok(Self {
+            embeddings,
+            text: StringArray::from(text),
+            metadata: builder.finish(),
+        })
+    }
+
+    /// Builds a batch from the output of [`Embedder::embed`](super::embed::Embedder::embed),
+    /// which must be dense vectors of the same dimension.
+    pub fn from_results(
+        results: Vec<EmbeddingResult>,
+        text: Vec<Option<String>>,
+        metadata: Vec<Option<HashMap<String, String>>>,
+    ) -> anyhow::Result<Self> {
+        let mut dimension = None;
+        let mut values = Vec::new();
+        for result in results {
+            let vector = match result {
+                EmbeddingResult::DenseVector(vector) => vector,
+                EmbeddingResult::MultiVector(_) => {
+                    return Err(EmbedError::InvalidConfig(
+                        "EmbedDataBatch only holds dense vectors".to_string(),
+                    )
+                    .into())
+                }
+            };
+            if *dimension.get_or_insert(vector.len()) != vector.len() {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "Embeddings of dimension {} and {} cannot share a batch",
+                    dimension.unwrap(),
+                    vector.len()
+                ))
+                .into());
+            }
+            if values.is_empty() {
+                values.reserve(vector.len() * text.len());
+            }
+            values.extend_from_slice(&vector);
+        }
+        Self::from_vectors(values, dimension.unwrap_or(0), text, metadata)
+    }
+
+    pub fn len(&self) -> usize {
+        self.embeddings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.embeddings.is_empty()
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.embeddings.value_length() as usize
+    }
+
+    pub fn embeddings(&self) -> &FixedSizeListArray {
+        &self.embeddings
+    }
+
+    pub fn text(&self) -> &StringArray {
+        &self.text
+    }
+
+    pub fn metadata(&self) -> &MapArray {
+        &self.metadata
+    }
+
+    /// All vectors, one after the other.
+    pub fn values(&self) -> &[f32] {
+        self.embeddings
+            .values()
+            .as_primitive::<Float32Type>()
+            .values()
+    }
+
+    /// The vector of row `i`.
+    pub fn vector(&self, i: usize) -> &[f32] {
+        let dimension = self.dimension();
+        &self.values()[i * dimension..(i + 1) * dimension]
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("embedding", self.embeddings.data_type().clone(), false),
+            Field::new("text", DataType::Utf8, true),
+            Field::new("metadata", self.metadata.data_type().clone(), true),
+        ]))
+    }
+
+    /// The batch as a [`RecordBatch`] with `embedding`, `text` and `metadata` columns. The
+    /// arrays are shared, not copied.
+    pub fn to_record_batch(&self) -> anyhow::Result<RecordBatch> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.embeddings.clone()),
+            Arc::new(self.text.clone()),
+            Arc::new(self.metadata.clone()),
+        ];
+        Ok(RecordBatch::try_new(self.schema(), columns)?)
+    }
+
+    /// Converts row `i` back to an [`EmbedData`].
+    pub fn row(&self, i: usize) -> EmbedData {
+        let text = (!self.text.is_null(i)).then(|| self.text.value(i).to_string());
+        let metadata = (!self.metadata.is_null(i)).then(|| {
+            let entries = self.metadata.value(i);
+            let keys = entries.column(0).as_string::<i32>();
+            let values = entries.column(1).as_string::<i32>();
+            (0..entries.len())
+                .map(|j| (keys.value(j).to_string(), values.value(j).to_string()))
+                .collect()
+        });
+        EmbedData::new(
+            EmbeddingResult::DenseVector(self.vector(i).to_vec()),
+            text,
+            metadata,
+        )
+    }
+}
+
+impl TryFrom<Vec<EmbedData>> for EmbedDataBatch {
+    type Error = anyhow::Error;
+
+    fn try_from(data: Vec<EmbedData>) -> anyhow::Result<Self> {
+        let mut results = Vec::with_capacity(data.len());
+        let mut text = Vec::with_capacity(data.len());
+        let mut metadata = Vec::with_capacity(data.len());
+        for item in data {
+            results.push(item.embedding);
+            text.push(item.text);
+            metadata.push(item.metadata);
+        }
+        Self::from_results(results, text, metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_data_batch() {
+        let data = vec![
+            EmbedData::new(
+                EmbeddingResult::DenseVector(vec![1.0, 2.0, 3.0]),
+                Some("first".to_string()),
+                Some(HashMap::from([(
+                    "file_name".to_string(),
+                    "a.txt".to_string(),
+                )])),
+            ),
+            EmbedData::new(
+                EmbeddingResult::DenseVector(vec![4.0, 5.0, 6.0]),
+                None,
+                None,
+            ),
+        ];
+        let batch = EmbedDataBatch::try_from(data).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.dimension(), 3);
+        assert_eq!(batch.values(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(batch.vector(1), &[4.0, 5.0, 6.0]);
+
+        let row = batch.row(0);
+        assert_eq!(row.text.as_deref(), Some("first"));
+        assert_eq!(row.metadata.unwrap()["file_name"], "a.txt");
+        let row = batch.row(1);
+        assert!(row.text.is_none());
+        assert!(row.metadata.is_none());
+
+        let record_batch = batch.to_record_batch().unwrap();
+        assert_eq!(record_batch.num_rows(), 2);
+        assert_eq!(record_batch.num_columns(), 3);
+    }
+
+    #[test]
+    fn test_embed_data_batch_rejects_mixed_dimensions() {
+        let results = vec![
+            EmbeddingResult::DenseVector(vec![1.0, 2.0]),
+            EmbeddingResult::DenseVector(vec![1.0]),
+        ];
+        assert!(EmbedDataBatch::from_results(results, vec![None, None], vec![None, None]).is_err());
+        let results = vec![EmbeddingResult::MultiVector(vec![vec![1.0]])];
+        assert!(EmbedDataBatch::from_results(results, vec![None], vec![None]).is_err());
+    }
+}
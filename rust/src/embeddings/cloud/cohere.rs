@@ -1,8 +1,20 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use base64::Engine;
 use reqwest::Client;
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 
+use crate::embeddings::cloud::hooks::{self, RequestHook};
+use crate::embeddings::cloud::limits::RequestLimits;
+use crate::embeddings::cloud::secrets::SecretProvider;
+use crate::embeddings::cloud::truncation::{self, TruncationPolicy};
 use crate::embeddings::embed::EmbeddingResult;
+use crate::error::EmbedError;
+
+/// Endpoint of the v2 embed API, which accepts mixed text and image inputs.
+const V2_EMBED_URL: &str = "https://api.cohere.com/v2/embed";
 
 /// Represents the response from the Cohere embedding API.
 #[derive(Deserialize, Debug, Default)]
@@ -11,6 +23,43 @@ pub struct CohereEmbedResponse {
     pub embeddings: Vec<Vec<f32>>,
 }
 
+/// Represents the response from the v2 Cohere embedding API used for multimodal documents.
+#[derive(Deserialize, Debug, Default)]
+pub struct CohereEmbedV2Response {
+    pub embeddings: CohereEmbeddingsByType,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct CohereEmbeddingsByType {
+    pub float: Vec<Vec<f32>>,
+}
+
+/// One part of a multimodal document. Images are read from disk and sent inline as data URLs.
+#[derive(Debug, Clone)]
+pub enum ContentPart {
+    Text(String),
+    Image(PathBuf),
+}
+
+impl ContentPart {
+    fn to_json(&self) -> anyhow::Result<Value> {
+        match self {
+            ContentPart::Text(text) => Ok(json!({"type": "text", "text": text})),
+            ContentPart::Image(path) => {
+                let mime = image::ImageFormat::from_path(path)
+                    .map_err(|_| EmbedError::UnsupportedFile(path.display().to_string()))?
+                    .to_mime_type();
+                let bytes = std::fs::read(path).map_err(EmbedError::from)?;
+                let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+                Ok(json!({
+                    "type": "image_url",
+                    "image_url": {"url": format!("data:{};base64,{}", mime, data)}
+                }))
+            }
+        }
+    }
+}
+
 /// Represents a CohereEmbeder struct that contains the URL and API key for making requests to the Cohere API.
 #[derive(Debug)]
 pub struct CohereEmbedder {
@@ -22,6 +71,12 @@ pub struct CohereEmbedder {
     api_key: String,
     /// The HTTP client for making requests.
     client: Client,
+    /// Observes every request, see [`with_hook`](Self::with_hook).
+    hook: Option<Arc<dyn RequestHook>>,
+    /// Batches larger than one request may hold are split.
+    limits: RequestLimits,
+    /// Applied to inputs over the token limit before they are sent.
+    truncation: Option<TruncationPolicy>,
 }
 
 impl Default for CohereEmbedder {
@@ -32,6 +87,9 @@ impl Default for CohereEmbedder {
 }
 
 impl CohereEmbedder {
+    /// Name of the environment variable, or secret, that holds the API key.
+    pub const API_KEY_NAME: &'static str = "CO_API_KEY";
+
     /// Creates a new instance of `CohereEmbeder` with the specified model and API key.
     ///
     /// # Arguments
@@ -44,21 +102,98 @@ impl CohereEmbedder {
     /// A new instance of `CohereEmbeder`.
     pub fn new(model: String, api_key: Option<String>) -> Self {
         let api_key =
-            api_key.unwrap_or_else(|| std::env::var("CO_API_KEY").expect("API key not set"));
+            api_key.unwrap_or_else(|| std::env::var(Self::API_KEY_NAME).expect("API key not set"));
 
         Self {
             model,
             url: "https://api.cohere.com/v1/embed".to_string(),
             api_key,
             client: Client::new(),
+            hook: None,
+            limits: RequestLimits::COHERE,
+            truncation: None,
         }
     }
 
+    /// Like [`new`](Self::new), but resolves the API key named [`API_KEY_NAME`](Self::API_KEY_NAME)
+    /// with `secrets` and fails instead of panicking when it cannot be found.
+    pub fn from_secret(model: String, secrets: &dyn SecretProvider) -> Result<Self, EmbedError> {
+        let api_key = secrets.resolve(Self::API_KEY_NAME)?;
+        Ok(Self::new(model, Some(api_key)))
+    }
+
+    /// Sends requests with `client`, e.g. one built from an
+    /// [`HttpClientConfig`](super::http::HttpClientConfig) to go through a proxy.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Reports the size, status and latency of every request to `hook`.
+    pub fn with_hook(mut self, hook: Arc<dyn RequestHook>) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+
+    /// Overrides the per-request limits, by default [`RequestLimits::COHERE`], e.g. for a
+    /// compatible endpoint with lower limits.
+    pub fn with_request_limits(mut self, limits: RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Applies `policy` to inputs longer than the
+    /// [`max_input_tokens`](RequestLimits::max_input_tokens) of the model. By default they are sent
+    /// as they are and the provider decides.
+    pub fn with_truncation(mut self, policy: TruncationPolicy) -> Self {
+        self.truncation = Some(policy);
+        self
+    }
+
+    /// The truncation policy and the token limit it applies to, if one is set.
+    pub fn truncation(&self) -> Option<(TruncationPolicy, usize)> {
+        self.truncation.zip(self.limits.max_input_tokens)
+    }
+
+    /// Embeds `text_batch` as documents to search over, split into as many requests as the
+    /// provider's limits require.
     pub async fn embed(
         &self,
         text_batch: &[String],
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
-        let response = self
+        self.embed_as(text_batch, "search_document").await
+    }
+
+    /// Embeds `text_batch` as search queries, with the `search_query` input type.
+    pub async fn embed_query(
+        &self,
+        text_batch: &[String],
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.embed_as(text_batch, "search_query").await
+    }
+
+    async fn embed_as(
+        &self,
+        text_batch: &[String],
+        input_type: &str,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        let (pieces, owners) = match self.truncation() {
+            Some((policy, max_tokens)) => truncation::prepare(policy, text_batch, max_tokens)?,
+            None => (text_batch.to_vec(), (0..text_batch.len()).collect()),
+        };
+        let mut encodings = Vec::with_capacity(pieces.len());
+        for chunk in self.limits.split(&pieces) {
+            encodings.extend(self.embed_request(chunk, input_type).await?);
+        }
+        truncation::combine(&pieces, &owners, encodings, text_batch.len())
+    }
+
+    async fn embed_request(
+        &self,
+        text_batch: &[String],
+        input_type: &str,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        let request = self
             .client
             .post(&self.url)
             .header("Accept", "application/json")
@@ -67,12 +202,24 @@ impl CohereEmbedder {
             .json(&json!({
                 "texts": text_batch,
                 "model": self.model,
-                "input_type": "search_document"
-            }))
-            .send()
-            .await?;
+                "input_type": input_type
+            }));
+        let response = hooks::send(
+            request,
+            self.hook.as_deref(),
+            "cohere",
+            &self.model,
+            text_batch.len(),
+        )
+        .await?;
+        if !response.status().is_success() {
+            return Err(EmbedError::from_response(response).await.into());
+        }
 
-        let data = response.json::<CohereEmbedResponse>().await?;
+        let data = response
+            .json::<CohereEmbedResponse>()
+            .await
+            .map_err(EmbedError::from)?;
         let encodings = data.embeddings;
 
         let encodings = encodings
@@ -82,6 +229,72 @@ impl CohereEmbedder {
 
         Ok(encodings)
     }
+
+    /// Embeds documents made of interleaved text and image parts into one vector per document.
+    /// Requires a multimodal model such as `embed-v4.0`. Documents are sent at most
+    /// [`max_inputs`](RequestLimits::max_inputs) per request.
+    pub async fn embed_documents(
+        &self,
+        documents: &[Vec<ContentPart>],
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        let mut encodings = Vec::with_capacity(documents.len());
+        for chunk in documents.chunks(self.limits.max_inputs.max(1)) {
+            encodings.extend(self.embed_documents_request(chunk).await?);
+        }
+        Ok(encodings)
+    }
+
+    async fn embed_documents_request(
+        &self,
+        documents: &[Vec<ContentPart>],
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        let inputs = documents
+            .iter()
+            .map(|parts| -> anyhow::Result<Value> {
+                let content = parts
+                    .iter()
+                    .map(ContentPart::to_json)
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Ok(json!({ "content": content }))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let request = self
+            .client
+            .post(V2_EMBED_URL)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&json!({
+                "inputs": inputs,
+                "model": self.model,
+                "input_type": "search_document",
+                "embedding_types": ["float"]
+            }));
+        let response = hooks::send(
+            request,
+            self.hook.as_deref(),
+            "cohere",
+            &self.model,
+            documents.len(),
+        )
+        .await?;
+        if !response.status().is_success() {
+            return Err(EmbedError::from_response(response).await.into());
+        }
+
+        let data = response
+            .json::<CohereEmbedV2Response>()
+            .await
+            .map_err(EmbedError::from)?;
+
+        Ok(data
+            .embeddings
+            .float
+            .into_iter()
+            .map(EmbeddingResult::DenseVector)
+            .collect())
+    }
 }
 
 #[cfg(test)]
@@ -99,4 +312,19 @@ mod tests {
         let embeddings = cohere.embed(&text_batch).await.unwrap();
         assert_eq!(embeddings.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_cohere_embed_documents() {
+        let cohere = CohereEmbedder::new("embed-v4.0".to_string(), None);
+        let documents = vec![
+            vec![
+                ContentPart::Text("A photo of a cat".to_string()),
+                ContentPart::Image(PathBuf::from("../test_files/clip/cat1.jpg")),
+            ],
+            vec![ContentPart::Text("Just text".to_string())],
+        ];
+
+        let embeddings = cohere.embed_documents(&documents).await.unwrap();
+        assert_eq!(embeddings.len(), 2);
+    }
 }
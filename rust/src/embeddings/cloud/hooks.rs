@@ -0,0 +1,177 @@
+//! Hooks that observe the requests cloud embedders send, e.g. to feed latencies and error rates
+//! into your own telemetry. Events only carry sizes, status codes and timings: API keys, headers
+//! and the texts being embedded never reach a hook, and query strings are stripped from URLs.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use reqwest::{RequestBuilder, Response};
+
+use crate::error::EmbedError;
+
+/// A request about to be sent.
+#[derive(Debug, Clone)]
+pub struct RequestEvent {
+    /// The provider, e.g. `"openai"`.
+    pub provider: &'static str,
+    pub model: String,
+    pub url: String,
+    /// Number of texts or documents in the request.
+    pub inputs: usize,
+    /// Size of the request body in bytes.
+    pub request_bytes: usize,
+}
+
+/// The outcome of a request.
+#[derive(Debug, Clone)]
+pub struct ResponseEvent {
+    pub request: RequestEvent,
+    /// HTTP status code, `None` if no response was received.
+    pub status: Option<u16>,
+    /// Time until the response headers arrived.
+    pub latency: Duration,
+    /// Size of the response body, if the server reported it.
+    pub response_bytes: Option<u64>,
+    /// Why no response was received, e.g. a timeout or a refused connection.
+    pub error: Option<String>,
+}
+
+/// Receives an event before and after every request of a cloud embedder. Hooks run inline, so
+/// they should hand slow work off to another task.
+pub trait RequestHook: Send + Sync {
+    fn on_request(&self, _event: &RequestEvent) {}
+
+    fn on_response(&self, event: &ResponseEvent);
+}
+
+impl fmt::Debug for dyn RequestHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RequestHook")
+    }
+}
+
+/// Logs every response through `tracing`: successes at debug level, failures at warn level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingRequestHook;
+
+impl RequestHook for TracingRequestHook {
+    fn on_response(&self, event: &ResponseEvent) {
+        let request = &event.request;
+        if event.status.is_some_and(|status| status < 400) {
+            tracing::debug!(
+                provider = request.provider,
+                model = %request.model,
+                inputs = request.inputs,
+                request_bytes = request.request_bytes,
+                status = event.status,
+                latency_ms = event.latency.as_millis() as u64,
+                response_bytes = event.response_bytes,
+                "cloud embedding request"
+            );
+        } else {
+            tracing::warn!(
+                provider = request.provider,
+                model = %request.model,
+                inputs = request.inputs,
+                request_bytes = request.request_bytes,
+                status = event.status,
+                latency_ms = event.latency.as_millis() as u64,
+                error = event.error.as_deref(),
+                "cloud embedding request failed"
+            );
+        }
+    }
+}
+
+/// Sends `request`, reporting it to `hook` if there is one.
+pub(crate) async fn send(
+    request: RequestBuilder,
+    hook: Option<&dyn RequestHook>,
+    provider: &'static str,
+    model: &str,
+    inputs: usize,
+) -> Result<Response, EmbedError> {
+    let Some(hook) = hook else {
+        return Ok(request.send().await?);
+    };
+    let (client, request) = request.build_split();
+    let request = request?;
+
+    let mut url = request.url().clone();
+    url.set_query(None);
+    let event = RequestEvent {
+        provider,
+        model: model.to_string(),
+        url: url.to_string(),
+        inputs,
+        request_bytes: request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map_or(0, <[u8]>::len),
+    };
+    hook.on_request(&event);
+
+    let start = Instant::now();
+    let result = client.execute(request).await;
+    let latency = start.elapsed();
+    let (status, response_bytes, error) = match &result {
+        Ok(response) => (
+            Some(response.status().as_u16()),
+            response.content_length(),
+            None,
+        ),
+        Err(e) => (None, None, Some(e.without_url().to_string())),
+    };
+    hook.on_response(&ResponseEvent {
+        request: event,
+        status,
+        latency,
+        response_bytes,
+        error,
+    });
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use reqwest::Client;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingHook {
+        events: Mutex<Vec<ResponseEvent>>,
+    }
+
+    impl RequestHook for RecordingHook {
+        fn on_response(&self, event: &ResponseEvent) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_reports_redacted_events() {
+        let hook = RecordingHook::default();
+        // Nothing listens on the discard port, so the request fails without leaving the machine.
+        let request = Client::new()
+            .post("http://127.0.0.1:9/v1/embeddings?key=secret-key")
+            .header("Authorization", "Bearer secret-key")
+            .json(&json!({"input": ["secret text"], "model": "test-model"}));
+
+        let result = send(request, Some(&hook), "openai", "test-model", 1).await;
+        assert!(result.is_err());
+
+        let events = hook.events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.request.inputs, 1);
+        assert!(event.request.request_bytes > 0);
+        assert_eq!(event.status, None);
+        assert!(event.error.is_some());
+        let logged = format!("{:?}", event);
+        assert!(!logged.contains("secret"), "{}", logged);
+    }
+}
@@ -0,0 +1,88 @@
+//! HTTP client settings for the cloud embedders, for deployments that reach the APIs through a
+//! proxy or a TLS-inspecting gateway with its own CA.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use reqwest::{Certificate, Client, Proxy};
+
+use crate::error::EmbedError;
+
+/// Options for the [`Client`] the cloud embedders send requests with. Without a proxy, the
+/// `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` environment variables are honored as usual. Callers who
+/// need more control can build their own [`Client`] and pass it to the embedders instead.
+#[derive(Debug, Clone, Default)]
+pub struct HttpClientConfig {
+    /// Proxy URL for all requests, e.g. `http://proxy.corp:3128`.
+    pub proxy: Option<String>,
+    /// PEM files with extra root certificates to trust.
+    pub ca_certificates: Vec<PathBuf>,
+    /// Timeout for a whole request, from connecting until the response body is read.
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+}
+
+impl HttpClientConfig {
+    pub fn with_proxy(mut self, proxy: &str) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    pub fn with_ca_certificate(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_certificates.push(path.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn build(&self) -> Result<Client, EmbedError> {
+        let mut builder = Client::builder();
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy)
+                .map_err(|e| EmbedError::InvalidConfig(format!("proxy {}: {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+        for path in &self.ca_certificates {
+            let pem = std::fs::read(path)?;
+            let certificate = Certificate::from_pem(&pem).map_err(|e| {
+                EmbedError::InvalidConfig(format!("CA certificate {}: {}", path.display(), e))
+            })?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_client_config() {
+        let config = HttpClientConfig::default()
+            .with_proxy("http://localhost:3128")
+            .with_timeout(Duration::from_secs(30))
+            .with_connect_timeout(Duration::from_secs(5));
+        assert!(config.build().is_ok());
+
+        let config = HttpClientConfig::default().with_proxy("not a url");
+        assert!(matches!(config.build(), Err(EmbedError::InvalidConfig(_))));
+
+        let config = HttpClientConfig::default().with_ca_certificate("missing.pem");
+        assert!(config.build().is_err());
+    }
+}
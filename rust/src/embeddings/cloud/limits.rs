@@ -0,0 +1,93 @@
+//! Per-request limits of the cloud embedding APIs. Batches larger than a provider accepts are split
+//! into several requests instead of failing.
+
+/// How much a single request to a cloud embedding API may hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// Most texts or documents in one request.
+    pub max_inputs: usize,
+    /// Most tokens summed over all inputs of one request, if the provider limits it.
+    pub max_tokens: Option<usize>,
+    /// Most tokens in a single input, used by
+    /// [`TruncationPolicy`](super::truncation::TruncationPolicy).
+    pub max_input_tokens: Option<usize>,
+}
+
+impl RequestLimits {
+    /// <https://platform.openai.com/docs/api-reference/embeddings/create>
+    pub const OPENAI: Self = Self {
+        max_inputs: 2048,
+        max_tokens: Some(300_000),
+        max_input_tokens: Some(8192),
+    };
+
+    /// <https://docs.cohere.com/reference/embed>
+    pub const COHERE: Self = Self {
+        max_inputs: 96,
+        max_tokens: None,
+        max_input_tokens: Some(512),
+    };
+
+    /// Splits `texts` into consecutive chunks that each fit in one request. A single text over the
+    /// token limit still gets a request of its own, for the provider to truncate or reject.
+    pub fn split<'a>(&self, texts: &'a [String]) -> Vec<&'a [String]> {
+        let max_inputs = self.max_inputs.max(1);
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let mut tokens = 0;
+        for (i, text) in texts.iter().enumerate() {
+            let text_tokens = estimate_tokens(text);
+            let over_tokens = self
+                .max_tokens
+                .is_some_and(|max_tokens| tokens + text_tokens > max_tokens);
+            if i > start && (i - start == max_inputs || over_tokens) {
+                chunks.push(&texts[start..i]);
+                start = i;
+                tokens = 0;
+            }
+            tokens += text_tokens;
+        }
+        if start < texts.len() {
+            chunks.push(&texts[start..]);
+        }
+        chunks
+    }
+}
+
+/// Overestimates the token count of `text` without the provider's tokenizer. English averages about
+/// four bytes per token; counting three keeps requests under the limit for denser text too.
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_by_inputs_and_tokens() {
+        let texts = vec!["abc".to_string(); 10];
+        let limits = RequestLimits {
+            max_inputs: 4,
+            max_tokens: None,
+            max_input_tokens: None,
+        };
+        let sizes: Vec<_> = limits.split(&texts).iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![4, 4, 2]);
+
+        let limits = RequestLimits {
+            max_inputs: 100,
+            max_tokens: Some(3),
+            max_input_tokens: None,
+        };
+        let sizes: Vec<_> = limits.split(&texts).iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![3, 3, 3, 1]);
+
+        // A text over the token limit is sent on its own rather than dropped.
+        let texts = vec!["a".repeat(30), "abc".to_string()];
+        let sizes: Vec<_> = limits.split(&texts).iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![1, 1]);
+
+        assert!(limits.split(&[]).is_empty());
+    }
+}
@@ -1,2 +1,7 @@
 pub mod cohere;
+pub mod hooks;
+pub mod http;
+pub mod limits;
 pub mod openai;
+pub mod secrets;
+pub mod truncation;
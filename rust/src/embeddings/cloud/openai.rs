@@ -1,8 +1,15 @@
+use std::sync::Arc;
+
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
 
+use crate::embeddings::cloud::hooks::{self, RequestHook};
+use crate::embeddings::cloud::limits::RequestLimits;
+use crate::embeddings::cloud::secrets::SecretProvider;
+use crate::embeddings::cloud::truncation::{self, TruncationPolicy};
 use crate::embeddings::embed::EmbeddingResult;
+use crate::error::EmbedError;
 
 #[derive(Deserialize, Debug, Default)]
 pub struct OpenAIEmbedResponse {
@@ -30,6 +37,9 @@ pub struct OpenAIEmbedder {
     model: String,
     api_key: String,
     client: Client,
+    hook: Option<Arc<dyn RequestHook>>,
+    limits: RequestLimits,
+    truncation: Option<TruncationPolicy>,
 }
 
 impl Default for OpenAIEmbedder {
@@ -39,23 +49,85 @@ impl Default for OpenAIEmbedder {
 }
 
 impl OpenAIEmbedder {
+    /// Name of the environment variable, or secret, that holds the API key.
+    pub const API_KEY_NAME: &'static str = "OPENAI_API_KEY";
+
     pub fn new(model: String, api_key: Option<String>) -> Self {
         let api_key =
-            api_key.unwrap_or_else(|| std::env::var("OPENAI_API_KEY").expect("API Key not set"));
+            api_key.unwrap_or_else(|| std::env::var(Self::API_KEY_NAME).expect("API Key not set"));
 
         Self {
             model,
             url: "https://api.openai.com/v1/embeddings".to_string(),
             api_key,
             client: Client::new(),
+            hook: None,
+            limits: RequestLimits::OPENAI,
+            truncation: None,
         }
     }
 
+    /// Like [`new`](Self::new), but resolves the API key named [`API_KEY_NAME`](Self::API_KEY_NAME)
+    /// with `secrets` and fails instead of panicking when it cannot be found.
+    pub fn from_secret(model: String, secrets: &dyn SecretProvider) -> Result<Self, EmbedError> {
+        let api_key = secrets.resolve(Self::API_KEY_NAME)?;
+        Ok(Self::new(model, Some(api_key)))
+    }
+
+    /// Sends requests with `client`, e.g. one built from an
+    /// [`HttpClientConfig`](super::http::HttpClientConfig) to go through a proxy.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Reports the size, status and latency of every request to `hook`.
+    pub fn with_hook(mut self, hook: Arc<dyn RequestHook>) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+
+    /// Overrides the per-request limits, by default [`RequestLimits::OPENAI`], e.g. for a
+    /// compatible endpoint with lower limits.
+    pub fn with_request_limits(mut self, limits: RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Applies `policy` to inputs longer than the
+    /// [`max_input_tokens`](RequestLimits::max_input_tokens) of the model. By default they are sent
+    /// as they are and the provider decides.
+    pub fn with_truncation(mut self, policy: TruncationPolicy) -> Self {
+        self.truncation = Some(policy);
+        self
+    }
+
+    /// The truncation policy and the token limit it applies to, if one is set.
+    pub fn truncation(&self) -> Option<(TruncationPolicy, usize)> {
+        self.truncation.zip(self.limits.max_input_tokens)
+    }
+
+    /// Embeds `text_batch`, split into as many requests as the provider's limits require.
     pub async fn embed(
         &self,
         text_batch: &[String],
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
-        let response = self
+        let (pieces, owners) = match self.truncation() {
+            Some((policy, max_tokens)) => truncation::prepare(policy, text_batch, max_tokens)?,
+            None => (text_batch.to_vec(), (0..text_batch.len()).collect()),
+        };
+        let mut encodings = Vec::with_capacity(pieces.len());
+        for chunk in self.limits.split(&pieces) {
+            encodings.extend(self.embed_request(chunk).await?);
+        }
+        truncation::combine(&pieces, &owners, encodings, text_batch.len())
+    }
+
+    async fn embed_request(
+        &self,
+        text_batch: &[String],
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        let request = self
             .client
             .post(&self.url)
             .header("Content-Type", "application/json")
@@ -64,12 +136,24 @@ impl OpenAIEmbedder {
                 "input": text_batch,
                 "model": self.model,
                 "encoding_format": "float"
-            }))
-            .send()
-            .await?;
-        let data = response.json::<OpenAIEmbedResponse>().await?;
+            }));
+        let response = hooks::send(
+            request,
+            self.hook.as_deref(),
+            "openai",
+            &self.model,
+            text_batch.len(),
+        )
+        .await?;
+        if !response.status().is_success() {
+            return Err(EmbedError::from_response(response).await.into());
+        }
+        let data = response
+            .json::<OpenAIEmbedResponse>()
+            .await
+            .map_err(EmbedError::from)?;
 
-        println!("{:?}", data.usage);
+        tracing::debug!("{:?}", data.usage);
 
         let encodings = data
             .data
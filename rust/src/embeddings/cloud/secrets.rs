@@ -0,0 +1,111 @@
+//! Resolves the API keys of cloud embedders, so keys can come from a secret manager or a mounted
+//! file instead of the process environment.
+
+use std::path::PathBuf;
+
+use crate::error::EmbedError;
+
+/// Looks up a secret by name. Cloud embedders ask for the name of their usual environment
+/// variable, e.g. `OPENAI_API_KEY` or `CO_API_KEY`, and resolve it once when they are created.
+pub trait SecretProvider: Send + Sync {
+    fn resolve(&self, name: &str) -> Result<String, EmbedError>;
+}
+
+/// Reads secrets from environment variables, which is what the embedders do by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvSecretProvider;
+
+impl SecretProvider for EnvSecretProvider {
+    fn resolve(&self, name: &str) -> Result<String, EmbedError> {
+        std::env::var(name).map_err(|_| {
+            EmbedError::InvalidConfig(format!("environment variable {} is not set", name))
+        })
+    }
+}
+
+/// Reads each secret from a file named after it in `dir`, the layout of Docker and Kubernetes
+/// secrets, e.g. `/run/secrets/OPENAI_API_KEY`. Surrounding whitespace is trimmed.
+#[derive(Debug, Clone)]
+pub struct FileSecretProvider {
+    dir: PathBuf,
+}
+
+impl FileSecretProvider {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl SecretProvider for FileSecretProvider {
+    fn resolve(&self, name: &str) -> Result<String, EmbedError> {
+        let path = self.dir.join(name);
+        let secret = std::fs::read_to_string(&path)?;
+        let secret = secret.trim();
+        if secret.is_empty() {
+            return Err(EmbedError::InvalidConfig(format!(
+                "secret file {} is empty",
+                path.display()
+            )));
+        }
+        Ok(secret.to_string())
+    }
+}
+
+/// Resolves secrets with a closure, e.g. one that queries a vault client.
+pub struct CallbackSecretProvider<F> {
+    callback: F,
+}
+
+impl<F> CallbackSecretProvider<F>
+where
+    F: Fn(&str) -> Result<String, EmbedError> + Send + Sync,
+{
+    pub fn new(callback: F) -> Self {
+        Self { callback }
+    }
+}
+
+impl<F> SecretProvider for CallbackSecretProvider<F>
+where
+    F: Fn(&str) -> Result<String, EmbedError> + Send + Sync,
+{
+    fn resolve(&self, name: &str) -> Result<String, EmbedError> {
+        (self.callback)(name)
+    }
+}
+
+impl std::fmt::Debug for dyn SecretProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretProvider")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_secret_providers() {
+        let dir = TempDir::new("secrets").unwrap();
+        std::fs::write(dir.path().join("OPENAI_API_KEY"), "sk-from-file\n").unwrap();
+        let files = FileSecretProvider::new(dir.path());
+        assert_eq!(files.resolve("OPENAI_API_KEY").unwrap(), "sk-from-file");
+        assert!(files.resolve("CO_API_KEY").is_err());
+
+        let callback = CallbackSecretProvider::new(|name: &str| match name {
+            "CO_API_KEY" => Ok("co-from-vault".to_string()),
+            _ => Err(EmbedError::InvalidConfig(format!(
+                "unknown secret {}",
+                name
+            ))),
+        });
+        assert_eq!(callback.resolve("CO_API_KEY").unwrap(), "co-from-vault");
+        assert!(callback.resolve("OPENAI_API_KEY").is_err());
+
+        assert!(matches!(
+            EnvSecretProvider.resolve("EMBED_ANYTHING_TEST_UNSET_SECRET"),
+            Err(EmbedError::InvalidConfig(_))
+        ));
+    }
+}
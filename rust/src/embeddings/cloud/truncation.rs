@@ -0,0 +1,275 @@
+//! What to do with inputs longer than a cloud model accepts, applied before they are sent. Token
+//! counts are estimated from the byte length (see [`RequestLimits`](super::limits::RequestLimits)),
+//! so texts somewhat under the real limit may be shortened too.
+
+use std::str::FromStr;
+
+use super::limits::estimate_tokens;
+use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+use crate::error::EmbedError;
+
+/// Metadata field set to the [`TruncationPolicy::as_str`] of the policy that shortened a chunk.
+pub const TRUNCATED_KEY: &str = "truncated";
+
+/// Bytes per token assumed when cutting text to a token budget, matching
+/// [`estimate_tokens`](super::limits::estimate_tokens).
+const BYTES_PER_TOKEN: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Fail the batch with [`EmbedError::InvalidConfig`].
+    Error,
+    /// Drop the beginning of the text and keep its end.
+    TruncateHead,
+    /// Drop the end of the text and keep its beginning.
+    TruncateTail,
+    /// Embed the text in pieces and average their embeddings, weighted by length.
+    SplitAndAverage,
+}
+
+impl TruncationPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TruncationPolicy::Error => "error",
+            TruncationPolicy::TruncateHead => "truncate_head",
+            TruncationPolicy::TruncateTail => "truncate_tail",
+            TruncationPolicy::SplitAndAverage => "split_and_average",
+        }
+    }
+
+    /// Cuts `text` into the pieces to send for it, or returns it whole if it fits in `max_tokens`.
+    fn apply(&self, text: &str, max_tokens: usize) -> Result<Vec<String>, EmbedError> {
+        if estimate_tokens(text) <= max_tokens {
+            return Ok(vec![text.to_string()]);
+        }
+        let max_bytes = (max_tokens * BYTES_PER_TOKEN).max(4);
+        match self {
+            TruncationPolicy::Error => Err(EmbedError::InvalidConfig(format!(
+                "input of about {} tokens is longer than the {} the model accepts",
+                estimate_tokens(text),
+                max_tokens
+            ))),
+            TruncationPolicy::TruncateHead => {
+                let mut start = text.len() - max_bytes;
+                while !text.is_char_boundary(start) {
+                    start += 1;
+                }
+                Ok(vec![text[start..].to_string()])
+            }
+            TruncationPolicy::TruncateTail => {
+                Ok(vec![text[..floor_boundary(text, max_bytes)].to_string()])
+            }
+            TruncationPolicy::SplitAndAverage => {
+                let mut pieces = Vec::new();
+                let mut rest = text;
+                while rest.len() > max_bytes {
+                    let end = floor_boundary(rest, max_bytes);
+                    // Prefer to split between words when a space is not too far back.
+                    let end = match rest[..end].rfind(char::is_whitespace) {
+                        Some(space) if space > end / 2 => space,
+                        _ => end,
+                    };
+                    pieces.push(rest[..end].to_string());
+                    rest = rest[end..].trim_start();
+                }
+                if !rest.is_empty() {
+                    pieces.push(rest.to_string());
+                }
+                Ok(pieces)
+            }
+        }
+    }
+
+    /// Whether the policy changes `text` before it is sent.
+    pub fn shortens(&self, text: &str, max_tokens: usize) -> bool {
+        *self != TruncationPolicy::Error && estimate_tokens(text) > max_tokens
+    }
+}
+
+impl FromStr for TruncationPolicy {
+    type Err = EmbedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "error" => Ok(TruncationPolicy::Error),
+            "truncate_head" => Ok(TruncationPolicy::TruncateHead),
+            "truncate_tail" => Ok(TruncationPolicy::TruncateTail),
+            "split_and_average" => Ok(TruncationPolicy::SplitAndAverage),
+            _ => Err(EmbedError::InvalidConfig(format!(
+                "unknown truncation policy {:?}, expected error, truncate_head, truncate_tail or \
+                 split_and_average",
+                s
+            ))),
+        }
+    }
+}
+
+/// Largest char boundary of `text` at or before `index`.
+fn floor_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// The texts to send for `texts` under `policy`, and the index of the input each one belongs to.
+pub(crate) fn prepare(
+    policy: TruncationPolicy,
+    texts: &[String],
+    max_tokens: usize,
+) -> Result<(Vec<String>, Vec<usize>), EmbedError> {
+    let mut pieces = Vec::with_capacity(texts.len());
+    let mut owners = Vec::with_capacity(texts.len());
+    for (index, text) in texts.iter().enumerate() {
+        for piece in policy.apply(text, max_tokens)? {
+            pieces.push(piece);
+            owners.push(index);
+        }
+    }
+    Ok((pieces, owners))
+}
+
+/// Averages the embeddings of the pieces from [`prepare`] back into one per input. Inputs sent in
+/// one piece keep their embedding; averaged ones are normalized again.
+pub(crate) fn combine(
+    pieces: &[String],
+    owners: &[usize],
+    encodings: Vec<EmbeddingResult>,
+    inputs: usize,
+) -> anyhow::Result<Vec<EmbeddingResult>> {
+    if pieces.len() == inputs {
+        return Ok(encodings);
+    }
+    let mut sums: Vec<Option<(Vec<f32>, usize)>> = vec![None; inputs];
+    let mut counts = vec![0; inputs];
+    for ((piece, &owner), encoding) in pieces.iter().zip(owners).zip(encodings) {
+        let vector = encoding.to_dense()?;
+        let weight = piece.len().max(1);
+        counts[owner] += 1;
+        match &mut sums[owner] {
+            Some((sum, total)) => {
+                sum.iter_mut()
+                    .zip(&vector)
+                    .for_each(|(s, v)| *s += v * weight as f32);
+                *total += weight;
+            }
+            None => {
+                sums[owner] = Some((vector.iter().map(|v| v * weight as f32).collect(), weight));
+            }
+        }
+    }
+    Ok(sums
+        .into_iter()
+        .zip(counts)
+        .map(|(sum, count)| {
+            let (mut sum, total) = sum.unwrap_or_default();
+            sum.iter_mut().for_each(|s| *s /= total.max(1) as f32);
+            if count > 1 {
+                let norm = sum.iter().map(|s| s * s).sum::<f32>().sqrt();
+                if norm > 0.0 {
+                    sum.iter_mut().for_each(|s| *s /= norm);
+                }
+            }
+            EmbeddingResult::DenseVector(sum)
+        })
+        .collect())
+}
+
+/// Sets [`TRUNCATED_KEY`] on the embeddings whose text was shortened under `truncation`, as
+/// returned by [`Embedder::truncation`](crate::embeddings::embed::Embedder::truncation).
+pub(crate) fn flag_truncated(
+    truncation: Option<(TruncationPolicy, usize)>,
+    embeddings: &mut [EmbedData],
+) {
+    let Some((policy, max_tokens)) = truncation else {
+        return;
+    };
+    for embedding in embeddings {
+        let shortened = embedding
+            .text
+            .as_deref()
+            .is_some_and(|text| policy.shortens(text, max_tokens));
+        if shortened {
+            embedding
+                .metadata
+                .get_or_insert_with(Default::default)
+                .insert(TRUNCATED_KEY.to_string(), policy.as_str().to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncation_policies() {
+        let text = "one two three four five six seven eight nine ten".to_string();
+        let short = "short".to_string();
+
+        assert_eq!(
+            TruncationPolicy::Error.apply(&short, 4).unwrap(),
+            vec!["short"]
+        );
+        assert!(TruncationPolicy::Error.apply(&text, 4).is_err());
+
+        let tail = TruncationPolicy::TruncateTail.apply(&text, 4).unwrap();
+        assert_eq!(tail, vec!["one two thre"]);
+        let head = TruncationPolicy::TruncateHead.apply(&text, 4).unwrap();
+        assert_eq!(head, vec!["ght nine ten"]);
+
+        let pieces = TruncationPolicy::SplitAndAverage.apply(&text, 4).unwrap();
+        assert!(pieces.len() > 1);
+        assert!(pieces.iter().all(|piece| piece.len() <= 12));
+        assert_eq!(pieces.join(" "), text);
+
+        // Multi-byte characters are never cut in half.
+        let accents = "é".repeat(20);
+        let tail = TruncationPolicy::TruncateTail.apply(&accents, 4).unwrap();
+        assert_eq!(tail[0], "é".repeat(6));
+        let head = TruncationPolicy::TruncateHead.apply(&accents, 4).unwrap();
+        assert_eq!(head[0], "é".repeat(6));
+
+        assert_eq!(
+            "split-and-average".parse::<TruncationPolicy>().unwrap(),
+            TruncationPolicy::SplitAndAverage
+        );
+        assert!("middle".parse::<TruncationPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_combine_and_flag() {
+        let texts = vec!["a".to_string(), "b".repeat(30)];
+        let (pieces, owners) = prepare(TruncationPolicy::SplitAndAverage, &texts, 4).unwrap();
+        assert_eq!(owners, vec![0, 1, 1, 1]);
+
+        let encodings = vec![
+            EmbeddingResult::DenseVector(vec![1.0, 0.0]),
+            EmbeddingResult::DenseVector(vec![1.0, 0.0]),
+            EmbeddingResult::DenseVector(vec![0.0, 1.0]),
+            EmbeddingResult::DenseVector(vec![0.0, 1.0]),
+        ];
+        let combined = combine(&pieces, &owners, encodings, texts.len()).unwrap();
+        assert_eq!(combined[0].to_dense().unwrap(), vec![1.0, 0.0]);
+        let averaged = combined[1].to_dense().unwrap();
+        assert!(averaged[1] > averaged[0]);
+        let norm = averaged.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-6);
+
+        let mut embeddings = texts
+            .iter()
+            .zip(combined)
+            .map(|(text, encoding)| EmbedData::new(encoding, Some(text.clone()), None))
+            .collect::<Vec<_>>();
+        flag_truncated(
+            Some((TruncationPolicy::SplitAndAverage, 4)),
+            &mut embeddings,
+        );
+        assert!(embeddings[0].metadata.is_none());
+        assert_eq!(
+            embeddings[1].metadata.as_ref().unwrap()[TRUNCATED_KEY],
+            "split_and_average"
+        );
+    }
+}
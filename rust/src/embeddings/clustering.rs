@@ -0,0 +1,246 @@
+//! Mini-batch k-means over embeddings, for deduplicating, topic-clustering or sampling a corpus
+//! after it has been embedded.
+
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::{Rng, SeedableRng};
+use strum::EnumString;
+
+use super::embed::EmbedData;
+use crate::error::EmbedError;
+
+/// The distance vectors are clustered by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Distance {
+    /// Vectors are normalized and centroids kept on the unit sphere (spherical k-means).
+    #[default]
+    Cosine,
+    Euclidean,
+}
+
+#[derive(Debug, Clone)]
+pub struct KMeansConfig {
+    /// Number of clusters.
+    pub k: usize,
+    pub distance: Distance,
+    /// Number of vectors sampled per iteration. The whole corpus is used if it is smaller.
+    pub batch_size: usize,
+    pub max_iterations: usize,
+    /// Stops early once no centroid moves further than this in an iteration.
+    pub tolerance: f32,
+    /// Seed for initialization and sampling, so runs are reproducible.
+    pub seed: u64,
+}
+
+impl Default for KMeansConfig {
+    fn default() -> Self {
+        Self {
+            k: 8,
+            distance: Distance::Cosine,
+            batch_size: 1024,
+            max_iterations: 100,
+            tolerance: 1e-4,
+            seed: 42,
+        }
+    }
+}
+
+impl KMeansConfig {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            ..Default::default()
+        }
+    }
+}
+
+/// Centroids fitted by [`KMeans::fit`].
+#[derive(Debug, Clone)]
+pub struct KMeans {
+    pub centroids: Vec<Vec<f32>>,
+    pub distance: Distance,
+}
+
+impl KMeans {
+    /// Fits `config.k` centroids to `vectors` with mini-batch k-means and k-means++ seeding.
+    pub fn fit(vectors: &[Vec<f32>], config: &KMeansConfig) -> anyhow::Result<Self> {
+        if config.k == 0 || config.k > vectors.len() {
+            return Err(EmbedError::InvalidConfig(format!(
+                "k must be between 1 and the number of vectors ({}), got {}",
+                vectors.len(),
+                config.k
+            ))
+            .into());
+        }
+        let dim = vectors[0].len();
+        if vectors.iter().any(|vector| vector.len() != dim) {
+            return Err(EmbedError::InvalidConfig(
+                "all vectors must have the same number of dimensions".to_string(),
+            )
+            .into());
+        }
+
+        let vectors = match config.distance {
+            Distance::Cosine => vectors.iter().map(|v| normalized(v)).collect(),
+            Distance::Euclidean => vectors.to_vec(),
+        };
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut centroids = kmeans_plus_plus(&vectors, config.k, &mut rng);
+        let mut counts = vec![0usize; config.k];
+        let batch_size = config.batch_size.clamp(1, vectors.len());
+
+        for _ in 0..config.max_iterations {
+            let previous = centroids.clone();
+            let batch = sample(&mut rng, vectors.len(), batch_size);
+            let assignments = batch
+                .iter()
+                .map(|i| (i, nearest(&centroids, &vectors[i]).0))
+                .collect::<Vec<_>>();
+            for (i, cluster) in assignments {
+                counts[cluster] += 1;
+                let eta = 1.0 / counts[cluster] as f32;
+                for (c, x) in centroids[cluster].iter_mut().zip(&vectors[i]) {
+                    *c += eta * (x - *c);
+                }
+            }
+            if config.distance == Distance::Cosine {
+                centroids.iter_mut().for_each(|c| *c = normalized(c));
+            }
+            let shift = centroids
+                .iter()
+                .zip(&previous)
+                .map(|(a, b)| squared_euclidean(a, b))
+                .fold(0.0, f32::max)
+                .sqrt();
+            if shift < config.tolerance {
+                break;
+            }
+        }
+
+        Ok(Self {
+            centroids,
+            distance: config.distance,
+        })
+    }
+
+    /// Returns the cluster of `vector`.
+    pub fn predict(&self, vector: &[f32]) -> usize {
+        match self.distance {
+            Distance::Cosine => nearest(&self.centroids, &normalized(vector)).0,
+            Distance::Euclidean => nearest(&self.centroids, vector).0,
+        }
+    }
+
+    /// Returns the cluster of every vector.
+    pub fn labels(&self, vectors: &[Vec<f32>]) -> Vec<usize> {
+        vectors.iter().map(|vector| self.predict(vector)).collect()
+    }
+}
+
+/// Clusters dense embeddings and returns the fitted model with one label per embedding, in the
+/// order of `data`.
+pub fn cluster_embeddings(
+    data: &[EmbedData],
+    config: &KMeansConfig,
+) -> anyhow::Result<(KMeans, Vec<usize>)> {
+    let vectors = data
+        .iter()
+        .map(|data| data.embedding.to_dense())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let kmeans = KMeans::fit(&vectors, config)?;
+    let labels = kmeans.labels(&vectors);
+    Ok((kmeans, labels))
+}
+
+/// Picks the initial centroids far apart from each other, each with probability proportional to its
+/// squared distance from the closest centroid picked so far.
+fn kmeans_plus_plus(vectors: &[Vec<f32>], k: usize, rng: &mut StdRng) -> Vec<Vec<f32>> {
+    let mut centroids = vec![vectors[rng.gen_range(0..vectors.len())].clone()];
+    let mut distances = vectors
+        .iter()
+        .map(|vector| squared_euclidean(vector, &centroids[0]))
+        .collect::<Vec<_>>();
+    while centroids.len() < k {
+        let total = distances.iter().sum::<f32>();
+        let next = if total > 0.0 {
+            let mut target = rng.gen::<f32>() * total;
+            distances
+                .iter()
+                .position(|&d| {
+                    target -= d;
+                    target <= 0.0
+                })
+                .unwrap_or(vectors.len() - 1)
+        } else {
+            // Every vector sits on a centroid already; any of them will do.
+            rng.gen_range(0..vectors.len())
+        };
+        centroids.push(vectors[next].clone());
+        for (distance, vector) in distances.iter_mut().zip(vectors) {
+            *distance = distance.min(squared_euclidean(vector, &vectors[next]));
+        }
+    }
+    centroids
+}
+
+fn nearest(centroids: &[Vec<f32>], vector: &[f32]) -> (usize, f32) {
+    centroids
+        .iter()
+        .map(|centroid| squared_euclidean(centroid, vector))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, 0.0))
+}
+
+fn squared_euclidean(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blobs() -> Vec<Vec<f32>> {
+        let mut vectors = Vec::new();
+        for i in 0..20 {
+            let jitter = i as f32 * 0.001;
+            vectors.push(vec![1.0 + jitter, 0.0]);
+            vectors.push(vec![0.0, 1.0 + jitter]);
+        }
+        vectors
+    }
+
+    #[test]
+    fn test_kmeans_separates_blobs() {
+        let vectors = blobs();
+        for distance in [Distance::Cosine, Distance::Euclidean] {
+            let config = KMeansConfig {
+                k: 2,
+                distance,
+                batch_size: 8,
+                ..Default::default()
+            };
+            let labels = KMeans::fit(&vectors, &config).unwrap().labels(&vectors);
+            assert_ne!(labels[0], labels[1]);
+            for pair in labels.chunks(2) {
+                assert_eq!(pair, &labels[..2]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_kmeans_rejects_invalid_k() {
+        let vectors = blobs();
+        assert!(KMeans::fit(&vectors, &KMeansConfig::new(0)).is_err());
+        assert!(KMeans::fit(&vectors, &KMeansConfig::new(vectors.len() + 1)).is_err());
+    }
+}
@@ -1,24 +1,90 @@
+use crate::error::EmbedError;
 use crate::file_processor::audio::audio_processor::Segment;
+use crate::hub::{self, PrefetchReport};
 use crate::Dtype;
 
 use super::cloud::cohere::CohereEmbedder;
+use super::cloud::hooks::RequestHook;
+use super::cloud::http::HttpClientConfig;
 use super::cloud::openai::OpenAIEmbedder;
+use super::cloud::secrets::SecretProvider;
+use super::cloud::truncation::TruncationPolicy;
 use super::local::bert::{
     BertEmbed, BertEmbedder, OrtBertEmbedder, OrtSparseBertEmbedder, SparseBertEmbedder,
 };
+use super::local::bge_m3::OrtBgeM3Embedder;
 use super::local::clip::ClipEmbedder;
 use super::local::colbert::OrtColbertEmbedder;
 use super::local::colpali::{ColPaliEmbed, ColPaliEmbedder};
 use super::local::jina::{JinaEmbed, JinaEmbedder, OrtJinaEmbedder};
+use super::local::resnet::ResNetEmbedder;
 use super::local::text_embedding::ONNXModel;
+use super::local::token_pooling::TokenPooledColPali;
+use super::timing::{StageTimer, TimingReport};
+use super::ComputeDevice;
 use anyhow::anyhow;
-use serde::Deserialize;
-use std::collections::HashMap;
+use image::DynamicImage;
+use reqwest::Client;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokenizers::Tokenizer;
 
-#[derive(Deserialize, Debug, Clone)]
+/// Version of the JSON form of [`EmbedData`], written as its `schema_version` field. It changes
+/// when existing fields change shape; a new kind of embedding, such as a sparse vector, only adds a
+/// `type` value.
+pub const EMBED_DATA_SCHEMA_VERSION: u32 = 1;
+
+/// Metadata fields naming where a chunk came from, in the order [`EmbedData::id`] looks for them.
+pub const SOURCE_KEYS: [&str; 3] = ["file_name", "url", "document_id"];
+
+/// The default ID of a chunk, a UUID made from the SHA-256 of `source` and `text`.
+///
+/// The ID only changes when the source or the text changes, so re-running a pipeline upserts the
+/// same IDs instead of adding duplicates:
+///
+/// - Whitespace in `text` is collapsed and trimmed first, so re-extracting a file with different
+///   line breaks keeps the IDs.
+/// - The same text twice in one source gets one ID, and is stored once by an upsert.
+/// - The embedding, the model and the other metadata do not change the ID. Store the embeddings
+///   of different models in different collections.
+/// - The ID is stable across releases; a change to it would be a breaking change.
+pub fn chunk_id(source: &str, text: &str) -> String {
+    let normalized = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut hasher = Sha256::new();
+    hasher.update(source.as_bytes());
+    hasher.update([0]);
+    hasher.update(normalized.as_bytes());
+    let mut bytes: [u8; 16] = hasher.finalize()[..16].try_into().unwrap();
+    // Version 8 (custom) and the RFC 9562 variant, so databases that require UUIDs accept it.
+    bytes[6] = (bytes[6] & 0x0f) | 0x80;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Serialized as `{"type": "dense", "values": [...]}` or `{"type": "multi_vector", "values":
+/// [[...], ...]}`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "values")]
 pub enum EmbeddingResult {
+    #[serde(rename = "dense")]
     DenseVector(Vec<f32>),
+    #[serde(rename = "multi_vector")]
     MultiVector(Vec<Vec<f32>>),
 }
 
@@ -54,13 +120,64 @@ impl EmbeddingResult {
     }
 }
 
+/// An embedding with the text it was computed from and its metadata.
+///
+/// The JSON form is versioned by [`EMBED_DATA_SCHEMA_VERSION`] and described by
+/// `docs/schema/embed_data.v1.schema.json`. Metadata keys are written in sorted order, so the same
+/// data always serializes to the same bytes.
 #[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "EmbedDataJson")]
 pub struct EmbedData {
     pub embedding: EmbeddingResult,
     pub text: Option<String>,
     pub metadata: Option<HashMap<String, String>>,
 }
 
+impl Serialize for EmbedData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let metadata = self
+            .metadata
+            .as_ref()
+            .map(|metadata| metadata.iter().collect::<BTreeMap<_, _>>());
+        let mut state = serializer.serialize_struct("EmbedData", 4)?;
+        state.serialize_field("schema_version", &EMBED_DATA_SCHEMA_VERSION)?;
+        state.serialize_field("embedding", &self.embedding)?;
+        state.serialize_field("text", &self.text)?;
+        state.serialize_field("metadata", &metadata)?;
+        state.end()
+    }
+}
+
+/// The serialized form of [`EmbedData`]. Data without a `schema_version` is read as version 1.
+#[derive(Deserialize)]
+struct EmbedDataJson {
+    #[serde(default = "first_schema_version")]
+    schema_version: u32,
+    embedding: EmbeddingResult,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    metadata: Option<HashMap<String, String>>,
+}
+
+fn first_schema_version() -> u32 {
+    1
+}
+
+impl TryFrom<EmbedDataJson> for EmbedData {
+    type Error = String;
+
+    fn try_from(value: EmbedDataJson) -> Result<Self, Self::Error> {
+        if value.schema_version > EMBED_DATA_SCHEMA_VERSION {
+            return Err(format!(
+                "EmbedData schema version {} is newer than the supported version {}",
+                value.schema_version, EMBED_DATA_SCHEMA_VERSION
+            ));
+        }
+        Ok(EmbedData::new(value.embedding, value.text, value.metadata))
+    }
+}
+
 impl EmbedData {
     pub fn new(
         embedding: EmbeddingResult,
@@ -74,6 +191,27 @@ impl EmbedData {
         }
     }
 
+    /// The ID to upsert this chunk with: [`chunk_id`] of its text and the first of the
+    /// [`SOURCE_KEYS`] in its metadata, or of the text alone when it has no source.
+    pub fn id(&self) -> String {
+        let source = self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| SOURCE_KEYS.iter().find_map(|key| metadata.get(*key)))
+            .map_or("", String::as_str);
+        chunk_id(source, self.text.as_deref().unwrap_or_default())
+    }
+
+    /// Serializes to the versioned JSON form, see [`EMBED_DATA_SCHEMA_VERSION`].
+    pub fn to_json(&self) -> Result<String, anyhow::Error> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Reads the JSON written by [`to_json`](Self::to_json), by this or an older version.
+    pub fn from_json(json: &str) -> Result<Self, anyhow::Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
     pub fn __str__(&self) -> String {
         format!(
             "EmbedData(embedding: {:?}, text: {:?}, metadata: {:?})",
@@ -84,6 +222,15 @@ impl EmbedData {
     }
 }
 
+/// Wraps constructor failures in [`EmbedError::ModelLoad`], keeping errors that are already typed.
+fn model_load_error(error: anyhow::Error) -> anyhow::Error {
+    if error.is::<EmbedError>() {
+        error
+    } else {
+        EmbedError::ModelLoad(format!("{:#}", error)).into()
+    }
+}
+
 pub trait AudioDecoder {
     fn decode_audio(&mut self, audio_file: &std::path::Path)
         -> Result<Vec<Segment>, anyhow::Error>;
@@ -95,9 +242,45 @@ pub enum TextEmbedder {
     Jina(Box<dyn JinaEmbed + Send + Sync>),
     Bert(Box<dyn BertEmbed + Send + Sync>),
     ColBert(Box<dyn BertEmbed + Send + Sync>),
+    BgeM3(Box<OrtBgeM3Embedder>),
 }
 
 impl TextEmbedder {
+    /// The truncation policy of a cloud embedder and the token limit it applies to, if one is set.
+    pub fn truncation(&self) -> Option<(TruncationPolicy, usize)> {
+        match self {
+            TextEmbedder::OpenAI(embedder) => embedder.truncation(),
+            TextEmbedder::Cohere(embedder) => embedder.truncation(),
+            _ => None,
+        }
+    }
+
+    /// The tokenizer of a local model, used to chunk text in the model's own tokens. Cloud models
+    /// have none.
+    pub fn tokenizer(&self) -> Option<&Tokenizer> {
+        match self {
+            TextEmbedder::Bert(embedder) | TextEmbedder::ColBert(embedder) => embedder.tokenizer(),
+            TextEmbedder::BgeM3(embedder) => embedder.tokenizer(),
+            TextEmbedder::Jina(embedder) => embedder.tokenizer(),
+            TextEmbedder::OpenAI(_) | TextEmbedder::Cohere(_) => None,
+        }
+    }
+
+    /// The timer the model records its stages into, if timing is turned on. See
+    /// [`EmbedderBuilder::with_timing`].
+    pub fn timer(&self) -> Option<&StageTimer> {
+        match self {
+            TextEmbedder::Bert(embedder) | TextEmbedder::ColBert(embedder) => embedder.timer(),
+            TextEmbedder::Jina(embedder) => embedder.timer(),
+            _ => None,
+        }
+    }
+
+    /// The time spent per stage since the last report, if the model records it.
+    pub fn timing_report(&self) -> Option<TimingReport> {
+        self.timer().map(StageTimer::take)
+    }
+
     pub async fn embed(
         &self,
         text_batch: &[String],
@@ -109,25 +292,115 @@ impl TextEmbedder {
             TextEmbedder::Jina(embedder) => embedder.embed(text_batch, batch_size),
             TextEmbedder::Bert(embedder) => embedder.embed(text_batch, batch_size),
             TextEmbedder::ColBert(embedder) => embedder.embed(text_batch, batch_size),
+            TextEmbedder::BgeM3(embedder) => embedder.embed(text_batch, batch_size),
         }
     }
 
+    /// Embeds search queries the way the model expects them: BERT models prepend their query
+    /// instruction (see [`PromptTemplate::for_model`](super::prompts::PromptTemplate::for_model)),
+    /// jina-embeddings-v3 uses its `retrieval.query` adapter and Cohere the `search_query` input
+    /// type. Symmetric models embed queries like [`TextEmbedder::embed`].
+    pub async fn embed_queries(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        match self {
+            TextEmbedder::Bert(embedder) | TextEmbedder::ColBert(embedder) => {
+                embedder.embed_query(text_batch, batch_size)
+            }
+            TextEmbedder::Jina(embedder) => embedder.embed_query(text_batch, batch_size),
+            TextEmbedder::Cohere(embedder) => embedder.embed_query(text_batch).await,
+            _ => self.embed(text_batch, batch_size).await,
+        }
+    }
+
+    /// Embeds documents to search over, the counterpart of
+    /// [`embed_queries`](Self::embed_queries): BERT models prepend their document instruction,
+    /// jina-embeddings-v3 uses its `retrieval.passage` adapter and Cohere the `search_document`
+    /// input type.
+    pub async fn embed_documents(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        match self {
+            TextEmbedder::Jina(embedder) => embedder.embed_document(text_batch, batch_size),
+            _ => self.embed(text_batch, batch_size).await,
+        }
+    }
+
+    /// Same as [`embed_queries`](Self::embed_queries).
+    pub async fn embed_query(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.embed_queries(text_batch, batch_size).await
+    }
+
+    /// Loads a Candle text embedding model from the Hugging Face Hub.
     pub fn from_pretrained_hf(
         model: &str,
         model_id: &str,
         revision: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::from_pretrained_hf_with_dtype(model, model_id, revision, None)
+    }
+
+    /// Like [`from_pretrained_hf`](Self::from_pretrained_hf), but with the compute precision.
+    ///
+    /// `dtype` is honored for the BERT and Jina variants, which run in F16/BF16 on GPUs that support
+    /// it. Jina also switches to flash attention for F16/BF16 when built with the `flash-attn` feature.
+    pub fn from_pretrained_hf_with_dtype(
+        model: &str,
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::from_pretrained_hf_on_device(model, model_id, revision, dtype, ComputeDevice::Auto)
+    }
+
+    /// Like [`from_pretrained_hf_with_dtype`](Self::from_pretrained_hf_with_dtype), but loads the
+    /// model on `device`.
+    pub fn from_pretrained_hf_on_device(
+        model: &str,
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+        device: ComputeDevice,
     ) -> Result<Self, anyhow::Error> {
         match model {
-            "jina" | "Jina" => Ok(Self::Jina(Box::new(JinaEmbedder::new(model_id, revision)?))),
+            "jina" | "Jina" => Ok(Self::Jina(Box::new(
+                JinaEmbedder::new_on_device(
+                    model_id,
+                    revision,
+                    dtype,
+                    cfg!(feature = "flash-attn") && matches!(dtype, Some(Dtype::F16 | Dtype::BF16)),
+                    device,
+                )
+                .map_err(model_load_error)?,
+            ))),
 
-            "Bert" | "bert" => Ok(Self::Bert(Box::new(BertEmbedder::new(
-                model_id.to_string(),
-                revision.map(|s| s.to_string()),
-            )?))),
+            "Bert" | "bert" => Ok(Self::Bert(Box::new(
+                BertEmbedder::new_on_device(
+                    model_id.to_string(),
+                    revision.map(|s| s.to_string()),
+                    dtype,
+                    device,
+                )
+                .map_err(model_load_error)?,
+            ))),
             "sparse-bert" | "SparseBert" | "SPARSE-BERT" => Ok(Self::Bert(Box::new(
-                SparseBertEmbedder::new(model_id.to_string(), revision.map(|s| s.to_string()))?,
+                SparseBertEmbedder::new_on_device(
+                    model_id.to_string(),
+                    revision.map(|s| s.to_string()),
+                    dtype,
+                    device,
+                )
+                .map_err(model_load_error)?,
             ))),
-            _ => Err(anyhow::anyhow!("Model not supported")),
+            _ => Err(EmbedError::UnsupportedModel(model.to_string()).into()),
         }
     }
 
@@ -138,49 +411,108 @@ impl TextEmbedder {
         model_id: Option<&str>,
         dtype: Option<Dtype>,
         path_in_repo: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::from_pretrained_ort_on_device(
+            model_architecture,
+            model_name,
+            revision,
+            model_id,
+            dtype,
+            path_in_repo,
+            ComputeDevice::Auto,
+        )
+    }
+
+    /// Like [`from_pretrained_ort`](Self::from_pretrained_ort), but runs the model on `device`.
+    pub fn from_pretrained_ort_on_device(
+        model_architecture: &str,
+        model_name: Option<ONNXModel>,
+        revision: Option<&str>,
+        model_id: Option<&str>,
+        dtype: Option<Dtype>,
+        path_in_repo: Option<&str>,
+        device: ComputeDevice,
     ) -> Result<Self, anyhow::Error> {
         if model_name.is_some() {
             match model_architecture {
-                "Bert" | "bert" => Ok(Self::Bert(Box::new(OrtBertEmbedder::new(
-                    model_name,
-                    model_id,
-                    revision,
-                    dtype,
-                    path_in_repo,
-                )?))),
+                "Bert" | "bert" => Ok(Self::Bert(Box::new(
+                    OrtBertEmbedder::new_on_device(
+                        model_name,
+                        model_id,
+                        revision,
+                        dtype,
+                        path_in_repo,
+                        device,
+                    )
+                    .map_err(model_load_error)?,
+                ))),
                 "sparse-bert" | "SparseBert" | "SPARSE-BERT" => Ok(Self::Bert(Box::new(
-                    OrtSparseBertEmbedder::new(model_name, model_id, revision, path_in_repo)?,
+                    OrtSparseBertEmbedder::new_on_device(
+                        model_name,
+                        model_id,
+                        revision,
+                        path_in_repo,
+                        device,
+                    )
+                    .map_err(model_load_error)?,
+                ))),
+                "jina" | "Jina" => Ok(Self::Jina(Box::new(
+                    OrtJinaEmbedder::new_on_device(
+                        model_name,
+                        model_id,
+                        revision,
+                        dtype,
+                        path_in_repo,
+                        device,
+                    )
+                    .map_err(model_load_error)?,
+                ))),
+                "bge-m3" | "BgeM3" => Ok(Self::BgeM3(Box::new(
+                    OrtBgeM3Embedder::new_on_device(
+                        model_name,
+                        model_id,
+                        revision,
+                        path_in_repo,
+                        device,
+                    )
+                    .map_err(model_load_error)?,
                 ))),
-                "jina" | "Jina" => Ok(Self::Jina(Box::new(OrtJinaEmbedder::new(
-                    model_name,
-                    model_id,
-                    revision,
-                    dtype,
-                    path_in_repo,
-                )?))),
 
-                _ => Err(anyhow::anyhow!("Model not supported")),
+                _ => Err(EmbedError::UnsupportedModel(model_architecture.to_string()).into()),
             }
         } else if model_id.is_some() {
             match model_architecture {
                 "colbert" | "Colbert" | "COLBERT" => Ok(Self::ColBert(Box::new(
-                    OrtColbertEmbedder::new(model_id, revision, path_in_repo)?,
+                    OrtColbertEmbedder::new_on_device(model_id, revision, path_in_repo, device)
+                        .map_err(model_load_error)?,
                 ))),
-                "bert" | "Bert" => Ok(Self::Bert(Box::new(OrtBertEmbedder::new(
-                    None,
-                    model_id,
-                    revision,
-                    None,
-                    path_in_repo,
-                )?))),
-                "jina" | "Jina" => Ok(Self::Jina(Box::new(OrtJinaEmbedder::new(
-                    None,
-                    model_id,
-                    revision,
-                    dtype,
-                    path_in_repo,
-                )?))),
-                _ => Err(anyhow::anyhow!("Model not supported")),
+                "bert" | "Bert" => Ok(Self::Bert(Box::new(
+                    OrtBertEmbedder::new_on_device(
+                        None,
+                        model_id,
+                        revision,
+                        None,
+                        path_in_repo,
+                        device,
+                    )
+                    .map_err(model_load_error)?,
+                ))),
+                "jina" | "Jina" => Ok(Self::Jina(Box::new(
+                    OrtJinaEmbedder::new_on_device(
+                        None,
+                        model_id,
+                        revision,
+                        dtype,
+                        path_in_repo,
+                        device,
+                    )
+                    .map_err(model_load_error)?,
+                ))),
+                "bge-m3" | "BgeM3" => Ok(Self::BgeM3(Box::new(
+                    OrtBgeM3Embedder::new_on_device(None, model_id, revision, path_in_repo, device)
+                        .map_err(model_load_error)?,
+                ))),
+                _ => Err(EmbedError::UnsupportedModel(model_architecture.to_string()).into()),
             }
         } else {
             Err(anyhow::anyhow!(
@@ -211,17 +543,34 @@ impl TextEmbedder {
         model: &str,
         model_id: &str,
         api_key: Option<String>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::from_pretrained_cloud_with_client(model, model_id, api_key, None)
+    }
+
+    /// Like [`from_pretrained_cloud`](Self::from_pretrained_cloud), but sends requests with
+    /// `client` when given, e.g. to go through a proxy or trust a corporate CA.
+    pub fn from_pretrained_cloud_with_client(
+        model: &str,
+        model_id: &str,
+        api_key: Option<String>,
+        client: Option<Client>,
     ) -> Result<Self, anyhow::Error> {
         match model {
-            "openai" | "OpenAI" => Ok(Self::OpenAI(OpenAIEmbedder::new(
-                model_id.to_string(),
-                api_key,
-            ))),
-            "cohere" | "Cohere" => Ok(Self::Cohere(CohereEmbedder::new(
-                model_id.to_string(),
-                api_key,
-            ))),
-            _ => Err(anyhow::anyhow!("Model not supported")),
+            "openai" | "OpenAI" => {
+                let embedder = OpenAIEmbedder::new(model_id.to_string(), api_key);
+                Ok(Self::OpenAI(match client {
+                    Some(client) => embedder.with_client(client),
+                    None => embedder,
+                }))
+            }
+            "cohere" | "Cohere" => {
+                let embedder = CohereEmbedder::new(model_id.to_string(), api_key);
+                Ok(Self::Cohere(match client {
+                    Some(client) => embedder.with_client(client),
+                    None => embedder,
+                }))
+            }
+            _ => Err(EmbedError::UnsupportedModel(model.to_string()).into()),
         }
     }
 }
@@ -229,6 +578,8 @@ impl TextEmbedder {
 pub enum VisionEmbedder {
     Clip(ClipEmbedder),
     ColPali(Box<dyn ColPaliEmbed + Send + Sync>),
+    /// Image-only; embedding text with it fails.
+    ResNet(ResNetEmbedder),
 }
 
 impl From<VisionEmbedder> for Embedder {
@@ -256,24 +607,57 @@ impl From<Embedder> for TextEmbedder {
 }
 
 impl VisionEmbedder {
+    /// Loads a Candle vision embedding model from the Hugging Face Hub.
     pub fn from_pretrained_hf(
         model: &str,
         model_id: &str,
         revision: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::from_pretrained_hf_with_dtype(model, model_id, revision, None)
+    }
+
+    /// Like [`from_pretrained_hf`](Self::from_pretrained_hf), but with the compute precision.
+    ///
+    /// `dtype` is honored for CLIP and ResNet, which run in F16/BF16 on GPUs that support it.
+    pub fn from_pretrained_hf_with_dtype(
+        model: &str,
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::from_pretrained_hf_on_device(model, model_id, revision, dtype, ComputeDevice::Auto)
+    }
+
+    /// Like [`from_pretrained_hf_with_dtype`](Self::from_pretrained_hf_with_dtype), but loads the
+    /// model on `device`.
+    pub fn from_pretrained_hf_on_device(
+        model: &str,
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+        device: ComputeDevice,
     ) -> Result<Self, anyhow::Error> {
         match model {
-            "clip" | "Clip" | "CLIP" => Ok(Self::Clip(ClipEmbedder::new(
-                model_id.to_string(),
-                revision,
-            )?)),
-            "colpali" | "ColPali" | "COLPALI" => Ok(Self::ColPali(Box::new(ColPaliEmbedder::new(
-                model_id, revision,
-            )?))),
-            _ => Err(anyhow::anyhow!("Model not supported")),
+            "clip" | "Clip" | "CLIP" => Ok(Self::Clip(
+                ClipEmbedder::new_on_device(model_id.to_string(), revision, dtype, device)
+                    .map_err(model_load_error)?,
+            )),
+            "colpali" | "ColPali" | "COLPALI" => Ok(Self::ColPali(Box::new(
+                ColPaliEmbedder::new_on_device(model_id, revision, device)
+                    .map_err(model_load_error)?,
+            ))),
+            "resnet" | "ResNet" | "RESNET" => Ok(Self::ResNet(
+                ResNetEmbedder::new_on_device(model_id, revision, dtype, device)
+                    .map_err(model_load_error)?,
+            )),
+            _ => Err(EmbedError::UnsupportedModel(model.to_string()).into()),
         }
     }
 }
 
+/// Every embedder is `Send + Sync`, so a single `Arc<Embedder>` can be shared across threads and
+/// tokio tasks and `embed` can be called concurrently. Candle and ONNX models run their forward
+/// passes with shared references; ColPali is the exception and serializes calls on its model.
 pub enum Embedder {
     Text(TextEmbedder),
     Vision(VisionEmbedder),
@@ -291,25 +675,128 @@ impl Embedder {
         }
     }
 
+    /// See [`TextEmbedder::truncation`].
+    pub fn truncation(&self) -> Option<(TruncationPolicy, usize)> {
+        match self {
+            Self::Text(embedder) => embedder.truncation(),
+            Self::Vision(_) => None,
+        }
+    }
+
+    /// See [`TextEmbedder::tokenizer`].
+    pub fn tokenizer(&self) -> Option<&Tokenizer> {
+        match self {
+            Self::Text(embedder) => embedder.tokenizer(),
+            Self::Vision(_) => None,
+        }
+    }
+
+    /// See [`TextEmbedder::timing_report`].
+    pub fn timing_report(&self) -> Option<TimingReport> {
+        match self {
+            Self::Text(embedder) => embedder.timing_report(),
+            Self::Vision(_) => None,
+        }
+    }
+
+    /// Embeds search queries for retrieval, applying the prefixes, instructions or task adapters the
+    /// model expects for queries. See [`TextEmbedder::embed_queries`].
+    pub async fn embed_queries(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        match self {
+            Self::Text(embedder) => embedder.embed_queries(text_batch, batch_size).await,
+            Self::Vision(embedder) => embedder.embed(text_batch, batch_size),
+        }
+    }
+
+    /// Embeds documents for retrieval, the counterpart of [`embed_queries`](Self::embed_queries).
+    /// See [`TextEmbedder::embed_documents`].
+    pub async fn embed_documents(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        match self {
+            Self::Text(embedder) => embedder.embed_documents(text_batch, batch_size).await,
+            Self::Vision(embedder) => embedder.embed(text_batch, batch_size),
+        }
+    }
+
+    /// Same as [`embed_queries`](Self::embed_queries).
+    pub async fn embed_query(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.embed_queries(text_batch, batch_size).await
+    }
+
+    /// Runs a dummy batch of `target_batch` texts of about `target_seq_len` tokens, so kernel
+    /// selection and memory allocation happen at load time instead of on the first real request.
+    /// Warm up with the largest batch expected, since allocators reuse the buffers for smaller
+    /// ones. Cloud embedders are skipped, as every request is billed.
+    pub async fn warmup(
+        &self,
+        target_batch: usize,
+        target_seq_len: usize,
+    ) -> Result<Duration, anyhow::Error> {
+        let start = Instant::now();
+        if matches!(
+            self,
+            Self::Text(TextEmbedder::OpenAI(_) | TextEmbedder::Cohere(_))
+        ) {
+            return Ok(start.elapsed());
+        }
+        // Common words are a single token in every tokenizer; longer inputs are truncated.
+        let text = vec!["the"; target_seq_len.max(1)].join(" ");
+        let texts = vec![text; target_batch.max(1)];
+        self.embed(&texts, Some(texts.len())).await?;
+        Ok(start.elapsed())
+    }
+
+    /// Returns a builder for loading `model` with more options than the `from_pretrained_*`
+    /// functions, such as the device to load it on.
+    pub fn builder(model: &str) -> EmbedderBuilder {
+        EmbedderBuilder::new(model)
+    }
+
     pub fn from_pretrained_hf(
         model: &str,
         model_id: &str,
         revision: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::from_pretrained_hf_with_dtype(model, model_id, revision, None)
+    }
+
+    /// Like [`from_pretrained_hf`](Self::from_pretrained_hf), but with the compute precision.
+    /// `Dtype::F16` and `Dtype::BF16` are honored for BERT, Jina, CLIP and ResNet on GPUs that
+    /// support them; every other model runs in F32.
+    pub fn from_pretrained_hf_with_dtype(
+        model: &str,
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
     ) -> Result<Self, anyhow::Error> {
         match model {
-            "clip" | "Clip" | "CLIP" => Ok(Self::Vision(VisionEmbedder::from_pretrained_hf(
-                model, model_id, revision,
-            )?)),
+            "clip" | "Clip" | "CLIP" => Ok(Self::Vision(
+                VisionEmbedder::from_pretrained_hf_with_dtype(model, model_id, revision, dtype)?,
+            )),
             "colpali" | "ColPali" | "COLPALI" => Ok(Self::Vision(
-                VisionEmbedder::from_pretrained_hf(model, model_id, revision)?,
+                VisionEmbedder::from_pretrained_hf_with_dtype(model, model_id, revision, dtype)?,
             )),
-            "bert" | "Bert" => Ok(Self::Text(TextEmbedder::from_pretrained_hf(
-                model, model_id, revision,
+            "resnet" | "ResNet" | "RESNET" => Ok(Self::Vision(
+                VisionEmbedder::from_pretrained_hf_with_dtype(model, model_id, revision, dtype)?,
+            )),
+            "bert" | "Bert" => Ok(Self::Text(TextEmbedder::from_pretrained_hf_with_dtype(
+                model, model_id, revision, dtype,
             )?)),
-            "jina" | "Jina" => Ok(Self::Text(TextEmbedder::from_pretrained_hf(
-                model, model_id, revision,
+            "jina" | "Jina" => Ok(Self::Text(TextEmbedder::from_pretrained_hf_with_dtype(
+                model, model_id, revision, dtype,
             )?)),
-            _ => Err(anyhow::anyhow!("Model not supported")),
+            _ => Err(EmbedError::UnsupportedModel(model.to_string()).into()),
         }
     }
 
@@ -325,10 +812,27 @@ impl Embedder {
             "cohere" | "Cohere" => Ok(Self::Text(TextEmbedder::from_pretrained_cloud(
                 model, model_id, api_key,
             )?)),
-            _ => Err(anyhow::anyhow!("Model not supported")),
+            _ => Err(EmbedError::UnsupportedModel(model.to_string()).into()),
         }
     }
 
+    /// Downloads the files of a Hub model into the local cache without loading it, so caches can be
+    /// warmed during deploys instead of on the first request. Partial downloads are resumed.
+    ///
+    /// `files` selects the files to fetch; by default the configs, tokenizer and weights used by
+    /// [`Embedder::from_pretrained_hf`] are fetched. The report holds the total size of the files and
+    /// how much of it had to be downloaded.
+    pub fn prefetch(
+        model_id: &str,
+        revision: Option<&str>,
+        files: Option<&[&str]>,
+    ) -> Result<PrefetchReport, anyhow::Error> {
+        hub::prefetch(model_id, revision, files)
+    }
+
+    /// Loads an ONNX text embedding model, either a known `model_name` or any `model_id` with
+    /// `path_in_repo` pointing at the `.onnx` file. `model_id` can also be a local directory with
+    /// the tokenizer files; `path_in_repo` is then relative to it or an absolute path.
     pub fn from_pretrained_onnx(
         model_architecture: &str,
         model_name: Option<ONNXModel>,
@@ -348,6 +852,341 @@ impl Embedder {
     }
 }
 
+/// Loads an [`Embedder`] like [`Embedder::from_pretrained_hf`] or
+/// [`Embedder::from_pretrained_onnx`], and can place it on a specific device, e.g. to spread
+/// models over several GPUs or keep one on the CPU.
+///
+/// ```no_run
+/// use embed_anything::embeddings::embed::Embedder;
+/// use embed_anything::embeddings::ComputeDevice;
+///
+/// let embedder = Embedder::builder("bert")
+///     .with_model_id("sentence-transformers/all-MiniLM-L6-v2")
+///     .with_device(ComputeDevice::Cuda(1))
+///     .build_hf()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct EmbedderBuilder {
+    model: String,
+    model_id: Option<String>,
+    onnx_model: Option<ONNXModel>,
+    revision: Option<String>,
+    dtype: Option<Dtype>,
+    path_in_repo: Option<String>,
+    device: ComputeDevice,
+    api_key: Option<String>,
+    http_client: Option<Client>,
+    http_config: Option<HttpClientConfig>,
+    request_hook: Option<Arc<dyn RequestHook>>,
+    secret_provider: Option<Arc<dyn SecretProvider>>,
+    truncation: Option<TruncationPolicy>,
+    timing: bool,
+    ort_profiling: Option<PathBuf>,
+    token_pooling: Option<usize>,
+    flash_attn: Option<bool>,
+}
+
+impl EmbedderBuilder {
+    /// `model` is the architecture, as passed to the `from_pretrained_*` functions.
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            model_id: None,
+            onnx_model: None,
+            revision: None,
+            dtype: None,
+            path_in_repo: None,
+            device: ComputeDevice::Auto,
+            api_key: None,
+            http_client: None,
+            http_config: None,
+            request_hook: None,
+            secret_provider: None,
+            truncation: None,
+            timing: false,
+            ort_profiling: None,
+            token_pooling: None,
+            flash_attn: None,
+        }
+    }
+
+    pub fn with_model_id(mut self, model_id: &str) -> Self {
+        self.model_id = Some(model_id.to_string());
+        self
+    }
+
+    /// One of the known ONNX models, used by [`build_onnx`](Self::build_onnx).
+    pub fn with_onnx_model(mut self, onnx_model: ONNXModel) -> Self {
+        self.onnx_model = Some(onnx_model);
+        self
+    }
+
+    pub fn with_revision(mut self, revision: &str) -> Self {
+        self.revision = Some(revision.to_string());
+        self
+    }
+
+    pub fn with_dtype(mut self, dtype: Dtype) -> Self {
+        self.dtype = Some(dtype);
+        self
+    }
+
+    /// The `.onnx` file in the repository, used by [`build_onnx`](Self::build_onnx).
+    pub fn with_path_in_repo(mut self, path_in_repo: &str) -> Self {
+        self.path_in_repo = Some(path_in_repo.to_string());
+        self
+    }
+
+    /// The device to load the model on. Default is [`ComputeDevice::Auto`].
+    pub fn with_device(mut self, device: ComputeDevice) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// The API key of a cloud model, used by [`build_cloud`](Self::build_cloud). By default it is
+    /// read from the provider's environment variable.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Where [`build_cloud`](Self::build_cloud) looks up the API key when none is given with
+    /// [`with_api_key`](Self::with_api_key), e.g. a vault instead of the environment.
+    pub fn with_secret_provider(mut self, secrets: Arc<dyn SecretProvider>) -> Self {
+        self.secret_provider = Some(secrets);
+        self
+    }
+
+    /// The client a cloud model sends requests with, used by [`build_cloud`](Self::build_cloud).
+    /// Takes precedence over [`with_http_config`](Self::with_http_config).
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Proxy, CA and timeout settings for the client of a cloud model, used by
+    /// [`build_cloud`](Self::build_cloud).
+    pub fn with_http_config(mut self, config: HttpClientConfig) -> Self {
+        self.http_config = Some(config);
+        self
+    }
+
+    /// What a cloud model does with inputs over its token limit, used by
+    /// [`build_cloud`](Self::build_cloud).
+    pub fn with_truncation(mut self, policy: TruncationPolicy) -> Self {
+        self.truncation = Some(policy);
+        self
+    }
+
+    /// Observes the requests of a cloud model, used by [`build_cloud`](Self::build_cloud).
+    pub fn with_request_hook(mut self, hook: Arc<dyn RequestHook>) -> Self {
+        self.request_hook = Some(hook);
+        self
+    }
+
+    /// Records the time spent tokenizing, running, pooling and normalizing, read with
+    /// [`Embedder::timing_report`]. Supported by the BERT models of [`build_onnx`](Self::build_onnx)
+    /// and the BERT and Jina models of [`build_hf`](Self::build_hf).
+    pub fn with_timing(mut self, timing: bool) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Turns on ONNX Runtime profiling for the BERT models of [`build_onnx`](Self::build_onnx).
+    /// ORT writes a per-operator trace to a JSON file starting with `prefix` when the embedder is
+    /// dropped.
+    pub fn with_ort_profiling(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.ort_profiling = Some(prefix.into());
+        self
+    }
+
+    /// Pools the image and page embeddings of ColPali to at most `max_vectors` vectors each, see
+    /// [`pool_tokens`](super::local::token_pooling::pool_tokens). Used by
+    /// [`build_hf`](Self::build_hf).
+    pub fn with_token_pooling(mut self, max_vectors: usize) -> Self {
+        self.token_pooling = Some(max_vectors);
+        self
+    }
+
+    /// Runs the attention of the Candle Jina models of [`build_hf`](Self::build_hf) with the fused
+    /// flash-attn kernel. It needs the `flash-attn` feature, a CUDA device and F16/BF16, and falls
+    /// back to the regular attention otherwise. By default it is on for F16/BF16 when the feature
+    /// is enabled. The other models have no flash attention path, so turning it on for them fails.
+    pub fn with_flash_attn(mut self, flash_attn: bool) -> Self {
+        self.flash_attn = Some(flash_attn);
+        self
+    }
+
+    fn check_flash_attn(&self, supported: bool) -> Result<(), anyhow::Error> {
+        if self.flash_attn == Some(true) && !supported {
+            return Err(EmbedError::InvalidConfig(format!(
+                "flash attention is only supported for the Candle Jina models, not {}",
+                self.model
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Loads a Candle model from the Hugging Face Hub, like [`Embedder::from_pretrained_hf`].
+    pub fn build_hf(self) -> Result<Embedder, anyhow::Error> {
+        let model_id = self
+            .model_id
+            .as_deref()
+            .ok_or_else(|| anyhow!("Please provide a model_id"))?;
+        let revision = self.revision.as_deref();
+        self.check_flash_attn(matches!(self.model.as_str(), "jina" | "Jina"))?;
+        match self.model.as_str() {
+            "bert" | "Bert" if self.timing => {
+                let embedder = BertEmbedder::new_on_device(
+                    model_id.to_string(),
+                    revision.map(str::to_string),
+                    self.dtype,
+                    self.device,
+                )
+                .map_err(model_load_error)?;
+                Ok(Embedder::Text(TextEmbedder::Bert(Box::new(
+                    embedder.with_timing(),
+                ))))
+            }
+            "jina" | "Jina" if self.timing || self.flash_attn.is_some() => {
+                let flash_attn = self.flash_attn.unwrap_or(
+                    cfg!(feature = "flash-attn")
+                        && matches!(self.dtype, Some(Dtype::F16 | Dtype::BF16)),
+                );
+                let mut embedder = JinaEmbedder::new_on_device(
+                    model_id,
+                    revision,
+                    self.dtype,
+                    flash_attn,
+                    self.device,
+                )
+                .map_err(model_load_error)?;
+                if self.timing {
+                    embedder = embedder.with_timing();
+                }
+                Ok(Embedder::Text(TextEmbedder::Jina(Box::new(embedder))))
+            }
+            "colpali" | "ColPali" | "COLPALI" if self.token_pooling.is_some() => {
+                let embedder = ColPaliEmbedder::new_on_device(model_id, revision, self.device)
+                    .map_err(model_load_error)?;
+                Ok(Embedder::Vision(VisionEmbedder::ColPali(Box::new(
+                    TokenPooledColPali::new(embedder, self.token_pooling.unwrap_or_default()),
+                ))))
+            }
+            "clip" | "Clip" | "CLIP" | "colpali" | "ColPali" | "COLPALI" | "resnet" | "ResNet"
+            | "RESNET" => Ok(Embedder::Vision(
+                VisionEmbedder::from_pretrained_hf_on_device(
+                    &self.model,
+                    model_id,
+                    revision,
+                    self.dtype,
+                    self.device,
+                )?,
+            )),
+            _ => Ok(Embedder::Text(TextEmbedder::from_pretrained_hf_on_device(
+                &self.model,
+                model_id,
+                revision,
+                self.dtype,
+                self.device,
+            )?)),
+        }
+    }
+
+    /// Loads an ONNX text embedding model, like [`Embedder::from_pretrained_onnx`].
+    pub fn build_onnx(self) -> Result<Embedder, anyhow::Error> {
+        self.check_flash_attn(false)?;
+        if matches!(self.model.as_str(), "bert" | "Bert")
+            && (self.onnx_model.is_some() || self.model_id.is_some())
+            && (self.timing || self.ort_profiling.is_some())
+        {
+            // Like `from_pretrained_ort_on_device`, the dtype only applies to a known ONNXModel.
+            let dtype = self.onnx_model.and(self.dtype);
+            let mut embedder = OrtBertEmbedder::new_on_device(
+                self.onnx_model,
+                self.model_id.as_deref(),
+                self.revision.as_deref(),
+                dtype,
+                self.path_in_repo.as_deref(),
+                self.device,
+            )
+            .map_err(model_load_error)?;
+            if self.timing {
+                embedder = embedder.with_timing();
+            }
+            if let Some(prefix) = &self.ort_profiling {
+                embedder = embedder.with_profiling(prefix)?;
+            }
+            return Ok(Embedder::Text(TextEmbedder::Bert(Box::new(embedder))));
+        }
+        Ok(Embedder::Text(TextEmbedder::from_pretrained_ort_on_device(
+            &self.model,
+            self.onnx_model,
+            self.revision.as_deref(),
+            self.model_id.as_deref(),
+            self.dtype,
+            self.path_in_repo.as_deref(),
+            self.device,
+        )?))
+    }
+
+    /// Creates a cloud embedder, like [`Embedder::from_pretrained_cloud`].
+    pub fn build_cloud(self) -> Result<Embedder, anyhow::Error> {
+        self.check_flash_attn(false)?;
+        let model_id = self
+            .model_id
+            .as_deref()
+            .ok_or_else(|| anyhow!("Please provide a model_id"))?;
+        let client = match (self.http_client, &self.http_config) {
+            (Some(client), _) => Some(client),
+            (None, Some(config)) => Some(config.build()?),
+            (None, None) => None,
+        };
+        let api_key = match (self.api_key, &self.secret_provider) {
+            (Some(api_key), _) => Some(api_key),
+            (None, Some(secrets)) => {
+                let name = match self.model.as_str() {
+                    "openai" | "OpenAI" => OpenAIEmbedder::API_KEY_NAME,
+                    "cohere" | "Cohere" => CohereEmbedder::API_KEY_NAME,
+                    _ => return Err(EmbedError::UnsupportedModel(self.model.clone()).into()),
+                };
+                Some(secrets.resolve(name)?)
+            }
+            (None, None) => None,
+        };
+        let embedder = TextEmbedder::from_pretrained_cloud_with_client(
+            &self.model,
+            model_id,
+            api_key,
+            client,
+        )?;
+        let embedder = match embedder {
+            TextEmbedder::OpenAI(mut embedder) => {
+                if let Some(hook) = self.request_hook {
+                    embedder = embedder.with_hook(hook);
+                }
+                if let Some(policy) = self.truncation {
+                    embedder = embedder.with_truncation(policy);
+                }
+                TextEmbedder::OpenAI(embedder)
+            }
+            TextEmbedder::Cohere(mut embedder) => {
+                if let Some(hook) = self.request_hook {
+                    embedder = embedder.with_hook(hook);
+                }
+                if let Some(policy) = self.truncation {
+                    embedder = embedder.with_truncation(policy);
+                }
+                TextEmbedder::Cohere(embedder)
+            }
+            embedder => embedder,
+        };
+        Ok(Embedder::Text(embedder))
+    }
+}
+
 impl EmbedImage for Embedder {
     fn embed_image<T: AsRef<std::path::Path>>(
         &self,
@@ -356,7 +1195,10 @@ impl EmbedImage for Embedder {
     ) -> anyhow::Result<EmbedData> {
         match self {
             Self::Vision(embedder) => embedder.embed_image(image_path, metadata),
-            _ => Err(anyhow::anyhow!("Model not supported for vision embedding")),
+            _ => Err(EmbedError::UnsupportedModel(
+                "text model used for vision embedding".to_string(),
+            )
+            .into()),
         }
     }
 
@@ -366,7 +1208,34 @@ impl EmbedImage for Embedder {
     ) -> anyhow::Result<Vec<EmbedData>> {
         match self {
             Self::Vision(embedder) => embedder.embed_image_batch(image_paths),
-            _ => Err(anyhow::anyhow!("Model not supported for vision embedding")),
+            _ => Err(EmbedError::UnsupportedModel(
+                "text model used for vision embedding".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    fn embed_image_from(
+        &self,
+        image: &DynamicImage,
+        metadata: Option<HashMap<String, String>>,
+    ) -> anyhow::Result<EmbedData> {
+        match self {
+            Self::Vision(embedder) => embedder.embed_image_from(image, metadata),
+            _ => Err(EmbedError::UnsupportedModel(
+                "text model used for vision embedding".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        match self {
+            Self::Vision(embedder) => embedder.embed_image_batch_from(images),
+            _ => Err(EmbedError::UnsupportedModel(
+                "text model used for vision embedding".to_string(),
+            )
+            .into()),
         }
     }
 }
@@ -388,6 +1257,9 @@ impl TextEmbed for VisionEmbedder {
         match self {
             Self::Clip(embedder) => embedder.embed(text_batch, batch_size),
             Self::ColPali(embedder) => embedder.embed(text_batch, batch_size),
+            Self::ResNet(_) => {
+                Err(EmbedError::UnsupportedModel("ResNet embeds images only".to_string()).into())
+            }
         }
     }
 }
@@ -402,6 +1274,15 @@ pub trait EmbedImage {
         &self,
         image_paths: &[T],
     ) -> anyhow::Result<Vec<EmbedData>>;
+    /// Embeds an image that is already decoded, such as a video frame or a screenshot, without
+    /// writing it to disk.
+    fn embed_image_from(
+        &self,
+        image: &DynamicImage,
+        metadata: Option<HashMap<String, String>>,
+    ) -> anyhow::Result<EmbedData>;
+    /// Embeds decoded images. The embeddings are in the order of `images` and carry no metadata.
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>>;
 }
 
 impl EmbedImage for VisionEmbedder {
@@ -415,6 +1296,7 @@ impl EmbedImage for VisionEmbedder {
             Self::ColPali(embedder) => {
                 embedder.embed_image(PathBuf::from(image_path.as_ref()), metadata)
             }
+            Self::ResNet(embedder) => embedder.embed_image(image_path, metadata),
         }
     }
 
@@ -430,6 +1312,293 @@ impl EmbedImage for VisionEmbedder {
                     .map(|p| PathBuf::from(p.as_ref()))
                     .collect::<Vec<_>>(),
             ),
+            Self::ResNet(embedder) => embedder.embed_image_batch(image_paths),
+        }
+    }
+
+    fn embed_image_from(
+        &self,
+        image: &DynamicImage,
+        metadata: Option<HashMap<String, String>>,
+    ) -> anyhow::Result<EmbedData> {
+        match self {
+            Self::Clip(embedder) => embedder.embed_image_from(image, metadata),
+            Self::ColPali(embedder) => embedder.embed_image_from(image, metadata),
+            Self::ResNet(embedder) => embedder.embed_image_from(image, metadata),
         }
     }
+
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        match self {
+            Self::Clip(embedder) => embedder.embed_image_batch_from(images),
+            Self::ColPali(embedder) => embedder.embed_image_batch_from(images),
+            Self::ResNet(embedder) => embedder.embed_image_batch_from(images),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::cloud::hooks::TracingRequestHook;
+    use crate::embeddings::cloud::secrets::CallbackSecretProvider;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_embedders_are_send_sync() {
+        assert_send_sync::<Embedder>();
+        assert_send_sync::<TextEmbedder>();
+        assert_send_sync::<VisionEmbedder>();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_embed() {
+        let embedder = Arc::new(
+            Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)
+                .unwrap(),
+        );
+        let text_batch = vec!["Hello, world!".to_string(), "How are you?".to_string()];
+        let expected = embedder
+            .embed(&text_batch, None)
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| e.to_dense().unwrap())
+            .collect::<Vec<_>>();
+
+        let handles = (0..4)
+            .map(|_| {
+                let embedder = embedder.clone();
+                let text_batch = text_batch.clone();
+                tokio::spawn(async move {
+                    crate::embed_query(text_batch, &embedder, None)
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            let embeddings = handle
+                .await
+                .unwrap()
+                .iter()
+                .map(|e| e.embedding.to_dense().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(embeddings.len(), expected.len());
+            for (a, b) in embeddings.iter().zip(expected.iter()) {
+                for (x, y) in a.iter().zip(b.iter()) {
+                    assert!((x - y).abs() < 1e-5);
+                }
+            }
+        }
+    }
+    #[cfg(feature = "metal")]
+    #[tokio::test]
+    async fn test_metal_bert_and_clip() {
+        let text_batch = vec!["Hello, world!".to_string(), "How are you?".to_string()];
+        let unit_norm = |embedding: &EmbeddingResult| {
+            let norm = embedding
+                .to_dense()
+                .unwrap()
+                .iter()
+                .map(|x| x * x)
+                .sum::<f32>()
+                .sqrt();
+            (norm - 1.0).abs() < 1e-3
+        };
+
+        let bert =
+            Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)
+                .unwrap();
+        let embeddings = bert.embed(&text_batch, None).await.unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert!(embeddings.iter().all(unit_norm));
+
+        let clip =
+            Embedder::from_pretrained_hf("clip", "openai/clip-vit-base-patch32", None).unwrap();
+        assert_eq!(clip.embed(&text_batch, None).await.unwrap().len(), 2);
+        let Embedder::Vision(clip) = clip else {
+            panic!("CLIP is a vision embedder");
+        };
+        let images = clip
+            .embed_image_batch(&["test_files/clip/cat1.jpg", "test_files/clip/cat2.jpeg"])
+            .unwrap();
+        assert_eq!(images.len(), 2);
+    }
+
+    #[test]
+    fn test_builder_on_cpu() {
+        let embedder = Embedder::builder("bert")
+            .with_model_id("sentence-transformers/all-MiniLM-L6-v2")
+            .with_device(ComputeDevice::Cpu)
+            .build_hf()
+            .unwrap();
+        let Embedder::Text(TextEmbedder::Bert(bert)) = &embedder else {
+            panic!("expected a BERT embedder");
+        };
+        assert_eq!(bert.embed(&["Hello".to_string()], None).unwrap().len(), 1);
+
+        assert!(Embedder::builder("bert").build_hf().is_err());
+        assert!(Embedder::builder("bert")
+            .with_model_id("sentence-transformers/all-MiniLM-L6-v2")
+            .with_flash_attn(true)
+            .build_hf()
+            .is_err());
+        if !cfg!(feature = "cuda") {
+            assert!(Embedder::builder("bert")
+                .with_model_id("sentence-transformers/all-MiniLM-L6-v2")
+                .with_device(ComputeDevice::Cuda(0))
+                .build_hf()
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embed_queries_and_documents() {
+        let embedder =
+            Embedder::from_pretrained_hf("bert", "BAAI/bge-small-en-v1.5", None).unwrap();
+        let texts = vec!["What is the capital of France?".to_string()];
+        let queries = embedder.embed_queries(&texts, None).await.unwrap();
+        let documents = embedder.embed_documents(&texts, None).await.unwrap();
+        let plain = embedder.embed(&texts, None).await.unwrap();
+
+        // BGE prepends an instruction to queries only.
+        assert_ne!(
+            queries[0].to_dense().unwrap(),
+            documents[0].to_dense().unwrap()
+        );
+        assert_eq!(
+            documents[0].to_dense().unwrap(),
+            plain[0].to_dense().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_embed_data_json() {
+        let metadata = HashMap::from([
+            ("file_name".to_string(), "a.txt".to_string()),
+            ("chunk".to_string(), "0".to_string()),
+        ]);
+        let data = EmbedData::new(
+            EmbeddingResult::DenseVector(vec![0.5, -1.0]),
+            Some("hello".to_string()),
+            Some(metadata),
+        );
+        let json = data.to_json().unwrap();
+        assert_eq!(
+            json,
+            r#"{"schema_version":1,"embedding":{"type":"dense","values":[0.5,-1.0]},"text":"hello","metadata":{"chunk":"0","file_name":"a.txt"}}"#
+        );
+        let parsed = EmbedData::from_json(&json).unwrap();
+        assert_eq!(parsed.embedding.to_dense().unwrap(), vec![0.5, -1.0]);
+        assert_eq!(parsed.metadata, data.metadata);
+
+        let multi: EmbedData =
+            serde_json::from_str(r#"{"embedding":{"type":"multi_vector","values":[[1.0],[2.0]]}}"#)
+                .unwrap();
+        assert_eq!(multi.embedding.to_multi_vector().unwrap().len(), 2);
+        assert!(multi.text.is_none());
+
+        let newer = r#"{"schema_version":2,"embedding":{"type":"dense","values":[]}}"#;
+        assert!(serde_json::from_str::<EmbedData>(newer).is_err());
+    }
+
+    #[test]
+    fn test_chunk_id() {
+        let metadata = HashMap::from([("file_name".to_string(), "/docs/a.txt".to_string())]);
+        let data = |text: &str, metadata: &HashMap<String, String>| {
+            EmbedData::new(
+                EmbeddingResult::DenseVector(vec![0.0]),
+                Some(text.to_string()),
+                Some(metadata.clone()),
+            )
+        };
+        let id = data("Hello  world\n", &metadata).id();
+        assert_eq!(id, chunk_id("/docs/a.txt", "Hello world"));
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "8");
+
+        let mut other = metadata.clone();
+        other.insert("chunk".to_string(), "3".to_string());
+        assert_eq!(data("Hello world", &other).id(), id);
+        other.insert("file_name".to_string(), "/docs/b.txt".to_string());
+        assert_ne!(data("Hello world", &other).id(), id);
+        assert_ne!(data("Hello there", &metadata).id(), id);
+    }
+
+    #[test]
+    fn test_builder_cloud() {
+        let config = HttpClientConfig::default()
+            .with_proxy("http://localhost:3128")
+            .with_timeout(Duration::from_secs(10));
+        let embedder = Embedder::builder("cohere")
+            .with_model_id("embed-english-v3.0")
+            .with_api_key("test")
+            .with_http_config(config)
+            .build_cloud()
+            .unwrap();
+        assert!(matches!(embedder, Embedder::Text(TextEmbedder::Cohere(_))));
+
+        let hook: Arc<dyn RequestHook> = Arc::new(TracingRequestHook);
+        let embedder = Embedder::builder("openai")
+            .with_model_id("text-embedding-3-small")
+            .with_api_key("test")
+            .with_request_hook(hook)
+            .build_cloud()
+            .unwrap();
+        assert!(matches!(embedder, Embedder::Text(TextEmbedder::OpenAI(_))));
+
+        let secrets: Arc<dyn SecretProvider> =
+            Arc::new(CallbackSecretProvider::new(|name: &str| match name {
+                "CO_API_KEY" => Ok("co-from-vault".to_string()),
+                _ => Err(EmbedError::InvalidConfig(name.to_string())),
+            }));
+        let builder = Embedder::builder("cohere")
+            .with_model_id("embed-english-v3.0")
+            .with_secret_provider(secrets.clone());
+        assert!(builder.build_cloud().is_ok());
+        assert!(Embedder::builder("openai")
+            .with_model_id("text-embedding-3-small")
+            .with_secret_provider(secrets)
+            .build_cloud()
+            .is_err());
+
+        let embedder = Embedder::builder("openai")
+            .with_model_id("text-embedding-3-small")
+            .with_api_key("test")
+            .with_truncation(TruncationPolicy::TruncateTail)
+            .build_cloud()
+            .unwrap();
+        assert_eq!(
+            embedder.truncation(),
+            Some((TruncationPolicy::TruncateTail, 8192))
+        );
+
+        let bad_proxy = HttpClientConfig::default().with_proxy("not a url");
+        assert!(Embedder::builder("openai")
+            .with_model_id("text-embedding-3-small")
+            .with_api_key("test")
+            .with_http_config(bad_proxy)
+            .build_cloud()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_warmup() {
+        let embedder =
+            Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)
+                .unwrap();
+        embedder.warmup(4, 512).await.unwrap();
+
+        // Cloud embedders are skipped, so no request is sent with the fake key.
+        let cloud = Embedder::from_pretrained_cloud(
+            "openai",
+            "text-embedding-3-small",
+            Some("sk-test".to_string()),
+        )
+        .unwrap();
+        cloud.warmup(4, 512).await.unwrap();
+    }
 }
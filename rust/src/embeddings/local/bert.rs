@@ -5,22 +5,25 @@ extern crate intel_mkl_src;
 extern crate accelerate_src;
 
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use crate::embeddings::embed::EmbeddingResult;
 use crate::embeddings::local::text_embedding::{get_model_info_by_hf_id, models_map};
+use crate::embeddings::prompts::PromptTemplate;
+use crate::embeddings::timing::{timed, timed_on_device, Stage, StageTimer, TimingReport};
 use crate::embeddings::utils::{
-    get_attention_mask, get_attention_mask_ndarray, get_type_ids_ndarray, tokenize_batch,
-    tokenize_batch_ndarray,
+    attention_mask_ndarray, attention_mask_tensor, encode_batch, ids_ndarray, ids_tensor,
+    length_sorted_batches, type_ids_ndarray, TokenizationCache,
 };
-use crate::embeddings::{normalize_l2, select_device};
-use crate::models::bert::{BertForMaskedLM, BertModel, Config, DTYPE};
+use crate::embeddings::{normalize_l2, select_dtype, ComputeDevice};
+use crate::hub::HubRepo;
+use crate::models::bert::{BertForMaskedLM, BertModel, Config};
 use crate::Dtype;
 use anyhow::Error as E;
 use candle_core::{DType, Device, Tensor};
 use candle_nn::VarBuilder;
-use hf_hub::{api::sync::Api, Repo};
 use ndarray::prelude::*;
-use ort::execution_providers::{CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProvider};
+use ort::execution_providers::{CUDAExecutionProvider, ExecutionProvider};
 use ort::session::builder::GraphOptimizationLevel;
 use ort::session::Session;
 use ort::value::Value;
@@ -28,7 +31,9 @@ use rayon::prelude::*;
 use serde::Deserialize;
 use tokenizers::{AddedToken, PaddingParams, Tokenizer, TruncationParams};
 
+use super::io_binding::{io_binding_available, run_session};
 use super::pooling::{ModelOutput, Pooling};
+use super::providers::{build_session, execution_providers};
 use super::text_embedding::ONNXModel;
 
 pub trait BertEmbed {
@@ -37,6 +42,33 @@ pub trait BertEmbed {
         text_batch: &[String],
         batch_size: Option<usize>,
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error>;
+
+    /// Embeds search queries. Models with a query instruction prepend it; the others embed the
+    /// queries like documents.
+    fn embed_query(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.embed(text_batch, batch_size)
+    }
+
+    /// The tokenizer the model encodes its inputs with, used to chunk text in the model's own
+    /// tokens.
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        None
+    }
+
+    /// The timer the model records its stages into, once timing is turned on.
+    fn timer(&self) -> Option<&StageTimer> {
+        None
+    }
+
+    /// The stage timings recorded since the last call, for models that record them once timing
+    /// is turned on.
+    fn timing_report(&self) -> Option<TimingReport> {
+        self.timer().map(StageTimer::take)
+    }
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct TokenizerConfig {
@@ -62,6 +94,14 @@ pub struct OrtBertEmbedder {
     pub tokenizer: Tokenizer,
     pub model: Session,
     pub pooling: Pooling,
+    pub tokenization_cache: Option<TokenizationCache>,
+    pub prompts: Option<PromptTemplate>,
+    /// Copies the output into pinned host memory through an IoBinding. On by default when ORT can
+    /// use CUDA.
+    pub io_binding: bool,
+    pub timer: Option<StageTimer>,
+    weights_path: PathBuf,
+    device: ComputeDevice,
 }
 
 impl OrtBertEmbedder {
@@ -71,6 +111,25 @@ impl OrtBertEmbedder {
         revision: Option<&str>,
         dtype: Option<Dtype>,
         path_in_repo: Option<&str>,
+    ) -> Result<Self, E> {
+        Self::new_on_device(
+            model_name,
+            model_id,
+            revision,
+            dtype,
+            path_in_repo,
+            ComputeDevice::Auto,
+        )
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_name: Option<ONNXModel>,
+        model_id: Option<&str>,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+        path_in_repo: Option<&str>,
+        device: ComputeDevice,
     ) -> Result<Self, E> {
         let hf_model_id = match model_id {
             Some(id) => id,
@@ -102,18 +161,7 @@ impl OrtBertEmbedder {
         };
 
         let (_, tokenizer_filename, weights_filename, tokenizer_config_filename) = {
-            let api = Api::new().unwrap();
-            let api = match revision {
-                Some(rev) => api.repo(Repo::with_revision(
-                    hf_model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                    rev.to_string(),
-                )),
-                None => api.repo(hf_hub::Repo::new(
-                    hf_model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                )),
-            };
+            let api = HubRepo::new(hf_model_id, revision)?;
             let config = api.get("config.json")?;
             let tokenizer = api.get("tokenizer.json")?;
             let tokenizer_config = api.get("tokenizer_config.json")?;
@@ -127,6 +175,7 @@ impl OrtBertEmbedder {
                 Some(Dtype::BNB4) => format!("{base_path}/model_bnb4.onnx"),
                 Some(Dtype::F32) => format!("{base_path}/model.onnx"),
                 Some(Dtype::QUANTIZED) => format!("{base_path}/model_quantized.onnx"),
+                Some(Dtype::BF16) => format!("{base_path}/model_bf16.onnx"),
                 None => path.to_string(),
             };
             let weights = api.get(model_path.as_str());
@@ -173,75 +222,139 @@ impl OrtBertEmbedder {
         let cuda = CUDAExecutionProvider::default();
 
         if !cuda.is_available()? {
-            eprintln!("CUDAExecutionProvider is not available");
+            tracing::warn!("CUDAExecutionProvider is not available");
         } else {
-            println!("Session is using CUDAExecutionProvider");
+            tracing::info!("Session is using CUDAExecutionProvider");
         }
 
-        let threads = std::thread::available_parallelism().unwrap().get();
-        let model = Session::builder()?
-            .with_execution_providers([
-                CUDAExecutionProvider::default().build(),
-                CoreMLExecutionProvider::default().build(),
-            ])?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(threads)?
-            .commit_from_file(weights_filename)?;
+        let model = build_session(&weights_filename, device, None)?;
 
         Ok(OrtBertEmbedder {
             tokenizer,
             model,
             pooling,
+            tokenization_cache: None,
+            prompts: PromptTemplate::for_model(hf_model_id),
+            io_binding: io_binding_available() && device.allows_cuda(),
+            timer: None,
+            weights_path: weights_filename,
+            device,
         })
     }
+
+    /// Keeps the encodings of the last `capacity` texts so repeated chunks are tokenized once.
+    pub fn with_tokenization_cache(mut self, capacity: usize) -> Self {
+        self.tokenization_cache = Some(TokenizationCache::new(capacity));
+        self
+    }
+
+    /// Replaces the model's default pooling, e.g. with [`Pooling::Weighted`] using IDF or SIF
+    /// weights built from the model's tokenizer.
+    pub fn with_pooling(mut self, pooling: Pooling) -> Self {
+        self.pooling = pooling;
+        self
+    }
+
+    /// Overrides the query and document instructions picked from the model id. `None` embeds the
+    /// texts as they are.
+    pub fn with_prompts(mut self, prompts: Option<PromptTemplate>) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Turns the pinned output copy through an IoBinding on or off, e.g. to compare throughput.
+    pub fn with_io_binding(mut self, enabled: bool) -> Self {
+        self.io_binding = enabled;
+        self
+    }
+
+    /// Records how long tokenization, inference, pooling and normalization take, see
+    /// [`BertEmbed::timing_report`].
+    pub fn with_timing(mut self) -> Self {
+        self.timer = Some(StageTimer::new());
+        self
+    }
+
+    /// Reloads the session with ONNX Runtime profiling, which writes a per-operator trace to a
+    /// file starting with `prefix` when the embedder is dropped. Open it in `chrome://tracing` or
+    /// Perfetto.
+    pub fn with_profiling(mut self, prefix: impl AsRef<Path>) -> Result<Self, E> {
+        self.model = build_session(&self.weights_path, self.device, Some(prefix.as_ref()))?;
+        Ok(self)
+    }
 }
 
-impl BertEmbed for OrtBertEmbedder {
-    fn embed(
+impl OrtBertEmbedder {
+    /// Runs the session on a tokenized batch and returns the last hidden state.
+    fn run(&self, encodings: &[tokenizers::Encoding]) -> Result<Array3<f32>, E> {
+        let input_ids: Array2<i64> = ids_ndarray(encodings)?;
+        let token_type_ids: Array2<i64> = Array2::zeros(input_ids.raw_dim());
+        let attention_mask: Array2<i64> = Array2::ones(input_ids.raw_dim());
+
+        let input_names = self
+            .model
+            .inputs
+            .iter()
+            .map(|input| input.name.as_str())
+            .collect::<Vec<_>>();
+
+        let mut inputs = vec![
+            ("input_ids", Value::from_array(input_ids)?.into_dyn()),
+            (
+                "attention_mask",
+                Value::from_array(attention_mask)?.into_dyn(),
+            ),
+        ];
+        if input_names.iter().any(|&x| x == "token_type_ids") {
+            inputs.push((
+                "token_type_ids",
+                Value::from_array(token_type_ids)?.into_dyn(),
+            ));
+        }
+        Ok(run_session(
+            &self.model,
+            inputs,
+            self.model.outputs.first().unwrap().name.as_str(),
+            self.io_binding,
+        )?
+        .into_dimensionality::<ndarray::Ix3>()?)
+    }
+
+    fn embed_texts(
         &self,
         text_batch: &[String],
         batch_size: Option<usize>,
     ) -> Result<Vec<EmbeddingResult>, E> {
         let batch_size = batch_size.unwrap_or(32);
+        let timer = self.timer.as_ref();
         let encodings = text_batch
             .par_chunks(batch_size)
-            .flat_map(|mini_text_batch| -> Result<Vec<Vec<f32>>, E> {
-                let input_ids: Array2<i64> =
-                    tokenize_batch_ndarray(&self.tokenizer, mini_text_batch)?;
-                let token_type_ids: Array2<i64> = Array2::zeros(input_ids.raw_dim());
-                let attention_mask: Array2<i64> = Array2::ones(input_ids.raw_dim());
-
-                let input_names = self
-                    .model
-                    .inputs
-                    .iter()
-                    .map(|input| input.name.as_str())
-                    .collect::<Vec<_>>();
-
-                let mut inputs =
-                    ort::inputs!["input_ids" => input_ids, "attention_mask" => attention_mask]?;
-                if input_names.iter().any(|&x| x == "token_type_ids") {
-                    inputs.push((
-                        "token_type_ids".into(),
-                        Value::from_array(token_type_ids.clone())?.into(),
-                    ));
+            .map(|mini_text_batch| -> Result<Vec<Vec<f32>>, E> {
+                let encodings = timed(timer, Stage::Tokenize, || {
+                    encode_batch(
+                        &self.tokenizer,
+                        mini_text_batch,
+                        self.tokenization_cache.as_ref(),
+                    )
+                })?;
+                let embeddings = timed(timer, Stage::Inference, || self.run(&encodings))?;
+                let embeddings = timed(timer, Stage::Pooling, || {
+                    self.pooling
+                        .pool_encodings(&ModelOutput::Array(embeddings), &encodings)?
+                        .to_array()
+                })?;
+                let embeddings = timed(timer, Stage::Normalize, || {
+                    let norms = embeddings.mapv(|x| x * x).sum_axis(Axis(1)).mapv(f32::sqrt);
+                    &embeddings / &norms.insert_axis(Axis(1))
+                });
+                if let Some(timer) = timer {
+                    timer.batch(mini_text_batch.len());
                 }
-                let outputs = self.model.run(inputs)?;
-                let embeddings: Array3<f32> = outputs
-                    [self.model.outputs.first().unwrap().name.as_str()]
-                .try_extract_tensor::<f32>()?
-                .to_owned()
-                .into_dimensionality::<ndarray::Ix3>()?;
-                let (_, _, _) = embeddings.dim();
-                let embeddings = self
-                    .pooling
-                    .pool(&ModelOutput::Array(embeddings))?
-                    .to_array()?;
-                let norms = embeddings.mapv(|x| x * x).sum_axis(Axis(1)).mapv(f32::sqrt);
-                let embeddings = &embeddings / &norms.insert_axis(Axis(1));
 
                 Ok(embeddings.outer_iter().map(|row| row.to_vec()).collect())
             })
+            .collect::<Result<Vec<_>, E>>()?
+            .into_iter()
             .flatten()
             .collect::<Vec<_>>();
 
@@ -252,10 +365,46 @@ impl BertEmbed for OrtBertEmbedder {
     }
 }
 
+impl BertEmbed for OrtBertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
+    fn embed(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        match &self.prompts {
+            Some(prompts) => self.embed_texts(&prompts.apply_document(text_batch), batch_size),
+            None => self.embed_texts(text_batch, batch_size),
+        }
+    }
+
+    fn embed_query(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        match &self.prompts {
+            Some(prompts) => self.embed_texts(&prompts.apply_query(text_batch), batch_size),
+            None => self.embed_texts(text_batch, batch_size),
+        }
+    }
+
+    fn timer(&self) -> Option<&StageTimer> {
+        self.timer.as_ref()
+    }
+}
+
 pub struct BertEmbedder {
     pub model: BertModel,
     pub pooling: Pooling,
     pub tokenizer: Tokenizer,
+    pub dtype: DType,
+    pub tokenization_cache: Option<TokenizationCache>,
+    pub prompts: Option<PromptTemplate>,
+    pub timer: Option<StageTimer>,
 }
 
 impl Default for BertEmbedder {
@@ -264,7 +413,28 @@ impl Default for BertEmbedder {
     }
 }
 impl BertEmbedder {
+    /// Loads a BERT model from the Hugging Face Hub in F32.
     pub fn new(model_id: String, revision: Option<String>) -> Result<Self, E> {
+        Self::new_with_dtype(model_id, revision, None)
+    }
+
+    /// Like [`new`](Self::new), but with the compute precision. `Dtype::F16` and `Dtype::BF16` are
+    /// honored on GPUs that support them; otherwise the model runs in F32.
+    pub fn new_with_dtype(
+        model_id: String,
+        revision: Option<String>,
+        dtype: Option<Dtype>,
+    ) -> Result<Self, E> {
+        Self::new_on_device(model_id, revision, dtype, ComputeDevice::Auto)
+    }
+
+    /// Like [`new_with_dtype`](Self::new_with_dtype), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: String,
+        revision: Option<String>,
+        dtype: Option<Dtype>,
+        device: ComputeDevice,
+    ) -> Result<Self, E> {
         let model_info = get_model_info_by_hf_id(&model_id);
         let pooling = match model_info {
             Some(info) => info
@@ -275,14 +445,7 @@ impl BertEmbedder {
         };
 
         let (config_filename, tokenizer_filename, weights_filename) = {
-            let api = Api::new().unwrap();
-            let api = match revision {
-                Some(rev) => api.repo(Repo::with_revision(model_id, hf_hub::RepoType::Model, rev)),
-                None => api.repo(hf_hub::Repo::new(
-                    model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                )),
-            };
+            let api = HubRepo::new(&model_id, revision.as_deref())?;
             let config = api.get("config.json")?;
             let tokenizer = api.get("tokenizer.json")?;
             let weights = match api.get("model.safetensors") {
@@ -319,14 +482,15 @@ impl BertEmbedder {
             .with_truncation(Some(trunc))
             .unwrap();
 
-        println!("Loading weights from {:?}", weights_filename);
-        let device = select_device();
+        tracing::debug!("Loading weights from {:?}", weights_filename);
+        let device = device.candle_device()?;
+        let dtype = select_dtype(dtype, &device);
 
         let vb = if weights_filename.ends_with("model.safetensors") {
-            unsafe { VarBuilder::from_mmaped_safetensors(&[weights_filename], DTYPE, &device)? }
+            unsafe { VarBuilder::from_mmaped_safetensors(&[weights_filename], dtype, &device)? }
         } else {
-            println!("Can't find model.safetensors, loading from pytorch_model.bin");
-            VarBuilder::from_pth(&weights_filename, DTYPE, &device)?
+            tracing::info!("Can't find model.safetensors, loading from pytorch_model.bin");
+            VarBuilder::from_pth(&weights_filename, dtype, &device)?
         };
 
         let model = BertModel::load(vb, &config)?;
@@ -336,48 +500,131 @@ impl BertEmbedder {
             model,
             tokenizer,
             pooling,
+            dtype,
+            tokenization_cache: None,
+            prompts: PromptTemplate::for_model(&model_id),
+            timer: None,
         })
     }
+
+    /// Keeps the encodings of the last `capacity` texts so repeated chunks are tokenized once.
+    pub fn with_tokenization_cache(mut self, capacity: usize) -> Self {
+        self.tokenization_cache = Some(TokenizationCache::new(capacity));
+        self
+    }
+
+    /// Replaces the model's default pooling, e.g. with [`Pooling::Weighted`] using IDF or SIF
+    /// weights built from the model's tokenizer.
+    pub fn with_pooling(mut self, pooling: Pooling) -> Self {
+        self.pooling = pooling;
+        self
+    }
+
+    /// Overrides the query and document instructions picked from the model id. `None` embeds the
+    /// texts as they are.
+    pub fn with_prompts(mut self, prompts: Option<PromptTemplate>) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Records how long tokenization, the forward pass, pooling and normalization take, see
+    /// [`BertEmbed::timing_report`]. GPU kernels run asynchronously, so each stage waits for the
+    /// device to finish, which costs a little throughput.
+    pub fn with_timing(mut self) -> Self {
+        self.timer = Some(StageTimer::new());
+        self
+    }
 }
 
-impl BertEmbed for BertEmbedder {
-    fn embed(
+impl BertEmbedder {
+    fn embed_texts(
         &self,
         text_batch: &[String],
         batch_size: Option<usize>,
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
         let batch_size = batch_size.unwrap_or(32);
-        let mut encodings: Vec<EmbeddingResult> = Vec::new();
+        let mut encodings: Vec<Option<EmbeddingResult>> = vec![None; text_batch.len()];
+        let timer = self.timer.as_ref();
+        let device = &self.model.device;
+
+        // Texts are batched by token length so that short texts are not padded to the length of
+        // a long one, and put back in input order afterwards.
+        let batches = timed(timer, Stage::Tokenize, || {
+            length_sorted_batches(
+                &self.tokenizer,
+                text_batch,
+                batch_size,
+                self.tokenization_cache.as_ref(),
+            )
+        })?;
+        for (indices, batch_tokens) in batches {
+            let embeddings = timed_on_device(timer, Stage::Inference, device, || {
+                let token_ids = ids_tensor(&batch_tokens, device)?;
+                let token_type_ids = token_ids.zeros_like()?;
+                let attention_mask = attention_mask_tensor(&batch_tokens, device)?;
+                Ok(self
+                    .model
+                    .forward(&token_ids, &token_type_ids, Some(&attention_mask))?)
+            })?;
+            let pooled_output = timed_on_device(timer, Stage::Pooling, device, || {
+                Ok(self
+                    .pooling
+                    .pool_encodings(&ModelOutput::Tensor(embeddings), &batch_tokens)?
+                    .to_tensor()?
+                    .to_dtype(DType::F32)?)
+            })?;
+
+            let batch_encodings = timed(timer, Stage::Normalize, || {
+                normalize_l2(&pooled_output)?.to_vec2::<f32>()
+            })?;
+            if let Some(timer) = timer {
+                timer.batch(indices.len());
+            }
 
-        for mini_text_batch in text_batch.chunks(batch_size) {
-            let token_ids =
-                tokenize_batch(&self.tokenizer, mini_text_batch, &self.model.device).unwrap();
-            let token_type_ids = token_ids.zeros_like().unwrap();
-            let embeddings: Tensor = self
-                .model
-                .forward(&token_ids, &token_type_ids, None)
-                .unwrap();
-            let pooled_output = self
-                .pooling
-                .pool(&ModelOutput::Tensor(embeddings.clone()))?
-                .to_tensor()?;
+            for (index, embedding) in indices.into_iter().zip(batch_encodings) {
+                encodings[index] = Some(EmbeddingResult::DenseVector(embedding));
+            }
+        }
+        Ok(encodings.into_iter().flatten().collect())
+    }
+}
 
-            let embeddings = normalize_l2(&pooled_output).unwrap();
-            let batch_encodings = embeddings.to_vec2::<f32>().unwrap();
+impl BertEmbed for BertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
 
-            encodings.extend(
-                batch_encodings
-                    .iter()
-                    .map(|x| EmbeddingResult::DenseVector(x.to_vec())),
-            );
+    fn embed(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        match &self.prompts {
+            Some(prompts) => self.embed_texts(&prompts.apply_document(text_batch), batch_size),
+            None => self.embed_texts(text_batch, batch_size),
         }
-        Ok(encodings)
+    }
+
+    fn embed_query(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        match &self.prompts {
+            Some(prompts) => self.embed_texts(&prompts.apply_query(text_batch), batch_size),
+            None => self.embed_texts(text_batch, batch_size),
+        }
+    }
+
+    fn timer(&self) -> Option<&StageTimer> {
+        self.timer.as_ref()
     }
 }
 
 pub struct OrtSparseBertEmbedder {
     pub tokenizer: Tokenizer,
     pub model: Session,
+    pub tokenization_cache: Option<TokenizationCache>,
 }
 
 impl OrtSparseBertEmbedder {
@@ -386,6 +633,23 @@ impl OrtSparseBertEmbedder {
         model_id: Option<&str>,
         revision: Option<&str>,
         path_in_repo: Option<&str>,
+    ) -> Result<Self, E> {
+        Self::new_on_device(
+            model_name,
+            model_id,
+            revision,
+            path_in_repo,
+            ComputeDevice::Auto,
+        )
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_name: Option<ONNXModel>,
+        model_id: Option<&str>,
+        revision: Option<&str>,
+        path_in_repo: Option<&str>,
+        device: ComputeDevice,
     ) -> Result<Self, E> {
         let hf_model_id = match model_id {
             Some(id) => id,
@@ -408,18 +672,7 @@ impl OrtSparseBertEmbedder {
         };
 
         let (_, tokenizer_filename, weights_filename, tokenizer_config_filename) = {
-            let api = Api::new().unwrap();
-            let api = match revision {
-                Some(rev) => api.repo(Repo::with_revision(
-                    hf_model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                    rev.to_string(),
-                )),
-                None => api.repo(hf_hub::Repo::new(
-                    hf_model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                )),
-            };
+            let api = HubRepo::new(hf_model_id, revision)?;
             let config = api.get("config.json")?;
             let tokenizer = api.get("tokenizer.json")?;
             let tokenizer_config = api.get("tokenizer_config.json")?;
@@ -456,37 +709,49 @@ impl OrtSparseBertEmbedder {
 
         let cuda = CUDAExecutionProvider::default();
         if !cuda.is_available()? {
-            eprintln!("CUDAExecutionProvider is not available");
+            tracing::warn!("CUDAExecutionProvider is not available");
         } else {
-            println!("Session is using CUDAExecutionProvider");
+            tracing::info!("Session is using CUDAExecutionProvider");
         }
 
         let threads = std::thread::available_parallelism().unwrap().get();
         let model = Session::builder()?
-            .with_execution_providers([
-                CUDAExecutionProvider::default().build(),
-                CoreMLExecutionProvider::default().build(),
-            ])?
+            .with_execution_providers(execution_providers(device))?
             .with_optimization_level(GraphOptimizationLevel::Level3)?
             .with_intra_threads(threads)?
             .commit_from_file(weights_filename)?;
 
-        Ok(OrtSparseBertEmbedder { tokenizer, model })
+        Ok(OrtSparseBertEmbedder {
+            tokenizer,
+            model,
+            tokenization_cache: None,
+        })
+    }
+
+    /// Keeps the encodings of the last `capacity` texts so repeated chunks are tokenized once.
+    pub fn with_tokenization_cache(mut self, capacity: usize) -> Self {
+        self.tokenization_cache = Some(TokenizationCache::new(capacity));
+        self
     }
 }
 
 impl BertEmbed for OrtSparseBertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[String],
         batch_size: Option<usize>,
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
         let batch_size = batch_size.unwrap_or(32);
-        let encodings = text_batch.par_chunks(batch_size).flat_map(|mini_text_batch| -> Result<Vec<Vec<f32>>, E> {
-            let token_ids: Array2<i64> = tokenize_batch_ndarray(&self.tokenizer, mini_text_batch)?;
-            let token_type_ids: Array2<i64> = get_type_ids_ndarray(&self.tokenizer, mini_text_batch)?;
-            let attention_mask = get_attention_mask_ndarray(&self.tokenizer, mini_text_batch)?;
-            let outputs = self.model.run(ort::inputs!["input_ids" => token_ids, "input_mask" => attention_mask.clone(), "segment_ids" => token_type_ids]?).unwrap();
+        let encodings = text_batch.par_chunks(batch_size).map(|mini_text_batch| -> Result<Vec<Vec<f32>>, E> {
+            let encodings = encode_batch(&self.tokenizer, mini_text_batch, self.tokenization_cache.as_ref())?;
+            let token_ids: Array2<i64> = ids_ndarray(&encodings)?;
+            let token_type_ids: Array2<i64> = type_ids_ndarray(&encodings)?;
+            let attention_mask = attention_mask_ndarray(&encodings)?;
+            let outputs = self.model.run(ort::inputs!["input_ids" => token_ids, "input_mask" => attention_mask.clone(), "segment_ids" => token_type_ids]?)?;
             let embeddings: Array3<f32> = outputs["output"]
                 .try_extract_tensor::<f32>()?
                 .to_owned()
@@ -497,7 +762,7 @@ impl BertEmbed for OrtSparseBertEmbedder {
             let norms = scores.mapv(|x| x * x).sum_axis(Axis(1)).mapv(f32::sqrt);
             let embeddings = &scores / &norms.insert_axis(Axis(1));
             Ok(embeddings.outer_iter().map(|row| row.to_vec()).collect())
-        }).flatten().collect::<Vec<_>>();
+        }).collect::<Result<Vec<_>, E>>()?.into_iter().flatten().collect::<Vec<_>>();
 
         Ok(encodings
             .iter()
@@ -511,19 +776,33 @@ pub struct SparseBertEmbedder {
     pub model: BertForMaskedLM,
     pub device: Device,
     pub dtype: DType,
+    pub tokenization_cache: Option<TokenizationCache>,
 }
 
 impl SparseBertEmbedder {
     pub fn new(model_id: String, revision: Option<String>) -> Result<Self, E> {
+        Self::new_with_dtype(model_id, revision, None)
+    }
+
+    /// Like [`new`](Self::new), but with the compute precision, as for
+    /// [`BertEmbedder::new_with_dtype`].
+    pub fn new_with_dtype(
+        model_id: String,
+        revision: Option<String>,
+        dtype: Option<Dtype>,
+    ) -> Result<Self, E> {
+        Self::new_on_device(model_id, revision, dtype, ComputeDevice::Auto)
+    }
+
+    /// Like [`new_with_dtype`](Self::new_with_dtype), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: String,
+        revision: Option<String>,
+        dtype: Option<Dtype>,
+        device: ComputeDevice,
+    ) -> Result<Self, E> {
         let (config_filename, tokenizer_filename, weights_filename) = {
-            let api = Api::new().unwrap();
-            let api = match revision {
-                Some(rev) => api.repo(Repo::with_revision(model_id, hf_hub::RepoType::Model, rev)),
-                None => api.repo(hf_hub::Repo::new(
-                    model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                )),
-            };
+            let api = HubRepo::new(&model_id, revision.as_deref())?;
             let config = api.get("config.json")?;
             let tokenizer = api.get("tokenizer.json")?;
             let weights = match api.get("model.safetensors") {
@@ -560,14 +839,15 @@ impl SparseBertEmbedder {
             .with_truncation(Some(trunc))
             .unwrap();
 
-        println!("Loading weights from {:?}", weights_filename);
+        tracing::debug!("Loading weights from {:?}", weights_filename);
 
-        let device = select_device();
+        let device = device.candle_device()?;
+        let dtype = select_dtype(dtype, &device);
         let vb = if weights_filename.ends_with("model.safetensors") {
-            unsafe { VarBuilder::from_mmaped_safetensors(&[weights_filename], DTYPE, &device)? }
+            unsafe { VarBuilder::from_mmaped_safetensors(&[weights_filename], dtype, &device)? }
         } else {
-            println!("Loading weights from pytorch_model.bin");
-            VarBuilder::from_pth(&weights_filename, DTYPE, &device)?
+            tracing::info!("Loading weights from pytorch_model.bin");
+            VarBuilder::from_pth(&weights_filename, dtype, &device)?
         };
         let model = BertForMaskedLM::load(vb, &config)?;
         let tokenizer = tokenizer;
@@ -576,12 +856,23 @@ impl SparseBertEmbedder {
             model,
             tokenizer,
             device,
-            dtype: DTYPE,
+            dtype,
+            tokenization_cache: None,
         })
     }
+
+    /// Keeps the encodings of the last `capacity` texts so repeated chunks are tokenized once.
+    pub fn with_tokenization_cache(mut self, capacity: usize) -> Self {
+        self.tokenization_cache = Some(TokenizationCache::new(capacity));
+        self
+    }
 }
 
 impl BertEmbed for SparseBertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[String],
@@ -591,14 +882,15 @@ impl BertEmbed for SparseBertEmbedder {
         let mut encodings: Vec<EmbeddingResult> = Vec::new();
 
         for mini_text_batch in text_batch.chunks(batch_size) {
-            let token_ids = tokenize_batch(&self.tokenizer, mini_text_batch, &self.device).unwrap();
-            let token_type_ids = token_ids.zeros_like().unwrap();
-            let embeddings: Tensor = self
-                .model
-                .forward(&token_ids, &token_type_ids, None)
-                .unwrap();
-            let attention_mask =
-                get_attention_mask(&self.tokenizer, mini_text_batch, &self.device).unwrap();
+            let encodings = encode_batch(
+                &self.tokenizer,
+                mini_text_batch,
+                self.tokenization_cache.as_ref(),
+            )?;
+            let token_ids = ids_tensor(&encodings, &self.device)?;
+            let token_type_ids = token_ids.zeros_like()?;
+            let embeddings: Tensor = self.model.forward(&token_ids, &token_type_ids, None)?;
+            let attention_mask = attention_mask_tensor(&encodings, &self.device)?;
 
             let batch_encodings = Tensor::log(
                 &Tensor::try_from(1.0)?
@@ -609,7 +901,8 @@ impl BertEmbed for SparseBertEmbedder {
 
             let batch_encodings = batch_encodings
                 .broadcast_mul(&attention_mask.unsqueeze(2)?.to_dtype(self.dtype)?)?
-                .max(1)?;
+                .max(1)?
+                .to_dtype(DType::F32)?;
             let batch_encodings = normalize_l2(&batch_encodings)?;
 
             encodings.extend(
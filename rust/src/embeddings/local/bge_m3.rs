@@ -0,0 +1,310 @@
+//! BGE-M3 (BAAI/bge-m3) produces a dense vector, sparse lexical weights and ColBERT token vectors
+//! for a text in a single forward pass.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Error as E;
+use ndarray::prelude::*;
+use ort::execution_providers::{CUDAExecutionProvider, ExecutionProvider};
+use ort::session::builder::GraphOptimizationLevel;
+use ort::session::Session;
+use rayon::prelude::*;
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+use super::bert::{BertEmbed, TokenizerConfig};
+use super::providers::execution_providers;
+use super::text_embedding::{models_map, ONNXModel};
+use crate::embeddings::embed::EmbeddingResult;
+use crate::embeddings::utils::{attention_mask_ndarray, encode_batch, ids_ndarray};
+use crate::embeddings::ComputeDevice;
+use crate::error::EmbedError;
+use crate::hub::HubRepo;
+
+/// The representation returned by [`BertEmbed::embed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BgeM3Output {
+    #[default]
+    Dense,
+    /// Lexical weights as a vocabulary-sized vector, like the SPLADE models.
+    Sparse,
+    ColBert,
+}
+
+impl From<ONNXModel> for BgeM3Output {
+    fn from(model: ONNXModel) -> Self {
+        match model {
+            ONNXModel::BGEM3Sparse => BgeM3Output::Sparse,
+            ONNXModel::BGEM3ColBERT => BgeM3Output::ColBert,
+            _ => BgeM3Output::Dense,
+        }
+    }
+}
+
+/// All three BGE-M3 representations of a text.
+#[derive(Debug, Clone)]
+pub struct BgeM3Embedding {
+    pub dense: Vec<f32>,
+    /// Weight of each token id that occurs in the text. Special tokens are left out.
+    pub sparse: HashMap<u32, f32>,
+    pub colbert: Vec<Vec<f32>>,
+}
+
+pub struct OrtBgeM3Embedder {
+    pub tokenizer: Tokenizer,
+    pub model: Session,
+    pub output: BgeM3Output,
+    special_ids: HashSet<u32>,
+    vocab_size: usize,
+}
+
+impl OrtBgeM3Embedder {
+    pub fn new(
+        model_name: Option<ONNXModel>,
+        model_id: Option<&str>,
+        revision: Option<&str>,
+        path_in_repo: Option<&str>,
+    ) -> Result<Self, E> {
+        Self::new_on_device(
+            model_name,
+            model_id,
+            revision,
+            path_in_repo,
+            ComputeDevice::Auto,
+        )
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_name: Option<ONNXModel>,
+        model_id: Option<&str>,
+        revision: Option<&str>,
+        path_in_repo: Option<&str>,
+        device: ComputeDevice,
+    ) -> Result<Self, E> {
+        let model_info = model_name.and_then(|name| models_map().get(&name));
+        let hf_model_id = match (model_id, model_info) {
+            (Some(id), _) => id,
+            (None, Some(info)) => info.model_code.as_str(),
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "Please provide either model_name or model_id"
+                ))
+            }
+        };
+        let path = match (path_in_repo, model_info) {
+            (Some(path), _) => path,
+            (None, Some(info)) => info.model_file.as_str(),
+            (None, None) => "model.onnx",
+        };
+
+        let api = HubRepo::new(hf_model_id, revision)?;
+        let tokenizer_filename = api.get("tokenizer.json")?;
+        let tokenizer_config_filename = api.get("tokenizer_config.json")?;
+        let weights_filename = api.get(path)?;
+        // The exported model is larger than 2GB, so its weights live in a separate file that has to
+        // sit next to the graph. Offline, a graph without it fails to load with a clear error.
+        let external_data = format!("{path}_data");
+        if let Err(e) = api.get_optional(&external_data) {
+            tracing::warn!("Could not fetch {}: {}", external_data, e);
+        }
+
+        let tokenizer_config = std::fs::read_to_string(tokenizer_config_filename)?;
+        let tokenizer_config: TokenizerConfig = serde_json::from_str(&tokenizer_config)?;
+        let max_length = match (
+            tokenizer_config.max_length,
+            tokenizer_config.model_max_length,
+        ) {
+            (Some(max_len), Some(model_max_len)) => std::cmp::min(max_len, model_max_len),
+            (Some(max_len), None) => max_len,
+            (None, Some(model_max_len)) => model_max_len,
+            (None, None) => 8192,
+        };
+
+        let mut tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
+        let pp = PaddingParams {
+            strategy: tokenizers::PaddingStrategy::BatchLongest,
+            ..Default::default()
+        };
+        let trunc = TruncationParams {
+            max_length,
+            ..Default::default()
+        };
+        tokenizer
+            .with_padding(Some(pp))
+            .with_truncation(Some(trunc))
+            .map_err(E::msg)?;
+
+        let special_ids = tokenizer
+            .get_added_tokens_decoder()
+            .into_iter()
+            .filter(|(_, token)| token.special)
+            .map(|(id, _)| id)
+            .collect();
+        let vocab_size = tokenizer.get_vocab_size(true);
+
+        let cuda = CUDAExecutionProvider::default();
+        if !cuda.is_available()? {
+            tracing::warn!("CUDAExecutionProvider is not available");
+        } else {
+            tracing::info!("Session is using CUDAExecutionProvider");
+        }
+
+        let threads = std::thread::available_parallelism().unwrap().get();
+        let model = Session::builder()?
+            .with_execution_providers(execution_providers(device))?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_intra_threads(threads)?
+            .commit_from_file(weights_filename)?;
+
+        Ok(Self {
+            tokenizer,
+            model,
+            output: model_name.map(BgeM3Output::from).unwrap_or_default(),
+            special_ids,
+            vocab_size,
+        })
+    }
+
+    /// Sets the representation returned by [`BertEmbed::embed`].
+    pub fn with_output(mut self, output: BgeM3Output) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Returns the dense, sparse and ColBERT representations of every text.
+    pub fn embed_all(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<BgeM3Embedding>, E> {
+        let batch_size = batch_size.unwrap_or(32);
+        Ok(text_batch
+            .par_chunks(batch_size)
+            .map(|mini_text_batch| self.embed_mini_batch(mini_text_batch))
+            .collect::<Result<Vec<_>, E>>()?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    fn embed_mini_batch(&self, text_batch: &[String]) -> Result<Vec<BgeM3Embedding>, E> {
+        let encodings = encode_batch(&self.tokenizer, text_batch, None)?;
+        let input_ids = ids_ndarray(&encodings)?;
+        let attention_mask = attention_mask_ndarray(&encodings)?;
+        let outputs = self.model.run(ort::inputs![
+            "input_ids" => input_ids.clone(),
+            "attention_mask" => attention_mask.clone()
+        ]?)?;
+
+        let output = |name: &str| {
+            outputs
+                .get(name)
+                .ok_or_else(|| EmbedError::Inference(format!("BGE-M3 output `{name}` is missing")))
+        };
+        let dense = output("dense_vecs")?
+            .try_extract_tensor::<f32>()?
+            .to_owned()
+            .into_dimensionality::<Ix2>()?;
+        let sparse = output("sparse_vecs")?
+            .try_extract_tensor::<f32>()?
+            .to_owned()
+            .into_dimensionality::<Ix3>()?;
+        // The ColBERT head skips the [CLS] token, so its rows are shifted by one.
+        let colbert = output("colbert_vecs")?
+            .try_extract_tensor::<f32>()?
+            .to_owned()
+            .into_dimensionality::<Ix3>()?;
+
+        Ok((0..text_batch.len())
+            .map(|i| {
+                let mut weights = HashMap::new();
+                for (position, &id) in input_ids.row(i).iter().enumerate() {
+                    let id = id as u32;
+                    if attention_mask[[i, position]] == 0 || self.special_ids.contains(&id) {
+                        continue;
+                    }
+                    let weight = sparse[[i, position, 0]].max(0.0);
+                    if weight > 0.0 {
+                        let entry = weights.entry(id).or_insert(0.0f32);
+                        *entry = entry.max(weight);
+                    }
+                }
+
+                let token_vectors = colbert
+                    .index_axis(Axis(0), i)
+                    .outer_iter()
+                    .enumerate()
+                    .filter(|(position, _)| attention_mask[[i, position + 1]] == 1)
+                    .map(|(_, vector)| normalize(vector.to_vec()))
+                    .collect();
+
+                BgeM3Embedding {
+                    dense: normalize(dense.row(i).to_vec()),
+                    sparse: weights,
+                    colbert: token_vectors,
+                }
+            })
+            .collect())
+    }
+}
+
+fn normalize(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+impl BertEmbed for OrtBgeM3Embedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
+    fn embed(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        Ok(self
+            .embed_all(text_batch, batch_size)?
+            .into_iter()
+            .map(|embedding| match self.output {
+                BgeM3Output::Dense => EmbeddingResult::DenseVector(embedding.dense),
+                BgeM3Output::Sparse => {
+                    let mut vector = vec![0.0; self.vocab_size];
+                    for (id, weight) in embedding.sparse {
+                        if let Some(value) = vector.get_mut(id as usize) {
+                            *value = weight;
+                        }
+                    }
+                    EmbeddingResult::DenseVector(vector)
+                }
+                BgeM3Output::ColBert => EmbeddingResult::MultiVector(embedding.colbert),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bge_m3_embed_all() {
+        let embedder = OrtBgeM3Embedder::new(Some(ONNXModel::BGEM3), None, None, None).unwrap();
+        let texts = vec![
+            "What is BGE M3?".to_string(),
+            "BGE M3 is an embedding model supporting dense, lexical and multi-vector retrieval."
+                .to_string(),
+        ];
+        let embeddings = embedder.embed_all(&texts, None).unwrap();
+        assert_eq!(embeddings.len(), 2);
+        for embedding in &embeddings {
+            assert_eq!(embedding.dense.len(), 1024);
+            assert!(!embedding.sparse.is_empty());
+            assert!(embedding.colbert.iter().all(|vector| vector.len() == 1024));
+        }
+        assert!(embeddings[1].colbert.len() > embeddings[0].colbert.len());
+    }
+}
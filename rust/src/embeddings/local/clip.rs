@@ -8,13 +8,17 @@ use std::{collections::HashMap, fs};
 
 use anyhow::Error as E;
 
+use crate::error::EmbedError;
+use crate::hub::HubRepo;
 use crate::{
-    embeddings::{embed::EmbeddingResult, select_device},
+    embeddings::{embed::EmbeddingResult, select_dtype, ComputeDevice},
     models::clip::{self, ClipConfig},
+    Dtype,
 };
 use candle_core::{DType, Device, Tensor};
 
 use candle_nn::VarBuilder;
+use image::DynamicImage;
 use tokenizers::Tokenizer;
 
 use crate::embeddings::embed::{EmbedData, EmbedImage};
@@ -23,6 +27,7 @@ pub struct ClipEmbedder {
     pub model: clip::ClipModel,
     pub tokenizer: Tokenizer,
     pub device: Device,
+    pub dtype: DType,
 }
 impl Default for ClipEmbedder {
     fn default() -> Self {
@@ -35,29 +40,39 @@ impl Default for ClipEmbedder {
 }
 
 impl ClipEmbedder {
+    /// Loads a CLIP model from the Hugging Face Hub in F32.
     pub fn new(model_id: String, revision: Option<&str>) -> Result<Self, E> {
-        let api = hf_hub::api::sync::Api::new()?;
-
-        let api = match revision {
-            Some(rev) => api.repo(hf_hub::Repo::with_revision(
-                model_id.to_string(),
-                hf_hub::RepoType::Model,
-                rev.to_string(),
-            )),
-            None => api.repo(hf_hub::Repo::new(
-                model_id.to_string(),
-                hf_hub::RepoType::Model,
-            )),
-        };
+        Self::new_with_dtype(model_id, revision, None)
+    }
+
+    /// Like [`new`](Self::new), but with the compute precision. `Dtype::F16` and `Dtype::BF16` are
+    /// honored on GPUs that support them; otherwise the model runs in F32.
+    pub fn new_with_dtype(
+        model_id: String,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+    ) -> Result<Self, E> {
+        Self::new_on_device(model_id, revision, dtype, ComputeDevice::Auto)
+    }
+
+    /// Like [`new_with_dtype`](Self::new_with_dtype), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: String,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+        device: ComputeDevice,
+    ) -> Result<Self, E> {
+        let api = HubRepo::new(&model_id, revision)?;
 
-        let device = select_device();
+        let device = device.candle_device()?;
+        let dtype = select_dtype(dtype, &device);
 
         let vb = match api.get("model.safetensors") {
             Ok(safetensors) => unsafe {
-                VarBuilder::from_mmaped_safetensors(&[safetensors], DType::F32, &device)?
+                VarBuilder::from_mmaped_safetensors(&[safetensors], dtype, &device)?
             },
             Err(_) => match api.get("pytorch_model.bin") {
-                Ok(pytorch_model) => VarBuilder::from_pth(pytorch_model, DType::F32, &device)?,
+                Ok(pytorch_model) => VarBuilder::from_pth(pytorch_model, dtype, &device)?,
                 Err(e) => {
                     return Err(anyhow::Error::msg(format!(
                         "Model weights not found. The weights should either be a `model.safetensors` or `pytorch_model.bin` file.  Error: {}",
@@ -77,18 +92,14 @@ impl ClipEmbedder {
             model,
             tokenizer,
             device,
+            dtype,
         })
     }
 
     pub fn get_tokenizer(tokenizer: Option<String>) -> anyhow::Result<Tokenizer> {
         let tokenizer = match tokenizer {
             None => {
-                let api = hf_hub::api::sync::Api::new()?;
-                let api = api.repo(hf_hub::Repo::with_revision(
-                    "openai/clip-vit-base-patch32".to_string(),
-                    hf_hub::RepoType::Model,
-                    "refs/pr/15".to_string(),
-                ));
+                let api = HubRepo::dependency("openai/clip-vit-base-patch32", Some("refs/pr/15"))?;
                 api.get("tokenizer.json")?
             }
             Some(file) => file.into(),
@@ -119,7 +130,9 @@ impl ClipEmbedder {
         let mut tokens = vec![];
 
         for seq in vec_seq.clone() {
-            let encoding = tokenizer.encode(seq, true).map_err(E::msg)?;
+            let encoding = tokenizer
+                .encode(seq, true)
+                .map_err(EmbedError::tokenization)?;
             tokens.push(encoding.get_ids().to_vec());
         }
 
@@ -144,6 +157,10 @@ impl ClipEmbedder {
         image_size: usize,
     ) -> anyhow::Result<Tensor> {
         let img = image::ImageReader::open(path)?.decode()?;
+        self.image_to_tensor(&img, image_size)
+    }
+
+    fn image_to_tensor(&self, img: &DynamicImage, image_size: usize) -> anyhow::Result<Tensor> {
         let (height, width) = (image_size, image_size);
         let img = img.resize_to_fill(
             width as u32,
@@ -157,7 +174,8 @@ impl ClipEmbedder {
         let img = Tensor::from_vec(img, (height, width, 3), &self.device)?
             .permute((2, 0, 1))?
             .to_dtype(DType::F32)?
-            .affine(2. / 255., -1.)?;
+            .affine(2. / 255., -1.)?
+            .to_dtype(self.dtype)?;
         // .unsqueeze(0)?;
         Ok(img)
     }
@@ -179,6 +197,14 @@ impl ClipEmbedder {
         Ok(images)
     }
 
+    fn image_features(&self, images: &Tensor) -> anyhow::Result<Vec<Vec<f32>>> {
+        Ok(self
+            .model
+            .get_image_features(images)?
+            .to_dtype(DType::F32)?
+            .to_vec2::<f32>()?)
+    }
+
     pub fn embed(
         &self,
         text_batch: &[String],
@@ -189,16 +215,14 @@ impl ClipEmbedder {
         let batch_size = batch_size.unwrap_or(32);
 
         for mini_text_batch in text_batch.chunks(batch_size) {
-            let (input_ids, _vec_seq) = self
-                .tokenize_sequences(Some(mini_text_batch.to_vec()), &self.tokenizer)
-                .unwrap();
+            let (input_ids, _vec_seq) =
+                self.tokenize_sequences(Some(mini_text_batch.to_vec()), &self.tokenizer)?;
 
             let batch_encodings = self
                 .model
-                .get_text_features(&input_ids)
-                .unwrap()
-                .to_vec2::<f32>()
-                .unwrap();
+                .get_text_features(&input_ids)?
+                .to_dtype(DType::F32)?
+                .to_vec2::<f32>()?;
 
             encodings.extend(
                 batch_encodings
@@ -220,15 +244,12 @@ impl EmbedImage for ClipEmbedder {
 
         let mut encodings = Vec::new();
         for image_batch in image_paths.chunks(32) {
-            let images = self
-                .load_images(image_batch, config.vision_config.image_size)
-                .unwrap();
+            let images = self.load_images(image_batch, config.vision_config.image_size)?;
             let batch_encodings = self
                 .model
-                .get_image_features(&images)
-                .unwrap()
-                .to_vec2::<f32>()
-                .unwrap();
+                .get_image_features(&images)?
+                .to_dtype(DType::F32)?
+                .to_vec2::<f32>()?;
             encodings.extend(batch_encodings);
         }
 
@@ -271,6 +292,8 @@ impl EmbedImage for ClipEmbedder {
             .model
             .get_image_features(&image)
             .unwrap()
+            .to_dtype(DType::F32)
+            .unwrap()
             .to_vec2::<f32>()
             .unwrap()[0];
         Ok(EmbedData::new(
@@ -279,6 +302,40 @@ impl EmbedImage for ClipEmbedder {
             metadata.clone(),
         ))
     }
+
+    fn embed_image_from(
+        &self,
+        image: &DynamicImage,
+        metadata: Option<HashMap<String, String>>,
+    ) -> anyhow::Result<EmbedData> {
+        let config = clip::ClipConfig::vit_base_patch32();
+        let image = self
+            .image_to_tensor(image, config.vision_config.image_size)?
+            .unsqueeze(0)?;
+        let encoding = self.image_features(&image)?.swap_remove(0);
+        Ok(EmbedData::new(
+            EmbeddingResult::DenseVector(encoding),
+            None,
+            metadata,
+        ))
+    }
+
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        let config = clip::ClipConfig::vit_base_patch32();
+
+        let mut embeddings = Vec::with_capacity(images.len());
+        for image_batch in images.chunks(32) {
+            let tensors = image_batch
+                .iter()
+                .map(|image| self.image_to_tensor(image, config.vision_config.image_size))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let batch_encodings = self.image_features(&Tensor::stack(&tensors, 0)?)?;
+            embeddings.extend(batch_encodings.into_iter().map(|encoding| {
+                EmbedData::new(EmbeddingResult::DenseVector(encoding), None, None)
+            }));
+        }
+        Ok(embeddings)
+    }
 }
 
 #[cfg(test)]
@@ -338,4 +395,30 @@ mod tests {
             .unwrap();
         assert_eq!(embeddings.len(), 2);
     }
+
+    // Tests that decoded images embed the same as the files they were read from.
+    #[test]
+    fn test_embed_image_from() {
+        let clip_embedder = ClipEmbedder::default();
+        let paths = ["test_files/clip/cat1.jpg", "test_files/clip/cat2.jpeg"];
+        let images = paths
+            .iter()
+            .map(|path| image::open(path).unwrap())
+            .collect::<Vec<_>>();
+
+        let from_files = clip_embedder.embed_image_batch(&paths).unwrap();
+        let from_images = clip_embedder.embed_image_batch_from(&images).unwrap();
+        assert_eq!(from_images.len(), 2);
+        for (file, image) in from_files.iter().zip(&from_images) {
+            let (EmbeddingResult::DenseVector(file), EmbeddingResult::DenseVector(image)) =
+                (&file.embedding, &image.embedding)
+            else {
+                panic!("CLIP embeddings are dense");
+            };
+            assert!(file.iter().zip(image).all(|(a, b)| (a - b).abs() < 1e-4));
+        }
+
+        let single = clip_embedder.embed_image_from(&images[0], None).unwrap();
+        assert!(single.metadata.is_none());
+    }
 }
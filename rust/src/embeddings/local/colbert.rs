@@ -4,13 +4,13 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
+use std::collections::HashSet;
 use std::ops::Mul;
 
 use anyhow::{Error as E, Result};
-use hf_hub::{api::sync::Api, Repo};
-use ndarray::{Array2, Array3, Axis};
+use ndarray::{Array2, Array3, ArrayView1, ArrayView2, Axis};
 use ort::{
-    execution_providers::{CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProvider},
+    execution_providers::{CUDAExecutionProvider, ExecutionProvider},
     session::{builder::GraphOptimizationLevel, Session},
     value::Value,
 };
@@ -20,9 +20,13 @@ use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 use crate::embeddings::{
         embed::EmbeddingResult,
         utils::{get_attention_mask_ndarray, tokenize_batch_ndarray},
+        ComputeDevice,
     };
 
+use crate::hub::HubRepo;
+
 use super::bert::{BertEmbed, TokenizerConfig};
+use super::providers::execution_providers;
 
 pub trait ColbertEmbed {
     fn embed(
@@ -33,6 +37,37 @@ pub trait ColbertEmbed {
     ) -> Result<Vec<EmbeddingResult>, E>;
 }
 
+/// Selects which token vectors end up in the `MultiVector` output. The default keeps every token,
+/// including padding.
+#[derive(Debug, Clone, Default)]
+pub struct TokenFilter {
+    /// Drops punctuation tokens from documents, like ColBERT's skiplist. Queries keep them.
+    pub skip_punctuation: bool,
+    /// Drops special tokens, markers and padding. The `[MASK]` padding of queries is kept since
+    /// ColBERT relies on it for query augmentation.
+    pub skip_special_tokens: bool,
+    /// Keeps at most `top_k` tokens per text, the ones with the largest norm before normalization,
+    /// in their original order.
+    pub top_k: Option<usize>,
+}
+
+impl TokenFilter {
+    pub fn with_skip_punctuation(mut self, skip_punctuation: bool) -> Self {
+        self.skip_punctuation = skip_punctuation;
+        self
+    }
+
+    pub fn with_skip_special_tokens(mut self, skip_special_tokens: bool) -> Self {
+        self.skip_special_tokens = skip_special_tokens;
+        self
+    }
+
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct OrtColbertEmbedder {
     pub tokenizer: Tokenizer,
@@ -41,6 +76,9 @@ pub struct OrtColbertEmbedder {
     pub query_marker_token_id: Option<i64>,
     pub pad_id: Option<i64>,
     pub mask_token: Option<String>,
+    pub token_filter: TokenFilter,
+    punctuation_ids: HashSet<i64>,
+    special_ids: HashSet<i64>,
 }
 
 impl OrtColbertEmbedder {
@@ -48,6 +86,16 @@ impl OrtColbertEmbedder {
         model_id: Option<&str>,
         revision: Option<&str>,
         path_in_repo: Option<&str>,
+    ) -> Result<Self, E> {
+        Self::new_on_device(model_id, revision, path_in_repo, ComputeDevice::Auto)
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: Option<&str>,
+        revision: Option<&str>,
+        path_in_repo: Option<&str>,
+        device: ComputeDevice,
     ) -> Result<Self, E> {
         let path_in_repo = path_in_repo.unwrap_or("model.onnx");
         let hf_model_id = match model_id {
@@ -56,18 +104,7 @@ impl OrtColbertEmbedder {
         };
 
         let (_, tokenizer_filename, weights_filename, tokenizer_config_filename, data_filename) = {
-            let api = Api::new().unwrap();
-            let api = match revision {
-                Some(rev) => api.repo(Repo::with_revision(
-                    hf_model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                    rev.to_string(),
-                )),
-                None => api.repo(hf_hub::Repo::new(
-                    hf_model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                )),
-            };
+            let api = HubRepo::new(hf_model_id, revision)?;
             let config = api.get("config.json")?;
             let tokenizer = api.get("tokenizer.json")?;
             let tokenizer_config = api.get("tokenizer_config.json")?;
@@ -131,21 +168,35 @@ impl OrtColbertEmbedder {
         let cuda = CUDAExecutionProvider::default();
 
         if !cuda.is_available()? {
-            eprintln!("CUDAExecutionProvider is not available");
+            tracing::warn!("CUDAExecutionProvider is not available");
         } else {
-            println!("Session is using CUDAExecutionProvider");
+            tracing::info!("Session is using CUDAExecutionProvider");
         }
 
         let threads = std::thread::available_parallelism().unwrap().get();
         let model = Session::builder()?
-            .with_execution_providers([
-                CUDAExecutionProvider::default().build(),
-                CoreMLExecutionProvider::default().build(),
-            ])?
+            .with_execution_providers(execution_providers(device))?
             .with_optimization_level(GraphOptimizationLevel::Level3)?
             .with_intra_threads(threads)?
             .commit_from_file(weights_filename)?;
 
+        let punctuation_ids = tokenizer
+            .get_vocab(true)
+            .into_iter()
+            .filter(|(token, _)| {
+                !token.is_empty() && token.chars().all(|c| c.is_ascii_punctuation())
+            })
+            .map(|(_, id)| id as i64)
+            .collect();
+        let mut special_ids: HashSet<i64> = tokenizer
+            .get_added_tokens_decoder()
+            .into_iter()
+            .filter(|(_, token)| token.special)
+            .map(|(id, _)| id as i64)
+            .collect();
+        special_ids.extend(document_marker_token_id);
+        special_ids.extend(query_marker_token_id);
+
         Ok(OrtColbertEmbedder {
             tokenizer,
             model,
@@ -153,8 +204,79 @@ impl OrtColbertEmbedder {
             query_marker_token_id,
             pad_id,
             mask_token,
+            token_filter: TokenFilter::default(),
+            punctuation_ids,
+            special_ids,
         })
     }
+
+    pub fn with_token_filter(mut self, token_filter: TokenFilter) -> Self {
+        self.token_filter = token_filter;
+        self
+    }
+
+    /// Returns whether a token survives the punctuation and special token filters.
+    fn keep_token(&self, id: i64, mask: i64, is_doc: bool) -> bool {
+        let filter = &self.token_filter;
+        if filter.skip_special_tokens {
+            let is_query_mask = !is_doc && self.pad_id == Some(id);
+            if !is_query_mask && (mask == 0 || self.special_ids.contains(&id)) {
+                return false;
+            }
+        }
+        !(filter.skip_punctuation && is_doc && self.punctuation_ids.contains(&id))
+    }
+
+    /// Normalizes the token vectors of one text and applies the token filter.
+    fn token_vectors(
+        &self,
+        embeddings: ArrayView2<f32>,
+        input_ids: ArrayView1<i64>,
+        attention_mask: ArrayView1<i64>,
+        is_doc: bool,
+    ) -> Vec<Vec<f32>> {
+        let mut tokens = embeddings
+            .outer_iter()
+            .zip(input_ids.iter().zip(attention_mask.iter()))
+            .filter(|(_, (&id, &mask))| self.keep_token(id, mask, is_doc))
+            .map(|(vector, _)| {
+                let norm = (vector.dot(&vector)).sqrt();
+                (norm, vector.map(|&x| x / (norm + 1e-10)).to_vec())
+            })
+            .enumerate()
+            .collect::<Vec<_>>();
+
+        if let Some(top_k) = self.token_filter.top_k {
+            if tokens.len() > top_k {
+                tokens.sort_by(|(_, (a, _)), (_, (b, _))| b.total_cmp(a));
+                tokens.truncate(top_k);
+                tokens.sort_by_key(|(position, _)| *position);
+            }
+        }
+        tokens.into_iter().map(|(_, (_, vector))| vector).collect()
+    }
+
+    /// Splits a batch of token embeddings into one normalized `MultiVector` per text.
+    fn multi_vectors(
+        &self,
+        embeddings: Array3<f32>,
+        input_ids: &Array2<i64>,
+        attention_mask: &Array2<i64>,
+        is_doc: bool,
+    ) -> Vec<EmbeddingResult> {
+        embeddings
+            .outer_iter()
+            .zip(input_ids.outer_iter().zip(attention_mask.outer_iter()))
+            .map(|(embeddings, (input_ids, attention_mask))| {
+                EmbeddingResult::MultiVector(self.token_vectors(
+                    embeddings,
+                    input_ids,
+                    attention_mask,
+                    is_doc,
+                ))
+            })
+            .collect()
+    }
 }
 
 impl ColbertEmbed for OrtColbertEmbedder {
@@ -179,7 +301,7 @@ impl ColbertEmbed for OrtColbertEmbedder {
         let batch_size = batch_size.unwrap_or(32);
         let encodings = text_batch
             .par_chunks(batch_size)
-            .flat_map(|mini_text_batch| -> Result<Vec<EmbeddingResult>, E> {
+            .map(|mini_text_batch| -> Result<Vec<EmbeddingResult>, E> {
                 let mut input_ids: Array2<i64> =
                     tokenize_batch_ndarray(&tokenizer, mini_text_batch)?;
                 let token_type_ids: Array2<i64> = Array2::zeros(input_ids.raw_dim());
@@ -209,6 +331,7 @@ impl ColbertEmbed for OrtColbertEmbedder {
                     .map(|input| input.name.as_str())
                     .collect::<Vec<_>>();
 
+                let ids = input_ids.clone();
                 let mut inputs =
                     ort::inputs!["input_ids" => input_ids, "attention_mask" => attention_mask.clone()]?;
                 if input_names.iter().any(|&x| x == "token_type_ids") {
@@ -224,31 +347,13 @@ impl ColbertEmbed for OrtColbertEmbedder {
                 .to_owned()
                 .into_dimensionality::<ndarray::Ix3>()?;
 
-                let attention_mask = attention_mask.mapv(|x| x as f32).insert_axis(Axis(2));
-                let embeddings = embeddings.mul(attention_mask);
-                let (batch_size, seq_len, embed_dim) = embeddings.dim();
-                // Normalize each token's embedding vector
-                let normalized_embeddings = embeddings.to_owned().to_shape((batch_size * seq_len, embed_dim))?
-                    .outer_iter()
-                    .map(|vector| {
-                        let norm = (vector.dot(&vector)).sqrt();
-                        vector.map(|&x| x / (norm + 1e-10)).to_vec()
-                    })
-                    .collect::<Vec<_>>();
-
-                // Reshape back to [Batch, Seq, Embedding Dimension]
-                let normalized_embeddings = normalized_embeddings
-                    .chunks(seq_len)
-                    .map(|batch| batch.to_vec())
-                    .collect::<Vec<_>>();
+                let mask = attention_mask.mapv(|x| x as f32).insert_axis(Axis(2));
+                let embeddings = embeddings.mul(mask);
 
-                let e = normalized_embeddings
-                    .into_iter()
-                    .map(EmbeddingResult::MultiVector)
-                    .collect::<Vec<_>>();
-
-                Ok(e)
+                Ok(self.multi_vectors(embeddings, &ids, &attention_mask, is_doc))
             })
+            .collect::<Result<Vec<_>, E>>()?
+            .into_iter()
             .flatten()
             .collect::<Vec<_>>();
 
@@ -257,6 +362,10 @@ impl ColbertEmbed for OrtColbertEmbedder {
 }
 
 impl BertEmbed for OrtColbertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[String],
@@ -265,7 +374,7 @@ impl BertEmbed for OrtColbertEmbedder {
         let batch_size = batch_size.unwrap_or(32);
         let encodings = text_batch
             .par_chunks(batch_size)
-            .flat_map(|mini_text_batch| -> Result<Vec<EmbeddingResult>, E> {
+            .map(|mini_text_batch| -> Result<Vec<EmbeddingResult>, E> {
                 let input_ids: Array2<i64> =
                     tokenize_batch_ndarray(&self.tokenizer, mini_text_batch)?;
 
@@ -279,6 +388,7 @@ impl BertEmbed for OrtColbertEmbedder {
                     .map(|input| input.name.as_str())
                     .collect::<Vec<_>>();
 
+                let ids = input_ids.clone();
                 let mut inputs =
                     ort::inputs!["input_ids" => input_ids, "attention_mask" => attention_mask.clone()]?;
                 if input_names.iter().any(|&x| x == "token_type_ids") {
@@ -294,31 +404,13 @@ impl BertEmbed for OrtColbertEmbedder {
                 .to_owned()
                 .into_dimensionality::<ndarray::Ix3>()?;
 
-                let attention_mask = attention_mask.mapv(|x| x as f32).insert_axis(Axis(2));
-                let embeddings = embeddings.mul(attention_mask);
-                let (batch_size, seq_len, embed_dim) = embeddings.dim();
-                // Normalize each token's embedding vector
-                let normalized_embeddings = embeddings.to_owned().to_shape((batch_size * seq_len, embed_dim))?
-                    .outer_iter()
-                    .map(|vector| {
-                        let norm = (vector.dot(&vector)).sqrt();
-                        vector.map(|&x| x / (norm + 1e-10)).to_vec()
-                    })
-                    .collect::<Vec<_>>();
-
-                // Reshape back to [Batch, Seq, Embedding Dimension]
-                let normalized_embeddings = normalized_embeddings
-                    .chunks(seq_len)
-                    .map(|batch| batch.to_vec())
-                    .collect::<Vec<_>>();
-
-                let e = normalized_embeddings
-                    .into_iter()
-                    .map(EmbeddingResult::MultiVector)
-                    .collect::<Vec<_>>();
+                let mask = attention_mask.mapv(|x| x as f32).insert_axis(Axis(2));
+                let embeddings = embeddings.mul(mask);
 
-                Ok(e)
+                Ok(self.multi_vectors(embeddings, &ids, &attention_mask, true))
             })
+            .collect::<Result<Vec<_>, E>>()?
+            .into_iter()
             .flatten()
             .collect::<Vec<_>>();
 
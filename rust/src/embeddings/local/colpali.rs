@@ -1,9 +1,12 @@
 use std::path::PathBuf;
-use std::sync::RwLock;
+use std::sync::{PoisonError, RwLock};
 use std::{collections::HashMap, path::Path};
 
+use crate::config::ImageEmbedConfig;
 use crate::embeddings::embed::{EmbedData, EmbeddingResult};
-use crate::embeddings::select_device;
+use crate::embeddings::ComputeDevice;
+use crate::error::EmbedError;
+use crate::hub::HubRepo;
 use crate::models::{colpali::Model, paligemma};
 use anyhow::Error as E;
 use base64::Engine;
@@ -11,7 +14,7 @@ use candle_core::{DType, Device, Tensor};
 use candle_nn::VarBuilder;
 use image::{DynamicImage, ImageFormat};
 
-use pdf2image::{Pages, RenderOptionsBuilder, PDF};
+use pdf2image::{Pages, RenderOptionsBuilder, DPI, PDF};
 use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
 pub trait ColPaliEmbed {
@@ -22,7 +25,17 @@ pub trait ColPaliEmbed {
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error>;
 
     fn embed_query(&self, query: &str) -> anyhow::Result<Vec<EmbedData>>;
-    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>>;
+    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>> {
+        self.embed_file_with_config(file_path, batch_size, &ImageEmbedConfig::default())
+    }
+
+    /// Embeds the pages of a PDF rendered with the `pdf_*` settings of `config`.
+    fn embed_file_with_config(
+        &self,
+        file_path: PathBuf,
+        batch_size: usize,
+        config: &ImageEmbedConfig,
+    ) -> anyhow::Result<Vec<EmbedData>>;
     fn embed_image(
         &self,
         image_path: PathBuf,
@@ -30,8 +43,25 @@ pub trait ColPaliEmbed {
     ) -> anyhow::Result<EmbedData>;
 
     fn embed_image_batch(&self, image_paths: &[PathBuf]) -> anyhow::Result<Vec<EmbedData>>;
+
+    /// Embeds a decoded image, see [`crate::embeddings::embed::EmbedImage::embed_image_from`].
+    fn embed_image_from(
+        &self,
+        image: &DynamicImage,
+        metadata: Option<HashMap<String, String>>,
+    ) -> anyhow::Result<EmbedData> {
+        let mut embeddings = self.embed_image_batch_from(std::slice::from_ref(image))?;
+        let mut embedding = embeddings.swap_remove(0);
+        embedding.metadata = metadata;
+        Ok(embedding)
+    }
+
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>>;
 }
 
+/// The PaliGemma backbone keeps a KV cache, so forward passes need exclusive access to the model
+/// and concurrent calls through a shared `ColPaliEmbedder` are serialized on `model`. Every pass
+/// clears the cache first, which makes it safe to keep using the lock after a panicked call.
 pub struct ColPaliEmbedder {
     pub model: RwLock<Model>,
     pub tokenizer: Tokenizer,
@@ -43,23 +73,18 @@ pub struct ColPaliEmbedder {
 
 impl ColPaliEmbedder {
     pub fn new(model_id: &str, revision: Option<&str>) -> Result<Self, anyhow::Error> {
-        let api = hf_hub::api::sync::Api::new()?;
-        let repo: hf_hub::api::sync::ApiRepo = match revision {
-            Some(rev) => api.repo(hf_hub::Repo::with_revision(
-                model_id.to_string(),
-                hf_hub::RepoType::Model,
-                rev.to_string(),
-            )),
-            None => api.repo(hf_hub::Repo::new(
-                model_id.to_string(),
-                hf_hub::RepoType::Model,
-            )),
-        };
+        Self::new_on_device(model_id, revision, ComputeDevice::Auto)
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: &str,
+        revision: Option<&str>,
+        device: ComputeDevice,
+    ) -> Result<Self, anyhow::Error> {
+        let repo = HubRepo::new(model_id, revision)?;
 
-        let tokenizer_api = api.repo(hf_hub::Repo::new(
-            "vidore/colpali".to_string(),
-            hf_hub::RepoType::Model,
-        ));
+        let tokenizer_api = HubRepo::dependency("vidore/colpali", None)?;
 
         let (tokenizer_filename, weights_filename) = {
             let tokenizer = tokenizer_api.get("tokenizer.json")?;
@@ -87,7 +112,7 @@ impl ColPaliEmbedder {
             .with_truncation(Some(trunc))
             .unwrap();
 
-        let device = select_device();
+        let device = device.candle_device()?;
 
         let dtype = if device.is_cuda() {
             DType::BF16
@@ -156,7 +181,7 @@ impl ColPaliEmbed for ColPaliEmbedder {
             let batch_encodings = self
                 .model
                 .write()
-                .unwrap()
+                .unwrap_or_else(PoisonError::into_inner)
                 .forward_text(&input_ids)?
                 .to_dtype(DType::F32)?;
 
@@ -176,7 +201,7 @@ impl ColPaliEmbed for ColPaliEmbedder {
         let encoding = self
             .model
             .write()
-            .unwrap()
+            .unwrap_or_else(PoisonError::into_inner)
             .forward_text(&input_ids)?
             .to_dtype(DType::F32)?
             .to_vec3::<f32>()?
@@ -203,7 +228,7 @@ impl ColPaliEmbed for ColPaliEmbedder {
         let encoding = self
             .model
             .write()
-            .unwrap()
+            .unwrap_or_else(PoisonError::into_inner)
             .forward_images(&pixel_values, &self.dummy_input)?
             .to_dtype(DType::F32)?
             .to_vec3::<f32>()?
@@ -224,7 +249,7 @@ impl ColPaliEmbed for ColPaliEmbedder {
         let encodings = self
             .model
             .write()
-            .unwrap()
+            .unwrap_or_else(PoisonError::into_inner)
             .forward_images(&pixel_values, &self.dummy_input)?
             .to_dtype(DType::F32)?
             .to_vec3::<f32>()?;
@@ -234,12 +259,37 @@ impl ColPaliEmbed for ColPaliEmbedder {
             .map(|x| EmbedData::new(EmbeddingResult::MultiVector(x), None, None))
             .collect::<Vec<_>>())
     }
-    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>> {
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pixel_values = self.images_to_tensor(images, self.config.vision_config.image_size)?;
+        let dummy_input = self.dummy_input.repeat((pixel_values.dims()[0], 0))?;
+        let encodings = self
+            .model
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .forward_images(&pixel_values, &dummy_input)?
+            .to_dtype(DType::F32)?
+            .to_vec3::<f32>()?;
+
+        Ok(encodings
+            .into_iter()
+            .map(|x| EmbedData::new(EmbeddingResult::MultiVector(x), None, None))
+            .collect::<Vec<_>>())
+    }
+
+    fn embed_file_with_config(
+        &self,
+        file_path: PathBuf,
+        batch_size: usize,
+        config: &ImageEmbedConfig,
+    ) -> anyhow::Result<Vec<EmbedData>> {
         let dtype = self.dtype;
-        let pages = get_images_from_pdf(&file_path)?;
+        let (first_page, pages) = render_pdf(&file_path, config)?;
         let mut embed_data = Vec::new();
         for (index, batch) in pages.chunks(batch_size).enumerate() {
-            let start_page = index * batch_size + 1;
+            let start_page = first_page + index * batch_size;
             let end_page = start_page + batch.len();
             let page_numbers = (start_page..=end_page).collect::<Vec<_>>();
             let page_images = self
@@ -251,7 +301,7 @@ impl ColPaliEmbed for ColPaliEmbedder {
             let image_embeddings = self
                 .model
                 .write()
-                .unwrap()
+                .unwrap_or_else(PoisonError::into_inner)
                 .forward_images(&page_images, &dummy_input)?
                 .to_dtype(DType::F32)?
                 .to_vec3::<f32>()?
@@ -290,7 +340,9 @@ fn tokenize_batch(
     text_batch: Vec<&str>,
     device: &Device,
 ) -> anyhow::Result<Tensor> {
-    let tokens = tokenizer.encode_batch(text_batch, true).map_err(E::msg)?;
+    let tokens = tokenizer
+        .encode_batch(text_batch, true)
+        .map_err(EmbedError::tokenization)?;
     let token_ids = tokens
         .iter()
         .map(|tokens| {
@@ -302,11 +354,8 @@ fn tokenize_batch(
     Ok(Tensor::stack(&token_ids, 0)?)
 }
 
-pub fn hub_load_safetensors(
-    repo: &hf_hub::api::sync::ApiRepo,
-    json_file: &str,
-) -> Result<Vec<std::path::PathBuf>, E> {
-    let json_file = repo.get(json_file).map_err(candle_core::Error::wrap)?;
+pub fn hub_load_safetensors(repo: &HubRepo, json_file: &str) -> Result<Vec<std::path::PathBuf>, E> {
+    let json_file = repo.get(json_file)?;
     let json_file = std::fs::File::open(json_file)?;
     let json: serde_json::Value =
         serde_json::from_reader(&json_file).map_err(candle_core::Error::wrap)?;
@@ -323,7 +372,7 @@ pub fn hub_load_safetensors(
     }
     let safetensors_files = safetensors_files
         .iter()
-        .map(|v| repo.get(v).map_err(candle_core::Error::wrap))
+        .map(|v| repo.get(v))
         .collect::<Result<Vec<_>, _>>()?;
     Ok(safetensors_files)
 }
@@ -368,12 +417,34 @@ fn load_images<T: AsRef<std::path::Path>>(
     Ok(images)
 }
 
+/// Resolution PDF pages are rendered at unless set with [`ImageEmbedConfig::with_pdf_dpi`], the
+/// default of `pdftoppm`.
+pub const DEFAULT_PDF_DPI: u32 = 150;
+
 pub fn get_images_from_pdf<T: AsRef<Path>>(file_path: &T) -> Result<Vec<DynamicImage>, E> {
+    let (_, pages) = render_pdf(file_path, &ImageEmbedConfig::default())?;
+    Ok(pages)
+}
+
+/// Renders the pages of a PDF selected by [`ImageEmbedConfig::pdf_page_range`] at its DPI and
+/// colour settings. Returns the number of the first rendered page along with the pages.
+pub fn render_pdf<T: AsRef<Path>>(
+    file_path: &T,
+    config: &ImageEmbedConfig,
+) -> Result<(usize, Vec<DynamicImage>), E> {
     let pdf = PDF::from_file(file_path)?;
-    let page_count = pdf.page_count();
+    let pages = config.pdf_page_range(pdf.page_count() as usize);
+    let first_page = *pages.start();
+    if pages.is_empty() {
+        return Ok((first_page, Vec::new()));
+    }
+    let mut options = RenderOptionsBuilder::default();
+    options
+        .resolution(DPI::Uniform(config.pdf_dpi.unwrap_or(DEFAULT_PDF_DPI)))
+        .greyscale(config.pdf_grayscale.unwrap_or(false));
     let pages = pdf.render(
-        Pages::Range(1..=page_count),
-        RenderOptionsBuilder::default().build()?,
+        Pages::Range(first_page as u32..=*pages.end() as u32),
+        options.build()?,
     )?;
-    Ok(pages)
+    Ok((first_page, pages))
 }
@@ -1,20 +1,25 @@
 use std::{collections::HashMap, path::PathBuf};
 
+use crate::error::EmbedError;
+use crate::hub::HubRepo;
 use crate::models::paligemma;
 use anyhow::Error as E;
 use base64::Engine;
 use half::f16;
 use image::{DynamicImage, ImageFormat};
 use ndarray::prelude::*;
-use ort::execution_providers::{CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProvider};
+use ort::execution_providers::{CUDAExecutionProvider, ExecutionProvider};
 use ort::session::builder::GraphOptimizationLevel;
 use ort::session::Session;
 use rayon::prelude::*;
 use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
+use crate::config::ImageEmbedConfig;
 use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+use crate::embeddings::ComputeDevice;
 
-use super::colpali::{get_images_from_pdf, ColPaliEmbed};
+use super::colpali::{render_pdf, ColPaliEmbed};
+use super::providers::execution_providers;
 
 pub struct OrtColPaliEmbedder {
     pub model: Session,
@@ -26,18 +31,16 @@ pub struct OrtColPaliEmbedder {
 
 impl OrtColPaliEmbedder {
     pub fn new(model_id: &str, revision: Option<&str>) -> Result<Self, E> {
-        let api = hf_hub::api::sync::Api::new()?;
-        let repo: hf_hub::api::sync::ApiRepo = match revision {
-            Some(rev) => api.repo(hf_hub::Repo::with_revision(
-                model_id.to_string(),
-                hf_hub::RepoType::Model,
-                rev.to_string(),
-            )),
-            None => api.repo(hf_hub::Repo::new(
-                model_id.to_string(),
-                hf_hub::RepoType::Model,
-            )),
-        };
+        Self::new_on_device(model_id, revision, ComputeDevice::Auto)
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: &str,
+        revision: Option<&str>,
+        device: ComputeDevice,
+    ) -> Result<Self, E> {
+        let repo = HubRepo::new(model_id, revision)?;
 
         let (_, tokenizer_filename, weights_filename, _) = {
             let config = repo.get("config.json")?;
@@ -73,17 +76,14 @@ impl OrtColPaliEmbedder {
         let cuda = CUDAExecutionProvider::default();
 
         if !cuda.is_available()? {
-            eprintln!("CUDAExecutionProvider is not available");
+            tracing::warn!("CUDAExecutionProvider is not available");
         } else {
-            println!("Session is using CUDAExecutionProvider");
+            tracing::info!("Session is using CUDAExecutionProvider");
         }
 
         let threads = std::thread::available_parallelism().unwrap().get();
         let model = Session::builder()?
-            .with_execution_providers([
-                CUDAExecutionProvider::default().build(),
-                CoreMLExecutionProvider::default().build(),
-            ])?
+            .with_execution_providers(execution_providers(device))?
             .with_optimization_level(GraphOptimizationLevel::Level3)?
             .with_intra_threads(threads)?
             .commit_from_file(weights_filename)?;
@@ -104,7 +104,7 @@ impl OrtColPaliEmbedder {
 fn tokenize_batch(tokenizer: &Tokenizer, text_batch: &[String]) -> Result<Array2<i64>, E> {
     let token_ids = tokenizer
         .encode_batch_fast(text_batch.to_vec(), true)
-        .map_err(E::msg)?
+        .map_err(EmbedError::tokenization)?
         .iter()
         .map(|tokens| {
             tokens
@@ -125,7 +125,9 @@ fn tokenize_batch(tokenizer: &Tokenizer, text_batch: &[String]) -> Result<Array2
 }
 
 fn tokenize(tokenizer: &Tokenizer, text: String) -> Result<Array2<i64>, E> {
-    let token_ids = tokenizer.encode(text, true).map_err(E::msg)?;
+    let token_ids = tokenizer
+        .encode(text, true)
+        .map_err(EmbedError::tokenization)?;
     let token_ids_array = Array2::from_shape_vec(
         (1, token_ids.len()),
         token_ids
@@ -141,7 +143,7 @@ fn tokenize(tokenizer: &Tokenizer, text: String) -> Result<Array2<i64>, E> {
 fn get_attention_mask(tokenizer: &Tokenizer, text_batch: &[String]) -> Result<Array2<i64>, E> {
     let attention_mask = tokenizer
         .encode_batch(text_batch.to_vec(), true)
-        .map_err(E::msg)?
+        .map_err(EmbedError::tokenization)?
         .iter()
         .map(|tokens| {
             tokens
@@ -190,6 +192,28 @@ impl OrtColPaliEmbedder {
 
         Ok(e)
     }
+
+    fn embed_pixel_values(&self, image_array: Array4<f32>) -> anyhow::Result<Vec<EmbeddingResult>> {
+        let attention_mask =
+            Array2::<i64>::ones((image_array.shape()[0], 1024 + self.dummy_input.shape()[1]));
+
+        // to the dummy input prefix the token id 257152.
+        let image_input_ids = Array2::<i64>::from_elem((image_array.shape()[0], 1024), 257152);
+        let mut dummy_input_batches = vec![];
+        for _ in 0..image_array.shape()[0] {
+            dummy_input_batches.push(self.dummy_input.clone());
+        }
+        let dummy_input_batches = Array2::<i64>::from_shape_vec(
+            (image_array.shape()[0], self.dummy_input.shape()[1]),
+            dummy_input_batches
+                .into_iter()
+                .flatten()
+                .collect::<Vec<i64>>(),
+        )?;
+        let input_ids = ndarray::concatenate![Axis(1), image_input_ids, dummy_input_batches];
+
+        self.run_model(input_ids, attention_mask, image_array)
+    }
 }
 
 impl ColPaliEmbed for OrtColPaliEmbedder {
@@ -201,7 +225,7 @@ impl ColPaliEmbed for OrtColPaliEmbedder {
         let batch_size = batch_size.unwrap_or(32);
         let encodings = text_batch
             .par_chunks(batch_size)
-            .flat_map(|mini_text_batch| -> Result<Vec<EmbeddingResult>, E> {
+            .map(|mini_text_batch| -> Result<Vec<EmbeddingResult>, E> {
                 let token_ids: Array2<i64> = tokenize_batch(&self.tokenizer, mini_text_batch)?;
                 let attention_mask: Array2<i64> =
                     get_attention_mask(&self.tokenizer, mini_text_batch)?;
@@ -214,6 +238,8 @@ impl ColPaliEmbed for OrtColPaliEmbedder {
                 let e = self.run_model(token_ids, attention_mask, pixel_values)?;
                 Ok(e)
             })
+            .collect::<Result<Vec<_>, E>>()?
+            .into_iter()
             .flatten()
             .collect::<Vec<_>>();
 
@@ -233,11 +259,16 @@ impl ColPaliEmbed for OrtColPaliEmbedder {
         Ok(e)
     }
 
-    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>> {
-        let pages = get_images_from_pdf(&file_path)?;
+    fn embed_file_with_config(
+        &self,
+        file_path: PathBuf,
+        batch_size: usize,
+        config: &ImageEmbedConfig,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        let (first_page, pages) = render_pdf(&file_path, config)?;
         let mut embed_data = Vec::new();
         for (index, batch) in pages.chunks(batch_size).enumerate() {
-            let start_page = index * batch_size + 1;
+            let start_page = first_page + index * batch_size;
             let end_page = start_page + batch.len();
             let page_numbers = (start_page..=end_page).collect::<Vec<_>>();
             let page_images = pages_to_array(batch, self.num_channels, self.image_size)?;
@@ -312,27 +343,8 @@ impl ColPaliEmbed for OrtColPaliEmbedder {
 
     fn embed_image_batch(&self, image_paths: &[PathBuf]) -> anyhow::Result<Vec<EmbedData>> {
         let image_array = load_images_as_array(image_paths, self.num_channels, self.image_size)?;
-
-        let attention_mask =
-            Array2::<i64>::ones((image_array.shape()[0], 1024 + self.dummy_input.shape()[1]));
-
-        // to the dummy input prefix the token id 257152.
-        let image_input_ids = Array2::<i64>::from_elem((image_array.shape()[0], 1024), 257152);
-        let mut dummy_input_batches = vec![];
-        for _ in 0..image_array.shape()[0] {
-            dummy_input_batches.push(self.dummy_input.clone());
-        }
-        let dummy_input_batches = Array2::<i64>::from_shape_vec(
-            (image_array.shape()[0], self.dummy_input.shape()[1]),
-            dummy_input_batches
-                .into_iter()
-                .flatten()
-                .collect::<Vec<i64>>(),
-        )?;
-        let input_ids = ndarray::concatenate![Axis(1), image_input_ids, dummy_input_batches];
-
         let e = self
-            .run_model(input_ids, attention_mask, image_array)?
+            .embed_pixel_values(image_array)?
             .into_iter()
             .enumerate()
             .map(|(i, x)| {
@@ -346,6 +358,18 @@ impl ColPaliEmbed for OrtColPaliEmbedder {
             .collect::<Vec<_>>();
         Ok(e)
     }
+
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        if images.is_empty() {
+            return Ok(Vec::new());
+        }
+        let image_array = pages_to_array(images, self.num_channels, self.image_size)?;
+        Ok(self
+            .embed_pixel_values(image_array)?
+            .into_iter()
+            .map(|x| EmbedData::new(x, None, None))
+            .collect())
+    }
 }
 
 fn pages_to_array(
@@ -483,6 +507,19 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_colpali_embed_image_batch_from() -> anyhow::Result<()> {
+        download_image().await?;
+        let model = MODEL.lock().unwrap();
+        let image = image::open(IMAGE_PATH)?;
+        let embeddings = model.embed_image_batch_from(&[image.clone(), image])?;
+        assert_eq!(embeddings.len(), 2, "There should be two embeddings");
+        assert!(embeddings
+            .iter()
+            .all(|embedding| embedding.metadata.is_none()));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_colpali_embed_file() -> anyhow::Result<()> {
         let model = MODEL.lock().unwrap();
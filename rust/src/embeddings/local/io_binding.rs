@@ -0,0 +1,55 @@
+//! Runs ONNX Runtime sessions through an [`IoBinding`](ort::io_binding::IoBinding) on CUDA.
+//!
+//! Without a binding, ORT copies every output from the device into freshly allocated pageable
+//! host memory after each run. Binding the outputs to pinned host memory lets the copy run as a
+//! single DMA transfer, which matters for the large hidden-state outputs of embedding models.
+//!
+//! Only the output copy changes: a binding is still created for every batch, and the inputs are
+//! host tensors that ORT copies to the device on each run, as it does without a binding.
+
+use std::borrow::Cow;
+
+use ndarray::ArrayD;
+use ort::execution_providers::{CUDAExecutionProvider, ExecutionProvider};
+use ort::memory::{AllocationDevice, AllocatorType, MemoryInfo, MemoryType};
+use ort::session::{Session, SessionInputValue};
+use ort::value::DynValue;
+
+/// Whether sessions should be run through an IoBinding: only when ORT can use CUDA.
+pub fn io_binding_available() -> bool {
+    CUDAExecutionProvider::default()
+        .is_available()
+        .unwrap_or(false)
+}
+
+/// Runs `session` on `inputs` and returns the `output` tensor. With `io_binding` the output is
+/// copied back into pinned host memory; the inputs are copied from the host as usual.
+pub fn run_session(
+    session: &Session,
+    inputs: Vec<(&str, DynValue)>,
+    output: &str,
+    io_binding: bool,
+) -> anyhow::Result<ArrayD<f32>> {
+    if io_binding {
+        let mut binding = session.create_binding()?;
+        for (name, value) in &inputs {
+            binding.bind_input(*name, value)?;
+        }
+        let pinned = MemoryInfo::new(
+            AllocationDevice::CUDA_PINNED,
+            0,
+            AllocatorType::Device,
+            MemoryType::CPUOutput,
+        )?;
+        binding.bind_output_to_device(output, &pinned)?;
+        let outputs = binding.run()?;
+        return Ok(outputs[output].try_extract_tensor::<f32>()?.to_owned());
+    }
+
+    let inputs = inputs
+        .into_iter()
+        .map(|(name, value)| (Cow::from(name), SessionInputValue::from(value)))
+        .collect::<Vec<_>>();
+    let outputs = session.run(inputs)?;
+    Ok(outputs[output].try_extract_tensor::<f32>()?.to_owned())
+}
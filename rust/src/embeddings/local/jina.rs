@@ -4,23 +4,28 @@ extern crate intel_mkl_src;
 #[cfg(feature = "accelerate")]
 extern crate accelerate_src;
 
-use crate::embeddings::select_device;
+use crate::embeddings::timing::{timed, timed_on_device, Stage, StageTimer, TimingReport};
 use crate::embeddings::{embed::EmbeddingResult, normalize_l2};
+use crate::embeddings::{select_dtype, ComputeDevice};
+use crate::error::EmbedError;
+use crate::hub::HubRepo;
 use crate::models::jina_bert::{BertModel, Config};
 use crate::Dtype;
 use anyhow::Error as E;
 use candle_core::{DType, Device, Tensor};
 use candle_nn::{Module, VarBuilder};
-use hf_hub::api::sync::Api;
-use hf_hub::Repo;
 use ndarray::prelude::*;
-use ort::execution_providers::{CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProvider};
+use ort::execution_providers::{CUDAExecutionProvider, ExecutionProvider};
 use ort::session::builder::GraphOptimizationLevel;
 use ort::session::Session;
+use serde::Deserialize;
+use std::collections::HashMap;
+use strum::EnumString;
 use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
 use super::bert::TokenizerConfig;
 use super::pooling::{ModelOutput, Pooling};
+use super::providers::execution_providers;
 use super::text_embedding::{models_map, ONNXModel};
 use rayon::prelude::*;
 
@@ -30,6 +35,89 @@ pub trait JinaEmbed {
         text_batch: &[String],
         batch_size: Option<usize>,
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error>;
+
+    /// Embeds search queries. Models with task adapters use their query adapter; the others embed
+    /// the queries like any text.
+    fn embed_query(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.embed(text_batch, batch_size)
+    }
+
+    /// Embeds documents to search over. Models with task adapters use their passage adapter; the
+    /// others embed the documents like any text.
+    fn embed_document(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.embed(text_batch, batch_size)
+    }
+
+    /// The tokenizer the model encodes its inputs with, used to chunk text in the model's own
+    /// tokens.
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        None
+    }
+
+    /// The timer the model records its stages into, once timing is turned on.
+    fn timer(&self) -> Option<&StageTimer> {
+        None
+    }
+
+    /// The stage timings recorded since the last call, for models that record them once timing
+    /// is turned on.
+    fn timing_report(&self) -> Option<TimingReport> {
+        self.timer().map(StageTimer::take)
+    }
+}
+
+/// The task adapters (LoRA) of jina-embeddings-v3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+pub enum JinaTask {
+    #[strum(serialize = "retrieval.query")]
+    RetrievalQuery,
+    #[strum(serialize = "retrieval.passage")]
+    RetrievalPassage,
+    #[strum(serialize = "separation")]
+    Separation,
+    #[strum(serialize = "classification")]
+    Classification,
+    #[strum(serialize = "text-matching")]
+    TextMatching,
+}
+
+impl JinaTask {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JinaTask::RetrievalQuery => "retrieval.query",
+            JinaTask::RetrievalPassage => "retrieval.passage",
+            JinaTask::Separation => "separation",
+            JinaTask::Classification => "classification",
+            JinaTask::TextMatching => "text-matching",
+        }
+    }
+}
+
+/// The task adapters of jina-embeddings-v3 in the order of their index, for exports whose config
+/// does not list them.
+const V3_LORA_ADAPTATIONS: [&str; 5] = [
+    "retrieval.query",
+    "retrieval.passage",
+    "separation",
+    "classification",
+    "text-matching",
+];
+
+/// The parts of a jina-embeddings-v3 config that describe its task adapters.
+#[derive(Debug, Default, Deserialize)]
+struct TaskConfig {
+    #[serde(default)]
+    lora_adaptations: Vec<String>,
+    #[serde(default)]
+    task_instructions: HashMap<String, String>,
 }
 
 #[derive(Debug)]
@@ -38,6 +126,9 @@ pub struct OrtJinaEmbedder {
     pub version: String,
     pub tokenizer: Tokenizer,
     pub pooling: Pooling,
+    task_config: TaskConfig,
+    task_id: i64,
+    prompt: String,
 }
 
 impl OrtJinaEmbedder {
@@ -47,6 +138,25 @@ impl OrtJinaEmbedder {
         revision: Option<&str>,
         dtype: Option<Dtype>,
         path_in_repo: Option<&str>,
+    ) -> Result<Self, E> {
+        Self::new_on_device(
+            model_name,
+            model_id,
+            revision,
+            dtype,
+            path_in_repo,
+            ComputeDevice::Auto,
+        )
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_name: Option<ONNXModel>,
+        model_id: Option<&str>,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+        path_in_repo: Option<&str>,
+        device: ComputeDevice,
     ) -> Result<Self, E> {
         let hf_model_id = match model_id {
             Some(id) => id,
@@ -77,19 +187,8 @@ impl OrtJinaEmbedder {
             },
         };
 
-        let (_, tokenizer_filename, weights_filename, tokenizer_config_filename) = {
-            let api = Api::new().unwrap();
-            let api = match revision {
-                Some(rev) => api.repo(Repo::with_revision(
-                    hf_model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                    rev.to_string(),
-                )),
-                None => api.repo(hf_hub::Repo::new(
-                    hf_model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                )),
-            };
+        let (config_filename, tokenizer_filename, weights_filename, tokenizer_config_filename) = {
+            let api = HubRepo::new(hf_model_id, revision)?;
             let config = api.get("config.json")?;
             let tokenizer = api.get("tokenizer.json")?;
             let tokenizer_config = api.get("tokenizer_config.json")?;
@@ -103,6 +202,7 @@ impl OrtJinaEmbedder {
                 Some(Dtype::BNB4) => format!("{base_path}/model_bnb4.onnx"),
                 Some(Dtype::F32) => format!("{base_path}/model.onnx"),
                 Some(Dtype::QUANTIZED) => format!("{base_path}/model_quantized.onnx"),
+                Some(Dtype::BF16) => format!("{base_path}/model_bf16.onnx"),
                 None => path.to_string(),
             };
             let weights = api.get(model_path.as_str());
@@ -152,17 +252,14 @@ impl OrtJinaEmbedder {
         let cuda = CUDAExecutionProvider::default();
 
         if !cuda.is_available()? {
-            eprintln!("CUDAExecutionProvider is not available");
+            tracing::warn!("CUDAExecutionProvider is not available");
         } else {
-            println!("Session is using CUDAExecutionProvider");
+            tracing::info!("Session is using CUDAExecutionProvider");
         }
 
         let threads = std::thread::available_parallelism().unwrap().get();
         let model = Session::builder()?
-            .with_execution_providers([
-                CUDAExecutionProvider::default().build(),
-                CoreMLExecutionProvider::default().build(),
-            ])?
+            .with_execution_providers(execution_providers(device))?
             .with_optimization_level(GraphOptimizationLevel::Level3)?
             .with_intra_threads(threads)?
             .commit_from_file(weights_filename)?;
@@ -172,20 +269,85 @@ impl OrtJinaEmbedder {
             (_, Some(id)) if id.contains("jina-embeddings-v3") => "v3",
             _ => "v2",
         };
+        let task_config: TaskConfig =
+            serde_json::from_str(&std::fs::read_to_string(config_filename)?).unwrap_or_default();
 
-        Ok(OrtJinaEmbedder {
+        let embedder = OrtJinaEmbedder {
             session: model,
             version: version.to_string(),
             tokenizer,
             pooling,
-        })
+            task_config,
+            task_id: 0,
+            prompt: String::new(),
+        };
+        if embedder.version == "v3" {
+            embedder.with_task(JinaTask::TextMatching)
+        } else {
+            Ok(embedder)
+        }
+    }
+
+    /// Selects the task adapter of jina-embeddings-v3 and the instruction prepended to every text.
+    /// Defaults to `TextMatching`. Models without task adapters return an error. For asymmetric
+    /// search, [`Embedder::embed_queries`](crate::embeddings::embed::Embedder::embed_queries) and
+    /// [`Embedder::embed_documents`](crate::embeddings::embed::Embedder::embed_documents) use the
+    /// `RetrievalQuery` and `RetrievalPassage` adapters whatever task is selected.
+    pub fn with_task(mut self, task: JinaTask) -> Result<Self, E> {
+        let (task_id, prompt) = self
+            .task(task)
+            .map(|(task_id, prompt)| (task_id, prompt.to_string()))
+            .ok_or_else(|| {
+                EmbedError::InvalidConfig(format!(
+                    "the model has no `{}` task adapter",
+                    task.as_str()
+                ))
+            })?;
+        self.task_id = task_id;
+        self.prompt = prompt;
+        Ok(self)
+    }
+
+    /// The adapter index and instruction of `task`, if the model has that adapter.
+    fn task(&self, task: JinaTask) -> Option<(i64, &str)> {
+        let adaptations = &self.task_config.lora_adaptations;
+        let task_id = if adaptations.is_empty() && self.version == "v3" {
+            V3_LORA_ADAPTATIONS
+                .iter()
+                .position(|&adaptation| adaptation == task.as_str())
+        } else {
+            adaptations
+                .iter()
+                .position(|adaptation| adaptation == task.as_str())
+        }?;
+        let prompt = self
+            .task_config
+            .task_instructions
+            .get(task.as_str())
+            .map_or("", String::as_str);
+        Some((task_id as i64, prompt))
+    }
+
+    /// Embeds with `task` instead of the selected task, if the model has its adapter.
+    fn embed_as(
+        &self,
+        task: JinaTask,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        match self.task(task) {
+            Some((task_id, prompt)) if self.version == "v3" => {
+                self.embed_with_task(text_batch, batch_size, task_id, prompt)
+            }
+            _ => self.embed(text_batch, batch_size),
+        }
     }
 
     fn tokenize_batch(&self, text_batch: &[String]) -> Result<Array2<i64>, E> {
         let token_ids = self
             .tokenizer
             .encode_batch(text_batch.to_vec(), true)
-            .map_err(E::msg)?
+            .map_err(EmbedError::tokenization)?
             .iter()
             .map(|tokens| {
                 tokens
@@ -206,16 +368,56 @@ impl OrtJinaEmbedder {
 }
 
 impl JinaEmbed for OrtJinaEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[String],
         batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        self.embed_with_task(text_batch, batch_size, self.task_id, &self.prompt)
+    }
+
+    fn embed_query(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        self.embed_as(JinaTask::RetrievalQuery, text_batch, batch_size)
+    }
+
+    fn embed_document(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        self.embed_as(JinaTask::RetrievalPassage, text_batch, batch_size)
+    }
+}
+
+impl OrtJinaEmbedder {
+    fn embed_with_task(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+        task_id: i64,
+        prompt: &str,
     ) -> Result<Vec<EmbeddingResult>, E> {
         let batch_size = batch_size.unwrap_or(32);
         let encodings = text_batch
             .par_chunks(batch_size)
-            .flat_map(|mini_text_batch| -> Result<Vec<Vec<f32>>, E> {
-                let token_ids: Array2<i64> = self.tokenize_batch(mini_text_batch)?;
+            .map(|mini_text_batch| -> Result<Vec<Vec<f32>>, E> {
+                let token_ids: Array2<i64> = if prompt.is_empty() {
+                    self.tokenize_batch(mini_text_batch)?
+                } else {
+                    let prompted = mini_text_batch
+                        .iter()
+                        .map(|text| format!("{}{}", prompt, text))
+                        .collect::<Vec<_>>();
+                    self.tokenize_batch(&prompted)?
+                };
                 let token_type_ids: Array2<i64> = Array2::zeros(token_ids.raw_dim());
                 let attention_mask: Array2<i64> = Array2::ones(token_ids.raw_dim());
 
@@ -223,7 +425,7 @@ impl JinaEmbed for OrtJinaEmbedder {
                     let outputs = self.session.run(ort::inputs! {
                         "input_ids" => token_ids,
                         "attention_mask" => attention_mask,
-                        "task_id" => Array1::<i64>::from_vec(vec![4])
+                        "task_id" => Array1::<i64>::from_vec(vec![task_id])
                     }?)?;
                     outputs["text_embeds"]
                         .try_extract_tensor::<f32>()?
@@ -251,6 +453,8 @@ impl JinaEmbed for OrtJinaEmbedder {
 
                 Ok(embeddings.outer_iter().map(|row| row.to_vec()).collect())
             })
+            .collect::<Result<Vec<_>, E>>()?
+            .into_iter()
             .flatten()
             .collect::<Vec<_>>();
 
@@ -275,6 +479,7 @@ impl JinaEmbed for OrtJinaEmbedder {
 pub struct JinaEmbedder {
     pub model: BertModel,
     pub tokenizer: Tokenizer,
+    pub timer: Option<StageTimer>,
 }
 
 impl Default for JinaEmbedder {
@@ -284,29 +489,53 @@ impl Default for JinaEmbedder {
 }
 
 impl JinaEmbedder {
+    /// Loads a JinaBERT model from the Hugging Face Hub in F32.
     pub fn new(model_id: &str, revision: Option<&str>) -> Result<Self, E> {
-        let api = hf_hub::api::sync::Api::new()?;
-        let api = match revision {
-            Some(rev) => api.repo(Repo::with_revision(
-                model_id.to_string(),
-                hf_hub::RepoType::Model,
-                rev.to_string(),
-            )),
-            None => api.repo(Repo::new(model_id.to_string(), hf_hub::RepoType::Model)),
-        };
+        Self::new_with_dtype(model_id, revision, None, false)
+    }
+
+    /// Like [`new`](Self::new), but with the compute precision. `dtype` selects F16/BF16 on GPUs
+    /// that support it. `use_flash_attn` swaps the attention for the fused flash-attn kernel; it
+    /// needs the `flash-attn` feature, a CUDA device and a half precision dtype, and falls back to
+    /// the regular attention otherwise.
+    pub fn new_with_dtype(
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+        use_flash_attn: bool,
+    ) -> Result<Self, E> {
+        Self::new_on_device(
+            model_id,
+            revision,
+            dtype,
+            use_flash_attn,
+            ComputeDevice::Auto,
+        )
+    }
+
+    /// Like [`new_with_dtype`](Self::new_with_dtype), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+        use_flash_attn: bool,
+        device: ComputeDevice,
+    ) -> Result<Self, E> {
+        let api = HubRepo::new(model_id, revision)?;
 
         let config_filename = api.get("config.json")?;
         let tokenizer_filename = api.get("tokenizer.json")?;
         let mut tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
         let config = std::fs::read_to_string(config_filename)?;
         let config: Config = serde_json::from_str(&config)?;
-        let device = select_device();
+        let device = device.candle_device()?;
+        let dtype = select_dtype(dtype, &device);
         let vb = match api.get("model.safetensors") {
             Ok(safetensors) => unsafe {
-                VarBuilder::from_mmaped_safetensors(&[safetensors], DType::F32, &device)?
+                VarBuilder::from_mmaped_safetensors(&[safetensors], dtype, &device)?
             },
             Err(_) => match api.get("pytorch_model.bin") {
-                Ok(pytorch_model) => VarBuilder::from_pth(pytorch_model, DType::F32, &device)?,
+                Ok(pytorch_model) => VarBuilder::from_pth(pytorch_model, dtype, &device)?,
                 Err(e) => {
                     return Err(anyhow::Error::msg(format!(
                         "Model weights not found. The weights should either be a `model.safetensors` or `pytorch_model.bin` file.  Error: {}",
@@ -315,21 +544,43 @@ impl JinaEmbedder {
                 }
             },
         };
-        let model = BertModel::new(vb, &config)?;
+        let use_flash_attn = if !use_flash_attn {
+            false
+        } else if !cfg!(feature = "flash-attn") {
+            tracing::warn!("Flash attention requires the `flash-attn` feature, falling back to regular attention");
+            false
+        } else if !device.is_cuda() || dtype == DType::F32 {
+            tracing::warn!("Flash attention requires a CUDA device and F16/BF16 dtype, falling back to regular attention");
+            false
+        } else {
+            true
+        };
+        let model = BertModel::new(vb, &config, use_flash_attn)?;
         // let mut tokenizer = Self::get_tokenizer(None)?;
         let pp = tokenizers::PaddingParams {
             strategy: tokenizers::PaddingStrategy::BatchLongest,
             ..Default::default()
         };
         tokenizer.with_padding(Some(pp));
-        Ok(Self { model, tokenizer })
+        Ok(Self {
+            model,
+            tokenizer,
+            timer: None,
+        })
+    }
+
+    /// Records how long tokenization, the forward pass, pooling and normalization take, see
+    /// [`JinaEmbed::timing_report`].
+    pub fn with_timing(mut self) -> Self {
+        self.timer = Some(StageTimer::new());
+        self
     }
 
     pub fn tokenize_batch(&self, text_batch: &[String], device: &Device) -> anyhow::Result<Tensor> {
         let tokens = self
             .tokenizer
             .encode_batch(text_batch.to_vec(), true)
-            .map_err(E::msg)?;
+            .map_err(EmbedError::tokenization)?;
         let token_ids = tokens
             .iter()
             .map(|tokens| {
@@ -347,20 +598,31 @@ impl JinaEmbedder {
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
         let mut encodings: Vec<EmbeddingResult> = Vec::new();
         let batch_size = batch_size.unwrap_or(32);
+        let timer = self.timer.as_ref();
+        let device = &self.model.device;
         for mini_text_batch in text_batch.chunks(batch_size) {
-            let token_ids = self
-                .tokenize_batch(mini_text_batch, &self.model.device)
-                .unwrap();
-            let embeddings = self.model.forward(&token_ids).unwrap();
-            let (_n_sentence, n_tokens, _hidden_size) = embeddings.dims3().unwrap();
-
-            let embeddings = (embeddings.sum(1).unwrap() / (n_tokens as f64)).unwrap();
-            let embeddings = normalize_l2(&embeddings).unwrap();
-
-            // Avoid using to_vec2() and instead work with the Tensor directly
-            encodings.extend((0..embeddings.dim(0)?).map(|i| {
-                EmbeddingResult::DenseVector(embeddings.get(i).unwrap().to_vec1().unwrap())
-            }));
+            let token_ids = timed_on_device(timer, Stage::Tokenize, device, || {
+                self.tokenize_batch(mini_text_batch, device)
+            })?;
+            let embeddings = timed_on_device(timer, Stage::Inference, device, || {
+                Ok(self.model.forward(&token_ids)?.to_dtype(DType::F32)?)
+            })?;
+            let embeddings = timed_on_device(timer, Stage::Pooling, device, || {
+                let (_n_sentence, n_tokens, _hidden_size) = embeddings.dims3()?;
+                Ok((embeddings.sum(1)? / (n_tokens as f64))?)
+            })?;
+
+            timed(timer, Stage::Normalize, || -> anyhow::Result<()> {
+                let embeddings = normalize_l2(&embeddings)?;
+                // Avoid using to_vec2() and instead work with the Tensor directly
+                for i in 0..embeddings.dim(0)? {
+                    encodings.push(EmbeddingResult::DenseVector(embeddings.get(i)?.to_vec1()?));
+                }
+                Ok(())
+            })?;
+            if let Some(timer) = timer {
+                timer.batch(mini_text_batch.len());
+            }
         }
 
         Ok(encodings)
@@ -368,6 +630,10 @@ impl JinaEmbedder {
 }
 
 impl JinaEmbed for JinaEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[String],
@@ -375,12 +641,28 @@ impl JinaEmbed for JinaEmbedder {
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
         self.embed(text_batch, batch_size)
     }
+
+    fn timer(&self) -> Option<&StageTimer> {
+        self.timer.as_ref()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_jina_task_from_str() {
+        use std::str::FromStr;
+
+        assert_eq!(
+            JinaTask::from_str("retrieval.query").unwrap(),
+            JinaTask::RetrievalQuery
+        );
+        assert_eq!(JinaTask::TextMatching.as_str(), "text-matching");
+        assert!(JinaTask::from_str("retrieval").is_err());
+    }
+
     #[test]
     fn test_embed() {
         let embedder = JinaEmbedder::new("jinaai/jina-embeddings-v2-small-en", None).unwrap();
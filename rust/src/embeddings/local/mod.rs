@@ -1,9 +1,14 @@
 pub mod bert;
+pub mod bge_m3;
 pub mod clip;
 pub mod colbert;
 pub mod colpali;
 pub mod colpali_ort;
+pub mod io_binding;
 pub mod jina;
 pub mod model_info;
 pub mod pooling;
+pub mod providers;
+pub mod resnet;
 pub mod text_embedding;
+pub mod token_pooling;
@@ -1,12 +1,136 @@
-use candle_core::Tensor;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use candle_core::{DType, Tensor};
 use ndarray::prelude::*;
 use ndarray::{Array2, Array3};
+use tokenizers::{Encoding, Tokenizer};
+
+use crate::error::EmbedError;
 
 #[derive(Debug, Clone, Default)]
 pub enum Pooling {
     #[default]
     Mean,
     Cls,
+    /// Weighted mean of the token embeddings. Padding and special tokens get no weight.
+    Weighted(TokenWeights),
+}
+
+/// Per-token weights for [`Pooling::Weighted`], keyed by token id. Tokens without an entry get the
+/// default weight, which is the weight of a token never seen in the corpus the weights came from.
+#[derive(Debug, Clone)]
+pub struct TokenWeights {
+    weights: Arc<HashMap<u32, f32>>,
+    default: f32,
+}
+
+impl TokenWeights {
+    pub fn new(weights: HashMap<u32, f32>, default: f32) -> Self {
+        Self {
+            weights: Arc::new(weights),
+            default,
+        }
+    }
+
+    /// Smoothed IDF weights, `ln((1 + n_docs) / (1 + df)) + 1`, from the number of documents each
+    /// token appears in.
+    pub fn idf(document_frequencies: &HashMap<u32, usize>, n_docs: usize) -> Self {
+        let idf = |df: usize| ((1.0 + n_docs as f32) / (1.0 + df as f32)).ln() + 1.0;
+        let weights = document_frequencies
+            .iter()
+            .map(|(&id, &df)| (id, idf(df)))
+            .collect();
+        Self::new(weights, idf(0))
+    }
+
+    /// SIF weights, `a / (a + p(w))`, from the number of times each token occurs. `a` is usually
+    /// between `1e-4` and `1e-3`.
+    pub fn sif(token_counts: &HashMap<u32, usize>, a: f32) -> Self {
+        let total = token_counts.values().sum::<usize>().max(1) as f32;
+        let weights = token_counts
+            .iter()
+            .map(|(&id, &count)| (id, a / (a + count as f32 / total)))
+            .collect();
+        Self::new(weights, 1.0)
+    }
+
+    /// Tokenizes `corpus` with the model's tokenizer and builds [`TokenWeights::idf`] from it.
+    pub fn idf_from_corpus(tokenizer: &Tokenizer, corpus: &[String]) -> anyhow::Result<Self> {
+        let mut document_frequencies = HashMap::new();
+        for encoding in Self::encode_corpus(tokenizer, corpus)? {
+            let mut ids = encoding.get_ids().to_vec();
+            ids.sort_unstable();
+            ids.dedup();
+            for id in ids {
+                *document_frequencies.entry(id).or_insert(0) += 1;
+            }
+        }
+        Ok(Self::idf(&document_frequencies, corpus.len()))
+    }
+
+    /// Tokenizes `corpus` with the model's tokenizer and builds [`TokenWeights::sif`] from it.
+    pub fn sif_from_corpus(
+        tokenizer: &Tokenizer,
+        corpus: &[String],
+        a: f32,
+    ) -> anyhow::Result<Self> {
+        let mut token_counts = HashMap::new();
+        for encoding in Self::encode_corpus(tokenizer, corpus)? {
+            for &id in encoding.get_ids() {
+                *token_counts.entry(id).or_insert(0) += 1;
+            }
+        }
+        Ok(Self::sif(&token_counts, a))
+    }
+
+    pub fn weight(&self, token_id: u32) -> f32 {
+        self.weights.get(&token_id).copied().unwrap_or(self.default)
+    }
+
+    fn encode_corpus(tokenizer: &Tokenizer, corpus: &[String]) -> anyhow::Result<Vec<Encoding>> {
+        Ok(tokenizer
+            .encode_batch(corpus.to_vec(), false)
+            .map_err(EmbedError::tokenization)?)
+    }
+
+    /// Builds the `(batch, tokens)` weight matrix for a padded batch.
+    fn matrix(&self, encodings: &[Encoding]) -> anyhow::Result<Array2<f32>> {
+        let width = encodings.first().map_or(0, |encoding| encoding.len());
+        let values = encodings
+            .iter()
+            .flat_map(|encoding| {
+                encoding
+                    .get_ids()
+                    .iter()
+                    .zip(encoding.get_attention_mask())
+                    .zip(encoding.get_special_tokens_mask())
+                    .map(|((&id, &mask), &special)| {
+                        if mask == 0 || special == 1 {
+                            0.0
+                        } else {
+                            self.weight(id)
+                        }
+                    })
+            })
+            .collect::<Vec<f32>>();
+        Ok(Array2::from_shape_vec((encodings.len(), width), values)?)
+    }
+}
+
+/// Builds the `(batch, tokens)` attention mask of a padded batch as mean pooling weights.
+fn attention_mask_matrix(encodings: &[Encoding]) -> anyhow::Result<Array2<f32>> {
+    let width = encodings.first().map_or(0, |encoding| encoding.len());
+    let values = encodings
+        .iter()
+        .flat_map(|encoding| {
+            encoding
+                .get_attention_mask()
+                .iter()
+                .map(|&mask| mask as f32)
+        })
+        .collect::<Vec<f32>>();
+    Ok(Array2::from_shape_vec((encodings.len(), width), values)?)
 }
 
 #[derive(Debug, Clone)]
@@ -41,6 +165,24 @@ impl Pooling {
         match self {
             Pooling::Cls => Self::cls(output),
             Pooling::Mean => Self::mean(output),
+            Pooling::Weighted(_) => Err(anyhow::anyhow!(
+                "Weighted pooling needs the token ids of the batch, use pool_encodings"
+            )),
+        }
+    }
+
+    /// Pools `output` for the padded batch tokenized into `encodings`. [`Pooling::Mean`] averages
+    /// only the tokens the attention mask keeps, so a text pools to the same vector whatever it is
+    /// batched with; [`Pooling::Cls`] behaves like [`Pooling::pool`].
+    pub fn pool_encodings(
+        &self,
+        output: &ModelOutput,
+        encodings: &[Encoding],
+    ) -> Result<PooledOutput, anyhow::Error> {
+        match self {
+            Pooling::Mean => Self::weighted(output, &attention_mask_matrix(encodings)?),
+            Pooling::Weighted(weights) => Self::weighted(output, &weights.matrix(encodings)?),
+            Pooling::Cls => self.pool(output),
         }
     }
 
@@ -56,10 +198,13 @@ impl Pooling {
         }
     }
 
+    // Sums over the tokens are accumulated in F32 whatever the compute dtype, since F16 and BF16
+    // sums drift over long sequences. The pooled tensor is F32.
     fn mean(output: &ModelOutput) -> Result<PooledOutput, anyhow::Error> {
         match output {
             ModelOutput::Tensor(tensor) => tensor
-                .mean(1)
+                .to_dtype(DType::F32)
+                .and_then(|tensor| tensor.mean(1))
                 .map(PooledOutput::Tensor)
                 .map_err(|_| anyhow::anyhow!("Mean of empty tensor")),
             ModelOutput::Array(array) => array
@@ -68,4 +213,173 @@ impl Pooling {
                 .ok_or_else(|| anyhow::anyhow!("Mean of empty array")),
         }
     }
+
+    fn weighted(
+        output: &ModelOutput,
+        weights: &Array2<f32>,
+    ) -> Result<PooledOutput, anyhow::Error> {
+        // A row whose tokens all weigh nothing pools to zeros instead of NaN.
+        let totals = weights
+            .sum_axis(Axis(1))
+            .mapv(|total| total.max(f32::EPSILON));
+        match output {
+            ModelOutput::Tensor(tensor) => {
+                let (batch, tokens) = weights.dim();
+                let device = tensor.device();
+                let weights = Tensor::from_iter(weights.iter().copied(), device)?
+                    .reshape((batch, tokens, 1))?;
+                let totals = Tensor::from_vec(totals.to_vec(), (batch, 1), device)?;
+                let pooled = tensor
+                    .to_dtype(DType::F32)?
+                    .broadcast_mul(&weights)?
+                    .sum(1)?
+                    .broadcast_div(&totals)?;
+                Ok(PooledOutput::Tensor(pooled))
+            }
+            ModelOutput::Array(array) => {
+                let pooled = (array * &weights.view().insert_axis(Axis(2))).sum_axis(Axis(1));
+                Ok(PooledOutput::Array(pooled / &totals.insert_axis(Axis(1))))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_pooling_ignores_zero_weight_tokens() {
+        let output = ModelOutput::Array(
+            Array3::from_shape_vec((1, 3, 2), vec![1.0, 1.0, 3.0, 5.0, 100.0, 100.0]).unwrap(),
+        );
+        let weights = Array2::from_shape_vec((1, 3), vec![1.0, 3.0, 0.0]).unwrap();
+        let pooled = Pooling::weighted(&output, &weights)
+            .unwrap()
+            .to_array()
+            .unwrap();
+        assert_eq!(pooled.row(0).to_vec(), vec![2.5, 4.0]);
+    }
+
+    fn padded_encoding(ids: &[u32], width: usize) -> Encoding {
+        let tokens = ids
+            .iter()
+            .map(|&id| tokenizers::Token::new(id, id.to_string(), (0, 0)))
+            .collect();
+        let mut encoding = Encoding::from_tokens(tokens, 0);
+        encoding.pad(width, 0, 0, "[PAD]", tokenizers::PaddingDirection::Right);
+        encoding
+    }
+
+    #[test]
+    fn test_mean_pooling_skips_padding() {
+        // The second text is padded with one token, whose hidden state must not pull its mean.
+        let encodings = [padded_encoding(&[1, 2, 3], 3), padded_encoding(&[1, 2], 3)];
+        let values = vec![
+            1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 1.0, 1.0, 3.0, 5.0, 100.0, 100.0,
+        ];
+        let expected: Vec<Vec<f32>> = vec![vec![3.0, 4.0], vec![2.0, 3.0]];
+
+        let array = Array3::from_shape_vec((2, 3, 2), values.clone()).unwrap();
+        let pooled = Pooling::Mean
+            .pool_encodings(&ModelOutput::Array(array), &encodings)
+            .unwrap()
+            .to_array()
+            .unwrap();
+        assert_eq!(
+            pooled
+                .outer_iter()
+                .map(|row| row.to_vec())
+                .collect::<Vec<_>>(),
+            expected
+        );
+
+        let tensor = Tensor::from_vec(values, (2, 3, 2), &candle_core::Device::Cpu).unwrap();
+        let pooled = Pooling::Mean
+            .pool_encodings(&ModelOutput::Tensor(tensor), &encodings)
+            .unwrap()
+            .to_tensor()
+            .unwrap()
+            .to_vec2::<f32>()
+            .unwrap();
+        assert_eq!(pooled, expected);
+    }
+
+    // Reference values computed in f64 from the same (f16-rounded) token embeddings.
+    fn reference_mean(values: &[f32], tokens: usize, dim: usize) -> Vec<f32> {
+        (0..dim)
+            .map(|d| {
+                let sum = (0..tokens).map(|t| values[t * dim + d] as f64).sum::<f64>();
+                (sum / tokens as f64) as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_mean_pooling_accumulates_in_f32() {
+        let (tokens, dim) = (4096, 4);
+        let values = (0..tokens * dim)
+            .map(|i| half::f16::from_f32(1.0 + (i % 7) as f32 * 0.37).to_f32())
+            .collect::<Vec<_>>();
+        let expected = reference_mean(&values, tokens, dim);
+
+        for dtype in [DType::F16, DType::BF16, DType::F32] {
+            let tensor =
+                Tensor::from_vec(values.clone(), (1, tokens, dim), &candle_core::Device::Cpu)
+                    .unwrap()
+                    .to_dtype(dtype)
+                    .unwrap();
+            let pooled = Pooling::Mean
+                .pool(&ModelOutput::Tensor(tensor))
+                .unwrap()
+                .to_tensor()
+                .unwrap();
+            assert_eq!(pooled.dtype(), DType::F32);
+            let pooled = pooled.to_vec2::<f32>().unwrap();
+            for (pooled, expected) in pooled[0].iter().zip(&expected) {
+                // BF16 keeps fewer mantissa bits of the inputs, which bounds its accuracy.
+                let tolerance = if dtype == DType::BF16 { 2e-2 } else { 1e-4 };
+                assert!(
+                    (pooled - expected).abs() < tolerance,
+                    "{:?}: {} vs {}",
+                    dtype,
+                    pooled,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_weighted_pooling_accumulates_in_f32() {
+        let (tokens, dim) = (2048, 2);
+        let values = (0..tokens * dim)
+            .map(|i| half::f16::from_f32(0.5 + (i % 5) as f32 * 0.21).to_f32())
+            .collect::<Vec<_>>();
+        let weights = Array2::ones((1, tokens));
+        let expected = reference_mean(&values, tokens, dim);
+
+        let tensor = Tensor::from_vec(values, (1, tokens, dim), &candle_core::Device::Cpu)
+            .unwrap()
+            .to_dtype(DType::F16)
+            .unwrap();
+        let pooled = Pooling::weighted(&ModelOutput::Tensor(tensor), &weights)
+            .unwrap()
+            .to_tensor()
+            .unwrap()
+            .to_vec2::<f32>()
+            .unwrap();
+        for (pooled, expected) in pooled[0].iter().zip(&expected) {
+            assert!((pooled - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_idf_weights_favour_rare_tokens() {
+        let document_frequencies = HashMap::from([(1, 10), (2, 1)]);
+        let weights = TokenWeights::idf(&document_frequencies, 10);
+        assert!(weights.weight(2) > weights.weight(1));
+        assert!(weights.weight(3) > weights.weight(2));
+        assert!((weights.weight(1) - 1.0).abs() < 1e-6);
+    }
 }
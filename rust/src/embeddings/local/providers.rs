@@ -0,0 +1,95 @@
+//! The ONNX Runtime execution providers the ONNX models are run with.
+
+use std::path::Path;
+
+use ort::execution_providers::{
+    CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProviderDispatch,
+};
+#[cfg(feature = "rocm")]
+use ort::execution_providers::{ExecutionProvider, ROCmExecutionProvider};
+use ort::session::builder::GraphOptimizationLevel;
+use ort::session::Session;
+
+use crate::embeddings::ComputeDevice;
+
+/// Execution providers for ONNX sessions on `device`, in order of preference. ORT skips a
+/// provider that is not available and falls back to the CPU when none of them is.
+pub fn execution_providers(device: ComputeDevice) -> Vec<ExecutionProviderDispatch> {
+    match device {
+        ComputeDevice::Auto => {
+            let mut providers = vec![CUDAExecutionProvider::default().build()];
+            #[cfg(feature = "rocm")]
+            providers.push(ROCmExecutionProvider::default().build());
+            providers.push(CoreMLExecutionProvider::default().build());
+            providers
+        }
+        ComputeDevice::Cpu => Vec::new(),
+        ComputeDevice::Cuda(ordinal) => vec![CUDAExecutionProvider::default()
+            .with_device_id(ordinal as i32)
+            .build()],
+        ComputeDevice::Metal(_) => vec![CoreMLExecutionProvider::default().build()],
+    }
+}
+
+/// Loads the ONNX model at `weights` on `device` with all graph optimizations. With `profiling`,
+/// ORT records every operator and writes a Chrome trace JSON file, named `profiling` followed by
+/// the start time, when the session is dropped.
+pub fn build_session(
+    weights: &Path,
+    device: ComputeDevice,
+    profiling: Option<&Path>,
+) -> anyhow::Result<Session> {
+    let threads = std::thread::available_parallelism()?.get();
+    let mut builder = Session::builder()?
+        .with_execution_providers(execution_providers(device))?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_intra_threads(threads)?;
+    if let Some(profiling) = profiling {
+        builder = builder.with_profiling(profiling)?;
+    }
+    Ok(builder.commit_from_file(weights)?)
+}
+
+/// Whether ORT can run sessions on an AMD GPU through ROCm. Always false without the `rocm`
+/// feature. Candle has no ROCm backend, so this only applies to the ONNX models.
+pub fn rocm_available() -> bool {
+    #[cfg(feature = "rocm")]
+    {
+        ROCmExecutionProvider::default()
+            .is_available()
+            .unwrap_or(false)
+    }
+    #[cfg(not(feature = "rocm"))]
+    {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::local::bert::{BertEmbed, OrtBertEmbedder};
+    use crate::embeddings::local::text_embedding::ONNXModel;
+
+    // Needs an AMD GPU, so it only runs when EMBED_ANYTHING_TEST_ROCM is set.
+    #[test]
+    fn test_rocm_smoke() {
+        if std::env::var_os("EMBED_ANYTHING_TEST_ROCM").is_none() {
+            return;
+        }
+        assert!(rocm_available());
+        let embedder =
+            OrtBertEmbedder::new(Some(ONNXModel::BGESmallENV15), None, None, None, None).unwrap();
+        let embeddings = embedder
+            .embed(&["Hello, world!".to_string()], None)
+            .unwrap();
+        assert_eq!(embeddings[0].to_dense().unwrap().len(), 384);
+    }
+
+    #[test]
+    fn test_execution_providers() {
+        assert!(execution_providers(ComputeDevice::Cpu).is_empty());
+        assert_eq!(execution_providers(ComputeDevice::Cuda(1)).len(), 1);
+        assert!(!execution_providers(ComputeDevice::Auto).is_empty());
+    }
+}
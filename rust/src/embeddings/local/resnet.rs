@@ -0,0 +1,361 @@
+//! Image embeddings from the pooled features of a ResNet classifier, the 512 (ResNet-18/34) or
+//! 2048 (ResNet-50/101/152) dimensional output of its global average pool. Loads timm checkpoints
+//! such as `timm/resnet50.a1_in1k` and transformers checkpoints such as `microsoft/resnet-50`.
+
+use std::collections::HashMap;
+
+use anyhow::Error as E;
+use candle_core::{DType, Device, Module, Tensor};
+use candle_nn::{Func, VarBuilder};
+use candle_transformers::models::resnet;
+use image::DynamicImage;
+use serde::Deserialize;
+
+use crate::embeddings::embed::{EmbedData, EmbedImage, EmbeddingResult};
+use crate::embeddings::{select_dtype, ComputeDevice};
+use crate::error::EmbedError;
+use crate::hub::HubRepo;
+use crate::Dtype;
+
+const IMAGE_SIZE: usize = 224;
+const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const IMAGENET_STD: [f32; 3] = [0.229, 0.224, 0.225];
+const BATCH_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResNetDepth {
+    ResNet18,
+    ResNet34,
+    ResNet50,
+    ResNet101,
+    ResNet152,
+}
+
+impl ResNetDepth {
+    /// The width of the pooled features.
+    pub fn hidden_size(&self) -> usize {
+        match self {
+            ResNetDepth::ResNet18 | ResNetDepth::ResNet34 => 512,
+            _ => 2048,
+        }
+    }
+
+    fn backbone(&self, vb: VarBuilder) -> candle_core::Result<Func<'static>> {
+        match self {
+            ResNetDepth::ResNet18 => resnet::resnet18_no_final_layer(vb),
+            ResNetDepth::ResNet34 => resnet::resnet34_no_final_layer(vb),
+            ResNetDepth::ResNet50 => resnet::resnet50_no_final_layer(vb),
+            ResNetDepth::ResNet101 => resnet::resnet101_no_final_layer(vb),
+            ResNetDepth::ResNet152 => resnet::resnet152_no_final_layer(vb),
+        }
+    }
+}
+
+/// The fields of a timm or a transformers `config.json` that tell the ResNet variant apart.
+#[derive(Debug, Default, Deserialize)]
+struct ResNetConfig {
+    /// timm, e.g. `resnet50`.
+    architecture: Option<String>,
+    /// transformers, e.g. `ResNetForImageClassification`.
+    #[serde(default)]
+    architectures: Vec<String>,
+    model_type: Option<String>,
+    depths: Option<Vec<usize>>,
+    layer_type: Option<String>,
+}
+
+impl ResNetConfig {
+    fn is_transformers(&self) -> bool {
+        self.model_type.as_deref() == Some("resnet")
+    }
+
+    fn depth(&self) -> Result<ResNetDepth, EmbedError> {
+        let depth = if self.is_transformers() {
+            match (
+                self.layer_type.as_deref().unwrap_or("bottleneck"),
+                self.depths.as_deref(),
+            ) {
+                ("basic", Some([2, 2, 2, 2])) => Some(ResNetDepth::ResNet18),
+                ("basic", Some([3, 4, 6, 3])) => Some(ResNetDepth::ResNet34),
+                ("bottleneck", Some([3, 4, 6, 3])) => Some(ResNetDepth::ResNet50),
+                ("bottleneck", Some([3, 4, 23, 3])) => Some(ResNetDepth::ResNet101),
+                ("bottleneck", Some([3, 8, 36, 3])) => Some(ResNetDepth::ResNet152),
+                _ => None,
+            }
+        } else {
+            match self.architecture.as_deref() {
+                Some("resnet18") => Some(ResNetDepth::ResNet18),
+                Some("resnet34") => Some(ResNetDepth::ResNet34),
+                Some("resnet50") => Some(ResNetDepth::ResNet50),
+                Some("resnet101") => Some(ResNetDepth::ResNet101),
+                Some("resnet152") => Some(ResNetDepth::ResNet152),
+                _ => None,
+            }
+        };
+        depth.ok_or_else(|| {
+            EmbedError::UnsupportedModel(format!(
+                "Only ResNet-18/34/50/101/152 backbones are supported, got {}",
+                self.architecture
+                    .clone()
+                    .or_else(|| self.architectures.first().cloned())
+                    .unwrap_or_else(|| "an unknown architecture".to_string())
+            ))
+        })
+    }
+}
+
+/// Maps a timm tensor name, which candle's ResNet uses, to the name transformers gives it, e.g.
+/// `layer2.0.downsample.1.weight` to `encoder.stages.1.layers.0.shortcut.normalization.weight`.
+fn transformers_tensor_name(name: &str) -> String {
+    let parts = name.split('.').collect::<Vec<_>>();
+    let layer = |module: &str| match module {
+        "downsample.0" => Some("shortcut.convolution".to_string()),
+        "downsample.1" => Some("shortcut.normalization".to_string()),
+        _ => {
+            let index = module
+                .strip_prefix("conv")
+                .map(|index| (index, "convolution"))
+                .or_else(|| {
+                    module
+                        .strip_prefix("bn")
+                        .map(|index| (index, "normalization"))
+                });
+            let (index, kind) = index?;
+            Some(format!(
+                "layer.{}.{}",
+                index.parse::<usize>().ok()? - 1,
+                kind
+            ))
+        }
+    };
+    match parts.as_slice() {
+        ["conv1", param] => format!("embedder.embedder.convolution.{param}"),
+        ["bn1", param] => format!("embedder.embedder.normalization.{param}"),
+        [stage, block, module @ .., param] if stage.starts_with("layer") => {
+            let stage = stage["layer".len()..].parse::<usize>().unwrap_or(1) - 1;
+            match layer(&module.join(".")) {
+                Some(layer) => format!("encoder.stages.{stage}.layers.{block}.{layer}.{param}"),
+                None => name.to_string(),
+            }
+        }
+        _ => name.to_string(),
+    }
+}
+
+pub struct ResNetEmbedder {
+    model: Func<'static>,
+    pub depth: ResNetDepth,
+    pub device: Device,
+    pub dtype: DType,
+}
+
+impl ResNetEmbedder {
+    /// Loads a ResNet model from the Hugging Face Hub.
+    pub fn new(model_id: &str, revision: Option<&str>, dtype: Option<Dtype>) -> Result<Self, E> {
+        Self::new_on_device(model_id, revision, dtype, ComputeDevice::Auto)
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Option<Dtype>,
+        device: ComputeDevice,
+    ) -> Result<Self, E> {
+        let api = HubRepo::new(model_id, revision)?;
+        let device = device.candle_device()?;
+        let dtype = select_dtype(dtype, &device);
+
+        let config: ResNetConfig =
+            serde_json::from_str(&std::fs::read_to_string(api.get("config.json")?)?)?;
+        let depth = config.depth()?;
+
+        let vb = match api.get("model.safetensors") {
+            Ok(safetensors) => unsafe {
+                VarBuilder::from_mmaped_safetensors(&[safetensors], dtype, &device)?
+            },
+            Err(_) => VarBuilder::from_pth(api.get("pytorch_model.bin")?, dtype, &device)?,
+        };
+        let vb = if config.is_transformers() {
+            // Image classification checkpoints keep the backbone under `resnet.`.
+            let prefix = if config
+                .architectures
+                .iter()
+                .any(|architecture| architecture == "ResNetForImageClassification")
+            {
+                "resnet."
+            } else {
+                ""
+            };
+            vb.rename_f(move |name| format!("{}{}", prefix, transformers_tensor_name(name)))
+        } else {
+            vb
+        };
+        let model = depth.backbone(vb)?;
+
+        Ok(Self {
+            model,
+            depth,
+            device,
+            dtype,
+        })
+    }
+
+    fn image_to_tensor(&self, image: &DynamicImage) -> anyhow::Result<Tensor> {
+        let image = image
+            .resize_to_fill(
+                IMAGE_SIZE as u32,
+                IMAGE_SIZE as u32,
+                image::imageops::FilterType::Triangle,
+            )
+            .to_rgb8()
+            .into_raw();
+        let mean = Tensor::new(&IMAGENET_MEAN, &self.device)?.reshape((3, 1, 1))?;
+        let std = Tensor::new(&IMAGENET_STD, &self.device)?.reshape((3, 1, 1))?;
+        Ok(
+            Tensor::from_vec(image, (IMAGE_SIZE, IMAGE_SIZE, 3), &self.device)?
+                .permute((2, 0, 1))?
+                .to_dtype(DType::F32)?
+                .affine(1. / 255., 0.)?
+                .broadcast_sub(&mean)?
+                .broadcast_div(&std)?
+                .to_dtype(self.dtype)?,
+        )
+    }
+
+    fn image_features(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut features = Vec::with_capacity(images.len());
+        for batch in images.chunks(BATCH_SIZE) {
+            let tensors = batch
+                .iter()
+                .map(|image| self.image_to_tensor(image))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let batch_features = self
+                .model
+                .forward(&Tensor::stack(&tensors, 0)?)
+                .map_err(|e| EmbedError::Inference(e.to_string()))?
+                .to_dtype(DType::F32)?
+                .to_vec2::<f32>()?;
+            features.extend(batch_features);
+        }
+        Ok(features)
+    }
+}
+
+impl EmbedImage for ResNetEmbedder {
+    fn embed_image<T: AsRef<std::path::Path>>(
+        &self,
+        image_path: T,
+        metadata: Option<HashMap<String, String>>,
+    ) -> anyhow::Result<EmbedData> {
+        let image = image::ImageReader::open(image_path)?.decode()?;
+        self.embed_image_from(&image, metadata)
+    }
+
+    fn embed_image_batch<T: AsRef<std::path::Path>>(
+        &self,
+        image_paths: &[T],
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        let images = image_paths
+            .iter()
+            .map(|path| Ok(image::ImageReader::open(path)?.decode()?))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let embeddings = self
+            .image_features(&images)?
+            .into_iter()
+            .zip(image_paths)
+            .map(|(features, path)| {
+                let path = path.as_ref().to_string_lossy().to_string();
+                let file_name = std::fs::canonicalize(&path)
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| path.clone());
+                let metadata = HashMap::from([("file_name".to_string(), file_name)]);
+                EmbedData::new(
+                    EmbeddingResult::DenseVector(features),
+                    Some(path),
+                    Some(metadata),
+                )
+            })
+            .collect();
+        Ok(embeddings)
+    }
+
+    fn embed_image_from(
+        &self,
+        image: &DynamicImage,
+        metadata: Option<HashMap<String, String>>,
+    ) -> anyhow::Result<EmbedData> {
+        let features = self
+            .image_features(std::slice::from_ref(image))?
+            .swap_remove(0);
+        Ok(EmbedData::new(
+            EmbeddingResult::DenseVector(features),
+            None,
+            metadata,
+        ))
+    }
+
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        Ok(self
+            .image_features(images)?
+            .into_iter()
+            .map(|features| EmbedData::new(EmbeddingResult::DenseVector(features), None, None))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transformers_tensor_name() {
+        assert_eq!(
+            transformers_tensor_name("conv1.weight"),
+            "embedder.embedder.convolution.weight"
+        );
+        assert_eq!(
+            transformers_tensor_name("bn1.running_var"),
+            "embedder.embedder.normalization.running_var"
+        );
+        assert_eq!(
+            transformers_tensor_name("layer1.1.conv2.weight"),
+            "encoder.stages.0.layers.1.layer.1.convolution.weight"
+        );
+        assert_eq!(
+            transformers_tensor_name("layer4.0.bn3.bias"),
+            "encoder.stages.3.layers.0.layer.2.normalization.bias"
+        );
+        assert_eq!(
+            transformers_tensor_name("layer2.0.downsample.1.running_mean"),
+            "encoder.stages.1.layers.0.shortcut.normalization.running_mean"
+        );
+    }
+
+    #[test]
+    fn test_config_depth() {
+        let transformers: ResNetConfig = serde_json::from_str(
+            r#"{"model_type": "resnet", "layer_type": "basic", "depths": [2, 2, 2, 2],
+                "architectures": ["ResNetForImageClassification"]}"#,
+        )
+        .unwrap();
+        assert_eq!(transformers.depth().unwrap(), ResNetDepth::ResNet18);
+
+        let timm: ResNetConfig =
+            serde_json::from_str(r#"{"architecture": "resnet50", "num_classes": 1000}"#).unwrap();
+        assert_eq!(timm.depth().unwrap(), ResNetDepth::ResNet50);
+
+        let other: ResNetConfig = serde_json::from_str(r#"{"architecture": "resnet50d"}"#).unwrap();
+        assert!(other.depth().is_err());
+    }
+
+    #[test]
+    fn test_embed_image_batch() {
+        let embedder = ResNetEmbedder::new("microsoft/resnet-18", None, None).unwrap();
+        let embeddings = embedder
+            .embed_image_batch(&["test_files/clip/cat1.jpg", "test_files/clip/cat2.jpeg"])
+            .unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert_eq!(embeddings[0].embedding.to_dense().unwrap().len(), 512);
+    }
+}
@@ -81,6 +81,12 @@ pub enum ONNXModel {
     SPLADEPPENV2,
     /// onnx-models/jina-colbert-v1-en-onnx
     JinaColBERTv1,
+    /// BAAI/bge-m3, dense output
+    BGEM3,
+    /// BAAI/bge-m3, sparse (lexical weights) output
+    BGEM3Sparse,
+    /// BAAI/bge-m3, ColBERT multi-vector output
+    BGEM3ColBERT,
 }
 
 /// Centralized function to initialize the models map.
@@ -366,6 +372,30 @@ fn init_models_map() -> HashMap<ONNXModel, ModelInfo<ONNXModel>> {
             model_code: String::from("onnx-models/jina-colbert-v1-en-onnx"),
             model_file: String::from("model.onnx"),
         },
+        ModelInfo {
+            model: ONNXModel::BGEM3,
+            dim: 1024,
+            description: String::from("Multilingual BGE-M3, dense output"),
+            hf_model_id: String::from("BAAI/bge-m3"),
+            model_code: String::from("aapot/bge-m3-onnx"),
+            model_file: String::from("model.onnx"),
+        },
+        ModelInfo {
+            model: ONNXModel::BGEM3Sparse,
+            dim: 250002,
+            description: String::from("Multilingual BGE-M3, sparse lexical weights output"),
+            hf_model_id: String::from("BAAI/bge-m3"),
+            model_code: String::from("aapot/bge-m3-onnx"),
+            model_file: String::from("model.onnx"),
+        },
+        ModelInfo {
+            model: ONNXModel::BGEM3ColBERT,
+            dim: 1024,
+            description: String::from("Multilingual BGE-M3, ColBERT multi-vector output"),
+            hf_model_id: String::from("BAAI/bge-m3"),
+            model_code: String::from("aapot/bge-m3-onnx"),
+            model_file: String::from("model.onnx"),
+        },
     ];
 
     // TODO: Use when out in stable
@@ -452,6 +482,9 @@ impl ONNXModel {
             ONNXModel::SPLADEPPENV1 => Some(Pooling::Mean),
             ONNXModel::SPLADEPPENV2 => Some(Pooling::Mean),
             ONNXModel::JinaColBERTv1 => None,
+            ONNXModel::BGEM3 => Some(Pooling::Cls),
+            ONNXModel::BGEM3Sparse => None,
+            ONNXModel::BGEM3ColBERT => None,
         }
     }
 
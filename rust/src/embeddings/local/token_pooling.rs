@@ -0,0 +1,150 @@
+//! Shrinks multi-vector image embeddings by pooling similar vectors. A ColPali page embedding has
+//! 1030 vectors of 128 dimensions, and indexes store and score every one of them; pooling a page
+//! to a few hundred vectors keeps most of the retrieval quality at a fraction of the size.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use image::DynamicImage;
+
+use super::colpali::ColPaliEmbed;
+use crate::config::ImageEmbedConfig;
+use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+use crate::embeddings::similarity::cosine_similarity;
+
+/// Hierarchically mean-pools `vectors` down to at most `max_vectors`: the two neighbouring groups
+/// of vectors whose means are most similar are merged until few enough are left. Neighbouring
+/// image patches are usually alike, so mostly background and repeated regions get merged. The
+/// pooled vectors keep the order of the groups they come from.
+pub fn pool_tokens(vectors: Vec<Vec<f32>>, max_vectors: usize) -> Vec<Vec<f32>> {
+    let max_vectors = max_vectors.max(1);
+    if vectors.len() <= max_vectors {
+        return vectors;
+    }
+    // Groups are kept as sums, whose cosine similarity is the one of their means.
+    let mut sums = vectors;
+    let mut counts = vec![1usize; sums.len()];
+    let mut similarities = sums
+        .windows(2)
+        .map(|pair| cosine_similarity(&pair[0], &pair[1]))
+        .collect::<Vec<_>>();
+    while sums.len() > max_vectors {
+        let (left, _) = similarities
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap();
+        let right = sums.remove(left + 1);
+        sums[left]
+            .iter_mut()
+            .zip(right)
+            .for_each(|(sum, value)| *sum += value);
+        counts[left] += counts.remove(left + 1);
+        similarities.remove(left);
+        if left > 0 {
+            similarities[left - 1] = cosine_similarity(&sums[left - 1], &sums[left]);
+        }
+        if left < similarities.len() {
+            similarities[left] = cosine_similarity(&sums[left], &sums[left + 1]);
+        }
+    }
+    sums.into_iter()
+        .zip(counts)
+        .map(|(sum, count)| sum.into_iter().map(|value| value / count as f32).collect())
+        .collect()
+}
+
+/// A ColPali model whose image and page embeddings are pooled to at most `max_vectors` vectors
+/// with [`pool_tokens`]. Query embeddings are left as they are, since they are short and every
+/// query token takes part in the late-interaction score.
+pub struct TokenPooledColPali<E: ColPaliEmbed> {
+    inner: E,
+    max_vectors: usize,
+}
+
+impl<E: ColPaliEmbed> TokenPooledColPali<E> {
+    pub fn new(inner: E, max_vectors: usize) -> Self {
+        Self { inner, max_vectors }
+    }
+
+    fn pool(&self, mut embedding: EmbedData) -> EmbedData {
+        if let EmbeddingResult::MultiVector(vectors) = embedding.embedding {
+            embedding.embedding =
+                EmbeddingResult::MultiVector(pool_tokens(vectors, self.max_vectors));
+        }
+        embedding
+    }
+
+    fn pool_all(&self, embeddings: Vec<EmbedData>) -> Vec<EmbedData> {
+        embeddings
+            .into_iter()
+            .map(|embedding| self.pool(embedding))
+            .collect()
+    }
+}
+
+impl<E: ColPaliEmbed> ColPaliEmbed for TokenPooledColPali<E> {
+    fn embed(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.inner.embed(text_batch, batch_size)
+    }
+
+    fn embed_query(&self, query: &str) -> anyhow::Result<Vec<EmbedData>> {
+        self.inner.embed_query(query)
+    }
+
+    fn embed_file_with_config(
+        &self,
+        file_path: PathBuf,
+        batch_size: usize,
+        config: &ImageEmbedConfig,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        Ok(self.pool_all(
+            self.inner
+                .embed_file_with_config(file_path, batch_size, config)?,
+        ))
+    }
+
+    fn embed_image(
+        &self,
+        image_path: PathBuf,
+        metadata: Option<HashMap<String, String>>,
+    ) -> anyhow::Result<EmbedData> {
+        Ok(self.pool(self.inner.embed_image(image_path, metadata)?))
+    }
+
+    fn embed_image_batch(&self, image_paths: &[PathBuf]) -> anyhow::Result<Vec<EmbedData>> {
+        Ok(self.pool_all(self.inner.embed_image_batch(image_paths)?))
+    }
+
+    fn embed_image_batch_from(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        Ok(self.pool_all(self.inner.embed_image_batch_from(images)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_tokens_merges_most_similar_neighbours() {
+        let vectors = vec![
+            vec![1.0, 0.0],
+            vec![0.9, 0.1],
+            vec![0.0, 1.0],
+            vec![0.1, 0.9],
+            vec![1.0, 1.0],
+        ];
+        let pooled = pool_tokens(vectors.clone(), 3);
+        assert_eq!(pooled.len(), 3);
+        assert!((pooled[0][0] - 0.95).abs() < 1e-6);
+        assert!((pooled[1][1] - 0.95).abs() < 1e-6);
+        assert_eq!(pooled[2], vec![1.0, 1.0]);
+
+        assert_eq!(pool_tokens(vectors.clone(), 10), vectors);
+        assert_eq!(pool_tokens(vectors, 0).len(), 1);
+    }
+}
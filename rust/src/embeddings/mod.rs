@@ -1,21 +1,161 @@
 //! This module contains the different embedding models that can be used to generate embeddings for the text data.
 
-use std::{collections::HashMap, rc::Rc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-use candle_core::{Device, Tensor};
+use candle_core::{DType, Device, Tensor};
 use embed::{EmbedData, Embedder, EmbeddingResult};
 
+use crate::config::{DocumentPooling, DEFAULT_BATCH_SIZE};
+use crate::error::EmbedError;
 use crate::file_processor::audio::audio_processor::Segment;
+use crate::warning::{warn, EmbedWarning, Warnings};
+use crate::Dtype;
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
 pub mod cloud;
+pub mod clustering;
 pub mod embed;
 pub mod local;
+pub mod projection;
+pub mod prompts;
+pub mod quality;
+pub mod routing;
+pub mod similarity;
+pub mod timing;
 pub mod utils;
+pub mod validation;
 
 use rayon::prelude::*;
+
+/// Smallest batch size the out-of-memory backoff shrinks to before giving up, unless
+/// [`BatchBackoff::new`] is given another.
+pub const MIN_BACKOFF_BATCH_SIZE: usize = 1;
+
+/// The batch size the out-of-memory backoff has shrunk to. Clones share it, so once a batch ran
+/// out of memory the following batches and calls with the same
+/// [`TextEmbedConfig`](crate::config::TextEmbedConfig) start at the reduced size instead of
+/// running out of memory again.
+#[derive(Debug, Clone)]
+pub struct BatchBackoff {
+    min_batch_size: usize,
+    limit: Arc<AtomicUsize>,
+}
+
+impl Default for BatchBackoff {
+    fn default() -> Self {
+        Self::new(MIN_BACKOFF_BATCH_SIZE)
+    }
+}
+
+impl BatchBackoff {
+    /// Gives up once a batch of `min_batch_size` runs out of memory.
+    pub fn new(min_batch_size: usize) -> Self {
+        Self {
+            min_batch_size: min_batch_size.max(1),
+            limit: Arc::new(AtomicUsize::new(usize::MAX)),
+        }
+    }
+
+    pub fn min_batch_size(&self) -> usize {
+        self.min_batch_size
+    }
+
+    /// The batch size the backoff has shrunk to, if a batch ran out of memory.
+    pub fn limit(&self) -> Option<usize> {
+        Some(self.limit.load(Ordering::Relaxed)).filter(|&limit| limit != usize::MAX)
+    }
+
+    fn batch_size(&self, requested: usize) -> usize {
+        requested
+            .min(self.limit.load(Ordering::Relaxed))
+            .max(self.min_batch_size)
+    }
+
+    /// Halves the batch size after a batch of `batch_size` ran out of memory, or returns `None`
+    /// when it cannot shrink any further.
+    fn reduce(&self, batch_size: usize) -> Option<usize> {
+        if batch_size <= self.min_batch_size {
+            return None;
+        }
+        let reduced = (batch_size / 2).max(self.min_batch_size);
+        self.limit.fetch_min(reduced, Ordering::Relaxed);
+        Some(reduced)
+    }
+}
+
+/// Returns true if the error looks like a device or allocator out-of-memory failure
+/// raised by candle (CUDA/Metal) or ONNX Runtime.
+pub fn is_oom_error(error: &anyhow::Error) -> bool {
+    let message = format!("{:?}", error).to_lowercase();
+    message.contains("out of memory")
+        || message.contains("out_of_memory")
+        || message.contains("failed to allocate memory")
+}
+
+/// Embeds `texts` one batch at a time with `embed`, which gets the texts of the batch and the
+/// batch size. When the backend runs out of memory, only the failed batch is retried, at half the
+/// size, and the rest follow at that size; see [`BatchBackoff`]. Untyped backend failures are
+/// returned as [`EmbedError::Inference`].
+pub async fn embed_with_backoff<'a, F, Fut>(
+    texts: &'a [String],
+    batch_size: Option<usize>,
+    backoff: &BatchBackoff,
+    embed: F,
+) -> anyhow::Result<Vec<EmbeddingResult>>
+where
+    F: Fn(&'a [String], usize) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<Vec<EmbeddingResult>>>,
+{
+    embed_with_backoff_warnings(texts, batch_size, backoff, None, embed).await
+}
+
+/// Like [`embed_with_backoff`], recording every retry in `warnings` as
+/// [`EmbedWarning::BatchSizeReduced`].
+pub(crate) async fn embed_with_backoff_warnings<'a, F, Fut>(
+    texts: &'a [String],
+    batch_size: Option<usize>,
+    backoff: &BatchBackoff,
+    warnings: Option<&Warnings>,
+    embed: F,
+) -> anyhow::Result<Vec<EmbeddingResult>>
+where
+    F: Fn(&'a [String], usize) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<Vec<EmbeddingResult>>>,
+{
+    let requested = batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    let mut encodings = Vec::with_capacity(texts.len());
+    let mut start = 0;
+    while start < texts.len() {
+        let batch_size = backoff.batch_size(requested);
+        let end = (start + batch_size).min(texts.len());
+        match embed(&texts[start..end], batch_size).await {
+            Ok(batch) => {
+                encodings.extend(batch);
+                start = end;
+            }
+            Err(e) if is_oom_error(&e) => match backoff.reduce(batch_size) {
+                Some(reduced) => warn(
+                    warnings,
+                    EmbedWarning::BatchSizeReduced {
+                        from: batch_size,
+                        to: reduced,
+                    },
+                ),
+                None => return Err(EmbedError::Inference(format!("{:#}", e)).into()),
+            },
+            Err(e) if e.is::<EmbedError>() => return Err(e),
+            Err(e) => return Err(EmbedError::Inference(format!("{:#}", e)).into()),
+        }
+    }
+    Ok(encodings)
+}
+
 pub fn get_text_metadata(
-    encodings: &Rc<Vec<EmbeddingResult>>,
-    text_batch: &Vec<String>,
+    encodings: &[EmbeddingResult],
+    text_batch: &[String],
     metadata: &Option<HashMap<String, String>>,
 ) -> anyhow::Result<Vec<EmbedData>> {
     let final_embeddings = encodings
@@ -26,6 +166,79 @@ pub fn get_text_metadata(
     Ok(final_embeddings)
 }
 
+/// Metadata field set to `chunk` or `document` when [`DocumentPooling`] is enabled.
+pub const EMBEDDING_LEVEL_KEY: &str = "embedding_level";
+
+/// Pools the chunk embeddings of one file into its document embedding.
+pub struct DocumentPooler {
+    pooling: DocumentPooling,
+    sum: Vec<f32>,
+    weight: f32,
+    metadata: Option<HashMap<String, String>>,
+}
+
+impl DocumentPooler {
+    pub fn new(pooling: DocumentPooling) -> Self {
+        Self {
+            pooling,
+            sum: Vec::new(),
+            weight: 0.0,
+            metadata: None,
+        }
+    }
+
+    /// Adds a chunk embedding and marks it as a chunk in its metadata. The document embedding
+    /// gets the metadata of the first chunk. Multi-vector embeddings are not pooled.
+    pub fn add(&mut self, embedding: &mut EmbedData) {
+        if self.weight == 0.0 {
+            self.metadata = embedding.metadata.clone();
+        }
+        embedding
+            .metadata
+            .get_or_insert_with(HashMap::new)
+            .insert(EMBEDDING_LEVEL_KEY.to_string(), "chunk".to_string());
+        let EmbeddingResult::DenseVector(vector) = &embedding.embedding else {
+            return;
+        };
+        if self.sum.is_empty() {
+            self.sum = vec![0.0; vector.len()];
+        } else if self.sum.len() != vector.len() {
+            return;
+        }
+        let weight = match self.pooling {
+            DocumentPooling::Mean => 1.0,
+            DocumentPooling::Weighted => embedding
+                .text
+                .as_ref()
+                .map_or(1, |text| text.chars().count().max(1))
+                as f32,
+        };
+        for (sum, value) in self.sum.iter_mut().zip(vector) {
+            *sum += weight * value;
+        }
+        self.weight += weight;
+    }
+
+    /// The document embedding, or `None` if no dense chunk embedding was added.
+    pub fn finish(self) -> Option<EmbedData> {
+        if self.weight == 0.0 {
+            return None;
+        }
+        let vector = self
+            .sum
+            .iter()
+            .map(|sum| sum / self.weight)
+            .collect::<Vec<_>>();
+        let mut metadata = self.metadata.unwrap_or_default();
+        metadata.insert(EMBEDDING_LEVEL_KEY.to_string(), "document".to_string());
+        Some(EmbedData::new(
+            EmbeddingResult::DenseVector(vector),
+            None,
+            Some(metadata),
+        ))
+    }
+}
+
 pub fn get_audio_metadata<T: AsRef<std::path::Path>>(
     encodings: Vec<EmbeddingResult>,
     segments: Vec<Segment>,
@@ -70,7 +283,13 @@ pub async fn embed_audio<T: AsRef<std::path::Path>>(
     batch_size: Option<usize>,
 ) -> Result<Vec<EmbedData>, anyhow::Error> {
     let text_batch = text_batch_from_audio(&segments);
-    let encodings = embedder.embed(&text_batch, batch_size).await?;
+    let encodings = embed_with_backoff(
+        &text_batch,
+        batch_size,
+        &BatchBackoff::default(),
+        |texts, b| embedder.embed(texts, Some(b)),
+    )
+    .await?;
     get_audio_metadata(encodings, segments, audio_file)
 }
 
@@ -78,10 +297,121 @@ pub fn normalize_l2(v: &Tensor) -> candle_core::Result<Tensor> {
     v.broadcast_div(&v.sqr()?.sum_keepdim(1)?.sqrt()?)
 }
 
+/// Picks the candle compute dtype for the requested `Dtype` on the given device.
+///
+/// Half precision is only used where the backend has fast kernels for it: F16 on CUDA and Metal,
+/// BF16 on CUDA. Everything else, including the quantized ONNX-only variants, runs in F32.
+pub fn select_dtype(dtype: Option<Dtype>, device: &Device) -> DType {
+    match dtype {
+        Some(Dtype::F16) if device.is_cuda() || device.is_metal() => DType::F16,
+        Some(Dtype::BF16) if device.is_cuda() => DType::BF16,
+        Some(Dtype::F16) | Some(Dtype::BF16) => {
+            tracing::warn!(
+                "{:?} is not supported on {:?}, falling back to F32",
+                dtype.unwrap(),
+                device
+            );
+            DType::F32
+        }
+        Some(Dtype::F32) | None => DType::F32,
+        Some(other) => {
+            tracing::warn!(
+                "{:?} is only supported for ONNX models, falling back to F32",
+                other
+            );
+            DType::F32
+        }
+    }
+}
+
+/// Where a model runs. [`ComputeDevice::Auto`] picks the device with [`select_device`] for Candle
+/// models and tries the GPU execution providers in turn for ONNX models. The other variants pin a
+/// model to one device, e.g. to put models on different GPUs or keep a reranker on the CPU while
+/// the embedder uses the GPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ComputeDevice {
+    #[default]
+    Auto,
+    Cpu,
+    /// The CUDA device with this ordinal.
+    Cuda(usize),
+    /// The Metal device with this ordinal. ONNX models use the CoreML execution provider.
+    Metal(usize),
+}
+
+impl ComputeDevice {
+    /// The Candle device for this choice. Fails when the backend is not compiled in or the device
+    /// does not exist.
+    pub fn candle_device(self) -> anyhow::Result<Device> {
+        Ok(match self {
+            ComputeDevice::Auto => select_device(),
+            ComputeDevice::Cpu => Device::Cpu,
+            ComputeDevice::Cuda(ordinal) => Device::new_cuda(ordinal)?,
+            ComputeDevice::Metal(ordinal) => Device::new_metal(ordinal)?,
+        })
+    }
+
+    /// Whether ONNX models may run on CUDA with this choice.
+    pub fn allows_cuda(self) -> bool {
+        matches!(self, ComputeDevice::Auto | ComputeDevice::Cuda(_))
+    }
+}
+
+impl std::str::FromStr for ComputeDevice {
+    type Err = EmbedError;
+
+    /// Parses `auto`, `cpu`, `cuda`, `cuda:N`, `metal` or `metal:N`.
+    fn from_str(device: &str) -> Result<Self, Self::Err> {
+        let (name, ordinal) = match device.split_once(':') {
+            Some((name, ordinal)) => (name, Some(ordinal)),
+            None => (device, None),
+        };
+        let ordinal = match ordinal.map(str::parse::<usize>) {
+            Some(Ok(ordinal)) => ordinal,
+            Some(Err(_)) => {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "invalid device ordinal in '{}'",
+                    device
+                )))
+            }
+            None => 0,
+        };
+        match name.to_lowercase().as_str() {
+            "auto" => Ok(ComputeDevice::Auto),
+            "cpu" => Ok(ComputeDevice::Cpu),
+            "cuda" | "gpu" => Ok(ComputeDevice::Cuda(ordinal)),
+            "metal" | "mps" => Ok(ComputeDevice::Metal(ordinal)),
+            _ => Err(EmbedError::InvalidConfig(format!(
+                "device must be 'auto', 'cpu', 'cuda:N' or 'metal:N', got '{}'",
+                device
+            ))),
+        }
+    }
+}
+
+/// Returns the device Candle models load on, logging it the first time. With the `metal` feature
+/// every model shares one Metal device: Metal compiles a compute pipeline the first time a kernel
+/// runs and caches it on the device, so a model loaded later reuses the pipelines instead of
+/// compiling them again.
 pub fn select_device() -> Device {
+    static LOGGED: std::sync::Once = std::sync::Once::new();
+    let device = select_backend_device();
+    LOGGED.call_once(|| tracing::info!("Candle models run on {}", device_name(&device)));
+    device
+}
+
+fn select_backend_device() -> Device {
     #[cfg(feature = "metal")]
     {
-        Device::new_metal(0).unwrap_or(Device::Cpu)
+        static METAL: std::sync::OnceLock<Device> = std::sync::OnceLock::new();
+        METAL
+            .get_or_init(|| {
+                Device::new_metal(0).unwrap_or_else(|e| {
+                    tracing::warn!("Metal is not available, falling back to the CPU: {}", e);
+                    Device::Cpu
+                })
+            })
+            .clone()
     }
     #[cfg(all(not(feature = "metal"), feature = "cuda"))]
     {
@@ -92,3 +422,288 @@ pub fn select_device() -> Device {
         Device::Cpu
     }
 }
+
+/// What the library runs on, for health and readiness checks.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RuntimeInfo {
+    /// Version of this crate.
+    pub version: String,
+    /// Device used by Candle models: `cpu`, `cuda:N` or `metal:N`.
+    pub device: String,
+    /// Whether ONNX Runtime can use its CUDA execution provider.
+    pub ort_cuda_available: bool,
+    /// Whether ONNX Runtime can use its ROCm execution provider, which needs the `rocm` feature.
+    pub ort_rocm_available: bool,
+    /// The compiled-in cargo features that affect inference.
+    pub features: Vec<String>,
+}
+
+/// Names `device` as `cpu`, `cuda:N` or `metal:N`.
+pub fn device_name(device: &Device) -> String {
+    match device.location() {
+        candle_core::DeviceLocation::Cpu => "cpu".to_string(),
+        candle_core::DeviceLocation::Cuda { gpu_id } => format!("cuda:{}", gpu_id),
+        candle_core::DeviceLocation::Metal { gpu_id } => format!("metal:{}", gpu_id),
+    }
+}
+
+/// Reports the device Candle models load on and the inference backends compiled in.
+pub fn runtime_info() -> RuntimeInfo {
+    use ort::execution_providers::ExecutionProvider;
+
+    let device = device_name(&select_device());
+    let features = [
+        ("cuda", cfg!(feature = "cuda")),
+        ("cudnn", cfg!(feature = "cudnn")),
+        ("flash-attn", cfg!(feature = "flash-attn")),
+        ("metal", cfg!(feature = "metal")),
+        ("rocm", cfg!(feature = "rocm")),
+        ("mkl", cfg!(feature = "mkl")),
+        ("accelerate", cfg!(feature = "accelerate")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name.to_string())
+    .collect();
+    RuntimeInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        device,
+        ort_cuda_available: ort::execution_providers::CUDAExecutionProvider::default()
+            .is_available()
+            .unwrap_or(false),
+        ort_rocm_available: local::providers::rocm_available(),
+        features,
+    }
+}
+
+/// Memory held by the process and the GPU, to check what a model costs or whether closing it
+/// released its memory.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct MemoryUsage {
+    /// Resident memory of the process. Only reported on Linux.
+    pub process_rss_bytes: Option<u64>,
+    /// Memory in use on the CUDA device Candle models load on, by all processes.
+    pub device_used_bytes: Option<u64>,
+    /// Total memory of that CUDA device.
+    pub device_total_bytes: Option<u64>,
+}
+
+/// Reports current memory usage. Fields that can't be measured on this platform or build are
+/// `None`.
+pub fn memory_usage() -> MemoryUsage {
+    let process_rss_bytes = std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("VmRSS:"))
+                .and_then(|value| {
+                    value
+                        .trim()
+                        .trim_end_matches("kB")
+                        .trim()
+                        .parse::<u64>()
+                        .ok()
+                })
+        })
+        .map(|kb| kb * 1024);
+
+    #[cfg(feature = "cuda")]
+    let (device_used_bytes, device_total_bytes) = match select_device() {
+        // Creating the device makes its context current, which `mem_get_info` needs.
+        Device::Cuda(_) => {
+            match candle_core::cuda_backend::cudarc::driver::result::mem_get_info() {
+                Ok((free, total)) => (Some((total - free) as u64), Some(total as u64)),
+                Err(_) => (None, None),
+            }
+        }
+        _ => (None, None),
+    };
+    #[cfg(not(feature = "cuda"))]
+    let (device_used_bytes, device_total_bytes) = (None, None);
+
+    MemoryUsage {
+        process_rss_bytes,
+        device_used_bytes,
+        device_total_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_oom_error() {
+        assert!(is_oom_error(&anyhow::anyhow!(
+            "DriverError(CUDA_ERROR_OUT_OF_MEMORY, \"out of memory\")"
+        )));
+        assert!(is_oom_error(&anyhow::anyhow!(
+            "Failed to allocate memory for requested buffer of size 402653184"
+        )));
+        assert!(!is_oom_error(&anyhow::anyhow!("Model not supported")));
+    }
+
+    #[tokio::test]
+    async fn test_embed_with_backoff() {
+        let texts = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+        let calls = std::sync::Mutex::new(Vec::new());
+        // Runs out of memory on batches of more than 2 texts.
+        let embed = |batch: &[String], batch_size: usize| {
+            calls.lock().unwrap().push(batch_size);
+            let result: anyhow::Result<Vec<EmbeddingResult>> = if batch.len() > 2 {
+                Err(anyhow::anyhow!("CUDA_ERROR_OUT_OF_MEMORY"))
+            } else {
+                Ok(batch
+                    .iter()
+                    .map(|text| EmbeddingResult::DenseVector(vec![text.parse().unwrap()]))
+                    .collect())
+            };
+            async move { result }
+        };
+
+        let backoff = BatchBackoff::default();
+        let warnings = Warnings::new();
+        let encodings =
+            embed_with_backoff_warnings(&texts, Some(8), &backoff, Some(&warnings), embed)
+                .await
+                .unwrap();
+        let values = encodings
+            .iter()
+            .map(|encoding| encoding.to_dense().unwrap()[0])
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..10).map(|i| i as f32).collect::<Vec<_>>());
+        assert_eq!(*calls.lock().unwrap(), vec![8, 4, 2, 2, 2, 2, 2]);
+        assert_eq!(backoff.limit(), Some(2));
+        assert_eq!(warnings.take().len(), 2);
+
+        // The next call starts at the reduced size.
+        calls.lock().unwrap().clear();
+        embed_with_backoff(&texts, Some(8), &backoff, embed)
+            .await
+            .unwrap();
+        assert_eq!(*calls.lock().unwrap(), vec![2; 5]);
+
+        let error = embed_with_backoff(&texts, Some(8), &BatchBackoff::new(4), embed)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(EmbedError::Inference(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_text_metadata() {
+        let encodings = vec![
+            EmbeddingResult::DenseVector(vec![1.0, 0.0]),
+            EmbeddingResult::DenseVector(vec![0.0, 1.0]),
+        ];
+        let text_batch = vec!["first".to_string(), "second".to_string()];
+        let metadata = Some(HashMap::from([(
+            "file_name".to_string(),
+            "a.txt".to_string(),
+        )]));
+
+        let embeddings = get_text_metadata(&encodings, &text_batch, &metadata).unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert_eq!(embeddings[1].text.as_deref(), Some("second"));
+        assert_eq!(embeddings[1].embedding.to_dense().unwrap(), vec![0.0, 1.0]);
+        assert_eq!(embeddings[0].metadata, metadata);
+    }
+
+    #[test]
+    fn test_document_pooler() {
+        let encodings = vec![
+            EmbeddingResult::DenseVector(vec![1.0, 0.0]),
+            EmbeddingResult::DenseVector(vec![0.0, 1.0]),
+        ];
+        let text_batch = vec!["a".to_string(), "bbb".to_string()];
+        let metadata = Some(HashMap::from([(
+            "file_name".to_string(),
+            "a.txt".to_string(),
+        )]));
+        let mut embeddings = get_text_metadata(&encodings, &text_batch, &metadata).unwrap();
+
+        let mut mean = DocumentPooler::new(DocumentPooling::Mean);
+        let mut weighted = DocumentPooler::new(DocumentPooling::Weighted);
+        for embedding in embeddings.iter_mut() {
+            mean.add(embedding);
+            weighted.add(embedding);
+        }
+        assert_eq!(
+            embeddings[0].metadata.as_ref().unwrap()[EMBEDDING_LEVEL_KEY],
+            "chunk"
+        );
+
+        let document = mean.finish().unwrap();
+        assert_eq!(document.embedding.to_dense().unwrap(), vec![0.5, 0.5]);
+        let metadata = document.metadata.unwrap();
+        assert_eq!(metadata[EMBEDDING_LEVEL_KEY], "document");
+        assert_eq!(metadata["file_name"], "a.txt");
+        let document = weighted.finish().unwrap();
+        assert_eq!(document.embedding.to_dense().unwrap(), vec![0.25, 0.75]);
+
+        assert!(DocumentPooler::new(DocumentPooling::Mean)
+            .finish()
+            .is_none());
+    }
+
+    #[test]
+    fn test_runtime_info() {
+        let info = runtime_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        if !cfg!(any(feature = "cuda", feature = "metal")) {
+            assert_eq!(info.device, "cpu");
+        }
+    }
+
+    #[cfg(feature = "metal")]
+    #[test]
+    fn test_metal_device_is_shared() {
+        let device = select_device();
+        if device.is_metal() {
+            assert!(device.same_device(&select_device()));
+            assert_eq!(device_name(&device), "metal:0");
+        }
+    }
+
+    #[test]
+    fn test_compute_device() {
+        assert_eq!("cpu".parse::<ComputeDevice>().unwrap(), ComputeDevice::Cpu);
+        assert_eq!(
+            "cuda:1".parse::<ComputeDevice>().unwrap(),
+            ComputeDevice::Cuda(1)
+        );
+        assert_eq!(
+            "metal".parse::<ComputeDevice>().unwrap(),
+            ComputeDevice::Metal(0)
+        );
+        assert!("tpu".parse::<ComputeDevice>().is_err());
+        assert!("cuda:x".parse::<ComputeDevice>().is_err());
+
+        assert!(ComputeDevice::Cpu.candle_device().unwrap().is_cpu());
+        if !cfg!(feature = "cuda") {
+            assert!(ComputeDevice::Cuda(0).candle_device().is_err());
+        }
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let usage = memory_usage();
+        if cfg!(target_os = "linux") {
+            assert!(usage.process_rss_bytes.unwrap() > 0);
+        }
+        if !cfg!(feature = "cuda") {
+            assert!(usage.device_total_bytes.is_none());
+        }
+    }
+
+    #[test]
+    fn test_select_dtype_falls_back_on_cpu() {
+        assert_eq!(select_dtype(Some(Dtype::F16), &Device::Cpu), DType::F32);
+        assert_eq!(select_dtype(Some(Dtype::BF16), &Device::Cpu), DType::F32);
+        assert_eq!(select_dtype(Some(Dtype::INT8), &Device::Cpu), DType::F32);
+        assert_eq!(select_dtype(None, &Device::Cpu), DType::F32);
+    }
+}
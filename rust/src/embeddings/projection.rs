@@ -0,0 +1,234 @@
+//! PCA projection of embeddings to a few dimensions, with CSV and JSON export of the coordinates
+//! so a corpus can be plotted straight from the pipeline output.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use ndarray::{Array1, Array2, Axis};
+use serde::Serialize;
+
+use super::embed::EmbedData;
+use crate::error::EmbedError;
+
+/// Power iterations per component. Plenty for the leading components of embedding corpora.
+const POWER_ITERATIONS: usize = 100;
+
+/// Principal components fitted by [`Pca::fit`].
+#[derive(Debug, Clone)]
+pub struct Pca {
+    pub mean: Array1<f32>,
+    /// One row per component, strongest first.
+    pub components: Array2<f32>,
+    /// Variance of the data along each component.
+    pub explained_variance: Vec<f32>,
+}
+
+impl Pca {
+    /// Finds the `n_components` directions of largest variance with power iteration and deflation.
+    pub fn fit(vectors: &[Vec<f32>], n_components: usize) -> anyhow::Result<Self> {
+        let data = to_matrix(vectors)?;
+        let (rows, dim) = data.dim();
+        if n_components == 0 || n_components > dim {
+            return Err(EmbedError::InvalidConfig(format!(
+                "n_components must be between 1 and the embedding dimension ({}), got {}",
+                dim, n_components
+            ))
+            .into());
+        }
+
+        let mean = data
+            .mean_axis(Axis(0))
+            .unwrap_or_else(|| Array1::zeros(dim));
+        let mut centered = &data - &mean;
+        let mut components = Array2::zeros((n_components, dim));
+        let mut explained_variance = Vec::with_capacity(n_components);
+
+        for n in 0..n_components {
+            // Start away from the previous components so deflated data still has a direction.
+            let mut component =
+                Array1::from_shape_fn(dim, |i| if i % n_components == n { 1.0 } else { 0.5 });
+            let norm = component.dot(&component).sqrt();
+            component /= norm;
+            for _ in 0..POWER_ITERATIONS {
+                let next = centered.t().dot(&centered.dot(&component));
+                let norm = next.dot(&next).sqrt();
+                if norm == 0.0 {
+                    break;
+                }
+                component = next / norm;
+            }
+            let scores = centered.dot(&component);
+            explained_variance.push(scores.dot(&scores) / rows.saturating_sub(1).max(1) as f32);
+            // Remove this component so the next iteration finds the following one.
+            centered -= &scores
+                .view()
+                .insert_axis(Axis(1))
+                .dot(&component.view().insert_axis(Axis(0)));
+            components.row_mut(n).assign(&component);
+        }
+
+        Ok(Self {
+            mean,
+            components,
+            explained_variance,
+        })
+    }
+
+    /// Projects `vectors` onto the fitted components.
+    pub fn transform(&self, vectors: &[Vec<f32>]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let data = to_matrix(vectors)?;
+        if data.ncols() != self.mean.len() {
+            return Err(EmbedError::InvalidConfig(format!(
+                "vectors have {} dimensions but the PCA was fitted on {}",
+                data.ncols(),
+                self.mean.len()
+            ))
+            .into());
+        }
+        let projected = (&data - &self.mean).dot(&self.components.t());
+        Ok(projected.outer_iter().map(|row| row.to_vec()).collect())
+    }
+}
+
+/// A projected embedding with the text and metadata it came from.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectedPoint {
+    pub coordinates: Vec<f32>,
+    pub text: Option<String>,
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+/// Projects dense embeddings to `n_components` dimensions with PCA, keeping their text and
+/// metadata. Use 2 or 3 components for plotting.
+pub fn project_embeddings(
+    data: &[EmbedData],
+    n_components: usize,
+) -> anyhow::Result<Vec<ProjectedPoint>> {
+    let vectors = data
+        .iter()
+        .map(|data| data.embedding.to_dense())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let coordinates = Pca::fit(&vectors, n_components)?.transform(&vectors)?;
+    Ok(coordinates
+        .into_iter()
+        .zip(data)
+        .map(|(coordinates, data)| ProjectedPoint {
+            coordinates,
+            text: data.text.clone(),
+            metadata: data.metadata.clone(),
+        })
+        .collect())
+}
+
+/// Writes `points` as a JSON array of `{coordinates, text, metadata}` objects.
+pub fn export_json<P: AsRef<Path>>(points: &[ProjectedPoint], path: P) -> anyhow::Result<()> {
+    let file = std::fs::File::create(path).map_err(EmbedError::Io)?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), points)?;
+    Ok(())
+}
+
+/// Writes `points` as CSV with one column per coordinate (`x`, `y`, `z`, then `c3`, ...), then the
+/// text and one column per metadata key found in any point.
+pub fn export_csv<P: AsRef<Path>>(points: &[ProjectedPoint], path: P) -> anyhow::Result<()> {
+    let dims = points.first().map_or(0, |point| point.coordinates.len());
+    let mut keys = points
+        .iter()
+        .flat_map(|point| point.metadata.iter().flat_map(|metadata| metadata.keys()))
+        .cloned()
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path).map_err(EmbedError::Io)?);
+    let header = (0..dims)
+        .map(|i| match i {
+            0 => "x".to_string(),
+            1 => "y".to_string(),
+            2 => "z".to_string(),
+            _ => format!("c{}", i),
+        })
+        .chain(std::iter::once("text".to_string()))
+        .chain(keys.iter().cloned())
+        .map(|field| csv_field(&field))
+        .collect::<Vec<_>>();
+    writeln!(writer, "{}", header.join(","))?;
+
+    for point in points {
+        let row = point
+            .coordinates
+            .iter()
+            .map(|value| value.to_string())
+            .chain(std::iter::once(csv_field(
+                point.text.as_deref().unwrap_or_default(),
+            )))
+            .chain(keys.iter().map(|key| {
+                csv_field(
+                    point
+                        .metadata
+                        .as_ref()
+                        .and_then(|metadata| metadata.get(key))
+                        .map_or("", String::as_str),
+                )
+            }))
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_matrix(vectors: &[Vec<f32>]) -> anyhow::Result<Array2<f32>> {
+    let dim = vectors.first().map_or(0, Vec::len);
+    if vectors.is_empty() || vectors.iter().any(|vector| vector.len() != dim) {
+        return Err(EmbedError::InvalidConfig(
+            "PCA needs at least one vector and all vectors must have the same dimension"
+                .to_string(),
+        )
+        .into());
+    }
+    Ok(Array2::from_shape_vec(
+        (vectors.len(), dim),
+        vectors.iter().flatten().copied().collect(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pca_finds_main_direction() {
+        let vectors = (0..20)
+            .map(|i| {
+                let t = i as f32 - 10.0;
+                vec![t, t, 0.01 * (i % 3) as f32]
+            })
+            .collect::<Vec<_>>();
+        let pca = Pca::fit(&vectors, 2).unwrap();
+        let first = pca.components.row(0);
+        assert!((first[0].abs() - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!((first[1].abs() - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!(pca.explained_variance[0] > pca.explained_variance[1]);
+
+        let projected = pca.transform(&vectors).unwrap();
+        assert_eq!(projected.len(), 20);
+        assert_eq!(projected[0].len(), 2);
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
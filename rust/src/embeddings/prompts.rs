@@ -0,0 +1,163 @@
+//! Instructions that instruction-tuned embedding models expect in front of their inputs.
+
+use std::borrow::Cow;
+
+/// The instructions prepended to queries and documents before they are embedded. Models trained
+/// with instructions produce noticeably worse embeddings without them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptTemplate {
+    pub query: String,
+    pub document: String,
+}
+
+struct PromptPreset {
+    /// Matched case-insensitively against the model id.
+    pattern: &'static str,
+    query: &'static str,
+    document: &'static str,
+}
+
+const WEB_SEARCH_INSTRUCTION: &str =
+    "Instruct: Given a web search query, retrieve relevant passages that answer the query\nQuery: ";
+
+/// Known prompt templates. The first preset whose pattern is part of the model id wins, so the
+/// more specific patterns come first. Presets without prompts stop a broader pattern from matching.
+const PROMPT_PRESETS: &[PromptPreset] = &[
+    PromptPreset {
+        pattern: "bge-m3",
+        query: "",
+        document: "",
+    },
+    PromptPreset {
+        pattern: "bge-reranker",
+        query: "",
+        document: "",
+    },
+    PromptPreset {
+        pattern: "-zh-v1.5",
+        query: "为这个句子生成表示以用于检索相关文章：",
+        document: "",
+    },
+    PromptPreset {
+        pattern: "bge-",
+        query: "Represent this sentence for searching relevant passages: ",
+        document: "",
+    },
+    PromptPreset {
+        pattern: "mxbai-embed-large",
+        query: "Represent this sentence for searching relevant passages: ",
+        document: "",
+    },
+    PromptPreset {
+        pattern: "gte-qwen",
+        query: WEB_SEARCH_INSTRUCTION,
+        document: "",
+    },
+    PromptPreset {
+        pattern: "qwen3-embedding",
+        query: "Instruct: Given a web search query, retrieve relevant passages that answer the query\nQuery:",
+        document: "",
+    },
+    PromptPreset {
+        pattern: "e5-mistral",
+        query: WEB_SEARCH_INSTRUCTION,
+        document: "",
+    },
+    PromptPreset {
+        pattern: "e5-large-instruct",
+        query: WEB_SEARCH_INSTRUCTION,
+        document: "",
+    },
+    PromptPreset {
+        pattern: "e5-",
+        query: "query: ",
+        document: "passage: ",
+    },
+    PromptPreset {
+        pattern: "nv-embed",
+        query: "Instruct: Given a question, retrieve passages that answer the question\nQuery: ",
+        document: "",
+    },
+    PromptPreset {
+        pattern: "nomic-embed-text",
+        query: "search_query: ",
+        document: "search_document: ",
+    },
+    PromptPreset {
+        pattern: "modernbert-embed",
+        query: "search_query: ",
+        document: "search_document: ",
+    },
+];
+
+impl PromptTemplate {
+    pub fn new(query: impl Into<String>, document: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            document: document.into(),
+        }
+    }
+
+    /// Looks up the prompt template of a known model, e.g. `BAAI/bge-small-en-v1.5` or
+    /// `intfloat/multilingual-e5-base`. Returns `None` for models that take their inputs as is.
+    pub fn for_model(model_id: &str) -> Option<Self> {
+        let model_id = model_id.to_lowercase();
+        PROMPT_PRESETS
+            .iter()
+            .find(|preset| model_id.contains(preset.pattern))
+            .filter(|preset| !preset.query.is_empty() || !preset.document.is_empty())
+            .map(|preset| Self::new(preset.query, preset.document))
+    }
+
+    /// Prepends the query instruction to every text.
+    pub fn apply_query<'a>(&self, texts: &'a [String]) -> Cow<'a, [String]> {
+        prepend(&self.query, texts)
+    }
+
+    /// Prepends the document instruction to every text.
+    pub fn apply_document<'a>(&self, texts: &'a [String]) -> Cow<'a, [String]> {
+        prepend(&self.document, texts)
+    }
+}
+
+fn prepend<'a>(prompt: &str, texts: &'a [String]) -> Cow<'a, [String]> {
+    if prompt.is_empty() {
+        return Cow::Borrowed(texts);
+    }
+    Cow::Owned(
+        texts
+            .iter()
+            .map(|text| format!("{}{}", prompt, text))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_presets() {
+        let bge = PromptTemplate::for_model("Xenova/bge-small-en-v1.5").unwrap();
+        assert!(bge.query.starts_with("Represent this sentence"));
+        assert!(bge.document.is_empty());
+
+        let e5 = PromptTemplate::for_model("intfloat/multilingual-e5-base").unwrap();
+        assert_eq!(e5, PromptTemplate::new("query: ", "passage: "));
+
+        let instruct =
+            PromptTemplate::for_model("intfloat/multilingual-e5-large-instruct").unwrap();
+        assert!(instruct.query.starts_with("Instruct: "));
+
+        assert!(PromptTemplate::for_model("BAAI/bge-m3").is_none());
+        assert!(PromptTemplate::for_model("sentence-transformers/all-MiniLM-L6-v2").is_none());
+    }
+
+    #[test]
+    fn test_apply_prompts() {
+        let texts = vec!["what is rust".to_string()];
+        let template = PromptTemplate::new("query: ", "");
+        assert_eq!(template.apply_query(&texts)[0], "query: what is rust");
+        assert!(matches!(template.apply_document(&texts), Cow::Borrowed(_)));
+    }
+}
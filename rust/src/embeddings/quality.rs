@@ -0,0 +1,197 @@
+//! Post-embedding quality checks that flag chunks worth keeping out of an index: near-duplicates
+//! of many other chunks (boilerplate headers, footers, navigation) and statistical outliers, which
+//! are often OCR garbage.
+
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use super::embed::EmbedData;
+use super::similarity::cosine_similarity;
+use crate::error::EmbedError;
+
+/// Metadata field set to `ok`, `near_duplicate` or `outlier` by [`flag_low_quality`].
+pub const QUALITY_FLAG_KEY: &str = "quality_flag";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityFlag {
+    Ok,
+    NearDuplicate,
+    Outlier,
+}
+
+impl QualityFlag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QualityFlag::Ok => "ok",
+            QualityFlag::NearDuplicate => "near_duplicate",
+            QualityFlag::Outlier => "outlier",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QualityConfig {
+    /// Cosine similarity above which two chunks count as near-duplicates.
+    pub duplicate_threshold: f32,
+    /// A chunk is flagged once it is a near-duplicate of more than this many other chunks.
+    pub max_duplicates: usize,
+    /// A chunk is an outlier when its similarity to the corpus centroid is this many standard
+    /// deviations below the mean.
+    pub outlier_z_score: f32,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            duplicate_threshold: 0.98,
+            max_duplicates: 3,
+            outlier_z_score: 3.0,
+        }
+    }
+}
+
+/// Number of chunks flagged by [`flag_low_quality`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QualityReport {
+    pub near_duplicates: usize,
+    pub outliers: usize,
+}
+
+/// Flags low quality chunks by writing [`QUALITY_FLAG_KEY`] into the metadata of every dense
+/// embedding. Near-duplicate detection compares every pair of chunks, so run it per document or
+/// per batch of a few thousand chunks rather than over a whole corpus at once.
+pub fn flag_low_quality(
+    data: &mut [EmbedData],
+    config: &QualityConfig,
+) -> anyhow::Result<QualityReport> {
+    if !(0.0..=1.0).contains(&config.duplicate_threshold) {
+        return Err(EmbedError::InvalidConfig(format!(
+            "duplicate_threshold must be between 0 and 1, got {}",
+            config.duplicate_threshold
+        ))
+        .into());
+    }
+    let (indices, vectors): (Vec<usize>, Vec<Vec<f32>>) = data
+        .iter()
+        .enumerate()
+        .filter_map(|(i, data)| data.embedding.to_dense().ok().map(|vector| (i, vector)))
+        .unzip();
+    let flags = quality_flags(&vectors, config);
+
+    let mut report = QualityReport::default();
+    for (i, flag) in indices.into_iter().zip(flags) {
+        match flag {
+            QualityFlag::NearDuplicate => report.near_duplicates += 1,
+            QualityFlag::Outlier => report.outliers += 1,
+            QualityFlag::Ok => {}
+        }
+        data[i]
+            .metadata
+            .get_or_insert_with(HashMap::new)
+            .insert(QUALITY_FLAG_KEY.to_string(), flag.as_str().to_string());
+    }
+    Ok(report)
+}
+
+/// Returns the flag of every vector. Near-duplicates take precedence over outliers.
+pub fn quality_flags(vectors: &[Vec<f32>], config: &QualityConfig) -> Vec<QualityFlag> {
+    if vectors.is_empty() {
+        return Vec::new();
+    }
+    let duplicates = vectors
+        .par_iter()
+        .enumerate()
+        .map(|(i, vector)| {
+            vectors
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| {
+                    i != j && cosine_similarity(vector, other) >= config.duplicate_threshold
+                })
+                .count()
+        })
+        .collect::<Vec<_>>();
+
+    // Similarity of each vector to the mean direction of the corpus.
+    let dim = vectors[0].len();
+    let mut centroid = vec![0.0f32; dim];
+    for vector in vectors.iter().filter(|vector| vector.len() == dim) {
+        let norm = vector
+            .iter()
+            .map(|x| x * x)
+            .sum::<f32>()
+            .sqrt()
+            .max(f32::EPSILON);
+        for (c, x) in centroid.iter_mut().zip(vector) {
+            *c += x / norm;
+        }
+    }
+    let similarities = vectors
+        .iter()
+        .map(|vector| cosine_similarity(vector, &centroid))
+        .collect::<Vec<_>>();
+    let mean = similarities.iter().sum::<f32>() / similarities.len() as f32;
+    let std = (similarities
+        .iter()
+        .map(|s| (s - mean) * (s - mean))
+        .sum::<f32>()
+        / similarities.len() as f32)
+        .sqrt();
+
+    duplicates
+        .iter()
+        .zip(&similarities)
+        .map(|(&duplicates, &similarity)| {
+            if duplicates > config.max_duplicates {
+                QualityFlag::NearDuplicate
+            } else if std > 0.0 && (mean - similarity) / std > config.outlier_z_score {
+                QualityFlag::Outlier
+            } else {
+                QualityFlag::Ok
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::embed::EmbeddingResult;
+
+    #[test]
+    fn test_flag_low_quality() {
+        let mut vectors = (0..30)
+            .map(|i| {
+                let mut vector = vec![0.1; 32];
+                vector[i] += 1.0;
+                vector
+            })
+            .collect::<Vec<_>>();
+        // Boilerplate repeated five times and one garbage chunk pointing elsewhere.
+        let mut boilerplate = vec![0.1; 32];
+        boilerplate[30] += 1.0;
+        boilerplate[31] += 1.0;
+        for _ in 0..5 {
+            vectors.push(boilerplate.clone());
+        }
+        vectors.push(vec![-1.0; 32]);
+        let mut data = vectors
+            .into_iter()
+            .map(|vector| EmbedData::new(EmbeddingResult::DenseVector(vector), None, None))
+            .collect::<Vec<_>>();
+
+        let report = flag_low_quality(&mut data, &QualityConfig::default()).unwrap();
+        assert_eq!(
+            report,
+            QualityReport {
+                near_duplicates: 5,
+                outliers: 1
+            }
+        );
+        let flag = |i: usize| data[i].metadata.as_ref().unwrap()[QUALITY_FLAG_KEY].clone();
+        assert_eq!(flag(0), "ok");
+        assert_eq!(flag(30), "near_duplicate");
+        assert_eq!(flag(35), "outlier");
+    }
+}
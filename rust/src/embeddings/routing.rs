@@ -0,0 +1,104 @@
+//! Routes small batches to a CPU copy of a model and large ones to its GPU copy. A single query
+//! is often faster on the CPU than paying the kernel launch and transfer overhead of the GPU,
+//! while large batches want the GPU.
+
+use std::sync::Arc;
+
+use super::embed::{Embedder, EmbeddingResult};
+
+/// Largest batch [`DeviceRouter`] sends to the CPU by default.
+pub const DEFAULT_CPU_MAX_BATCH: usize = 4;
+
+/// Holds a GPU and a CPU copy of the same model and picks one per batch by its size.
+///
+/// ```no_run
+/// use std::sync::Arc;
+///
+/// use embed_anything::embeddings::embed::Embedder;
+/// use embed_anything::embeddings::routing::DeviceRouter;
+/// use embed_anything::embeddings::ComputeDevice;
+///
+/// let builder = Embedder::builder("bert").with_model_id("sentence-transformers/all-MiniLM-L6-v2");
+/// let gpu = builder.clone().with_device(ComputeDevice::Cuda(0)).build_hf()?;
+/// let cpu = builder.with_device(ComputeDevice::Cpu).build_hf()?;
+/// let router = DeviceRouter::new(Arc::new(gpu), Arc::new(cpu)).with_cpu_max_batch(8);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct DeviceRouter {
+    gpu: Arc<Embedder>,
+    cpu: Arc<Embedder>,
+    cpu_max_batch: usize,
+}
+
+impl DeviceRouter {
+    pub fn new(gpu: Arc<Embedder>, cpu: Arc<Embedder>) -> Self {
+        Self {
+            gpu,
+            cpu,
+            cpu_max_batch: DEFAULT_CPU_MAX_BATCH,
+        }
+    }
+
+    /// Batches of up to `cpu_max_batch` texts run on the CPU. Zero sends everything to the GPU.
+    pub fn with_cpu_max_batch(mut self, cpu_max_batch: usize) -> Self {
+        self.cpu_max_batch = cpu_max_batch;
+        self
+    }
+
+    /// The copy of the model a batch of `batch_len` texts runs on, e.g. to pass to
+    /// [`embed_query`](crate::embed_query).
+    pub fn route(&self, batch_len: usize) -> &Arc<Embedder> {
+        if batch_len <= self.cpu_max_batch {
+            &self.cpu
+        } else {
+            &self.gpu
+        }
+    }
+
+    pub async fn embed(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.route(text_batch.len())
+            .embed(text_batch, batch_size)
+            .await
+    }
+
+    pub async fn embed_query(
+        &self,
+        text_batch: &[String],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        self.route(text_batch.len())
+            .embed_query(text_batch, batch_size)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::ComputeDevice;
+
+    #[tokio::test]
+    async fn test_device_router() {
+        let builder =
+            Embedder::builder("bert").with_model_id("sentence-transformers/all-MiniLM-L6-v2");
+        let gpu = Arc::new(builder.clone().build_hf().unwrap());
+        let cpu = Arc::new(builder.with_device(ComputeDevice::Cpu).build_hf().unwrap());
+        let router = DeviceRouter::new(gpu.clone(), cpu.clone()).with_cpu_max_batch(2);
+
+        assert!(Arc::ptr_eq(router.route(1), &cpu));
+        assert!(Arc::ptr_eq(router.route(2), &cpu));
+        assert!(Arc::ptr_eq(router.route(3), &gpu));
+
+        let texts = vec!["Hello, world!".to_string()];
+        let routed = router.embed_query(&texts, None).await.unwrap();
+        let expected = cpu.embed_query(&texts, None).await.unwrap();
+        assert_eq!(
+            routed[0].to_dense().unwrap(),
+            expected[0].to_dense().unwrap()
+        );
+    }
+}
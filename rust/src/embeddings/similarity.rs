@@ -0,0 +1,116 @@
+//! Similarity helpers for post-processing retrieval results inside the crate.
+
+use super::embed::EmbedData;
+use crate::error::EmbedError;
+
+/// Cosine similarity of two vectors. Returns 0 if either vector is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Picks `top_k` candidates with Maximal Marginal Relevance, trading relevance to `query` against
+/// similarity to the candidates already picked. `lambda` of 1 ranks by relevance only, 0 by
+/// diversity only; 0.5 to 0.7 is typical.
+///
+/// Returns the indices of the picked candidates in the order they were picked.
+pub fn mmr(
+    query: &[f32],
+    candidates: &[EmbedData],
+    lambda: f32,
+    top_k: usize,
+) -> anyhow::Result<Vec<usize>> {
+    let vectors = candidates
+        .iter()
+        .map(|candidate| candidate.embedding.to_dense())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    mmr_vectors(query, &vectors, lambda, top_k)
+}
+
+/// [`mmr`] over plain vectors.
+pub fn mmr_vectors(
+    query: &[f32],
+    candidates: &[Vec<f32>],
+    lambda: f32,
+    top_k: usize,
+) -> anyhow::Result<Vec<usize>> {
+    if !(0.0..=1.0).contains(&lambda) {
+        return Err(EmbedError::InvalidConfig(format!(
+            "MMR lambda must be between 0 and 1, got {}",
+            lambda
+        ))
+        .into());
+    }
+    if let Some(candidate) = candidates.iter().find(|c| c.len() != query.len()) {
+        return Err(EmbedError::InvalidConfig(format!(
+            "candidate has {} dimensions but the query has {}",
+            candidate.len(),
+            query.len()
+        ))
+        .into());
+    }
+
+    let relevance = candidates
+        .iter()
+        .map(|candidate| cosine_similarity(query, candidate))
+        .collect::<Vec<_>>();
+    // Highest similarity of each candidate to any picked candidate.
+    let mut redundancy = vec![f32::NEG_INFINITY; candidates.len()];
+    let mut picked = vec![false; candidates.len()];
+    let mut selected = Vec::with_capacity(top_k.min(candidates.len()));
+
+    while selected.len() < top_k.min(candidates.len()) {
+        let best = (0..candidates.len())
+            .filter(|&i| !picked[i])
+            .map(|i| {
+                let redundancy = if selected.is_empty() {
+                    0.0
+                } else {
+                    redundancy[i]
+                };
+                (i, lambda * relevance[i] - (1.0 - lambda) * redundancy)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(i, _)| i);
+        let Some(best) = best else { break };
+
+        picked[best] = true;
+        selected.push(best);
+        for i in (0..candidates.len()).filter(|&i| !picked[i]) {
+            redundancy[i] = redundancy[i].max(cosine_similarity(&candidates[i], &candidates[best]));
+        }
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mmr_prefers_diverse_candidates() {
+        let query = vec![1.0, 0.5];
+        let candidates = vec![vec![1.0, 0.0], vec![1.0, 0.01], vec![0.0, 1.0]];
+
+        assert_eq!(
+            mmr_vectors(&query, &candidates, 1.0, 2).unwrap(),
+            vec![1, 0]
+        );
+        assert_eq!(
+            mmr_vectors(&query, &candidates, 0.5, 2).unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(mmr_vectors(&query, &candidates, 0.5, 10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_mmr_rejects_invalid_input() {
+        assert!(mmr_vectors(&[1.0], &[vec![1.0]], 1.5, 1).is_err());
+        assert!(mmr_vectors(&[1.0], &[vec![1.0, 0.0]], 0.5, 1).is_err());
+    }
+}
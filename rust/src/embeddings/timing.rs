@@ -0,0 +1,155 @@
+//! Where the time of embedding calls goes, split into the stages every local model runs through.
+//! Embedders that support it record into a [`StageTimer`] once timing is turned on, and
+//! [`TextEmbedder::timing_report`](super::embed::TextEmbedder::timing_report) hands back what was
+//! recorded since the last report. Files embedded with such an embedder also record the time spent
+//! extracting and chunking their text.
+
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use candle_core::Device;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Extraction,
+    Chunking,
+    Tokenize,
+    Inference,
+    Pooling,
+    Normalize,
+}
+
+/// Time spent in each stage. Batches run in parallel are added up, so the stages can sum to more
+/// than the wall time of the call. `inference` is the forward pass of the model.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimingReport {
+    pub extraction: Duration,
+    pub chunking: Duration,
+    pub tokenize: Duration,
+    pub inference: Duration,
+    pub pooling: Duration,
+    pub normalize: Duration,
+    pub batches: usize,
+    pub texts: usize,
+}
+
+impl TimingReport {
+    pub fn total(&self) -> Duration {
+        self.extraction
+            + self.chunking
+            + self.tokenize
+            + self.inference
+            + self.pooling
+            + self.normalize
+    }
+
+    fn stage_mut(&mut self, stage: Stage) -> &mut Duration {
+        match stage {
+            Stage::Extraction => &mut self.extraction,
+            Stage::Chunking => &mut self.chunking,
+            Stage::Tokenize => &mut self.tokenize,
+            Stage::Inference => &mut self.inference,
+            Stage::Pooling => &mut self.pooling,
+            Stage::Normalize => &mut self.normalize,
+        }
+    }
+}
+
+impl Display for TimingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} texts in {} batches:", self.texts, self.batches)?;
+        write!(f, " extraction {:?},", self.extraction)?;
+        write!(f, " chunking {:?},", self.chunking)?;
+        write!(f, " tokenize {:?},", self.tokenize)?;
+        write!(f, " inference {:?},", self.inference)?;
+        write!(f, " pooling {:?},", self.pooling)?;
+        write!(f, " normalize {:?}", self.normalize)
+    }
+}
+
+/// Collects the stage timings of an embedder. Clones share the same report.
+#[derive(Debug, Clone, Default)]
+pub struct StageTimer {
+    report: Arc<Mutex<TimingReport>>,
+}
+
+impl StageTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` and adds its duration to `stage`.
+    pub fn time<T>(&self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        *self.report.lock().unwrap().stage_mut(stage) += start.elapsed();
+        result
+    }
+
+    /// Counts a batch of `texts` texts.
+    pub fn batch(&self, texts: usize) {
+        let mut report = self.report.lock().unwrap();
+        report.batches += 1;
+        report.texts += texts;
+    }
+
+    /// Returns what was recorded since the last call and starts over.
+    pub fn take(&self) -> TimingReport {
+        std::mem::take(&mut *self.report.lock().unwrap())
+    }
+}
+
+/// Runs `f` through `timer` when there is one.
+pub(crate) fn timed<T>(timer: Option<&StageTimer>, stage: Stage, f: impl FnOnce() -> T) -> T {
+    match timer {
+        Some(timer) => timer.time(stage, f),
+        None => f(),
+    }
+}
+
+/// Like [`timed`] for Candle work. Kernels run asynchronously on GPUs, so the time is only
+/// recorded once `device` has finished them.
+pub(crate) fn timed_on_device<T>(
+    timer: Option<&StageTimer>,
+    stage: Stage,
+    device: &Device,
+    f: impl FnOnce() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    timed(timer, stage, || {
+        let result = f()?;
+        if timer.is_some() {
+            device.synchronize()?;
+        }
+        Ok(result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_timer() {
+        let timer = StageTimer::new();
+        let value = timer.time(Stage::Inference, || {
+            std::thread::sleep(Duration::from_millis(5));
+            42
+        });
+        assert_eq!(value, 42);
+        timer.batch(3);
+        timed(Some(&timer), Stage::Tokenize, || {});
+        timed(Some(&timer), Stage::Chunking, || {});
+
+        let report = timer.take();
+        assert!(report.inference >= Duration::from_millis(5));
+        assert_eq!(report.batches, 1);
+        assert_eq!(report.texts, 3);
+        assert_eq!(
+            report.total(),
+            report.chunking + report.tokenize + report.inference
+        );
+        assert!(report.to_string().starts_with("3 texts in 1 batches"));
+        assert_eq!(timer.take(), TimingReport::default());
+    }
+}
@@ -1,7 +1,11 @@
-use anyhow::Error as E;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, PoisonError};
+
+use crate::error::EmbedError;
 use candle_core::{Device, Tensor};
 use ndarray::Array2;
-use tokenizers::Tokenizer;
+use rayon::prelude::*;
+use tokenizers::{Encoding, Tokenizer};
 
 pub fn tokenize_batch(
     tokenizer: &Tokenizer,
@@ -10,7 +14,7 @@ pub fn tokenize_batch(
 ) -> anyhow::Result<Tensor> {
     let tokens = tokenizer
         .encode_batch(text_batch.to_vec(), true)
-        .map_err(E::msg)?;
+        .map_err(EmbedError::tokenization)?;
     let token_ids = tokens
         .iter()
         .map(|tokens| {
@@ -29,7 +33,7 @@ pub fn get_attention_mask(
 ) -> anyhow::Result<Tensor> {
     let tokens = tokenizer
         .encode_batch(text_batch.to_vec(), true)
-        .map_err(E::msg)?;
+        .map_err(EmbedError::tokenization)?;
 
     let attention_mask = tokens
         .iter()
@@ -47,7 +51,7 @@ pub fn get_attention_mask_ndarray(
 ) -> anyhow::Result<Array2<i64>> {
     let attention_mask = tokenizer
         .encode_batch(text_batch.to_vec(), true)
-        .map_err(E::msg)?
+        .map_err(EmbedError::tokenization)?
         .iter()
         .map(|tokens| {
             tokens
@@ -72,7 +76,7 @@ pub fn tokenize_batch_ndarray(
 ) -> anyhow::Result<Array2<i64>> {
     let token_ids = tokenizer
         .encode_batch(text_batch.to_vec(), true)
-        .map_err(E::msg)?
+        .map_err(EmbedError::tokenization)?
         .iter()
         .map(|tokens| {
             tokens
@@ -97,7 +101,7 @@ pub fn get_type_ids_ndarray(
 ) -> anyhow::Result<Array2<i64>> {
     let token_ids = tokenizer
         .encode_batch(text_batch.to_vec(), true)
-        .map_err(E::msg)?
+        .map_err(EmbedError::tokenization)?
         .iter()
         .map(|tokens| {
             tokens
@@ -115,3 +119,248 @@ pub fn get_type_ids_ndarray(
     .unwrap();
     Ok(token_ids_array)
 }
+
+/// Keeps the encodings of the most recently tokenized texts so chunks that come up again, like
+/// overlapping windows or repeated boilerplate, are not tokenized twice. A cache belongs to one
+/// tokenizer and must not be shared between models.
+#[derive(Debug)]
+pub struct TokenizationCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    encodings: HashMap<String, Encoding>,
+    order: VecDeque<String>,
+}
+
+impl TokenizationCache {
+    /// Creates a cache holding at most `capacity` encodings. The oldest ones are evicted first.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .encodings
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, text: &str) -> Option<Encoding> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .encodings
+            .get(text)
+            .cloned()
+    }
+
+    fn insert(&self, text: &str, encoding: &Encoding) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.encodings.contains_key(text) {
+            return;
+        }
+        while entries.order.len() >= self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.encodings.remove(&oldest);
+            }
+        }
+        entries.order.push_back(text.to_string());
+        entries.encodings.insert(text.to_string(), encoding.clone());
+    }
+}
+
+/// Tokenizes `text_batch` once and pads it with the padding settings of `tokenizer`. Use
+/// [`ids_ndarray`], [`attention_mask_ndarray`] and [`type_ids_ndarray`] to build the model inputs
+/// from the result instead of tokenizing the batch again for each of them. Encodings found in
+/// `cache` are reused.
+pub fn encode_batch(
+    tokenizer: &Tokenizer,
+    text_batch: &[String],
+    cache: Option<&TokenizationCache>,
+) -> anyhow::Result<Vec<Encoding>> {
+    let Some(cache) = cache else {
+        return Ok(tokenizer
+            .encode_batch(text_batch.to_vec(), true)
+            .map_err(EmbedError::tokenization)?);
+    };
+
+    let mut encodings = encode_unpadded(tokenizer, text_batch, Some(cache))?;
+    pad(tokenizer, &mut encodings)?;
+    Ok(encodings)
+}
+
+/// Tokenizes every text on its own, so each encoding only covers its own tokens. Cached
+/// encodings are stored unpadded too, since the padded length depends on the batch.
+fn encode_unpadded(
+    tokenizer: &Tokenizer,
+    text_batch: &[String],
+    cache: Option<&TokenizationCache>,
+) -> anyhow::Result<Vec<Encoding>> {
+    text_batch
+        .par_iter()
+        .map(|text| match cache.and_then(|cache| cache.get(text)) {
+            Some(encoding) => Ok(encoding),
+            None => {
+                let encoding = tokenizer
+                    .encode(text.as_str(), true)
+                    .map_err(EmbedError::tokenization)?;
+                if let Some(cache) = cache {
+                    cache.insert(text, &encoding);
+                }
+                Ok(encoding)
+            }
+        })
+        .collect()
+}
+
+fn pad(tokenizer: &Tokenizer, encodings: &mut [Encoding]) -> anyhow::Result<()> {
+    if let Some(padding) = tokenizer.get_padding() {
+        tokenizers::pad_encodings(encodings, padding).map_err(EmbedError::tokenization)?;
+    }
+    Ok(())
+}
+
+/// Tokenizes `text_batch` and groups it into batches of at most `batch_size` texts of similar
+/// token length, so that each batch is only padded to its own longest text instead of a short
+/// text being padded to the longest one that happens to share its batch. Returns the indices
+/// into `text_batch` of every batch along with its padded encodings, so callers can restore the
+/// input order.
+pub fn length_sorted_batches(
+    tokenizer: &Tokenizer,
+    text_batch: &[String],
+    batch_size: usize,
+    cache: Option<&TokenizationCache>,
+) -> anyhow::Result<Vec<(Vec<usize>, Vec<Encoding>)>> {
+    let mut encodings = encode_unpadded(tokenizer, text_batch, cache)?
+        .into_iter()
+        .map(Some)
+        .collect::<Vec<_>>();
+    let mut order = (0..encodings.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| encodings[index].as_ref().map_or(0, Encoding::len));
+
+    order
+        .chunks(batch_size.max(1))
+        .map(|indices| {
+            let mut batch = indices
+                .iter()
+                .filter_map(|&index| encodings[index].take())
+                .collect::<Vec<_>>();
+            pad(tokenizer, &mut batch)?;
+            Ok((indices.to_vec(), batch))
+        })
+        .collect()
+}
+
+fn encodings_ndarray(
+    encodings: &[Encoding],
+    values: impl Fn(&Encoding) -> &[u32],
+) -> anyhow::Result<Array2<i64>> {
+    let width = encodings
+        .first()
+        .map_or(0, |encoding| values(encoding).len());
+    let values = encodings
+        .iter()
+        .flat_map(|encoding| values(encoding).iter().map(|&value| value as i64))
+        .collect::<Vec<i64>>();
+    Ok(Array2::from_shape_vec((encodings.len(), width), values)?)
+}
+
+pub fn ids_ndarray(encodings: &[Encoding]) -> anyhow::Result<Array2<i64>> {
+    encodings_ndarray(encodings, Encoding::get_ids)
+}
+
+pub fn attention_mask_ndarray(encodings: &[Encoding]) -> anyhow::Result<Array2<i64>> {
+    encodings_ndarray(encodings, Encoding::get_attention_mask)
+}
+
+pub fn type_ids_ndarray(encodings: &[Encoding]) -> anyhow::Result<Array2<i64>> {
+    encodings_ndarray(encodings, Encoding::get_type_ids)
+}
+
+/// Stacks the token ids of `encodings` into a `(batch, tokens)` tensor.
+pub fn ids_tensor(encodings: &[Encoding], device: &Device) -> anyhow::Result<Tensor> {
+    let token_ids = encodings
+        .iter()
+        .map(|encoding| Tensor::new(encoding.get_ids(), device))
+        .collect::<candle_core::Result<Vec<_>>>()?;
+    Ok(Tensor::stack(&token_ids, 0)?)
+}
+
+/// Stacks the attention masks of `encodings` into a `(batch, tokens)` tensor.
+pub fn attention_mask_tensor(encodings: &[Encoding], device: &Device) -> anyhow::Result<Tensor> {
+    let attention_mask = encodings
+        .iter()
+        .map(|encoding| Tensor::new(encoding.get_attention_mask(), device))
+        .collect::<candle_core::Result<Vec<_>>>()?;
+    Ok(Tensor::stack(&attention_mask, 0)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenization_cache() {
+        let mut tokenizer = Tokenizer::from_pretrained("bert-base-cased", None).unwrap();
+        tokenizer.with_padding(Some(tokenizers::PaddingParams::default()));
+        let texts = vec![
+            "The same boilerplate footer".to_string(),
+            "A longer chunk of text that needs more tokens".to_string(),
+        ];
+
+        let expected = encode_batch(&tokenizer, &texts, None).unwrap();
+        let cache = TokenizationCache::new(1);
+        let encodings = encode_batch(&tokenizer, &texts, Some(&cache)).unwrap();
+        assert_eq!(cache.len(), 1);
+        let encodings = encode_batch(&tokenizer, &texts, Some(&cache)).unwrap();
+        for (encoding, expected) in encodings.iter().zip(&expected) {
+            assert_eq!(encoding.get_ids(), expected.get_ids());
+            assert_eq!(encoding.get_attention_mask(), expected.get_attention_mask());
+        }
+        assert_eq!(
+            ids_ndarray(&encodings).unwrap().dim(),
+            (2, expected[0].get_ids().len())
+        );
+    }
+
+    #[test]
+    fn test_length_sorted_batches() {
+        let mut tokenizer = Tokenizer::from_pretrained("bert-base-cased", None).unwrap();
+        tokenizer.with_padding(Some(tokenizers::PaddingParams::default()));
+        let texts = vec![
+            "A much longer chunk of text that needs quite a few more tokens".to_string(),
+            "Short".to_string(),
+            "Another long chunk of text with plenty of tokens in it".to_string(),
+            "Tiny".to_string(),
+        ];
+
+        let batches = length_sorted_batches(&tokenizer, &texts, 2, None).unwrap();
+        let indices = batches
+            .iter()
+            .map(|(indices, _)| indices.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(indices, vec![vec![1, 3], vec![2, 0]]);
+
+        // The short texts are no longer padded to the length of the long ones.
+        let (_, short) = &batches[0];
+        let (_, long) = &batches[1];
+        let expected = tokenizer.encode("Tiny", true).unwrap();
+        assert_eq!(short[1].get_ids(), expected.get_ids());
+        assert_eq!(short[0].len(), short[1].len());
+        assert!(short[0].len() < long[0].len());
+    }
+}
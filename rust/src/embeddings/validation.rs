@@ -0,0 +1,184 @@
+//! Checks embeddings for NaN or infinite values and for all-zero vectors. Bad inputs occasionally
+//! make a model produce them, and once indexed they match nothing or everything.
+
+use strum::EnumString;
+
+use super::embed::{EmbedData, EmbeddingResult, SOURCE_KEYS};
+use crate::warning::{warn, EmbedWarning, Warnings};
+
+/// What happens to a chunk whose embedding fails [`check_embedding`]. It is reported as an
+/// [`EmbedWarning::InvalidEmbedding`] either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum InvalidEmbeddingPolicy {
+    /// Keep the embedding.
+    #[default]
+    Keep,
+    /// Leave the chunk out of the results.
+    Drop,
+    /// Embed the chunk again on its own, without the padding of a batch, and leave it out if the
+    /// embedding is still invalid.
+    Retry,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidEmbedding {
+    /// Some values are NaN or infinite.
+    NonFinite,
+    /// All values are zero.
+    Zero,
+}
+
+impl InvalidEmbedding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvalidEmbedding::NonFinite => "non_finite",
+            InvalidEmbedding::Zero => "zero",
+        }
+    }
+}
+
+/// Returns what is wrong with `embedding`, if anything. A multi-vector embedding is invalid when
+/// any of its values is not finite or when all of its vectors are zero.
+pub fn check_embedding(embedding: &EmbeddingResult) -> Option<InvalidEmbedding> {
+    let vectors = match embedding {
+        EmbeddingResult::DenseVector(vector) => vec![vector.as_slice()],
+        EmbeddingResult::MultiVector(vectors) => vectors.iter().map(Vec::as_slice).collect(),
+    };
+    let mut values = vectors.into_iter().flatten();
+    if values.clone().any(|value| !value.is_finite()) {
+        Some(InvalidEmbedding::NonFinite)
+    } else if values.all(|value| *value == 0.0) {
+        Some(InvalidEmbedding::Zero)
+    } else {
+        None
+    }
+}
+
+/// Checks every embedding with [`check_embedding`], reporting the invalid ones in `warnings` and
+/// handling them as `policy` says. `retry` embeds the text of a single chunk again.
+pub(crate) async fn guard_embeddings<F, Fut>(
+    embeddings: Vec<EmbedData>,
+    policy: InvalidEmbeddingPolicy,
+    warnings: Option<&Warnings>,
+    retry: F,
+) -> anyhow::Result<Vec<EmbedData>>
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<EmbeddingResult>>,
+{
+    let mut checked = Vec::with_capacity(embeddings.len());
+    for mut embedding in embeddings {
+        let Some(problem) = check_embedding(&embedding.embedding) else {
+            checked.push(embedding);
+            continue;
+        };
+        warn(warnings, invalid_embedding_warning(&embedding, problem));
+        match policy {
+            InvalidEmbeddingPolicy::Keep => checked.push(embedding),
+            InvalidEmbeddingPolicy::Drop => {}
+            InvalidEmbeddingPolicy::Retry => {
+                let retried = retry(embedding.text.clone().unwrap_or_default()).await?;
+                if check_embedding(&retried).is_none() {
+                    embedding.embedding = retried;
+                    checked.push(embedding);
+                }
+            }
+        }
+    }
+    Ok(checked)
+}
+
+/// Number of characters of the chunk quoted in an [`EmbedWarning::InvalidEmbedding`].
+const CHUNK_PREVIEW_CHARS: usize = 60;
+
+fn invalid_embedding_warning(embedding: &EmbedData, problem: InvalidEmbedding) -> EmbedWarning {
+    let source = embedding
+        .metadata
+        .as_ref()
+        .and_then(|metadata| SOURCE_KEYS.iter().find_map(|key| metadata.get(*key)))
+        .cloned();
+    let text = embedding.text.as_deref().unwrap_or_default();
+    let mut chunk = text.chars().take(CHUNK_PREVIEW_CHARS).collect::<String>();
+    if chunk.len() < text.len() {
+        chunk.push('…');
+    }
+    EmbedWarning::InvalidEmbedding {
+        source,
+        chunk,
+        problem: problem.as_str(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding(vector: Vec<f32>, text: &str) -> EmbedData {
+        EmbedData::new(vector.into(), Some(text.to_string()), None)
+    }
+
+    #[test]
+    fn test_check_embedding() {
+        assert_eq!(check_embedding(&vec![0.1, 0.2].into()), None);
+        assert_eq!(
+            check_embedding(&vec![0.1, f32::NAN].into()),
+            Some(InvalidEmbedding::NonFinite)
+        );
+        assert_eq!(
+            check_embedding(&vec![0.0, 0.0].into()),
+            Some(InvalidEmbedding::Zero)
+        );
+        assert_eq!(
+            check_embedding(&vec![vec![0.0, 0.0], vec![0.0, 1.0]].into()),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_guard_embeddings_policies() {
+        let embeddings = || {
+            vec![
+                embedding(vec![0.5, 0.5], "fine"),
+                embedding(vec![f32::NAN, 0.5], "nan"),
+                embedding(vec![0.0, 0.0], "zero"),
+            ]
+        };
+        let retry = |text: String| async move {
+            Ok::<EmbeddingResult, anyhow::Error>(if text == "nan" {
+                vec![1.0, 0.0].into()
+            } else {
+                vec![0.0, 0.0].into()
+            })
+        };
+
+        let warnings = Warnings::new();
+        let kept = guard_embeddings(
+            embeddings(),
+            InvalidEmbeddingPolicy::Keep,
+            Some(&warnings),
+            retry,
+        )
+        .await
+        .unwrap();
+        assert_eq!(kept.len(), 3);
+        let reported = warnings.take();
+        assert_eq!(reported.len(), 2);
+        assert_eq!(reported[0].kind(), "invalid_embedding");
+
+        let dropped = guard_embeddings(embeddings(), InvalidEmbeddingPolicy::Drop, None, retry)
+            .await
+            .unwrap();
+        assert_eq!(dropped.len(), 1);
+
+        let retried = guard_embeddings(embeddings(), InvalidEmbeddingPolicy::Retry, None, retry)
+            .await
+            .unwrap();
+        let texts = retried
+            .iter()
+            .map(|embedding| embedding.text.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["fine", "nan"]);
+        assert_eq!(retried[1].embedding.to_dense().unwrap(), vec![1.0, 0.0]);
+    }
+}
@@ -0,0 +1,110 @@
+//! Crate level error type.
+//!
+//! The public API still returns `anyhow::Error`, but errors raised by this crate wrap an
+//! [`EmbedError`] so callers can tell failures apart with `err.downcast_ref::<EmbedError>()`
+//! or [`EmbedError::from_anyhow`].
+
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum EmbedError {
+    /// Model weights, configs or tokenizers could not be downloaded or loaded.
+    ModelLoad(String),
+    /// The requested model or architecture is not supported.
+    UnsupportedModel(String),
+    /// The tokenizer failed to encode the input.
+    Tokenization(String),
+    /// The forward pass of the model failed.
+    Inference(String),
+    Io(std::io::Error),
+    /// A request to a cloud embedding API failed.
+    Network(String),
+    /// A cloud embedding API rejected the request because of rate limits.
+    RateLimited(String),
+    /// The file type cannot be embedded.
+    UnsupportedFile(String),
+    /// A configuration value is out of range or inconsistent with another one.
+    InvalidConfig(String),
+}
+
+impl EmbedError {
+    /// Returns the `EmbedError` wrapped by an `anyhow::Error`, if any.
+    pub fn from_anyhow(error: &anyhow::Error) -> Option<&EmbedError> {
+        error.downcast_ref::<EmbedError>()
+    }
+
+    pub(crate) fn tokenization(error: tokenizers::Error) -> Self {
+        EmbedError::Tokenization(error.to_string())
+    }
+
+    /// Turns a non-success response from a cloud embedding API into a `RateLimited` or `Network`
+    /// error.
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            EmbedError::RateLimited(body)
+        } else {
+            EmbedError::Network(format!("{}: {}", status, body))
+        }
+    }
+}
+
+impl Display for EmbedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmbedError::ModelLoad(msg) => write!(f, "Failed to load model: {}", msg),
+            EmbedError::UnsupportedModel(model) => write!(f, "Model not supported: {}", model),
+            EmbedError::Tokenization(msg) => write!(f, "Tokenization failed: {}", msg),
+            EmbedError::Inference(msg) => write!(f, "Inference failed: {}", msg),
+            EmbedError::Io(e) => write!(f, "IO error: {}", e),
+            EmbedError::Network(msg) => write!(f, "Network error: {}", msg),
+            EmbedError::RateLimited(msg) => write!(f, "Rate limited: {}", msg),
+            EmbedError::UnsupportedFile(file) => write!(
+                f,
+                "Unsupported file type: {:?}. Currently supported file types are: pdf, md, txt, docx, pptx, epub, json, jsonl, csv, xlsx, xls",
+                file
+            ),
+            EmbedError::InvalidConfig(msg) => write!(f, "Invalid configuration: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EmbedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmbedError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for EmbedError {
+    fn from(error: std::io::Error) -> Self {
+        EmbedError::Io(error)
+    }
+}
+
+impl From<reqwest::Error> for EmbedError {
+    fn from(error: reqwest::Error) -> Self {
+        EmbedError::Network(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow() {
+        let error: anyhow::Error = EmbedError::UnsupportedModel("gpt2".to_string()).into();
+        assert!(matches!(
+            EmbedError::from_anyhow(&error),
+            Some(EmbedError::UnsupportedModel(_))
+        ));
+        assert_eq!(error.to_string(), "Model not supported: gpt2");
+
+        let error = anyhow::anyhow!("something else");
+        assert!(EmbedError::from_anyhow(&error).is_none());
+    }
+}
@@ -1,11 +1,225 @@
-use std::{collections::HashSet, io::Error, path::PathBuf};
+//! Finding the files of a directory to embed.
+//!
+//! [`FileParser::text_entries`], [`FileParser::image_entries`] and
+//! [`FileParser::audio_entries`] walk a directory lazily and yield a [`FileEntry`] per matching
+//! file, so large directories can be processed as they are read. The `get_*` methods collect the
+//! same walk into paths for the directory embedding functions.
+//!
+//! Symbolic links are skipped unless [`WalkOptions::follow_symlinks`] is set, and the depth of the
+//! walk can be capped with [`WalkOptions::max_depth`].
+//!
+//! Directories are walked in the order of their file names, so every run and every machine finds
+//! the files of the same directory in the same order. With [`WalkOptions::shard`], several workers
+//! can each embed a disjoint part of a directory without coordinating.
+
+use std::{
+    collections::HashSet,
+    io::Error,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use regex::Regex;
+use sha2::{Digest, Sha256};
 use walkdir::WalkDir;
-// use tokio::fs;
 
+/// The extensions of the files [`FileParser::text_entries`] yields by default.
+pub const TEXT_EXTENSIONS: &[&str] = &[
+    "pdf", "md", "txt", "docx", "pptx", "epub", "json", "jsonl", "csv", "xlsx", "xls",
+];
+/// The extensions of the files [`FileParser::image_entries`] yields.
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "tiff", "webp"];
+/// The extensions of the files [`FileParser::audio_entries`] yields.
+pub const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "flac", "ogg", "m4a"];
+
+/// How a directory is walked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalkOptions {
+    /// Follow symbolic links to files and directories. Links back to a directory being walked are
+    /// skipped with a warning, and a file reached through several links is yielded once. Default
+    /// is false: links are skipped.
+    pub follow_symlinks: bool,
+    /// The deepest level walked, 1 for the files directly in the directory, 2 to also walk its
+    /// subdirectories, and so on. Default is no limit, except for documents, which are only looked
+    /// for directly in the directory.
+    pub max_depth: Option<usize>,
+    /// Only yield the files of one shard of the directory. Default is every file.
+    pub shard: Option<Shard>,
+}
+
+/// One of `count` disjoint parts of the files of a directory, numbered from 0.
+///
+/// A file belongs to the shard picked by the hash of its path relative to the walked directory,
+/// so workers agree on the partition whatever the machine or where the directory is mounted, and
+/// adding a file does not move the others to another shard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// Whether the file at `relative_path` in the walked directory belongs to this shard.
+    pub fn contains(&self, relative_path: &Path) -> bool {
+        // Joined with `/` so that Windows and Unix workers hash the same path.
+        let path = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let digest = Sha256::digest(path.as_bytes());
+        let hash = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        hash % self.count.max(1) as u64 == self.index as u64
+    }
+}
+
+/// A file found in a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// The absolute path of the file, or the path as found if it cannot be canonicalized.
+    pub path: PathBuf,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The MIME type of the file guessed from its extension, see [`mime_type`].
+    pub mime: &'static str,
+    /// When the file was last modified, if the platform records it.
+    pub modified: Option<SystemTime>,
+}
+
+impl FileEntry {
+    fn from_dir_entry(entry: &walkdir::DirEntry) -> Result<Self, Error> {
+        let metadata = entry.metadata()?;
+        Ok(Self {
+            path: entry
+                .path()
+                .canonicalize()
+                .unwrap_or_else(|_| entry.path().to_path_buf()),
+            size: metadata.len(),
+            mime: mime_type(entry.path()),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    /// The path of the file as a string, as the embedding functions take it.
+    pub fn path_string(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+}
+
+/// The MIME type of a file from its extension, `application/octet-stream` when unknown.
+pub fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "pdf" => "application/pdf",
+        "md" => "text/markdown",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "jsonl" => "application/jsonl",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "xls" => "application/vnd.ms-excel",
+        "epub" => "application/epub+zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "tiff" => "image/tiff",
+        "webp" => "image/webp",
+        "wav" => "audio/wav",
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "ogg" => "audio/ogg",
+        "m4a" => "audio/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The files of a directory whose name ends with one of a set of extensions, yielded lazily as
+/// the directory is walked. Errors reading the directory are yielded as they occur.
+pub struct FileEntries {
+    directory: PathBuf,
+    walk: walkdir::IntoIter,
+    extension_regex: Regex,
+    /// The files yielded so far, when following links could reach a file twice.
+    seen: Option<HashSet<PathBuf>>,
+    shard: Option<Shard>,
+}
+
+impl FileEntries {
+    fn new(directory: &Path, extensions: &[String], recursive: bool, options: WalkOptions) -> Self {
+        let walk = WalkDir::new(directory)
+            .min_depth(1)
+            .follow_links(options.follow_symlinks)
+            .sort_by_file_name();
+        let walk = match options.max_depth {
+            Some(max_depth) => walk.max_depth(max_depth),
+            None if !recursive => walk.max_depth(1),
+            None => walk,
+        };
+        Self {
+            directory: directory.to_path_buf(),
+            walk: walk.into_iter(),
+            extension_regex: Regex::new(&format!(r"\.({})$", extensions.join("|"))).unwrap(),
+            seen: options.follow_symlinks.then(HashSet::new),
+            shard: options.shard,
+        }
+    }
+}
+
+impl Iterator for FileEntries {
+    type Item = Result<FileEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.walk.next()? {
+                Ok(entry) => entry,
+                Err(e) if e.loop_ancestor().is_some() => {
+                    tracing::warn!("Skipping symbolic link loop at {:?}", e.path());
+                    continue;
+                }
+                Err(e) => return Some(Err(e.into())),
+            };
+            let name = entry.file_name().to_str().unwrap_or("");
+            if !entry.file_type().is_file() || !self.extension_regex.is_match(name) {
+                continue;
+            }
+            if let Some(shard) = &self.shard {
+                let relative_path = entry
+                    .path()
+                    .strip_prefix(&self.directory)
+                    .unwrap_or(entry.path());
+                if !shard.contains(relative_path) {
+                    continue;
+                }
+            }
+            let file = FileEntry::from_dir_entry(&entry);
+            match (&file, &mut self.seen) {
+                (Ok(file), Some(seen)) if !seen.insert(file.path.clone()) => {}
+                _ => return Some(file),
+            }
+        }
+    }
+}
+
+fn to_strings(extensions: &[&str]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|extension| extension.to_string())
+        .collect()
+}
+
+/// Lists the files of a directory to embed. The `get_*` methods walk it as set by
+/// [`walk`](Self::walk) and keep the paths they find in [`files`](Self::files).
 pub struct FileParser {
     pub files: Vec<String>,
+    pub walk: WalkOptions,
 }
 
 impl Default for FileParser {
@@ -16,80 +230,80 @@ impl Default for FileParser {
 
 impl FileParser {
     pub fn new() -> Self {
-        Self { files: Vec::new() }
+        Self {
+            files: Vec::new(),
+            walk: WalkOptions::default(),
+        }
+    }
+
+    pub fn with_walk_options(mut self, walk: WalkOptions) -> Self {
+        self.walk = walk;
+        self
+    }
+
+    /// The documents with one of `extensions` or else one of [`TEXT_EXTENSIONS`] directly in
+    /// `directory`, or down to `options.max_depth` when set.
+    pub fn text_entries<P: AsRef<Path>>(
+        directory: P,
+        extensions: Option<Vec<String>>,
+        options: WalkOptions,
+    ) -> FileEntries {
+        let extensions = extensions.unwrap_or_else(|| to_strings(TEXT_EXTENSIONS));
+        FileEntries::new(directory.as_ref(), &extensions, false, options)
     }
 
+    /// The images in `directory` and its subdirectories.
+    pub fn image_entries<P: AsRef<Path>>(directory: P, options: WalkOptions) -> FileEntries {
+        FileEntries::new(
+            directory.as_ref(),
+            &to_strings(IMAGE_EXTENSIONS),
+            true,
+            options,
+        )
+    }
+
+    /// The audio files in `directory` and its subdirectories.
+    pub fn audio_entries<P: AsRef<Path>>(directory: P, options: WalkOptions) -> FileEntries {
+        FileEntries::new(
+            directory.as_ref(),
+            &to_strings(AUDIO_EXTENSIONS),
+            true,
+            options,
+        )
+    }
+
+    /// The paths of the [`text_entries`](Self::text_entries) of `directory_path`. Fails on the
+    /// first error reading the directory.
     pub fn get_text_files(
         &mut self,
         directory_path: &PathBuf,
         extensions: Option<Vec<String>>,
     ) -> Result<Vec<String>, Error> {
-        let extension_regex = match extensions {
-            Some(exts) => Regex::new(&format!(r"\.({})$", exts.join("|"))).unwrap(),
-            None => Regex::new(r"\.(pdf|md|txt|docx)$").unwrap(),
-        };
-
-        let entries = std::fs::read_dir(directory_path)?;
-        let mut files = Vec::new();
-
-        for entry in entries {
-            let entry = entry?;
-            if entry.file_type()?.is_file() {
-                let file_name = entry.file_name();
-                if extension_regex.is_match(file_name.to_str().unwrap_or("")) {
-                    let absolute_path =
-                        std::fs::canonicalize(entry.path()).unwrap_or_else(|_| entry.path());
-                    files.push(absolute_path.to_string_lossy().to_string());
-                }
-            }
-        }
-
-        self.files = files;
+        self.files = Self::text_entries(directory_path, extensions, self.walk)
+            .map(|entry| Ok(entry?.path_string()))
+            .collect::<Result<_, Error>>()?;
         Ok(self.files.clone())
     }
 
+    /// The paths of the [`image_entries`](Self::image_entries) of `directory_path`. Entries that
+    /// cannot be read are skipped.
     pub fn get_image_paths(&mut self, directory_path: &PathBuf) -> Result<Vec<String>, Error> {
-        let image_regex = Regex::new(r".*\.(png|jpg|jpeg|gif|bmp|tiff|webp)$").unwrap();
-
-        let image_paths: Vec<String> = WalkDir::new(directory_path)
-            .into_iter()
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.file_type().is_file())
-            .filter(|entry| image_regex.is_match(entry.file_name().to_str().unwrap_or("")))
-            .map(|entry| {
-                let absolute_path = entry
-                    .path()
-                    .canonicalize()
-                    .unwrap_or_else(|_| entry.path().to_path_buf());
-                absolute_path.to_string_lossy().to_string()
-            })
+        self.files = Self::image_entries(directory_path, self.walk)
+            .filter_map(|entry| Some(entry.ok()?.path_string()))
             .collect();
-
-        self.files = image_paths;
         Ok(self.files.clone())
     }
 
+    /// The paths of the [`audio_entries`](Self::audio_entries) of `directory_path`. Entries that
+    /// cannot be read are skipped.
     pub fn get_audio_files(&mut self, directory_path: &PathBuf) -> Result<Vec<String>, Error> {
-        let audio_regex = Regex::new(r".*\.(wav)$").unwrap();
-
-        let audio_paths: Vec<String> = WalkDir::new(directory_path)
-            .into_iter()
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.file_type().is_file())
-            .filter(|entry| audio_regex.is_match(entry.file_name().to_str().unwrap_or("")))
-            .map(|entry| {
-                let absolute_path = entry
-                    .path()
-                    .canonicalize()
-                    .unwrap_or_else(|_| entry.path().to_path_buf());
-                absolute_path.to_string_lossy().to_string()
-            })
+        self.files = Self::audio_entries(directory_path, self.walk)
+            .filter_map(|entry| Some(entry.ok()?.path_string()))
             .collect();
-
-        self.files = audio_paths;
         Ok(self.files.clone())
     }
 
+    /// The [`files`](Self::files) not in `indexed_files`.
     pub fn get_files_to_index(&self, indexed_files: &HashSet<String>) -> Vec<String> {
         let files = self
             .files
@@ -173,6 +387,121 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_text_entries() {
+        let temp_dir = TempDir::new("example").unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "hello").unwrap();
+        std::fs::create_dir(temp_dir.path().join("nested")).unwrap();
+        File::create(temp_dir.path().join("nested").join("deep.txt")).unwrap();
+        File::create(temp_dir.path().join("photo.png")).unwrap();
+
+        let entries = FileParser::text_entries(temp_dir.path(), None, WalkOptions::default())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].path.ends_with("notes.txt"));
+        assert_eq!(entries[0].size, 5);
+        assert_eq!(entries[0].mime, "text/plain");
+        assert!(entries[0].modified.is_some());
+
+        let images =
+            FileParser::image_entries(temp_dir.path(), WalkOptions::default()).collect::<Vec<_>>();
+        assert_eq!(images.len(), 1);
+        assert!(
+            FileParser::text_entries("missing_directory", None, WalkOptions::default())
+                .next()
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_options() {
+        let temp_dir = TempDir::new("example").unwrap();
+        let nested = temp_dir.path().join("a").join("b");
+        std::fs::create_dir_all(&nested).unwrap();
+        File::create(temp_dir.path().join("top.txt")).unwrap();
+        File::create(nested.join("deep.txt")).unwrap();
+        // A loop back to the root, and a second way to reach `deep.txt`.
+        std::os::unix::fs::symlink(temp_dir.path(), nested.join("loop")).unwrap();
+        std::os::unix::fs::symlink(&nested, temp_dir.path().join("shortcut")).unwrap();
+
+        let names = |options: WalkOptions| {
+            let mut names = FileParser::text_entries(temp_dir.path(), None, options)
+                .map(|entry| {
+                    let path = entry.unwrap().path;
+                    path.file_name().unwrap().to_string_lossy().to_string()
+                })
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        let recursive = |follow_symlinks| WalkOptions {
+            follow_symlinks,
+            max_depth: Some(usize::MAX),
+        };
+        assert_eq!(names(WalkOptions::default()), ["top.txt"]);
+        assert_eq!(names(recursive(false)), ["deep.txt", "top.txt"]);
+        assert_eq!(names(recursive(true)), ["deep.txt", "top.txt"]);
+        assert_eq!(
+            names(WalkOptions {
+                follow_symlinks: true,
+                max_depth: Some(2),
+            }),
+            ["deep.txt", "top.txt"]
+        );
+        assert_eq!(
+            names(WalkOptions {
+                follow_symlinks: false,
+                max_depth: Some(2),
+            }),
+            ["top.txt"]
+        );
+    }
+
+    #[test]
+    fn test_shards() {
+        let names = (0..20).map(|i| format!("{:02}.txt", i)).collect::<Vec<_>>();
+        let directories = [
+            TempDir::new("first").unwrap(),
+            TempDir::new("second").unwrap(),
+        ];
+        for directory in &directories {
+            for name in names.iter().rev() {
+                File::create(directory.path().join(name)).unwrap();
+            }
+        }
+
+        let names_in = |directory: &TempDir, shard: Option<Shard>| {
+            let options = WalkOptions {
+                shard,
+                ..Default::default()
+            };
+            FileParser::text_entries(directory.path(), None, options)
+                .map(|entry| {
+                    let path = entry.unwrap().path;
+                    path.file_name().unwrap().to_string_lossy().to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        // Sorted by name, whatever the order the files were created in.
+        assert_eq!(names_in(&directories[0], None), names);
+
+        let shards = (0..3)
+            .map(|index| names_in(&directories[0], Some(Shard { index, count: 3 })))
+            .collect::<Vec<_>>();
+        assert_eq!(shards.iter().map(Vec::len).collect::<Vec<_>>(), [4, 6, 10]);
+        let mut all = shards.concat();
+        all.sort();
+        assert_eq!(all, names);
+        // The same files in another directory are split the same way.
+        assert_eq!(
+            names_in(&directories[1], Some(Shard { index: 1, count: 3 })),
+            shards[1]
+        );
+    }
+
     #[test]
     fn test_get_image_paths() {
         let temp_dir = TempDir::new("example").unwrap();
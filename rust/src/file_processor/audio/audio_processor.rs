@@ -9,15 +9,20 @@ use std::path::PathBuf;
 use anyhow::{Error as E, Result};
 use candle_core::{Device, IndexOp, Tensor};
 use candle_nn::{ops::softmax, VarBuilder};
-use hf_hub::{api::sync::Api, Repo, RepoType};
 use rand::{distributions::Distribution, SeedableRng};
 use tokenizers::Tokenizer;
 
 use candle_transformers::models::whisper::{self as m, audio, Config};
 
+use crate::config::{AudioDecoderConfig, DEFAULT_VAD_AGGRESSIVENESS};
 use crate::embeddings::select_device;
-use crate::{embeddings::embed::AudioDecoder, file_processor::audio::pcm_decode};
+use crate::hub::HubRepo;
+use crate::{
+    embeddings::embed::AudioDecoder,
+    file_processor::audio::{pcm_decode, vad},
+};
 
+#[derive(Clone)]
 pub enum WhichAudioDecoderModel {
     Normal(m::model::Whisper),
     Quantized(m::quantized_model::Whisper),
@@ -58,11 +63,14 @@ impl WhichAudioDecoderModel {
     }
 }
 
+/// Cloning is cheap: the weights are shared, so clones can decode on several threads.
+#[derive(Clone)]
 pub struct AudioDecoderModel {
     pub model: WhichAudioDecoderModel,
     pub tokenizer: Tokenizer,
     pub config: Config,
     pub device: Device,
+    pub decoder_config: AudioDecoderConfig,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -160,7 +168,7 @@ impl<'a> Decoder<'a> {
         let model = &mut self.model;
         let audio_features = model.model.encoder_forward(mel, true)?;
         if self.verbose {
-            println!("audio features: {:?}", audio_features.dims());
+            tracing::debug!("audio features: {:?}", audio_features.dims());
         }
         let sample_len = model.model.config().max_target_positions / 2;
         let mut sum_logprob = 0f64;
@@ -263,7 +271,7 @@ impl<'a> Decoder<'a> {
                     }
                 }
                 Err(err) => {
-                    println!("Error running at {t}: {err}")
+                    tracing::warn!("Error running at {t}: {err}")
                 }
             }
         }
@@ -283,7 +291,7 @@ impl<'a> Decoder<'a> {
             let dr = self.decode_with_fallback(&mel_segment)?;
             seek += segment_size;
             if dr.no_speech_prob > m::NO_SPEECH_THRESHOLD && dr.avg_logprob < m::LOGPROB_THRESHOLD {
-                println!("no speech detected, skipping {seek} {dr:?}");
+                tracing::debug!("no speech detected, skipping {seek} {dr:?}");
                 continue;
             }
             let segment = Segment {
@@ -292,7 +300,7 @@ impl<'a> Decoder<'a> {
                 dr,
             };
             if self.timestamps {
-                println!(
+                tracing::debug!(
                     "{:.1}s -- {:.1}s",
                     segment.start,
                     segment.start + segment.duration,
@@ -312,7 +320,12 @@ impl<'a> Decoder<'a> {
                                 .tokenizer
                                 .decode(&tokens_to_decode, true)
                                 .map_err(E::msg)?;
-                            println!("  {:.1}s-{:.1}s: {}", prev_timestamp_s, timestamp_s, text);
+                            tracing::debug!(
+                                "  {:.1}s-{:.1}s: {}",
+                                prev_timestamp_s,
+                                timestamp_s,
+                                text
+                            );
                             tokens_to_decode.clear()
                         }
                         prev_timestamp_s = timestamp_s;
@@ -327,12 +340,12 @@ impl<'a> Decoder<'a> {
                         .decode(&tokens_to_decode, true)
                         .map_err(E::msg)?;
                     if !text.is_empty() {
-                        println!("  {:.1}s-...: {}", prev_timestamp_s, text);
+                        tracing::debug!("  {:.1}s-...: {}", prev_timestamp_s, text);
                     }
                     tokens_to_decode.clear()
                 }
             } else {
-                println!(
+                tracing::debug!(
                     "{:.1}s -- {:.1}s: {}",
                     segment.start,
                     segment.start + segment.duration,
@@ -340,7 +353,7 @@ impl<'a> Decoder<'a> {
                 )
             }
             if self.verbose {
-                println!("{seek}: {segment:?}, in {:?}", start.elapsed());
+                tracing::debug!("{seek}: {segment:?}, in {:?}", start.elapsed());
             }
             segments.push(segment)
         }
@@ -453,12 +466,7 @@ pub fn build_model(
         (None, None) => (default_model, default_revision),
     };
 
-    let api = Api::new()?;
-    let repo = api.repo(Repo::with_revision(
-        model_id.to_string(),
-        RepoType::Model,
-        revision.to_string(),
-    ));
+    let repo = HubRepo::new(model_id, Some(revision))?;
 
     let (config, tokenizer, model) = if quantized {
         let ext = match model_type {
@@ -524,6 +532,7 @@ impl AudioDecoderModel {
                     tokenizer,
                     config,
                     device,
+                    decoder_config: AudioDecoderConfig::default(),
                 })
             }
             true => {
@@ -551,15 +560,48 @@ impl AudioDecoderModel {
                     tokenizer,
                     config,
                     device,
+                    decoder_config: AudioDecoderConfig::default(),
                 })
             }
         }
     }
 
+    /// Files to decode at the same time, from [`AudioDecoderConfig::num_workers`] or the device.
+    /// Candle already spreads one decode over several CPU threads, so the default uses half the
+    /// cores.
+    pub fn num_workers(&self) -> usize {
+        self.decoder_config.num_workers.unwrap_or_else(|| {
+            if self.device.is_cpu() {
+                std::thread::available_parallelism().map_or(1, |n| (n.get() / 2).max(1))
+            } else {
+                1
+            }
+        })
+    }
+
+    pub fn with_decoder_config(mut self, decoder_config: AudioDecoderConfig) -> Self {
+        self.decoder_config = decoder_config;
+        self
+    }
+
+    /// Transcribes `audio_path` into segments, merged into windows when
+    /// [`AudioDecoderConfig::window_duration`] is set.
     pub fn process_audio<T: AsRef<std::path::Path>>(
         &mut self,
         audio_path: T,
     ) -> Result<Vec<Segment>> {
+        let (pcm_data, sample_rate) = pcm_decode::pcm_decode(audio_path)?;
+        if sample_rate != m::SAMPLE_RATE as u32 {
+            anyhow::bail!("input file must have a {} sampling rate", m::SAMPLE_RATE)
+        }
+        self.process_pcm(&pcm_data)
+    }
+
+    /// Transcribes mono samples at [`SAMPLE_RATE`] into segments, like
+    /// [`process_audio`](Self::process_audio).
+    pub fn process_pcm(&mut self, pcm_data: &[f32]) -> Result<Vec<Segment>> {
+        self.decoder_config.validate()?;
+        let sample_rate = SAMPLE_RATE;
         let mel_bytes = match self.config.num_mel_bins {
             80 => include_bytes!("melfilters.bytes").as_slice(),
             128 => include_bytes!("melfilters128.bytes").as_slice(),
@@ -571,23 +613,41 @@ impl AudioDecoderModel {
             &mut mel_filters,
         );
 
-        let (pcm_data, sample_rate) = pcm_decode::pcm_decode(audio_path)?;
-        if sample_rate != m::SAMPLE_RATE as u32 {
-            anyhow::bail!("input file must have a {} sampling rate", m::SAMPLE_RATE)
-        }
-        println!("pcm data loaded {}", pcm_data.len());
-        let mel = audio::pcm_to_mel(&self.config, &pcm_data, &mel_filters);
-        let mel_len = mel.len();
-        let mel = Tensor::from_vec(
-            mel,
-            (
-                1,
-                self.config.num_mel_bins,
-                mel_len / self.config.num_mel_bins,
-            ),
-            &self.device,
-        )?;
-        println!("loaded mel: {:?}", mel.dims());
+        tracing::debug!("pcm data loaded {}", pcm_data.len());
+        let regions = if self.decoder_config.vad {
+            let aggressiveness = self
+                .decoder_config
+                .vad_aggressiveness
+                .unwrap_or(DEFAULT_VAD_AGGRESSIVENESS);
+            let regions = vad::speech_regions(pcm_data, sample_rate, aggressiveness);
+            let speech = regions.iter().map(|r| r.len()).sum::<usize>();
+            tracing::debug!(
+                "speech detected in {:.1}s of {:.1}s",
+                speech as f64 / sample_rate as f64,
+                pcm_data.len() as f64 / sample_rate as f64
+            );
+            regions
+        } else {
+            vec![0..pcm_data.len()]
+        };
+        let mels = regions
+            .into_iter()
+            .map(|region| {
+                let offset = region.start as f64 / sample_rate as f64;
+                let mel = audio::pcm_to_mel(&self.config, &pcm_data[region], &mel_filters);
+                let mel_len = mel.len();
+                let mel = Tensor::from_vec(
+                    mel,
+                    (
+                        1,
+                        self.config.num_mel_bins,
+                        mel_len / self.config.num_mel_bins,
+                    ),
+                    &self.device,
+                )?;
+                Ok((offset, mel))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         let language_token = None;
 
@@ -600,10 +660,215 @@ impl AudioDecoderModel {
             false,
             false,
         )?;
-        let segments = dc.run(&mel)?;
+        let mut segments = Vec::new();
+        for (offset, mel) in mels {
+            tracing::debug!("loaded mel: {:?}", mel.dims());
+            segments.extend(dc.run(&mel)?.into_iter().map(|mut segment| {
+                segment.start += offset;
+                segment
+            }));
+        }
 
+        Ok(match self.decoder_config.window_duration {
+            Some(duration) => merge_segments(
+                segments,
+                duration,
+                self.decoder_config.window_overlap.unwrap_or(0.0),
+            ),
+            None => segments,
+        })
+    }
+}
+
+/// Sample rate, in Hz, of the audio passed to [`AudioDecoderModel::process_pcm`] and
+/// [`AudioStream`].
+pub const SAMPLE_RATE: u32 = m::SAMPLE_RATE as u32;
+
+/// Transcribes audio that arrives in chunks, such as a microphone or a network stream.
+///
+/// Samples are buffered until a full Whisper window of 30 seconds is available, which is then
+/// transcribed. Segment start times count from the first sample pushed. The stream only holds the
+/// audio, so one [`AudioDecoderModel`] can serve several streams.
+#[derive(Debug, Default)]
+pub struct AudioStream {
+    buffer: Vec<f32>,
+    /// Seconds of audio transcribed before `buffer`.
+    offset: f64,
+    /// Odd trailing byte of the last [`push_bytes`](Self::push_bytes) call.
+    pending_byte: Option<u8>,
+}
+
+impl AudioStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds mono samples at [`SAMPLE_RATE`], and returns the segments of every full window.
+    pub fn push(&mut self, model: &mut AudioDecoderModel, pcm: &[f32]) -> Result<Vec<Segment>> {
+        self.buffer.extend_from_slice(pcm);
+        let mut segments = Vec::new();
+        while self.buffer.len() >= m::N_SAMPLES {
+            let window = self.buffer.drain(..m::N_SAMPLES).collect::<Vec<_>>();
+            segments.extend(self.transcribe(model, &window)?);
+        }
         Ok(segments)
     }
+
+    /// Adds 16-bit little-endian mono PCM at [`SAMPLE_RATE`], like [`push`](Self::push).
+    /// Chunks do not have to end on a sample boundary.
+    pub fn push_bytes(
+        &mut self,
+        model: &mut AudioDecoderModel,
+        bytes: &[u8],
+    ) -> Result<Vec<Segment>> {
+        let mut data = Vec::with_capacity(bytes.len() + 1);
+        data.extend(self.pending_byte.take());
+        data.extend_from_slice(bytes);
+        let samples = data.chunks_exact(2);
+        self.pending_byte = samples.remainder().first().copied();
+        let pcm = samples
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect::<Vec<_>>();
+        self.push(model, &pcm)
+    }
+
+    /// Transcribes the audio still buffered, once the input has ended.
+    pub fn finish(&mut self, model: &mut AudioDecoderModel) -> Result<Vec<Segment>> {
+        self.pending_byte = None;
+        if self.buffer.is_empty() {
+            return Ok(Vec::new());
+        }
+        let window = std::mem::take(&mut self.buffer);
+        self.transcribe(model, &window)
+    }
+
+    fn transcribe(
+        &mut self,
+        model: &mut AudioDecoderModel,
+        window: &[f32],
+    ) -> Result<Vec<Segment>> {
+        let offset = self.offset;
+        self.offset += window.len() as f64 / SAMPLE_RATE as f64;
+        Ok(model
+            .process_pcm(window)?
+            .into_iter()
+            .map(|mut segment| {
+                segment.start += offset;
+                segment
+            })
+            .collect())
+    }
+}
+
+/// Merges consecutive segments into windows of at most `duration` seconds, unless a single
+/// segment is longer. A window starts at the first segment that begins `overlap` seconds or less
+/// before the end of the previous one, and always after the start of the previous window.
+pub fn merge_segments(segments: Vec<Segment>, duration: f64, overlap: f64) -> Vec<Segment> {
+    let end = |segment: &Segment| segment.start + segment.duration;
+    let mut windows = Vec::new();
+    let mut first = 0;
+    while first < segments.len() {
+        let window_start = segments[first].start;
+        let mut last = first;
+        while last + 1 < segments.len() && end(&segments[last + 1]) - window_start <= duration {
+            last += 1;
+        }
+        let window = &segments[first..=last];
+        windows.push(merge_window(window));
+        if last + 1 == segments.len() {
+            break;
+        }
+        let window_end = end(&segments[last]);
+        first = (first + 1..=last)
+            .find(|&i| segments[i].start >= window_end - overlap)
+            .unwrap_or(last + 1);
+    }
+    windows
+}
+
+fn merge_window(window: &[Segment]) -> Segment {
+    if let [segment] = window {
+        return segment.clone();
+    }
+    let count = window.len() as f64;
+    let mean = |value: fn(&DecodingResult) -> f64| {
+        window.iter().map(|segment| value(&segment.dr)).sum::<f64>() / count
+    };
+    let start = window[0].start;
+    let last = &window[window.len() - 1];
+    Segment {
+        start,
+        duration: last.start + last.duration - start,
+        dr: DecodingResult {
+            tokens: window
+                .iter()
+                .flat_map(|segment| segment.dr.tokens.iter().copied())
+                .collect(),
+            text: window
+                .iter()
+                .map(|segment| segment.dr.text.trim())
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+            avg_logprob: mean(|dr| dr.avg_logprob),
+            no_speech_prob: mean(|dr| dr.no_speech_prob),
+            temperature: window
+                .iter()
+                .map(|segment| segment.dr.temperature)
+                .fold(0.0, f64::max),
+            compression_ratio: mean(|dr| dr.compression_ratio),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, duration: f64, text: &str) -> Segment {
+        Segment {
+            start,
+            duration,
+            dr: DecodingResult {
+                text: text.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_merge_segments() {
+        let segments = (0..6)
+            .map(|i| segment(i as f64 * 5.0, 5.0, &format!("s{i}")))
+            .collect::<Vec<_>>();
+
+        let windows = merge_segments(segments.clone(), 10.0, 0.0);
+        let texts = windows
+            .iter()
+            .map(|w| w.dr.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["s0 s1", "s2 s3", "s4 s5"]);
+        assert_eq!(windows[1].start, 10.0);
+        assert_eq!(windows[1].duration, 10.0);
+
+        let windows = merge_segments(segments, 15.0, 5.0);
+        let texts = windows
+            .iter()
+            .map(|w| w.dr.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["s0 s1 s2", "s2 s3 s4", "s4 s5"]);
+    }
+
+    #[test]
+    fn test_merge_keeps_long_segments() {
+        let segments = vec![segment(0.0, 30.0, "long"), segment(30.0, 2.0, "short")];
+        let windows = merge_segments(segments, 10.0, 5.0);
+        let texts = windows
+            .iter()
+            .map(|w| w.dr.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["long", "short"]);
+    }
 }
 
 impl AudioDecoder for AudioDecoderModel {
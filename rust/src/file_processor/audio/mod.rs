@@ -1,2 +1,3 @@
 pub mod audio_processor;
 pub mod pcm_decode;
+pub mod vad;
@@ -0,0 +1,127 @@
+//! Energy based voice activity detection, used to skip silence before running Whisper on long
+//! recordings.
+
+use std::ops::Range;
+
+/// Length of the frames the energy is measured on.
+const FRAME_SECONDS: f64 = 0.03;
+/// Frames quieter than this are never speech, whatever the noise floor.
+const MIN_SPEECH_DB: f32 = -55.0;
+/// Noise floor used when the recording is mostly speech, so its quietest frames are not
+/// taken as background noise.
+const MAX_NOISE_FLOOR_DB: f32 = -45.0;
+/// Speech kept before and after each region so word onsets are not clipped.
+const PADDING_SECONDS: f64 = 0.2;
+/// Regions shorter than this are dropped as clicks.
+const MIN_SPEECH_SECONDS: f64 = 0.25;
+
+/// How far above the noise floor a frame has to be to count as speech, by aggressiveness.
+const MARGIN_DB: [f32; 4] = [6.0, 9.0, 12.0, 15.0];
+/// Pauses shorter than this are kept inside a region, by aggressiveness.
+const MAX_PAUSE_SECONDS: [f64; 4] = [1.5, 1.0, 0.6, 0.3];
+
+/// Returns the sample ranges of `pcm` that contain speech.
+///
+/// `aggressiveness` goes from 0 to 3, like webrtc VAD modes: higher values need louder speech
+/// and split on shorter pauses, so more audio is skipped.
+pub fn speech_regions(pcm: &[f32], sample_rate: u32, aggressiveness: u8) -> Vec<Range<usize>> {
+    let level = usize::from(aggressiveness.min(3));
+    let frame_len = ((sample_rate as f64 * FRAME_SECONDS) as usize).max(1);
+    let energies = pcm.chunks(frame_len).map(frame_db).collect::<Vec<_>>();
+    if energies.is_empty() {
+        return Vec::new();
+    }
+
+    let mut sorted = energies.clone();
+    sorted.sort_by(f32::total_cmp);
+    let noise_floor = sorted[sorted.len() / 10].min(MAX_NOISE_FLOOR_DB);
+    let threshold = (noise_floor + MARGIN_DB[level]).max(MIN_SPEECH_DB);
+
+    let to_frames = |seconds: f64| (seconds / FRAME_SECONDS).ceil() as usize;
+    let max_pause = to_frames(MAX_PAUSE_SECONDS[level]);
+    let min_speech = to_frames(MIN_SPEECH_SECONDS);
+    let padding = (sample_rate as f64 * PADDING_SECONDS) as usize;
+
+    let mut frames: Vec<Range<usize>> = Vec::new();
+    for (i, _) in energies.iter().enumerate().filter(|(_, &e)| e > threshold) {
+        match frames.last_mut() {
+            Some(region) if i - region.end <= max_pause => region.end = i + 1,
+            _ => frames.push(i..i + 1),
+        }
+    }
+
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    for region in frames.into_iter().filter(|r| r.len() >= min_speech) {
+        let start = (region.start * frame_len).saturating_sub(padding);
+        let end = (region.end * frame_len + padding).min(pcm.len());
+        match regions.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => regions.push(start..end),
+        }
+    }
+    regions
+}
+
+fn frame_db(frame: &[f32]) -> f32 {
+    let power = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+    10.0 * (power + 1e-10).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn tone(seconds: f64) -> Vec<f32> {
+        (0..(seconds * RATE as f64) as usize)
+            .map(|i| 0.3 * (i as f32 * 440.0 * std::f32::consts::TAU / RATE as f32).sin())
+            .collect()
+    }
+
+    fn silence(seconds: f64) -> Vec<f32> {
+        vec![0.0; (seconds * RATE as f64) as usize]
+    }
+
+    #[test]
+    fn test_speech_regions() {
+        let pcm = [
+            silence(5.0),
+            tone(2.0),
+            silence(5.0),
+            tone(1.0),
+            silence(3.0),
+        ]
+        .concat();
+        let regions = speech_regions(&pcm, RATE, 1);
+        assert_eq!(regions.len(), 2);
+        let seconds = |sample: usize| sample as f64 / RATE as f64;
+        assert!((seconds(regions[0].start) - 4.8).abs() < 0.1);
+        assert!((seconds(regions[0].end) - 7.2).abs() < 0.1);
+        assert!((seconds(regions[1].start) - 11.8).abs() < 0.1);
+        assert!((seconds(regions[1].end) - 13.2).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_speech_regions_pauses_by_aggressiveness() {
+        let pcm = [tone(1.0), silence(0.8), tone(1.0)].concat();
+        assert_eq!(speech_regions(&pcm, RATE, 0).len(), 1);
+        assert_eq!(speech_regions(&pcm, RATE, 3).len(), 2);
+
+        let pcm = [tone(1.0), silence(2.0), tone(1.0)].concat();
+        assert_eq!(speech_regions(&pcm, RATE, 0).len(), 2);
+    }
+
+    #[test]
+    fn test_speech_regions_without_silence() {
+        let pcm = tone(3.0);
+        assert_eq!(speech_regions(&pcm, RATE, 3), vec![0..pcm.len()]);
+    }
+
+    #[test]
+    fn test_speech_regions_silence() {
+        assert!(speech_regions(&silence(10.0), RATE, 0).is_empty());
+        assert!(speech_regions(&[], RATE, 0).is_empty());
+        assert!(speech_regions(&tone(0.1), RATE, 0).is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::Error;
+
+use super::table::Table;
+
+/// A struct for processing CSV files.
+pub struct CsvProcessor;
+
+impl CsvProcessor {
+    /// Reads a CSV file whose first row holds the column names. Rows may have more or fewer
+    /// cells than the header, and invalid UTF-8 is replaced rather than rejected.
+    pub fn read_table<T: AsRef<std::path::Path>>(file_path: &T) -> Result<Table, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(file_path)?;
+        let record_values = |record: &csv::ByteRecord| {
+            record
+                .iter()
+                .map(|value| String::from_utf8_lossy(value).to_string())
+                .collect::<Vec<_>>()
+        };
+        let columns = record_values(reader.byte_headers()?);
+        let rows = reader
+            .byte_records()
+            .map(|record| Ok(record_values(&record?)))
+            .collect::<Result<Vec<_>, csv::Error>>()?;
+        Ok(Table { columns, rows })
+    }
+
+    /// Extracts the rows of a CSV file as `column: value` lines, see [`Table::row_text`].
+    pub fn extract_text<T: AsRef<std::path::Path>>(file_path: &T) -> Result<String, Error> {
+        Ok(Self::read_table(file_path)?.to_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_read_table() {
+        let temp_dir = TempDir::new("csv").unwrap();
+        let csv_file = temp_dir.path().join("people.csv");
+        let mut file = std::fs::File::create(&csv_file).unwrap();
+        file.write_all(b"name,city\nAlice,\"Paris, France\"\nBob\n")
+            .unwrap();
+
+        let table = CsvProcessor::read_table(&csv_file).unwrap();
+        assert_eq!(table.columns, ["name", "city"]);
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(
+            CsvProcessor::extract_text(&csv_file).unwrap(),
+            "name: Alice; city: Paris, France\nname: Bob"
+        );
+    }
+}
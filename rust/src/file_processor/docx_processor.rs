@@ -1,25 +1,156 @@
 use anyhow::Error;
 use docx_parser::MarkdownDocument;
-/// A struct for processing PDF files.
+
+use super::ooxml;
+
+/// Which parts of a DOCX file besides its body to take the text of. The body alone is taken by
+/// default, since headers, footers and notes repeat or interrupt it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DocxTextOptions {
+    /// Put the text of the page headers before the body and of the page footers after it. Each
+    /// distinct paragraph is kept once, even if it is on the headers of several sections.
+    pub headers_footers: bool,
+    /// Put the footnotes and endnotes after the body, under the headings `Footnotes` and
+    /// `Endnotes`.
+    pub footnotes: bool,
+}
+
+/// The text under one heading of a DOCX file, up to the next heading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocxSection {
+    /// The heading of the section and the headings above it, outermost first. Empty for the text
+    /// before the first heading.
+    pub headings: Vec<String>,
+    /// The section as Markdown, starting with its heading.
+    pub text: String,
+}
+
+/// A struct for processing DOCX files.
 pub struct DocxProcessor;
 
 impl DocxProcessor {
-    /// Extracts text from a PDF file.
+    /// Extracts text from a DOCX file as Markdown, so headings, lists and tables keep their
+    /// structure in the chunks.
     ///
     /// # Arguments
     ///
-    /// * `file_path` - The path to the PDF file.
+    /// * `file_path` - The path to the DOCX file.
     ///
     /// # Returns
     ///
     /// Returns a `Result` containing the extracted text as a `String` if successful,
     /// or an `Error` if an error occurred during the extraction process.
     pub fn extract_text<T: AsRef<std::path::Path>>(file_path: &T) -> Result<String, Error> {
+        Self::extract_text_with_options(file_path, &DocxTextOptions::default())
+    }
+
+    /// Extracts text from a DOCX file like [`extract_text`](Self::extract_text), together with
+    /// the headers, footers and notes selected by `options`.
+    pub fn extract_text_with_options<T: AsRef<std::path::Path>>(
+        file_path: &T,
+        options: &DocxTextOptions,
+    ) -> Result<String, Error> {
+        if !file_path.as_ref().exists() {
+            return Err(anyhow::anyhow!(
+                "File not found: {}",
+                file_path.as_ref().display()
+            ));
+        }
         let docs = MarkdownDocument::from_file(file_path);
-        let markdown = docs.to_markdown(false);
-        let content = markdown_to_text::convert(&markdown);
-        Ok(content)
+        let body = docs.to_markdown(false);
+        if *options == DocxTextOptions::default() {
+            return Ok(body);
+        }
+
+        let mut archive = ooxml::open_archive(file_path)?;
+        let mut part_paragraphs = |prefix: &str| -> Result<Vec<String>, Error> {
+            let mut names = archive
+                .file_names()
+                .filter(|name| name.starts_with(prefix) && name.ends_with(".xml"))
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            names.sort();
+            let mut paragraphs = Vec::<String>::new();
+            for name in names {
+                for paragraph in ooxml::paragraphs(&ooxml::read_part(&mut archive, &name)?, "w")? {
+                    if !paragraphs.contains(&paragraph) {
+                        paragraphs.push(paragraph);
+                    }
+                }
+            }
+            Ok(paragraphs)
+        };
+
+        let mut blocks = Vec::new();
+        if options.headers_footers {
+            blocks.extend(part_paragraphs("word/header")?);
+        }
+        blocks.push(body);
+        if options.headers_footers {
+            blocks.extend(part_paragraphs("word/footer")?);
+        }
+        if options.footnotes {
+            for (prefix, heading) in [
+                ("word/footnotes", "Footnotes"),
+                ("word/endnotes", "Endnotes"),
+            ] {
+                let notes = part_paragraphs(prefix)?;
+                if !notes.is_empty() {
+                    blocks.push(format!("# {heading}"));
+                    blocks.extend(notes);
+                }
+            }
+        }
+        Ok(blocks.join("\n\n"))
     }
+
+    /// Extracts the text of a DOCX file like
+    /// [`extract_text_with_options`](Self::extract_text_with_options), split into one section per
+    /// heading.
+    pub fn extract_sections<T: AsRef<std::path::Path>>(
+        file_path: &T,
+        options: &DocxTextOptions,
+    ) -> Result<Vec<DocxSection>, Error> {
+        Ok(split_at_headings(&Self::extract_text_with_options(
+            file_path, options,
+        )?))
+    }
+}
+
+/// The level and title of a Markdown heading line.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let title = line[level..].strip_prefix(' ')?.trim();
+    ((1..=6).contains(&level) && !title.is_empty()).then_some((level, title))
+}
+
+fn split_at_headings(markdown: &str) -> Vec<DocxSection> {
+    let mut headings = Vec::<(usize, String)>::new();
+    let mut sections = vec![DocxSection {
+        headings: Vec::new(),
+        text: String::new(),
+    }];
+    for line in markdown.lines() {
+        if let Some((level, title)) = heading(line) {
+            headings.retain(|(outer, _)| *outer < level);
+            headings.push((level, title.to_string()));
+            sections.push(DocxSection {
+                headings: headings.iter().map(|(_, title)| title.clone()).collect(),
+                text: String::new(),
+            });
+        }
+        let section = sections.last_mut().unwrap();
+        section.text.push_str(line);
+        section.text.push('\n');
+    }
+    // A heading directly followed by a subheading has no text of its own.
+    sections.retain(|section| {
+        section
+            .text
+            .lines()
+            .any(|line| !line.trim().is_empty() && heading(line).is_none())
+    });
+    sections
 }
 
 #[cfg(test)]
@@ -43,4 +174,19 @@ mod tests {
 
         DocxProcessor::extract_text(&invalid_file_path).unwrap_err();
     }
+
+    #[test]
+    fn test_split_at_headings() {
+        let markdown = "Cover text\n\n# Setup\n\n## Linux\n\n- install\n- run\n\n\
+                        ## Windows\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n# Usage\n\nCall it.\n";
+        let sections = split_at_headings(markdown);
+        let headings = sections
+            .iter()
+            .map(|section| section.headings.join(" > "))
+            .collect::<Vec<_>>();
+        assert_eq!(headings, ["", "Setup > Linux", "Setup > Windows", "Usage"]);
+        assert!(sections[1].text.starts_with("## Linux"));
+        assert!(sections[1].text.contains("- install\n- run"));
+        assert!(sections[2].text.contains("| 1 | 2 |"));
+    }
 }
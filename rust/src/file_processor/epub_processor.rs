@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Error};
+use quick_xml::events::{BytesStart, Event};
+use scraper::{ElementRef, Html, Selector};
+
+use super::ooxml;
+
+/// The text of one chapter of an EPUB file, i.e. one document of its reading order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpubChapter {
+    /// The first heading of the chapter, or the title of its document if it has no heading.
+    pub title: Option<String>,
+    /// The chapter as Markdown, with its headings kept as `#` lines.
+    pub text: String,
+}
+
+/// A struct for processing EPUB files.
+pub struct EpubProcessor;
+
+impl EpubProcessor {
+    /// Extracts the chapters of an EPUB file in reading order. Chapters without text, such as
+    /// cover images, are left out.
+    pub fn extract_chapters<T: AsRef<std::path::Path>>(
+        file_path: &T,
+    ) -> Result<Vec<EpubChapter>, Error> {
+        let mut archive = ooxml::open_archive(file_path)?;
+        let container = ooxml::read_part(&mut archive, "META-INF/container.xml")?;
+        let package_path = attribute_values(&container, b"rootfile", "full-path")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("EPUB container names no package document"))?;
+        let package = ooxml::read_part(&mut archive, &package_path)?;
+        let package_dir = package_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+        let mut chapters = Vec::new();
+        for href in reading_order(&package)? {
+            let xhtml = ooxml::read_part(&mut archive, &resolve(package_dir, &href))?;
+            let chapter = chapter(&xhtml);
+            if !chapter.text.is_empty() {
+                chapters.push(chapter);
+            }
+        }
+        Ok(chapters)
+    }
+
+    /// Extracts the text of an EPUB file, with the chapters separated by blank lines.
+    pub fn extract_text<T: AsRef<std::path::Path>>(file_path: &T) -> Result<String, Error> {
+        Ok(Self::extract_chapters(file_path)?
+            .into_iter()
+            .map(|chapter| chapter.text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
+
+/// The values of `attribute` on the elements named `tag`, whatever their namespace prefix.
+fn attribute_values(xml: &str, tag: &[u8], attribute: &str) -> Result<Vec<String>, Error> {
+    let mut values = Vec::new();
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) | Event::Empty(element)
+                if element.local_name().as_ref() == tag =>
+            {
+                if let Some(value) = attribute_value(&element, attribute)? {
+                    values.push(value);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(values)
+}
+
+fn attribute_value(element: &BytesStart, attribute: &str) -> Result<Option<String>, Error> {
+    Ok(match element.try_get_attribute(attribute)? {
+        Some(value) => Some(value.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+/// The paths, relative to the package document, of the documents of its spine in order.
+fn reading_order(package: &str) -> Result<Vec<String>, Error> {
+    let mut manifest = Vec::new();
+    let mut spine = Vec::new();
+    let mut reader = quick_xml::Reader::from_str(package);
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) | Event::Empty(element) => match element.local_name().as_ref() {
+                b"item" => {
+                    if let (Some(id), Some(href)) = (
+                        attribute_value(&element, "id")?,
+                        attribute_value(&element, "href")?,
+                    ) {
+                        manifest.push((id, href));
+                    }
+                }
+                b"itemref" => spine.extend(attribute_value(&element, "idref")?),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(spine
+        .iter()
+        .filter_map(|idref| {
+            manifest
+                .iter()
+                .find(|(id, _)| id == idref)
+                .map(|(_, href)| href.clone())
+        })
+        .collect())
+}
+
+/// The path in the archive of `href`, relative to the directory `base`.
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let mut segments = base
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// The text of a chapter document, one block per paragraph, list item or heading.
+fn chapter(xhtml: &str) -> EpubChapter {
+    let document = Html::parse_document(xhtml);
+    let blocks = Selector::parse("h1, h2, h3, h4, h5, h6, p, li, blockquote, pre, td").unwrap();
+    let text_of = |element: &ElementRef| {
+        element
+            .text()
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut title = None;
+    let mut lines = Vec::new();
+    for element in document.select(&blocks) {
+        // Paragraphs inside list items or quotes are part of their text already.
+        let nested = element
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .any(|ancestor| blocks.matches(&ancestor));
+        let text = text_of(&element);
+        if nested || text.is_empty() {
+            continue;
+        }
+        let name = element.value().name();
+        match name
+            .strip_prefix('h')
+            .and_then(|level| level.parse::<usize>().ok())
+        {
+            Some(level) => {
+                title.get_or_insert_with(|| text.clone());
+                lines.push(format!("{} {}", "#".repeat(level), text));
+            }
+            None if name == "li" => lines.push(format!("- {text}")),
+            None => lines.push(text),
+        }
+    }
+    let title = title.or_else(|| {
+        let selector = Selector::parse("title").unwrap();
+        document
+            .select(&selector)
+            .map(|element| text_of(&element))
+            .find(|text| !text.is_empty())
+    });
+    EpubChapter {
+        title,
+        text: lines.join("\n\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reading_order() {
+        let package = r#"<package xmlns="http://www.idpf.org/2007/opf">
+            <manifest>
+                <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+                <item id="c2" href="text/ch2.xhtml" media-type="application/xhtml+xml"/>
+                <item id="c1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+            </manifest>
+            <spine><itemref idref="cover"/><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+        assert_eq!(
+            reading_order(package).unwrap(),
+            ["cover.xhtml", "text/ch1.xhtml", "text/ch2.xhtml"]
+        );
+        assert_eq!(
+            resolve("OEBPS", "text/ch1.xhtml#start"),
+            "OEBPS/text/ch1.xhtml"
+        );
+        assert_eq!(resolve("OEBPS/text", "../ch1.xhtml"), "OEBPS/ch1.xhtml");
+        assert_eq!(resolve("", "ch1.xhtml"), "ch1.xhtml");
+    }
+
+    #[test]
+    fn test_chapter() {
+        let xhtml = r#"<html><head><title>Book</title></head><body>
+            <h1>Chapter One</h1>
+            <p>It was a   bright cold day.</p>
+            <ul><li><p>first</p></li><li>second</li></ul>
+            <h2>A scene</h2><p>The clocks were striking.</p>
+        </body></html>"#;
+        let chapter = chapter(xhtml);
+        assert_eq!(chapter.title.as_deref(), Some("Chapter One"));
+        assert_eq!(
+            chapter.text,
+            "# Chapter One\n\nIt was a bright cold day.\n\n- first\n\n- second\n\n\
+             ## A scene\n\nThe clocks were striking."
+        );
+
+        let untitled = super::chapter("<html><head><title>Cover</title></head><body/></html>");
+        assert_eq!(untitled.title.as_deref(), Some("Cover"));
+        assert!(untitled.text.is_empty());
+    }
+
+    #[test]
+    fn test_extract_text_invalid_file_path() {
+        EpubProcessor::extract_text(&"invalid.epub").unwrap_err();
+    }
+}
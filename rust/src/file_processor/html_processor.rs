@@ -1,11 +1,11 @@
+use crate::embeddings::cloud::truncation::flag_truncated;
 use crate::embeddings::embed::{EmbedData, Embedder};
-use crate::embeddings::get_text_metadata;
+use crate::embeddings::{embed_with_backoff, get_text_metadata, BatchBackoff};
 use crate::text_loader::{SplittingStrategy, TextLoader};
 use anyhow::Result;
 use scraper::{Html, Selector};
 use serde_json::json;
 use std::collections::{HashMap, HashSet};
-use std::rc::Rc;
 use url::Url;
 
 #[derive(Debug)]
@@ -113,9 +113,13 @@ impl HtmlDocument {
 
             let metadata_hashmap: HashMap<String, String> = serde_json::from_value(metadata)?;
 
-            let encodings = embedder.embed(&chunks, batch_size).await?;
-            let embeddings =
-                get_text_metadata(&Rc::new(encodings), &chunks, &Some(metadata_hashmap))?;
+            let encodings =
+                embed_with_backoff(&chunks, batch_size, &BatchBackoff::default(), |texts, b| {
+                    embedder.embed(texts, Some(b))
+                })
+                .await?;
+            let mut embeddings = get_text_metadata(&encodings, &chunks, &Some(metadata_hashmap))?;
+            flag_truncated(embedder.truncation(), &mut embeddings);
             embed_data.extend(embeddings);
         }
 
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::error::EmbedError;
+
+/// Images larger than this are rejected unless a different limit is configured.
+pub const DEFAULT_MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+/// Time allowed for the whole image download unless a different timeout is configured.
+pub const DEFAULT_IMAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An image on disk, removed when dropped if it was downloaded.
+pub struct ImageSource {
+    pub path: PathBuf,
+    downloaded: bool,
+}
+
+impl Drop for ImageSource {
+    fn drop(&mut self) {
+        if self.downloaded {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Resolves `source` to a local image. `http(s)` URLs are downloaded to a temporary file, refusing
+/// bodies larger than `max_bytes` and giving up after `timeout`. `file://` URLs and plain paths are
+/// used as they are.
+pub async fn resolve_image(
+    source: &str,
+    max_bytes: u64,
+    timeout: Duration,
+) -> anyhow::Result<ImageSource> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        return download_image(source, max_bytes, timeout).await;
+    }
+    let path = PathBuf::from(source.strip_prefix("file://").unwrap_or(source));
+    if !path.exists() {
+        return Err(EmbedError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Image not found: {:?}", path),
+        ))
+        .into());
+    }
+    Ok(ImageSource {
+        path,
+        downloaded: false,
+    })
+}
+
+async fn download_image(
+    url: &str,
+    max_bytes: u64,
+    timeout: Duration,
+) -> anyhow::Result<ImageSource> {
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(EmbedError::from)?;
+    let mut response = client.get(url).send().await.map_err(EmbedError::from)?;
+    if !response.status().is_success() {
+        return Err(EmbedError::from_response(response).await.into());
+    }
+    if response.content_length().is_some_and(|len| len > max_bytes) {
+        return Err(too_large(url, max_bytes));
+    }
+
+    // The content length can be missing or wrong, so the limit is enforced while reading too.
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(EmbedError::from)? {
+        if (bytes.len() + chunk.len()) as u64 > max_bytes {
+            return Err(too_large(url, max_bytes));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    // The image decoder picks the format from the file extension.
+    let format = image::guess_format(&bytes)
+        .map_err(|_| EmbedError::UnsupportedFile(format!("{} is not an image", url)))?;
+    let extension = format.extensions_str().first().copied().unwrap_or("img");
+    let path = std::env::temp_dir().join(format!(
+        "embed_anything_{:016x}.{}",
+        rand::thread_rng().gen::<u64>(),
+        extension
+    ));
+    std::fs::write(&path, bytes).map_err(EmbedError::from)?;
+    Ok(ImageSource {
+        path,
+        downloaded: true,
+    })
+}
+
+fn too_large(url: &str, max_bytes: u64) -> anyhow::Error {
+    EmbedError::Network(format!("{} is larger than {} bytes", url, max_bytes)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_local_image() {
+        let image = resolve_image(
+            "../test_files/clip/cat1.jpg",
+            DEFAULT_MAX_IMAGE_BYTES,
+            DEFAULT_IMAGE_TIMEOUT,
+        )
+        .await
+        .unwrap();
+        assert!(image.path.exists());
+
+        assert!(resolve_image(
+            "file://../test_files/clip/missing.jpg",
+            DEFAULT_MAX_IMAGE_BYTES,
+            DEFAULT_IMAGE_TIMEOUT
+        )
+        .await
+        .is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Error};
+use serde_json::Value;
+
+/// Which fields of JSON records to embed and which to attach to their chunks as metadata.
+/// Fields of nested objects are named by their path, e.g. `author.name`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonTextOptions {
+    /// The fields whose values are embedded, in this order. Empty to embed every field as
+    /// `field: value` lines.
+    pub text_fields: Vec<String>,
+    /// The fields whose values are added to the metadata of the chunks of their record, under
+    /// the field name.
+    pub metadata_fields: Vec<String>,
+}
+
+/// One record of a JSON or JSONL file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonRecord {
+    /// The number of the record, counting from 1: its line in a JSONL file, its position in a
+    /// JSON array.
+    pub number: usize,
+    pub text: String,
+    /// The values of the metadata fields the record has.
+    pub metadata: HashMap<String, String>,
+}
+
+/// A struct for processing JSON and JSONL files.
+pub struct JsonProcessor;
+
+impl JsonProcessor {
+    /// Reads the records of a JSON or JSONL file: the lines of a `.jsonl` file, the elements of a
+    /// JSON array, or a single JSON value. Records without any of the text fields are left out.
+    pub fn extract_records<T: AsRef<std::path::Path>>(
+        file_path: &T,
+        options: &JsonTextOptions,
+    ) -> Result<Vec<JsonRecord>, Error> {
+        let content = std::fs::read_to_string(file_path)?;
+        let values = if file_path
+            .as_ref()
+            .extension()
+            .is_some_and(|extension| extension == "jsonl")
+        {
+            parse_lines(&content)?
+        } else {
+            match serde_json::from_str(&content)? {
+                Value::Array(values) => (1..).zip(values).collect(),
+                value => vec![(1, value)],
+            }
+        };
+        Ok(values
+            .into_iter()
+            .filter_map(|(number, value)| record(number, &value, options))
+            .collect())
+    }
+
+    /// Extracts the text of every record of a JSON or JSONL file, separated by blank lines.
+    pub fn extract_text<T: AsRef<std::path::Path>>(file_path: &T) -> Result<String, Error> {
+        Ok(
+            Self::extract_records(file_path, &JsonTextOptions::default())?
+                .into_iter()
+                .map(|record| record.text)
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        )
+    }
+}
+
+/// The values of the non-blank lines of a JSONL file, with their line numbers.
+fn parse_lines(content: &str) -> Result<Vec<(usize, Value)>, Error> {
+    (1..)
+        .zip(content.lines())
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            serde_json::from_str(line)
+                .map(|value| (number, value))
+                .map_err(|e| anyhow!("Invalid JSON on line {number}: {e}"))
+        })
+        .collect()
+}
+
+/// The value at a dotted path of `value`, e.g. `author.name`.
+fn field<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |value, key| value.get(key))
+        .filter(|value| !value.is_null())
+}
+
+/// A value as text: strings as they are, anything else as JSON.
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn record(number: usize, value: &Value, options: &JsonTextOptions) -> Option<JsonRecord> {
+    let text = if options.text_fields.is_empty() {
+        match value {
+            Value::Object(fields) => fields
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| format!("{key}: {}", value_text(value)))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            value => value_text(value),
+        }
+    } else {
+        options
+            .text_fields
+            .iter()
+            .filter_map(|path| field(value, path))
+            .map(value_text)
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+    if text.trim().is_empty() {
+        return None;
+    }
+    let metadata = options
+        .metadata_fields
+        .iter()
+        .filter_map(|path| Some((path.clone(), value_text(field(value, path)?))))
+        .collect();
+    Some(JsonRecord {
+        number,
+        text,
+        metadata,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_extract_records() {
+        let temp_dir = TempDir::new("json").unwrap();
+        let jsonl_file = temp_dir.path().join("corpus.jsonl");
+        let mut file = std::fs::File::create(&jsonl_file).unwrap();
+        file.write_all(
+            b"{\"id\": 7, \"title\": \"Rust\", \"body\": \"Ownership rules.\", \"meta\": {\"lang\": \"en\"}}\n\
+              \n\
+              {\"id\": 8, \"title\": \"Empty\"}\n\
+              {\"id\": 9, \"body\": \"Borrowing.\"}\n",
+        )
+        .unwrap();
+
+        let options = JsonTextOptions {
+            text_fields: vec!["title".to_string(), "body".to_string()],
+            metadata_fields: vec!["id".to_string(), "meta.lang".to_string()],
+        };
+        let records = JsonProcessor::extract_records(&jsonl_file, &options).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].text, "Rust\n\nOwnership rules.");
+        assert_eq!(records[0].metadata["id"], "7");
+        assert_eq!(records[0].metadata["meta.lang"], "en");
+        assert_eq!(records[1].number, 3);
+        assert_eq!(records[2].text, "Borrowing.");
+        assert!(!records[2].metadata.contains_key("meta.lang"));
+
+        let options = JsonTextOptions {
+            text_fields: vec!["body".to_string()],
+            ..Default::default()
+        };
+        let records = JsonProcessor::extract_records(&jsonl_file, &options).unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.number)
+                .collect::<Vec<_>>(),
+            [1, 4]
+        );
+    }
+
+    #[test]
+    fn test_extract_text() {
+        let temp_dir = TempDir::new("json").unwrap();
+        let json_file = temp_dir.path().join("items.json");
+        std::fs::write(
+            &json_file,
+            r#"[{"name": "a", "tags": ["x"], "none": null}, "plain"]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            JsonProcessor::extract_text(&json_file).unwrap(),
+            "name: a\ntags: [\"x\"]\n\nplain"
+        );
+
+        std::fs::write(&json_file, "{\"a\": 1}\n{\"b\": 2}").unwrap();
+        JsonProcessor::extract_text(&json_file).unwrap_err();
+    }
+}
@@ -10,10 +10,44 @@ pub mod txt_processor;
 /// This module contains the processor to process web links.
 pub mod website_processor;
 
+/// This module contains the fetcher that applies robots.txt, rate limits and the User-Agent to web
+/// requests.
+pub mod web_fetcher;
+
+/// This module contains the cache of embedded webpages used to skip unchanged pages.
+pub mod web_cache;
+
+/// This module contains the parser of the sitemaps listing the pages of a website.
+pub mod sitemap;
+
 /// This module contains the file processor for HTML files.
 pub mod html_processor;
 
 /// This module contains the file processor for DOCX files.
 pub mod docx_processor;
 
+/// This module contains the helpers to read the XML parts of DOCX, PPTX and EPUB files.
+mod ooxml;
+
+/// This module contains the file processor for PPTX files.
+pub mod pptx_processor;
+
+/// This module contains the file processor for EPUB ebooks.
+pub mod epub_processor;
+
+/// This module contains the file processor for JSON and JSONL files.
+pub mod json_processor;
+
+/// This module contains the file processor for CSV files.
+pub mod csv_processor;
+
+/// This module contains the file processor for Excel workbooks.
+pub mod xlsx_processor;
+
+/// This module contains the table that CSV and Excel rows are written out from.
+pub mod table;
+
 pub mod audio;
+
+/// This module contains the helpers to fetch images from URLs and local paths.
+pub mod image_url;
@@ -0,0 +1,79 @@
+//! Helpers for the XML parts of zip-packaged documents (DOCX, PPTX and EPUB) read straight from
+//! the archive.
+
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::Error;
+use quick_xml::events::Event;
+use zip::ZipArchive;
+
+/// Opens an Office Open XML file as the zip archive it is.
+pub(crate) fn open_archive<T: AsRef<Path>>(file_path: &T) -> Result<ZipArchive<File>, Error> {
+    Ok(ZipArchive::new(File::open(file_path)?)?)
+}
+
+/// Reads the part `name` of the archive, e.g. `word/footnotes.xml`.
+pub(crate) fn read_part(archive: &mut ZipArchive<File>, name: &str) -> Result<String, Error> {
+    let mut xml = String::new();
+    archive.by_name(name)?.read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+/// The non-empty paragraphs of a part, made of the text runs of the elements with the given
+/// namespace prefix: `a` for DrawingML (slides) or `w` for WordprocessingML (documents). Line
+/// breaks and tabs inside a paragraph become spaces.
+pub(crate) fn paragraphs(xml: &str, prefix: &str) -> Result<Vec<String>, Error> {
+    let tag = |name: &str| format!("{prefix}:{name}").into_bytes();
+    let (text_tag, paragraph_tag) = (tag("t"), tag("p"));
+    let (break_tag, tab_tag) = (tag("br"), tag("tab"));
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut paragraphs = Vec::new();
+    let mut paragraph = String::new();
+    let mut in_text = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) if start.name().as_ref() == text_tag => in_text = true,
+            Event::End(end) if end.name().as_ref() == text_tag => in_text = false,
+            Event::Empty(empty)
+                if empty.name().as_ref() == break_tag || empty.name().as_ref() == tab_tag =>
+            {
+                paragraph.push(' ')
+            }
+            Event::Text(text) if in_text => paragraph.push_str(&text.unescape()?),
+            Event::End(end) if end.name().as_ref() == paragraph_tag => {
+                let line = paragraph.trim();
+                if !line.is_empty() {
+                    paragraphs.push(line.to_string());
+                }
+                paragraph.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(paragraphs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paragraphs() {
+        let slide = r#"<p:sld><p:cSld><p:spTree>
+            <p:sp><p:txBody><a:p><a:r><a:t>Quarterly </a:t></a:r><a:r><a:t>results</a:t></a:r></a:p></p:txBody></p:sp>
+            <p:sp><p:txBody><a:p><a:r><a:t>R&amp;D up</a:t></a:r><a:br/><a:r><a:t>10%</a:t></a:r></a:p><a:p/></p:txBody></p:sp>
+        </p:spTree></p:cSld></p:sld>"#;
+        assert_eq!(
+            paragraphs(slide, "a").unwrap(),
+            ["Quarterly results", "R&D up 10%"]
+        );
+
+        let footnotes = r#"<w:footnotes>
+            <w:footnote w:type="separator" w:id="-1"><w:p><w:r><w:separator/></w:r></w:p></w:footnote>
+            <w:footnote w:id="1"><w:p><w:r><w:t xml:space="preserve">See the</w:t></w:r><w:r><w:tab/><w:t>appendix.</w:t></w:r></w:p></w:footnote>
+        </w:footnotes>"#;
+        assert_eq!(paragraphs(footnotes, "w").unwrap(), ["See the appendix."]);
+    }
+}
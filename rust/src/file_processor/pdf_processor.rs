@@ -1,8 +1,45 @@
+use std::ops::RangeInclusive;
+
 use anyhow::Error;
 use image::DynamicImage;
 use pdf2image::{Pages, RenderOptionsBuilder, PDF};
 use rusty_tesseract::{self, Args, Image};
 
+/// Which pages of a PDF to take the text of, whether to drop their running headers and footers
+/// and whether to split it at its outline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PdfTextOptions {
+    /// First page to extract, counting from 1. Default is the first page.
+    pub first_page: Option<usize>,
+    /// Last page to extract. Default is the last page.
+    pub last_page: Option<usize>,
+    /// Split the text into one section per top-level outline entry (bookmark), e.g. the chapters
+    /// of a book. Pages before the first entry form a section without title.
+    pub split_by_outline: bool,
+    /// Drop the lines at the top and bottom of pages that repeat on most pages, such as running
+    /// titles and page numbers. Only documents of at least three pages are checked.
+    pub strip_headers_footers: bool,
+}
+
+impl PdfTextOptions {
+    /// The pages of a PDF of `page_count` pages to extract, clamped to the document.
+    pub fn page_range(&self, page_count: usize) -> RangeInclusive<usize> {
+        let first = self.first_page.unwrap_or(1).max(1);
+        let last = self.last_page.unwrap_or(page_count).min(page_count);
+        first..=last
+    }
+}
+
+/// The text of the pages from one top-level outline entry up to the next one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdfSection {
+    /// Title of the outline entry, `None` for the pages before the first entry or when the PDF is
+    /// not split.
+    pub title: Option<String>,
+    pub first_page: usize,
+    pub text: String,
+}
+
 /// A struct for processing PDF files.
 pub struct PdfProcessor;
 
@@ -27,6 +64,161 @@ impl PdfProcessor {
             pdf_extract::extract_text(file_path).map_err(|e| anyhow::anyhow!(e))
         }
     }
+
+    /// Extracts the text of the pages selected by `options`, split into sections at the outline
+    /// if asked to. Sections start at page boundaries, so of several entries starting on the same
+    /// page only the last one gets a section.
+    pub fn extract_sections<T: AsRef<std::path::Path>>(
+        file_path: T,
+        use_ocr: bool,
+        options: &PdfTextOptions,
+    ) -> Result<Vec<PdfSection>, Error> {
+        let mut pages = extract_pages(&file_path, use_ocr, options)?;
+        if options.strip_headers_footers {
+            strip_headers_footers(&mut pages);
+        }
+        let outline = if options.split_by_outline {
+            top_level_outline(&file_path)?
+        } else {
+            Vec::new()
+        };
+        Ok(split_sections(pages, &outline))
+    }
+}
+
+/// The text of each page selected by `options`, with its page number.
+fn extract_pages<T: AsRef<std::path::Path>>(
+    file_path: &T,
+    use_ocr: bool,
+    options: &PdfTextOptions,
+) -> Result<Vec<(usize, String)>, Error> {
+    if use_ocr {
+        let pdf = PDF::from_file(file_path)?;
+        let pages = options.page_range(pdf.page_count() as usize);
+        if pages.is_empty() {
+            return Ok(Vec::new());
+        }
+        let images = pdf.render(
+            Pages::Range(*pages.start() as u32..=*pages.end() as u32),
+            RenderOptionsBuilder::default().build()?,
+        )?;
+        pages
+            .zip(images)
+            .map(|(page, image)| Ok((page, extract_text_from_image(&image, &Args::default())?)))
+            .collect()
+    } else {
+        let texts =
+            pdf_extract::extract_text_by_pages(file_path).map_err(|e| anyhow::anyhow!(e))?;
+        let pages = options.page_range(texts.len());
+        Ok((1..)
+            .zip(texts)
+            .filter(|(page, _)| pages.contains(page))
+            .collect())
+    }
+}
+
+/// How many lines at the top and at the bottom of a page can be part of a header or footer.
+const MARGIN_LINES: usize = 2;
+
+/// A margin line with its digits masked, so `Page 3 of 9` and `Page 4 of 9` compare equal.
+fn margin_key(line: &str) -> String {
+    line.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .map(|c| if c.is_ascii_digit() { '#' } else { c })
+        .collect()
+}
+
+/// The indices of the non-empty lines of a page that are within [`MARGIN_LINES`] of its top or
+/// bottom.
+fn margin_lines(lines: &[&str]) -> Vec<usize> {
+    let filled = (0..lines.len())
+        .filter(|&index| !lines[index].trim().is_empty())
+        .collect::<Vec<_>>();
+    let mut margins = filled.iter().take(MARGIN_LINES).collect::<Vec<_>>();
+    margins.extend(filled.iter().rev().take(MARGIN_LINES));
+    margins.sort();
+    margins.dedup();
+    margins.into_iter().copied().collect()
+}
+
+/// Removes the margin lines that appear, up to their digits, on at least half of the pages.
+fn strip_headers_footers(pages: &mut [(usize, String)]) {
+    if pages.len() < 3 {
+        return;
+    }
+    let mut counts = std::collections::HashMap::<String, usize>::new();
+    for (_, text) in pages.iter() {
+        let lines = text.lines().collect::<Vec<_>>();
+        let mut keys = margin_lines(&lines)
+            .into_iter()
+            .map(|index| margin_key(lines[index]))
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            *counts.entry(key).or_default() += 1;
+        }
+    }
+    let threshold = pages.len().div_ceil(2).max(2);
+    for (_, text) in pages.iter_mut() {
+        let lines = text.lines().collect::<Vec<_>>();
+        let repeated = margin_lines(&lines)
+            .into_iter()
+            .filter(|&index| counts[&margin_key(lines[index])] >= threshold)
+            .collect::<Vec<_>>();
+        if !repeated.is_empty() {
+            *text = (0..lines.len())
+                .filter(|index| !repeated.contains(index))
+                .map(|index| lines[index])
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+    }
+}
+
+/// The first page and title of the top-level entries of the outline of a PDF, in page order.
+/// Empty when the PDF has no outline.
+fn top_level_outline<T: AsRef<std::path::Path>>(
+    file_path: &T,
+) -> Result<Vec<(usize, String)>, Error> {
+    let document = lopdf::Document::load(file_path)?;
+    let Ok(toc) = document.get_toc() else {
+        return Ok(Vec::new());
+    };
+    let top_level = toc.toc.iter().map(|entry| entry.level).min();
+    let mut entries = toc
+        .toc
+        .into_iter()
+        .filter(|entry| Some(entry.level) == top_level)
+        .map(|entry| (entry.page, entry.title))
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|(page, _)| *page);
+    Ok(entries)
+}
+
+/// Groups `pages` into one section per `outline` entry, each running up to the next entry.
+fn split_sections(pages: Vec<(usize, String)>, outline: &[(usize, String)]) -> Vec<PdfSection> {
+    let mut sections = Vec::<(Option<usize>, PdfSection)>::new();
+    for (page, text) in pages {
+        let entry = outline.iter().rposition(|(start, _)| *start <= page);
+        match sections.last_mut() {
+            Some((last_entry, section)) if *last_entry == entry => {
+                section.text.push('\n');
+                section.text.push_str(&text);
+            }
+            _ => sections.push((
+                entry,
+                PdfSection {
+                    title: entry.map(|entry| outline[entry].1.clone()),
+                    first_page: page,
+                    text,
+                },
+            )),
+        }
+    }
+    sections.into_iter().map(|(_, section)| section).collect()
 }
 
 fn get_images_from_pdf<T: AsRef<std::path::Path>>(
@@ -74,6 +266,70 @@ mod tests {
         assert_eq!(text.len(), 4271);
     }
 
+    #[test]
+    fn test_split_sections() {
+        let pages = (1..=5)
+            .map(|page| (page, format!("page {page}")))
+            .collect::<Vec<_>>();
+        let outline = [(2, "Intro".to_string()), (4, "Results".to_string())];
+        let sections = split_sections(pages.clone(), &outline);
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].title, None);
+        assert_eq!(sections[1].title.as_deref(), Some("Intro"));
+        assert_eq!(sections[1].first_page, 2);
+        assert_eq!(sections[1].text, "page 2\npage 3");
+        assert_eq!(sections[2].first_page, 4);
+
+        let sections = split_sections(pages[2..4].to_vec(), &[]);
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].first_page, 3);
+    }
+
+    #[test]
+    fn test_strip_headers_footers() {
+        let bodies = [
+            "Revenue grew.\nCosts fell.",
+            "Hiring slowed.",
+            "Outlook is stable.",
+        ];
+        let mut pages = (1..=4)
+            .zip(bodies.iter().chain(&[""]))
+            .map(|(page, body)| {
+                (
+                    page,
+                    format!("Annual Report 2024\n\n{body}\nPage {page} of 4"),
+                )
+            })
+            .collect::<Vec<_>>();
+        pages[3].1 = "Appendix\nOnly on the last page.".to_string();
+        strip_headers_footers(&mut pages);
+        assert_eq!(pages[0].1, "\nRevenue grew.\nCosts fell.");
+        assert_eq!(pages[2].1, "\nOutlook is stable.");
+        assert_eq!(pages[3].1, "Appendix\nOnly on the last page.");
+
+        let mut short = vec![
+            (1, "Title\ntext".to_string()),
+            (2, "Title\nmore".to_string()),
+        ];
+        strip_headers_footers(&mut short);
+        assert_eq!(short[0].1, "Title\ntext");
+    }
+
+    #[test]
+    fn test_extract_page_range() {
+        let options = PdfTextOptions {
+            first_page: Some(1),
+            last_page: Some(1),
+            split_by_outline: true,
+            ..Default::default()
+        };
+        let sections =
+            PdfProcessor::extract_sections("test_files/test.pdf", false, &options).unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].first_page, 1);
+        assert!(!sections[0].text.is_empty());
+    }
+
     #[test]
     fn test_extract_text_with_ocr() {
         let pdf_file = "../test_files/test.pdf";
@@ -0,0 +1,73 @@
+use anyhow::Error;
+
+use super::ooxml;
+
+/// The text of one slide of a PPTX file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PptxSlide {
+    /// The number of the slide, counting from 1.
+    pub number: usize,
+    /// The paragraphs of the slide, one per line.
+    pub text: String,
+}
+
+/// A struct for processing PPTX files.
+pub struct PptxProcessor;
+
+impl PptxProcessor {
+    /// Extracts the text of every slide of a PPTX file in slide order. Slides without text are
+    /// left out.
+    pub fn extract_slides<T: AsRef<std::path::Path>>(
+        file_path: &T,
+    ) -> Result<Vec<PptxSlide>, Error> {
+        let mut archive = ooxml::open_archive(file_path)?;
+        let mut slides = archive
+            .file_names()
+            .filter_map(|name| Some((slide_number(name)?, name.to_string())))
+            .collect::<Vec<_>>();
+        slides.sort();
+
+        let mut extracted = Vec::new();
+        for (number, name) in slides {
+            let text = ooxml::paragraphs(&ooxml::read_part(&mut archive, &name)?, "a")?.join("\n");
+            if !text.is_empty() {
+                extracted.push(PptxSlide { number, text });
+            }
+        }
+        Ok(extracted)
+    }
+
+    /// Extracts the text of a PPTX file, with the slides separated by blank lines.
+    pub fn extract_text<T: AsRef<std::path::Path>>(file_path: &T) -> Result<String, Error> {
+        Ok(Self::extract_slides(file_path)?
+            .into_iter()
+            .map(|slide| slide.text)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
+
+/// The number of a slide from its path in the archive, e.g. 3 for `ppt/slides/slide3.xml`.
+fn slide_number(name: &str) -> Option<usize> {
+    name.strip_prefix("ppt/slides/slide")?
+        .strip_suffix(".xml")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slide_number() {
+        assert_eq!(slide_number("ppt/slides/slide12.xml"), Some(12));
+        assert_eq!(slide_number("ppt/slides/_rels/slide1.xml.rels"), None);
+        assert_eq!(slide_number("ppt/slideLayouts/slideLayout1.xml"), None);
+    }
+
+    #[test]
+    fn test_extract_text_invalid_file_path() {
+        PptxProcessor::extract_text(&"invalid.pptx").unwrap_err();
+    }
+}
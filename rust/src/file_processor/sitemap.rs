@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use anyhow::Error;
+use quick_xml::events::Event;
+
+use super::web_fetcher::WebFetcher;
+
+/// How many levels of sitemap indexes are followed below the sitemap passed to
+/// [`fetch_sitemap`].
+pub const MAX_SITEMAP_DEPTH: usize = 3;
+
+/// A page listed in a sitemap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SitemapUrl {
+    /// The URL of the page, from `<loc>`.
+    pub loc: String,
+    /// When the page last changed, from `<lastmod>`, as written in the sitemap.
+    pub lastmod: Option<String>,
+    /// The URL of the sitemap that lists the page.
+    pub sitemap: String,
+}
+
+/// The content of a sitemap file: either pages, or other sitemaps for a sitemap index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sitemap {
+    /// A `<urlset>` and its pages as `(loc, lastmod)`.
+    Urls(Vec<(String, Option<String>)>),
+    /// A `<sitemapindex>` and the URLs of its sitemaps.
+    Index(Vec<String>),
+}
+
+impl Sitemap {
+    /// Parses a sitemap following the sitemaps.org protocol. Entries without `<loc>` are left out.
+    pub fn parse(xml: &str) -> Result<Self, Error> {
+        let mut index = false;
+        let mut entries = Vec::new();
+        let mut entry: Option<(String, Option<String>)> = None;
+        let mut field: Option<Vec<u8>> = None;
+        let mut reader = quick_xml::Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+        loop {
+            match reader.read_event()? {
+                Event::Start(element) => match element.local_name().as_ref() {
+                    b"sitemapindex" => index = true,
+                    b"url" | b"sitemap" => entry = Some((String::new(), None)),
+                    name @ (b"loc" | b"lastmod") => field = Some(name.to_vec()),
+                    _ => {}
+                },
+                Event::Text(text) => {
+                    if let (Some(field), Some((loc, lastmod))) = (&field, &mut entry) {
+                        let text = text.unescape()?;
+                        match field.as_slice() {
+                            b"loc" => loc.push_str(text.trim()),
+                            _ => *lastmod = Some(text.trim().to_string()),
+                        }
+                    }
+                }
+                Event::CData(text) => {
+                    if let (Some(b"loc"), Some((loc, _))) = (field.as_deref(), &mut entry) {
+                        loc.push_str(String::from_utf8_lossy(&text).trim());
+                    }
+                }
+                Event::End(element) => match element.local_name().as_ref() {
+                    b"url" | b"sitemap" => {
+                        if let Some(entry) = entry.take().filter(|(loc, _)| !loc.is_empty()) {
+                            entries.push(entry);
+                        }
+                    }
+                    b"loc" | b"lastmod" => field = None,
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(if index {
+            Sitemap::Index(entries.into_iter().map(|(loc, _)| loc).collect())
+        } else {
+            Sitemap::Urls(entries)
+        })
+    }
+}
+
+/// Fetches the sitemap at `url` and lists its pages, following sitemap indexes up to
+/// [`MAX_SITEMAP_DEPTH`] levels deep. Pages listed more than once are only kept the first time.
+///
+/// Fails when `url` itself cannot be fetched or parsed. Nested sitemaps that fail are skipped with
+/// a warning, so one broken sitemap does not hide the pages of the others.
+pub async fn fetch_sitemap(fetcher: &WebFetcher, url: &str) -> Result<Vec<SitemapUrl>, Error> {
+    let mut pages = Vec::new();
+    let mut seen_pages = HashSet::new();
+    let mut seen_sitemaps = HashSet::from([url.to_string()]);
+    let mut sitemaps = vec![(url.to_string(), 0)];
+    while let Some((sitemap_url, depth)) = sitemaps.pop() {
+        let sitemap = fetcher
+            .fetch_uncached(&sitemap_url)
+            .await
+            .and_then(|xml| Sitemap::parse(&xml));
+        let sitemap = match sitemap {
+            Ok(sitemap) => sitemap,
+            Err(e) if depth == 0 => return Err(e),
+            Err(e) => {
+                tracing::warn!("Skipping sitemap {}: {:?}", sitemap_url, e);
+                continue;
+            }
+        };
+        match sitemap {
+            Sitemap::Urls(urls) => {
+                for (loc, lastmod) in urls {
+                    if seen_pages.insert(loc.clone()) {
+                        pages.push(SitemapUrl {
+                            loc,
+                            lastmod,
+                            sitemap: sitemap_url.clone(),
+                        });
+                    }
+                }
+            }
+            Sitemap::Index(_) if depth == MAX_SITEMAP_DEPTH => {
+                tracing::warn!("Skipping sitemap index {}: nested too deep", sitemap_url);
+            }
+            Sitemap::Index(children) => {
+                // Pushed in reverse so the sitemaps are visited in the order of the index.
+                for child in children.into_iter().rev() {
+                    if seen_sitemaps.insert(child.clone()) {
+                        sitemaps.push((child, depth + 1));
+                    }
+                }
+            }
+        }
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_urlset() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/?a=1&amp;b=2</loc>
+    <lastmod>2024-05-01</lastmod>
+  </url>
+  <url><loc><![CDATA[https://example.com/about]]></loc></url>
+  <url><lastmod>2024-05-02</lastmod></url>
+</urlset>"#;
+        assert_eq!(
+            Sitemap::parse(xml).unwrap(),
+            Sitemap::Urls(vec![
+                (
+                    "https://example.com/?a=1&b=2".to_string(),
+                    Some("2024-05-01".to_string())
+                ),
+                ("https://example.com/about".to_string(), None),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let xml = r#"<sm:sitemapindex xmlns:sm="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sm:sitemap><sm:loc>https://example.com/posts.xml</sm:loc></sm:sitemap>
+  <sm:sitemap>
+    <sm:loc>https://example.com/pages.xml</sm:loc>
+    <sm:lastmod>2024-05-01</sm:lastmod>
+  </sm:sitemap>
+</sm:sitemapindex>"#;
+        assert_eq!(
+            Sitemap::parse(xml).unwrap(),
+            Sitemap::Index(vec![
+                "https://example.com/posts.xml".to_string(),
+                "https://example.com/pages.xml".to_string(),
+            ])
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Rows of tabular files, such as CSV and Excel, written out as text to embed.
+
+/// The header row of a table and the rows below it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// The text of consecutive rows of a [`Table`], numbered from 1 for the first row below the
+/// header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowWindow {
+    pub first_row: usize,
+    pub last_row: usize,
+    pub text: String,
+}
+
+impl Table {
+    /// The name of column `index`, made up for cells beyond the header.
+    fn column(&self, index: usize) -> String {
+        match self.columns.get(index) {
+            Some(column) if !column.trim().is_empty() => column.trim().to_string(),
+            _ => format!("column_{}", index + 1),
+        }
+    }
+
+    /// Writes a row as `column: value` pairs, so every value is embedded next to its column
+    /// name. Empty cells are left out.
+    pub fn row_text(&self, row: &[String]) -> String {
+        row.iter()
+            .enumerate()
+            .filter(|(_, value)| !value.trim().is_empty())
+            .map(|(index, value)| format!("{}: {}", self.column(index), value.trim()))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// The text of every row, one per line.
+    pub fn to_text(&self) -> String {
+        self.windows(self.rows.len().max(1))
+            .into_iter()
+            .map(|window| window.text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The rows in windows of `rows_per_window`, one line per row. Rows without values are
+    /// skipped.
+    pub fn windows(&self, rows_per_window: usize) -> Vec<RowWindow> {
+        self.rows
+            .chunks(rows_per_window.max(1))
+            .enumerate()
+            .filter_map(|(index, rows)| {
+                let first_row = index * rows_per_window.max(1) + 1;
+                let text = rows
+                    .iter()
+                    .map(|row| self.row_text(row))
+                    .filter(|text| !text.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                (!text.is_empty()).then(|| RowWindow {
+                    first_row,
+                    last_row: first_row + rows.len() - 1,
+                    text,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_windows() {
+        let table = Table {
+            columns: row(&["name", "age"]),
+            rows: vec![
+                row(&["Alice", "30"]),
+                row(&["Bob", ""]),
+                row(&["", ""]),
+                row(&["Carol", "41", "admin"]),
+            ],
+        };
+        let windows = table.windows(2);
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].text, "name: Alice; age: 30\nname: Bob");
+        assert_eq!((windows[1].first_row, windows[1].last_row), (3, 4));
+        assert_eq!(windows[1].text, "name: Carol; age: 41; column_3: admin");
+        assert_eq!(table.to_text().lines().count(), 3);
+    }
+}
@@ -1,23 +1,177 @@
+use std::{
+    fs::File,
+    io::{Read, Seek},
+};
+
 use anyhow::Error;
+use encoding_rs::{Decoder, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// Bytes read at a time from text files that are streamed, see [`TxtProcessor::stream_blocks`].
+pub const STREAM_BLOCK_SIZE: usize = 1 << 20;
 
-/// A struct for processing PDF files.
+/// A struct for processing text files.
 pub struct TxtProcessor;
 
 impl TxtProcessor {
-    /// Extracts text from a PDF file.
+    /// Extracts text from a text file, transcoded to UTF-8 from the encoding found by
+    /// [`decode`](Self::decode).
     ///
     /// # Arguments
     ///
-    /// * `file_path` - The path to the PDF file.
+    /// * `file_path` - The path to the text file.
     ///
     /// # Returns
     ///
     /// Returns a `Result` containing the extracted text as a `String` if successful,
     /// or an `Error` if an error occurred during the extraction process.
     pub fn extract_text<T: AsRef<std::path::Path>>(file_path: &T) -> Result<String, Error> {
+        Ok(Self::extract_text_with_encoding(file_path)?.0)
+    }
+
+    /// Extracts text from a text file like [`extract_text`](Self::extract_text), together with
+    /// the name of the encoding it was read as, e.g. `UTF-8` or `windows-1252`.
+    pub fn extract_text_with_encoding<T: AsRef<std::path::Path>>(
+        file_path: &T,
+    ) -> Result<(String, &'static str), Error> {
         let bytes = std::fs::read(file_path)?;
-        let out = String::from_utf8_lossy(&bytes);
-        Ok(out.to_string())
+        let (text, encoding) = Self::decode(&bytes);
+        Ok((text, encoding.name()))
+    }
+
+    /// Decodes `bytes` from the encoding named by their byte order mark, from UTF-8 when they
+    /// are valid UTF-8, from UTF-16 when every other byte is mostly zero, and otherwise from the
+    /// legacy encoding guessed by `chardetng`, such as Latin-1 or Shift JIS. Invalid sequences
+    /// are replaced rather than rejected.
+    pub fn decode(bytes: &[u8]) -> (String, &'static Encoding) {
+        // Strips the byte order mark, if any.
+        let (text, encoding, _) = detect_encoding(bytes).decode(bytes);
+        (text.into_owned(), encoding)
+    }
+
+    /// Reads a text file block by block rather than all at once, so files of any size can be
+    /// embedded in bounded memory. Blocks are read `block_size` bytes at a time and end at the last
+    /// line break read, so lines are only cut when longer than a block. The encoding is detected from the
+    /// first `block_size` bytes like [`decode`](Self::decode) does.
+    pub fn stream_blocks<T: AsRef<std::path::Path>>(
+        file_path: &T,
+        block_size: usize,
+    ) -> Result<TextBlocks, Error> {
+        let block_size = block_size.max(1);
+        let mut file = File::open(file_path)?;
+        let mut prefix = Vec::with_capacity(block_size);
+        (&mut file)
+            .take(block_size as u64)
+            .read_to_end(&mut prefix)?;
+        file.rewind()?;
+        let encoding = detect_encoding(&prefix);
+        Ok(TextBlocks {
+            file,
+            decoder: encoding.new_decoder(),
+            encoding,
+            bytes: vec![0; block_size],
+            text: String::new(),
+            finished: false,
+        })
+    }
+}
+
+/// The blocks of text of a file being streamed, see [`TxtProcessor::stream_blocks`].
+pub struct TextBlocks {
+    file: File,
+    decoder: Decoder,
+    encoding: &'static Encoding,
+    bytes: Vec<u8>,
+    /// Decoded text not returned yet.
+    text: String,
+    finished: bool,
+}
+
+impl TextBlocks {
+    /// The name of the encoding the file is read as, e.g. `UTF-8`.
+    pub fn encoding(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    /// Decodes the next bytes of the file into `text`.
+    fn read(&mut self) -> Result<(), Error> {
+        let read = self.file.read(&mut self.bytes)?;
+        self.finished = read == 0;
+        self.text.reserve(
+            self.decoder
+                .max_utf8_buffer_length(read)
+                .unwrap_or(read * 3),
+        );
+        self.decoder
+            .decode_to_string(&self.bytes[..read], &mut self.text, self.finished);
+        Ok(())
+    }
+}
+
+impl Iterator for TextBlocks {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished && self.text.len() < self.bytes.len() {
+            if let Err(e) = self.read() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+        if self.text.is_empty() {
+            return None;
+        }
+        // The rest of the last line waits for the next block, unless the file ends here or the
+        // line is longer than a block.
+        let end = match self.text.rfind('\n') {
+            Some(index) if !self.finished => index + 1,
+            _ => self.text.len(),
+        };
+        let rest = self.text.split_off(end);
+        Some(Ok(std::mem::replace(&mut self.text, rest)))
+    }
+}
+
+/// The encoding named by the byte order mark of `bytes`, UTF-8 if they are valid UTF-8 (up to a
+/// character cut off at their end), UTF-16 if every other byte is mostly zero, and otherwise the
+/// legacy encoding guessed by `chardetng`.
+fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    let utf8 = match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => encoding,
+        None if utf8 => UTF_8,
+        None => utf16_without_bom(bytes).unwrap_or_else(|| {
+            let mut detector = chardetng::EncodingDetector::new();
+            detector.feed(bytes, true);
+            detector.guess(None, true)
+        }),
+    }
+}
+
+/// UTF-16 text without byte order mark, recognised by the zero high bytes of ASCII characters
+/// on one side of most byte pairs.
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    let pairs = bytes.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let zeros = |offset: usize| {
+        bytes
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 10 >= pairs * 7 && even * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even * 10 >= pairs * 7 && odd * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
     }
 }
 
@@ -45,6 +199,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decode() {
+        let (text, encoding) = TxtProcessor::decode("Grüße".as_bytes());
+        assert_eq!((text.as_str(), encoding.name()), ("Grüße", "UTF-8"));
+
+        let latin1 = b"Le caf\xe9 est pr\xeat, servez-vous \xe0 volont\xe9.";
+        let (text, encoding) = TxtProcessor::decode(latin1);
+        assert_eq!(text, "Le café est prêt, servez-vous à volonté.");
+        assert_eq!(encoding.name(), "windows-1252");
+
+        let utf16 = "naïve text"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let (text, encoding) = TxtProcessor::decode(&utf16);
+        assert_eq!((text.as_str(), encoding.name()), ("naïve text", "UTF-16LE"));
+
+        let mut with_bom = vec![0xFE, 0xFF];
+        with_bom.extend("BOM".encode_utf16().flat_map(u16::to_be_bytes));
+        let (text, encoding) = TxtProcessor::decode(&with_bom);
+        assert_eq!((text.as_str(), encoding.name()), ("BOM", "UTF-16BE"));
+    }
+
+    #[test]
+    fn test_stream_blocks() {
+        let temp_dir = TempDir::new("example").unwrap();
+        let txt_file = temp_dir.path().join("large.txt");
+        let lines = (0..100)
+            .map(|line| format!("Line {line} of a café log"))
+            .collect::<Vec<_>>();
+        std::fs::write(&txt_file, lines.join("\n")).unwrap();
+
+        let blocks = TxtProcessor::stream_blocks(&txt_file, 64).unwrap();
+        assert_eq!(blocks.encoding(), "UTF-8");
+        let blocks = blocks.collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(blocks.len() > 10);
+        assert!(blocks[..blocks.len() - 1]
+            .iter()
+            .all(|block| block.ends_with('\n')));
+        assert_eq!(blocks.concat(), lines.join("\n"));
+    }
+
     // Returns an error if the file path is invalid.
     #[test]
     fn test_extract_text_invalid_file_path() {
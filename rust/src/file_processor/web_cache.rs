@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::EmbedError;
+
+/// What is known about a page from the last time it was embedded.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// SHA-256 of the page body, see [`content_hash`].
+    pub content_hash: String,
+}
+
+/// The pages embedded by earlier runs, stored as JSON so re-indexing a site can skip the pages
+/// that did not change.
+#[derive(Debug)]
+pub struct WebCache {
+    path: PathBuf,
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl WebCache {
+    /// Loads the cache at `path`. A missing file gives an empty cache.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| {
+                EmbedError::InvalidConfig(format!("Invalid web cache {:?}: {}", path, e))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(EmbedError::Io(e).into()),
+        };
+        Ok(Self { path, entries })
+    }
+
+    pub fn get(&self, url: &str) -> Option<&CacheEntry> {
+        self.entries.get(url)
+    }
+
+    pub fn insert(&mut self, url: impl Into<String>, entry: CacheEntry) {
+        self.entries.insert(url.into(), entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Writes the cache back to its file. The file is replaced atomically so an interrupted save
+    /// keeps the previous cache.
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(EmbedError::from)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string(&self.entries)?).map_err(EmbedError::from)?;
+        std::fs::rename(&tmp, &self.path).map_err(EmbedError::from)?;
+        Ok(())
+    }
+}
+
+/// Hex encoded SHA-256 of a page body or file.
+pub fn content_hash(body: impl AsRef<[u8]>) -> String {
+    format!("{:x}", Sha256::digest(body.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_web_cache_roundtrip() {
+        let temp_dir = TempDir::new("web_cache").unwrap();
+        let path = temp_dir.path().join("cache").join("pages.json");
+
+        let mut cache = WebCache::open(&path).unwrap();
+        assert!(cache.is_empty());
+        let entry = CacheEntry {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            content_hash: content_hash("<html></html>"),
+        };
+        cache.insert("https://example.com/", entry.clone());
+        cache.save().unwrap();
+
+        let cache = WebCache::open(&path).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get("https://example.com/"), Some(&entry));
+        assert_eq!(content_hash("<html></html>"), entry.content_hash);
+        assert_ne!(content_hash("<html> </html>"), entry.content_hash);
+    }
+}
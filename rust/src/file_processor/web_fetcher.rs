@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{StatusCode, Url};
+
+use crate::config::{WebFetchConfig, DEFAULT_USER_AGENT, DEFAULT_WEB_TIMEOUT};
+use crate::error::EmbedError;
+use crate::file_processor::web_cache::{content_hash, CacheEntry, WebCache};
+
+/// The rules of a `robots.txt` file that apply to one user agent.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsTxt {
+    rules: Vec<RobotsRule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+struct RobotsRule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Debug, Default)]
+struct RobotsGroup {
+    agents: Vec<String>,
+    rules: Vec<RobotsRule>,
+    crawl_delay: Option<Duration>,
+}
+
+impl RobotsTxt {
+    /// Parses `content` and keeps the groups for `user_agent`, or the `*` groups when none of them
+    /// names it. Agents are matched on the product token, e.g. `embed_anything` for
+    /// `embed_anything/0.5`.
+    pub fn parse(content: &str, user_agent: &str) -> Self {
+        let mut groups: Vec<RobotsGroup> = Vec::new();
+        let mut in_agents = false;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push(RobotsGroup::default());
+                        in_agents = true;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(product_token(value));
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // An empty `Disallow` allows everything, which is the same as having no rule.
+                    if value.is_empty() {
+                        continue;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.rules.push(RobotsRule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_agents = false;
+                    if let Some(group) = groups.last_mut() {
+                        group.crawl_delay = value
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|delay| delay.is_finite() && *delay >= 0.0)
+                            .map(Duration::from_secs_f64);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let token = product_token(user_agent);
+        let named = groups
+            .iter()
+            .any(|group| group.agents.iter().any(|agent| *agent == token));
+        let mut robots = RobotsTxt::default();
+        for group in groups.into_iter().filter(|group| {
+            group
+                .agents
+                .iter()
+                .any(|agent| if named { *agent == token } else { agent == "*" })
+        }) {
+            robots.rules.extend(group.rules);
+            robots.crawl_delay = robots.crawl_delay.max(group.crawl_delay);
+        }
+        robots
+    }
+
+    /// Rules for a site whose `robots.txt` is missing.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Rules for a site whose `robots.txt` could not be fetched.
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![RobotsRule {
+                allow: false,
+                pattern: "/".to_string(),
+            }],
+            crawl_delay: None,
+        }
+    }
+
+    /// Whether `path` (including the query) may be fetched. The longest matching rule wins, and
+    /// `Allow` wins a tie.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        match self
+            .rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+        {
+            Some(rule) => rule.allow,
+            None => true,
+        }
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+fn product_token(user_agent: &str) -> String {
+    user_agent
+        .split(['/', ' '])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Matches a `robots.txt` path pattern, where `*` matches any characters and a trailing `$`
+/// anchors the pattern at the end of the path.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let parts = pattern.split('*').collect::<Vec<_>>();
+    let Some(rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    let mut rest = rest;
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// Fetches pages with a fixed User-Agent, honouring `robots.txt` and keeping a minimum delay
+/// between requests to the same host. Shared by all the pages of [`crate::embed_webpages`].
+///
+/// With a [`WebCache`], requests are conditional on the ETag and Last-Modified of the last
+/// embedded version, and pages whose body hashes the same are reported as unchanged.
+pub struct WebFetcher {
+    client: reqwest::Client,
+    user_agent: String,
+    respect_robots_txt: bool,
+    domain_delay: Duration,
+    /// `robots.txt` rules by origin.
+    robots: Mutex<HashMap<String, Arc<RobotsTxt>>>,
+    /// The earliest time the next request to a host may start.
+    next_request: Mutex<HashMap<String, Instant>>,
+    cache: Option<Mutex<WebCache>>,
+    /// Cache entries of fetched pages, moved to `cache` by [`WebFetcher::commit`].
+    pending: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl WebFetcher {
+    pub fn new(config: &WebFetchConfig) -> anyhow::Result<Self> {
+        let user_agent = config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string());
+        let client = reqwest::Client::builder()
+            .user_agent(user_agent.as_str())
+            .timeout(config.timeout.unwrap_or(DEFAULT_WEB_TIMEOUT))
+            .build()
+            .map_err(EmbedError::from)?;
+        let cache = match &config.cache_path {
+            Some(path) => Some(Mutex::new(WebCache::open(path)?)),
+            None => None,
+        };
+        Ok(Self {
+            client,
+            user_agent,
+            respect_robots_txt: config.respect_robots_txt.unwrap_or(true),
+            domain_delay: config.domain_delay.unwrap_or(Duration::ZERO),
+            robots: Mutex::new(HashMap::new()),
+            next_request: Mutex::new(HashMap::new()),
+            cache,
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the body of `url`, or `None` when the cache shows it did not change since it was
+    /// last embedded. Fails without sending a request when `robots.txt` disallows it.
+    pub async fn fetch(&self, url: &str) -> anyhow::Result<Option<String>> {
+        self.get(url, self.cache.as_ref()).await
+    }
+
+    /// Returns the body of `url` like [`fetch`](Self::fetch), but always downloads it and leaves
+    /// the cache alone. Used for files such as sitemaps that are read rather than embedded.
+    pub async fn fetch_uncached(&self, url: &str) -> anyhow::Result<String> {
+        Ok(self.get(url, None).await?.unwrap_or_default())
+    }
+
+    async fn get(
+        &self,
+        url: &str,
+        cache: Option<&Mutex<WebCache>>,
+    ) -> anyhow::Result<Option<String>> {
+        let url = Url::parse(url)
+            .map_err(|e| EmbedError::InvalidConfig(format!("Invalid URL {}: {}", url, e)))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| EmbedError::InvalidConfig(format!("URL {} has no host", url)))?
+            .to_string();
+
+        let mut delay = self.domain_delay;
+        if self.respect_robots_txt {
+            let robots = self.robots_txt(&url, &host).await;
+            let path = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            if !robots.is_allowed(&path) {
+                return Err(
+                    EmbedError::Network(format!("{} is disallowed by robots.txt", url)).into(),
+                );
+            }
+            delay = delay.max(robots.crawl_delay().unwrap_or_default());
+        }
+
+        let key = url.to_string();
+        let cached = cache.and_then(|cache| cache.lock().unwrap().get(&key).cloned());
+        let mut request = self.client.get(url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        self.wait_turn(&host, delay).await;
+        let response = request.send().await.map_err(EmbedError::from)?;
+        if response.status() == StatusCode::NOT_MODIFIED && cached.is_some() {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(EmbedError::from_response(response).await.into());
+        }
+        let header = |name: HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let body = response.text().await.map_err(EmbedError::from)?;
+
+        if cache.is_some() {
+            let content_hash = content_hash(&body);
+            if cached.is_some_and(|cached| cached.content_hash == content_hash) {
+                return Ok(None);
+            }
+            self.pending.lock().unwrap().insert(
+                key,
+                CacheEntry {
+                    etag,
+                    last_modified,
+                    content_hash,
+                },
+            );
+        }
+        Ok(Some(body))
+    }
+
+    /// Records that `url` was embedded, so the next run skips it while it stays unchanged. Pages
+    /// that fail to embed are never committed and are fetched again next time.
+    pub fn commit(&self, url: &str) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        let key = Url::parse(url).map_or_else(|_| url.to_string(), |url| url.to_string());
+        if let Some(entry) = self.pending.lock().unwrap().remove(&key) {
+            cache.lock().unwrap().insert(key, entry);
+        }
+    }
+
+    /// Writes the committed pages to the cache file, if there is one.
+    pub fn save_cache(&self) -> anyhow::Result<()> {
+        match &self.cache {
+            Some(cache) => cache.lock().unwrap().save(),
+            None => Ok(()),
+        }
+    }
+
+    /// Fetches the `robots.txt` of the origin of `url` once. A missing file (4xx) allows
+    /// everything, a server error or an unreachable server disallows everything.
+    async fn robots_txt(&self, url: &Url, host: &str) -> Arc<RobotsTxt> {
+        let origin = url.origin().ascii_serialization();
+        if let Some(robots) = self.robots.lock().unwrap().get(&origin) {
+            return robots.clone();
+        }
+
+        self.wait_turn(host, self.domain_delay).await;
+        let robots = match self
+            .client
+            .get(format!("{}/robots.txt", origin))
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => match response.text().await {
+                Ok(content) => RobotsTxt::parse(&content, &self.user_agent),
+                Err(_) => RobotsTxt::disallow_all(),
+            },
+            Ok(response) if response.status().is_client_error() => RobotsTxt::allow_all(),
+            Ok(_) | Err(_) => {
+                tracing::warn!("Could not fetch {}/robots.txt, skipping the site", origin);
+                RobotsTxt::disallow_all()
+            }
+        };
+        let robots = Arc::new(robots);
+        self.robots.lock().unwrap().insert(origin, robots.clone());
+        robots
+    }
+
+    /// Waits until `delay` has passed since the previous request to `host` was scheduled.
+    async fn wait_turn(&self, host: &str, delay: Duration) {
+        if delay.is_zero() {
+            return;
+        }
+        let wait = {
+            let mut next_request = self.next_request.lock().unwrap();
+            let now = Instant::now();
+            let slot = next_request
+                .get(host)
+                .copied()
+                .filter(|slot| *slot > now)
+                .unwrap_or(now);
+            next_request.insert(host.to_string(), slot + delay);
+            slot - now
+        };
+        if !wait.is_zero() {
+            // Not tied to a runtime, so callers without the `stream` feature can use any executor.
+            futures_timer::Delay::new(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+        # Comments are ignored
+        User-agent: *
+        Disallow: /private/
+        Allow: /private/public-page
+        Disallow: /*.pdf$
+
+        User-agent: embed_anything
+        User-agent: other-bot
+        Disallow: /no-embeddings/
+        Crawl-delay: 2
+    ";
+
+    #[test]
+    fn test_robots_named_agent() {
+        let robots = RobotsTxt::parse(ROBOTS, "embed_anything/0.5.0");
+        assert!(!robots.is_allowed("/no-embeddings/page"));
+        // Only the most specific group applies.
+        assert!(robots.is_allowed("/private/page"));
+        assert_eq!(robots.crawl_delay(), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_robots_wildcard_agent() {
+        let robots = RobotsTxt::parse(ROBOTS, "SomeBot/1.0");
+        assert!(!robots.is_allowed("/private/page"));
+        assert!(robots.is_allowed("/private/public-page"));
+        assert!(!robots.is_allowed("/files/report.pdf"));
+        assert!(robots.is_allowed("/files/report.pdf?download=1"));
+        assert!(robots.is_allowed("/no-embeddings/page"));
+        assert_eq!(robots.crawl_delay(), None);
+    }
+
+    #[test]
+    fn test_robots_disallow_all() {
+        let robots = RobotsTxt::disallow_all();
+        assert!(!robots.is_allowed("/"));
+        assert!(robots.is_allowed("/robots.txt"));
+        assert!(RobotsTxt::allow_all().is_allowed("/anything"));
+    }
+}
@@ -1,17 +1,16 @@
-use std::{
-    collections::{HashMap, HashSet},
-    rc::Rc,
-};
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Result;
 use serde_json::json;
 
 use crate::{
+    config::DEFAULT_USER_AGENT,
     embeddings::{
+        cloud::truncation::flag_truncated,
         embed::{EmbedData, Embedder},
-        get_text_metadata,
+        embed_with_backoff, get_text_metadata, BatchBackoff,
     },
-    file_processor::html_processor::HtmlProcessor,
+    file_processor::{html_processor::HtmlProcessor, web_fetcher::WebFetcher},
     text_loader::{SplittingStrategy, TextLoader},
 };
 
@@ -120,9 +119,13 @@ impl WebPage {
 
             let metadata_hashmap: HashMap<String, String> = serde_json::from_value(metadata)?;
 
-            let encodings = embedder.embed(&chunks, batch_size).await?;
-            let embeddings =
-                get_text_metadata(&Rc::new(encodings), &chunks, &Some(metadata_hashmap))?;
+            let encodings =
+                embed_with_backoff(&chunks, batch_size, &BatchBackoff::default(), |texts, b| {
+                    embedder.embed(texts, Some(b))
+                })
+                .await?;
+            let mut embeddings = get_text_metadata(&encodings, &chunks, &Some(metadata_hashmap))?;
+            flag_truncated(embedder.truncation(), &mut embeddings);
             embed_data.extend(embeddings);
         }
 
@@ -161,15 +164,33 @@ impl WebsiteProcessor {
     }
 
     pub fn process_website(&self, website: &str) -> Result<WebPage> {
-        // check if https is in the website. If not, add it.
-        let website = if website.starts_with("http") {
-            website
-        } else {
-            &format!("https://{}", website)
-        };
+        let website = normalize_url(website);
+        let response = reqwest::blocking::Client::builder()
+            .user_agent(DEFAULT_USER_AGENT)
+            .build()?
+            .get(&website)
+            .send()?
+            .text()?;
+        self.to_webpage(response, &website)
+    }
+
+    /// Fetches `website` with `fetcher` without blocking, so many pages can be fetched at once.
+    /// The fetcher applies `robots.txt`, the per-domain delay and the User-Agent. Returns `None`
+    /// when the fetcher's cache shows the page did not change since it was last embedded.
+    pub async fn fetch_website(
+        &self,
+        fetcher: &WebFetcher,
+        website: &str,
+    ) -> Result<Option<WebPage>> {
+        let website = normalize_url(website);
+        match fetcher.fetch(&website).await? {
+            Some(html) => Ok(Some(self.to_webpage(html, &website)?)),
+            None => Ok(None),
+        }
+    }
 
-        let response = reqwest::blocking::get(website)?.text()?;
-        let html_document = self.html_processor.process_html(response, Some(website))?;
+    fn to_webpage(&self, html: String, website: &str) -> Result<WebPage> {
+        let html_document = self.html_processor.process_html(html, Some(website))?;
 
         let web_page = WebPage {
             url: website.to_string(),
@@ -184,6 +205,15 @@ impl WebsiteProcessor {
     }
 }
 
+/// Adds `https://` to URLs without a scheme.
+fn normalize_url(website: &str) -> String {
+    if website.starts_with("http") {
+        website.to_string()
+    } else {
+        format!("https://{}", website)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -195,4 +225,22 @@ mod tests {
         let result = website_processor.process_website(website);
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_fetch_website() {
+        let website_processor = WebsiteProcessor::new();
+        let fetcher = WebFetcher::new(&Default::default()).unwrap();
+        let result = website_processor
+            .fetch_website(
+                &fetcher,
+                "https://www.scrapingbee.com/blog/web-scraping-rust/",
+            )
+            .await;
+        assert!(result.unwrap().unwrap().paragraphs.is_some());
+
+        let result = website_processor
+            .fetch_website(&fetcher, "http://127.0.0.1:9/")
+            .await;
+        assert!(result.is_err());
+    }
 }
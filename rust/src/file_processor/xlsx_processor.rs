@@ -0,0 +1,50 @@
+use anyhow::Error;
+use calamine::{open_workbook_auto, Reader};
+
+use super::table::Table;
+
+/// A struct for processing Excel workbooks (xlsx, xls).
+pub struct XlsxProcessor;
+
+impl XlsxProcessor {
+    /// Reads every non-empty sheet of a workbook with its name. The first row of a sheet holds
+    /// the column names.
+    pub fn read_sheets<T: AsRef<std::path::Path>>(
+        file_path: &T,
+    ) -> Result<Vec<(String, Table)>, Error> {
+        let mut workbook = open_workbook_auto(file_path)?;
+        let mut sheets = Vec::new();
+        for name in workbook.sheet_names().to_vec() {
+            let range = workbook.worksheet_range(&name)?;
+            let mut rows = range
+                .rows()
+                .map(|row| row.iter().map(|cell| cell.to_string()).collect::<Vec<_>>());
+            let Some(columns) = rows.next() else {
+                continue;
+            };
+            let rows = rows.collect();
+            sheets.push((name, Table { columns, rows }));
+        }
+        Ok(sheets)
+    }
+
+    /// Extracts the rows of every sheet as `column: value` lines, see [`Table::row_text`].
+    pub fn extract_text<T: AsRef<std::path::Path>>(file_path: &T) -> Result<String, Error> {
+        Ok(Self::read_sheets(file_path)?
+            .into_iter()
+            .map(|(_, table)| table.to_text())
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Returns an error if the file path is invalid.
+    #[test]
+    fn test_read_sheets_invalid_file_path() {
+        XlsxProcessor::read_sheets(&"invalid.xlsx").unwrap_err();
+    }
+}
@@ -0,0 +1,712 @@
+//! Model downloads from the Hugging Face Hub.
+//!
+//! Files are stored in the regular Hugging Face cache layout, as blobs named by their etag with
+//! symbolic links from the snapshot of a commit, so models downloaded by the Python
+//! `huggingface_hub` package or by `hf-hub` are reused and vice versa. Unlike `hf-hub`, the
+//! endpoint, proxy, connect timeout and progress reporting can be configured from code with
+//! [`set_hub_config`] instead of only through environment variables.
+//!
+//! A model id that is an existing local directory is read from disk instead, so models exported
+//! in-house can be loaded the same way as models on the Hub.
+//!
+//! Requests are blocking and run on a thread of their own, so models can be loaded from async code
+//! without the blocking client touching the async runtime.
+
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use hf_hub::{Cache, Repo, RepoType};
+use reqwest::blocking::Client;
+use reqwest::header::{AUTHORIZATION, CONTENT_LENGTH, RANGE};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::error::EmbedError;
+
+pub const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+
+/// Called while a file is downloaded with the file name, the bytes downloaded so far and the total
+/// size of the file if the Hub reported it.
+pub type DownloadProgress = Arc<dyn Fn(&str, u64, Option<u64>) + Send + Sync>;
+
+static HUB_CONFIG: RwLock<Option<HubConfig>> = RwLock::new(None);
+
+/// Settings used to download model files from the Hugging Face Hub.
+///
+/// Unset values fall back to the usual environment variables: `HF_ENDPOINT` for the endpoint,
+/// `HF_HOME` for the cache directory, the token saved by `huggingface-cli login`, and the
+/// `HTTP(S)_PROXY` variables for the proxy.
+#[derive(Clone, Default)]
+pub struct HubConfig {
+    pub endpoint: Option<String>,
+    pub proxy: Option<String>,
+    pub connect_timeout: Option<Duration>,
+    pub cache_dir: Option<PathBuf>,
+    pub token: Option<String>,
+    pub progress: Option<DownloadProgress>,
+    /// Model ids that may be loaded. `None` allows every model.
+    pub allowed_models: Option<Vec<String>>,
+}
+
+impl HubConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the Hub endpoint or mirror, e.g. `https://hf-mirror.com`.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Sets the proxy used for all Hub requests, e.g. `http://proxy.corp:3128`.
+    pub fn with_proxy(mut self, proxy: &str) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    pub fn with_progress(mut self, progress: DownloadProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Restricts loading to the given model ids, so untrusted input cannot trigger arbitrary
+    /// downloads. The repositories some models take their tokenizer from, `vidore/colpali` for
+    /// ColPali and `openai/clip-vit-base-patch32` for CLIP, are fixed by the model and not checked.
+    pub fn with_allowed_models(mut self, allowed_models: Vec<String>) -> Self {
+        self.allowed_models = Some(allowed_models);
+        self
+    }
+
+    /// Returns whether `model_id` passes the allowlist.
+    pub fn is_allowed(&self, model_id: &str) -> bool {
+        match &self.allowed_models {
+            Some(allowed) => allowed.iter().any(|allowed| allowed == model_id),
+            None => true,
+        }
+    }
+
+    fn endpoint(&self) -> String {
+        match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => std::env::var("HF_ENDPOINT")
+                .map(|endpoint| endpoint.trim_end_matches('/').to_string())
+                .unwrap_or_else(|_| DEFAULT_ENDPOINT.to_string()),
+        }
+    }
+
+    fn cache(&self) -> Cache {
+        match &self.cache_dir {
+            Some(cache_dir) => Cache::new(cache_dir.clone()),
+            None => Cache::default(),
+        }
+    }
+
+    fn client(&self, follow_redirects: bool) -> anyhow::Result<Client> {
+        let mut builder = Client::builder();
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(EmbedError::from)?);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if !follow_redirects {
+            builder = builder.redirect(reqwest::redirect::Policy::none());
+        }
+        Ok(builder.build().map_err(EmbedError::from)?)
+    }
+}
+
+/// Sets the Hub configuration used by every model loaded afterwards.
+pub fn set_hub_config(config: HubConfig) {
+    *HUB_CONFIG.write().unwrap_or_else(PoisonError::into_inner) = Some(config);
+}
+
+/// Returns the Hub configuration set with [`set_hub_config`], or the defaults.
+pub fn hub_config() -> HubConfig {
+    HUB_CONFIG
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_default()
+}
+
+#[derive(Deserialize)]
+struct RepoInfo {
+    siblings: Vec<Sibling>,
+}
+
+#[derive(Deserialize)]
+struct Sibling {
+    rfilename: String,
+    size: Option<u64>,
+}
+
+/// A file in a Hub repository.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepoFile {
+    pub name: String,
+    pub size: Option<u64>,
+}
+
+/// What [`prefetch`] downloaded.
+#[derive(Debug, Clone, Default)]
+pub struct PrefetchReport {
+    /// Local paths of all the prefetched files.
+    pub files: Vec<PathBuf>,
+    /// Total size of the prefetched files as reported by the Hub.
+    pub total_bytes: u64,
+    /// Size of the files that were not cached yet and had to be downloaded.
+    pub downloaded_bytes: u64,
+}
+
+/// The configs, tokenizer and weights a Candle model needs: `model.safetensors`, the sharded
+/// safetensors listed next to `model.safetensors.index.json`, or `pytorch_model.bin`, in that order
+/// of preference.
+pub fn required_files(files: &[RepoFile]) -> Vec<RepoFile> {
+    let has = |name: &str| files.iter().any(|file| file.name == name);
+    let is_weight = |name: &str| {
+        if has("model.safetensors") {
+            name == "model.safetensors"
+        } else if has("model.safetensors.index.json") {
+            name == "model.safetensors.index.json"
+                || (name.ends_with(".safetensors") && !name.contains('/'))
+        } else {
+            name == "pytorch_model.bin"
+        }
+    };
+    files
+        .iter()
+        .filter(|file| {
+            matches!(
+                file.name.as_str(),
+                "config.json" | "tokenizer.json" | "tokenizer_config.json"
+            ) || is_weight(&file.name)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Downloads the files of `model_id` into the cache ahead of time, resuming partial downloads.
+///
+/// `files` selects the files to fetch, e.g. the ONNX weights of an ONNX model. By default the
+/// configs, tokenizer and weights of a Candle model are fetched, see [`required_files`].
+pub fn prefetch(
+    model_id: &str,
+    revision: Option<&str>,
+    files: Option<&[&str]>,
+) -> anyhow::Result<PrefetchReport> {
+    let repo = HubRepo::new(model_id, revision)?;
+    let listing = repo.files()?;
+    let selected = match files {
+        Some(files) => files
+            .iter()
+            .map(|name| {
+                listing
+                    .iter()
+                    .find(|file| file.name == *name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        EmbedError::ModelLoad(format!("{} not found in {}", name, model_id))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => required_files(&listing),
+    };
+
+    let mut report = PrefetchReport::default();
+    for file in selected {
+        let size = file.size.unwrap_or(0);
+        report.total_bytes += size;
+        if repo.cached(&file.name).is_none() {
+            report.downloaded_bytes += size;
+        }
+        report.files.push(repo.get(&file.name)?);
+    }
+    Ok(report)
+}
+
+/// A model repository on the Hub, downloaded into the local cache on demand.
+pub struct HubRepo {
+    repo: Repo,
+    cache: Cache,
+    token: Option<String>,
+    endpoint: String,
+    config: HubConfig,
+    /// Set when the model id is a local directory; files are then read from it.
+    local_dir: Option<PathBuf>,
+}
+
+impl HubRepo {
+    /// Opens `model_id` at `revision` (defaults to `main`) with the global [`HubConfig`].
+    pub fn new(model_id: &str, revision: Option<&str>) -> anyhow::Result<Self> {
+        Self::with_config(model_id, revision, hub_config())
+    }
+
+    /// Opens a repository a model takes files from besides its own, such as a shared tokenizer.
+    /// It is fixed by the code rather than chosen by the caller, so the allowlist does not apply.
+    pub(crate) fn dependency(model_id: &str, revision: Option<&str>) -> anyhow::Result<Self> {
+        let mut config = hub_config();
+        config.allowed_models = None;
+        Self::with_config(model_id, revision, config)
+    }
+
+    pub fn with_config(
+        model_id: &str,
+        revision: Option<&str>,
+        config: HubConfig,
+    ) -> anyhow::Result<Self> {
+        if !config.is_allowed(model_id) {
+            return Err(EmbedError::UnsupportedModel(format!(
+                "{} is not in the list of allowed models",
+                model_id
+            ))
+            .into());
+        }
+        let repo = match revision {
+            Some(rev) => {
+                Repo::with_revision(model_id.to_string(), RepoType::Model, rev.to_string())
+            }
+            None => Repo::new(model_id.to_string(), RepoType::Model),
+        };
+        let cache = config.cache();
+        let token = config.token.clone().or_else(|| cache.token());
+        let local_dir = Path::new(model_id)
+            .is_dir()
+            .then(|| PathBuf::from(model_id));
+        Ok(Self {
+            repo,
+            cache,
+            token,
+            endpoint: config.endpoint(),
+            config,
+            local_dir,
+        })
+    }
+
+    fn url(&self, filename: &str) -> String {
+        format!(
+            "{}/{}/resolve/{}/{}",
+            self.endpoint,
+            self.repo.url(),
+            self.repo.url_revision(),
+            filename
+        )
+    }
+
+    /// Lists the files in the repository with their sizes.
+    pub fn files(&self) -> anyhow::Result<Vec<RepoFile>> {
+        if let Some(dir) = &self.local_dir {
+            return Ok(walkdir::WalkDir::new(dir)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .filter_map(|entry| {
+                    let name = entry.path().strip_prefix(dir).ok()?;
+                    Some(RepoFile {
+                        name: name.to_string_lossy().replace('\\', "/"),
+                        size: entry.metadata().ok().map(|metadata| metadata.len()),
+                    })
+                })
+                .collect());
+        }
+        off_runtime(|| self.list_files())
+    }
+
+    fn list_files(&self) -> anyhow::Result<Vec<RepoFile>> {
+        let url = format!("{}/api/{}?blobs=true", self.endpoint, self.repo.api_url());
+        let mut request = self.config.client(true)?.get(&url);
+        if let Some(token) = &self.token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let response = request.send().map_err(EmbedError::from)?;
+        if !response.status().is_success() {
+            return Err(EmbedError::Network(format!("{}: {}", response.status(), url)).into());
+        }
+        let info: RepoInfo = response.json().map_err(EmbedError::from)?;
+        Ok(info
+            .siblings
+            .into_iter()
+            .map(|sibling| RepoFile {
+                name: sibling.rfilename,
+                size: sibling.size,
+            })
+            .collect())
+    }
+
+    /// Returns the cached path of `filename` without touching the network. For a local directory
+    /// `filename` may also be an absolute path, e.g. to an `.onnx` file stored elsewhere.
+    pub fn cached(&self, filename: &str) -> Option<PathBuf> {
+        match &self.local_dir {
+            Some(dir) => Some(dir.join(filename)).filter(|path| path.is_file()),
+            None => self.cache.repo(self.repo.clone()).get(filename),
+        }
+    }
+
+    /// Returns the local path of `filename`, downloading it first if it is not cached yet.
+    pub fn get(&self, filename: &str) -> anyhow::Result<PathBuf> {
+        match (self.cached(filename), &self.local_dir) {
+            (Some(path), _) => Ok(path),
+            (None, Some(dir)) => {
+                Err(
+                    EmbedError::ModelLoad(format!("{} not found in {}", filename, dir.display()))
+                        .into(),
+                )
+            }
+            (None, None) => off_runtime(|| self.download(filename)),
+        }
+    }
+
+    /// Like [`get`](Self::get), but returns `None` when the repository has no `filename`. Files the
+    /// Hub reported missing before are recorded in the cache, so they are not requested again.
+    pub fn get_optional(&self, filename: &str) -> anyhow::Result<Option<PathBuf>> {
+        if self.local_dir.is_some() || self.known_missing(filename) {
+            return Ok(self.cached(filename));
+        }
+        match self.get(filename) {
+            Ok(path) => Ok(Some(path)),
+            Err(e) if matches!(e.downcast_ref(), Some(EmbedError::ModelLoad(_))) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether the Hub reported `filename` missing at the cached commit of the revision, in the
+    /// `.no_exist` folder the Hub libraries use for this.
+    fn known_missing(&self, filename: &str) -> bool {
+        let repo_dir = self.cache.path().join(self.repo.folder_name());
+        let commit_hash = fs::read_to_string(repo_dir.join("refs").join(self.repo.revision()))
+            .map(|commit_hash| commit_hash.trim().to_string())
+            .unwrap_or_else(|_| self.repo.revision().to_string());
+        repo_dir
+            .join(".no_exist")
+            .join(commit_hash)
+            .join(filename)
+            .exists()
+    }
+
+    fn download(&self, filename: &str) -> anyhow::Result<PathBuf> {
+        let url = self.url(filename);
+
+        // The commit hash is only sent on the first response, before the redirect to the CDN.
+        let mut request = self.config.client(false)?.head(&url);
+        if let Some(token) = &self.token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let response = request.send().map_err(EmbedError::from)?;
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        let repo_dir = self.cache.path().join(self.repo.folder_name());
+        if response.status() == StatusCode::NOT_FOUND {
+            if let (Some("EntryNotFound"), Some(commit_hash)) =
+                (header("x-error-code").as_deref(), header("x-repo-commit"))
+            {
+                let marker = repo_dir.join(".no_exist").join(commit_hash).join(filename);
+                fs::create_dir_all(marker.parent().unwrap()).map_err(EmbedError::from)?;
+                fs::write(marker, "").map_err(EmbedError::from)?;
+            }
+            return Err(EmbedError::ModelLoad(format!(
+                "{} not found in {}",
+                filename,
+                self.repo.url()
+            ))
+            .into());
+        }
+        if !response.status().is_success() && !response.status().is_redirection() {
+            return Err(EmbedError::Network(format!("{}: {}", response.status(), url)).into());
+        }
+        let commit_hash =
+            header("x-repo-commit").unwrap_or_else(|| self.repo.revision().to_string());
+        let etag = header("x-linked-etag")
+            .or_else(|| header("etag"))
+            .map(|etag| etag.trim_start_matches("W/").trim_matches('"').to_string());
+        let size = header("x-linked-size")
+            .or_else(|| header(CONTENT_LENGTH.as_str()))
+            .and_then(|size| size.parse::<u64>().ok());
+
+        let path = repo_dir.join("snapshots").join(&commit_hash).join(filename);
+        // The content is stored once as a blob named by its etag, and linked from the snapshot.
+        let blob = match &etag {
+            Some(etag) => repo_dir.join("blobs").join(etag),
+            None => path.clone(),
+        };
+        for dir in [path.parent(), blob.parent()].into_iter().flatten() {
+            fs::create_dir_all(dir).map_err(EmbedError::from)?;
+        }
+        if blob != path && blob.is_file() {
+            link_blob(&blob, &path)?;
+            self.update_ref(&repo_dir, &commit_hash)?;
+            return Ok(path);
+        }
+        let incomplete = blob.with_file_name(format!(
+            "{}.incomplete",
+            blob.file_name().unwrap().to_string_lossy()
+        ));
+
+        // Resume from a partial file left behind by an interrupted download.
+        let mut downloaded = fs::metadata(&incomplete).map(|m| m.len()).unwrap_or(0);
+        let mut request = self.config.client(true)?.get(&url);
+        if let Some(token) = &self.token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        if downloaded > 0 {
+            request = request.header(RANGE, format!("bytes={downloaded}-"));
+        }
+        let mut response = request.send().map_err(EmbedError::from)?;
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // The partial file is already complete, or stale; start over.
+            fs::remove_file(&incomplete).map_err(EmbedError::from)?;
+            return self.download(filename);
+        }
+        if !response.status().is_success() {
+            return Err(EmbedError::Network(format!("{}: {}", response.status(), url)).into());
+        }
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+        if !resumed {
+            downloaded = 0;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(&incomplete)
+            .map_err(EmbedError::from)?;
+        let mut buffer = vec![0u8; 1 << 16];
+        loop {
+            let read = response.read(&mut buffer).map_err(EmbedError::from)?;
+            if read == 0 {
+                break;
+            }
+            file.write_all(&buffer[..read]).map_err(EmbedError::from)?;
+            downloaded += read as u64;
+            if let Some(progress) = &self.config.progress {
+                progress(filename, downloaded, size);
+            }
+        }
+        file.flush().map_err(EmbedError::from)?;
+        drop(file);
+
+        fs::rename(&incomplete, &blob).map_err(EmbedError::from)?;
+        if blob != path {
+            link_blob(&blob, &path)?;
+        }
+        self.update_ref(&repo_dir, &commit_hash)?;
+        Ok(path)
+    }
+
+    /// Points the ref of the revision at `commit_hash`, unless it already does.
+    fn update_ref(&self, repo_dir: &Path, commit_hash: &str) -> anyhow::Result<()> {
+        let ref_path = repo_dir.join("refs").join(self.repo.revision());
+        if fs::read_to_string(ref_path).is_ok_and(|current| current.trim() == commit_hash) {
+            return Ok(());
+        }
+        self.cache
+            .repo(self.repo.clone())
+            .create_ref(commit_hash)
+            .map_err(EmbedError::from)?;
+        Ok(())
+    }
+}
+
+/// Links the snapshot file `path` to `blob` with a relative symbolic link, as the Hub libraries
+/// do. Where symbolic links are not available the blob is copied instead.
+fn link_blob(blob: &Path, path: &Path) -> anyhow::Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path).map_err(EmbedError::from)?;
+    }
+    // From `snapshots/<commit>/<filename>` back up to the repository folder.
+    let depth = path
+        .strip_prefix(blob.parent().and_then(Path::parent).unwrap_or(blob))
+        .map(|relative| relative.components().count() - 1)
+        .unwrap_or(0);
+    let mut target = PathBuf::new();
+    for _ in 0..depth {
+        target.push("..");
+    }
+    target.push("blobs");
+    target.push(blob.file_name().unwrap());
+
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(&target, path);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_file(&target, path);
+    #[cfg(not(any(unix, windows)))]
+    let linked: std::io::Result<()> = Err(std::io::ErrorKind::Unsupported.into());
+    if linked.is_err() {
+        fs::copy(blob, path).map_err(EmbedError::from)?;
+    }
+    Ok(())
+}
+
+/// Runs the blocking Hub request `f` on a thread of its own. The blocking client panics when it is
+/// created or dropped on a thread driving an async runtime, which is where models loaded from async
+/// code would otherwise make their requests.
+fn off_runtime<T: Send>(f: impl FnOnce() -> anyhow::Result<T> + Send) -> anyhow::Result<T> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(f)
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo_files(names: &[&str]) -> Vec<RepoFile> {
+        names
+            .iter()
+            .map(|name| RepoFile {
+                name: name.to_string(),
+                size: Some(1),
+            })
+            .collect()
+    }
+
+    fn names(files: Vec<RepoFile>) -> Vec<String> {
+        files.into_iter().map(|file| file.name).collect()
+    }
+
+    #[test]
+    fn test_allowed_models() {
+        let config = HubConfig::new()
+            .with_allowed_models(vec!["sentence-transformers/all-MiniLM-L6-v2".to_string()]);
+        assert!(HubRepo::with_config(
+            "sentence-transformers/all-MiniLM-L6-v2",
+            None,
+            config.clone()
+        )
+        .is_ok());
+        assert!(HubRepo::with_config("someone/untrusted-model", None, config).is_err());
+    }
+
+    #[test]
+    fn test_local_dir() {
+        let dir = std::env::temp_dir().join("embed_anything_test_local_dir");
+        fs::create_dir_all(dir.join("onnx")).unwrap();
+        fs::write(dir.join("tokenizer.json"), "{}").unwrap();
+        fs::write(dir.join("onnx/model.onnx"), "onnx").unwrap();
+
+        let repo = HubRepo::new(dir.to_str().unwrap(), None).unwrap();
+        assert_eq!(
+            repo.get("onnx/model.onnx").unwrap(),
+            dir.join("onnx/model.onnx")
+        );
+        assert!(repo.get("config.json").is_err());
+        let mut files = names(repo.files().unwrap());
+        files.sort();
+        assert_eq!(files, vec!["onnx/model.onnx", "tokenizer.json"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_required_files() {
+        let files = repo_files(&[
+            "config.json",
+            "tokenizer.json",
+            "model.safetensors",
+            "pytorch_model.bin",
+            "onnx/model.onnx",
+            "README.md",
+        ]);
+        assert_eq!(
+            names(required_files(&files)),
+            vec!["config.json", "tokenizer.json", "model.safetensors"]
+        );
+
+        let files = repo_files(&[
+            "config.json",
+            "model-00001-of-00002.safetensors",
+            "model-00002-of-00002.safetensors",
+            "model.safetensors.index.json",
+        ]);
+        assert_eq!(required_files(&files).len(), 4);
+
+        let files = repo_files(&["config.json", "pytorch_model.bin", "flax_model.msgpack"]);
+        assert_eq!(
+            names(required_files(&files)),
+            vec!["config.json", "pytorch_model.bin"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_blob() {
+        let dir = std::env::temp_dir().join("embed_anything_test_link_blob");
+        let blob = dir.join("blobs/abc");
+        let path = dir.join("snapshots/123/onnx/model.onnx");
+        fs::create_dir_all(blob.parent().unwrap()).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&blob, "onnx").unwrap();
+
+        link_blob(&blob, &path).unwrap();
+        assert_eq!(
+            fs::read_link(&path).unwrap(),
+            PathBuf::from("../../../blobs/abc")
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "onnx");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_optional_known_missing() {
+        let dir = std::env::temp_dir().join("embed_anything_test_known_missing");
+        let repo_dir = dir.join("models--BAAI--bge-m3");
+        fs::create_dir_all(repo_dir.join("refs")).unwrap();
+        fs::write(repo_dir.join("refs/main"), "123").unwrap();
+        fs::create_dir_all(repo_dir.join(".no_exist/123/onnx")).unwrap();
+        fs::write(repo_dir.join(".no_exist/123/onnx/model.onnx_data"), "").unwrap();
+
+        // The endpoint is unreachable, so a request would fail instead of returning `None`.
+        let config = HubConfig::new()
+            .with_cache_dir(dir.clone())
+            .with_endpoint("http://127.0.0.1:9");
+        let repo = HubRepo::with_config("BAAI/bge-m3", None, config).unwrap();
+        assert!(repo.get_optional("onnx/model.onnx_data").unwrap().is_none());
+        assert!(repo.get_optional("onnx/model.onnx").is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_hub_repo_url() {
+        let config = HubConfig::new().with_endpoint("https://hf-mirror.com/");
+        let repo = HubRepo::with_config(
+            "sentence-transformers/all-MiniLM-L6-v2",
+            Some("refs/pr/21"),
+            config,
+        )
+        .unwrap();
+        assert_eq!(
+            repo.url("config.json"),
+            "https://hf-mirror.com/sentence-transformers/all-MiniLM-L6-v2/resolve/refs%2Fpr%2F21/config.json"
+        );
+    }
+}
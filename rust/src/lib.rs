@@ -1,29 +1,54 @@
 //! # Embed Anything
 //! This library provides a simple interface to embed text and images using various embedding models.
+pub mod adapters;
 pub mod chunkers;
 pub mod config;
 pub mod embeddings;
+pub mod error;
 pub mod file_loader;
 pub mod file_processor;
+pub mod hub;
 pub mod models;
+pub mod pipeline;
+#[cfg(feature = "stream")]
+mod progress;
 pub mod reranker;
 pub mod text_loader;
+pub mod warning;
 
-use std::{collections::HashMap, fs, path::PathBuf, rc::Rc, sync::Arc};
+pub use reranker::model::{RerankScore, Reranker};
 
+use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
+
+use adapters::{EmbeddingSink, VectorAdapter};
 use anyhow::Result;
-use config::{ImageEmbedConfig, TextEmbedConfig};
+use config::{
+    DocumentPooling, ImageEmbedConfig, MetadataConfig, TextEmbedConfig, WebFetchConfig,
+    DEFAULT_BUFFER_SIZE, DEFAULT_CHUNK_SIZE, DEFAULT_OVERLAP_RATIO, DEFAULT_STREAMING_THRESHOLD,
+    DEFAULT_TABLE_ROWS_PER_CHUNK, DEFAULT_WEB_CONCURRENCY,
+};
 use embeddings::{
+    cloud::truncation::{flag_truncated, TRUNCATED_KEY},
     embed::{EmbedData, EmbedImage, Embedder, TextEmbedder, VisionEmbedder},
-    embed_audio, get_text_metadata,
+    embed_audio, embed_with_backoff, embed_with_backoff_warnings, get_text_metadata,
+    timing::{timed, Stage},
+    BatchBackoff, DocumentPooler,
 };
 use file_loader::FileParser;
 use file_processor::audio::audio_processor::{self, AudioDecoderModel};
+use file_processor::{
+    docx_processor::DocxTextOptions, json_processor::JsonTextOptions, pdf_processor::PdfTextOptions,
+};
+use futures::{SinkExt, StreamExt};
 use itertools::Itertools;
 use rayon::prelude::*;
-use text_loader::{SplittingStrategy, TextLoader};
+use text_loader::{Chunk, ChunkUnit, SplittingStrategy, TextLoader, TextSection};
+#[cfg(feature = "stream")]
 use tokio::sync::mpsc; // Add this at the top of your file
 
+use warning::{warn, EmbedWarning, Warnings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Dtype {
     F16,
     INT8,
@@ -33,6 +58,7 @@ pub enum Dtype {
     F32,
     Q4F16,
     QUANTIZED,
+    BF16,
 }
 
 /// Embeds a list of queries using the specified embedding model.
@@ -72,15 +98,40 @@ pub async fn embed_query(
 ) -> Result<Vec<EmbedData>> {
     let binding = TextEmbedConfig::default();
     let config = config.unwrap_or(&binding);
-    let _chunk_size = config.chunk_size.unwrap_or(256);
+    let _chunk_size = config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
     let batch_size = config.batch_size;
 
-    let encodings = embedder.embed(&query, batch_size).await.unwrap();
-    let embeddings = get_text_metadata(&Rc::new(encodings), &query, &None)?;
+    let encodings = embed_with_backoff(&query, batch_size, &config.backoff, |texts, b| {
+        embedder.embed_query(texts, Some(b))
+    })
+    .await?;
+    let embeddings = get_text_metadata(&encodings, &query, &None)?;
 
     Ok(embeddings)
 }
 
+/// Embeds a list of queries like [`embed_query`], returning them as an Arrow backed
+/// [`EmbedDataBatch`](embeddings::arrow::EmbedDataBatch) instead of one `EmbedData` per query.
+#[cfg(feature = "arrow")]
+pub async fn embed_query_batch(
+    query: Vec<String>,
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+) -> Result<embeddings::arrow::EmbedDataBatch> {
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let encodings = embed_with_backoff(&query, config.batch_size, &config.backoff, |texts, b| {
+        embedder.embed_query(texts, Some(b))
+    })
+    .await?;
+    let metadata = vec![None; query.len()];
+    embeddings::arrow::EmbedDataBatch::from_results(
+        encodings,
+        query.into_iter().map(Some).collect(),
+        metadata,
+    )
+}
+
 /// Embeds the text from a file using the specified embedding model.
 ///
 /// # Arguments
@@ -120,9 +171,10 @@ where
 {
     let binding = TextEmbedConfig::default();
     let config = config.unwrap_or(&binding);
-    let chunk_size = config.chunk_size.unwrap_or(256);
-    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
+    let chunk_size = config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+    let overlap_ratio = config.effective_overlap_ratio();
     let batch_size = config.batch_size;
+    let buffer_size = config.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE);
     let splitting_strategy = config
         .splitting_strategy
         .unwrap_or(SplittingStrategy::Sentence);
@@ -137,10 +189,26 @@ where
                 Some(chunk_size),
                 Some(overlap_ratio),
                 batch_size,
+                &config.backoff,
+                buffer_size,
                 Some(splitting_strategy),
+                config.chunk_unit,
                 semantic_encoder,
                 adapter,
                 use_ocr,
+                config.metadata.as_ref(),
+                config.document_pooling,
+                config.invalid_embeddings,
+                config.warnings.as_ref(),
+                config.pdf,
+                config.docx,
+                &config.json,
+                config
+                    .table_rows_per_chunk
+                    .unwrap_or(DEFAULT_TABLE_ROWS_PER_CHUNK),
+                config
+                    .streaming_threshold
+                    .unwrap_or(DEFAULT_STREAMING_THRESHOLD),
             )
             .await
         }
@@ -148,7 +216,83 @@ where
     }
 }
 
-/// Embeddings of a webpage using the specified embedding model.
+/// Splits text into chunks the same way the embedding functions do, without embedding them.
+///
+/// Uses the chunk size, overlap ratio, chunk unit, splitting strategy and semantic encoder of
+/// `config`. Without a model, [`ChunkUnit::Tokens`] counts cl100k tokens; use
+/// [`TokenChunker`](chunkers::token::TokenChunker) to chunk in the tokens of a model.
+///
+/// Fails when the chunk size and overlap of `config` cannot be used together. Empty text has no
+/// chunks.
+pub fn chunk_text(text: &str, config: Option<&TextEmbedConfig>) -> Result<Vec<String>> {
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let textloader = TextLoader::with_chunk_unit(
+        config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE),
+        config.effective_overlap_ratio(),
+        config.chunk_unit,
+        None,
+    )?;
+    Ok(textloader
+        .split_into_chunks(
+            text,
+            config
+                .splitting_strategy
+                .unwrap_or(SplittingStrategy::Sentence),
+            config.semantic_encoder.clone(),
+        )
+        .unwrap_or_default())
+}
+
+/// Extracts the text of a file and splits it into chunks without embedding them, so the parsers
+/// can be reused on their own. Every chunk carries the metadata of the file, and that of its
+/// section for DOCX files and PDFs split at their outline, see [`TextLoader::extract_sections`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::chunk_file;
+///
+/// let chunks = chunk_file("test_files/test.pdf", None).unwrap();
+/// ```
+pub fn chunk_file<T: AsRef<std::path::Path>>(
+    file_name: T,
+    config: Option<&TextEmbedConfig>,
+) -> Result<Vec<Chunk>> {
+    let use_ocr = config.and_then(|config| config.use_ocr).unwrap_or(false);
+    let pdf_options = config.map(|config| config.pdf).unwrap_or_default();
+    let docx_options = config.map(|config| config.docx).unwrap_or_default();
+    let json_options = config.map(|config| config.json.clone()).unwrap_or_default();
+    let table_rows_per_chunk = config
+        .and_then(|config| config.table_rows_per_chunk)
+        .unwrap_or(DEFAULT_TABLE_ROWS_PER_CHUNK);
+    let sections = TextLoader::extract_sections(
+        &file_name,
+        use_ocr,
+        &pdf_options,
+        &docx_options,
+        &json_options,
+        table_rows_per_chunk,
+    )?;
+    let metadata = TextLoader::get_metadata(&file_name).ok();
+    let mut chunks = Vec::new();
+    for section in &sections {
+        let metadata = section_metadata(&metadata, section);
+        chunks.extend(
+            chunk_text(&section.text, config)?
+                .into_iter()
+                .map(|text| Chunk {
+                    text,
+                    metadata: metadata.clone(),
+                }),
+        );
+    }
+    Ok(chunks)
+}
+
+/// Embeddings of a webpage using the specified embedding model. The page is fetched like
+/// [`embed_webpages`] fetches it, with the default [`WebFetchConfig`]: `robots.txt` is honoured and
+/// requests carry the `embed_anything` User-Agent.
 ///
 /// # Arguments
 ///
@@ -183,18 +327,23 @@ where
 ///     }
 /// };
 /// ```
-pub async fn embed_webpage<F>(
+pub async fn embed_webpage<S>(
     url: String,
     embedder: &Embedder,
     config: Option<&TextEmbedConfig>,
-    // Callback function
-    adapter: Option<F>,
+    // Callback function or sink
+    adapter: Option<S>,
 ) -> Result<Option<Vec<EmbedData>>>
 where
-    F: Fn(Vec<EmbedData>),
+    S: EmbeddingSink,
 {
+    let fetcher = file_processor::web_fetcher::WebFetcher::new(&WebFetchConfig::default())?;
     let website_processor = file_processor::website_processor::WebsiteProcessor::new();
-    let webpage = website_processor.process_website(url.as_ref())?;
+    // Without a cache the fetcher always returns the page.
+    let webpage = website_processor
+        .fetch_website(&fetcher, &url)
+        .await?
+        .unwrap_or_default();
 
     // if let Embedder::Clip(_) = embedder {
     //     return Err(anyhow!("Clip model does not support webpage embedding"));
@@ -202,23 +351,382 @@ where
 
     let binding = TextEmbedConfig::default();
     let config = config.unwrap_or(&binding);
-    let chunk_size = config.chunk_size.unwrap_or(256);
-    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
+    let chunk_size = config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+    let overlap_ratio = config.effective_overlap_ratio();
     let batch_size = config.batch_size;
 
-    let embeddings = webpage
+    let mut embeddings = webpage
         .embed_webpage(embedder, chunk_size, overlap_ratio, batch_size)
         .await?;
+    config.apply_metadata_config(&mut embeddings);
 
     // Send embeddings to vector database
-    if let Some(adapter) = adapter {
-        adapter(embeddings);
+    if let Some(mut adapter) = adapter {
+        adapter.upsert(embeddings).await?;
         Ok(None)
     } else {
         Ok(Some(embeddings))
     }
 }
 
+/// The outcome of one URL passed to [`embed_webpages`] or listed by [`embed_sitemap`].
+#[derive(Debug)]
+pub struct WebpageResult {
+    pub url: String,
+    /// The embeddings of the page, or `None` when they were handed to the adapter or the page was
+    /// unchanged.
+    pub result: Result<Option<Vec<EmbedData>>>,
+    /// Whether the page was skipped because it did not change since the run that filled
+    /// `fetch_config.cache_path`.
+    pub unchanged: bool,
+}
+
+/// Embeds many webpages, fetching up to `fetch_config.concurrency` of them at the same time.
+/// Pages disallowed by `robots.txt` are skipped and requests to the same host are spaced by
+/// `fetch_config.domain_delay`, see [`WebFetchConfig`].
+///
+/// A page that cannot be fetched or embedded does not stop the others: its error is reported in
+/// the [`WebpageResult`] of its URL. With an adapter, callback or [`EmbeddingSink`], the
+/// embeddings of each page are handed over as soon as the page is done. Fetching pauses while the
+/// sink is busy, and the first error of the sink stops the run. The results are in the order of
+/// `urls`.
+///
+/// With `fetch_config.cache_path`, only pages that changed since they were last embedded are
+/// embedded again. The cache is written when all pages are done.
+///
+/// # Example
+///
+/// ```no_run
+/// use embed_anything::embed_webpages;
+/// use embed_anything::embeddings::embed::{EmbedData, Embedder};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let embedder = Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)?;
+/// let urls = vec!["https://example.com".to_string(), "https://example.org".to_string()];
+/// let results = embed_webpages(&urls, &embedder, None, None, None::<fn(Vec<EmbedData>)>).await?;
+/// for page in results {
+///     if let Err(e) = page.result {
+///         eprintln!("{}: {}", page.url, e);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn embed_webpages<S>(
+    urls: &[String],
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+    fetch_config: Option<&WebFetchConfig>,
+    // Callback function or sink
+    adapter: Option<S>,
+) -> Result<Vec<WebpageResult>>
+where
+    S: EmbeddingSink,
+{
+    let fetch_binding = WebFetchConfig::default();
+    let fetch_config = fetch_config.unwrap_or(&fetch_binding);
+    let fetcher = file_processor::web_fetcher::WebFetcher::new(fetch_config)?;
+    embed_pages(
+        urls,
+        embedder,
+        config,
+        fetch_config,
+        &fetcher,
+        None,
+        adapter,
+    )
+    .await
+}
+
+/// Embeds every page listed in the sitemap at `url`, following sitemap indexes. The pages are
+/// fetched and embedded like [`embed_webpages`] does: up to `fetch_config.concurrency` at the same
+/// time, honouring `robots.txt`, and with the embeddings of each page handed to the adapter as soon
+/// as the page is done.
+///
+/// On top of the `url` of their page, the embeddings carry the `sitemap` that listed the page and
+/// its `lastmod` when the sitemap gives one. The results are in the order of the sitemap.
+///
+/// Fails only when the sitemap at `url` cannot be fetched or parsed. Nested sitemaps that fail are
+/// skipped, and pages that fail are reported in their [`WebpageResult`].
+///
+/// # Example
+///
+/// ```no_run
+/// use embed_anything::embed_sitemap;
+/// use embed_anything::embeddings::embed::{EmbedData, Embedder};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let embedder = Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)?;
+/// let results = embed_sitemap(
+///     "https://example.com/sitemap.xml",
+///     &embedder,
+///     None,
+///     None,
+///     Some(|batch: Vec<EmbedData>| println!("{} embeddings", batch.len())),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn embed_sitemap<S>(
+    url: &str,
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+    fetch_config: Option<&WebFetchConfig>,
+    // Callback function or sink
+    adapter: Option<S>,
+) -> Result<Vec<WebpageResult>>
+where
+    S: EmbeddingSink,
+{
+    let fetch_binding = WebFetchConfig::default();
+    let fetch_config = fetch_config.unwrap_or(&fetch_binding);
+    let fetcher = file_processor::web_fetcher::WebFetcher::new(fetch_config)?;
+    let pages = file_processor::sitemap::fetch_sitemap(&fetcher, url).await?;
+
+    let urls = pages
+        .iter()
+        .map(|page| page.loc.clone())
+        .collect::<Vec<_>>();
+    let page_metadata = pages
+        .into_iter()
+        .map(|page| {
+            let mut metadata = HashMap::from([("sitemap".to_string(), page.sitemap)]);
+            if let Some(lastmod) = page.lastmod {
+                metadata.insert("lastmod".to_string(), lastmod);
+            }
+            metadata
+        })
+        .collect::<Vec<_>>();
+    embed_pages(
+        &urls,
+        embedder,
+        config,
+        fetch_config,
+        &fetcher,
+        Some(&page_metadata),
+        adapter,
+    )
+    .await
+}
+
+/// Fetches and embeds `urls` concurrently with `fetcher`, adding `page_metadata[i]` to the
+/// metadata of the embeddings of `urls[i]`.
+async fn embed_pages<S>(
+    urls: &[String],
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+    fetch_config: &WebFetchConfig,
+    fetcher: &file_processor::web_fetcher::WebFetcher,
+    page_metadata: Option<&[HashMap<String, String>]>,
+    adapter: Option<S>,
+) -> Result<Vec<WebpageResult>>
+where
+    S: EmbeddingSink,
+{
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let chunk_size = config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+    let overlap_ratio = config.effective_overlap_ratio();
+    let batch_size = config.batch_size;
+
+    let concurrency = fetch_config
+        .concurrency
+        .unwrap_or(DEFAULT_WEB_CONCURRENCY)
+        .max(1);
+    let website_processor = file_processor::website_processor::WebsiteProcessor::new();
+
+    let mut pages = futures::stream::iter(urls.iter().enumerate())
+        .map(|(index, url)| {
+            let website_processor = &website_processor;
+            async move {
+                let result = async {
+                    let Some(webpage) = website_processor.fetch_website(fetcher, url).await? else {
+                        return Ok(None);
+                    };
+                    let embeddings = webpage
+                        .embed_webpage(embedder, chunk_size, overlap_ratio, batch_size)
+                        .await?;
+                    Ok::<_, anyhow::Error>(Some((webpage.url, embeddings)))
+                }
+                .await;
+                (index, result)
+            }
+        })
+        .buffer_unordered(concurrency);
+
+    // Bounded, so that fetching pauses while the adapter catches up.
+    let (sink_tx, mut sink_rx) = futures::channel::mpsc::channel(SINK_QUEUE_SIZE);
+    let mut sink_tx = adapter.is_some().then_some(sink_tx);
+    let embed = async move {
+        let mut results = Vec::with_capacity(urls.len());
+        while let Some((index, result)) = pages.next().await {
+            let unchanged = matches!(result, Ok(None));
+            let result = match result {
+                Ok(Some((page_url, mut embeddings))) => {
+                    if let Some(page_metadata) = page_metadata {
+                        for embedding in embeddings.iter_mut() {
+                            embedding
+                                .metadata
+                                .get_or_insert_with(HashMap::new)
+                                .extend(page_metadata[index].clone());
+                        }
+                    }
+                    config.apply_metadata_config(&mut embeddings);
+                    match sink_tx.as_mut() {
+                        Some(sink_tx) => {
+                            // The channel is closed once the sink failed, which ends the run.
+                            if sink_tx.send((page_url, embeddings)).await.is_err() {
+                                break;
+                            }
+                            Ok(None)
+                        }
+                        None => {
+                            fetcher.commit(&page_url);
+                            Ok(Some(embeddings))
+                        }
+                    }
+                }
+                Ok(None) => Ok(None),
+                Err(e) => Err(e),
+            };
+            results.push((
+                index,
+                WebpageResult {
+                    url: urls[index].clone(),
+                    result,
+                    unchanged,
+                },
+            ));
+        }
+        results
+    };
+    let sink = async move {
+        if let Some(mut adapter) = adapter {
+            while let Some((page_url, embeddings)) = sink_rx.next().await {
+                adapter.upsert(embeddings).await?;
+                // Pages are only skipped by later runs once the sink stored them.
+                fetcher.commit(&page_url);
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    let (mut results, sink) = futures::join!(embed, sink);
+    if let Err(e) = fetcher.save_cache() {
+        tracing::warn!("Error saving the web cache: {:?}", e);
+    }
+    sink?;
+
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+/// The outcome of [`ingest_files`] or [`ingest_url`].
+#[derive(Debug, Default)]
+pub struct IngestReport {
+    /// The number of sources whose embeddings were all written to the adapter.
+    pub sources: usize,
+    /// The number of embeddings written to the adapter.
+    pub embeddings: usize,
+    /// The sources that could not be embedded or written, with their error.
+    pub errors: Vec<(String, anyhow::Error)>,
+}
+
+impl IngestReport {
+    /// Writes the embeddings of `source` to `adapter`, creating the index from the first
+    /// embedding written, and counts them or records the error.
+    fn write<A: VectorAdapter + ?Sized>(
+        &mut self,
+        source: String,
+        embeddings: Result<Vec<EmbedData>>,
+        adapter: &A,
+    ) {
+        let written = embeddings.and_then(|embeddings| {
+            if embeddings.is_empty() {
+                return Ok(0);
+            }
+            if self.embeddings == 0 {
+                let (dimension, multi_vector) = adapters::index_shape(&embeddings[0]);
+                adapter.create_index(dimension, multi_vector)?;
+            }
+            adapter.upsert(&embeddings)?;
+            Ok(embeddings.len())
+        });
+        match written {
+            Ok(count) => {
+                self.sources += 1;
+                self.embeddings += count;
+            }
+            Err(e) => {
+                tracing::warn!("Could not ingest {}: {:?}", source, e);
+                self.errors.push((source, e));
+            }
+        }
+    }
+}
+
+/// Embeds `files` one after the other like [`embed_file`] and writes the embeddings of each file
+/// to `adapter`, creating its index from the first embedding. A file that cannot be embedded or
+/// written does not stop the others: its error is reported in the [`IngestReport`].
+///
+/// # Example
+///
+/// ```no_run
+/// use embed_anything::adapters::qdrant::QdrantAdapter;
+/// use embed_anything::embeddings::embed::Embedder;
+/// use embed_anything::ingest_files;
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let embedder = Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)?;
+/// let qdrant = QdrantAdapter::new("http://localhost:6333", "documents");
+/// let report = ingest_files(&["report.pdf", "notes.md"], &embedder, None, &qdrant).await;
+/// println!("{} embeddings, {} errors", report.embeddings, report.errors.len());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn ingest_files<T, A>(
+    files: &[T],
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+    adapter: &A,
+) -> IngestReport
+where
+    T: AsRef<std::path::Path>,
+    A: VectorAdapter + ?Sized,
+{
+    let mut report = IngestReport::default();
+    for file in files {
+        let embeddings = embed_file(file, embedder, config, None::<fn(Vec<EmbedData>)>)
+            .await
+            .map(Option::unwrap_or_default);
+        let source = file.as_ref().to_string_lossy().to_string();
+        report.write(source, embeddings, adapter);
+    }
+    report
+}
+
+/// Fetches and embeds the webpage at `url` like [`embed_webpage`] and writes its embeddings to
+/// `adapter`, creating its index. A page that cannot be fetched, embedded or written is reported
+/// in the errors of the [`IngestReport`].
+pub async fn ingest_url<A: VectorAdapter + ?Sized>(
+    url: &str,
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+    adapter: &A,
+) -> IngestReport {
+    let embeddings = embed_webpage(
+        url.to_string(),
+        embedder,
+        config,
+        None::<fn(Vec<EmbedData>)>,
+    )
+    .await
+    .map(Option::unwrap_or_default);
+    let mut report = IngestReport::default();
+    report.write(url.to_string(), embeddings, adapter);
+    report
+}
+
 /// Embeds an HTML document using the specified embedding model.
 ///
 /// # Arguments
@@ -243,33 +751,37 @@ where
 ///     "https://example.com/",
 ///     &Embedder::Text(TextEmbedder::Jina(JinaEmbedder::default())),
 ///     Some(&config),
-///     None,
+///     None::<fn(Vec<EmbedData>)>,
 /// )
 /// ```
-pub async fn embed_html(
+pub async fn embed_html<S>(
     file_name: impl AsRef<std::path::Path>,
     origin: Option<impl Into<String>>,
     embedder: &Embedder,
     config: Option<&TextEmbedConfig>,
-    // Callback function
-    adapter: Option<Box<dyn FnOnce(Vec<EmbedData>)>>,
-) -> Result<Option<Vec<EmbedData>>> {
+    // Callback function or sink
+    adapter: Option<S>,
+) -> Result<Option<Vec<EmbedData>>>
+where
+    S: EmbeddingSink,
+{
     let html_processor = file_processor::html_processor::HtmlProcessor::new();
     let html = html_processor.process_html_file(file_name.as_ref(), origin)?;
 
     let binding = TextEmbedConfig::default();
     let config = config.unwrap_or(&binding);
-    let chunk_size = config.chunk_size.unwrap_or(256);
-    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
+    let chunk_size = config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+    let overlap_ratio = config.effective_overlap_ratio();
     let batch_size = config.batch_size;
 
-    let embeddings = html
+    let mut embeddings = html
         .embed_webpage(embedder, chunk_size, overlap_ratio, batch_size)
         .await?;
+    config.apply_metadata_config(&mut embeddings);
 
     // Send embeddings to vector database
-    if let Some(adapter) = adapter {
-        adapter(embeddings);
+    if let Some(mut adapter) = adapter {
+        adapter.upsert(embeddings).await?;
         Ok(None)
     } else {
         Ok(Some(embeddings))
@@ -283,39 +795,225 @@ async fn emb_text<T: AsRef<std::path::Path>, F>(
     chunk_size: Option<usize>,
     overlap_ratio: Option<f32>,
     batch_size: Option<usize>,
+    backoff: &BatchBackoff,
+    buffer_size: usize,
     splitting_strategy: Option<SplittingStrategy>,
+    chunk_unit: Option<ChunkUnit>,
     semantic_encoder: Option<Arc<Embedder>>,
     adapter: Option<F>,
     use_ocr: bool,
+    metadata_config: Option<&MetadataConfig>,
+    document_pooling: Option<DocumentPooling>,
+    invalid_embeddings: InvalidEmbeddingPolicy,
+    warnings: Option<&Warnings>,
+    pdf_options: PdfTextOptions,
+    docx_options: DocxTextOptions,
+    json_options: &JsonTextOptions,
+    table_rows_per_chunk: usize,
+    streaming_threshold: u64,
 ) -> Result<Option<Vec<EmbedData>>>
 where
     F: Fn(Vec<EmbedData>),
 {
-    let text = TextLoader::extract_text(&file, use_ocr)?;
-    let textloader = TextLoader::new(chunk_size.unwrap_or(256), overlap_ratio.unwrap_or(0.0));
-    let chunks = textloader
-        .split_into_chunks(
-            &text,
-            splitting_strategy.unwrap_or(SplittingStrategy::Sentence),
-            semantic_encoder,
+    // Recorded alongside the model's own stages when it was loaded with timing on.
+    let timer = embedding_model.timer();
+    // Large text files come in one block at a time, so they are chunked and embedded as they
+    // are read.
+    let mut sections = timed(timer, Stage::Extraction, || {
+        TextLoader::stream_sections(
+            &file,
+            use_ocr,
+            &pdf_options,
+            &docx_options,
+            json_options,
+            table_rows_per_chunk,
+            streaming_threshold,
         )
-        .unwrap_or_default();
+    })?;
+    let textloader = TextLoader::with_chunk_unit(
+        chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE),
+        overlap_ratio.unwrap_or(DEFAULT_OVERLAP_RATIO),
+        chunk_unit,
+        embedding_model.tokenizer(),
+    )?;
+    let source = file.as_ref().to_string_lossy().to_string();
+    let mut metadata = TextLoader::get_metadata(&file).ok();
+    if let Some(metadata_config) = metadata_config {
+        // Files without metadata still get the namespace.
+        if metadata.is_some() || metadata_config.namespace.is_some() {
+            metadata_config.apply(metadata.get_or_insert_with(HashMap::new));
+        }
+    }
 
-    let metadata = TextLoader::get_metadata(file).ok();
+    let mut truncated = 0;
+    let mut pooler = document_pooling.map(DocumentPooler::new);
+    let mut pool = |embeddings: &mut [EmbedData]| {
+        truncated += count_truncated(embeddings);
+        if let Some(pooler) = pooler.as_mut() {
+            embeddings
+                .iter_mut()
+                .for_each(|embedding| pooler.add(embedding));
+        }
+    };
+
+    // The chunks not embedded yet, with the metadata of the section each comes from.
+    let mut chunks = Vec::new();
+    let mut chunk_metadata = Vec::new();
+    let mut chunk_count = 0;
+    let buffer_size = buffer_size.max(1);
+    while let Some(section) = timed(timer, Stage::Extraction, || sections.next()) {
+        let section = section?;
+        let section_chunks = timed(timer, Stage::Chunking, || {
+            textloader
+                .split_into_chunks(
+                    &section.text,
+                    splitting_strategy.unwrap_or(SplittingStrategy::Sentence),
+                    semantic_encoder.clone(),
+                )
+                .unwrap_or_default()
+        });
+        chunk_count += section_chunks.len();
+        chunk_metadata.extend(std::iter::repeat_n(section.metadata, section_chunks.len()));
+        chunks.extend(section_chunks);
+
+        // Hand the embeddings over every `buffer_size` chunks so large files are never held in
+        // memory all at once.
+        if let Some(adapter) = adapter.as_ref() {
+            while chunks.len() >= buffer_size {
+                let rest = chunks.split_off(buffer_size);
+                let rest_metadata = chunk_metadata.split_off(buffer_size);
+                let mut embeddings = embed_chunks(
+                    embedding_model,
+                    &std::mem::replace(&mut chunks, rest),
+                    &std::mem::replace(&mut chunk_metadata, rest_metadata),
+                    &metadata,
+                    batch_size,
+                    backoff,
+                    invalid_embeddings,
+                    warnings,
+                )
+                .await?;
+                pool(&mut embeddings);
+                adapter(embeddings);
+            }
+        }
+    }
+    if chunk_count == 0 {
+        warn(warnings, EmbedWarning::EmptyFile { file: source });
+        return Ok(adapter.is_none().then(Vec::new));
+    }
 
+    let mut embeddings = Vec::new();
+    if !chunks.is_empty() {
+        embeddings = embed_chunks(
+            embedding_model,
+            &chunks,
+            &chunk_metadata,
+            &metadata,
+            batch_size,
+            backoff,
+            invalid_embeddings,
+            warnings,
+        )
+        .await?;
+        pool(&mut embeddings);
+    }
+    let document = pooler.and_then(DocumentPooler::finish);
+    warn_truncated(warnings, source, truncated);
     if let Some(adapter) = adapter {
-        let encodings = embedding_model.embed(&chunks, batch_size).await.unwrap();
-        let embeddings = get_text_metadata(&Rc::new(encodings), &chunks, &metadata).unwrap();
-        adapter(embeddings);
+        if !embeddings.is_empty() {
+            adapter(embeddings);
+        }
+        if let Some(document) = document {
+            adapter(vec![document]);
+        }
         Ok(None)
     } else {
-        let encodings = embedding_model.embed(&chunks, batch_size).await.unwrap();
-        let embeddings = get_text_metadata(&Rc::new(encodings), &chunks, &metadata).unwrap();
-
+        embeddings.extend(document);
         Ok(Some(embeddings))
     }
 }
 
+/// Embeds chunks of a file, giving each the metadata of the file and of its section, and applies
+/// the [`InvalidEmbeddingPolicy`].
+#[allow(clippy::too_many_arguments)]
+async fn embed_chunks(
+    embedding_model: &TextEmbedder,
+    chunks: &[String],
+    chunk_metadata: &[HashMap<String, String>],
+    metadata: &Option<HashMap<String, String>>,
+    batch_size: Option<usize>,
+    backoff: &BatchBackoff,
+    invalid_embeddings: InvalidEmbeddingPolicy,
+    warnings: Option<&Warnings>,
+) -> Result<Vec<EmbedData>> {
+    let retry = |text: String| async move {
+        retry_embedding(embedding_model.embed(&[text], Some(1)).await)
+    };
+    let encodings =
+        embed_with_backoff_warnings(chunks, batch_size, backoff, warnings, |texts, b| {
+            embedding_model.embed(texts, Some(b))
+        })
+        .await?;
+    let mut embeddings = get_text_metadata(&encodings, chunks, metadata)?;
+    add_section_metadata(&mut embeddings, chunk_metadata);
+    let mut embeddings = guard_embeddings(embeddings, invalid_embeddings, warnings, retry).await?;
+    flag_truncated(embedding_model.truncation(), &mut embeddings);
+    Ok(embeddings)
+}
+
+/// Adds the metadata of the section each chunk comes from, `chunk_metadata` holding that of the
+/// chunks `embeddings` were made from.
+fn add_section_metadata(embeddings: &mut [EmbedData], chunk_metadata: &[HashMap<String, String>]) {
+    for (embedding, metadata) in embeddings.iter_mut().zip(chunk_metadata) {
+        if !metadata.is_empty() {
+            embedding
+                .metadata
+                .get_or_insert_with(HashMap::new)
+                .extend(metadata.clone());
+        }
+    }
+}
+
+/// The metadata of a file merged with that of one of its sections.
+fn section_metadata(
+    metadata: &Option<HashMap<String, String>>,
+    section: &TextSection,
+) -> Option<HashMap<String, String>> {
+    if section.metadata.is_empty() {
+        return metadata.clone();
+    }
+    let mut metadata = metadata.clone().unwrap_or_default();
+    metadata.extend(section.metadata.clone());
+    Some(metadata)
+}
+
+/// The embedding of the single chunk that [`guard_embeddings`] embeds again.
+fn retry_embedding(encodings: Result<Vec<EmbeddingResult>>) -> Result<EmbeddingResult> {
+    encodings?
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("No embedding returned for the retried chunk"))
+}
+
+/// The number of `embeddings` flagged by [`flag_truncated`].
+fn count_truncated(embeddings: &[EmbedData]) -> usize {
+    embeddings
+        .iter()
+        .filter(|embedding| {
+            embedding
+                .metadata
+                .as_ref()
+                .is_some_and(|metadata| metadata.contains_key(TRUNCATED_KEY))
+        })
+        .count()
+}
+
+fn warn_truncated(warnings: Option<&Warnings>, source: String, count: usize) {
+    if count > 0 {
+        warn(warnings, EmbedWarning::TruncatedChunks { source, count });
+    }
+}
+
 fn emb_image<T: AsRef<std::path::Path>>(
     image_path: T,
     embedding_model: &VisionEmbedder,
@@ -338,20 +1036,220 @@ pub async fn emb_audio<T: AsRef<std::path::Path>>(
     embedder: &Arc<Embedder>,
     text_embed_config: Option<&TextEmbedConfig>,
 ) -> Result<Option<Vec<EmbedData>>> {
-    let segments: Vec<audio_processor::Segment> = audio_decoder.process_audio(&audio_file).unwrap();
-    let embeddings = embed_audio(
-        embedder,
-        segments,
-        audio_file,
-        text_embed_config
-            .unwrap_or(&TextEmbedConfig::default())
-            .batch_size,
-    )
-    .await?;
+    let binding = TextEmbedConfig::default();
+    let config = text_embed_config.unwrap_or(&binding);
+    let segments: Vec<audio_processor::Segment> = audio_decoder.process_audio(&audio_file)?;
+    let mut embeddings = embed_audio(embedder, segments, audio_file, config.batch_size).await?;
+    config.apply_metadata_config(&mut embeddings);
 
     Ok(Some(embeddings))
 }
 
+/// Embeds audio that arrives in chunks, such as a microphone or a network stream, while it is
+/// transcribed.
+///
+/// `chunks` yields mono samples at
+/// [`SAMPLE_RATE`](file_processor::audio::audio_processor::SAMPLE_RATE). Every 30 seconds of audio
+/// is transcribed and embedded as soon as it is complete, and what is left is embedded when the
+/// stream ends. `source` is stored in the `file_name` metadata field. With an adapter, each batch of
+/// embeddings is passed to it as it is ready and `None` is returned.
+pub async fn embed_audio_stream<S, F>(
+    chunks: S,
+    source: &str,
+    audio_decoder: &mut AudioDecoderModel,
+    embedder: &Embedder,
+    text_embed_config: Option<&TextEmbedConfig>,
+    adapter: Option<F>,
+) -> Result<Option<Vec<EmbedData>>>
+where
+    S: futures::Stream<Item = Vec<f32>>,
+    F: Fn(Vec<EmbedData>),
+{
+    let binding = TextEmbedConfig::default();
+    let config = text_embed_config.unwrap_or(&binding);
+    let mut stream = audio_processor::AudioStream::new();
+    let mut all_embeddings = Vec::new();
+    let mut emit = |mut embeddings: Vec<EmbedData>| {
+        config.apply_metadata_config(&mut embeddings);
+        match &adapter {
+            Some(adapter) => adapter(embeddings),
+            None => all_embeddings.extend(embeddings),
+        }
+    };
+
+    futures::pin_mut!(chunks);
+    while let Some(chunk) = chunks.next().await {
+        let segments = stream.push(audio_decoder, &chunk)?;
+        if !segments.is_empty() {
+            emit(embed_audio(embedder, segments, source, config.batch_size).await?);
+        }
+    }
+    let segments = stream.finish(audio_decoder)?;
+    if !segments.is_empty() {
+        emit(embed_audio(embedder, segments, source, config.batch_size).await?);
+    }
+
+    Ok(adapter.is_none().then_some(all_embeddings))
+}
+
+/// Transcribes and embeds the audio files in a directory.
+///
+/// Files are decoded in parallel by [`AudioDecoderModel::num_workers`] threads, each with its own
+/// copy of `audio_decoder`. The segments of each file are embedded as soon as it is decoded and
+/// passed to the adapter if one is given, in which case `None` is returned. Embedding pauses while
+/// the adapter is busy, and the first error of the adapter stops the run. Files that fail to
+/// decode or embed are logged and skipped.
+///
+/// # Example
+///
+/// ```rust
+/// use embed_anything::embed_audio_directory;
+///
+/// let audio_decoder = AudioDecoderModel::from_pretrained(Some("openai/whisper-tiny.en"), Some("main"), "tiny-en", false).unwrap();
+/// let embedder = Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None).unwrap();
+/// let embeddings = embed_audio_directory(PathBuf::from("recordings"), &audio_decoder, &embedder, None, None::<fn(Vec<EmbedData>)>).await.unwrap();
+/// ```
+#[cfg(feature = "stream")]
+pub async fn embed_audio_directory<S>(
+    directory: PathBuf,
+    audio_decoder: &AudioDecoderModel,
+    embedder: &Embedder,
+    text_embed_config: Option<&TextEmbedConfig>,
+    adapter: Option<S>,
+) -> Result<Option<Vec<EmbedData>>>
+where
+    S: EmbeddingSink,
+{
+    let binding = TextEmbedConfig::default();
+    let config = text_embed_config.unwrap_or(&binding);
+    audio_decoder.decoder_config.validate()?;
+    let files = FileParser::new()
+        .with_walk_options(config.walk)
+        .get_audio_files(&directory)?;
+    let num_workers = audio_decoder.num_workers().min(files.len()).max(1);
+    let pb = progress::progress_bar(files.len() as u64);
+
+    let queue = Arc::new(std::sync::Mutex::new(files.into_iter()));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let workers = (0..num_workers)
+        .map(|_| {
+            let mut decoder = audio_decoder.clone();
+            let queue = queue.clone();
+            let tx = tx.clone();
+            std::thread::spawn(move || loop {
+                let Some(file) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let segments = decoder.process_audio(&file);
+                if tx.send((file, segments)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(tx);
+
+    // Bounded, so that embedding pauses while the adapter catches up.
+    let (sink_tx, sink_rx) = futures::channel::mpsc::channel(SINK_QUEUE_SIZE);
+    let mut sink_tx = adapter.is_some().then_some(sink_tx);
+    let with_adapter = adapter.is_some();
+    let embed = async move {
+        let mut all_embeddings = Vec::new();
+        while let Some((file, segments)) = rx.recv().await {
+            pb.inc(1);
+            let segments = match segments {
+                Ok(segments) if segments.is_empty() => continue,
+                Ok(segments) => segments,
+                Err(e) => {
+                    tracing::error!("Error decoding {}: {:?}", file, e);
+                    continue;
+                }
+            };
+            match embed_audio(embedder, segments, &file, config.batch_size).await {
+                Ok(mut embeddings) => {
+                    config.apply_metadata_config(&mut embeddings);
+                    match sink_tx.as_mut() {
+                        Some(sink_tx) => {
+                            // The channel is closed once the sink failed, which ends the run.
+                            if sink_tx.send(embeddings).await.is_err() {
+                                break;
+                            }
+                        }
+                        None => all_embeddings.extend(embeddings),
+                    }
+                }
+                Err(e) => tracing::error!("Error embedding {}: {:?}", file, e),
+            }
+        }
+        all_embeddings
+    };
+    let (all_embeddings, sink) = futures::join!(embed, drain_to_sink(sink_rx, adapter));
+
+    // The receiver is gone, so the workers stop at their next file.
+    for worker in workers {
+        if worker.join().is_err() {
+            tracing::error!("Audio decoding worker panicked");
+        }
+    }
+    sink?;
+
+    Ok((!with_adapter).then_some(all_embeddings))
+}
+
+/// Hands the batches received on `batches` to `adapter` until the channel closes. The first error
+/// of the adapter drops `batches`, so the pipeline sending to it stops, and is returned.
+#[cfg(feature = "stream")]
+async fn drain_to_sink<S: EmbeddingSink>(
+    mut batches: futures::channel::mpsc::Receiver<Vec<EmbedData>>,
+    adapter: Option<S>,
+) -> Result<()> {
+    if let Some(mut adapter) = adapter {
+        while let Some(batch) = batches.next().await {
+            adapter.upsert(batch).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Embeds a single image given as an `http(s)` URL, a `file://` URL or a local path.
+///
+/// Remote images are downloaded to a temporary file first. Downloads larger than
+/// `config.max_download_bytes` or slower than `config.download_timeout` are rejected. The source is
+/// stored in the `image_url` metadata field.
+///
+/// # Example
+///
+/// ```no_run
+/// use embed_anything::embed_image_url;
+/// use embed_anything::embeddings::embed::Embedder;
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let embedder = Embedder::from_pretrained_hf("clip", "openai/clip-vit-base-patch16", None)?;
+/// let embedding = embed_image_url("https://example.com/cat.jpg", &embedder, None).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn embed_image_url(
+    url: &str,
+    embedder: &Embedder,
+    config: Option<&ImageEmbedConfig>,
+) -> Result<EmbedData> {
+    let binding = ImageEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let image = file_processor::image_url::resolve_image(
+        url,
+        config
+            .max_download_bytes
+            .unwrap_or(file_processor::image_url::DEFAULT_MAX_IMAGE_BYTES),
+        config
+            .download_timeout
+            .unwrap_or(file_processor::image_url::DEFAULT_IMAGE_TIMEOUT),
+    )
+    .await?;
+    let metadata = HashMap::from([("image_url".to_string(), url.to_string())]);
+    embedder.embed_image(&image.path, Some(metadata))
+}
+
 /// Embeds images in a directory using the specified embedding model.
 ///
 /// # Arguments
@@ -359,7 +1257,9 @@ pub async fn emb_audio<T: AsRef<std::path::Path>>(
 /// * `directory` - A `PathBuf` representing the directory containing the images to embed.
 /// * `embedder` - A reference to the embedding model to use.
 /// * `config` - An optional `ImageEmbedConfig` object specifying the configuration for the embedding model. Default buffer size is 100.
-/// * `adapter` - An optional callback function to handle the embeddings.
+/// * `adapter` - An optional callback function or [`EmbeddingSink`] to handle the embeddings. The
+///   embedding of further images pauses while the sink is busy, and the first error of the sink
+///   stops the run.
 ///
 /// # Returns
 /// An `Option` containing a vector of `EmbedData` objects representing the embeddings of the images, or `None` if an adapter is used.
@@ -380,35 +1280,30 @@ pub async fn emb_audio<T: AsRef<std::path::Path>>(
 /// ```
 /// This will output the embeddings of the images in the specified directory using the specified embedding model.
 ///
-pub async fn embed_image_directory<T: EmbedImage + Send + Sync + 'static, F>(
+#[cfg(feature = "stream")]
+pub async fn embed_image_directory<T: EmbedImage + Send + Sync + 'static, S>(
     directory: PathBuf,
     embedding_model: &Arc<T>,
     config: Option<&ImageEmbedConfig>,
-    adapter: Option<F>,
+    mut adapter: Option<S>,
 ) -> Result<Option<Vec<EmbedData>>>
 where
-    F: Fn(Vec<EmbedData>),
+    S: EmbeddingSink,
 {
-    let mut file_parser = FileParser::new();
+    let binding = ImageEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let mut file_parser = FileParser::new().with_walk_options(config.walk);
     file_parser.get_image_paths(&directory).unwrap();
 
-    let buffer_size = config
-        .unwrap_or(&ImageEmbedConfig::default())
-        .buffer_size
-        .unwrap_or(100);
+    let buffer_size = config.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE);
 
     let (tx, mut rx) = mpsc::unbounded_channel();
-    let (collector_tx, mut collector_rx) = mpsc::unbounded_channel();
+    // Bounded, so that embedding pauses while the adapter catches up.
+    let (collector_tx, mut collector_rx) = mpsc::channel(SINK_QUEUE_SIZE);
 
     let embedder = embedding_model.clone();
 
-    let pb = indicatif::ProgressBar::new(file_parser.files.len() as u64);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )
-        .unwrap(),
-    );
+    let pb = progress::progress_bar(file_parser.files.len() as u64);
 
     let processing_task = tokio::spawn({
         async move {
@@ -436,11 +1331,11 @@ where
 
                             pb.inc(new_len - old_len);
 
-                            if let Err(e) = collector_tx.send(embeddings) {
-                                eprintln!("Error sending embeddings to collector: {:?}", e);
+                            if let Err(e) = collector_tx.send(embeddings).await {
+                                tracing::error!("Error sending embeddings to collector: {:?}", e);
                             }
                         }
-                        Err(e) => eprintln!("Error processing images: {:?}", e),
+                        Err(e) => tracing::error!("Error processing images: {:?}", e),
                     }
 
                     image_buffer.clear();
@@ -463,11 +1358,11 @@ where
 
                         pb.inc(new_len - old_len);
 
-                        if let Err(e) = collector_tx.send(embeddings) {
-                            eprintln!("Error sending embeddings to collector: {:?}", e);
+                        if let Err(e) = collector_tx.send(embeddings).await {
+                            tracing::error!("Error sending embeddings to collector: {:?}", e);
                         }
                     }
-                    Err(e) => eprintln!("Error processing images: {:?}", e),
+                    Err(e) => tracing::error!("Error processing images: {:?}", e),
                 }
             }
         }
@@ -475,7 +1370,7 @@ where
 
     file_parser.files.par_iter().for_each(|image| {
         if let Err(e) = tx.send(image.clone()) {
-            eprintln!("Error sending image: {:?}", e);
+            tracing::error!("Error sending image: {:?}", e);
         }
     });
 
@@ -483,8 +1378,11 @@ where
 
     let mut all_embeddings = Vec::new();
     while let Some(embeddings) = collector_rx.recv().await {
-        if let Some(adapter) = &adapter {
-            adapter(embeddings.to_vec());
+        if let Some(adapter) = adapter.as_mut() {
+            if let Err(e) = adapter.upsert(embeddings.to_vec()).await {
+                processing_task.abort();
+                return Err(e);
+            }
         } else {
             all_embeddings.extend(embeddings.to_vec());
         }
@@ -500,6 +1398,7 @@ where
     }
 }
 
+#[cfg(feature = "stream")]
 async fn process_images<E: EmbedImage>(
     image_buffer: &[String],
     embedder: Arc<E>,
@@ -508,6 +1407,9 @@ async fn process_images<E: EmbedImage>(
     Ok(Arc::new(embeddings))
 }
 
+/// Buffers of embeddings a pipeline holds ready while its adapter is busy.
+const SINK_QUEUE_SIZE: usize = 2;
+
 /// Embeds text from files in a directory using the specified embedding model.
 ///
 /// # Arguments
@@ -516,7 +1418,9 @@ async fn process_images<E: EmbedImage>(
 /// * `embedder` - A reference to the embedding model to use.
 /// * `extensions` - An optional vector of strings representing the file extensions to consider for embedding. If `None`, all files in the directory will be considered.
 /// * `config` - An optional `TextEmbedConfig` object specifying the configuration for the embedding model.
-/// * `adapter` - An optional callback function to handle the embeddings.
+/// * `adapter` - An optional callback function or [`EmbeddingSink`] to handle the embeddings. The
+///   embedding of further chunks pauses while the sink is busy, and the first error of the sink
+///   stops the run.
 ///
 /// # Returns
 /// An `Option` containing a vector of `EmbedData` objects representing the embeddings of the files, or `None` if an adapter is used.
@@ -538,39 +1442,45 @@ async fn process_images<E: EmbedImage>(
 /// let embeddings = embed_directory_stream(directory, &embedder, extensions, config, None).await.unwrap();
 /// ```
 /// This will output the embeddings of the files in the specified directory using the specified embedding model.
-pub async fn embed_directory_stream<F>(
+#[cfg(feature = "stream")]
+pub async fn embed_directory_stream<S>(
     directory: PathBuf,
     embedder: &Arc<Embedder>,
     extensions: Option<Vec<String>>,
     config: Option<&TextEmbedConfig>,
-    adapter: Option<F>,
+    mut adapter: Option<S>,
 ) -> Result<Option<Vec<EmbedData>>>
 where
-    F: Fn(Vec<EmbedData>),
+    S: EmbeddingSink,
 {
-    println!("Embedding directory: {:?}", directory);
+    tracing::info!("Embedding directory: {:?}", directory);
 
     let binding = TextEmbedConfig::default();
     let config = config.unwrap_or(&binding);
-    let chunk_size = config.chunk_size.unwrap_or(binding.chunk_size.unwrap());
-    let buffer_size = config.buffer_size.unwrap_or(binding.buffer_size.unwrap());
+    let chunk_size = config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+    let buffer_size = config.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE);
     let batch_size = config.batch_size;
+    let length_sorted = config.length_sorted_batching.unwrap_or(false);
+    let invalid_embeddings = config.invalid_embeddings;
     let use_ocr = config.use_ocr.unwrap_or(false);
-    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
-    let mut file_parser = FileParser::new();
+    let overlap_ratio = config.effective_overlap_ratio();
+    let textloader = TextLoader::with_chunk_unit(
+        chunk_size,
+        overlap_ratio,
+        config.chunk_unit,
+        embedder.tokenizer(),
+    )?;
+    let mut file_parser = FileParser::new().with_walk_options(config.walk);
     file_parser.get_text_files(&directory, extensions)?;
     let files = file_parser.files.clone();
     let (tx, mut rx) = mpsc::unbounded_channel();
-    let (collector_tx, mut collector_rx) = mpsc::unbounded_channel();
+    // Bounded, so that embedding pauses while the adapter catches up.
+    let (collector_tx, mut collector_rx) = mpsc::channel(SINK_QUEUE_SIZE);
 
     let embedder = embedder.clone();
-    let pb = indicatif::ProgressBar::new(files.len() as u64);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )
-        .unwrap(),
-    );
+    let backoff = config.backoff.clone();
+    let warnings = config.warnings.clone();
+    let pb = progress::progress_bar(files.len() as u64);
 
     let processing_task = tokio::spawn({
         async move {
@@ -584,8 +1494,17 @@ where
                 metadata_buffer.push(metadata);
 
                 if chunk_buffer.len() == buffer_size {
-                    match process_chunks(&chunk_buffer, &metadata_buffer, &embedder, batch_size)
-                        .await
+                    match process_chunks(
+                        &chunk_buffer,
+                        &metadata_buffer,
+                        &embedder,
+                        batch_size,
+                        &backoff,
+                        length_sorted,
+                        invalid_embeddings,
+                        warnings.as_ref(),
+                    )
+                    .await
                     {
                         Ok(embeddings) => {
                             let files = embeddings
@@ -601,11 +1520,11 @@ where
 
                             pb.inc(new_len - old_len);
 
-                            if let Err(e) = collector_tx.send(embeddings) {
-                                eprintln!("Error sending embeddings to collector: {:?}", e);
+                            if let Err(e) = collector_tx.send(embeddings).await {
+                                tracing::error!("Error sending embeddings to collector: {:?}", e);
                             }
                         }
-                        Err(e) => eprintln!("Error processing chunks: {:?}", e),
+                        Err(e) => tracing::error!("Error processing chunks: {:?}", e),
                     }
 
                     chunk_buffer.clear();
@@ -615,7 +1534,18 @@ where
 
             // Process any remaining chunks
             if !chunk_buffer.is_empty() {
-                match process_chunks(&chunk_buffer, &metadata_buffer, &embedder, batch_size).await {
+                match process_chunks(
+                    &chunk_buffer,
+                    &metadata_buffer,
+                    &embedder,
+                    batch_size,
+                    &backoff,
+                    length_sorted,
+                    invalid_embeddings,
+                    warnings.as_ref(),
+                )
+                .await
+                {
                     Ok(embeddings) => {
                         let files = embeddings
                             .iter()
@@ -629,55 +1559,121 @@ where
 
                         pb.inc(new_len - old_len);
 
-                        if let Err(e) = collector_tx.send(embeddings) {
-                            eprintln!("Error sending embeddings to collector: {:?}", e);
+                        if let Err(e) = collector_tx.send(embeddings).await {
+                            tracing::error!("Error sending embeddings to collector: {:?}", e);
                         }
                     }
-                    Err(e) => eprintln!("Error processing chunks: {:?}", e),
+                    Err(e) => tracing::error!("Error processing chunks: {:?}", e),
                 }
             }
         }
     });
 
-    let textloader = TextLoader::new(chunk_size, overlap_ratio);
+    let warnings = config.warnings.as_ref();
 
     file_parser.files.iter().for_each(|file| {
-        let text = match TextLoader::extract_text(file, use_ocr) {
-            Ok(text) => text,   
-            Err(_) => {
+        let sections = match TextLoader::stream_sections(
+            file,
+            use_ocr,
+            &config.pdf,
+            &config.docx,
+            &config.json,
+            config
+                .table_rows_per_chunk
+                .unwrap_or(DEFAULT_TABLE_ROWS_PER_CHUNK),
+            config
+                .streaming_threshold
+                .unwrap_or(DEFAULT_STREAMING_THRESHOLD),
+        ) {
+            Ok(sections) => sections,
+            Err(e) => {
+                let (file, reason) = (file.clone(), e.to_string());
+                warn(warnings, EmbedWarning::SkippedFile { file, reason });
                 return;
             }
         };
-        let chunks = textloader
-            .split_into_chunks(&text, SplittingStrategy::Sentence, None)
-            .unwrap_or_else(|| vec![text.clone()])
-            .into_iter()
-            .filter(|chunk| !chunk.trim().is_empty())
-            .collect::<Vec<_>>();
-        if chunks.is_empty() {
-            return;
-        }
-        let metadata = TextLoader::get_metadata(file).unwrap();
-        for chunk in chunks {
-            if let Err(e) = tx.send((chunk, Some(metadata.clone()))) {
-                eprintln!("Error sending chunk: {:?}", e);
+        let metadata = Some(TextLoader::get_metadata(file).unwrap());
+        let mut chunk_count = 0;
+        for section in sections {
+            let section = match section {
+                Ok(section) => section,
+                Err(e) => {
+                    let (file, reason) = (file.clone(), e.to_string());
+                    warn(warnings, EmbedWarning::SkippedFile { file, reason });
+                    return;
+                }
+            };
+            let chunks = textloader
+                .split_into_chunks(&section.text, SplittingStrategy::Sentence, None)
+                .unwrap_or_else(|| vec![section.text.clone()])
+                .into_iter()
+                .filter(|chunk| !chunk.trim().is_empty())
+                .collect::<Vec<_>>();
+            chunk_count += chunks.len();
+            for chunk in chunks {
+                if let Err(e) = tx.send((chunk, section_metadata(&metadata, &section))) {
+                    tracing::error!("Error sending chunk: {:?}", e);
+                }
             }
         }
+        if chunk_count == 0 {
+            let file = file.clone();
+            warn(warnings, EmbedWarning::EmptyFile { file });
+        }
     });
 
     drop(tx);
 
     let mut all_embeddings = Vec::new();
+    let mut poolers: HashMap<String, DocumentPooler> = HashMap::new();
     while let Some(embeddings) = collector_rx.recv().await {
-        if let Some(adapter) = &adapter {
-            adapter(embeddings.to_vec());
+        let mut embeddings = embeddings.to_vec();
+        let file_names = embeddings
+            .iter()
+            .map(|embedding| {
+                embedding
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.get("file_name"))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        // Applied here rather than to the file metadata, since progress is tracked by file name.
+        config.apply_metadata_config(&mut embeddings);
+        if let Some(pooling) = config.document_pooling {
+            for (embedding, file_name) in embeddings.iter_mut().zip(file_names) {
+                poolers
+                    .entry(file_name)
+                    .or_insert_with(|| DocumentPooler::new(pooling))
+                    .add(embedding);
+            }
+        }
+        if let Some(adapter) = adapter.as_mut() {
+            if let Err(e) = adapter.upsert(embeddings).await {
+                processing_task.abort();
+                return Err(e);
+            }
         } else {
-            all_embeddings.extend(embeddings.to_vec());
+            all_embeddings.extend(embeddings);
         }
     }
     // Wait for the spawned task to complete
     processing_task.await.unwrap();
 
+    // Chunks of a file can arrive in several buffers, so documents are pooled once all are in.
+    let documents = poolers
+        .into_values()
+        .filter_map(DocumentPooler::finish)
+        .collect::<Vec<_>>();
+    if !documents.is_empty() {
+        if let Some(adapter) = adapter.as_mut() {
+            adapter.upsert(documents).await?;
+        } else {
+            all_embeddings.extend(documents);
+        }
+    }
+
     if adapter.is_some() {
         Ok(None)
     } else {
@@ -685,13 +1681,177 @@ where
     }
 }
 
+/// The models [`embed_mixed_directory`] sends each kind of file to. Files of a kind without a
+/// model are skipped.
+#[derive(Clone, Default)]
+pub struct ModalityEmbedders {
+    /// Embeds the text of documents (pdf, md, txt, docx, pptx, epub, json, jsonl, csv, xlsx).
+    pub text: Option<Arc<Embedder>>,
+    /// Embeds images.
+    pub image: Option<Arc<Embedder>>,
+    /// Transcribes audio files, whose segments are embedded with the text embedder.
+    pub audio: Option<(AudioDecoderModel, Arc<Embedder>)>,
+}
+
+impl ModalityEmbedders {
+    pub fn with_text(mut self, embedder: Arc<Embedder>) -> Self {
+        self.text = Some(embedder);
+        self
+    }
+
+    pub fn with_image(mut self, embedder: Arc<Embedder>) -> Self {
+        self.image = Some(embedder);
+        self
+    }
+
+    pub fn with_audio(mut self, audio_decoder: AudioDecoderModel, embedder: Arc<Embedder>) -> Self {
+        self.audio = Some((audio_decoder, embedder));
+        self
+    }
+}
+
+/// Embeds a directory of mixed files in one call, sending documents, images and audio to the
+/// matching embedder of `embedders`.
+///
+/// Each kind goes through its usual pipeline: [`embed_directory_stream`],
+/// [`embed_image_directory`] and [`embed_audio_directory`]. The `modality` metadata field is set
+/// to `text`, `image` or `audio`, since the embedders usually have different dimensions.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// use embed_anything::embeddings::embed::{EmbedData, Embedder};
+/// use embed_anything::{embed_mixed_directory, ModalityEmbedders};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let embedders = ModalityEmbedders::default()
+///     .with_text(Arc::new(Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)?))
+///     .with_image(Arc::new(Embedder::from_pretrained_hf("clip", "openai/clip-vit-base-patch16", None)?));
+/// let embeddings = embed_mixed_directory(PathBuf::from("test_files"), &embedders, None, None, None::<fn(Vec<EmbedData>)>).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "stream")]
+pub async fn embed_mixed_directory<S>(
+    directory: PathBuf,
+    embedders: &ModalityEmbedders,
+    config: Option<&TextEmbedConfig>,
+    image_config: Option<&ImageEmbedConfig>,
+    mut adapter: Option<S>,
+) -> Result<Option<Vec<EmbedData>>>
+where
+    S: EmbeddingSink,
+{
+    let mut all_embeddings = Vec::new();
+    if let Some(embedder) = &embedders.text {
+        let sink = ModalitySink::wrap(&mut adapter, "text");
+        let embeddings =
+            embed_directory_stream(directory.clone(), embedder, None, config, sink).await?;
+        all_embeddings.extend(embeddings.map(|e| (e, "text")));
+    }
+    if let Some(embedder) = &embedders.image {
+        let sink = ModalitySink::wrap(&mut adapter, "image");
+        let embeddings =
+            embed_image_directory(directory.clone(), embedder, image_config, sink).await?;
+        all_embeddings.extend(embeddings.map(|e| (e, "image")));
+    }
+    if let Some((audio_decoder, embedder)) = &embedders.audio {
+        let sink = ModalitySink::wrap(&mut adapter, "audio");
+        let embeddings =
+            embed_audio_directory(directory, audio_decoder, embedder, config, sink).await?;
+        all_embeddings.extend(embeddings.map(|e| (e, "audio")));
+    }
+
+    if adapter.is_some() {
+        return Ok(None);
+    }
+    Ok(Some(
+        all_embeddings
+            .into_iter()
+            .flat_map(|(mut embeddings, modality)| {
+                tag_modality(&mut embeddings, modality);
+                embeddings
+            })
+            .collect(),
+    ))
+}
+
+/// Sets the `modality` of the batches [`embed_mixed_directory`] hands to its adapter.
+#[cfg(feature = "stream")]
+struct ModalitySink<'a, S> {
+    sink: &'a mut S,
+    modality: &'static str,
+}
+
+#[cfg(feature = "stream")]
+impl<'a, S: EmbeddingSink> ModalitySink<'a, S> {
+    fn wrap(adapter: &'a mut Option<S>, modality: &'static str) -> Option<Self> {
+        adapter.as_mut().map(|sink| Self { sink, modality })
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<S: EmbeddingSink> EmbeddingSink for ModalitySink<'_, S> {
+    async fn upsert(&mut self, mut batch: Vec<EmbedData>) -> anyhow::Result<()> {
+        tag_modality(&mut batch, self.modality);
+        self.sink.upsert(batch).await
+    }
+}
+
+fn tag_modality(embeddings: &mut [EmbedData], modality: &str) {
+    for embedding in embeddings {
+        embedding
+            .metadata
+            .get_or_insert_with(HashMap::new)
+            .insert("modality".to_string(), modality.to_string());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn process_chunks(
     chunks: &Vec<String>,
     metadata: &Vec<Option<HashMap<String, String>>>,
     embedding_model: &Arc<Embedder>,
     batch_size: Option<usize>,
+    backoff: &BatchBackoff,
+    length_sorted: bool,
+    invalid_embeddings: InvalidEmbeddingPolicy,
+    warnings: Option<&Warnings>,
 ) -> Result<Arc<Vec<EmbedData>>> {
-    let encodings = embedding_model.embed(chunks, batch_size).await?;
+    let encodings = if length_sorted {
+        // Chunks are sorted by their token length under the model's tokenizer. Cloud models
+        // expose no tokenizer, so the character count stands in for it.
+        let tokenizer = embedding_model.tokenizer();
+        let mut order = (0..chunks.len()).collect::<Vec<_>>();
+        order.sort_by_cached_key(|&index| {
+            tokenizer
+                .and_then(|tokenizer| tokenizer.encode(chunks[index].as_str(), false).ok())
+                .map_or_else(|| chunks[index].chars().count(), |encoding| encoding.len())
+        });
+        let sorted = order
+            .iter()
+            .map(|&index| chunks[index].clone())
+            .collect::<Vec<_>>();
+        let sorted_encodings =
+            embed_with_backoff_warnings(&sorted, batch_size, backoff, warnings, |texts, b| {
+                embedding_model.embed(texts, Some(b))
+            })
+            .await?;
+
+        let mut encodings = vec![None; chunks.len()];
+        for (index, encoding) in order.into_iter().zip(sorted_encodings) {
+            encodings[index] = Some(encoding);
+        }
+        encodings.into_iter().flatten().collect()
+    } else {
+        embed_with_backoff_warnings(chunks, batch_size, backoff, warnings, |texts, b| {
+            embedding_model.embed(texts, Some(b))
+        })
+        .await?
+    };
 
     // zip encodings with chunks and metadata
     let embeddings = encodings
@@ -702,5 +1862,13 @@ pub async fn process_chunks(
             EmbedData::new(encoding.clone(), Some(chunk.clone()), metadata.clone())
         })
         .collect::<Vec<_>>();
+    let mut embeddings = guard_embeddings(
+        embeddings,
+        invalid_embeddings,
+        warnings,
+        |text| async move { retry_embedding(embedding_model.embed(&[text], Some(1)).await) },
+    )
+    .await?;
+    flag_truncated(embedding_model.truncation(), &mut embeddings);
     Ok(Arc::new(embeddings))
 }
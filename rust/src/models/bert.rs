@@ -487,7 +487,8 @@ impl BertModel {
             None => input_ids.ones_like()?,
         };
         // https://github.com/huggingface/transformers/blob/6eedfa6dd15dc1e22a55ae036f681914e5a0d9a1/src/transformers/models/bert/modeling_bert.py#L995
-        let attention_mask = get_extended_attention_mask(&attention_mask, DType::F32)?;
+        let attention_mask =
+            get_extended_attention_mask(&attention_mask, embedding_output.dtype())?;
         let sequence_output = self.encoder.forward(&embedding_output, &attention_mask)?;
         Ok(sequence_output)
     }
@@ -499,10 +500,16 @@ fn get_extended_attention_mask(attention_mask: &Tensor, dtype: DType) -> Result<
         2 => attention_mask.unsqueeze(1)?.unsqueeze(1)?,
         _ => candle_core::bail!("Wrong shape for input_ids or attention_mask"),
     };
-    let attention_mask = attention_mask.to_dtype(dtype)?;
+    let attention_mask = attention_mask.to_dtype(DType::F32)?;
     // torch.finfo(dtype).min
+    let min = match dtype {
+        DType::F16 => half::f16::MIN.to_f32(),
+        DType::BF16 => half::bf16::MIN.to_f32(),
+        _ => f32::MIN,
+    };
     (attention_mask.ones_like()? - &attention_mask)?
-        .broadcast_mul(&Tensor::try_from(f32::MIN)?.to_device(attention_mask.device())?)
+        .broadcast_mul(&Tensor::try_from(min)?.to_device(attention_mask.device())?)?
+        .to_dtype(dtype)
 }
 
 //https://github.com/huggingface/transformers/blob/1bd604d11c405dfb8b78bda4062d88fc75c17de0/src/transformers/models/bert/modeling_bert.py#L752-L766
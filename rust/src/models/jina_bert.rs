@@ -129,12 +129,13 @@ struct BertSelfAttention {
     value: Linear,
     num_attention_heads: usize,
     attention_head_size: usize,
+    use_flash_attn: bool,
     span: tracing::Span,
     span_softmax: tracing::Span,
 }
 
 impl BertSelfAttention {
-    fn new(vb: VarBuilder, cfg: &Config) -> Result<Self> {
+    fn new(vb: VarBuilder, cfg: &Config, use_flash_attn: bool) -> Result<Self> {
         let attention_head_size = cfg.hidden_size / cfg.num_attention_heads;
         let all_head_size = cfg.num_attention_heads * attention_head_size;
         let hidden_size = cfg.hidden_size;
@@ -147,6 +148,7 @@ impl BertSelfAttention {
             value,
             num_attention_heads: cfg.num_attention_heads,
             attention_head_size,
+            use_flash_attn,
             span: tracing::span!(tracing::Level::TRACE, "self-attn"),
             span_softmax: tracing::span!(tracing::Level::TRACE, "softmax"),
         })
@@ -160,12 +162,28 @@ impl BertSelfAttention {
         xs.reshape(x_shape)?.transpose(1, 2)?.contiguous()
     }
 
+    /// `bias` is the ALiBi bias matrix, or the per-head ALiBi slopes when flash attention is used.
     fn forward(&self, xs: &Tensor, bias: &Tensor) -> Result<Tensor> {
         let _enter = self.span.enter();
         let query_layer = self.query.forward(xs)?;
         let key_layer = self.key.forward(xs)?;
         let value_layer = self.value.forward(xs)?;
 
+        if self.use_flash_attn {
+            // flash-attn expects (b_sz, seq_len, nheads, head_dim)
+            let mut x_shape = xs.dims().to_vec();
+            x_shape.pop();
+            x_shape.push(self.num_attention_heads);
+            x_shape.push(self.attention_head_size);
+            let query_layer = query_layer.reshape(x_shape.as_slice())?;
+            let key_layer = key_layer.reshape(x_shape.as_slice())?;
+            let value_layer = value_layer.reshape(x_shape.as_slice())?;
+            let scale = 1f32 / (self.attention_head_size as f32).sqrt();
+            let context_layer =
+                flash_attn_alibi(&query_layer, &key_layer, &value_layer, bias, scale)?;
+            return context_layer.flatten_from(D::Minus2);
+        }
+
         let query_layer = self.transpose_for_scores(&query_layer)?;
         let key_layer = self.transpose_for_scores(&key_layer)?;
         let value_layer = self.transpose_for_scores(&value_layer)?;
@@ -184,6 +202,22 @@ impl BertSelfAttention {
     }
 }
 
+#[cfg(feature = "flash-attn")]
+fn flash_attn_alibi(
+    q: &Tensor,
+    k: &Tensor,
+    v: &Tensor,
+    alibi_slopes: &Tensor,
+    softmax_scale: f32,
+) -> Result<Tensor> {
+    candle_flash_attn::flash_attn_alibi(q, k, v, alibi_slopes, softmax_scale, false)
+}
+
+#[cfg(not(feature = "flash-attn"))]
+fn flash_attn_alibi(_: &Tensor, _: &Tensor, _: &Tensor, _: &Tensor, _: f32) -> Result<Tensor> {
+    candle_core::bail!("compile with '--features flash-attn'")
+}
+
 #[derive(Clone, Debug)]
 struct BertSelfOutput {
     dense: Linear,
@@ -217,8 +251,8 @@ struct BertAttention {
 }
 
 impl BertAttention {
-    fn new(vb: VarBuilder, cfg: &Config) -> Result<Self> {
-        let self_attention = BertSelfAttention::new(vb.pp("self"), cfg)?;
+    fn new(vb: VarBuilder, cfg: &Config, use_flash_attn: bool) -> Result<Self> {
+        let self_attention = BertSelfAttention::new(vb.pp("self"), cfg, use_flash_attn)?;
         let self_output = BertSelfOutput::new(vb.pp("output"), cfg)?;
         Ok(Self {
             self_attention,
@@ -283,8 +317,8 @@ struct BertLayer {
 }
 
 impl BertLayer {
-    fn new(vb: VarBuilder, cfg: &Config) -> Result<Self> {
-        let attention = BertAttention::new(vb.pp("attention"), cfg)?;
+    fn new(vb: VarBuilder, cfg: &Config, use_flash_attn: bool) -> Result<Self> {
+        let attention = BertAttention::new(vb.pp("attention"), cfg, use_flash_attn)?;
         let mlp = BertGLUMLP::new(vb.pp("mlp"), cfg)?;
         Ok(Self {
             attention,
@@ -299,23 +333,16 @@ impl BertLayer {
     }
 }
 
-fn build_alibi_bias(cfg: &Config) -> Result<Tensor> {
+fn alibi_slopes(cfg: &Config) -> Vec<f32> {
     let n_heads = cfg.num_attention_heads;
-    let seq_len = cfg.max_position_embeddings;
-    let alibi_bias = Tensor::arange(0, seq_len as i64, &Device::Cpu)?.to_dtype(DType::F32)?;
-    let alibi_bias = {
-        let a1 = alibi_bias.reshape((1, seq_len))?;
-        let a2 = alibi_bias.reshape((seq_len, 1))?;
-        a1.broadcast_sub(&a2)?.abs()?.broadcast_left(n_heads)?
-    };
     let mut n_heads2 = 1;
     while n_heads2 < n_heads {
         n_heads2 *= 2
     }
     let slopes = (1..=n_heads2)
-        .map(|v| -1f32 / 2f32.powf((v * 8) as f32 / n_heads2 as f32))
+        .map(|v| 1f32 / 2f32.powf((v * 8) as f32 / n_heads2 as f32))
         .collect::<Vec<_>>();
-    let slopes = if n_heads2 == n_heads {
+    if n_heads2 == n_heads {
         slopes
     } else {
         slopes
@@ -326,7 +353,19 @@ fn build_alibi_bias(cfg: &Config) -> Result<Tensor> {
             .take(n_heads)
             .cloned()
             .collect::<Vec<f32>>()
+    }
+}
+
+fn build_alibi_bias(cfg: &Config) -> Result<Tensor> {
+    let n_heads = cfg.num_attention_heads;
+    let seq_len = cfg.max_position_embeddings;
+    let alibi_bias = Tensor::arange(0, seq_len as i64, &Device::Cpu)?.to_dtype(DType::F32)?;
+    let alibi_bias = {
+        let a1 = alibi_bias.reshape((1, seq_len))?;
+        let a2 = alibi_bias.reshape((seq_len, 1))?;
+        a1.broadcast_sub(&a2)?.abs()?.broadcast_left(n_heads)?
     };
+    let slopes = alibi_slopes(cfg).iter().map(|s| -s).collect::<Vec<_>>();
     let slopes = Tensor::new(slopes, &Device::Cpu)?.reshape((1, (), 1, 1))?;
     alibi_bias.to_dtype(DType::F32)?.broadcast_mul(&slopes)
 }
@@ -335,22 +374,29 @@ fn build_alibi_bias(cfg: &Config) -> Result<Tensor> {
 struct BertEncoder {
     alibi: Tensor,
     layers: Vec<BertLayer>,
+    use_flash_attn: bool,
     span: tracing::Span,
 }
 
 impl BertEncoder {
-    fn new(vb: VarBuilder, cfg: &Config) -> Result<Self> {
+    fn new(vb: VarBuilder, cfg: &Config, use_flash_attn: bool) -> Result<Self> {
         if cfg.position_embedding_type != PositionEmbeddingType::Alibi {
             candle_core::bail!("only alibi is supported as a position-embedding-type")
         }
         let layers = (0..cfg.num_hidden_layers)
-            .map(|index| BertLayer::new(vb.pp(format!("layer.{index}")), cfg))
+            .map(|index| BertLayer::new(vb.pp(format!("layer.{index}")), cfg, use_flash_attn))
             .collect::<Result<Vec<_>>>()?;
         let span = tracing::span!(tracing::Level::TRACE, "encoder");
-        let alibi = build_alibi_bias(cfg)?.to_device(vb.device())?;
+        // flash-attn applies the ALiBi bias itself from the per-head slopes.
+        let alibi = if use_flash_attn {
+            Tensor::new(alibi_slopes(cfg), vb.device())?
+        } else {
+            build_alibi_bias(cfg)?.to_device(vb.device())?
+        };
         Ok(Self {
             alibi,
             layers,
+            use_flash_attn,
             span,
         })
     }
@@ -360,7 +406,13 @@ impl Module for BertEncoder {
     fn forward(&self, xs: &Tensor) -> Result<Tensor> {
         let _enter = self.span.enter();
         let seq_len = xs.dim(1)?;
-        let alibi_bias = self.alibi.i((.., .., ..seq_len, ..seq_len))?;
+        let alibi_bias = if self.use_flash_attn {
+            self.alibi.clone()
+        } else {
+            self.alibi
+                .i((.., .., ..seq_len, ..seq_len))?
+                .to_dtype(xs.dtype())?
+        };
         let mut xs = xs.clone();
         for layer in self.layers.iter() {
             xs = layer.forward(&xs, &alibi_bias)?
@@ -378,9 +430,13 @@ pub struct BertModel {
 }
 
 impl BertModel {
-    pub fn new(vb: VarBuilder, cfg: &Config) -> Result<Self> {
+    /// `use_flash_attn` needs the `flash-attn` feature; without it loading fails.
+    pub fn new(vb: VarBuilder, cfg: &Config, use_flash_attn: bool) -> Result<Self> {
+        if use_flash_attn && !cfg!(feature = "flash-attn") {
+            candle_core::bail!("flash attention needs the `flash-attn` feature");
+        }
         let embeddings = BertEmbeddings::new(vb.pp("embeddings"), cfg)?;
-        let encoder = BertEncoder::new(vb.pp("encoder"), cfg)?;
+        let encoder = BertEncoder::new(vb.pp("encoder"), cfg, use_flash_attn)?;
         Ok(Self {
             embeddings,
             encoder,
@@ -0,0 +1,176 @@
+//! End-to-end retrieval: embed a query, search a vector store and optionally rerank the hits with a
+//! cross-encoder. Ingestion jobs are described in [`spec`] and re-run periodically by
+//! [`schedule`], and [`queue`] spreads the files of a directory over many workers. [`topic`]
+//! embeds documents streamed through Kafka or NATS.
+
+#[cfg(feature = "stream")]
+pub mod queue;
+#[cfg(feature = "stream")]
+pub mod schedule;
+#[cfg(feature = "stream")]
+pub mod spec;
+#[cfg(feature = "stream")]
+pub mod topic;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::DEFAULT_BATCH_SIZE;
+use crate::embed_query;
+use crate::embeddings::embed::{Embedder, EmbeddingResult};
+use crate::error::EmbedError;
+use crate::reranker::model::Reranker;
+
+/// Number of chunks returned by [`Pipeline::retrieve`] unless set with [`Pipeline::with_top_k`].
+pub const DEFAULT_TOP_K: usize = 5;
+/// Number of candidates fetched from the vector store for the reranker to choose from.
+pub const DEFAULT_CANDIDATES: usize = 20;
+
+/// A chunk returned by a vector store or the pipeline. Higher scores are better.
+#[derive(Debug, Clone)]
+pub struct ScoredChunk {
+    pub text: String,
+    pub metadata: Option<HashMap<String, String>>,
+    pub score: f32,
+}
+
+/// A vector store that can be searched with a query embedding.
+pub trait SearchAdapter: Send + Sync {
+    /// Returns up to `top_k` chunks closest to `embedding`, best first.
+    fn query(&self, embedding: &EmbeddingResult, top_k: usize) -> anyhow::Result<Vec<ScoredChunk>>;
+}
+
+pub struct Pipeline<A: SearchAdapter> {
+    embedder: Arc<Embedder>,
+    adapter: A,
+    reranker: Option<Arc<Reranker>>,
+    top_k: usize,
+    candidates: usize,
+    batch_size: usize,
+}
+
+impl<A: SearchAdapter> Pipeline<A> {
+    pub fn new(embedder: Arc<Embedder>, adapter: A) -> Self {
+        Self {
+            embedder,
+            adapter,
+            reranker: None,
+            top_k: DEFAULT_TOP_K,
+            candidates: DEFAULT_CANDIDATES,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Reranks the candidates from the vector store with a cross-encoder before keeping the top k.
+    pub fn with_reranker(mut self, reranker: Arc<Reranker>) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Sets how many candidates are fetched for the reranker. Ignored without a reranker.
+    pub fn with_candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    /// Sets the batch size used by the reranker.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Returns the `top_k` chunks for `query`. With a reranker the scores are the reranker's
+    /// relevance scores, otherwise they are the ones reported by the adapter.
+    pub async fn retrieve(&self, query: &str) -> anyhow::Result<Vec<ScoredChunk>> {
+        let query_embedding = embed_query(vec![query.to_string()], &self.embedder, None)
+            .await?
+            .pop()
+            .ok_or_else(|| EmbedError::Inference("no embedding for the query".to_string()))?;
+
+        let Some(reranker) = &self.reranker else {
+            let mut chunks = self.adapter.query(&query_embedding.embedding, self.top_k)?;
+            chunks.truncate(self.top_k);
+            return Ok(chunks);
+        };
+
+        let mut chunks = self
+            .adapter
+            .query(&query_embedding.embedding, self.candidates.max(self.top_k))?;
+        if chunks.is_empty() {
+            return Ok(chunks);
+        }
+        let documents = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        let scores = reranker.compute_scores(vec![query], documents, self.batch_size)?;
+        for (chunk, score) in chunks.iter_mut().zip(&scores[0]) {
+            chunk.score = *score;
+        }
+        chunks.sort_by(|a, b| b.score.total_cmp(&a.score));
+        chunks.truncate(self.top_k);
+        Ok(chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::embed::TextEmbedder;
+    use crate::embeddings::local::bert::BertEmbedder;
+
+    struct InMemoryAdapter {
+        chunks: Vec<(Vec<f32>, String)>,
+    }
+
+    impl SearchAdapter for InMemoryAdapter {
+        fn query(
+            &self,
+            embedding: &EmbeddingResult,
+            top_k: usize,
+        ) -> anyhow::Result<Vec<ScoredChunk>> {
+            let query = embedding.to_dense()?;
+            let mut chunks = self
+                .chunks
+                .iter()
+                .map(|(vector, text)| ScoredChunk {
+                    text: text.clone(),
+                    metadata: None,
+                    score: vector.iter().zip(&query).map(|(a, b)| a * b).sum(),
+                })
+                .collect::<Vec<_>>();
+            chunks.sort_by(|a, b| b.score.total_cmp(&a.score));
+            chunks.truncate(top_k);
+            Ok(chunks)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_retrieve() {
+        let embedder = Arc::new(Embedder::Text(TextEmbedder::Bert(Box::new(
+            BertEmbedder::default(),
+        ))));
+        let texts = vec![
+            "The cat sat on the mat".to_string(),
+            "Stock markets fell sharply today".to_string(),
+            "Kittens love to play with yarn".to_string(),
+        ];
+        let vectors = embedder.embed(&texts, None).await.unwrap();
+        let adapter = InMemoryAdapter {
+            chunks: vectors
+                .into_iter()
+                .map(|vector| vector.to_dense().unwrap())
+                .zip(texts)
+                .collect(),
+        };
+
+        let pipeline = Pipeline::new(embedder, adapter).with_top_k(2);
+        let chunks = pipeline.retrieve("cats").await.unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks
+            .iter()
+            .all(|chunk| !chunk.text.contains("Stock markets")));
+    }
+}
@@ -0,0 +1,368 @@
+//! Splits the ingestion of a directory across processes: [`enqueue_directory`] lists its files
+//! into a [`WorkQueue`], and any number of workers started with [`run_worker`], on any number of
+//! machines, take files from the queue and embed them.
+//!
+//! A file taken from the queue is leased for the visibility timeout. When a worker crashes or
+//! hangs, its lease expires and the file is handed to another worker. Files that fail to embed
+//! are retried up to [`WorkerOptions::max_attempts`] times and then set aside as dead.
+//! [`InMemoryQueue`] shares the work between the tasks of one process, `RedisQueue` (feature
+//! `redis`) between processes.
+
+#[cfg(feature = "redis")]
+pub mod redis;
+
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::TextEmbedConfig;
+use crate::embed_file;
+use crate::embeddings::embed::{EmbedData, Embedder};
+use crate::file_loader::FileParser;
+
+/// How long a taken file stays invisible to other workers unless set with
+/// [`WorkerOptions::with_visibility_timeout`].
+pub const DEFAULT_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(300);
+/// How many times a file is tried unless set with [`WorkerOptions::with_max_attempts`].
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// How long an idle worker waits before looking at the queue again unless set with
+/// [`WorkerOptions::with_poll_interval`].
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A file to embed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkItem {
+    /// Tells apart the items of a queue, even for the same file.
+    pub id: String,
+    pub path: String,
+    /// How many times embedding the file failed so far.
+    pub attempts: u32,
+}
+
+impl WorkItem {
+    pub fn new(path: impl Into<String>) -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Self {
+            id: format!(
+                "{:x}-{:x}-{:x}",
+                nanos,
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ),
+            path: path.into(),
+            attempts: 0,
+        }
+    }
+}
+
+/// A queue of files shared by the workers of an ingestion job.
+///
+/// Taking an item leases it: other workers do not see it until the lease ends with
+/// [`ack`](Self::ack), [`retry`](Self::retry) or [`dead_letter`](Self::dead_letter), or expires.
+/// Items are delivered at least once, so a file whose worker lost its lease may be embedded
+/// twice; the ids of [`EmbedData`] make upserting it again harmless.
+pub trait WorkQueue: Send + Sync {
+    fn push(&self, items: &[WorkItem]) -> anyhow::Result<()>;
+
+    /// Takes the next item and leases it for `visibility_timeout`, or returns `None` when no item
+    /// is waiting. Items whose lease expired are waiting again.
+    fn take(&self, visibility_timeout: Duration) -> anyhow::Result<Option<WorkItem>>;
+
+    /// Removes a taken item for good, once its file is embedded.
+    fn ack(&self, item: &WorkItem) -> anyhow::Result<()>;
+
+    /// Ends the lease of a taken item and queues it again with one more attempt.
+    fn retry(&self, item: &WorkItem) -> anyhow::Result<()>;
+
+    /// Ends the lease of a taken item and keeps it apart, after its last attempt failed.
+    fn dead_letter(&self, item: &WorkItem) -> anyhow::Result<()>;
+
+    /// The number of items waiting or leased, 0 once all the work is done.
+    fn remaining(&self) -> anyhow::Result<usize>;
+}
+
+#[derive(Default)]
+struct QueueState {
+    waiting: VecDeque<WorkItem>,
+    leased: HashMap<String, (WorkItem, Instant)>,
+    dead: Vec<WorkItem>,
+}
+
+/// A [`WorkQueue`] in memory, for workers running as tasks of the same process.
+#[derive(Default)]
+pub struct InMemoryQueue {
+    state: Mutex<QueueState>,
+}
+
+impl InMemoryQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The items whose last attempt failed.
+    pub fn dead(&self) -> Vec<WorkItem> {
+        self.state.lock().unwrap().dead.clone()
+    }
+}
+
+impl WorkQueue for InMemoryQueue {
+    fn push(&self, items: &[WorkItem]) -> anyhow::Result<()> {
+        self.state
+            .lock()
+            .unwrap()
+            .waiting
+            .extend(items.iter().cloned());
+        Ok(())
+    }
+
+    fn take(&self, visibility_timeout: Duration) -> anyhow::Result<Option<WorkItem>> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let expired = state
+            .leased
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        for id in expired {
+            if let Some((item, _)) = state.leased.remove(&id) {
+                state.waiting.push_front(item);
+            }
+        }
+        let Some(item) = state.waiting.pop_front() else {
+            return Ok(None);
+        };
+        state
+            .leased
+            .insert(item.id.clone(), (item.clone(), now + visibility_timeout));
+        Ok(Some(item))
+    }
+
+    fn ack(&self, item: &WorkItem) -> anyhow::Result<()> {
+        self.state.lock().unwrap().leased.remove(&item.id);
+        Ok(())
+    }
+
+    fn retry(&self, item: &WorkItem) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((mut item, _)) = state.leased.remove(&item.id) {
+            item.attempts += 1;
+            state.waiting.push_back(item);
+        }
+        Ok(())
+    }
+
+    fn dead_letter(&self, item: &WorkItem) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((item, _)) = state.leased.remove(&item.id) {
+            state.dead.push(item);
+        }
+        Ok(())
+    }
+
+    fn remaining(&self) -> anyhow::Result<usize> {
+        let state = self.state.lock().unwrap();
+        Ok(state.waiting.len() + state.leased.len())
+    }
+}
+
+/// Queues the documents of `directory`, found like `embed_directory_stream` finds them with the
+/// walk options of `config`. Returns the number of files queued.
+pub fn enqueue_directory<Q: WorkQueue + ?Sized>(
+    queue: &Q,
+    directory: impl AsRef<Path>,
+    extensions: Option<Vec<String>>,
+    config: &TextEmbedConfig,
+) -> anyhow::Result<usize> {
+    let files = FileParser::new()
+        .with_walk_options(config.walk)
+        .get_text_files(&directory.as_ref().to_path_buf(), extensions)?;
+    let items = files.into_iter().map(WorkItem::new).collect::<Vec<_>>();
+    queue.push(&items)?;
+    Ok(items.len())
+}
+
+/// How a worker takes files from a [`WorkQueue`].
+#[derive(Debug, Clone, Copy)]
+pub struct WorkerOptions {
+    /// How long a taken file stays invisible to other workers. Should be longer than it takes to
+    /// embed the largest file, or the file is embedded again by another worker.
+    pub visibility_timeout: Duration,
+    /// How many times a file is tried before it is set aside as dead.
+    pub max_attempts: u32,
+    /// How long the worker waits when no file is waiting but others are still being embedded.
+    pub poll_interval: Duration,
+}
+
+impl Default for WorkerOptions {
+    fn default() -> Self {
+        Self {
+            visibility_timeout: DEFAULT_VISIBILITY_TIMEOUT,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+}
+
+impl WorkerOptions {
+    pub fn with_visibility_timeout(mut self, visibility_timeout: Duration) -> Self {
+        self.visibility_timeout = visibility_timeout;
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+}
+
+/// What one worker did, see [`run_worker`].
+#[derive(Debug, Clone, Default)]
+pub struct WorkerReport {
+    /// Files embedded and handed to the adapter.
+    pub embedded: usize,
+    /// Failed attempts that were queued again.
+    pub retried: usize,
+    /// Files set aside after their last attempt, with the error of that attempt.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Takes files from `queue` and embeds them with `embed_file`, handing the embeddings to
+/// `adapter`, until the queue has no file waiting or leased. Several workers can run on the same
+/// queue, in one process or on many machines.
+///
+/// Only errors of the queue itself stop the worker. A file that fails to embed is retried, by
+/// this worker or another one, up to `options.max_attempts` times.
+///
+/// ```no_run
+/// use embed_anything::config::TextEmbedConfig;
+/// use embed_anything::embeddings::embed::{EmbedData, Embedder};
+/// use embed_anything::pipeline::queue::{enqueue_directory, run_worker, InMemoryQueue, WorkerOptions};
+///
+/// # async fn run(embedder: Embedder) -> anyhow::Result<()> {
+/// let queue = InMemoryQueue::new();
+/// let config = TextEmbedConfig::default();
+/// enqueue_directory(&queue, "documents", None, &config)?;
+/// let report = run_worker(&queue, &embedder, Some(&config), &WorkerOptions::default(), |batch: Vec<EmbedData>| {
+///     println!("{} embeddings", batch.len())
+/// })
+/// .await?;
+/// println!("{} files embedded, {} failed", report.embedded, report.failed.len());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn run_worker<Q, F>(
+    queue: &Q,
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+    options: &WorkerOptions,
+    adapter: F,
+) -> anyhow::Result<WorkerReport>
+where
+    Q: WorkQueue + ?Sized,
+    F: Fn(Vec<EmbedData>),
+{
+    let mut report = WorkerReport::default();
+    loop {
+        let Some(item) = queue.take(options.visibility_timeout)? else {
+            if queue.remaining()? == 0 {
+                return Ok(report);
+            }
+            tokio::time::sleep(options.poll_interval).await;
+            continue;
+        };
+        match embed_file(&item.path, embedder, config, Some(&adapter)).await {
+            Ok(_) => {
+                queue.ack(&item)?;
+                report.embedded += 1;
+            }
+            Err(e) if item.attempts + 1 < options.max_attempts => {
+                tracing::warn!(
+                    "Retrying {} after attempt {}: {:?}",
+                    item.path,
+                    item.attempts + 1,
+                    e
+                );
+                queue.retry(&item)?;
+                report.retried += 1;
+            }
+            Err(e) => {
+                tracing::warn!("Giving up on {}: {:?}", item.path, e);
+                queue.dead_letter(&item)?;
+                report.failed.push((item.path, e.to_string()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::embed::TextEmbedder;
+    use crate::embeddings::local::bert::BertEmbedder;
+
+    #[test]
+    fn test_in_memory_queue_leases() {
+        let queue = InMemoryQueue::new();
+        queue
+            .push(&[WorkItem::new("a.txt"), WorkItem::new("b.txt")])
+            .unwrap();
+
+        let a = queue.take(Duration::ZERO).unwrap().unwrap();
+        assert_eq!(a.path, "a.txt");
+        // The lease of `a` expired, so it is waiting again ahead of `b`.
+        let again = queue.take(Duration::from_secs(60)).unwrap().unwrap();
+        assert_eq!(again, a);
+        let b = queue.take(Duration::from_secs(60)).unwrap().unwrap();
+        assert_eq!(b.path, "b.txt");
+        assert!(queue.take(Duration::from_secs(60)).unwrap().is_none());
+        assert_eq!(queue.remaining().unwrap(), 2);
+
+        queue.retry(&a).unwrap();
+        queue.ack(&b).unwrap();
+        let retried = queue.take(Duration::from_secs(60)).unwrap().unwrap();
+        assert_eq!((retried.id.as_str(), retried.attempts), (a.id.as_str(), 1));
+        queue.dead_letter(&retried).unwrap();
+        assert_eq!(queue.remaining().unwrap(), 0);
+        assert_eq!(queue.dead().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_worker() {
+        let embedder = Embedder::Text(TextEmbedder::Bert(Box::new(BertEmbedder::default())));
+        let queue = InMemoryQueue::new();
+        queue
+            .push(&[
+                WorkItem::new("test_files/test.txt"),
+                WorkItem::new("test_files/missing.txt"),
+            ])
+            .unwrap();
+
+        let embedded = Mutex::new(0);
+        let options = WorkerOptions::default().with_max_attempts(2);
+        let report = run_worker(&queue, &embedder, None, &options, |batch| {
+            *embedded.lock().unwrap() += batch.len();
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(report.embedded, 1);
+        assert!(*embedded.lock().unwrap() > 0);
+        assert_eq!(report.retried, 1);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(queue.dead()[0].path, "test_files/missing.txt");
+    }
+}
@@ -0,0 +1,156 @@
+//! A [`WorkQueue`] in Redis, shared by workers on any number of machines. Waiting items are kept
+//! in the list `{name}:waiting`, leased items in the sorted set `{name}:leased` scored by the end
+//! of their lease, and dead items in the list `{name}:dead`, all as JSON.
+//!
+//! Every change is a Lua script, so two workers never take the same item, and leases are timed
+//! with the clock of the Redis server rather than the clocks of the workers. Needs Redis 5 or
+//! later.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use ::redis::{Commands, Script};
+
+use super::{WorkItem, WorkQueue};
+use crate::error::EmbedError;
+
+/// Makes the items whose lease expired wait again, ahead of the others, then takes and leases the
+/// next item for `ARGV[1]` milliseconds.
+const TAKE_SCRIPT: &str = r#"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local expired = redis.call('ZRANGEBYSCORE', KEYS[2], '-inf', now)
+for _, item in ipairs(expired) do
+    redis.call('ZREM', KEYS[2], item)
+    redis.call('RPUSH', KEYS[1], item)
+end
+local item = redis.call('RPOP', KEYS[1])
+if item then
+    redis.call('ZADD', KEYS[2], now + tonumber(ARGV[1]), item)
+end
+return item
+"#;
+
+/// Ends the lease of `ARGV[1]` and, if it was still leased, pushes `ARGV[2]` to the list `KEYS[2]`.
+const MOVE_SCRIPT: &str = r#"
+if redis.call('ZREM', KEYS[1], ARGV[1]) == 1 then
+    redis.call('LPUSH', KEYS[2], ARGV[2])
+end
+return 0
+"#;
+
+pub struct RedisQueue {
+    connection: Mutex<::redis::Connection>,
+    waiting: String,
+    leased: String,
+    dead: String,
+    take_script: Script,
+    move_script: Script,
+}
+
+impl RedisQueue {
+    /// Uses the queue `name` of the Redis server at `url`, e.g. `redis://localhost:6379`.
+    pub fn new(url: &str, name: &str) -> anyhow::Result<Self> {
+        let connection = ::redis::Client::open(url)
+            .and_then(|client| client.get_connection())
+            .map_err(redis_error)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            waiting: format!("{}:waiting", name),
+            leased: format!("{}:leased", name),
+            dead: format!("{}:dead", name),
+            take_script: Script::new(TAKE_SCRIPT),
+            move_script: Script::new(MOVE_SCRIPT),
+        })
+    }
+
+    /// The items whose last attempt failed.
+    pub fn dead(&self) -> anyhow::Result<Vec<WorkItem>> {
+        let items: Vec<String> = self
+            .connection
+            .lock()
+            .unwrap()
+            .lrange(&self.dead, 0, -1)
+            .map_err(redis_error)?;
+        items
+            .iter()
+            .map(|item| Ok(serde_json::from_str(item)?))
+            .collect()
+    }
+
+    /// Ends the lease of `item` and pushes `moved` to the list `key`.
+    fn end_lease(&self, item: &WorkItem, key: &str, moved: &WorkItem) -> anyhow::Result<()> {
+        self.move_script
+            .key(&self.leased)
+            .key(key)
+            .arg(serde_json::to_string(item)?)
+            .arg(serde_json::to_string(moved)?)
+            .invoke::<i64>(&mut *self.connection.lock().unwrap())
+            .map_err(redis_error)?;
+        Ok(())
+    }
+}
+
+impl WorkQueue for RedisQueue {
+    fn push(&self, items: &[WorkItem]) -> anyhow::Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        let items = items
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.connection
+            .lock()
+            .unwrap()
+            .lpush::<_, _, ()>(&self.waiting, items)
+            .map_err(redis_error)?;
+        Ok(())
+    }
+
+    fn take(&self, visibility_timeout: Duration) -> anyhow::Result<Option<WorkItem>> {
+        let item: Option<String> = self
+            .take_script
+            .key(&self.waiting)
+            .key(&self.leased)
+            .arg(visibility_timeout.as_millis() as u64)
+            .invoke(&mut *self.connection.lock().unwrap())
+            .map_err(redis_error)?;
+        Ok(match item {
+            Some(item) => Some(serde_json::from_str(&item)?),
+            None => None,
+        })
+    }
+
+    fn ack(&self, item: &WorkItem) -> anyhow::Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .zrem::<_, _, ()>(&self.leased, serde_json::to_string(item)?)
+            .map_err(redis_error)?;
+        Ok(())
+    }
+
+    fn retry(&self, item: &WorkItem) -> anyhow::Result<()> {
+        let retried = WorkItem {
+            attempts: item.attempts + 1,
+            ..item.clone()
+        };
+        self.end_lease(item, &self.waiting, &retried)
+    }
+
+    fn dead_letter(&self, item: &WorkItem) -> anyhow::Result<()> {
+        self.end_lease(item, &self.dead, item)
+    }
+
+    fn remaining(&self) -> anyhow::Result<usize> {
+        let mut connection = self.connection.lock().unwrap();
+        let waiting: usize = connection.llen(&self.waiting).map_err(redis_error)?;
+        let leased: usize = connection.zcard(&self.leased).map_err(redis_error)?;
+        Ok(waiting + leased)
+    }
+}
+
+fn redis_error(e: ::redis::RedisError) -> EmbedError {
+    EmbedError::Network(format!("Redis: {}", e))
+}
@@ -0,0 +1,176 @@
+//! Re-runs [`PipelineSpec`]s at the interval of their `schedule`, so the crate can run as a
+//! standalone indexing daemon. Combined with [`SpecRunner::with_state_dir`] each run only embeds
+//! the files and pages that changed since the previous one.
+
+use tokio::time::Instant;
+
+use super::spec::{PipelineSpec, RunReport, SpecRunner};
+use crate::error::EmbedError;
+
+struct Job {
+    name: String,
+    spec: PipelineSpec,
+    interval: std::time::Duration,
+    next_run: Instant,
+}
+
+/// Runs every job once when started and then again each time its interval has passed.
+///
+/// ```no_run
+/// use embed_anything::pipeline::schedule::Scheduler;
+/// use embed_anything::pipeline::spec::{PipelineSpec, SpecRunner};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let runner = SpecRunner::new().with_state_dir(".embed_anything/state");
+/// let scheduler = Scheduler::new(runner).with_job(PipelineSpec::from_path("handbook.yaml")?)?;
+/// scheduler
+///     .run(|job, report| match report {
+///         Ok(report) => println!("{}: {} embedded, {} skipped", job, report.embedded, report.skipped),
+///         Err(e) => eprintln!("{}: {}", job, e),
+///     })
+///     .await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Scheduler {
+    runner: SpecRunner,
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    pub fn new(runner: SpecRunner) -> Self {
+        Self {
+            runner,
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Adds a job. The spec must have a `schedule`; jobs are named after the spec's `name`.
+    pub fn with_job(mut self, spec: PipelineSpec) -> Result<Self, EmbedError> {
+        let name = spec
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("job-{}", self.jobs.len()));
+        let interval = spec
+            .schedule
+            .as_ref()
+            .ok_or_else(|| {
+                EmbedError::InvalidConfig(format!("The pipeline spec '{}' has no schedule", name))
+            })?
+            .interval()?;
+        self.jobs.push(Job {
+            name,
+            spec,
+            interval,
+            next_run: Instant::now(),
+        });
+        Ok(self)
+    }
+
+    /// Runs the jobs that are due, in the order they were added, and returns their reports.
+    pub async fn run_pending(&mut self) -> Vec<(String, anyhow::Result<RunReport>)> {
+        let mut reports = Vec::new();
+        for job in &mut self.jobs {
+            if job.next_run > Instant::now() {
+                continue;
+            }
+            let report = self.runner.run(&job.spec).await;
+            match &report {
+                Ok(report) => tracing::info!(
+                    job = job.name.as_str(),
+                    embedded = report.embedded,
+                    skipped = report.skipped,
+                    filtered = report.filtered,
+                    errors = report.errors.len(),
+                    duration_ms = report.duration.as_millis() as u64,
+                    "ingestion run finished"
+                ),
+                Err(e) => {
+                    tracing::warn!(job = job.name.as_str(), error = %e, "ingestion run failed")
+                }
+            }
+            // Scheduled from the end of the run, so a run longer than the interval is not
+            // followed by a burst of catch-up runs.
+            job.next_run = Instant::now() + job.interval;
+            reports.push((job.name.clone(), report));
+        }
+        reports
+    }
+
+    /// Runs the jobs forever, handing each report to `on_report`.
+    pub async fn run<F>(mut self, mut on_report: F)
+    where
+        F: FnMut(&str, anyhow::Result<RunReport>),
+    {
+        loop {
+            for (job, report) in self.run_pending().await {
+                on_report(&job, report);
+            }
+            match self.jobs.iter().map(|job| job.next_run).min() {
+                Some(next_run) => tokio::time::sleep_until(next_run).await,
+                None => return,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    const SPEC: &str = r#"
+name: notes
+sources:
+  - type: file
+    path: notes.txt
+embedder:
+  model: bert
+  model_id: sentence-transformers/all-MiniLM-L6-v2
+schedule:
+  every: 1h
+"#;
+
+    #[tokio::test]
+    async fn test_scheduler_skips_unchanged_files() {
+        let dir = TempDir::new("scheduler").unwrap();
+        let notes = dir.path().join("notes.txt");
+        std::fs::write(&notes, "The quick brown fox jumps over the lazy dog.").unwrap();
+        let spec =
+            PipelineSpec::from_yaml(&SPEC.replace("notes.txt", notes.to_str().unwrap())).unwrap();
+        let runner = SpecRunner::new().with_state_dir(dir.path().join("state"));
+        let mut scheduler = Scheduler::new(runner).with_job(spec).unwrap();
+
+        let reports = scheduler.run_pending().await;
+        assert_eq!(reports.len(), 1);
+        let report = reports[0].1.as_ref().unwrap();
+        assert_eq!(reports[0].0, "notes");
+        assert!(report.embedded > 0);
+        assert_eq!(report.skipped, 0);
+
+        // Not due again for an hour.
+        assert!(scheduler.run_pending().await.is_empty());
+
+        scheduler.jobs[0].next_run = Instant::now();
+        let reports = scheduler.run_pending().await;
+        let report = reports[0].1.as_ref().unwrap();
+        assert_eq!(report.embedded, 0);
+        assert_eq!(report.skipped, 1);
+
+        std::fs::write(&notes, "A different sentence about a sleepy cat.").unwrap();
+        scheduler.jobs[0].next_run = Instant::now();
+        let reports = scheduler.run_pending().await;
+        let report = reports[0].1.as_ref().unwrap();
+        assert!(report.embedded > 0);
+        assert_eq!(report.skipped, 0);
+    }
+
+    #[test]
+    fn test_job_requires_schedule() {
+        let spec = PipelineSpec::from_yaml(&SPEC.replace("schedule:\n  every: 1h\n", "")).unwrap();
+        assert!(matches!(
+            Scheduler::new(SpecRunner::new()).with_job(spec),
+            Err(EmbedError::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,712 @@
+//! Declarative ingestion jobs. A [`PipelineSpec`] lists the sources to read, how to chunk and
+//! filter them, the models to embed them with and the adapter to send the embeddings to, so a job
+//! can be versioned as a YAML file:
+//!
+//! ```yaml
+//! name: handbook
+//! sources:
+//!   - type: directory
+//!     path: docs/handbook
+//!     extensions: [md, pdf]
+//!   - type: url
+//!     url: https://example.com/faq
+//! chunker:
+//!   chunk_size: 256
+//!   overlap_tokens: 32
+//! filters:
+//!   min_chars: 20
+//! embedder:
+//!   model: bert
+//!   model_id: sentence-transformers/all-MiniLM-L6-v2
+//! adapter: qdrant
+//! schedule:
+//!   every: 1h
+//! ```
+//!
+//! Adapters are code, so the spec only names one and [`SpecRunner::with_adapter`] registers it.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::{MetadataConfig, TextEmbedConfig, TextEmbedConfigBuilder, WebFetchConfig};
+use crate::embeddings::embed::{EmbedData, Embedder};
+use crate::error::EmbedError;
+use crate::file_loader::FileParser;
+use crate::file_processor::web_cache::{content_hash, CacheEntry, WebCache};
+use crate::text_loader::SplittingStrategy;
+use crate::warning::{EmbedWarning, Warnings};
+use crate::{embed_directory_stream, embed_file, embed_webpage, embed_webpages};
+
+/// An ingestion job, usually read from YAML with [`PipelineSpec::from_yaml`] or
+/// [`PipelineSpec::from_path`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineSpec {
+    pub name: Option<String>,
+    pub sources: Vec<SourceSpec>,
+    #[serde(default)]
+    pub processors: ProcessorSpec,
+    #[serde(default)]
+    pub chunker: ChunkerSpec,
+    #[serde(default)]
+    pub filters: FilterSpec,
+    /// The model to embed with. Use `embedders` to embed every chunk with several models.
+    pub embedder: Option<EmbedderSpec>,
+    #[serde(default)]
+    pub embedders: Vec<EmbedderSpec>,
+    /// The name of an adapter registered with [`SpecRunner::with_adapter`]. Without one the
+    /// embeddings are returned in the [`RunReport`].
+    pub adapter: Option<String>,
+    pub schedule: Option<ScheduleSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SourceSpec {
+    Directory {
+        path: PathBuf,
+        /// Only files with these extensions are read. Default is every supported file.
+        extensions: Option<Vec<String>>,
+    },
+    File {
+        path: PathBuf,
+    },
+    Url {
+        url: String,
+    },
+}
+
+impl std::fmt::Display for SourceSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceSpec::Directory { path, .. } | SourceSpec::File { path } => {
+                write!(f, "{}", path.display())
+            }
+            SourceSpec::Url { url } => write!(f, "{}", url),
+        }
+    }
+}
+
+/// How files are turned into text and which metadata is kept.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessorSpec {
+    pub use_ocr: Option<bool>,
+    pub metadata_fields: Option<Vec<String>>,
+    pub max_metadata_length: Option<usize>,
+    /// Added to the metadata of every embedding, see [`MetadataConfig::namespace`].
+    pub namespace: Option<String>,
+}
+
+/// The chunking and batching settings, see [`TextEmbedConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChunkerSpec {
+    pub chunk_size: Option<usize>,
+    pub overlap_ratio: Option<f32>,
+    pub overlap_tokens: Option<usize>,
+    /// `sentence`, `semantic` or `content_defined`. Semantic chunking uses the first embedder as
+    /// its encoder.
+    pub strategy: Option<String>,
+    pub batch_size: Option<usize>,
+    pub buffer_size: Option<usize>,
+}
+
+/// Chunks dropped before they reach the adapter.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterSpec {
+    /// Chunks with fewer characters, ignoring surrounding whitespace, are dropped.
+    pub min_chars: Option<usize>,
+    /// Chunks matching any of these regular expressions are dropped.
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Candle, see [`EmbedderBuilder::build_hf`](crate::embeddings::embed::EmbedderBuilder::build_hf).
+    #[default]
+    Hf,
+    Onnx,
+    Cloud,
+}
+
+/// A model, built with [`Embedder::builder`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmbedderSpec {
+    /// Added to the metadata of each embedding as `embedder`. Default is the model id.
+    pub name: Option<String>,
+    /// The architecture, e.g. `bert`, `jina` or `openai`.
+    pub model: String,
+    pub model_id: String,
+    #[serde(default)]
+    pub backend: Backend,
+    pub revision: Option<String>,
+    /// The `.onnx` file in the repository of an ONNX model.
+    pub path_in_repo: Option<String>,
+}
+
+impl EmbedderSpec {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.model_id)
+    }
+
+    pub fn build(&self) -> anyhow::Result<Embedder> {
+        let mut builder = Embedder::builder(&self.model).with_model_id(&self.model_id);
+        if let Some(revision) = &self.revision {
+            builder = builder.with_revision(revision);
+        }
+        if let Some(path_in_repo) = &self.path_in_repo {
+            builder = builder.with_path_in_repo(path_in_repo);
+        }
+        match self.backend {
+            Backend::Hf => builder.build_hf(),
+            Backend::Onnx => builder.build_onnx(),
+            Backend::Cloud => builder.build_cloud(),
+        }
+    }
+}
+
+/// How often the job should run, e.g. `every: 30m`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleSpec {
+    /// A number followed by `s`, `m`, `h` or `d`.
+    pub every: String,
+}
+
+impl ScheduleSpec {
+    pub fn interval(&self) -> Result<Duration, EmbedError> {
+        let invalid = || {
+            EmbedError::InvalidConfig(format!(
+                "schedule.every must be a number followed by s, m, h or d, got '{}'",
+                self.every
+            ))
+        };
+        let every = self.every.trim();
+        let unit = every.chars().last().ok_or_else(invalid)?;
+        let seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let count = every[..every.len() - 1]
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| invalid())?;
+        if count == 0 {
+            return Err(invalid());
+        }
+        Ok(Duration::from_secs(count * seconds))
+    }
+}
+
+impl PipelineSpec {
+    pub fn from_yaml(yaml: &str) -> Result<Self, EmbedError> {
+        let spec: Self = serde_yaml::from_str(yaml)
+            .map_err(|e| EmbedError::InvalidConfig(format!("Invalid pipeline spec: {}", e)))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Reads a spec from a YAML file. JSON files work too, since JSON is valid YAML.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, EmbedError> {
+        Self::from_yaml(&std::fs::read_to_string(path)?)
+    }
+
+    /// The models to embed with, `embedder` first.
+    pub fn embedders(&self) -> impl Iterator<Item = &EmbedderSpec> {
+        self.embedder.iter().chain(&self.embedders)
+    }
+
+    pub fn validate(&self) -> Result<(), EmbedError> {
+        if self.sources.is_empty() {
+            return Err(EmbedError::InvalidConfig(
+                "the pipeline spec has no sources".to_string(),
+            ));
+        }
+        if self.embedders().next().is_none() {
+            return Err(EmbedError::InvalidConfig(
+                "the pipeline spec has no embedder".to_string(),
+            ));
+        }
+        self.filters.patterns()?;
+        if let Some(schedule) = &self.schedule {
+            schedule.interval()?;
+        }
+        // The semantic encoder is only known once the models are loaded.
+        self.config_builder()?.build().map(|_| ())
+    }
+
+    /// The [`TextEmbedConfig`] of the chunker and processors. A semantic chunker needs the
+    /// `semantic_encoder`.
+    pub fn text_config(
+        &self,
+        semantic_encoder: Option<Arc<Embedder>>,
+    ) -> Result<TextEmbedConfig, EmbedError> {
+        let mut builder = self.config_builder()?;
+        match self.chunker.strategy.as_deref() {
+            Some("semantic") => {
+                builder = builder.with_splitting_strategy(SplittingStrategy::Semantic);
+                if let Some(encoder) = semantic_encoder {
+                    builder = builder.with_semantic_encoder(encoder);
+                }
+            }
+            Some("content_defined") => {
+                builder = builder.with_splitting_strategy(SplittingStrategy::ContentDefined);
+            }
+            _ => {}
+        }
+        builder.build()
+    }
+
+    /// Everything of [`text_config`](Self::text_config) but the semantic chunker.
+    fn config_builder(&self) -> Result<TextEmbedConfigBuilder, EmbedError> {
+        let chunker = &self.chunker;
+        let mut builder = TextEmbedConfig::builder();
+        if let Some(chunk_size) = chunker.chunk_size {
+            builder = builder.with_chunk_size(chunk_size);
+        }
+        if let Some(overlap_ratio) = chunker.overlap_ratio {
+            builder = builder.with_overlap_ratio(overlap_ratio);
+        }
+        if let Some(overlap_tokens) = chunker.overlap_tokens {
+            builder = builder.with_overlap_tokens(overlap_tokens);
+        }
+        if let Some(batch_size) = chunker.batch_size {
+            builder = builder.with_batch_size(batch_size);
+        }
+        if let Some(buffer_size) = chunker.buffer_size {
+            builder = builder.with_buffer_size(buffer_size);
+        }
+        match chunker.strategy.as_deref() {
+            None | Some("sentence") | Some("semantic") | Some("content_defined") => {}
+            Some(strategy) => {
+                return Err(EmbedError::InvalidConfig(format!(
+                    "chunker.strategy must be sentence, semantic or content_defined, got '{}'",
+                    strategy
+                )))
+            }
+        }
+        let processors = &self.processors;
+        if let Some(use_ocr) = processors.use_ocr {
+            builder = builder.with_ocr(use_ocr);
+        }
+        if processors.metadata_fields.is_some()
+            || processors.max_metadata_length.is_some()
+            || processors.namespace.is_some()
+        {
+            builder = builder.with_metadata_config(MetadataConfig {
+                fields: processors.metadata_fields.clone(),
+                max_value_length: processors.max_metadata_length,
+                namespace: processors.namespace.clone(),
+            });
+        }
+        Ok(builder)
+    }
+}
+
+impl FilterSpec {
+    fn patterns(&self) -> Result<Vec<Regex>, EmbedError> {
+        self.exclude_patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    EmbedError::InvalidConfig(format!(
+                        "Invalid filters.exclude_patterns entry '{}': {}",
+                        pattern, e
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    fn keeps(&self, patterns: &[Regex], embedding: &EmbedData) -> bool {
+        let text = embedding.text.as_deref().unwrap_or_default().trim();
+        if self.min_chars.is_some_and(|min| text.chars().count() < min) {
+            return false;
+        }
+        !patterns.iter().any(|pattern| pattern.is_match(text))
+    }
+}
+
+type Adapter = Box<dyn Fn(Vec<EmbedData>) + Send + Sync>;
+
+/// A source that could not be ingested. The other sources of the run are still ingested.
+#[derive(Debug)]
+pub struct SourceError {
+    pub source: String,
+    pub embedder: String,
+    pub error: anyhow::Error,
+}
+
+/// What a run of a [`PipelineSpec`] did.
+#[derive(Debug, Default)]
+pub struct RunReport {
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+    /// The number of embeddings kept by the filters.
+    pub embedded: usize,
+    /// The number of embeddings dropped by the filters.
+    pub filtered: usize,
+    /// The number of files and pages that did not change since the last run, see
+    /// [`SpecRunner::with_state_dir`].
+    pub skipped: usize,
+    pub errors: Vec<SourceError>,
+    /// Files without text, skipped files and truncated chunks, see [`EmbedWarning`].
+    pub warnings: Vec<EmbedWarning>,
+    /// The embeddings, when the spec has no adapter.
+    pub embeddings: Vec<EmbedData>,
+}
+
+/// Runs [`PipelineSpec`]s with the adapters they refer to.
+#[derive(Default)]
+pub struct SpecRunner {
+    adapters: HashMap<String, Adapter>,
+    state_dir: Option<PathBuf>,
+}
+
+impl SpecRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the adapter a spec refers to as `adapter: <name>`.
+    pub fn with_adapter<F>(mut self, name: &str, adapter: F) -> Self
+    where
+        F: Fn(Vec<EmbedData>) + Send + Sync + 'static,
+    {
+        self.adapters.insert(name.to_string(), Box::new(adapter));
+        self
+    }
+
+    /// Remembers the content hash of every file and page embedded in `state_dir`, so later runs
+    /// only embed the sources that changed. Default is embedding every source on every run.
+    pub fn with_state_dir(mut self, state_dir: impl Into<PathBuf>) -> Self {
+        self.state_dir = Some(state_dir.into());
+        self
+    }
+
+    /// Loads the models of `spec` and ingests each of its sources with each model.
+    ///
+    /// A source that fails is reported in [`RunReport::errors`]; the run only fails when the spec
+    /// is invalid, names an unknown adapter or a model cannot be loaded.
+    pub async fn run(&self, spec: &PipelineSpec) -> anyhow::Result<RunReport> {
+        let started_at = Utc::now();
+        let started = Instant::now();
+        spec.validate()?;
+        let adapter = match &spec.adapter {
+            Some(name) => Some(self.adapters.get(name).ok_or_else(|| {
+                EmbedError::InvalidConfig(format!("No adapter registered as '{}'", name))
+            })?),
+            None => None,
+        };
+        let mut embedders = Vec::new();
+        for embedder in spec.embedders() {
+            // Loading reads and may download the weights, which would stall the runtime.
+            let name = embedder.name().to_string();
+            let embedder = embedder.clone();
+            let embedder = tokio::task::spawn_blocking(move || embedder.build()).await??;
+            embedders.push((name, Arc::new(embedder)));
+        }
+        let warnings = Warnings::new();
+        let config = spec
+            .text_config(embedders.first().map(|(_, e)| Arc::clone(e)))?
+            .with_warnings(warnings.clone());
+        let patterns = spec.filters.patterns()?;
+
+        let report = Mutex::new(RunReport::default());
+        for (name, embedder) in &embedders {
+            let sink = |embeddings: Vec<EmbedData>| {
+                let total = embeddings.len();
+                let mut embeddings = embeddings
+                    .into_iter()
+                    .filter(|embedding| spec.filters.keeps(&patterns, embedding))
+                    .collect::<Vec<_>>();
+                for embedding in &mut embeddings {
+                    embedding
+                        .metadata
+                        .get_or_insert_with(HashMap::new)
+                        .insert("embedder".to_string(), name.clone());
+                }
+                let mut report = report.lock().unwrap();
+                report.embedded += embeddings.len();
+                report.filtered += total - embeddings.len();
+                match adapter {
+                    Some(adapter) => {
+                        drop(report);
+                        adapter(embeddings);
+                    }
+                    None => report.embeddings.extend(embeddings),
+                }
+            };
+            // Each model has its own state, so adding a model embeds every source for it.
+            let state_dir = self
+                .state_dir
+                .as_ref()
+                .map(|dir| dir.join(&content_hash(name)[..16]));
+            let mut files = match &state_dir {
+                Some(dir) => Some(WebCache::open(dir.join("files.json"))?),
+                None => None,
+            };
+            for source in &spec.sources {
+                let mut errors = Vec::new();
+                match (source, &mut files, &state_dir) {
+                    (SourceSpec::Directory { path, extensions }, Some(files), _) => {
+                        match FileParser::new()
+                            .with_walk_options(config.walk)
+                            .get_text_files(path, extensions.clone())
+                        {
+                            Ok(paths) => {
+                                for path in paths {
+                                    match embed_changed_file(&path, embedder, &config, &sink, files)
+                                        .await
+                                    {
+                                        Ok(true) => {}
+                                        Ok(false) => report.lock().unwrap().skipped += 1,
+                                        Err(error) => errors.push((path, error)),
+                                    }
+                                }
+                            }
+                            Err(error) => {
+                                errors.push((source.to_string(), EmbedError::Io(error).into()))
+                            }
+                        }
+                    }
+                    (SourceSpec::Directory { path, extensions }, None, _) => {
+                        if let Err(error) = embed_directory_stream(
+                            path.clone(),
+                            embedder,
+                            extensions.clone(),
+                            Some(&config),
+                            Some(&sink),
+                        )
+                        .await
+                        {
+                            errors.push((source.to_string(), error));
+                        }
+                    }
+                    (SourceSpec::File { path }, Some(files), _) => {
+                        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+                        let path = path.to_string_lossy();
+                        match embed_changed_file(&path, embedder, &config, &sink, files).await {
+                            Ok(true) => {}
+                            Ok(false) => report.lock().unwrap().skipped += 1,
+                            Err(error) => errors.push((source.to_string(), error)),
+                        }
+                    }
+                    (SourceSpec::File { path }, None, _) => {
+                        if let Err(error) =
+                            embed_file(path, embedder, Some(&config), Some(&sink)).await
+                        {
+                            errors.push((source.to_string(), error));
+                        }
+                    }
+                    (SourceSpec::Url { url }, _, Some(dir)) => {
+                        let fetch_config =
+                            WebFetchConfig::default().with_cache_path(dir.join("pages.json"));
+                        let pages = embed_webpages(
+                            std::slice::from_ref(url),
+                            embedder,
+                            Some(&config),
+                            Some(&fetch_config),
+                            Some(&sink),
+                        )
+                        .await;
+                        match pages {
+                            Ok(pages) => {
+                                for page in pages {
+                                    if page.unchanged {
+                                        report.lock().unwrap().skipped += 1;
+                                    } else if let Err(error) = page.result {
+                                        errors.push((page.url, error));
+                                    }
+                                }
+                            }
+                            Err(error) => errors.push((source.to_string(), error)),
+                        }
+                    }
+                    (SourceSpec::Url { url }, _, None) => {
+                        if let Err(error) =
+                            embed_webpage(url.clone(), embedder, Some(&config), Some(&sink)).await
+                        {
+                            errors.push((source.to_string(), error));
+                        }
+                    }
+                }
+                report
+                    .lock()
+                    .unwrap()
+                    .errors
+                    .extend(errors.into_iter().map(|(source, error)| SourceError {
+                        source,
+                        embedder: name.clone(),
+                        error,
+                    }));
+            }
+            if let Some(files) = files {
+                files.save()?;
+            }
+        }
+        let mut report = report.into_inner().unwrap();
+        report.started_at = started_at;
+        report.duration = started.elapsed();
+        report.warnings = warnings.take();
+        Ok(report)
+    }
+}
+
+/// Embeds the file at `path` unless its content hash is the one in `files`. Returns whether the
+/// file was embedded.
+async fn embed_changed_file<F>(
+    path: &str,
+    embedder: &Embedder,
+    config: &TextEmbedConfig,
+    sink: F,
+    files: &mut WebCache,
+) -> anyhow::Result<bool>
+where
+    F: Fn(Vec<EmbedData>),
+{
+    let hash = content_hash(std::fs::read(path).map_err(EmbedError::from)?);
+    if files
+        .get(path)
+        .is_some_and(|entry| entry.content_hash == hash)
+    {
+        return Ok(false);
+    }
+    embed_file(path, embedder, Some(config), Some(sink)).await?;
+    files.insert(
+        path,
+        CacheEntry {
+            content_hash: hash,
+            ..Default::default()
+        },
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    const SPEC: &str = r#"
+name: test
+sources:
+  - type: file
+    path: ../test_files/test.txt
+  - type: directory
+    path: ../test_files
+    extensions: [md]
+chunker:
+  chunk_size: 64
+  overlap_tokens: 8
+filters:
+  min_chars: 5
+  exclude_patterns: ["^Table of contents"]
+embedder:
+  model: bert
+  model_id: sentence-transformers/all-MiniLM-L6-v2
+schedule:
+  every: 30m
+"#;
+
+    #[test]
+    fn test_parse_spec() {
+        let spec = PipelineSpec::from_yaml(SPEC).unwrap();
+        assert_eq!(spec.sources.len(), 2);
+        assert_eq!(
+            spec.sources[1],
+            SourceSpec::Directory {
+                path: PathBuf::from("../test_files"),
+                extensions: Some(vec!["md".to_string()]),
+            }
+        );
+        assert_eq!(spec.embedders().count(), 1);
+        assert_eq!(
+            spec.embedders().next().unwrap().name(),
+            "sentence-transformers/all-MiniLM-L6-v2"
+        );
+        assert_eq!(
+            spec.schedule.unwrap().interval().unwrap(),
+            Duration::from_secs(30 * 60)
+        );
+        let config = spec.text_config(None).unwrap();
+        assert_eq!(config.chunk_size, Some(64));
+        assert_eq!(config.overlap_tokens, Some(8));
+    }
+
+    #[test]
+    fn test_invalid_spec() {
+        let invalid = [
+            SPEC.replace("model: bert", "modle: bert"),
+            SPEC.replace("every: 30m", "every: soon"),
+            SPEC.replace("^Table of contents", "("),
+            SPEC.replace("overlap_tokens: 8", "overlap_tokens: 64"),
+            "sources: []\nembedder: {model: bert, model_id: x}".to_string(),
+        ];
+        for yaml in invalid {
+            assert!(matches!(
+                PipelineSpec::from_yaml(&yaml),
+                Err(EmbedError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_spec() {
+        let spec = PipelineSpec::from_yaml(SPEC).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let runner = SpecRunner::new().with_adapter("memory", {
+            let received = Arc::clone(&received);
+            move |embeddings| received.lock().unwrap().extend(embeddings)
+        });
+
+        let report = runner.run(&spec).await.unwrap();
+        assert!(report.errors.is_empty());
+        assert!(report.embedded > 0);
+        assert_eq!(report.embeddings.len(), report.embedded);
+        assert!(report.embeddings.iter().all(|embedding| {
+            embedding.metadata.as_ref().unwrap()["embedder"]
+                == "sentence-transformers/all-MiniLM-L6-v2"
+        }));
+
+        let spec = PipelineSpec {
+            adapter: Some("memory".to_string()),
+            ..spec
+        };
+        let report = runner.run(&spec).await.unwrap();
+        assert!(report.embeddings.is_empty());
+        assert_eq!(received.lock().unwrap().len(), report.embedded);
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_empty_files() {
+        let dir = TempDir::new("spec").unwrap();
+        let empty = dir.path().join("empty.txt");
+        std::fs::write(&empty, "  \n").unwrap();
+        let spec = PipelineSpec::from_yaml(
+            &SPEC.replace("../test_files/test.txt", empty.to_str().unwrap()),
+        )
+        .unwrap();
+
+        let report = SpecRunner::new().run(&spec).await.unwrap();
+        assert!(report.errors.is_empty());
+        assert!(report.warnings.iter().any(|warning| matches!(
+            warning,
+            EmbedWarning::EmptyFile { file } if file.ends_with("empty.txt")
+        )));
+    }
+}
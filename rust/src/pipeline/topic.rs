@@ -0,0 +1,404 @@
+//! Embeds documents arriving on a message topic and publishes their embeddings to another topic,
+//! so EmbedAnything can sit inside an event-driven data platform. [`run_topic_pipeline`] reads
+//! documents from a [`TopicSource`], chunks and embeds them like `embed_file` does, and hands the
+//! embeddings to a [`TopicSink`].
+//!
+//! `KafkaSource` and `KafkaSink` (feature `kafka`) and `NatsSource` and `NatsSink` (feature
+//! `nats`) connect to brokers; tokio channels implement both traits for pipelines within one
+//! process. Payloads are JSON, or Avro with the feature `avro`, see [`PayloadFormat`].
+//!
+//! Documents are committed to the source only once all their embeddings are published, so a
+//! pipeline that stops or crashes embeds the uncommitted documents again when it restarts. Every
+//! embedding is published with its [`EmbedData::id`] as the key, which makes the repeats harmless
+//! for consumers that upsert by key.
+
+#[cfg(feature = "avro")]
+pub mod avro;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "nats")]
+pub mod nats;
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::config::{TextEmbedConfig, DEFAULT_BUFFER_SIZE, DEFAULT_CHUNK_SIZE};
+use crate::embeddings::embed::{EmbedData, Embedder};
+use crate::process_chunks;
+use crate::text_loader::{SplittingStrategy, TextLoader};
+
+/// Metadata field holding the id of the document a chunk comes from, taken from the `id` of the
+/// document or else the key of its message.
+pub const DOCUMENT_ID_KEY: &str = "document_id";
+/// How long the pipeline waits for more documents before embedding the ones it has, unless set
+/// with [`TopicOptions::with_flush_interval`].
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A message of a topic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub key: Option<String>,
+    pub payload: Vec<u8>,
+}
+
+/// A document to embed, the payload of the messages of a [`TopicSource`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Document {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub text: String,
+    /// Copied to the metadata of every embedding of the document.
+    #[serde(default)]
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+/// Where the documents of [`run_topic_pipeline`] come from.
+pub trait TopicSource {
+    /// Waits for the next message, or returns `None` once the topic is closed. The pipeline stops
+    /// waiting after the flush interval, so the future must be safe to drop.
+    fn recv(&mut self) -> impl Future<Output = anyhow::Result<Option<Message>>>;
+
+    /// Marks the messages received so far as processed, so they are not delivered again.
+    fn commit(&mut self) -> impl Future<Output = anyhow::Result<()>>;
+}
+
+/// Where [`run_topic_pipeline`] publishes the embeddings.
+pub trait TopicSink {
+    /// Publishes `messages` and returns once they are delivered.
+    fn publish(&mut self, messages: Vec<Message>) -> impl Future<Output = anyhow::Result<()>>;
+}
+
+impl TopicSource for mpsc::Receiver<Message> {
+    async fn recv(&mut self) -> anyhow::Result<Option<Message>> {
+        Ok(mpsc::Receiver::recv(self).await)
+    }
+
+    async fn commit(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl TopicSink for mpsc::Sender<Message> {
+    async fn publish(&mut self, messages: Vec<Message>) -> anyhow::Result<()> {
+        for message in messages {
+            self.send(message)
+                .await
+                .map_err(|_| anyhow::anyhow!("The receiver of the topic is closed"))?;
+        }
+        Ok(())
+    }
+}
+
+/// How documents and embeddings are encoded in the payloads of messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// Documents are JSON objects with a `text` and an optional `id` and `metadata`; embeddings
+    /// the JSON of [`EmbedData::to_json`].
+    #[default]
+    Json,
+    /// Single Avro datums without a header, written with the schemas in [`avro`].
+    #[cfg(feature = "avro")]
+    Avro,
+}
+
+impl PayloadFormat {
+    pub fn decode_document(&self, payload: &[u8]) -> anyhow::Result<Document> {
+        match self {
+            PayloadFormat::Json => Ok(serde_json::from_slice(payload)?),
+            #[cfg(feature = "avro")]
+            PayloadFormat::Avro => avro::decode_document(payload),
+        }
+    }
+
+    pub fn encode_embedding(&self, embedding: &EmbedData) -> anyhow::Result<Vec<u8>> {
+        match self {
+            PayloadFormat::Json => Ok(embedding.to_json()?.into_bytes()),
+            #[cfg(feature = "avro")]
+            PayloadFormat::Avro => avro::encode_embedding(embedding),
+        }
+    }
+}
+
+/// How [`run_topic_pipeline`] reads and publishes messages.
+#[derive(Debug, Clone, Copy)]
+pub struct TopicOptions {
+    pub format: PayloadFormat,
+    /// How long the pipeline waits for more documents before embedding and committing the ones
+    /// it has, so documents on a quiet topic are not held back until the buffer is full.
+    pub flush_interval: Duration,
+}
+
+impl Default for TopicOptions {
+    fn default() -> Self {
+        Self {
+            format: PayloadFormat::default(),
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+        }
+    }
+}
+
+impl TopicOptions {
+    pub fn with_format(mut self, format: PayloadFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+}
+
+/// What [`run_topic_pipeline`] did.
+#[derive(Debug, Clone, Default)]
+pub struct TopicReport {
+    /// Documents read and embedded.
+    pub documents: usize,
+    /// Embeddings published.
+    pub embeddings: usize,
+    /// Messages whose payload is not a document, skipped with a warning.
+    pub skipped: usize,
+}
+
+/// Chunks received and not embedded yet.
+#[derive(Default)]
+struct Pending {
+    chunks: Vec<String>,
+    metadata: Vec<Option<HashMap<String, String>>>,
+    /// Whether messages were received since the last commit, including skipped ones.
+    received: bool,
+}
+
+/// Embeds the documents of `source` with the chunking and batching of `config` and publishes
+/// their embeddings to `sink`, until `source` is closed. Chunks are embedded once the buffer of
+/// `config` is full, or once no document arrived for `options.flush_interval`; the source is
+/// committed after their embeddings are published.
+///
+/// Messages that are not documents are skipped. Errors of the source, the sink or the model stop
+/// the pipeline without committing, so the documents not committed yet are delivered again.
+///
+/// ```no_run
+/// use std::sync::Arc;
+///
+/// use embed_anything::embeddings::embed::Embedder;
+/// use embed_anything::pipeline::topic::{run_topic_pipeline, Message, TopicOptions};
+/// use tokio::sync::mpsc;
+///
+/// # async fn run(embedder: Arc<Embedder>) -> anyhow::Result<()> {
+/// let (documents, source) = mpsc::channel(100);
+/// let (sink, mut embeddings) = mpsc::channel::<Message>(100);
+/// tokio::spawn(async move {
+///     while let Some(message) = embeddings.recv().await {
+///         println!("{:?}", message.key);
+///     }
+/// });
+/// documents
+///     .send(Message {
+///         key: Some("doc-1".to_string()),
+///         payload: br#"{"text": "Hello"}"#.to_vec(),
+///     })
+///     .await?;
+/// drop(documents);
+/// run_topic_pipeline(source, sink, &embedder, None, &TopicOptions::default()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn run_topic_pipeline<S, K>(
+    mut source: S,
+    mut sink: K,
+    embedder: &Arc<Embedder>,
+    config: Option<&TextEmbedConfig>,
+    options: &TopicOptions,
+) -> anyhow::Result<TopicReport>
+where
+    S: TopicSource,
+    K: TopicSink,
+{
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let buffer_size = config.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE).max(1);
+    let textloader = TextLoader::with_chunk_unit(
+        config.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE),
+        config.effective_overlap_ratio(),
+        config.chunk_unit,
+        embedder.tokenizer(),
+    )?;
+    let splitting_strategy = config
+        .splitting_strategy
+        .unwrap_or(SplittingStrategy::Sentence);
+
+    let mut report = TopicReport::default();
+    let mut pending = Pending::default();
+    loop {
+        let message = match tokio::time::timeout(options.flush_interval, source.recv()).await {
+            Ok(message) => message?,
+            Err(_) => {
+                report.embeddings += flush(
+                    &mut pending,
+                    &mut source,
+                    &mut sink,
+                    embedder,
+                    config,
+                    options,
+                )
+                .await?;
+                continue;
+            }
+        };
+        let Some(message) = message else {
+            report.embeddings += flush(
+                &mut pending,
+                &mut source,
+                &mut sink,
+                embedder,
+                config,
+                options,
+            )
+            .await?;
+            return Ok(report);
+        };
+        pending.received = true;
+        let document = match options.format.decode_document(&message.payload) {
+            Ok(document) => document,
+            Err(e) => {
+                tracing::warn!("Skipping a message that is not a document: {:?}", e);
+                report.skipped += 1;
+                continue;
+            }
+        };
+
+        let mut metadata = document.metadata.unwrap_or_default();
+        if let Some(id) = document.id.or(message.key) {
+            metadata.insert(DOCUMENT_ID_KEY.to_string(), id);
+        }
+        let chunks = textloader
+            .split_into_chunks(
+                &document.text,
+                splitting_strategy,
+                config.semantic_encoder.clone(),
+            )
+            .unwrap_or_default();
+        pending
+            .metadata
+            .extend(std::iter::repeat_n(Some(metadata), chunks.len()));
+        pending.chunks.extend(chunks);
+        report.documents += 1;
+
+        if pending.chunks.len() >= buffer_size {
+            report.embeddings += flush(
+                &mut pending,
+                &mut source,
+                &mut sink,
+                embedder,
+                config,
+                options,
+            )
+            .await?;
+        }
+    }
+}
+
+/// Embeds the pending chunks, publishes their embeddings and commits the source. Returns the
+/// number of embeddings published.
+async fn flush<S: TopicSource, K: TopicSink>(
+    pending: &mut Pending,
+    source: &mut S,
+    sink: &mut K,
+    embedder: &Arc<Embedder>,
+    config: &TextEmbedConfig,
+    options: &TopicOptions,
+) -> anyhow::Result<usize> {
+    if !pending.received {
+        return Ok(0);
+    }
+    let mut published = 0;
+    if !pending.chunks.is_empty() {
+        let mut embeddings = process_chunks(
+            &pending.chunks,
+            &pending.metadata,
+            embedder,
+            config.batch_size,
+            &config.backoff,
+            config.length_sorted_batching.unwrap_or(false),
+            config.invalid_embeddings,
+            config.warnings.as_ref(),
+        )
+        .await?;
+        config.apply_metadata_config(&mut embeddings);
+        let messages = embeddings
+            .iter()
+            .map(|embedding| {
+                Ok(Message {
+                    key: Some(embedding.id()),
+                    payload: options.format.encode_embedding(embedding)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        published = messages.len();
+        sink.publish(messages).await?;
+    }
+    source.commit().await?;
+    *pending = Pending::default();
+    Ok(published)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::embed::TextEmbedder;
+    use crate::embeddings::local::bert::BertEmbedder;
+
+    #[test]
+    fn test_decode_json_document() {
+        let document = PayloadFormat::Json
+            .decode_document(br#"{"text": "Hello", "metadata": {"lang": "en"}}"#)
+            .unwrap();
+        assert_eq!(document.id, None);
+        assert_eq!(document.text, "Hello");
+        assert_eq!(document.metadata.unwrap()["lang"], "en");
+        assert!(PayloadFormat::Json.decode_document(b"not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_topic_pipeline() {
+        let embedder = Arc::new(Embedder::Text(TextEmbedder::Bert(Box::new(
+            BertEmbedder::default(),
+        ))));
+        let (documents, source) = mpsc::channel(10);
+        let (sink, mut embeddings) = mpsc::channel(10);
+        for (key, payload) in [
+            ("a", r#"{"text": "The first document."}"#),
+            ("b", "not a document"),
+            ("c", r#"{"id": "doc-c", "text": "The second document."}"#),
+        ] {
+            documents
+                .send(Message {
+                    key: Some(key.to_string()),
+                    payload: payload.as_bytes().to_vec(),
+                })
+                .await
+                .unwrap();
+        }
+        drop(documents);
+
+        let report = run_topic_pipeline(source, sink, &embedder, None, &TopicOptions::default())
+            .await
+            .unwrap();
+        assert_eq!((report.documents, report.skipped), (2, 1));
+        assert_eq!(report.embeddings, 2);
+
+        let mut ids = Vec::new();
+        while let Some(message) = embeddings.recv().await {
+            let embedding =
+                EmbedData::from_json(std::str::from_utf8(&message.payload).unwrap()).unwrap();
+            assert_eq!(message.key.unwrap(), embedding.id());
+            ids.push(embedding.metadata.unwrap()[DOCUMENT_ID_KEY].clone());
+        }
+        assert_eq!(ids, ["a", "doc-c"]);
+    }
+}
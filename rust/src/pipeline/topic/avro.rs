@@ -0,0 +1,131 @@
+//! Avro payloads for [`run_topic_pipeline`](super::run_topic_pipeline). Every payload is a single
+//! datum without a header or schema id, written with [`DOCUMENT_SCHEMA`] or [`EMBEDDING_SCHEMA`];
+//! register the schemas wherever the consumers of the topics look them up.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use apache_avro::types::Value;
+use apache_avro::{from_avro_datum, from_value, to_avro_datum, Schema};
+
+use super::Document;
+use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+
+/// The schema of the documents read from the source topic.
+pub const DOCUMENT_SCHEMA: &str = r#"{
+    "type": "record",
+    "name": "Document",
+    "namespace": "embed_anything",
+    "fields": [
+        {"name": "id", "type": ["null", "string"], "default": null},
+        {"name": "text", "type": "string"},
+        {"name": "metadata", "type": ["null", {"type": "map", "values": "string"}], "default": null}
+    ]
+}"#;
+
+/// The schema of the embeddings published to the sink topic. A dense embedding is one vector,
+/// a multi-vector embedding one vector per token or patch.
+pub const EMBEDDING_SCHEMA: &str = r#"{
+    "type": "record",
+    "name": "Embedding",
+    "namespace": "embed_anything",
+    "fields": [
+        {"name": "id", "type": "string"},
+        {"name": "text", "type": ["null", "string"], "default": null},
+        {"name": "vectors", "type": {"type": "array", "items": {"type": "array", "items": "float"}}},
+        {"name": "multi_vector", "type": "boolean"},
+        {"name": "metadata", "type": {"type": "map", "values": "string"}}
+    ]
+}"#;
+
+fn document_schema() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| Schema::parse_str(DOCUMENT_SCHEMA).unwrap())
+}
+
+fn embedding_schema() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| Schema::parse_str(EMBEDDING_SCHEMA).unwrap())
+}
+
+pub fn decode_document(payload: &[u8]) -> anyhow::Result<Document> {
+    let value = from_avro_datum(document_schema(), &mut &payload[..], None)?;
+    Ok(from_value(&value)?)
+}
+
+pub fn encode_embedding(embedding: &EmbedData) -> anyhow::Result<Vec<u8>> {
+    let (vectors, multi_vector) = match &embedding.embedding {
+        EmbeddingResult::DenseVector(vector) => (vec![vector.clone()], false),
+        EmbeddingResult::MultiVector(vectors) => (vectors.clone(), true),
+    };
+    let text = match &embedding.text {
+        Some(text) => Value::Union(1, Box::new(Value::String(text.clone()))),
+        None => Value::Union(0, Box::new(Value::Null)),
+    };
+    let metadata = embedding
+        .metadata
+        .iter()
+        .flatten()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect::<HashMap<_, _>>();
+    let record = Value::Record(vec![
+        ("id".to_string(), Value::String(embedding.id())),
+        ("text".to_string(), text),
+        (
+            "vectors".to_string(),
+            Value::Array(
+                vectors
+                    .into_iter()
+                    .map(|vector| Value::Array(vector.into_iter().map(Value::Float).collect()))
+                    .collect(),
+            ),
+        ),
+        ("multi_vector".to_string(), Value::Boolean(multi_vector)),
+        ("metadata".to_string(), Value::Map(metadata)),
+    ]);
+    Ok(to_avro_datum(embedding_schema(), record)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_avro_payloads() {
+        let document = Value::Record(vec![
+            (
+                "id".to_string(),
+                Value::Union(1, Box::new(Value::String("doc-1".to_string()))),
+            ),
+            ("text".to_string(), Value::String("Hello".to_string())),
+            (
+                "metadata".to_string(),
+                Value::Union(0, Box::new(Value::Null)),
+            ),
+        ]);
+        let payload = to_avro_datum(document_schema(), document).unwrap();
+        assert_eq!(
+            decode_document(&payload).unwrap(),
+            Document {
+                id: Some("doc-1".to_string()),
+                text: "Hello".to_string(),
+                metadata: None,
+            }
+        );
+
+        let embedding = EmbedData::new(vec![0.5, 1.0].into(), Some("Hello".to_string()), None);
+        let payload = encode_embedding(&embedding).unwrap();
+        let value = from_avro_datum(embedding_schema(), &mut &payload[..], None).unwrap();
+        let Value::Record(fields) = value else {
+            panic!("not a record");
+        };
+        assert_eq!(fields[0].1, Value::String(embedding.id()));
+        assert_eq!(
+            fields[2].1,
+            Value::Array(vec![Value::Array(vec![
+                Value::Float(0.5),
+                Value::Float(1.0)
+            ])])
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! A [`TopicSource`] and a [`TopicSink`] for Kafka topics. The source reads as a member of a
+//! consumer group and commits its offsets only through [`TopicSource::commit`], so documents
+//! whose embeddings were not published yet are read again by the group after a restart.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::error::KafkaError;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::{ClientConfig, Message as _, Offset, TopicPartitionList};
+
+use super::{Message, TopicSink, TopicSource};
+use crate::error::EmbedError;
+
+/// How long the sink waits for room in the queue of the producer before failing.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct KafkaSource {
+    consumer: StreamConsumer,
+    /// The next offset to commit for every partition read from since the last commit.
+    offsets: HashMap<(String, i32), i64>,
+}
+
+impl KafkaSource {
+    /// Reads `topic` from the brokers at `brokers`, e.g. `localhost:9092`, as a member of the
+    /// consumer group `group_id`. A new group starts at the earliest message.
+    pub fn new(brokers: &str, group_id: &str, topic: &str) -> anyhow::Result<Self> {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", brokers)
+            .set("group.id", group_id)
+            .set("auto.offset.reset", "earliest");
+        Self::from_config(config, topic)
+    }
+
+    /// Reads `topic` with a consumer created from `config`, for settings such as authentication.
+    /// Automatic commits are turned off.
+    pub fn from_config(mut config: ClientConfig, topic: &str) -> anyhow::Result<Self> {
+        let consumer: StreamConsumer = config
+            .set("enable.auto.commit", "false")
+            .create()
+            .map_err(kafka_error)?;
+        consumer.subscribe(&[topic]).map_err(kafka_error)?;
+        Ok(Self {
+            consumer,
+            offsets: HashMap::new(),
+        })
+    }
+}
+
+impl TopicSource for KafkaSource {
+    async fn recv(&mut self) -> anyhow::Result<Option<Message>> {
+        let message = self.consumer.recv().await.map_err(kafka_error)?;
+        self.offsets.insert(
+            (message.topic().to_string(), message.partition()),
+            message.offset() + 1,
+        );
+        Ok(Some(Message {
+            key: message
+                .key()
+                .map(|key| String::from_utf8_lossy(key).into_owned()),
+            payload: message.payload().unwrap_or_default().to_vec(),
+        }))
+    }
+
+    async fn commit(&mut self) -> anyhow::Result<()> {
+        if self.offsets.is_empty() {
+            return Ok(());
+        }
+        let mut partitions = TopicPartitionList::new();
+        for ((topic, partition), offset) in self.offsets.drain() {
+            partitions
+                .add_partition_offset(&topic, partition, Offset::Offset(offset))
+                .map_err(kafka_error)?;
+        }
+        self.consumer
+            .commit(&partitions, CommitMode::Async)
+            .map_err(kafka_error)?;
+        Ok(())
+    }
+}
+
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+}
+
+impl KafkaSink {
+    /// Publishes to `topic` of the brokers at `brokers`, e.g. `localhost:9092`.
+    pub fn new(brokers: &str, topic: &str) -> anyhow::Result<Self> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+        Self::from_config(config, topic)
+    }
+
+    /// Publishes to `topic` with a producer created from `config`, for settings such as
+    /// authentication or compression.
+    pub fn from_config(config: ClientConfig, topic: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            producer: config.create().map_err(kafka_error)?,
+            topic: topic.to_string(),
+        })
+    }
+}
+
+impl TopicSink for KafkaSink {
+    async fn publish(&mut self, messages: Vec<Message>) -> anyhow::Result<()> {
+        let deliveries = messages.iter().map(|message| {
+            let mut record = FutureRecord::to(&self.topic).payload(&message.payload);
+            if let Some(key) = &message.key {
+                record = record.key(key.as_str());
+            }
+            self.producer.send(record, QUEUE_TIMEOUT)
+        });
+        futures::future::try_join_all(deliveries)
+            .await
+            .map_err(|(e, _)| kafka_error(e))?;
+        Ok(())
+    }
+}
+
+fn kafka_error(e: KafkaError) -> EmbedError {
+    EmbedError::Network(format!("Kafka: {}", e))
+}
@@ -0,0 +1,104 @@
+//! A [`TopicSource`] and a [`TopicSink`] for NATS subjects. Core NATS does not keep messages, so
+//! documents published while no pipeline is subscribed, or not embedded yet when it stops, are
+//! lost: commits are no-ops. Pipelines sharing a queue group split the documents between them.
+//!
+//! The sink sets the `Nats-Msg-Id` header to the id of the embedding, so a JetStream stream on
+//! the sink subject drops the repeats within its duplicate window.
+
+use async_nats::header::NATS_MESSAGE_ID;
+use async_nats::{Client, HeaderMap, Subscriber};
+use futures::StreamExt;
+
+use super::{Message, TopicSink, TopicSource};
+use crate::error::EmbedError;
+
+pub struct NatsSource {
+    subscriber: Subscriber,
+}
+
+impl NatsSource {
+    /// Subscribes to `subject` on the server at `url`, e.g. `nats://localhost:4222`, as a member
+    /// of `queue_group` if given.
+    pub async fn new(url: &str, subject: &str, queue_group: Option<&str>) -> anyhow::Result<Self> {
+        let client = async_nats::connect(url).await.map_err(nats_error)?;
+        Self::with_client(&client, subject, queue_group).await
+    }
+
+    /// Subscribes to `subject` with a client that is already connected.
+    pub async fn with_client(
+        client: &Client,
+        subject: &str,
+        queue_group: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let subscriber = match queue_group {
+            Some(queue_group) => {
+                client
+                    .queue_subscribe(subject.to_string(), queue_group.to_string())
+                    .await
+            }
+            None => client.subscribe(subject.to_string()).await,
+        }
+        .map_err(nats_error)?;
+        Ok(Self { subscriber })
+    }
+}
+
+impl TopicSource for NatsSource {
+    async fn recv(&mut self) -> anyhow::Result<Option<Message>> {
+        Ok(self.subscriber.next().await.map(|message| Message {
+            key: message
+                .headers
+                .as_ref()
+                .and_then(|headers| headers.get(NATS_MESSAGE_ID))
+                .map(|id| id.as_str().to_string()),
+            payload: message.payload.to_vec(),
+        }))
+    }
+
+    async fn commit(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct NatsSink {
+    client: Client,
+    subject: String,
+}
+
+impl NatsSink {
+    /// Publishes to `subject` on the server at `url`, e.g. `nats://localhost:4222`.
+    pub async fn new(url: &str, subject: &str) -> anyhow::Result<Self> {
+        let client = async_nats::connect(url).await.map_err(nats_error)?;
+        Ok(Self::with_client(client, subject))
+    }
+
+    /// Publishes to `subject` with a client that is already connected.
+    pub fn with_client(client: Client, subject: &str) -> Self {
+        Self {
+            client,
+            subject: subject.to_string(),
+        }
+    }
+}
+
+impl TopicSink for NatsSink {
+    async fn publish(&mut self, messages: Vec<Message>) -> anyhow::Result<()> {
+        for message in messages {
+            let mut headers = HeaderMap::new();
+            if let Some(key) = &message.key {
+                headers.insert(NATS_MESSAGE_ID, key.as_str());
+            }
+            self.client
+                .publish_with_headers(self.subject.clone(), headers, message.payload.into())
+                .await
+                .map_err(nats_error)?;
+        }
+        // Publishing only queues the messages in the client.
+        self.client.flush().await.map_err(nats_error)?;
+        Ok(())
+    }
+}
+
+fn nats_error(e: impl std::fmt::Display) -> EmbedError {
+    EmbedError::Network(format!("NATS: {}", e))
+}
@@ -0,0 +1,27 @@
+//! Progress reporting for the directory pipelines. Without the `progress` feature the bar is a
+//! no-op and `indicatif` is not compiled in.
+
+#[cfg(feature = "progress")]
+pub(crate) fn progress_bar(len: u64) -> indicatif::ProgressBar {
+    let pb = indicatif::ProgressBar::new(len);
+    pb.set_style(
+        indicatif::ProgressStyle::with_template(
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+        )
+        .unwrap(),
+    );
+    pb
+}
+
+#[cfg(not(feature = "progress"))]
+pub(crate) struct ProgressBar;
+
+#[cfg(not(feature = "progress"))]
+impl ProgressBar {
+    pub(crate) fn inc(&self, _delta: u64) {}
+}
+
+#[cfg(not(feature = "progress"))]
+pub(crate) fn progress_bar(_len: u64) -> ProgressBar {
+    ProgressBar
+}
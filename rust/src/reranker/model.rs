@@ -1,16 +1,21 @@
 use anyhow::{Error as E, Result};
-use candle_core::{Device, Tensor};
-use hf_hub::{api::sync::Api, Repo};
 use ndarray::Array2;
 use ort::{
-    execution_providers::{CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProvider},
+    execution_providers::{CUDAExecutionProvider, ExecutionProvider},
     session::{builder::GraphOptimizationLevel, Session},
+    value::Value,
 };
 use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
 use crate::embeddings::local::bert::TokenizerConfig;
+use crate::embeddings::local::io_binding::{io_binding_available, run_session};
+use crate::embeddings::local::providers::execution_providers;
+use crate::embeddings::ComputeDevice;
+use crate::error::EmbedError;
+use crate::hub::HubRepo;
 use crate::Dtype;
 use serde::Serialize;
+use strum::EnumString;
 
 #[derive(Debug, Serialize)]
 pub struct RerankerResult {
@@ -25,26 +30,94 @@ pub struct DocumentRank {
     pub rank: usize,
 }
 
+/// A document scored by [`Reranker::rerank`]. `index` is the position of the document in the
+/// slice that was reranked.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct RerankScore {
+    pub index: usize,
+    pub score: f32,
+}
+
+/// How raw reranker logits are turned into scores. Rerankers output logits on different scales,
+/// so thresholds are only portable between models after normalization.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum ScoreNormalization {
+    /// Squashes each logit into `(0, 1)`.
+    #[default]
+    Sigmoid,
+    /// Rescales the scores of each query to `[0, 1]`, the best document getting 1. Scores are only
+    /// comparable within one query.
+    #[strum(serialize = "minmax", serialize = "min-max")]
+    MinMax,
+    /// Returns the raw logits.
+    None,
+}
+
+impl ScoreNormalization {
+    /// Normalizes the scores of one query in place. `offset` is added to every logit first.
+    fn apply(&self, scores: &mut [f32], offset: f32) {
+        scores.iter_mut().for_each(|score| *score += offset);
+        match self {
+            ScoreNormalization::Sigmoid => scores
+                .iter_mut()
+                .for_each(|score| *score = 1.0 / (1.0 + (-*score).exp())),
+            ScoreNormalization::MinMax => {
+                let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                let range = max - min;
+                scores.iter_mut().for_each(|score| {
+                    *score = if range > 0.0 {
+                        (*score - min) / range
+                    } else {
+                        1.0
+                    }
+                });
+            }
+            ScoreNormalization::None => {}
+        }
+    }
+}
+
+/// Number of query-document pairs scored per forward pass unless set with
+/// [`Reranker::with_batch_size`].
+pub const DEFAULT_RERANK_BATCH_SIZE: usize = 32;
+
+/// A cross-encoder that scores how relevant documents are to a query.
+///
+/// ```no_run
+/// use embed_anything::{Dtype, Reranker};
+///
+/// let reranker = Reranker::new("jinaai/jina-reranker-v2-base-multilingual", None, Dtype::F16)?;
+/// let documents = ["Paris is the capital of France.", "France is in Europe."];
+/// for hit in reranker.rerank("What is the capital of France?", &documents, 1)? {
+///     println!("{} {}", documents[hit.index], hit.score);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
 pub struct Reranker {
     model: Session,
     tokenizer: Tokenizer,
+    batch_size: usize,
+    normalization: ScoreNormalization,
+    calibration_offset: f32,
+    io_binding: bool,
 }
 
 impl Reranker {
     pub fn new(model_id: &str, revision: Option<&str>, dtype: Dtype) -> Result<Self, E> {
+        Self::new_on_device(model_id, revision, dtype, ComputeDevice::Auto)
+    }
+
+    /// Like [`new`](Self::new), but loads the model on `device`.
+    pub fn new_on_device(
+        model_id: &str,
+        revision: Option<&str>,
+        dtype: Dtype,
+        device: ComputeDevice,
+    ) -> Result<Self, E> {
         let (_, tokenizer_filename, weights_filename, tokenizer_config_filename) = {
-            let api = Api::new().unwrap();
-            let api = match revision {
-                Some(rev) => api.repo(Repo::with_revision(
-                    model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                    rev.to_string(),
-                )),
-                None => api.repo(hf_hub::Repo::new(
-                    model_id.to_string(),
-                    hf_hub::RepoType::Model,
-                )),
-            };
+            let api = HubRepo::new(model_id, revision)?;
             let config = api.get("config.json")?;
             let tokenizer = api.get("tokenizer.json")?;
             let tokenizer_config = api.get("tokenizer_config.json")?;
@@ -57,6 +130,7 @@ impl Reranker {
                 Dtype::BNB4 => api.get("onnx/model_bnb4.onnx")?,
                 Dtype::F32 => api.get("onnx/model.onnx")?,
                 Dtype::QUANTIZED => api.get("onnx/model_quantized.onnx")?,
+                Dtype::BF16 => api.get("onnx/model_bf16.onnx")?,
             };
             (config, tokenizer, weights, tokenizer_config)
         };
@@ -92,24 +166,89 @@ impl Reranker {
         let cuda = CUDAExecutionProvider::default();
 
         if !cuda.is_available()? {
-            eprintln!("CUDAExecutionProvider is not available");
+            tracing::warn!("CUDAExecutionProvider is not available");
         } else {
-            println!("Session is using CUDAExecutionProvider");
+            tracing::info!("Session is using CUDAExecutionProvider");
         }
 
         let threads = std::thread::available_parallelism().unwrap().get();
         let model = Session::builder()?
-            .with_execution_providers([
-                CUDAExecutionProvider::default().build(),
-                CoreMLExecutionProvider::default().build(),
-            ])?
+            .with_execution_providers(execution_providers(device))?
             .with_optimization_level(GraphOptimizationLevel::Level3)?
             .with_intra_threads(threads)?
             .commit_from_file(weights_filename)?;
 
-        Ok(Reranker { model, tokenizer })
+        Ok(Reranker {
+            model,
+            tokenizer,
+            batch_size: DEFAULT_RERANK_BATCH_SIZE,
+            normalization: ScoreNormalization::default(),
+            calibration_offset: 0.0,
+            io_binding: io_binding_available() && device.allows_cuda(),
+        })
+    }
+
+    /// Sets how logits are turned into scores. Defaults to [`ScoreNormalization::Sigmoid`].
+    pub fn with_normalization(mut self, normalization: ScoreNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Adds `offset` to every logit before normalization, shifting where a model's scores cross
+    /// 0.5 under sigmoid so one threshold works across rerankers.
+    pub fn with_calibration_offset(mut self, offset: f32) -> Self {
+        self.calibration_offset = offset;
+        self
+    }
+
+    /// Turns copying the logits into pinned host memory through an IoBinding on or off. On by
+    /// default when ORT can use CUDA.
+    pub fn with_io_binding(mut self, enabled: bool) -> Self {
+        self.io_binding = enabled;
+        self
+    }
+
+    /// Sets how many query-document pairs [`Reranker::rerank`] and [`Reranker::rerank_batch`]
+    /// score per forward pass.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Scores `documents` against `query` and returns the `top_k` most relevant ones, best first.
+    /// Scores are normalized as set with [`Reranker::with_normalization`].
+    pub fn rerank(
+        &self,
+        query: &str,
+        documents: &[&str],
+        top_k: usize,
+    ) -> Result<Vec<RerankScore>, E> {
+        Ok(self
+            .rerank_batch(&[query], documents, top_k)?
+            .pop()
+            .unwrap_or_default())
     }
 
+    /// Reranks the same `documents` for several queries in one pass over the model. The result
+    /// holds the `top_k` documents of each query, in the order of `queries`.
+    pub fn rerank_batch(
+        &self,
+        queries: &[&str],
+        documents: &[&str],
+        top_k: usize,
+    ) -> Result<Vec<Vec<RerankScore>>, E> {
+        if queries.is_empty() || documents.is_empty() {
+            return Ok(vec![Vec::new(); queries.len()]);
+        }
+        let scores = self.compute_scores(queries.to_vec(), documents.to_vec(), self.batch_size)?;
+        Ok(scores
+            .iter()
+            .map(|scores| top_k_scores(scores, top_k))
+            .collect())
+    }
+
+    /// Scores every query-document pair. Row `i` holds the normalized scores of `queries[i]`, in
+    /// the order of `documents`.
     pub fn compute_scores(
         &self,
         queries: Vec<&str>,
@@ -124,12 +263,14 @@ impl Reranker {
         for pair in pairs.chunks(batch_size) {
             let input_ids = self.tokenize_batch_ndarray(pair)?;
             let attention_mask = self.get_attention_mask_ndarray(pair)?;
-            let outputs = self
-                .model
-                .run(ort::inputs!["input_ids" => input_ids, "attention_mask" => attention_mask]?)?;
-            let logits = outputs["logits"]
-                .try_extract_tensor::<f32>()?
-                .to_owned()
+            let inputs = vec![
+                ("input_ids", Value::from_array(input_ids)?.into_dyn()),
+                (
+                    "attention_mask",
+                    Value::from_array(attention_mask)?.into_dyn(),
+                ),
+            ];
+            let logits = run_session(&self.model, inputs, "logits", self.io_binding)?
                 .into_dimensionality::<ndarray::Ix2>()?;
             scores.extend(
                 logits
@@ -138,16 +279,18 @@ impl Reranker {
                     .collect::<Vec<_>>(),
             );
         }
-        let scores_tensor = Tensor::from_vec(
-            scores.clone(),
-            (queries.len(), documents.len()),
-            &Device::Cpu,
-        )?;
-        let sigmoid_scores = candle_nn::ops::sigmoid(&scores_tensor).unwrap();
-        Ok(sigmoid_scores.to_vec2::<f32>()?)
+        Ok(scores
+            .chunks_mut(documents.len().max(1))
+            .map(|scores| {
+                self.normalization.apply(scores, self.calibration_offset);
+                scores.to_vec()
+            })
+            .collect())
     }
 
-    pub fn rerank(
+    /// Scores every document for every query and returns all documents with their rank. Use
+    /// [`Reranker::rerank`] to get only the best documents.
+    pub fn rank(
         &self,
         queries: Vec<&str>,
         documents: Vec<&str>,
@@ -185,7 +328,7 @@ impl Reranker {
         let token_ids = self
             .tokenizer
             .encode_batch(pairs.to_vec(), true)
-            .map_err(E::msg)?
+            .map_err(EmbedError::tokenization)?
             .iter()
             .map(|tokens| {
                 tokens
@@ -211,7 +354,7 @@ impl Reranker {
         let attention_mask = self
             .tokenizer
             .encode_batch(pairs.to_vec(), true)
-            .map_err(E::msg)?
+            .map_err(EmbedError::tokenization)?
             .iter()
             .map(|tokens| {
                 tokens
@@ -230,3 +373,60 @@ impl Reranker {
         Ok(attention_mask_array)
     }
 }
+
+/// Sorts `scores` in descending order and keeps the `top_k` best, remembering their positions.
+fn top_k_scores(scores: &[f32], top_k: usize) -> Vec<RerankScore> {
+    let mut ranked = scores
+        .iter()
+        .enumerate()
+        .map(|(index, &score)| RerankScore { index, score })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked.truncate(top_k);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_k_scores() {
+        let ranked = top_k_scores(&[0.2, 0.9, 0.5], 2);
+        assert_eq!(
+            ranked,
+            vec![
+                RerankScore {
+                    index: 1,
+                    score: 0.9
+                },
+                RerankScore {
+                    index: 2,
+                    score: 0.5
+                },
+            ]
+        );
+        assert_eq!(top_k_scores(&[0.2], 5).len(), 1);
+    }
+
+    #[test]
+    fn test_score_normalization() {
+        let mut scores = vec![-2.0, 0.0, 2.0];
+        ScoreNormalization::Sigmoid.apply(&mut scores, 0.0);
+        assert!((scores[1] - 0.5).abs() < 1e-6);
+        assert!(scores[0] < scores[1] && scores[1] < scores[2]);
+
+        let mut scores = vec![-2.0, 0.0, 2.0];
+        ScoreNormalization::MinMax.apply(&mut scores, 0.0);
+        assert_eq!(scores, vec![0.0, 0.5, 1.0]);
+
+        let mut scores = vec![1.0];
+        ScoreNormalization::None.apply(&mut scores, -0.5);
+        assert_eq!(scores, vec![0.5]);
+
+        assert_eq!(
+            "min-max".parse::<ScoreNormalization>().unwrap(),
+            ScoreNormalization::MinMax
+        );
+    }
+}
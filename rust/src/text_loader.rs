@@ -6,26 +6,93 @@ use std::{
 };
 
 use crate::{
-    chunkers::statistical::StatisticalChunker,
+    chunkers::{
+        content_defined::ContentDefinedChunker, statistical::StatisticalChunker,
+        token::TokenChunker,
+    },
     embeddings::{embed::TextEmbedder, local::jina::JinaEmbedder},
-    file_processor::docx_processor::DocxProcessor,
+    file_processor::{
+        csv_processor::CsvProcessor,
+        docx_processor::{DocxProcessor, DocxTextOptions},
+        epub_processor::EpubProcessor,
+        json_processor::{JsonProcessor, JsonTextOptions},
+        pptx_processor::PptxProcessor,
+        table::Table,
+        xlsx_processor::XlsxProcessor,
+    },
 };
 use crate::{
     embeddings::embed::Embedder,
-    file_processor::{markdown_processor::MarkdownProcessor, txt_processor::TxtProcessor},
+    error::EmbedError,
+    file_processor::{
+        markdown_processor::MarkdownProcessor,
+        txt_processor::{TxtProcessor, STREAM_BLOCK_SIZE},
+    },
 };
 use anyhow::Error;
 use chrono::{DateTime, Local};
-use text_splitter::{ChunkConfig, TextSplitter};
+use text_splitter::{Characters, ChunkConfig, TextSplitter};
 use tokenizers::Tokenizer;
 
-use super::file_processor::pdf_processor::PdfProcessor;
-use rayon::prelude::*;
+use super::file_processor::pdf_processor::{PdfProcessor, PdfTextOptions};
+
+/// Metadata field holding the title of the outline section a chunk of a PDF comes from.
+pub const SECTION_TITLE_KEY: &str = "section_title";
+/// Metadata field holding the first page of the outline section a chunk of a PDF comes from.
+pub const SECTION_PAGE_KEY: &str = "section_page";
+/// Metadata field holding the headings above a chunk of a DOCX file, e.g. `Setup > Linux`.
+pub const BREADCRUMB_KEY: &str = "breadcrumb";
+/// Metadata field holding the number of the JSON record a chunk comes from, counting from 1: its
+/// line in a JSONL file or its position in a JSON array.
+pub const RECORD_KEY: &str = "record";
+/// Metadata field holding the title of the EPUB chapter a chunk comes from.
+pub const CHAPTER_TITLE_KEY: &str = "chapter_title";
+/// Metadata field holding the encoding a text file was read as, e.g. `UTF-8` or `windows-1252`.
+pub const ENCODING_KEY: &str = "encoding";
+/// Metadata field holding the number of the PPTX slide a chunk comes from, counting from 1.
+pub const SLIDE_KEY: &str = "slide";
+/// Metadata field holding the column names of a CSV or Excel file, separated by commas.
+pub const COLUMNS_KEY: &str = "columns";
+/// Metadata field holding the rows of a CSV or Excel file a chunk comes from, e.g. `11-20`.
+pub const ROWS_KEY: &str = "rows";
+/// Metadata field holding the name of the Excel sheet a chunk comes from.
+pub const SHEET_KEY: &str = "sheet";
+
+/// Text of a file that is chunked on its own, such as a section of a PDF, with the metadata its
+/// chunks get on top of the metadata of the file.
+#[derive(Debug, Clone, Default)]
+pub struct TextSection {
+    pub text: String,
+    pub metadata: HashMap<String, String>,
+}
 
 #[derive(Clone, Copy)]
 pub enum SplittingStrategy {
     Sentence,
     Semantic,
+    /// Boundaries chosen by a rolling hash of the text, so an edit only changes the chunks around
+    /// it. See [`ContentDefinedChunker`]. The overlap is ignored.
+    ContentDefined,
+}
+
+/// What the chunk size and overlap are counted in. Without one, they are counted in tokens of the
+/// cl100k tokenizer, whatever the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkUnit {
+    /// Tokens of the embedding model, special tokens included, so no chunk is longer than the
+    /// model reads. Models without a tokenizer of their own, such as cloud models, count cl100k
+    /// tokens instead.
+    Tokens,
+    /// Unicode characters.
+    Characters,
+}
+
+/// Splits text for [`SplittingStrategy::Sentence`], in the unit the chunk size is counted in.
+#[derive(Debug)]
+pub enum Splitter {
+    Cl100k(TextSplitter<Tokenizer>),
+    Model(TokenChunker),
+    Characters(TextSplitter<Characters>),
 }
 
 impl Default for TextLoader {
@@ -53,35 +120,85 @@ impl Display for FileLoadingError {
 impl From<FileLoadingError> for Error {
     fn from(error: FileLoadingError) -> Self {
         match error {
-            FileLoadingError::FileNotFound(file) => {
-                Error::msg(format!("File not found: {:?}", file))
-            }
-            FileLoadingError::UnsupportedFileType(file) => Error::msg(format!(
-                "Unsupported file type: {:?}. Currently supported file types are: pdf, md, txt, docx",
-                file
-            )),
+            FileLoadingError::FileNotFound(file) => EmbedError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("File not found: {:?}", file),
+            ))
+            .into(),
+            FileLoadingError::UnsupportedFileType(file) => EmbedError::UnsupportedFile(file).into(),
         }
     }
 }
 
+/// A chunk of a document with the metadata of the file it came from.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub text: String,
+    pub metadata: Option<HashMap<String, String>>,
+}
+
 #[derive(Debug)]
 pub struct TextLoader {
-    pub splitter: TextSplitter<Tokenizer>,
+    pub splitter: Splitter,
+    pub content_defined: ContentDefinedChunker,
 }
 impl TextLoader {
+    /// Chunks of `chunk_size` tokens, consecutive chunks sharing `overlap_ratio * chunk_size`
+    /// tokens.
     pub fn new(chunk_size: usize, overlap_ratio: f32) -> Self {
+        Self::with_overlap_tokens(
+            chunk_size,
+            (chunk_size as f32 * overlap_ratio).round() as usize,
+        )
+    }
+
+    /// Chunks of `chunk_size` tokens, consecutive chunks sharing `overlap_tokens` tokens. Both are
+    /// counted with the same tokenizer, so the overlap does not depend on the script of the text.
+    pub fn with_overlap_tokens(chunk_size: usize, overlap_tokens: usize) -> Self {
         Self {
-            splitter: TextSplitter::new(
+            splitter: Splitter::Cl100k(TextSplitter::new(
                 ChunkConfig::new(chunk_size)
-                    .with_overlap(chunk_size * overlap_ratio as usize)
+                    .with_overlap(overlap_tokens.min(chunk_size.saturating_sub(1)))
                     .unwrap()
                     .with_sizer(
                         Tokenizer::from_pretrained("BEE-spoke-data/cl100k_base-mlm", None).unwrap(),
                     ),
-            ),
+            )),
             // splitter: TextSplitter::new(ChunkConfig::new(chunk_size)),
+            content_defined: ContentDefinedChunker::with_chunk_size(chunk_size),
         }
     }
+
+    /// Chunks of `chunk_size` `unit`s, consecutive chunks sharing `overlap_ratio * chunk_size` of
+    /// them. `tokenizer` is the tokenizer of the embedding model, used for [`ChunkUnit::Tokens`].
+    pub fn with_chunk_unit(
+        chunk_size: usize,
+        overlap_ratio: f32,
+        unit: Option<ChunkUnit>,
+        tokenizer: Option<&Tokenizer>,
+    ) -> Result<Self, Error> {
+        let overlap = (chunk_size as f32 * overlap_ratio).round() as usize;
+        let splitter = match (unit, tokenizer) {
+            (Some(ChunkUnit::Tokens), Some(tokenizer)) => {
+                Splitter::Model(TokenChunker::new(tokenizer, chunk_size, overlap)?)
+            }
+            (Some(ChunkUnit::Characters), _) => Splitter::Characters(TextSplitter::new(
+                ChunkConfig::new(chunk_size)
+                    .with_overlap(overlap.min(chunk_size.saturating_sub(1)))?,
+            )),
+            _ => return Ok(Self::with_overlap_tokens(chunk_size, overlap)),
+        };
+        Ok(Self {
+            splitter,
+            content_defined: ContentDefinedChunker::with_chunk_size(chunk_size),
+        })
+    }
+    /// Splits `text` into chunks of about `chunk_size` tokens, in document order.
+    ///
+    /// Chunks are always valid UTF-8 and never break a grapheme cluster: CJK text, emoji
+    /// sequences (ZWJ, skin tones, flags), combining marks and right-to-left scripts stay intact. The
+    /// only exception is a single grapheme that alone exceeds the chunk size, which is split on
+    /// `char` boundaries.
     pub fn split_into_chunks(
         &self,
         text: &str,
@@ -98,12 +215,21 @@ impl TextLoader {
             .replace("\n", " ")
             .replace("{{DOUBLE_NEWLINE}}", "\n\n");
         let chunks: Vec<String> = match splitting_strategy {
-            SplittingStrategy::Sentence => self
-                .splitter
-                .chunks(&cleaned_text)
-                .par_bridge()
-                .map(|chunk| chunk.to_string())
-                .collect(),
+            SplittingStrategy::Sentence => match &self.splitter {
+                Splitter::Cl100k(splitter) => splitter
+                    .chunks(&cleaned_text)
+                    .map(|chunk| chunk.to_string())
+                    .collect(),
+                Splitter::Model(chunker) => chunker
+                    .chunk(&cleaned_text)
+                    .into_iter()
+                    .map(|chunk| chunk.to_string())
+                    .collect(),
+                Splitter::Characters(splitter) => splitter
+                    .chunks(&cleaned_text)
+                    .map(|chunk| chunk.to_string())
+                    .collect(),
+            },
             SplittingStrategy::Semantic => {
                 let embedder = semantic_encoder.unwrap_or(Arc::new(Embedder::Text(
                     TextEmbedder::Jina(Box::new(JinaEmbedder::default())),
@@ -113,12 +239,14 @@ impl TextLoader {
                     ..Default::default()
                 };
 
-                tokio::task::block_in_place(|| {
-                    tokio::runtime::Runtime::new()
-                        .unwrap()
-                        .block_on(async { chunker.chunk(&cleaned_text, 64).await })
-                })
+                block_on(chunker.chunk(&cleaned_text, 64))
             }
+            SplittingStrategy::ContentDefined => self
+                .content_defined
+                .chunk(&cleaned_text)
+                .into_iter()
+                .map(|chunk| chunk.to_string())
+                .collect(),
         };
 
         Some(chunks)
@@ -140,6 +268,11 @@ impl TextLoader {
             "md" => MarkdownProcessor::extract_text(file),
             "txt" => TxtProcessor::extract_text(file),
             "docx" => DocxProcessor::extract_text(file),
+            "pptx" => PptxProcessor::extract_text(file),
+            "epub" => EpubProcessor::extract_text(file),
+            "json" | "jsonl" => JsonProcessor::extract_text(file),
+            "csv" => CsvProcessor::extract_text(file),
+            "xlsx" | "xls" => XlsxProcessor::extract_text(file),
             _ => Err(FileLoadingError::UnsupportedFileType(
                 file.as_ref()
                     .extension()
@@ -152,6 +285,170 @@ impl TextLoader {
         }
     }
 
+    /// Extracts the text of a file like [`extract_text`](Self::extract_text), split into sections
+    /// that are chunked on their own:
+    ///
+    /// - DOCX files at their headings, with the headings above each section as
+    ///   [`BREADCRUMB_KEY`], keeping the headers, footers and notes selected by `docx_options`.
+    /// - PPTX files at their slides, with the slide number as [`SLIDE_KEY`].
+    /// - EPUB files at their chapters, with the chapter title as [`CHAPTER_TITLE_KEY`].
+    /// - CSV and Excel files every `table_rows_per_chunk` rows.
+    /// - JSON and JSONL files at their records, with the record number as [`RECORD_KEY`] and the
+    ///   text and metadata fields selected by `json_options`.
+    /// - PDFs at their outline if `pdf_options` asks to, keeping only the pages it selects.
+    ///
+    /// Other files make a single section, text files with the encoding they were read in as
+    /// [`ENCODING_KEY`].
+    pub fn extract_sections<T: AsRef<std::path::Path>>(
+        file: &T,
+        use_ocr: bool,
+        pdf_options: &PdfTextOptions,
+        docx_options: &DocxTextOptions,
+        json_options: &JsonTextOptions,
+        table_rows_per_chunk: usize,
+    ) -> Result<Vec<TextSection>, Error> {
+        if !file.as_ref().exists() {
+            return Err(FileLoadingError::FileNotFound(
+                file.as_ref().to_string_lossy().to_string(),
+            )
+            .into());
+        }
+        let extension = file
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        match extension {
+            "pdf" if *pdf_options != PdfTextOptions::default() => {
+                let sections = PdfProcessor::extract_sections(file, use_ocr, pdf_options)?;
+                Ok(sections
+                    .into_iter()
+                    .map(|section| {
+                        let mut metadata = HashMap::new();
+                        if pdf_options.split_by_outline {
+                            metadata.insert(
+                                SECTION_PAGE_KEY.to_string(),
+                                section.first_page.to_string(),
+                            );
+                            if let Some(title) = section.title {
+                                metadata.insert(SECTION_TITLE_KEY.to_string(), title);
+                            }
+                        }
+                        TextSection {
+                            text: section.text,
+                            metadata,
+                        }
+                    })
+                    .collect())
+            }
+            "docx" => Ok(DocxProcessor::extract_sections(file, docx_options)?
+                .into_iter()
+                .map(|section| {
+                    let mut metadata = HashMap::new();
+                    if !section.headings.is_empty() {
+                        metadata.insert(BREADCRUMB_KEY.to_string(), section.headings.join(" > "));
+                    }
+                    TextSection {
+                        text: section.text,
+                        metadata,
+                    }
+                })
+                .collect()),
+            "pptx" => Ok(PptxProcessor::extract_slides(file)?
+                .into_iter()
+                .map(|slide| TextSection {
+                    text: slide.text,
+                    metadata: HashMap::from([(SLIDE_KEY.to_string(), slide.number.to_string())]),
+                })
+                .collect()),
+            "epub" => Ok(EpubProcessor::extract_chapters(file)?
+                .into_iter()
+                .map(|chapter| TextSection {
+                    text: chapter.text,
+                    metadata: chapter
+                        .title
+                        .map(|title| HashMap::from([(CHAPTER_TITLE_KEY.to_string(), title)]))
+                        .unwrap_or_default(),
+                })
+                .collect()),
+            "json" | "jsonl" => Ok(JsonProcessor::extract_records(file, json_options)?
+                .into_iter()
+                .map(|record| {
+                    let mut metadata = record.metadata;
+                    metadata.insert(RECORD_KEY.to_string(), record.number.to_string());
+                    TextSection {
+                        text: record.text,
+                        metadata,
+                    }
+                })
+                .collect()),
+            "txt" => {
+                let (text, encoding) = TxtProcessor::extract_text_with_encoding(file)?;
+                Ok(vec![TextSection {
+                    text,
+                    metadata: HashMap::from([(ENCODING_KEY.to_string(), encoding.to_string())]),
+                }])
+            }
+            "csv" => Ok(table_sections(
+                &CsvProcessor::read_table(file)?,
+                table_rows_per_chunk,
+                None,
+            )),
+            "xlsx" | "xls" => Ok(XlsxProcessor::read_sheets(file)?
+                .into_iter()
+                .flat_map(|(sheet, table)| {
+                    table_sections(&table, table_rows_per_chunk, Some(&sheet))
+                })
+                .collect()),
+            _ => Ok(vec![TextSection {
+                text: Self::extract_text(file, use_ocr)?,
+                metadata: HashMap::new(),
+            }]),
+        }
+    }
+
+    /// Yields the sections of a file like [`extract_sections`](Self::extract_sections) one at a
+    /// time. Text files larger than `streaming_threshold` bytes are read block by block, with a
+    /// section per block of about [`STREAM_BLOCK_SIZE`] bytes, so only one block is in memory at
+    /// a time. Other files are extracted whole first.
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_sections<T: AsRef<std::path::Path>>(
+        file: &T,
+        use_ocr: bool,
+        pdf_options: &PdfTextOptions,
+        docx_options: &DocxTextOptions,
+        json_options: &JsonTextOptions,
+        table_rows_per_chunk: usize,
+        streaming_threshold: u64,
+    ) -> Result<Box<dyn Iterator<Item = Result<TextSection, Error>> + Send>, Error> {
+        let is_txt = file
+            .as_ref()
+            .extension()
+            .is_some_and(|extension| extension == "txt");
+        let is_large =
+            fs::metadata(file).is_ok_and(|metadata| metadata.len() > streaming_threshold);
+        if is_txt && is_large {
+            let blocks = TxtProcessor::stream_blocks(file, STREAM_BLOCK_SIZE)?;
+            let metadata =
+                HashMap::from([(ENCODING_KEY.to_string(), blocks.encoding().to_string())]);
+            return Ok(Box::new(blocks.map(move |block| {
+                Ok(TextSection {
+                    text: block?,
+                    metadata: metadata.clone(),
+                })
+            })));
+        }
+        let sections = Self::extract_sections(
+            file,
+            use_ocr,
+            pdf_options,
+            docx_options,
+            json_options,
+            table_rows_per_chunk,
+        )?;
+        Ok(Box::new(sections.into_iter().map(Ok)))
+    }
+
     pub fn get_metadata<T: AsRef<std::path::Path>>(
         file: T,
     ) -> Result<HashMap<String, String>, Error> {
@@ -174,12 +471,81 @@ impl TextLoader {
     }
 }
 
+/// One section per window of rows of `table`, with the column names, row numbers and sheet as
+/// metadata.
+fn table_sections(table: &Table, rows_per_chunk: usize, sheet: Option<&str>) -> Vec<TextSection> {
+    let columns = table.columns.join(", ");
+    table
+        .windows(rows_per_chunk)
+        .into_iter()
+        .map(|window| {
+            let mut metadata = HashMap::from([
+                (COLUMNS_KEY.to_string(), columns.clone()),
+                (
+                    ROWS_KEY.to_string(),
+                    format!("{}-{}", window.first_row, window.last_row),
+                ),
+            ]);
+            if let Some(sheet) = sheet {
+                metadata.insert(SHEET_KEY.to_string(), sheet.to_string());
+            }
+            TextSection {
+                text: window.text,
+                metadata,
+            }
+        })
+        .collect()
+}
+
+/// Runs the semantic chunker from the synchronous splitting path. With the `stream` feature this
+/// reuses tokio so cloud encoders keep their reactor, otherwise a plain executor is enough.
+#[cfg(feature = "stream")]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| tokio::runtime::Runtime::new().unwrap().block_on(future))
+}
+
+#[cfg(not(feature = "stream"))]
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    futures::executor::block_on(future)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::embeddings::{embed::EmbedImage, local::clip::ClipEmbedder};
     use std::path::PathBuf;
 
+    #[test]
+    fn test_overlap_tokens() {
+        let text = (0..300)
+            .map(|i| format!("word{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let split = |overlap_tokens| {
+            TextLoader::with_overlap_tokens(32, overlap_tokens)
+                .split_into_chunks(&text, SplittingStrategy::Sentence, None)
+                .unwrap()
+        };
+        let without = split(0);
+        let with = split(16);
+        assert!(with.len() > without.len());
+
+        let first = with[0].split_whitespace().collect::<Vec<_>>();
+        assert!(with[1].split_whitespace().any(|word| first.contains(&word)));
+    }
+
+    #[test]
+    fn test_chunk_unit_characters() {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(10);
+        let loader =
+            TextLoader::with_chunk_unit(40, 0.0, Some(ChunkUnit::Characters), None).unwrap();
+        let chunks = loader
+            .split_into_chunks(&text, SplittingStrategy::Sentence, None)
+            .unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 40));
+    }
+
     #[test]
     fn test_text_loader() {
         let file_path = PathBuf::from("../test_files/test.pdf");
@@ -201,6 +567,62 @@ mod tests {
         assert!(!text.is_empty());
     }
 
+    /// Splits `text` into small chunks and checks that nothing is lost and no chunk starts or ends
+    /// inside a grapheme cluster.
+    fn assert_unicode_safe_chunks(text: &str) {
+        let text_loader = TextLoader::new(32, 0.0);
+        let chunks = text_loader
+            .split_into_chunks(text, SplittingStrategy::Sentence, None)
+            .unwrap();
+        assert!(chunks.len() > 1);
+
+        let strip = |s: &str| s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+        assert_eq!(strip(&chunks.concat()), strip(text));
+
+        let extends_grapheme = |c: char| {
+            matches!(
+                c,
+                '\u{200D}' | '\u{FE0F}' | '\u{0300}'..='\u{036F}' | '\u{1F3FB}'..='\u{1F3FF}'
+            )
+        };
+        for chunk in &chunks {
+            assert!(!chunk.is_empty());
+            assert!(
+                !chunk.chars().next().is_some_and(extends_grapheme),
+                "{chunk:?}"
+            );
+            assert!(!chunk.ends_with('\u{200D}'), "{chunk:?}");
+        }
+    }
+
+    #[test]
+    fn test_chunks_cjk() {
+        assert_unicode_safe_chunks(
+            "自然言語処理は、人間の言語をコンピュータで処理する技術です。\
+             文章の分類や機械翻訳、質問応答などに使われています。\
+             近年は大規模言語モデルによって大きく進歩しました。\
+             中文文本同样可以被正确地切分成多个片段，而不会破坏任何字符。",
+        );
+    }
+
+    #[test]
+    fn test_chunks_emoji() {
+        assert_unicode_safe_chunks(
+            "Family time 👨‍👩‍👧‍👦 at the beach 🏖️ with friends 👋🏽 and flags 🇯🇵 🇧🇷. \
+             Café crème and naïve résumé with combining accents: e\u{0301} a\u{0300}. \
+             The weather was great ☀️ and everyone was happy 😀😃😄😁 all day long.",
+        );
+    }
+
+    #[test]
+    fn test_chunks_rtl() {
+        assert_unicode_safe_chunks(
+            "مرحبا بكم في عالم معالجة اللغات الطبيعية. هذه الجملة مكتوبة باللغة العربية. \
+             שלום עולם, זהו משפט בעברית עם ניקוד: שָׁלוֹם. \
+             Mixed text with العربية and עברית in the same sentence.",
+        );
+    }
+
     #[test]
     fn test_metadata() {
         let file_path = PathBuf::from("test_files/test.pdf");
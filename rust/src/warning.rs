@@ -0,0 +1,135 @@
+//! Conditions that do not stop an embedding run but that callers may want to show or keep, such
+//! as files without text. Runs record them into the [`Warnings`] of their
+//! [`TextEmbedConfig`](crate::config::TextEmbedConfig) instead of only logging them.
+
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmbedWarning {
+    /// The file has no text to embed.
+    EmptyFile { file: String },
+    /// The file was skipped because its type cannot be read or its text could not be extracted.
+    SkippedFile { file: String, reason: String },
+    /// Chunks of `source` were shortened to fit the model's input, see
+    /// [`TRUNCATED_KEY`](crate::embeddings::cloud::truncation::TRUNCATED_KEY).
+    TruncatedChunks { source: String, count: usize },
+    /// The backend ran out of memory and the batch was embedded again with a smaller batch size.
+    BatchSizeReduced { from: usize, to: usize },
+    /// The embedding of a chunk has NaN or infinite values or is all zeros, see
+    /// [`check_embedding`](crate::embeddings::validation::check_embedding). `chunk` is the start
+    /// of its text.
+    InvalidEmbedding {
+        source: Option<String>,
+        chunk: String,
+        problem: &'static str,
+    },
+}
+
+impl EmbedWarning {
+    /// A short name of the kind of warning, e.g. for grouping or persisting them.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EmbedWarning::EmptyFile { .. } => "empty_file",
+            EmbedWarning::SkippedFile { .. } => "skipped_file",
+            EmbedWarning::TruncatedChunks { .. } => "truncated_chunks",
+            EmbedWarning::BatchSizeReduced { .. } => "batch_size_reduced",
+            EmbedWarning::InvalidEmbedding { .. } => "invalid_embedding",
+        }
+    }
+
+    /// The file or URL the warning is about, if it is about one.
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            EmbedWarning::EmptyFile { file } | EmbedWarning::SkippedFile { file, .. } => Some(file),
+            EmbedWarning::TruncatedChunks { source, .. } => Some(source),
+            EmbedWarning::InvalidEmbedding { source, .. } => source.as_deref(),
+            EmbedWarning::BatchSizeReduced { .. } => None,
+        }
+    }
+}
+
+impl Display for EmbedWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmbedWarning::EmptyFile { file } => write!(f, "{} has no text to embed", file),
+            EmbedWarning::SkippedFile { file, reason } => write!(f, "Skipped {}: {}", file, reason),
+            EmbedWarning::TruncatedChunks { source, count } => {
+                write!(f, "{} chunks of {} were truncated", count, source)
+            }
+            EmbedWarning::BatchSizeReduced { from, to } => write!(
+                f,
+                "Out of memory with batch size {}, retried with batch size {}",
+                from, to
+            ),
+            EmbedWarning::InvalidEmbedding {
+                source,
+                chunk,
+                problem,
+            } => {
+                write!(f, "Invalid embedding ({}) for chunk \"{}\"", problem, chunk)?;
+                match source {
+                    Some(source) => write!(f, " of {}", source),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Collects the warnings of embedding runs. Clones share the same warnings, so a clone kept by
+/// the caller sees what the runs recorded.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings {
+    warnings: Arc<Mutex<Vec<EmbedWarning>>>,
+}
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `warning` and logs it.
+    pub fn push(&self, warning: EmbedWarning) {
+        tracing::warn!(kind = warning.kind(), "{}", warning);
+        self.warnings.lock().unwrap().push(warning);
+    }
+
+    /// Returns the warnings recorded since the last call and starts over.
+    pub fn take(&self) -> Vec<EmbedWarning> {
+        std::mem::take(&mut *self.warnings.lock().unwrap())
+    }
+}
+
+/// Records `warning` in `warnings` when there is a collector, and only logs it otherwise.
+pub(crate) fn warn(warnings: Option<&Warnings>, warning: EmbedWarning) {
+    match warnings {
+        Some(warnings) => warnings.push(warning),
+        None => tracing::warn!(kind = warning.kind(), "{}", warning),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warnings_are_shared_and_taken() {
+        let warnings = Warnings::new();
+        let clone = warnings.clone();
+        clone.push(EmbedWarning::EmptyFile {
+            file: "empty.txt".to_string(),
+        });
+        warn(
+            Some(&clone),
+            EmbedWarning::BatchSizeReduced { from: 32, to: 16 },
+        );
+
+        let taken = warnings.take();
+        assert_eq!(taken.len(), 2);
+        assert_eq!(taken[0].kind(), "empty_file");
+        assert_eq!(taken[0].source(), Some("empty.txt"));
+        assert_eq!(taken[1].source(), None);
+        assert!(warnings.take().is_empty());
+    }
+}
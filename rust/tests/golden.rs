@@ -0,0 +1,138 @@
+//! Golden-output regression tests: embeds fixed sentences and compares the vectors with reference
+//! embeddings from sentence-transformers, to catch silent numerical changes in tokenization,
+//! pooling or normalization. They download models, so they are ignored by default:
+//!
+//! ```text
+//! cargo test --test golden -- --ignored
+//! ```
+//!
+//! The references live in `test_files/golden` and are written by `tests/golden/generate.py`.
+//! Regenerate them only when a numerical change is intended.
+
+use std::path::PathBuf;
+
+use embed_anything::embeddings::embed::Embedder;
+use embed_anything::embeddings::local::text_embedding::ONNXModel;
+use serde::Deserialize;
+
+/// Largest difference allowed in any component of a normalized vector.
+const TOLERANCE: f32 = 1e-3;
+
+#[derive(Deserialize)]
+struct Reference {
+    model_id: String,
+    sentences: Vec<String>,
+    embeddings: Vec<Vec<f32>>,
+}
+
+fn reference(name: &str) -> Reference {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../test_files/golden")
+        .join(format!("{}.json", name));
+    let file = std::fs::File::open(&path).unwrap_or_else(|e| {
+        panic!(
+            "{}: {}. Generate the references with tests/golden/generate.py",
+            path.display(),
+            e
+        )
+    });
+    serde_json::from_reader(file).unwrap()
+}
+
+async fn assert_matches_reference(embedder: &Embedder, reference: &Reference) {
+    let embeddings = embedder.embed(&reference.sentences, None).await.unwrap();
+    assert_eq!(embeddings.len(), reference.embeddings.len());
+    for ((embedding, expected), sentence) in embeddings
+        .iter()
+        .zip(&reference.embeddings)
+        .zip(&reference.sentences)
+    {
+        let embedding = embedding.to_dense().unwrap();
+        assert_eq!(embedding.len(), expected.len());
+        let max_diff = embedding
+            .iter()
+            .zip(expected)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(
+            max_diff < TOLERANCE,
+            "{}: {:?} differs from the reference by {}",
+            reference.model_id,
+            sentence,
+            max_diff
+        );
+    }
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_golden_all_minilm_l6_v2() {
+    let reference = reference("all-MiniLM-L6-v2");
+    let embedder = Embedder::from_pretrained_hf("bert", &reference.model_id, None).unwrap();
+    assert_matches_reference(&embedder, &reference).await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_golden_all_minilm_l6_v2_onnx() {
+    let reference = reference("all-MiniLM-L6-v2");
+    let embedder = Embedder::from_pretrained_onnx(
+        "bert",
+        Some(ONNXModel::AllMiniLML6V2),
+        None,
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    assert_matches_reference(&embedder, &reference).await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_golden_bge_small_en_v15() {
+    let reference = reference("bge-small-en-v1.5");
+    let embedder = Embedder::from_pretrained_hf("bert", &reference.model_id, None).unwrap();
+    assert_matches_reference(&embedder, &reference).await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_golden_bge_small_en_v15_onnx() {
+    let reference = reference("bge-small-en-v1.5");
+    let embedder = Embedder::from_pretrained_onnx(
+        "bert",
+        Some(ONNXModel::BGESmallENV15),
+        None,
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    assert_matches_reference(&embedder, &reference).await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_mean_pooling_is_independent_of_batch_padding() {
+    let embedder =
+        Embedder::from_pretrained_hf("bert", "sentence-transformers/all-MiniLM-L6-v2", None)
+            .unwrap();
+    let short = "The cat sat on the mat.".to_string();
+    let long = "A much longer sentence that pads the short one in its batch. ".repeat(12);
+
+    let alone = embedder.embed(&[short.clone()], None).await.unwrap();
+    let batched = embedder.embed(&[short, long], Some(2)).await.unwrap();
+    let alone = alone[0].to_dense().unwrap();
+    let batched = batched[0].to_dense().unwrap();
+    let max_diff = alone
+        .iter()
+        .zip(&batched)
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0, f32::max);
+    assert!(
+        max_diff < 1e-4,
+        "padding changed the embedding by {}",
+        max_diff
+    );
+}
@@ -0,0 +1,51 @@
+"""Writes the reference embeddings checked by rust/tests/golden.rs.
+
+Run it with sentence-transformers installed, and only after a numerical change is intended:
+
+    pip install sentence-transformers
+    python tests/golden/generate.py
+"""
+
+import json
+from pathlib import Path
+
+from sentence_transformers import SentenceTransformer
+
+SENTENCES = [
+    "The quick brown fox jumps over the lazy dog.",
+    "EmbedAnything turns documents into vectors.",
+    "What is the capital of France?",
+    "Paris is the capital and most populous city of France.",
+    "Ünïcödé, emoji 🚀 and punctuation: do they survive tokenization?!",
+    "a",
+    "Transformers process the whole sequence at once and use attention to relate every token "
+    "to every other token, which lets them capture long range dependencies that recurrent "
+    "networks struggle with, at the cost of memory that grows quadratically with the length "
+    "of the input sequence.",
+]
+
+MODELS = {
+    "all-MiniLM-L6-v2": "sentence-transformers/all-MiniLM-L6-v2",
+    "bge-small-en-v1.5": "BAAI/bge-small-en-v1.5",
+}
+
+OUTPUT_DIR = Path(__file__).resolve().parents[2] / "test_files" / "golden"
+
+
+def main():
+    OUTPUT_DIR.mkdir(parents=True, exist_ok=True)
+    for name, model_id in MODELS.items():
+        model = SentenceTransformer(model_id)
+        embeddings = model.encode(SENTENCES, normalize_embeddings=True)
+        reference = {
+            "model_id": model_id,
+            "sentences": SENTENCES,
+            "embeddings": embeddings.tolist(),
+        }
+        with open(OUTPUT_DIR / f"{name}.json", "w") as f:
+            json.dump(reference, f, indent=1)
+        print(f"Wrote {name}.json")
+
+
+if __name__ == "__main__":
+    main()
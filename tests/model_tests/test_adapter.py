@@ -26,3 +26,35 @@ def test_adapter_upsert_call_directory(bert_model, dummy_adapter, test_files_dir
         )
         is None
     )
+
+
+def test_adapter_batches(bert_model, test_txt_file):
+    from embed_anything.vectordb import Adapter
+
+    class RecordingAdapter(Adapter):
+        def __init__(self):
+            super().__init__("dummy", batch_size=2)
+            self.writes = []
+
+        def create_index(self, dimension: int, metric: str, index_name: str, **kwargs):
+            pass
+
+        def delete_index(self, index_name: str):
+            pass
+
+        def convert(self, embeddings):
+            return [embedding.text for embedding in embeddings]
+
+        def upsert(self, data):
+            self.writes.extend(self.batches(self.convert(data)))
+
+    adapter = RecordingAdapter()
+    assert (
+        embed_anything.embed_file(test_txt_file, embedder=bert_model, adapter=adapter)
+        is None
+    )
+    assert adapter.writes
+    assert all(len(batch) <= 2 for batch in adapter.writes)
+
+    with pytest.raises(ValueError):
+        Adapter.__init__(adapter, "dummy", batch_size=0)
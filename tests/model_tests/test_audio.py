@@ -1,5 +1,12 @@
-from embed_anything import AudioDecoderModel, EmbeddingModel, embed_audio_file
+from embed_anything import (
+    AudioDecoderModel,
+    AudioStream,
+    EmbeddingModel,
+    embed_audio_directory,
+    embed_audio_file,
+)
 import pytest
+import wave
 
 
 def test_audio_decoder(audio_decoder: AudioDecoderModel):
@@ -16,3 +23,30 @@ def test_audio_embed_file(
     assert len(data) == 1
     assert data[0].embedding is not None
     assert len(data[0].embedding) == 384
+
+
+def test_audio_embed_directory(
+    audio_decoder: AudioDecoderModel, bert_model: EmbeddingModel, test_files_directory
+):
+    data = embed_audio_directory(
+        f"{test_files_directory}/audio", audio_decoder, bert_model
+    )
+    assert data is not None
+    files = {d.metadata["file_name"].split("/")[-1] for d in data}
+    assert files == {"samples_jfk.wav", "samples_hp0.wav"}
+
+
+def test_audio_stream(
+    audio_decoder: AudioDecoderModel, bert_model: EmbeddingModel, test_audio_file
+):
+    with wave.open(test_audio_file) as f:
+        pcm = f.readframes(f.getnframes())
+    stream = AudioStream(audio_decoder, bert_model, source="jfk")
+    data = []
+    # Odd chunk sizes split samples across pushes.
+    for i in range(0, len(pcm), 4001):
+        data.extend(stream.push_bytes(pcm[i : i + 4001]))
+    data.extend(stream.finish())
+    assert len(data) == 1
+    assert len(data[0].embedding) == 384
+    assert data[0].metadata["file_name"] == "jfk"
@@ -1,10 +1,13 @@
 from embed_anything import (
+    EmbedData,
     EmbeddingModel,
     TextEmbedConfig,
     WhichModel,
     embed_query,
+    embed_text,
     embed_file,
     embed_directory,
+    embed_webpages,
     ONNXModel,
 )
 
@@ -37,6 +40,50 @@ def test_bert_model_file(model_fixture, config, test_pdf_file, request):
     assert data[0].metadata["file_name"] == path
 
 
+def test_bert_model_metadata_config(bert_model, test_pdf_file):
+    config = TextEmbedConfig(metadata_fields=["file_name"], max_metadata_length=20)
+    data = embed_file(test_pdf_file, bert_model, config)
+
+    assert len(data) > 0
+    assert list(data[0].metadata.keys()) == ["file_name"]
+    assert len(data[0].metadata["file_name"]) <= 20
+    assert data[0].metadata["file_name"].endswith("...[truncated]")
+
+
+def test_bert_model_document_pooling(bert_model, test_pdf_file):
+    config = TextEmbedConfig(document_pooling="mean")
+    data = embed_file(test_pdf_file, bert_model, config)
+
+    levels = [d.metadata["embedding_level"] for d in data]
+    assert levels.count("document") == 1
+    assert levels.count("chunk") == len(data) - 1
+    assert len(data[-1].embedding) == 384
+
+
+def test_bert_model_warnings(bert_model):
+    config = TextEmbedConfig()
+    with tempfile.TemporaryDirectory() as directory:
+        empty = os.path.join(directory, "empty.txt")
+        open(empty, "w").close()
+        assert embed_file(empty, bert_model, config) == []
+
+    warnings = config.take_warnings()
+    assert [w["kind"] for w in warnings] == ["empty_file"]
+    assert warnings[0]["source"].endswith("empty.txt")
+    assert config.take_warnings() == []
+
+
+def test_bert_model_webpages(bert_model):
+    urls = ["https://www.scrapingbee.com/blog/web-scraping-rust/", "http://127.0.0.1:9/"]
+    results = embed_webpages(urls, bert_model, concurrency=2)
+
+    assert [page.url for page in results] == urls
+    assert results[0].error is None
+    assert len(results[0].embeddings) > 0
+    assert results[1].error is not None
+    assert results[1].embeddings is None
+
+
 def test_bert_model_creation():
 
     model = EmbeddingModel.from_pretrained_hf(
@@ -47,6 +94,39 @@ def test_bert_model_creation():
     assert model is not None
 
 
+def test_bert_model_on_cpu():
+    model = EmbeddingModel.from_pretrained_hf(
+        WhichModel.Bert,
+        model_id="sentence-transformers/all-MiniLM-L6-v2",
+        device="cpu",
+    )
+    assert len(embed_query(["Hello"], embedder=model)) == 1
+
+    with pytest.raises(ValueError):
+        EmbeddingModel.from_pretrained_hf(
+            WhichModel.Bert,
+            model_id="sentence-transformers/all-MiniLM-L6-v2",
+            device="tpu",
+        )
+
+
+def test_bert_model_timing_report(bert_model, test_txt_file):
+    assert bert_model.timing_report() is None
+
+    model = EmbeddingModel.from_pretrained_hf(
+        WhichModel.Bert,
+        model_id="sentence-transformers/all-MiniLM-L6-v2",
+        timing=True,
+    )
+    data = embed_file(test_txt_file, model)
+    report = model.timing_report()
+    assert report["texts"] == len(data)
+    assert report["extraction"] > 0
+    assert report["inference"] > 0
+    assert report["total"] >= report["inference"]
+    assert model.timing_report()["texts"] == 0
+
+
 def test_onnx_model_creation():
     model = EmbeddingModel.from_pretrained_onnx(
         WhichModel.Bert, ONNXModel.AllMiniLML6V2Q
@@ -63,6 +143,50 @@ def test_bert_model_query(model_fixture, request):
     assert len(data[0].embedding) == 384
 
 
+@model_fixture_parametrize
+def test_bert_model_embed_text(model_fixture, request):
+    model = request.getfixturevalue(model_fixture)
+    single = embed_text("Photo of a monkey?", model)
+    batch = embed_text(["Photo of a monkey?", "Photo of a cat?"], model)
+
+    assert len(single.embedding) == 384
+    assert len(batch) == 2
+    assert batch[0].embedding == pytest.approx(single.embedding, abs=1e-4)
+
+
+def test_embed_queries_and_documents():
+    model = EmbeddingModel.from_pretrained_hf(
+        WhichModel.Bert, model_id="BAAI/bge-small-en-v1.5"
+    )
+    text = "What is the capital of France?"
+    query = model.embed_queries([text])[0]
+    document = model.embed_documents([text])[0]
+    assert query.text == text
+    assert len(query.embedding) == 384
+    # BGE prepends an instruction to queries only.
+    assert query.embedding != pytest.approx(document.embedding)
+    assert query.embedding == pytest.approx(embed_query([text], model)[0].embedding, abs=1e-4)
+
+
+def test_embed_data_json(bert_model):
+    data = embed_query(["Photo of a monkey?"], bert_model)[0]
+    data.metadata = {"source": "test"}
+    json = data.to_json()
+    assert '"schema_version":1' in json
+
+    restored = EmbedData.from_json(json)
+    assert restored.embedding == pytest.approx(data.embedding)
+    assert restored.text == data.text
+    assert restored.metadata == {"source": "test"}
+
+
+def test_embed_data_id(bert_model, test_txt_file):
+    first = embed_file(test_txt_file, bert_model)
+    second = embed_file(test_txt_file, bert_model)
+    assert [d.id for d in first] == [d.id for d in second]
+    assert len(first[0].id) == 36
+
+
 @model_and_config_parametrize
 def test_bert_model_directory(model_fixture, config, test_text_directory, request):
     model = request.getfixturevalue(model_fixture)
@@ -71,6 +195,14 @@ def test_bert_model_directory(model_fixture, config, test_text_directory, reques
     assert len(data[0].embedding) == 384
 
 
+def test_bert_model_directory_iter(bert_model, test_text_directory):
+    expected = embed_directory(test_text_directory, bert_model)
+    batches = list(bert_model.embed_directory_iter(test_text_directory))
+
+    assert all(len(batch) > 0 for batch in batches)
+    assert sum(len(batch) for batch in batches) == len(expected)
+
+
 @model_fixture_parametrize
 def test_bert_model_empty_query(model_fixture, request):
     model = request.getfixturevalue(model_fixture)
@@ -110,6 +242,56 @@ def test_bert_model_empty_directory(bert_model, tmp_path):
     assert len(data) == 0
 
 
+def test_bert_model_directory_walk(bert_model, tmp_path):
+    nested = tmp_path / "nested"
+    nested.mkdir()
+    (tmp_path / "top.txt").write_text("A file at the top of the directory.")
+    (nested / "deep.txt").write_text("A file in a subdirectory.")
+    (nested / "loop").symlink_to(tmp_path, target_is_directory=True)
+
+    assert len(embed_directory(str(tmp_path), bert_model)) == 1
+
+    config = TextEmbedConfig(follow_symlinks=True, max_depth=5)
+    assert config.follow_symlinks and config.max_depth == 5
+    data = embed_directory(str(tmp_path), bert_model, config=config)
+    assert sorted(d.text for d in data) == [
+        "A file at the top of the directory.",
+        "A file in a subdirectory.",
+    ]
+
+    with pytest.raises(ValueError):
+        TextEmbedConfig(max_depth=0)
+
+
+def test_bert_model_directory_shards(bert_model, tmp_path):
+    for i in range(6):
+        (tmp_path / f"{i}.txt").write_text(f"This is file number {i}.")
+
+    texts = []
+    for shard_index in range(3):
+        config = TextEmbedConfig(shard_index=shard_index, num_shards=3)
+        texts.extend(d.text for d in embed_directory(str(tmp_path), bert_model, config=config))
+    assert sorted(texts) == [f"This is file number {i}." for i in range(6)]
+
+    with pytest.raises(ValueError):
+        TextEmbedConfig(shard_index=3, num_shards=3)
+    with pytest.raises(ValueError):
+        TextEmbedConfig(shard_index=0)
+
+
+def test_bert_model_chunk_unit(bert_model, tmp_path):
+    path = tmp_path / "long.txt"
+    path.write_text("The quick brown fox jumps over the lazy dog. " * 50)
+
+    config = TextEmbedConfig(chunk_size=32, chunk_unit="tokens")
+    assert config.chunk_unit == "tokens"
+    data = embed_file(str(path), bert_model, config=config)
+    assert len(data) > 1
+
+    with pytest.raises(ValueError):
+        TextEmbedConfig(chunk_unit="words")
+
+
 def test_bert_model_unsupported_file_type(bert_model, tmp_path):
 
     # Create a file with an unsupported extension
@@ -118,3 +300,101 @@ def test_bert_model_unsupported_file_type(bert_model, tmp_path):
 
     with pytest.raises(ValueError):
         embed_file(str(tmp_path / "unsupported.mp3"), bert_model)
+
+
+def test_text_embed_config_fields():
+    config = TextEmbedConfig(chunk_size=100, splitting_strategy="sentence", use_ocr=False)
+
+    assert config.chunk_size == 100
+    assert config.splitting_strategy == "sentence"
+    assert config.use_ocr is False
+    assert config.document_pooling is None
+    assert config.length_sorted_batching is False
+    assert config.invalid_embeddings == "keep"
+    assert config.min_batch_size == 1
+    assert TextEmbedConfig(min_batch_size=4).min_batch_size == 4
+    assert config.namespace is None
+    assert TextEmbedConfig(namespace="tenant-a").namespace == "tenant-a"
+    assert TextEmbedConfig(invalid_embeddings="drop").invalid_embeddings == "drop"
+    assert repr(config).startswith("TextEmbedConfig(chunk_size=100, ")
+    with pytest.raises(ValueError):
+        TextEmbedConfig(splitting_strategy="sentences")
+    with pytest.raises(ValueError):
+        TextEmbedConfig(invalid_embeddings="skip")
+    assert TextEmbedConfig(batch_size=128).buffer_size == 128
+    with pytest.raises(ValueError):
+        TextEmbedConfig(batch_size=128, buffer_size=64)
+
+
+def test_bert_model_pdf_page_range(bert_model, test_pdf_file):
+    config = TextEmbedConfig(pdf_first_page=1, pdf_last_page=1, split_by_outline=True)
+    assert config.split_by_outline is True
+    data = embed_file(test_pdf_file, bert_model, config)
+    assert len(data) > 0
+    assert all(d.metadata["section_page"] == "1" for d in data)
+    with pytest.raises(ValueError):
+        TextEmbedConfig(pdf_first_page=5, pdf_last_page=2)
+
+
+def test_bert_model_csv_file(bert_model, tmp_path):
+    csv_file = tmp_path / "people.csv"
+    rows = "\n".join(f"person {i},{20 + i}" for i in range(1, 6))
+    csv_file.write_text("name,age\n" + rows + "\n")
+
+    config = TextEmbedConfig(table_rows_per_chunk=2)
+    assert config.table_rows_per_chunk == 2
+    data = embed_file(str(csv_file), bert_model, config)
+    assert [d.metadata["rows"] for d in data] == ["1-2", "3-4", "5-5"]
+    assert all(d.metadata["columns"] == "name, age" for d in data)
+    assert data[0].text.startswith("name: person 1; age: 21")
+    with pytest.raises(ValueError):
+        TextEmbedConfig(table_rows_per_chunk=0)
+
+
+def test_text_embed_config_headers_footers(bert_model, test_pdf_file):
+    assert TextEmbedConfig().headers_footers is None
+    assert TextEmbedConfig(headers_footers=True, footnotes=True).footnotes is True
+
+    config = TextEmbedConfig(headers_footers=False)
+    assert config.headers_footers is False
+    data = embed_file(test_pdf_file, bert_model, config)
+    assert len(data) > 0
+
+
+def test_bert_model_txt_encoding(bert_model, tmp_path):
+    latin1 = tmp_path / "latin1.txt"
+    latin1.write_bytes("Le café est prêt, servez-vous à volonté.".encode("latin-1"))
+    utf16 = tmp_path / "utf16.txt"
+    utf16.write_bytes("Grüße aus Köln".encode("utf-16"))
+
+    data = embed_file(str(latin1), bert_model)
+    assert data[0].metadata["encoding"] == "windows-1252"
+    assert "café" in data[0].text
+    data = embed_file(str(utf16), bert_model)
+    assert data[0].metadata["encoding"].startswith("UTF-16")
+    assert "Grüße" in data[0].text
+
+
+def test_bert_model_jsonl_file(bert_model, tmp_path):
+    jsonl_file = tmp_path / "corpus.jsonl"
+    jsonl_file.write_text(
+        '{"id": "a1", "title": "Rust", "body": "Ownership and borrowing."}\n'
+        '{"id": "a2", "title": "Python", "body": "Dynamic typing."}\n'
+    )
+    config = TextEmbedConfig(json_text_fields=["title", "body"], json_metadata_fields=["id"])
+    assert config.json_text_fields == ["title", "body"]
+
+    data = embed_file(str(jsonl_file), bert_model, config)
+    assert [d.metadata["id"] for d in data] == ["a1", "a2"]
+    assert [d.metadata["record"] for d in data] == ["1", "2"]
+    assert "Ownership" in data[0].text
+
+
+def test_bert_model_streamed_txt_file(bert_model, test_txt_file):
+    config = TextEmbedConfig(streaming_threshold=0)
+    assert config.streaming_threshold == 0
+
+    streamed = embed_file(test_txt_file, bert_model, config)
+    whole = embed_file(test_txt_file, bert_model)
+    assert [d.text for d in streamed] == [d.text for d in whole]
+    assert streamed[0].metadata["encoding"] == "UTF-8"
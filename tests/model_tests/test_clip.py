@@ -6,9 +6,11 @@ from embed_anything import (
     embed_file,
     embed_directory,
     embed_image_directory,
+    embed_mixed_directory,
 )
 import pytest
 import os
+import shutil
 
 
 def test_clip_model_creation():
@@ -48,3 +50,19 @@ def test_clip_model_directory(clip_model):
     assert len(data) == 5
     assert data[0].embedding is not None
     assert len(data[0].embedding) == 512
+
+
+def test_mixed_directory(clip_model, bert_model, tmp_path):
+    shutil.copy("test_files/test.txt", tmp_path)
+    shutil.copy("test_files/clip/cat1.jpg", tmp_path)
+
+    data = embed_mixed_directory(
+        str(tmp_path), text_embedder=bert_model, image_embedder=clip_model
+    )
+
+    text = [d for d in data if d.metadata["modality"] == "text"]
+    images = [d for d in data if d.metadata["modality"] == "image"]
+    assert len(text) > 0
+    assert all(len(d.embedding) == 384 for d in text)
+    assert len(images) == 1
+    assert len(images[0].embedding) == 512
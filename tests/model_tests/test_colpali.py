@@ -1,5 +1,5 @@
 import pytest
-from embed_anything import ColpaliModel
+from embed_anything import ColpaliModel, ImageEmbedConfig
 
 
 @pytest.mark.parametrize("model_fixture", ["colpali_model", "colpali_onnx_model"])
@@ -7,3 +7,19 @@ def test_colpali_model_file(model_fixture, test_pdf_file, request):
     model: ColpaliModel = request.getfixturevalue(model_fixture)
     data = model.embed_file(test_pdf_file, batch_size=1)
     assert len(data) == 1
+
+
+def test_colpali_model_token_pooling(test_pdf_file):
+    model = ColpaliModel.from_pretrained("vidore/colpali-v1.2-merged", max_vectors=256)
+    data = model.embed_file(test_pdf_file, batch_size=1)
+    assert len(data[0].embedding) <= 256
+    assert len(data[0].embedding[0]) == 128
+
+
+def test_colpali_model_page_range(colpali_onnx_model, test_pdf_file):
+    config = ImageEmbedConfig(pdf_dpi=72, pdf_grayscale=True, pdf_first_page=2)
+    assert colpali_onnx_model.embed_file(test_pdf_file, config=config) == []
+
+    config = ImageEmbedConfig(pdf_dpi=72, pdf_last_page=1)
+    data = colpali_onnx_model.embed_file(test_pdf_file, config=config)
+    assert data[0].metadata["page_number"] == "1"
@@ -1,4 +1,11 @@
-from embed_anything import TextEmbedConfig, embed_directory, embed_file, embed_query
+from embed_anything import (
+    EmbeddingModel,
+    TextEmbedConfig,
+    WhichModel,
+    embed_directory,
+    embed_file,
+    embed_query,
+)
 import pytest
 
 
@@ -21,3 +28,38 @@ def test_openai_model_query(openai_model):
     data = embed_query(["Hello world"], openai_model)
     assert data[0].embedding is not None
     assert len(data[0].embedding) == 1536
+
+
+def test_openai_model_http_options():
+    model = EmbeddingModel.from_pretrained_cloud(
+        WhichModel.OpenAI,
+        model_id="text-embedding-3-small",
+        api_key="sk-test",
+        proxy="http://localhost:3128",
+        timeout=10.0,
+    )
+    assert model is not None
+    with pytest.raises(Exception):
+        EmbeddingModel.from_pretrained_cloud(
+            WhichModel.OpenAI,
+            model_id="text-embedding-3-small",
+            api_key="sk-test",
+            proxy="not a url",
+        )
+
+
+def test_openai_model_truncation():
+    model = EmbeddingModel.from_pretrained_cloud(
+        WhichModel.OpenAI,
+        model_id="text-embedding-3-small",
+        api_key="sk-test",
+        truncation="truncate_tail",
+    )
+    assert model is not None
+    with pytest.raises(Exception):
+        EmbeddingModel.from_pretrained_cloud(
+            WhichModel.OpenAI,
+            model_id="text-embedding-3-small",
+            api_key="sk-test",
+            truncation="middle",
+        )
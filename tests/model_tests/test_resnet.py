@@ -0,0 +1,20 @@
+import pytest
+from embed_anything import EmbeddingModel, WhichModel, embed_image_directory, embed_query
+
+
+@pytest.fixture(scope="module")
+def resnet_model() -> EmbeddingModel:
+    return EmbeddingModel.from_pretrained_hf(
+        WhichModel.ResNet, model_id="microsoft/resnet-18"
+    )
+
+
+def test_resnet_model_directory(resnet_model):
+    data = embed_image_directory("test_files/clip", resnet_model)
+    assert len(data) == 5
+    assert len(data[0].embedding) == 512
+
+
+def test_resnet_model_rejects_text(resnet_model):
+    with pytest.raises(Exception):
+        embed_query(["Photo of a monkey?"], resnet_model)
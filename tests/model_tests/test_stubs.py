@@ -0,0 +1,27 @@
+import ast
+import inspect
+from pathlib import Path
+
+import pytest
+
+import embed_anything
+from embed_anything import _embed_anything
+
+STUB = Path(embed_anything.__file__).parent / "_embed_anything.pyi"
+
+
+def stub_functions():
+    tree = ast.parse(STUB.read_text())
+    return {
+        node.name: [arg.arg for arg in node.args.args]
+        for node in tree.body
+        if isinstance(node, ast.FunctionDef)
+    }
+
+
+@pytest.mark.parametrize("name,params", sorted(stub_functions().items()))
+def test_stub_matches_runtime_signature(name, params):
+    function = getattr(_embed_anything, name)
+    runtime = list(inspect.signature(function).parameters)
+
+    assert params == runtime